@@ -0,0 +1,72 @@
+//! SSE chunks per second: serializing each chunk with serde, as the stream
+//! did before `ChunkEncoder`, against the encoder.
+//!
+//! ```bash
+//! cargo bench --bench sse
+//! ```
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use llmsim::generator::{split_pieces, LoremGenerator, ResponseGenerator};
+use llmsim::openai::{ChatCompletionChunk, ChatCompletionRequest, Usage};
+use llmsim::stream::format_sse;
+use llmsim::ChunkEncoder;
+use std::hint::black_box;
+
+const ID: &str = "chatcmpl-8f2c1e0a9b7d4c3e";
+const MODEL: &str = "gpt-5";
+const CREATED: i64 = 1_760_000_000;
+const FINGERPRINT: &str = "fp_llmsim";
+
+fn pieces() -> Vec<String> {
+    let request: ChatCompletionRequest = serde_json::from_value(serde_json::json!({
+        "model": MODEL,
+        "seed": 7,
+        "messages": [{"role": "user", "content": "Tell me something."}]
+    }))
+    .unwrap();
+    split_pieces(&LoremGenerator::new(500).generate(&request))
+}
+
+fn sse_chunks(c: &mut Criterion) {
+    let pieces = pieces();
+    let usage = Usage {
+        prompt_tokens: 12,
+        completion_tokens: 500,
+        total_tokens: 512,
+    };
+    let mut group = c.benchmark_group("sse_chunks");
+    group.throughput(Throughput::Elements(pieces.len() as u64 + 2));
+
+    group.bench_function("serde", |b| {
+        b.iter(|| {
+            let base = ChatCompletionChunk {
+                system_fingerprint: Some(FINGERPRINT.to_string()),
+                ..ChatCompletionChunk::new(ID.to_string(), MODEL.to_string(), CREATED)
+            };
+            black_box(format_sse(&base.clone().with_role()));
+            for piece in &pieces {
+                black_box(format_sse(&base.clone().with_content(piece.clone())));
+            }
+            let finish = base
+                .with_finish("stop".to_string())
+                .with_usage(usage.clone());
+            black_box(format_sse(&finish));
+        })
+    });
+
+    group.bench_function("encoder", |b| {
+        b.iter(|| {
+            let encoder = ChunkEncoder::new(ID, MODEL, CREATED, Some(FINGERPRINT));
+            black_box(encoder.role(None));
+            for piece in &pieces {
+                black_box(encoder.content(piece, None));
+            }
+            black_box(encoder.finish("stop", Some(&usage)));
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, sse_chunks);
+criterion_main!(benches);
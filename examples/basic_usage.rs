@@ -56,8 +56,14 @@ async fn main() {
         user: None,
         tools: None,
         tool_choice: None,
+        parallel_tool_calls: None,
         response_format: None,
         seed: None,
+        stream_options: None,
+        functions: None,
+        function_call: None,
+        store: None,
+        metadata: None,
     };
 
     // Lorem generator - generates lorem ipsum to target token count
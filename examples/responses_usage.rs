@@ -45,6 +45,9 @@ async fn main() {
         reasoning: None,
         background: false,
         include: None,
+        store: None,
+        stream_options: None,
+        truncation: None,
     };
     println!("Simple request model: {}", simple_request.model);
     println!(
@@ -80,6 +83,9 @@ async fn main() {
         reasoning: None,
         background: false,
         include: None,
+        store: None,
+        stream_options: None,
+        truncation: None,
     };
 
     // Reasoning model request (o-series)
@@ -101,6 +107,9 @@ async fn main() {
         }),
         background: false,
         include: None,
+        store: None,
+        stream_options: None,
+        truncation: None,
     };
     println!(
         "Reasoning request model: {} (effort: {:?})",
@@ -144,8 +153,14 @@ async fn main() {
         user: None,
         tools: None,
         tool_choice: None,
+        parallel_tool_calls: None,
         response_format: None,
         seed: None,
+        stream_options: None,
+        functions: None,
+        function_call: None,
+        store: None,
+        metadata: None,
     };
 
     let content = generator.generate(&chat_request);
@@ -325,6 +340,7 @@ async fn main() {
                 id,
                 status,
                 summary,
+                ..
             } => {
                 println!("  [Thinking]");
                 println!("    ID: {}", id);
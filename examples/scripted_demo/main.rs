@@ -119,6 +119,9 @@ fn walk(script: &Script) {
                     err.status_code()
                 );
             }
+            ScriptedResponse::Turn(SimTurn::ExpectToolResults { then }) => {
+                println!("  [{}] expect_tool_results, then: {:?}", i, then);
+            }
             ScriptedResponse::Exhausted => {
                 println!("  [{}] EXHAUSTED (on_exhausted=Error)", i);
             }
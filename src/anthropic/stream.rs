@@ -9,13 +9,15 @@
 // Reference: https://docs.anthropic.com/en/docs/build-with-claude/streaming
 
 use super::types::Usage;
-use crate::ids::prefixed_compact_id;
-use crate::latency::LatencyProfile;
+use crate::ids::{new_id, IdKind};
+use crate::latency::{LatencyProfile, RequestLatency};
+use crate::sleep::{default_sleeper, Sleeper};
+use crate::watchdog::StreamWatchdog;
 use async_stream::stream;
 use futures_core::Stream;
 use serde_json::json;
 use std::pin::Pin;
-use tokio::time::sleep;
+use std::sync::Arc;
 
 /// Callback type for stream completion.
 type OnCompleteCallback = Box<dyn FnOnce() + Send + 'static>;
@@ -24,32 +26,62 @@ type OnCompleteCallback = Box<dyn FnOnce() + Send + 'static>;
 pub struct MessagesTokenStream {
     id: String,
     model: String,
-    latency: LatencyProfile,
+    latency: RequestLatency,
     content: String,
     input_tokens: u32,
     output_tokens: u32,
+    stop_sequence: Option<String>,
+    sleeper: Arc<dyn Sleeper>,
+    watchdog: StreamWatchdog,
     on_complete: Option<OnCompleteCallback>,
 }
 
 impl MessagesTokenStream {
-    pub fn new(id: String, model: String, content: String, latency: LatencyProfile) -> Self {
+    pub fn new(
+        id: String,
+        model: String,
+        content: String,
+        latency: impl Into<RequestLatency>,
+    ) -> Self {
         Self {
             id,
             model,
-            latency,
+            latency: latency.into(),
             content,
             input_tokens: 0,
             output_tokens: 0,
+            stop_sequence: None,
+            sleeper: default_sleeper(),
+            watchdog: StreamWatchdog::unlimited(),
             on_complete: None,
         }
     }
 
+    /// Wait out delays with this sleeper (default: the tokio timer)
+    pub fn with_sleeper(mut self, sleeper: Arc<dyn Sleeper>) -> Self {
+        self.sleeper = sleeper;
+        self
+    }
+
+    /// End with `stop_reason: "max_tokens"` once the watchdog's budget is
+    /// spent
+    pub fn with_watchdog(mut self, watchdog: StreamWatchdog) -> Self {
+        self.watchdog = watchdog;
+        self
+    }
+
     pub fn with_usage(mut self, usage: Usage) -> Self {
         self.input_tokens = usage.input_tokens;
         self.output_tokens = usage.output_tokens;
         self
     }
 
+    /// End with `stop_reason: "stop_sequence"`, reporting `stop`
+    pub fn with_stop_sequence(mut self, stop: impl Into<String>) -> Self {
+        self.stop_sequence = Some(stop.into());
+        self
+    }
+
     pub fn with_on_complete<F>(mut self, callback: F) -> Self
     where
         F: FnOnce() + Send + 'static,
@@ -83,18 +115,27 @@ impl MessagesTokenStream {
     /// Render the Anthropic streaming event sequence as SSE.
     pub fn into_stream(self) -> Pin<Box<dyn Stream<Item = String> + Send>> {
         let tokens = self.tokenize();
+        let token_count = tokens.len();
         let id = self.id.clone();
         let model = self.model.clone();
-        let latency = self.latency.clone();
+        let mut latency = self.latency.clone();
         let input_tokens = self.input_tokens;
         let output_tokens = self.output_tokens;
+        let stop_reason = if self.stop_sequence.is_some() {
+            "stop_sequence"
+        } else {
+            "end_turn"
+        };
+        let stop_sequence = self.stop_sequence;
+        let sleeper = self.sleeper;
+        let mut watchdog = self.watchdog;
         let on_complete = self.on_complete;
 
         Box::pin(stream! {
             // Time to first token.
-            let ttft = latency.sample_ttft();
-            if !ttft.is_zero() {
-                sleep(ttft).await;
+            let ttft = latency.ttft();
+            if watchdog.allows(ttft) && !ttft.is_zero() {
+                sleeper.sleep(ttft).await;
             }
 
             // 1. message_start (usage seeded with input tokens, output_tokens=1).
@@ -124,12 +165,18 @@ impl MessagesTokenStream {
             // 3. ping (Anthropic interleaves these to keep the connection warm).
             yield format_event("ping", &json!({"type": "ping"}));
 
-            // 4. content_block_delta for each token.
+            // 4. content_block_delta for each token, until the time budget
+            //    runs out.
+            let mut sent = 0;
             for token in tokens {
-                let tbt = latency.sample_tbt();
+                let tbt = latency.tbt();
+                if !watchdog.allows(tbt) {
+                    break;
+                }
                 if !tbt.is_zero() {
-                    sleep(tbt).await;
+                    sleeper.sleep(tbt).await;
                 }
+                sent += 1;
                 let delta = json!({
                     "type": "content_block_delta",
                     "index": 0,
@@ -144,10 +191,17 @@ impl MessagesTokenStream {
                 &json!({"type": "content_block_stop", "index": 0}),
             );
 
-            // 6. message_delta with final stop_reason + cumulative output usage.
+            // 6. message_delta with final stop_reason + cumulative output
+            //    usage; a stream out of time ends as if at max_tokens.
+            let (stop_reason, stop_sequence, output_tokens) = if watchdog.tripped() {
+                let sent_tokens = output_tokens as usize * sent / token_count.max(1);
+                ("max_tokens", None, sent_tokens as u32)
+            } else {
+                (stop_reason, stop_sequence, output_tokens)
+            };
             let message_delta = json!({
                 "type": "message_delta",
-                "delta": {"stop_reason": "end_turn", "stop_sequence": null},
+                "delta": {"stop_reason": stop_reason, "stop_sequence": stop_sequence},
                 "usage": {"output_tokens": output_tokens}
             });
             yield format_event("message_delta", &message_delta);
@@ -173,8 +227,11 @@ pub struct MessagesStreamBuilder {
     id: Option<String>,
     model: String,
     content: String,
-    latency: LatencyProfile,
+    latency: RequestLatency,
     usage: Option<Usage>,
+    stop_sequence: Option<String>,
+    sleeper: Option<Arc<dyn Sleeper>>,
+    watchdog: StreamWatchdog,
     on_complete: Option<OnCompleteCallback>,
 }
 
@@ -184,8 +241,11 @@ impl MessagesStreamBuilder {
             id: None,
             model: model.into(),
             content: content.into(),
-            latency: LatencyProfile::default(),
+            latency: LatencyProfile::default().into(),
             usage: None,
+            stop_sequence: None,
+            sleeper: None,
+            watchdog: StreamWatchdog::unlimited(),
             on_complete: None,
         }
     }
@@ -195,8 +255,8 @@ impl MessagesStreamBuilder {
         self
     }
 
-    pub fn latency(mut self, latency: LatencyProfile) -> Self {
-        self.latency = latency;
+    pub fn latency(mut self, latency: impl Into<RequestLatency>) -> Self {
+        self.latency = latency.into();
         self
     }
 
@@ -205,6 +265,24 @@ impl MessagesStreamBuilder {
         self
     }
 
+    /// The stop sequence the content ended at, if it ended at one
+    pub fn stop_sequence(mut self, stop: Option<String>) -> Self {
+        self.stop_sequence = stop;
+        self
+    }
+
+    /// Wait out delays with this sleeper
+    pub fn sleeper(mut self, sleeper: Arc<dyn Sleeper>) -> Self {
+        self.sleeper = Some(sleeper);
+        self
+    }
+
+    /// End with `max_tokens` once the watchdog's budget is spent
+    pub fn watchdog(mut self, watchdog: StreamWatchdog) -> Self {
+        self.watchdog = watchdog;
+        self
+    }
+
     pub fn on_complete<F>(mut self, callback: F) -> Self
     where
         F: FnOnce() + Send + 'static,
@@ -214,11 +292,18 @@ impl MessagesStreamBuilder {
     }
 
     pub fn build(self) -> MessagesTokenStream {
-        let id = self.id.unwrap_or_else(|| prefixed_compact_id("msg_"));
-        let mut stream = MessagesTokenStream::new(id, self.model, self.content, self.latency);
+        let id = self.id.unwrap_or_else(|| new_id(IdKind::AnthropicMessage));
+        let mut stream = MessagesTokenStream::new(id, self.model, self.content, self.latency)
+            .with_watchdog(self.watchdog);
         if let Some(usage) = self.usage {
             stream = stream.with_usage(usage);
         }
+        if let Some(stop) = self.stop_sequence {
+            stream = stream.with_stop_sequence(stop);
+        }
+        if let Some(sleeper) = self.sleeper {
+            stream = stream.with_sleeper(sleeper);
+        }
         if let Some(on_complete) = self.on_complete {
             stream = stream.with_on_complete(on_complete);
         }
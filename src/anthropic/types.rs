@@ -4,7 +4,7 @@
 // work against the simulator when pointed at `{base_url}/anthropic`.
 // Reference: https://docs.anthropic.com/en/api/messages
 
-use crate::ids::prefixed_compact_id;
+use crate::ids::{new_id, IdKind};
 use serde::{Deserialize, Serialize};
 
 /// Role of a message in an Anthropic conversation.
@@ -199,6 +199,21 @@ impl MessagesRequest {
         }
         parts.join("\n")
     }
+
+    /// `tool_use_id`s of the `tool_result` blocks the request sends back
+    pub fn tool_result_ids(&self) -> impl Iterator<Item = &str> {
+        self.messages.iter().flat_map(|msg| {
+            match &msg.content {
+                MessageContent::Blocks(blocks) => blocks.as_slice(),
+                MessageContent::Text(_) => &[],
+            }
+            .iter()
+            .filter_map(|block| match block {
+                RequestContentBlock::ToolResult { tool_use_id, .. } => Some(tool_use_id.as_str()),
+                _ => None,
+            })
+        })
+    }
 }
 
 /// A content block on the response. The simulator emits `text` blocks for prose
@@ -276,7 +291,7 @@ impl MessagesResponse {
     /// Build a plain-text response.
     pub fn text(model: impl Into<String>, content: impl Into<String>, usage: Usage) -> Self {
         Self {
-            id: prefixed_compact_id("msg_"),
+            id: new_id(IdKind::AnthropicMessage),
             response_type: "message".to_string(),
             role: Role::Assistant,
             model: model.into(),
@@ -295,7 +310,7 @@ impl MessagesResponse {
         usage: Usage,
     ) -> Self {
         Self {
-            id: prefixed_compact_id("msg_"),
+            id: new_id(IdKind::AnthropicMessage),
             response_type: "message".to_string(),
             role: Role::Assistant,
             model: model.into(),
@@ -341,7 +356,7 @@ impl AnthropicErrorResponse {
             400 => "invalid_request_error",
             401 => "authentication_error",
             403 => "permission_error",
-            404 => "not_found_error",
+            404 | 410 => "not_found_error",
             413 => "request_too_large",
             429 => "rate_limit_error",
             500 => "api_error",
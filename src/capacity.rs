@@ -0,0 +1,101 @@
+// Capacity Module
+// Provider-side load shedding: requests above a configured rate are rejected
+// up front with 503 + Retry-After instead of being served.
+//
+// Decision: admission is a fixed one-second window counter packed into a
+// single AtomicU64 (`(second_tag << 32) | count`, like the stats RPS
+// buckets), so the check is one lock-free compare-exchange per request.
+// Unlike a token bucket it allows a burst of up to twice the limit across a
+// window boundary; that is close enough to how providers shed load, and keeps
+// the hot path free of locks.
+
+use crate::clock::{self, Instant};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Rejects requests beyond `max_rps` in each one-second window
+#[derive(Debug)]
+pub struct LoadShedder {
+    max_rps: u32,
+    retry_after_seconds: u32,
+    start: Instant,
+    window: AtomicU64,
+}
+
+impl LoadShedder {
+    /// Admit at most `max_rps` requests per second
+    pub fn new(max_rps: u32) -> Self {
+        Self {
+            max_rps,
+            retry_after_seconds: 1,
+            start: clock::now(),
+            window: AtomicU64::new(0),
+        }
+    }
+
+    /// `Retry-After` sent with shed requests (default 1 second)
+    pub fn with_retry_after(mut self, seconds: u32) -> Self {
+        self.retry_after_seconds = seconds;
+        self
+    }
+
+    pub fn max_rps(&self) -> u32 {
+        self.max_rps
+    }
+
+    pub fn retry_after_seconds(&self) -> u32 {
+        self.retry_after_seconds
+    }
+
+    /// Count a request against the current second; false means shed it
+    pub fn try_admit(&self) -> bool {
+        let tag = clock::elapsed(self.start).as_secs() as u32 as u64;
+        let mut cur = self.window.load(Ordering::Relaxed);
+        loop {
+            let count = if (cur >> 32) == tag {
+                cur & 0xFFFF_FFFF
+            } else {
+                0
+            };
+            if count >= self.max_rps as u64 {
+                return false;
+            }
+            let new = (tag << 32) | (count + 1);
+            match self
+                .window
+                .compare_exchange_weak(cur, new, Ordering::Relaxed, Ordering::Relaxed)
+            {
+                Ok(_) => return true,
+                Err(x) => cur = x,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_admits_up_to_limit() {
+        let shedder = LoadShedder::new(3);
+        let admitted = (0..10).filter(|_| shedder.try_admit()).count();
+        assert_eq!(admitted, 3);
+    }
+
+    #[test]
+    fn test_zero_limit_sheds_everything() {
+        let shedder = LoadShedder::new(0);
+        assert!(!shedder.try_admit());
+    }
+
+    #[test]
+    fn test_retry_after() {
+        assert_eq!(LoadShedder::new(1).retry_after_seconds(), 1);
+        assert_eq!(
+            LoadShedder::new(1)
+                .with_retry_after(5)
+                .retry_after_seconds(),
+            5
+        );
+    }
+}
@@ -0,0 +1,118 @@
+// Citation Simulation
+// Scatters fake `url_citation` and `file_citation` annotations over generated
+// Responses text, for clients that highlight or link citations.
+//
+// Decision: citations attach to whole words, each chosen independently at the
+// configured rate, so their density scales with the answer's length. Indices
+// count characters of the output text: a url citation spans the cited word
+// and a file citation points just past it, where the API places its marker.
+// Targets come from short fixed lists; nothing is actually searched.
+
+use rand::RngExt;
+use serde_json::{json, Value};
+
+/// Pages that url citations point to, as `(url, title)`
+const PAGES: [(&str, &str); 4] = [
+    ("https://example.com/guide", "Getting Started Guide"),
+    ("https://example.org/reference", "API Reference"),
+    ("https://docs.example.net/faq", "Frequently Asked Questions"),
+    ("https://blog.example.com/release-notes", "Release Notes"),
+];
+
+/// Uploaded files that file citations point to, as `(file_id, filename)`
+const FILES: [(&str, &str); 3] = [
+    ("file-llmsim0000000000000001", "handbook.pdf"),
+    ("file-llmsim0000000000000002", "notes.md"),
+    ("file-llmsim0000000000000003", "report.docx"),
+];
+
+/// Annotations for `text`, citing each word with probability `rate`.
+/// Sorted by position; each is a url or a file citation at random.
+pub fn cite(text: &str, rate: f64, rng: &mut impl rand::Rng) -> Vec<Value> {
+    if rate <= 0.0 {
+        return Vec::new();
+    }
+    let rate = rate.min(1.0);
+    let mut annotations = Vec::new();
+    let mut word_start = None;
+    let chars = text.chars().chain(std::iter::once(' ')).enumerate();
+    for (index, ch) in chars {
+        match (ch.is_whitespace(), word_start) {
+            (false, None) => word_start = Some(index),
+            (true, Some(start)) => {
+                word_start = None;
+                if rng.random_bool(rate) {
+                    annotations.push(citation(start, index, rng));
+                }
+            }
+            _ => {}
+        }
+    }
+    annotations
+}
+
+/// A url citation over `start..end`, or a file citation at `end`
+fn citation(start: usize, end: usize, rng: &mut impl rand::Rng) -> Value {
+    if rng.random_bool(0.5) {
+        let (url, title) = PAGES[rng.random_range(0..PAGES.len())];
+        json!({
+            "type": "url_citation",
+            "start_index": start,
+            "end_index": end,
+            "url": url,
+            "title": title,
+        })
+    } else {
+        let (file_id, filename) = FILES[rng.random_range(0..FILES.len())];
+        json!({
+            "type": "file_citation",
+            "file_id": file_id,
+            "filename": filename,
+            "index": end,
+        })
+    }
+}
+
+/// Character offset in the text where an annotation has been fully
+/// generated, so streams can announce it right after that text
+pub fn end_of(annotation: &Value) -> usize {
+    annotation["end_index"]
+        .as_u64()
+        .or_else(|| annotation["index"].as_u64())
+        .unwrap_or(0) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cite_rate() {
+        let mut rng = rand::rng();
+        assert!(cite("one two three", 0.0, &mut rng).is_empty());
+        assert_eq!(cite("one two three", 1.0, &mut rng).len(), 3);
+        assert_eq!(cite("  ", 1.0, &mut rng).len(), 0);
+    }
+
+    #[test]
+    fn test_cite_positions() {
+        let text = "héllo  wörld";
+        let annotations = cite(text, 1.0, &mut rand::rng());
+        let ends: Vec<usize> = annotations.iter().map(end_of).collect();
+        assert_eq!(ends, [5, 12]);
+        for annotation in &annotations {
+            if annotation["type"] == "url_citation" {
+                let start = annotation["start_index"].as_u64().unwrap() as usize;
+                let word: String = text
+                    .chars()
+                    .skip(start)
+                    .take(end_of(annotation) - start)
+                    .collect();
+                assert!(word == "héllo" || word == "wörld");
+            } else {
+                assert_eq!(annotation["type"], "file_citation");
+                assert!(annotation["filename"].is_string());
+            }
+        }
+    }
+}
@@ -2,25 +2,24 @@
 // Implements POST /anthropic/v1/messages, GET /anthropic/v1/models, and
 // GET /anthropic/v1/models/:id, mirroring the Anthropic API wire format.
 
+use super::handlers::{apply_cost, injected_error_response, sse_response};
 use super::state::AppState;
 use crate::anthropic::{
     default_anthropic_model_ids, get_anthropic_model_profile, AnthropicErrorResponse,
     AnthropicModel, AnthropicModelsResponse, ContentBlock, MessagesRequest, MessagesResponse,
     MessagesStreamBuilder, StopReason, Usage,
 };
-use crate::ids::prefixed_compact_id;
-use crate::script::{ScriptedResponse, SimError, SimToolCall, SimTurn};
-use crate::{create_generator, EndpointType, ErrorInjector, LatencyProfile};
+use crate::ids::{new_id, IdKind};
+use crate::pipeline::{AnthropicFormat, PipelineRequest, RequestPipeline};
+use crate::script::{ScriptedResponse, SimError, SimToolCall};
+use crate::{EndpointType, RequestOverrides};
 use axum::{
-    body::Body,
     extract::{Path, State},
-    http::{header, StatusCode},
+    http::StatusCode,
     response::{IntoResponse, Response},
     Json,
 };
-use futures_util::StreamExt;
 use std::sync::Arc;
-use std::time::Instant;
 
 /// Map an HTTP status code to an Axum `StatusCode`, falling back to 500.
 fn status_from_code(code: u16) -> StatusCode {
@@ -46,10 +45,9 @@ fn sim_error_to_anthropic_response(err: &SimError) -> Response {
 /// POST /anthropic/v1/messages
 pub async fn create_message(
     State(state): State<Arc<AppState>>,
+    overrides: RequestOverrides,
     Json(request): Json<MessagesRequest>,
 ) -> Response {
-    let request_start = Instant::now();
-
     tracing::info!(
         model = %request.model,
         stream = request.stream,
@@ -57,55 +55,40 @@ pub async fn create_message(
         "Anthropic messages request"
     );
 
-    state
-        .stats
-        .record_request_start(&request.model, request.stream, EndpointType::Messages);
+    let req = state
+        .begin(&request.model, request.stream, EndpointType::Messages)
+        .with_overrides(&overrides);
+    state.stats.record_request_messages(request.messages.len());
+    req.set_prompt(&request.prompt_text());
+    if let Some(user) = request.metadata.as_ref().and_then(|m| m.user_id.as_ref()) {
+        req.set_user(user);
+    }
 
     // Error injection (Anthropic error wire shape).
-    let error_injector = ErrorInjector::new(state.config.error_config());
-    if let Some(error) = error_injector.maybe_inject() {
-        tracing::warn!("Injecting error: {:?}", error);
-        let status_code = error.status_code();
-        state.stats.record_error(status_code);
-
-        let message = error.to_error_response().error.message;
-        let mut response = anthropic_error(status_code, message);
-        if let Some(retry_after) = error.retry_after() {
-            response.headers_mut().insert(
-                header::RETRY_AFTER,
-                retry_after.to_string().parse().unwrap(),
-            );
-        }
-        return response;
+    if let Some(error) = req.inject_error() {
+        return injected_error_response::<AnthropicFormat>(&error);
     }
 
-    // Model-specific latency (unless overridden in config).
-    let latency =
-        if state.config.latency.profile.is_some() || state.config.latency.ttft_mean_ms.is_some() {
-            state.config.latency_profile()
-        } else {
-            LatencyProfile::from_model(&request.model)
-        };
-
     // Scripted non-streaming requests get full tool-call support.
     if let Some(script) = state.script.clone() {
         if !request.stream {
-            return handle_scripted_message(state, request, request_start, script).await;
+            return handle_scripted_message(state, request, req, script).await;
         }
     }
 
-    // Resolve the response content (scripted text turn, or generated).
-    let content = if let Some(script) = state.script.as_ref() {
-        match script.next_turn() {
-            ScriptedResponse::Turn(SimTurn::Assistant { text }) => text,
-            ScriptedResponse::Turn(SimTurn::Mixed { text, .. }) => text,
-            ScriptedResponse::Turn(SimTurn::ToolCalls { .. }) => String::new(),
-            ScriptedResponse::Turn(SimTurn::Error(err)) => {
-                state.stats.record_error(err.status_code());
-                return sim_error_to_anthropic_response(&err);
-            }
+    // Resolve the response content (scripted text turn, or generated) and
+    // the stop sequence generation ended at, if any.
+    let (content, stop_sequence) = if let Some(script) = state.script.as_ref() {
+        match script.next_turn_with_tool_results(request.tool_result_ids()) {
+            ScriptedResponse::Turn(turn) => match turn.into_parts() {
+                Ok((text, _)) => (text.unwrap_or_default(), None),
+                Err(err) => {
+                    req.fail(err.status_code());
+                    return sim_error_to_anthropic_response(&err);
+                }
+            },
             ScriptedResponse::Exhausted => {
-                state.stats.record_error(500);
+                req.fail(500);
                 return sim_error_to_anthropic_response(&SimError::Other {
                     message: "llmsim script exhausted (on_exhausted=error)".to_string(),
                     status_code: Some(500),
@@ -116,45 +99,34 @@ pub async fn create_message(
         generate_content(&state, &request)
     };
 
-    let input_tokens = count_input_tokens(&request);
-    let output_tokens =
-        crate::count_tokens_default(&content).unwrap_or(content.split_whitespace().count());
+    let input_tokens = count_input_tokens(&state.pipeline, &request);
+    let output_tokens = state.pipeline.count_tokens(&content);
     let usage = Usage::new(input_tokens as u32, output_tokens as u32);
+    let input_tok = usage.input_tokens;
+    let output_tok = usage.output_tokens;
 
-    if request.stream {
-        let stats = state.stats.clone();
-        let input_tok = usage.input_tokens;
-        let output_tok = usage.output_tokens;
-
+    let response = if request.stream {
         let stream = MessagesStreamBuilder::new(&request.model, content)
-            .latency(latency)
+            .latency(req.request_latency(input_tok))
+            .sleeper(req.sleeper().clone())
+            .watchdog(req.stream_watchdog())
             .usage(usage)
-            .on_complete(move || {
-                stats.record_request_end(request_start.elapsed(), input_tok, output_tok);
-            })
+            .stop_sequence(stop_sequence)
+            .on_complete(req.finish_callback(input_tok, output_tok))
             .build();
 
-        let body = Body::from_stream(stream.into_stream().map(Ok::<_, std::io::Error>));
-        Response::builder()
-            .status(StatusCode::OK)
-            .header(header::CONTENT_TYPE, "text/event-stream")
-            .header(header::CACHE_CONTROL, "no-cache")
-            .header(header::CONNECTION, "keep-alive")
-            .body(body)
-            .unwrap()
+        sse_response(&state, &req, stream.into_stream())
     } else {
-        let delay = latency.sample_ttft();
-        if !delay.is_zero() {
-            tokio::time::sleep(delay).await;
+        req.simulate_generation(input_tok).await;
+        req.finish(input_tok, output_tok);
+        let mut response = MessagesResponse::text(request.model.clone(), content, usage);
+        if stop_sequence.is_some() {
+            response.stop_reason = Some(StopReason::StopSequence);
+            response.stop_sequence = stop_sequence;
         }
-        state.stats.record_request_end(
-            request_start.elapsed(),
-            usage.input_tokens,
-            usage.output_tokens,
-        );
-        let response = MessagesResponse::text(request.model.clone(), content, usage);
         Json(response).into_response()
-    }
+    };
+    apply_cost(&state, response, &request.model, input_tok, output_tok)
 }
 
 /// Non-streaming scripted path: emits text and/or `tool_use` content blocks,
@@ -162,14 +134,14 @@ pub async fn create_message(
 async fn handle_scripted_message(
     state: Arc<AppState>,
     request: MessagesRequest,
-    request_start: Instant,
+    req: PipelineRequest,
     script: Arc<crate::script::Script>,
 ) -> Response {
     let turn_index = script.cursor();
-    let turn = match script.next_turn() {
+    let turn = match script.next_turn_with_tool_results(request.tool_result_ids()) {
         ScriptedResponse::Turn(t) => t,
         ScriptedResponse::Exhausted => {
-            state.stats.record_error(500);
+            req.fail(500);
             return sim_error_to_anthropic_response(&SimError::Other {
                 message: "llmsim script exhausted (on_exhausted=error)".to_string(),
                 status_code: Some(500),
@@ -177,34 +149,33 @@ async fn handle_scripted_message(
         }
     };
 
-    let (text, tool_calls) = match turn {
-        SimTurn::Assistant { text } => (Some(text), Vec::new()),
-        SimTurn::ToolCalls { calls } => (None, calls),
-        SimTurn::Mixed { text, calls } => (Some(text), calls),
-        SimTurn::Error(err) => {
-            state.stats.record_error(err.status_code());
+    let (text, mut tool_calls) = match turn.into_parts() {
+        Ok(parts) => parts,
+        Err(err) => {
+            req.fail(err.status_code());
             return sim_error_to_anthropic_response(&err);
         }
     };
+    for (i, call) in tool_calls.iter_mut().enumerate() {
+        call.id
+            .get_or_insert_with(|| anthropic_tool_use_id(turn_index, i));
+    }
+    script.record_tool_call_ids(tool_calls.iter().filter_map(|c| c.id.clone()).collect());
 
-    let input_tokens = count_input_tokens(&request);
-    let text_for_usage = text.clone().unwrap_or_default();
-    let mut output_tokens = crate::count_tokens_default(&text_for_usage)
-        .unwrap_or(text_for_usage.split_whitespace().count());
+    let input_tokens = count_input_tokens(&state.pipeline, &request);
+    let mut output_tokens = state
+        .pipeline
+        .count_tokens(text.as_deref().unwrap_or_default());
 
     let mut content: Vec<ContentBlock> = Vec::new();
     if let Some(t) = text {
         content.push(ContentBlock::text(t));
     }
     let has_tool_calls = !tool_calls.is_empty();
-    for (i, call) in tool_calls.into_iter().enumerate() {
-        output_tokens += tool_call_token_estimate(&call);
-        let id = call
-            .id
-            .clone()
-            .unwrap_or_else(|| anthropic_tool_use_id(turn_index, i));
+    for call in tool_calls {
+        output_tokens += tool_call_token_estimate(&state.pipeline, &call);
         content.push(ContentBlock::ToolUse {
-            id,
+            id: call.id.unwrap_or_default(),
             name: call.name,
             input: call.arguments,
         });
@@ -222,23 +193,24 @@ async fn handle_scripted_message(
     }
 
     let usage = Usage::new(input_tokens as u32, output_tokens as u32);
-    state.stats.record_request_end(
-        request_start.elapsed(),
-        usage.input_tokens,
-        usage.output_tokens,
-    );
+    req.finish(usage.input_tokens, usage.output_tokens);
 
-    let response = MessagesResponse::with_content(request.model, content, stop_reason, usage);
-    Json(response).into_response()
+    let (input_tok, output_tok) = (usage.input_tokens, usage.output_tokens);
+    let response =
+        MessagesResponse::with_content(request.model.clone(), content, stop_reason, usage);
+    apply_cost(
+        &state,
+        Json(response).into_response(),
+        &request.model,
+        input_tok,
+        output_tok,
+    )
 }
 
 /// Generate response content for non-scripted requests via the configured
-/// generator, reusing the OpenAI `ChatCompletionRequest` the generators accept.
-fn generate_content(state: &AppState, request: &MessagesRequest) -> String {
-    let generator = create_generator(
-        &state.config.response.generator,
-        state.config.response.target_tokens,
-    );
+/// generator, reusing the OpenAI `ChatCompletionRequest` the generators accept,
+/// along with the stop sequence it ended at.
+fn generate_content(state: &AppState, request: &MessagesRequest) -> (String, Option<String>) {
     let prompt = request.prompt_text();
     let chat_request = crate::openai::ChatCompletionRequest {
         model: request.model.clone(),
@@ -247,7 +219,10 @@ fn generate_content(state: &AppState, request: &MessagesRequest) -> String {
         top_p: request.top_p,
         n: None,
         stream: request.stream,
-        stop: None,
+        stop: request
+            .stop_sequences
+            .clone()
+            .map(crate::openai::StopCondition::Multiple),
         max_tokens: Some(request.max_tokens),
         max_completion_tokens: Some(request.max_tokens),
         presence_penalty: None,
@@ -256,31 +231,36 @@ fn generate_content(state: &AppState, request: &MessagesRequest) -> String {
         user: request.metadata.as_ref().and_then(|m| m.user_id.clone()),
         tools: None,
         tool_choice: None,
+        parallel_tool_calls: None,
         response_format: None,
         seed: None,
+        stream_options: None,
+        functions: None,
+        function_call: None,
+        store: None,
+        metadata: None,
     };
-    generator.generate(&chat_request)
+    state.pipeline.generate_with_stop(&chat_request)
 }
 
 /// Count input tokens for a Messages request (prompt text + small overhead).
-fn count_input_tokens(request: &MessagesRequest) -> usize {
+fn count_input_tokens(pipeline: &RequestPipeline, request: &MessagesRequest) -> usize {
     let text = request.prompt_text();
-    let base = crate::count_tokens_default(&text).unwrap_or(text.split_whitespace().count());
+    let base = pipeline.count_tokens(&text);
     // Per-message + request framing overhead, similar to the OpenAI handler.
     base + request.messages.len() * 3 + 5
 }
 
 /// Approximate output tokens contributed by a scripted tool call.
-fn tool_call_token_estimate(call: &SimToolCall) -> usize {
+fn tool_call_token_estimate(pipeline: &RequestPipeline, call: &SimToolCall) -> usize {
     let args = serde_json::to_string(&call.arguments).unwrap_or_default();
-    crate::count_tokens_default(&args).unwrap_or(args.split_whitespace().count())
-        + call.name.split_whitespace().count()
+    pipeline.count_tokens(&args) + call.name.split_whitespace().count()
 }
 
 /// Generate a deterministic-ish `toolu_`-prefixed tool-use id when the script
 /// did not supply one (Anthropic tool_use ids use the `toolu_` prefix).
 fn anthropic_tool_use_id(_turn_index: usize, _call_index: usize) -> String {
-    prefixed_compact_id("toolu_")
+    new_id(IdKind::ToolUse)
 }
 
 /// GET /anthropic/v1/models
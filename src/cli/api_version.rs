@@ -0,0 +1,155 @@
+// API Version Selection
+// Extracts the version selectors an OpenAI client sends (the `OpenAI-Beta`
+// header, an `api-version` query parameter or `OpenAI-Version` header) and
+// resolves the compatibility behavior configured for them.
+//
+// Decision: selectors are opaque strings matched exactly against the
+// `[api_versions]` config keys. The simulator does not know what any upstream
+// version means; operators map the versions their clients send onto the few
+// behaviors it can switch, so a new upstream version is a config change.
+
+use super::config::ApiVersionBehavior;
+use crate::openai::ToolCallFormat;
+use axum::{
+    extract::FromRequestParts,
+    http::{request::Parts, HeaderMap},
+};
+use std::collections::HashMap;
+use std::convert::Infallible;
+
+/// Version selectors sent with a request
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ApiVersion {
+    /// `api-version` query parameter (Azure style) or `OpenAI-Version` header
+    pub version: Option<String>,
+    /// `OpenAI-Beta` entries, e.g. `assistants=v2`, in the order sent
+    pub betas: Vec<String>,
+}
+
+impl ApiVersion {
+    pub fn from_parts(headers: &HeaderMap, query: Option<&str>) -> Self {
+        let version = query
+            .into_iter()
+            .flat_map(|q| q.split('&'))
+            .find_map(|pair| pair.strip_prefix("api-version="))
+            .or_else(|| headers.get("openai-version").and_then(|v| v.to_str().ok()))
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty());
+
+        let betas = headers
+            .get_all("openai-beta")
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .map(str::trim)
+            .filter(|b| !b.is_empty())
+            .map(String::from)
+            .collect();
+
+        Self { version, betas }
+    }
+
+    /// Selectors in precedence order: the API version, then beta entries
+    pub fn selectors(&self) -> impl Iterator<Item = &str> {
+        self.version
+            .as_deref()
+            .into_iter()
+            .chain(self.betas.iter().map(String::as_str))
+    }
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for ApiVersion {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(Self::from_parts(&parts.headers, parts.uri.query()))
+    }
+}
+
+/// Behavior switches resolved for one request
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ApiBehavior {
+    pub tool_call_format: ToolCallFormat,
+}
+
+impl ApiBehavior {
+    /// Each switch takes its value from the first selector whose
+    /// `[api_versions]` entry sets it, falling back to current API behavior.
+    pub fn resolve(rules: &HashMap<String, ApiVersionBehavior>, version: &ApiVersion) -> Self {
+        let mut matched = version.selectors().filter_map(|s| rules.get(s));
+        Self {
+            tool_call_format: matched.find_map(|r| r.tool_call_format).unwrap_or_default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_parts() {
+        let mut headers = HeaderMap::new();
+        headers.append("openai-beta", "assistants=v1, realtime=v1".parse().unwrap());
+        headers.append("openai-beta", "responses=v1".parse().unwrap());
+        headers.insert("openai-version", "2020-10-01".parse().unwrap());
+
+        let version = ApiVersion::from_parts(&headers, Some("foo=1&api-version=2023-05-15"));
+        assert_eq!(version.version.as_deref(), Some("2023-05-15"));
+        assert_eq!(
+            version.selectors().collect::<Vec<_>>(),
+            vec!["2023-05-15", "assistants=v1", "realtime=v1", "responses=v1"]
+        );
+
+        let version = ApiVersion::from_parts(&headers, None);
+        assert_eq!(version.version.as_deref(), Some("2020-10-01"));
+
+        assert_eq!(
+            ApiVersion::from_parts(&HeaderMap::new(), None),
+            ApiVersion::default()
+        );
+    }
+
+    #[test]
+    fn test_resolve() {
+        let rules = HashMap::from([
+            (
+                "2023-05-15".to_string(),
+                ApiVersionBehavior {
+                    tool_call_format: Some(ToolCallFormat::FunctionCall),
+                },
+            ),
+            (
+                "assistants=v2".to_string(),
+                ApiVersionBehavior {
+                    tool_call_format: Some(ToolCallFormat::ToolCalls),
+                },
+            ),
+        ]);
+        let resolve = |version: Option<&str>, betas: &[&str]| {
+            ApiBehavior::resolve(
+                &rules,
+                &ApiVersion {
+                    version: version.map(String::from),
+                    betas: betas.iter().map(|b| b.to_string()).collect(),
+                },
+            )
+            .tool_call_format
+        };
+
+        assert_eq!(resolve(None, &[]), ToolCallFormat::ToolCalls);
+        assert_eq!(
+            resolve(Some("2023-05-15"), &[]),
+            ToolCallFormat::FunctionCall
+        );
+        // The API version outranks beta entries
+        assert_eq!(
+            resolve(Some("2023-05-15"), &["assistants=v2"]),
+            ToolCallFormat::FunctionCall
+        );
+        assert_eq!(
+            resolve(Some("2024-01-01"), &["assistants=v2"]),
+            ToolCallFormat::ToolCalls
+        );
+    }
+}
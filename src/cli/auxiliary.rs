@@ -0,0 +1,128 @@
+// Auxiliary Endpoints Module
+// Router middleware applying `[auxiliary]`: a delay and injected errors for
+// the endpoints that generate nothing (model listing and lookup, stored
+// responses, vLLM metrics), so a client's startup path can meet slow or
+// failing model discovery.
+//
+// Decision: a middleware rather than code in each handler, because these
+// endpoints are spread over every provider's handlers and none needs its
+// request body read. Generation requests, `/health` and `/llmsim/*` pass
+// untouched, so `[latency]` and `[errors]` keep governing completions and
+// the simulator stays observable while its models endpoint is failing. The
+// delay comes before the error, as a slow upstream that then gives up would
+// answer; errors are drawn like `[errors]` and rendered in the format of the
+// provider the path belongs to.
+
+use super::config::Config;
+use super::handlers::injected_error_response;
+use super::proxy::endpoint_type;
+use super::state::AppState;
+use crate::errors::{ErrorConfig, ErrorInjector, SimulatedError};
+use crate::glob::glob_match;
+use crate::latency::LatencyProfile;
+use crate::pipeline::{AnthropicFormat, OpenAIFormat};
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::Response,
+};
+use std::sync::Arc;
+
+/// `[auxiliary]`, ready to apply
+#[derive(Debug)]
+pub struct Auxiliary {
+    latency: Option<LatencyProfile>,
+    errors: ErrorConfig,
+    paths: Vec<String>,
+}
+
+impl Auxiliary {
+    /// `None` when the section asks for neither latency nor errors
+    pub fn from_config(config: &Config) -> Option<Self> {
+        config.auxiliary.is_active().then(|| Self {
+            latency: config.auxiliary_latency(),
+            errors: config.auxiliary_error_config(),
+            paths: config.auxiliary.paths.clone(),
+        })
+    }
+
+    /// Whether a request is for an auxiliary endpoint the section covers
+    fn applies(&self, method: &str, path: &str) -> bool {
+        let generation = method == "POST" && endpoint_type(path).is_some();
+        let simulator = path == "/health" || path.starts_with("/llmsim/");
+        !generation
+            && !simulator
+            && (self.paths.is_empty() || self.paths.iter().any(|glob| glob_match(glob, path)))
+    }
+
+    fn error(&self) -> Option<SimulatedError> {
+        ErrorInjector::new(self.errors.clone()).maybe_inject()
+    }
+}
+
+/// Middleware: delay and fail requests to auxiliary endpoints
+pub async fn apply_auxiliary(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(auxiliary) = state.auxiliary.clone() else {
+        return next.run(request).await;
+    };
+    let path = request.uri().path().to_string();
+    if !auxiliary.applies(request.method().as_str(), &path) {
+        return next.run(request).await;
+    }
+
+    if let Some(latency) = &auxiliary.latency {
+        tokio::time::sleep(latency.sample_ttft()).await;
+    }
+    let Some(error) = auxiliary.error() else {
+        return next.run(request).await;
+    };
+    tracing::warn!(path, "Injecting auxiliary endpoint error: {:?}", error);
+    state.stats.record_error(error.status_code());
+    if path.starts_with("/anthropic/") {
+        injected_error_response::<AnthropicFormat>(&error)
+    } else {
+        injected_error_response::<OpenAIFormat>(&error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn auxiliary(toml: &str) -> Auxiliary {
+        let config = Config::from_toml(toml).unwrap();
+        Auxiliary::from_config(&config).unwrap()
+    }
+
+    #[test]
+    fn test_applies_only_to_auxiliary_endpoints() {
+        let all = auxiliary("[auxiliary]\nserver_error_rate = 1.0\n");
+        assert!(all.applies("GET", "/openai/v1/models"));
+        assert!(all.applies("GET", "/anthropic/v1/models/claude-sonnet-4"));
+        assert!(all.applies("GET", "/openai/v1/responses/resp_1"));
+        assert!(!all.applies("POST", "/openai/v1/chat/completions"));
+        assert!(!all.applies("POST", "/openai/v1/responses"));
+        assert!(!all.applies("GET", "/health"));
+        assert!(!all.applies("GET", "/llmsim/stats"));
+
+        let models = auxiliary("[auxiliary]\nlatency_mean_ms = 10\npaths = [\"*/v1/models*\"]\n");
+        assert!(models.applies("GET", "/vllm/v1/models"));
+        assert!(!models.applies("GET", "/vllm/metrics"));
+    }
+
+    #[test]
+    fn test_inactive_without_latency_or_errors() {
+        let config = Config::from_toml("[auxiliary]\npaths = [\"/openai/*\"]\n").unwrap();
+        assert!(Auxiliary::from_config(&config).is_none());
+
+        let config = Config::from_toml(
+            "[auxiliary]\nlatency_mean_ms = 500\n\n[simulation]\ndisable_delays = true\n",
+        )
+        .unwrap();
+        assert_eq!(Auxiliary::from_config(&config).unwrap().latency, None);
+    }
+}
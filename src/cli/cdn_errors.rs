@@ -0,0 +1,129 @@
+// CDN Errors Module
+// Router middleware applying `[errors] cdn_error_rate`: a generation request
+// is answered by a simulated intermediary in front of the API (a CDN or load
+// balancer) with an HTML page, a plain-text reason or an empty body, e.g.
+// Cloudflare's 520, instead of the API's JSON error.
+//
+// Decision: the pages are `[errors.responses]` templates sent by the same
+// code as a rule's, so both are written as is, without the endpoint's error
+// format. The middleware sits outside the API's own layers (keys, rate
+// limits, rules, proxy), since a CDN answers before the origin sees the
+// request. Pages are answered at once: a 524's wait is what `hang_rate` is
+// for.
+
+use super::config::{CdnError, ErrorTemplate};
+use super::error_rules::template_response;
+use super::proxy::endpoint_type;
+use super::request_body::ParsedBody;
+use super::state::AppState;
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::Response,
+};
+use rand::RngExt;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+const HTML_502: &str = "<html>\r\n<head><title>502 Bad Gateway</title></head>\r\n<body>\r\n<center><h1>502 Bad Gateway</h1></center>\r\n<hr><center>cloudflare</center>\r\n</body>\r\n</html>\r\n";
+const HTML_520: &str = "<!DOCTYPE html>\n<html lang=\"en-US\">\n<head><title>api.example.com | 520: Web server is returning an unknown error</title></head>\n<body>\n<div id=\"cf-error-details\">\n<h1>Web server is returning an unknown error</h1>\n<span>Error code 520</span>\n<p>There is an unknown connection issue between Cloudflare and the origin web server. As a result, the web page can not be displayed.</p>\n</div>\n</body>\n</html>\n";
+const HTML_524: &str = "<!DOCTYPE html>\n<html lang=\"en-US\">\n<head><title>api.example.com | 524: A timeout occurred</title></head>\n<body>\n<div id=\"cf-error-details\">\n<h1>A timeout occurred</h1>\n<span>Error code 524</span>\n<p>The origin web server timed out responding to this request.</p>\n</div>\n</body>\n</html>\n";
+const TEXT_502: &str =
+    "upstream connect error or disconnect/reset before headers. reset reason: connection termination";
+
+impl CdnError {
+    /// The page, as a template a rule could have written
+    pub fn template(self) -> ErrorTemplate {
+        let (status, body) = match self {
+            Self::Html502 => (502, Some(HTML_502)),
+            Self::Html520 => (520, Some(HTML_520)),
+            Self::Html524 => (524, Some(HTML_524)),
+            Self::Text502 => (502, Some(TEXT_502)),
+            Self::Empty502 => (502, None),
+            Self::Empty520 => (520, None),
+            Self::Empty524 => (524, None),
+        };
+        let server = if self == Self::Text502 {
+            "envoy"
+        } else {
+            "cloudflare"
+        };
+        let mut headers = BTreeMap::from([("server".to_string(), server.to_string())]);
+        if matches!(self, Self::Html502 | Self::Html520 | Self::Html524) {
+            headers.insert(
+                "content-type".to_string(),
+                "text/html; charset=UTF-8".to_string(),
+            );
+        }
+        ErrorTemplate {
+            status,
+            headers,
+            body: body.map_or(Value::Null, Value::from),
+        }
+    }
+}
+
+/// Middleware: answer generation requests with a CDN's error page at
+/// `[errors] cdn_error_rate`
+pub async fn inject_cdn_errors(
+    State(state): State<Arc<AppState>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let errors = &state.config.errors;
+    let rate = errors.cdn_error_rate.clamp(0.0, 1.0);
+    let path = request.uri().path().to_string();
+    let endpoint = (request.method() == "POST")
+        .then(|| endpoint_type(&path))
+        .flatten();
+    let Some(endpoint) = endpoint.filter(|_| rand::rng().random_bool(rate)) else {
+        return next.run(request).await;
+    };
+    let kinds = if errors.cdn_errors.is_empty() {
+        &CdnError::ALL[..]
+    } else {
+        &errors.cdn_errors[..]
+    };
+    let kind = kinds[rand::rng().random_range(0..kinds.len())];
+
+    let body = match ParsedBody::read(&mut request).await {
+        Ok(body) => body,
+        Err(response) => return response,
+    };
+    let model = body.model.as_str();
+    let template = kind.template();
+    tracing::info!(
+        model,
+        status = template.status,
+        "Answering with a CDN error page: {:?}",
+        kind
+    );
+    state
+        .begin(model, body.stream, endpoint)
+        .fail(template.status);
+    template_response(&template, model)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_templates() {
+        for kind in CdnError::ALL {
+            let template = kind.template();
+            assert!(matches!(template.status, 502 | 520 | 524), "{:?}", kind);
+            assert_eq!(
+                template.body.is_null(),
+                matches!(
+                    kind,
+                    CdnError::Empty502 | CdnError::Empty520 | CdnError::Empty524
+                )
+            );
+        }
+        let html = CdnError::Html520.template();
+        assert!(html.body.as_str().unwrap().contains("Error code 520"));
+        assert!(html.headers["content-type"].starts_with("text/html"));
+    }
+}
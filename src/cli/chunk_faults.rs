@@ -0,0 +1,166 @@
+// Chunk Faults Module
+// `[stream] duplicate_rate` and `reorder_rate`: delta events occasionally
+// sent twice, or swapped with the delta after them, to check that clients
+// either tolerate such streams or catch them by sequence number.
+//
+// Decision: only events carrying a piece of generated content are touched
+// (chat chunks with content or tool call deltas, Responses `*.delta` events,
+// Anthropic `content_block_delta`), told apart by parsing the event's data;
+// duplicating a role or finish chunk, a `response.completed` or `[DONE]`
+// tests something else, a stream that starts or ends wrong. Faults are applied
+// to events as generated, before slow-reader coalescing, so a swapped pair
+// can still land in one body frame like it would from a real server. A
+// duplicate is byte-identical, sequence number included.
+
+use futures_core::Stream;
+use futures_util::StreamExt;
+use rand::rngs::StdRng;
+use rand::RngExt;
+use serde_json::Value;
+
+/// Pass `events` through, duplicating delta events at `duplicate_rate` and
+/// swapping a delta event with the following one at `reorder_rate`
+pub(crate) fn fault_events<S>(
+    mut events: S,
+    duplicate_rate: f64,
+    reorder_rate: f64,
+) -> impl Stream<Item = String>
+where
+    S: Stream<Item = String> + Send + Unpin + 'static,
+{
+    let duplicate_rate = duplicate_rate.clamp(0.0, 1.0);
+    let reorder_rate = reorder_rate.clamp(0.0, 1.0);
+    async_stream::stream! {
+        let mut rng: StdRng = rand::make_rng();
+        let mut held: Option<String> = None;
+        while let Some(event) = events.next().await {
+            let delta = is_content_delta(&event);
+            if let Some(earlier) = held.take() {
+                // Swap only with another delta; anything else goes out in order
+                if delta {
+                    yield event;
+                    yield earlier;
+                } else {
+                    yield earlier;
+                    yield event;
+                }
+                continue;
+            }
+            if delta && rng.random_bool(reorder_rate) {
+                held = Some(event);
+                continue;
+            }
+            if delta && rng.random_bool(duplicate_rate) {
+                yield event.clone();
+            }
+            yield event;
+        }
+        if let Some(earlier) = held {
+            yield earlier;
+        }
+    }
+}
+
+/// Whether an SSE event (its `id:` or `event:` lines included) carries a
+/// piece of generated content
+fn is_content_delta(event: &str) -> bool {
+    let Some(data) = event.lines().find_map(|line| line.strip_prefix("data: ")) else {
+        return false;
+    };
+    let Ok(json) = serde_json::from_str::<Value>(data) else {
+        return false;
+    };
+    match json["type"].as_str() {
+        // Anthropic: text, thinking or tool input JSON
+        Some("content_block_delta") => json["delta"].as_object().is_some_and(|delta| {
+            delta
+                .iter()
+                .any(|(key, value)| key != "type" && is_text(value))
+        }),
+        // Responses and OpenResponses
+        Some(kind) => kind.ends_with(".delta") && is_text(&json["delta"]),
+        // Chat Completions
+        None => json["choices"].as_array().is_some_and(|choices| {
+            choices.iter().any(|choice| {
+                let delta = &choice["delta"];
+                is_text(&delta["content"])
+                    || is_text(&delta["reasoning_content"])
+                    || delta["tool_calls"]
+                        .as_array()
+                        .is_some_and(|calls| !calls.is_empty())
+            })
+        }),
+    }
+}
+
+/// A non-empty string
+fn is_text(value: &Value) -> bool {
+    value.as_str().is_some_and(|text| !text.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn run(events: &[&str], duplicate_rate: f64, reorder_rate: f64) -> Vec<String> {
+        let events: Vec<String> = events.iter().map(|e| e.to_string()).collect();
+        fault_events(
+            futures_util::stream::iter(events),
+            duplicate_rate,
+            reorder_rate,
+        )
+        .collect()
+        .await
+    }
+
+    const START: &str = "data: {\"choices\":[{\"delta\":{\"role\":\"assistant\"}}]}\n\n";
+    const ONE: &str = "data: {\"choices\":[{\"delta\":{\"content\":\"one\"}}]}\n\n";
+    const TWO: &str = "data: {\"choices\":[{\"delta\":{\"content\":\"two\"}}]}\n\n";
+    const THREE: &str = "data: {\"choices\":[{\"delta\":{\"content\":\"three\"}}]}\n\n";
+    const FINISH: &str = "data: {\"choices\":[{\"delta\":{},\"finish_reason\":\"stop\"}]}\n\n";
+    const DONE: &str = "data: [DONE]\n\n";
+
+    #[tokio::test]
+    async fn test_duplicates_only_deltas() {
+        let out = run(&[START, ONE, TWO, FINISH, DONE], 1.0, 0.0).await;
+        assert_eq!(out, vec![START, ONE, ONE, TWO, TWO, FINISH, DONE]);
+    }
+
+    #[tokio::test]
+    async fn test_swaps_adjacent_deltas() {
+        let out = run(&[START, ONE, TWO, THREE, FINISH, DONE], 0.0, 1.0).await;
+        // one and two swap; three is held but has no delta after it to swap
+        // with
+        assert_eq!(out, vec![START, TWO, ONE, THREE, FINISH, DONE]);
+        let out = run(&[ONE], 0.0, 1.0).await;
+        assert_eq!(out, vec![ONE]);
+    }
+
+    #[tokio::test]
+    async fn test_zero_rates_pass_through() {
+        let events = [START, ONE, TWO, FINISH, DONE];
+        assert_eq!(run(&events, 0.0, 0.0).await, events);
+    }
+
+    #[test]
+    fn test_content_deltas() {
+        assert!(is_content_delta(&format!("id: s:2\n{}", ONE)));
+        // Text that merely mentions a delta is not one
+        assert!(!is_content_delta(
+            "data: {\"choices\":[{\"delta\":{\"content\":\"\"}},\"note\":\"delta\"}]}\n\n"
+        ));
+        assert!(!is_content_delta(FINISH));
+        assert!(is_content_delta(
+            "data: {\"type\":\"response.output_text.delta\",\"delta\":\"Hi\"}\n\n"
+        ));
+        assert!(!is_content_delta(
+            "data: {\"type\":\"response.output_text.done\",\"text\":\"delta\"}\n\n"
+        ));
+        assert!(is_content_delta(
+            "event: content_block_delta\ndata: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\"Hi\"}}\n\n"
+        ));
+        assert!(!is_content_delta(
+            "event: message_delta\ndata: {\"type\":\"message_delta\",\"delta\":{\"stop_reason\":\"end_turn\"}}\n\n"
+        ));
+    }
+}
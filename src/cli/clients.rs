@@ -0,0 +1,141 @@
+// Clients Module
+// Router middleware counting each client's generation requests in flight, for
+// the per-client concurrency figures of `/llmsim/stats`, so a client-side
+// semaphore can be checked against the parallelism the server actually saw.
+//
+// Decision: a client is its API key when it sends one, else its address, so
+// several workers behind one key count together as the provider would see
+// them. Keys longer than a test key are masked to their first and last
+// characters before they reach the stats. A request stays in flight until
+// its response body is finished or dropped, which for a stream is its last
+// event or the client hanging up, not the moment the handler returns.
+
+use super::proxy::endpoint_type;
+use super::rate_limits::api_key;
+use super::state::AppState;
+use crate::stats::SharedStats;
+use axum::{
+    body::{Body, Bytes, HttpBody},
+    extract::{ConnectInfo, Request, State},
+    http::HeaderMap,
+    middleware::Next,
+    response::Response,
+};
+use http_body::{Frame, SizeHint};
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+/// Client of requests with neither a key nor a known address
+const ANONYMOUS_CLIENT: &str = "anonymous";
+/// Longest API key shown unmasked
+const MAX_UNMASKED_KEY_CHARS: usize = 12;
+
+/// Middleware: keep generation requests in their client's in-flight count
+/// until their response is sent
+pub async fn track_clients(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let generation = request.method() == "POST" && endpoint_type(request.uri().path()).is_some();
+    if !generation {
+        return next.run(request).await;
+    }
+    let peer = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| *addr);
+    let client = client_id(request.headers(), peer);
+    state.stats.record_client_start(&client);
+    let guard = InFlight {
+        stats: state.stats.clone(),
+        client,
+    };
+    next.run(request).await.map(|body| {
+        Body::new(TrackedBody {
+            inner: body,
+            _guard: guard,
+        })
+    })
+}
+
+/// Stats name of a request's client: its masked API key, or its IP address
+fn client_id(headers: &HeaderMap, peer: Option<SocketAddr>) -> String {
+    let key = api_key(headers);
+    if !key.is_empty() {
+        return mask_key(&key);
+    }
+    peer.map_or_else(
+        || ANONYMOUS_CLIENT.to_string(),
+        |addr| addr.ip().to_string(),
+    )
+}
+
+/// `sk-proj-abc...wxyz` as `sk-...wxyz`; keys too short to be real secrets,
+/// like test keys naming a worker, are kept whole
+pub(super) fn mask_key(key: &str) -> String {
+    let chars: Vec<char> = key.chars().collect();
+    if chars.len() <= MAX_UNMASKED_KEY_CHARS {
+        return key.to_string();
+    }
+    let head: String = chars[..3].iter().collect();
+    let tail: String = chars[chars.len() - 4..].iter().collect();
+    format!("{}...{}", head, tail)
+}
+
+/// Takes a request out of its client's in-flight count when dropped
+struct InFlight {
+    stats: SharedStats,
+    client: String,
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.stats.record_client_end(&self.client);
+    }
+}
+
+/// A response body holding its request in flight while it is being sent
+struct TrackedBody {
+    inner: Body,
+    _guard: InFlight,
+}
+
+impl HttpBody for TrackedBody {
+    type Data = Bytes;
+    type Error = axum::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, axum::Error>>> {
+        Pin::new(&mut self.get_mut().inner).poll_frame(cx)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_client_id() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(client_id(&headers, None), ANONYMOUS_CLIENT);
+        let peer = SocketAddr::from(([10, 0, 0, 7], 50123));
+        assert_eq!(client_id(&headers, Some(peer)), "10.0.0.7");
+        headers.insert("x-api-key", "sk-ant-api03-secret-wxyz".parse().unwrap());
+        assert_eq!(client_id(&headers, Some(peer)), "sk-...wxyz");
+        headers.insert("x-api-key", "worker-1".parse().unwrap());
+        assert_eq!(client_id(&headers, None), "worker-1");
+    }
+}
@@ -0,0 +1,213 @@
+// Cluster Module
+// `[cluster]`: several llmsim processes serving one benchmark, whether
+// `serve --workers N` on one port or separate servers behind a balancer,
+// with their stats combined at `/llmsim/cluster/stats`.
+//
+// Decision: each process writes its stats snapshot to a file in a shared
+// directory, and whichever process is asked merges the files. This needs no
+// coordinator, works for workers sharing a port through SO_REUSEPORT (which
+// cannot be addressed one by one over HTTP), and a process that dies only
+// leaves a file that goes stale. Files older than a few publish intervals are
+// left out of the total rather than counted with old numbers. The process
+// answering uses its live figures for itself.
+
+use super::config::ClusterConfig;
+use crate::stats::{SharedStats, StatsSnapshot};
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tokio::net::TcpListener;
+
+/// Publish intervals a stats file may miss before it is left out
+const STALE_INTERVALS: u32 = 3;
+
+/// Stats combined across the cluster, as served by `/llmsim/cluster/stats`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClusterStats {
+    /// Processes counted, this one first
+    pub nodes: Vec<ClusterNode>,
+    /// Processes whose stats file is too old to count
+    pub stale_nodes: Vec<String>,
+    pub stats: StatsSnapshot,
+}
+
+/// One process counted in the cluster stats
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClusterNode {
+    pub node: String,
+    /// Age of the stats counted for it; zero for the process answering
+    pub age_ms: u64,
+}
+
+/// This process's name in the stats directory
+pub(crate) fn node_name(config: &ClusterConfig) -> String {
+    config
+        .node
+        .clone()
+        .unwrap_or_else(|| format!("pid-{}", std::process::id()))
+}
+
+/// Write this process's stats to the directory every publish interval
+pub(crate) async fn publish(stats: SharedStats, config: ClusterConfig) {
+    let Some(dir) = config.stats_dir.as_deref().map(PathBuf::from) else {
+        return;
+    };
+    let node = node_name(&config);
+    let interval = Duration::from_millis(config.publish_interval_ms);
+    loop {
+        if let Err(e) = write_stats(&dir, &node, &stats.snapshot()) {
+            tracing::warn!("Cannot write cluster stats to {}: {}", dir.display(), e);
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
+
+/// Take this process's stats file away, so it is not counted once the
+/// process has stopped
+pub(crate) fn withdraw(config: &ClusterConfig) {
+    if let Some(dir) = &config.stats_dir {
+        let _ = std::fs::remove_file(stats_path(Path::new(dir), &node_name(config)));
+    }
+}
+
+/// Stats of this process, `own`, merged with every fresh stats file of the
+/// other processes in `dir`
+pub(crate) fn collect(config: &ClusterConfig, dir: &Path, own: StatsSnapshot) -> ClusterStats {
+    let node = node_name(config);
+    let stale_after = Duration::from_millis(config.publish_interval_ms) * STALE_INTERVALS;
+    let mut cluster = ClusterStats {
+        nodes: vec![ClusterNode {
+            node: node.clone(),
+            age_ms: 0,
+        }],
+        stale_nodes: Vec::new(),
+        stats: own,
+    };
+    let mut others = read_stats(dir);
+    others.sort_by(|a, b| a.0.cmp(&b.0));
+    for (name, age, snapshot) in others {
+        if name == node {
+            continue;
+        }
+        if age > stale_after {
+            cluster.stale_nodes.push(name);
+            continue;
+        }
+        cluster.stats.merge(&snapshot);
+        cluster.nodes.push(ClusterNode {
+            node: name,
+            age_ms: age.as_millis() as u64,
+        });
+    }
+    cluster
+}
+
+/// Every readable stats file in `dir`: its node, age and snapshot
+fn read_stats(dir: &Path) -> Vec<(String, Duration, StatsSnapshot)> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let now = SystemTime::now();
+    entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let path = entry.path();
+            let name = path
+                .file_name()?
+                .to_str()?
+                .strip_suffix(".json")?
+                .to_string();
+            let modified = entry.metadata().ok()?.modified().ok()?;
+            let snapshot = serde_json::from_slice(&std::fs::read(&path).ok()?).ok()?;
+            let age = now.duration_since(modified).unwrap_or_default();
+            Some((name, age, snapshot))
+        })
+        .collect()
+}
+
+/// Replace `node`'s stats file in one step, so readers never see half of it
+fn write_stats(dir: &Path, node: &str, snapshot: &StatsSnapshot) -> std::io::Result<()> {
+    let body = serde_json::to_vec(snapshot).map_err(std::io::Error::other)?;
+    let partial = dir.join(format!(".{}.json.tmp", node));
+    std::fs::write(&partial, body)?;
+    std::fs::rename(&partial, stats_path(dir, node))
+}
+
+fn stats_path(dir: &Path, node: &str) -> PathBuf {
+    dir.join(format!("{}.json", node))
+}
+
+/// A listener other processes can bind the same address with, the kernel
+/// spreading connections across them
+#[cfg(unix)]
+pub(crate) fn bind_shared(addr: SocketAddr) -> std::io::Result<TcpListener> {
+    let socket = if addr.is_ipv4() {
+        tokio::net::TcpSocket::new_v4()?
+    } else {
+        tokio::net::TcpSocket::new_v6()?
+    };
+    socket.set_reuseaddr(true)?;
+    socket.set_reuseport(true)?;
+    socket.bind(addr)?;
+    socket.listen(1024)
+}
+
+#[cfg(not(unix))]
+pub(crate) fn bind_shared(_addr: SocketAddr) -> std::io::Result<TcpListener> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "[cluster] reuse_port needs a Unix platform",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::Stats;
+    use crate::EndpointType;
+
+    fn stats_with(requests: usize) -> StatsSnapshot {
+        let stats = Stats::new();
+        for _ in 0..requests {
+            stats.record_request_start("gpt-5", false, EndpointType::ChatCompletions);
+        }
+        stats.snapshot()
+    }
+
+    #[test]
+    fn test_collect_merges_fresh_files() {
+        let dir = std::env::temp_dir().join(format!("llmsim-cluster-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let config = ClusterConfig {
+            stats_dir: Some(dir.display().to_string()),
+            node: Some("a".to_string()),
+            publish_interval_ms: 60_000,
+            reuse_port: false,
+        };
+        write_stats(&dir, "a", &stats_with(100)).unwrap();
+        write_stats(&dir, "b", &stats_with(2)).unwrap();
+        write_stats(&dir, "c", &stats_with(3)).unwrap();
+        std::fs::write(dir.join("notes.txt"), "not stats").unwrap();
+
+        let cluster = collect(&config, &dir, stats_with(1));
+        // The process's own file is replaced by its live figures
+        assert_eq!(cluster.stats.total_requests, 6);
+        let nodes: Vec<&str> = cluster.nodes.iter().map(|n| n.node.as_str()).collect();
+        assert_eq!(nodes, vec!["a", "b", "c"]);
+        assert!(cluster.stale_nodes.is_empty());
+
+        let strict = ClusterConfig {
+            publish_interval_ms: 0,
+            ..config.clone()
+        };
+        std::thread::sleep(Duration::from_millis(5));
+        let cluster = collect(&strict, &dir, stats_with(1));
+        assert_eq!(cluster.stats.total_requests, 1);
+        assert_eq!(cluster.stale_nodes, vec!["b", "c"]);
+
+        withdraw(&config);
+        assert!(!dir.join("a.json").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
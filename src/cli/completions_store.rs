@@ -0,0 +1,462 @@
+// Completion Store Module
+// Chat completions created with `store: true`, kept for the stored
+// completions endpoints: listed at `GET /openai/v1/chat/completions`,
+// retrieved, given new metadata and deleted by id, so retention-management
+// code can be tested against the simulator.
+//
+// Decision: a completion is captured from the response the client was sent
+// rather than rebuilt in each of the handler's paths (generated, pooled,
+// scripted, tool calls), so whatever answered the request is what is
+// stored. A streamed completion is folded back into one `chat.completion`
+// from its chunks once the stream has been sent in full; one that failed or
+// that the client abandoned is not stored. Like the response store, it is
+// bounded by count, drops the oldest first and forgets everything on
+// restart.
+
+use super::config::StoreConfig;
+use super::responses_store::{paginate, ListParams, Order};
+use crate::openai::ListResponse;
+use axum::{
+    body::Body,
+    http::{header, StatusCode},
+    response::Response,
+};
+use futures_util::StreamExt;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+/// Most key-value pairs in a completion's metadata
+const MAX_METADATA_PAIRS: usize = 16;
+/// Longest metadata key, in characters
+const MAX_METADATA_KEY_CHARS: usize = 64;
+/// Longest metadata value, in characters
+const MAX_METADATA_VALUE_CHARS: usize = 512;
+/// Largest response body captured for storing
+const MAX_CAPTURE_BYTES: usize = 16 * 1024 * 1024;
+
+/// Metadata of a stored completion
+pub type Metadata = BTreeMap<String, String>;
+
+/// Body of `POST /openai/v1/chat/completions/{id}`
+#[derive(Debug, Deserialize)]
+pub struct MetadataUpdate {
+    /// Replaces the stored metadata; `null` clears it
+    pub metadata: Option<Metadata>,
+}
+
+/// Stored chat completions by id, oldest dropped first beyond
+/// `max_completions`
+pub struct CompletionStore {
+    max_completions: usize,
+    inner: Mutex<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    completions: HashMap<String, Value>,
+    /// Ids in the order they were stored
+    order: VecDeque<String>,
+}
+
+impl CompletionStore {
+    pub fn new(config: &StoreConfig) -> Self {
+        Self {
+            max_completions: config.max_completions,
+            inner: Mutex::new(Inner::default()),
+        }
+    }
+
+    /// Keep `completion`, a `chat.completion` object, under its id
+    pub fn put(&self, completion: Value) {
+        let Some(id) = completion["id"].as_str().map(str::to_string) else {
+            return;
+        };
+        if self.max_completions == 0 {
+            return;
+        }
+        let mut inner = self.inner.lock().unwrap();
+        if inner.completions.insert(id.clone(), completion).is_none() {
+            inner.order.push_back(id);
+        }
+        while inner.order.len() > self.max_completions {
+            if let Some(oldest) = inner.order.pop_front() {
+                inner.completions.remove(&oldest);
+            }
+        }
+    }
+
+    pub fn get(&self, id: &str) -> Option<Value> {
+        self.inner.lock().unwrap().completions.get(id).cloned()
+    }
+
+    /// Replace the metadata of the completion `id`, returning it updated
+    pub fn set_metadata(&self, id: &str, metadata: Metadata) -> Option<Value> {
+        let mut inner = self.inner.lock().unwrap();
+        let completion = inner.completions.get_mut(id)?;
+        completion["metadata"] = json!(metadata);
+        Some(completion.clone())
+    }
+
+    /// Forget the completion `id`; false when there was none
+    pub fn delete(&self, id: &str) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.completions.remove(id).is_none() {
+            return false;
+        }
+        inner.order.retain(|stored| stored != id);
+        true
+    }
+
+    /// A page of the stored completions that match `filter`
+    pub fn list(
+        &self,
+        params: &ListParams,
+        filter: &CompletionFilter,
+    ) -> Result<ListResponse<Value>, String> {
+        let inner = self.inner.lock().unwrap();
+        let completions: Vec<&Value> = inner
+            .order
+            .iter()
+            .filter_map(|id| inner.completions.get(id))
+            .filter(|completion| filter.matches(completion))
+            .collect();
+        let page = paginate(
+            &completions,
+            |completion| completion["id"].as_str().unwrap_or_default(),
+            params,
+        )?;
+        Ok(page.cloned().cloned())
+    }
+
+    /// Pass `response` through to the client, storing the completion it
+    /// carries with `metadata` once its body has been sent in full
+    pub fn capture(self: Arc<Self>, response: Response, metadata: Metadata) -> Response {
+        if response.status() != StatusCode::OK {
+            return response;
+        }
+        let streamed = response
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.starts_with("text/event-stream"));
+        let (parts, body) = response.into_parts();
+        let mut data = body.into_data_stream();
+        let captured = async_stream::stream! {
+            let mut captured = Vec::new();
+            let mut complete = true;
+            while let Some(chunk) = data.next().await {
+                match &chunk {
+                    Ok(bytes) if captured.len() < MAX_CAPTURE_BYTES => {
+                        captured.extend_from_slice(bytes);
+                    }
+                    Ok(_) => {}
+                    Err(_) => complete = false,
+                }
+                yield chunk;
+            }
+            let completion = if !complete {
+                None
+            } else if streamed {
+                fold_chunks(&captured)
+            } else {
+                serde_json::from_slice::<Value>(&captured).ok()
+            };
+            if let Some(mut completion) = completion {
+                completion["metadata"] = json!(metadata);
+                self.put(completion);
+            }
+        };
+        Response::from_parts(parts, Body::from_stream(captured))
+    }
+}
+
+/// Query parameters of `GET /openai/v1/chat/completions` beyond pagination
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CompletionFilter {
+    /// Only completions from this model
+    pub model: Option<String>,
+    /// Only completions with all of these metadata pairs
+    pub metadata: Metadata,
+}
+
+impl CompletionFilter {
+    /// Filter from a query string: `model=...` and `metadata[key]=value`
+    pub fn from_query(query: Option<&str>) -> Self {
+        let mut filter = Self::default();
+        let pairs = query
+            .into_iter()
+            .flat_map(|q| q.split('&'))
+            .filter_map(|pair| pair.split_once('='));
+        for (name, value) in pairs {
+            let (name, value) = (percent_decode(name), percent_decode(value));
+            if name == "model" {
+                filter.model = Some(value);
+            } else if let Some(key) = name
+                .strip_prefix("metadata[")
+                .and_then(|rest| rest.strip_suffix(']'))
+            {
+                filter.metadata.insert(key.to_string(), value);
+            }
+        }
+        filter
+    }
+
+    fn matches(&self, completion: &Value) -> bool {
+        self.model
+            .as_deref()
+            .is_none_or(|model| completion["model"] == model)
+            && self
+                .metadata
+                .iter()
+                .all(|(key, value)| completion["metadata"][key] == value.as_str())
+    }
+}
+
+/// List parameters for stored completions, which are listed oldest first
+/// unless `order` says otherwise
+pub fn list_params(query: Option<&str>) -> Result<ListParams, String> {
+    let mut params = ListParams::from_query(query)?;
+    let ordered = query
+        .into_iter()
+        .flat_map(|q| q.split('&'))
+        .any(|pair| pair.starts_with("order="));
+    if !ordered {
+        params.order = Order::Asc;
+    }
+    Ok(params)
+}
+
+/// Check metadata against the API's limits
+pub fn validate_metadata(metadata: &Metadata) -> Result<(), String> {
+    if metadata.len() > MAX_METADATA_PAIRS {
+        return Err(format!(
+            "Invalid 'metadata': too many properties. Expected an object with at most {} properties, but got an object with {} properties instead.",
+            MAX_METADATA_PAIRS,
+            metadata.len()
+        ));
+    }
+    for (key, value) in metadata {
+        if key.chars().count() > MAX_METADATA_KEY_CHARS {
+            return Err(format!(
+                "Invalid 'metadata': key '{}' is longer than {} characters.",
+                key, MAX_METADATA_KEY_CHARS
+            ));
+        }
+        if value.chars().count() > MAX_METADATA_VALUE_CHARS {
+            return Err(format!(
+                "Invalid 'metadata.{}': string too long. Expected a string with maximum length {}, but got a string with length {} instead.",
+                key,
+                MAX_METADATA_VALUE_CHARS,
+                value.chars().count()
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// The `chat.completion` a streamed one adds up to: text, refusals, tool
+/// and function calls joined from their deltas, choice by choice, with the
+/// usage of the final chunk
+pub fn fold_chunks(body: &[u8]) -> Option<Value> {
+    let text = String::from_utf8_lossy(body);
+    let mut completion: Option<Value> = None;
+    let mut choices: BTreeMap<u64, Value> = BTreeMap::new();
+    let chunks = text
+        .lines()
+        .filter_map(|line| line.strip_prefix("data:"))
+        .filter_map(|data| serde_json::from_str::<Value>(data.trim()).ok())
+        .filter(|chunk| chunk["object"] == "chat.completion.chunk");
+    for chunk in chunks {
+        let completion = completion.get_or_insert_with(|| {
+            json!({
+                "id": chunk["id"],
+                "object": "chat.completion",
+                "created": chunk["created"],
+                "model": chunk["model"],
+                "choices": [],
+                "usage": null,
+                "system_fingerprint": chunk["system_fingerprint"],
+            })
+        });
+        if !chunk["usage"].is_null() {
+            completion["usage"] = chunk["usage"].clone();
+        }
+        for delta in chunk["choices"].as_array().into_iter().flatten() {
+            let index = delta["index"].as_u64().unwrap_or(0);
+            let choice = choices.entry(index).or_insert_with(|| {
+                json!({
+                    "index": index,
+                    "message": {"role": "assistant", "content": null, "refusal": null},
+                    "logprobs": null,
+                    "finish_reason": null,
+                })
+            });
+            fold_delta(&mut choice["message"], &delta["delta"]);
+            if !delta["finish_reason"].is_null() {
+                choice["finish_reason"] = delta["finish_reason"].clone();
+            }
+        }
+    }
+    let mut completion = completion?;
+    completion["choices"] = Value::Array(choices.into_values().collect());
+    Some(completion)
+}
+
+fn fold_delta(message: &mut Value, delta: &Value) {
+    for field in ["content", "refusal"] {
+        if let Some(text) = delta[field].as_str() {
+            append(&mut message[field], text);
+        }
+    }
+    if let Some(call) = delta.get("function_call") {
+        let folded = message["function_call"].as_object().is_some();
+        if !folded {
+            message["function_call"] = json!({"name": "", "arguments": ""});
+        }
+        fold_function(&mut message["function_call"], call);
+    }
+    for call in delta["tool_calls"].as_array().into_iter().flatten() {
+        if !message["tool_calls"].is_array() {
+            message["tool_calls"] = json!([]);
+        }
+        let index = call["index"].as_u64().unwrap_or(0) as usize;
+        let calls = message["tool_calls"].as_array_mut().unwrap();
+        while calls.len() <= index {
+            calls.push(json!({
+                "id": "",
+                "type": "function",
+                "function": {"name": "", "arguments": ""}
+            }));
+        }
+        let folded = &mut calls[index];
+        for field in ["id", "type"] {
+            if let Some(value) = call[field].as_str() {
+                folded[field] = json!(value);
+            }
+        }
+        fold_function(&mut folded["function"], &call["function"]);
+    }
+}
+
+fn fold_function(folded: &mut Value, delta: &Value) {
+    if let Some(name) = delta["name"].as_str() {
+        append(&mut folded["name"], name);
+    }
+    if let Some(arguments) = delta["arguments"].as_str() {
+        append(&mut folded["arguments"], arguments);
+    }
+}
+
+fn append(target: &mut Value, text: &str) {
+    match target.as_str() {
+        Some(existing) => *target = json!(format!("{}{}", existing, text)),
+        None => *target = json!(text),
+    }
+}
+
+/// `%XX` escapes and `+` decoded, as in a query string
+pub(super) fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| text.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], escaped) {
+            (_, Some(byte)) => {
+                out.push(byte);
+                i += 3;
+            }
+            (b'+', None) => {
+                out.push(b' ');
+                i += 1;
+            }
+            (byte, None) => {
+                out.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// The body `DELETE /openai/v1/chat/completions/{id}` answers with
+pub fn deleted(id: &str) -> Value {
+    json!({"object": "chat.completion.deleted", "id": id, "deleted": true})
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fold_chunks() {
+        let chunk = |choices: Value| {
+            format!(
+                "data: {}\n\n",
+                json!({
+                    "id": "chatcmpl-1",
+                    "object": "chat.completion.chunk",
+                    "created": 1,
+                    "model": "gpt-5",
+                    "system_fingerprint": "fp_1",
+                    "choices": choices
+                })
+            )
+        };
+        let body = [
+            chunk(json!([{"index": 0, "delta": {"role": "assistant", "content": ""}}])),
+            chunk(json!([{"index": 0, "delta": {"content": "Hel"}}])),
+            chunk(json!([{"index": 0, "delta": {"content": "lo"}}])),
+            chunk(json!([{"index": 0, "delta": {"tool_calls": [
+                {"index": 0, "id": "call_1", "type": "function",
+                 "function": {"name": "get_weather", "arguments": "{\"ci"}}
+            ]}}])),
+            chunk(json!([{"index": 0, "delta": {"tool_calls": [
+                {"index": 0, "function": {"arguments": "ty\":1}"}}
+            ]}}])),
+            chunk(json!([{"index": 0, "delta": {}, "finish_reason": "tool_calls"}])),
+            "data: [DONE]\n\n".to_string(),
+        ]
+        .concat();
+
+        let completion = fold_chunks(body.as_bytes()).unwrap();
+        assert_eq!(completion["object"], "chat.completion");
+        assert_eq!(completion["id"], "chatcmpl-1");
+        let choice = &completion["choices"][0];
+        assert_eq!(choice["message"]["content"], "Hello");
+        assert_eq!(choice["finish_reason"], "tool_calls");
+        let call = &choice["message"]["tool_calls"][0];
+        assert_eq!(call["id"], "call_1");
+        assert_eq!(call["function"]["name"], "get_weather");
+        assert_eq!(call["function"]["arguments"], "{\"city\":1}");
+
+        assert!(fold_chunks(b"data: [DONE]\n\n").is_none());
+    }
+
+    #[test]
+    fn test_filter_from_query() {
+        let filter =
+            CompletionFilter::from_query(Some("model=gpt-5&metadata%5Bteam%5D=a+b&limit=5"));
+        assert_eq!(filter.model.as_deref(), Some("gpt-5"));
+        assert_eq!(filter.metadata["team"], "a b");
+
+        let completion = json!({"model": "gpt-5", "metadata": {"team": "a b", "env": "ci"}});
+        assert!(filter.matches(&completion));
+        assert!(!CompletionFilter::from_query(Some("metadata[team]=c")).matches(&completion));
+    }
+
+    #[test]
+    fn test_validate_metadata() {
+        let mut metadata: Metadata = (0..16).map(|i| (i.to_string(), "x".to_string())).collect();
+        assert!(validate_metadata(&metadata).is_ok());
+        metadata.insert("16".to_string(), "x".to_string());
+        assert!(validate_metadata(&metadata).is_err());
+        let long = Metadata::from([("k".to_string(), "x".repeat(513))]);
+        assert!(validate_metadata(&long).is_err());
+    }
+}
@@ -1,15 +1,30 @@
 // Server Configuration Module
 // Handles configuration from files and environment variables.
 
-use crate::{ErrorConfig, LatencyProfile};
+use crate::clock::ClockSkew;
+use crate::deprecation::{DeprecationSchedule, Deprecations};
+use crate::mcp::{McpCatalogs, McpTool};
+use crate::openai::ToolCallFormat;
+use crate::openai::DEFAULT_SYSTEM_FINGERPRINT;
+use crate::tokens::{Encoding, EncodingMap, Tokenizer};
+use crate::{
+    ErrorConfig, IdFormat, IdFormats, IdKind, LatencyProfile, LoadShedder, ModelLatency,
+    ModelPrice, RateLimiter, RetryAfterMode, RetryAfterPolicy, Stamps, SystemFingerprints,
+    ToolArguments, ToolCallConfig,
+};
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 use std::path::Path;
+use std::time::Duration;
 
 /// Server configuration
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct Config {
     #[serde(default)]
     pub server: ServerConfig,
+    /// Threads of the tokio runtime the server runs on
+    #[serde(default)]
+    pub runtime: RuntimeConfig,
     #[serde(default)]
     pub latency: LatencyConfig,
     #[serde(default)]
@@ -18,48 +33,290 @@ pub struct Config {
     pub errors: ErrorsConfig,
     #[serde(default)]
     pub models: ModelsConfig,
+    #[serde(default)]
+    pub vllm: VllmConfig,
+    #[serde(default)]
+    pub cost: CostConfig,
+    #[serde(default)]
+    pub stream: StreamConfig,
+    #[serde(default)]
+    pub compression: CompressionConfig,
+    /// How response bodies are delimited on the wire
+    #[serde(default)]
+    pub framing: FramingConfig,
+    /// Byte-level variations of JSON response bodies
+    #[serde(default)]
+    pub json_body: JsonBodyConfig,
+    /// Chat completions generated at startup and served in turn
+    #[serde(default)]
+    pub pool: PoolConfig,
+    #[serde(default)]
+    pub tools: ToolsConfig,
+    #[serde(default)]
+    pub fingerprint: FingerprintConfig,
+    #[serde(default)]
+    pub capacity: CapacityConfig,
+    #[serde(default)]
+    pub tokens: TokensConfig,
+    #[serde(default)]
+    pub simulation: SimulationConfig,
+    /// Drift of the provider clock that stamps `created` on responses
+    #[serde(default)]
+    pub clock: ClockConfig,
+    /// Compatibility behavior keyed by an API version or `OpenAI-Beta` entry
+    #[serde(default)]
+    pub api_versions: HashMap<String, ApiVersionBehavior>,
+    /// Logical regions served alongside the main listener, keyed by name
+    #[serde(default)]
+    pub regions: BTreeMap<String, RegionConfig>,
+    /// Real provider APIs, keyed by route prefix (`openai`, `anthropic`,
+    /// `openresponses`, `vllm`)
+    #[serde(default)]
+    pub upstreams: BTreeMap<String, UpstreamConfig>,
+    /// Shadow traffic to `upstreams`
+    #[serde(default)]
+    pub mirror: MirrorConfig,
+    /// Requests answered by `upstreams` instead of the simulator
+    #[serde(default)]
+    pub proxy: ProxyConfig,
+    /// Profiles learned from proxied and mirrored traffic
+    #[serde(default)]
+    pub learned: LearnedConfig,
+    /// Hosting of generated artifacts under `/llmsim/files`
+    #[serde(default)]
+    pub files: FilesConfig,
+    /// Responses kept for retrieval and listing
+    #[serde(default)]
+    pub store: StoreConfig,
+    /// Recent requests kept for `/llmsim/requests`
+    #[serde(default)]
+    pub request_log: RequestLogConfig,
+    /// Usage and costs served in the shape of the organization usage API
+    #[serde(default)]
+    pub usage: UsageConfig,
+    /// API keys created and revoked at runtime through `/llmsim/keys`
+    #[serde(default)]
+    pub keys: KeysConfig,
+    /// Processes sharing one benchmark's traffic and stats
+    #[serde(default)]
+    pub cluster: ClusterConfig,
+    /// Fake MCP servers offered to the Responses API
+    #[serde(default)]
+    pub mcp: McpConfig,
+    /// Retirement schedules keyed by model
+    #[serde(default)]
+    pub deprecations: BTreeMap<String, DeprecationSchedule>,
+    /// Per-key budgets behind the rate limit headers, and `Retry-After`
+    #[serde(default)]
+    pub rate_limits: RateLimitsConfig,
+    /// Latency and errors for model listing and the other endpoints that
+    /// generate nothing
+    #[serde(default)]
+    pub auxiliary: AuxiliaryConfig,
+    /// Id formats keyed by kind, in place of the real APIs'
+    #[serde(default)]
+    pub ids: BTreeMap<IdKind, IdFormat>,
+    /// Settings of the `serve --tui` dashboard
+    #[serde(default)]
+    pub tui: TuiConfig,
+    /// Synthetic requests the server sends itself
+    #[serde(default)]
+    pub traffic: TrafficConfig,
 }
 
 impl Config {
-    /// Load configuration from a TOML file
+    /// Load configuration from a TOML file, along with the files it
+    /// includes
     pub fn from_file(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
-        let content =
-            std::fs::read_to_string(path.as_ref()).map_err(|e| ConfigError::Io(e.to_string()))?;
-        Self::from_toml(&content)
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path).map_err(|e| ConfigError::Io(e.to_string()))?;
+        Self::from_toml_in(&content, path.parent().unwrap_or(Path::new(".")))
     }
 
-    /// Parse configuration from TOML string
+    /// Parse configuration from TOML string, expanding `${VAR}` and
+    /// `${VAR:-fallback}` in its strings from the environment. Files it
+    /// includes are found relative to the working directory.
     pub fn from_toml(toml_str: &str) -> Result<Self, ConfigError> {
-        toml::from_str(toml_str).map_err(|e| ConfigError::Parse(e.to_string()))
+        Self::from_toml_in(toml_str, Path::new("."))
+    }
+
+    fn from_toml_in(toml_str: &str, dir: &Path) -> Result<Self, ConfigError> {
+        let config =
+            Self::parse_toml(toml_str, dir).map_err(|e| ConfigError::Parse(e.to_string()))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Deserialize TOML with its includes merged in and its placeholders
+    /// expanded, without validating
+    pub(crate) fn parse_toml(toml_str: &str, dir: &Path) -> Result<Self, toml::de::Error> {
+        let mut table: toml::Table = toml::from_str(toml_str)?;
+        if !table.contains_key("include") && !toml_str.contains("${") {
+            // Straight from the text, so errors keep their line and column
+            return toml::from_str(toml_str);
+        }
+        super::include::resolve_includes(&mut table, dir).map_err(serde::de::Error::custom)?;
+        let expansions =
+            super::interpolate::expand_env(&mut table).map_err(serde::de::Error::custom)?;
+        expansions.deserialize(table)
+    }
+
+    /// Check values the TOML types cannot express
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        for (provider, upstream) in &self.upstreams {
+            super::upstream::parse_base_url(&upstream.base_url)
+                .map_err(|e| ConfigError::Validation(format!("[upstreams.{}] {}", provider, e)))?;
+        }
+        Deprecations::new(&self.deprecations).map_err(ConfigError::Validation)?;
+        IdFormats::new(&self.ids).map_err(ConfigError::Validation)?;
+        let region_errors = self.regions.values().filter_map(|r| r.errors.as_ref());
+        for errors in std::iter::once(&self.errors).chain(region_errors) {
+            super::error_rules::ErrorRules::new(errors).map_err(ConfigError::Validation)?;
+        }
+        for (name, rate) in [
+            ("hang_rate", self.errors.hang_rate),
+            ("cdn_error_rate", self.errors.cdn_error_rate),
+        ] {
+            if !(0.0..=1.0).contains(&rate) {
+                return Err(ConfigError::Validation(format!(
+                    "[errors] {} must be between 0.0 and 1.0",
+                    name
+                )));
+            }
+        }
+        let limits = &self.rate_limits;
+        if !(0.0..=1.0).contains(&limits.retry_after_jitter) {
+            return Err(ConfigError::Validation(
+                "[rate_limits] retry_after_jitter must be between 0.0 and 1.0".to_string(),
+            ));
+        }
+        if limits.retry_after_secs == 0 || limits.max_retry_after_secs < limits.retry_after_secs {
+            return Err(ConfigError::Validation(
+                "[rate_limits] retry_after_secs must be at least 1 and at most max_retry_after_secs"
+                    .to_string(),
+            ));
+        }
+        let auxiliary = &self.auxiliary;
+        let rates = [
+            auxiliary.rate_limit_rate,
+            auxiliary.server_error_rate,
+            auxiliary.timeout_rate,
+        ];
+        if rates.iter().any(|rate| !(0.0..=1.0).contains(rate)) || rates.iter().sum::<f64>() > 1.0 {
+            return Err(ConfigError::Validation(
+                "[auxiliary] error rates must be between 0.0 and 1.0 and sum to at most 1.0"
+                    .to_string(),
+            ));
+        }
+        let alerts = &self.tui.alerts;
+        if alerts
+            .error_rate_percent
+            .is_some_and(|percent| !(0.0..=100.0).contains(&percent))
+        {
+            return Err(ConfigError::Validation(
+                "[tui.alerts] error_rate_percent must be between 0 and 100".to_string(),
+            ));
+        }
+        for (name, rate) in [
+            ("bom_rate", self.json_body.bom_rate),
+            (
+                "trailing_newline_rate",
+                self.json_body.trailing_newline_rate,
+            ),
+        ] {
+            if !(0.0..=1.0).contains(&rate) {
+                return Err(ConfigError::Validation(format!(
+                    "[json_body] {} must be between 0.0 and 1.0",
+                    name
+                )));
+            }
+        }
+        if self.framing.chunk_bytes == Some(0) {
+            return Err(ConfigError::Validation(
+                "[framing] chunk_bytes must be at least 1".to_string(),
+            ));
+        }
+        if self.runtime.worker_threads == Some(0) || self.runtime.max_blocking_threads == Some(0) {
+            return Err(ConfigError::Validation(
+                "[runtime] thread counts must be at least 1".to_string(),
+            ));
+        }
+        if self.stream.pending_events == 0 {
+            return Err(ConfigError::Validation(
+                "[stream] pending_events must be at least 1".to_string(),
+            ));
+        }
+        if self.stream.max_duration_secs == Some(0) {
+            return Err(ConfigError::Validation(
+                "[stream] max_duration_secs must be at least 1".to_string(),
+            ));
+        }
+        let stream_rates = [
+            ("malformed_rate", self.stream.malformed_rate),
+            ("duplicate_rate", self.stream.duplicate_rate),
+            ("reorder_rate", self.stream.reorder_rate),
+            ("tee.sample_rate", self.stream.tee.sample_rate),
+        ];
+        for (name, rate) in stream_rates {
+            if !(0.0..=1.0).contains(&rate) {
+                return Err(ConfigError::Validation(format!(
+                    "[stream] {} must be between 0.0 and 1.0",
+                    name
+                )));
+            }
+        }
+        for (model, latency) in &self.models.latency {
+            if latency.profile().is_none() {
+                return Err(ConfigError::Validation(format!(
+                    "[models.latency] {:?} names an unknown latency profile",
+                    model
+                )));
+            }
+        }
+        if self.pool.enabled && self.pool.size == 0 {
+            return Err(ConfigError::Validation(
+                "[pool] size must be at least 1".to_string(),
+            ));
+        }
+        if self.store.context_window == Some(0) {
+            return Err(ConfigError::Validation(
+                "[store] context_window must be at least 1".to_string(),
+            ));
+        }
+        if self.stream.event_ids && self.stream.resumable_streams == 0 {
+            return Err(ConfigError::Validation(
+                "[stream] resumable_streams must be at least 1 with event_ids".to_string(),
+            ));
+        }
+        if self.request_log.enabled && self.request_log.capacity == 0 {
+            return Err(ConfigError::Validation(
+                "[request_log] capacity must be at least 1".to_string(),
+            ));
+        }
+        if self.request_log.redact.max_content_chars == Some(0) {
+            return Err(ConfigError::Validation(
+                "[request_log.redact] max_content_chars must be at least 1; set [request_log] include_content = false to leave prompts out".to_string(),
+            ));
+        }
+        if self.cluster.publish_interval_ms == 0 {
+            return Err(ConfigError::Validation(
+                "[cluster] publish_interval_ms must be at least 1".to_string(),
+            ));
+        }
+        if !self.proxy.rules.is_empty() && self.upstreams.is_empty() {
+            return Err(ConfigError::Validation(
+                "[proxy] rules need at least one [upstreams] entry".to_string(),
+            ));
+        }
+        super::traffic::validate(&self.traffic)
+            .map_err(|e| ConfigError::Validation(format!("[traffic] {}", e)))?;
+        Ok(())
     }
 
     /// Create a latency profile from the configuration
     pub fn latency_profile(&self) -> LatencyProfile {
         if let Some(ref profile) = self.latency.profile {
-            match profile.to_lowercase().as_str() {
-                // GPT-5 family
-                "gpt5" | "gpt-5" => LatencyProfile::gpt5(),
-                "gpt5-mini" | "gpt-5-mini" => LatencyProfile::gpt5_mini(),
-                // O-series reasoning models (o3, o4)
-                "o3" | "o4" | "o-series" => LatencyProfile::o_series(),
-                // GPT-4 family
-                "gpt4" | "gpt-4" => LatencyProfile::gpt4(),
-                "gpt4o" | "gpt-4o" => LatencyProfile::gpt4o(),
-                // Claude family
-                "claude-opus" | "opus" => LatencyProfile::claude_opus(),
-                "claude-sonnet" | "sonnet" => LatencyProfile::claude_sonnet(),
-                "claude-haiku" | "haiku" => LatencyProfile::claude_haiku(),
-                // Gemini
-                "gemini" | "gemini-pro" => LatencyProfile::gemini_pro(),
-                "gemini-flash" => LatencyProfile::gemini_flash(),
-                // DeepSeek
-                "deepseek" | "deepseek-chat" => LatencyProfile::deepseek(),
-                "deepseek-reasoner" => LatencyProfile::deepseek_reasoner(),
-                // Special profiles
-                "instant" => LatencyProfile::instant(),
-                "fast" => LatencyProfile::fast(),
-                _ => LatencyProfile::gpt5(),
-            }
+            LatencyProfile::named(profile).unwrap_or_else(LatencyProfile::gpt5)
         } else if self.latency.ttft_mean_ms.is_some() || self.latency.tbt_mean_ms.is_some() {
             LatencyProfile::new(
                 self.latency.ttft_mean_ms.unwrap_or(600),
@@ -72,17 +329,203 @@ impl Config {
         }
     }
 
+    /// Latency profiles pinned to models by `[models.latency]`
+    pub fn model_latencies(&self) -> HashMap<String, LatencyProfile> {
+        self.models
+            .latency
+            .iter()
+            .filter_map(|(model, latency)| Some((model.clone(), latency.profile()?)))
+            .collect()
+    }
+
     /// Create an error config from the configuration
     pub fn error_config(&self) -> ErrorConfig {
         ErrorConfig {
             rate_limit_rate: self.errors.rate_limit_rate,
             server_error_rate: self.errors.server_error_rate,
             timeout_rate: self.errors.timeout_rate,
-            timeout_after_ms: self.errors.timeout_after_ms,
+            timeout_after_ms: (self.errors.timeout_after_ms as f64
+                * self.simulation.time_scale.max(0.0))
+            .round() as u64,
             invalid_request_rate: 0.0,
             auth_error_rate: 0.0,
         }
     }
+
+    /// How long a hung request is held before its 408, scaled by
+    /// `[simulation] time_scale`; none to hold it until the client gives up
+    pub fn hang_respond_after(&self) -> Option<Duration> {
+        self.errors
+            .hang_respond_after_ms
+            .map(|ms| Duration::from_millis(ms).mul_f64(self.simulation.time_scale.max(0.0)))
+    }
+
+    /// Error rates for the auxiliary endpoints, with `[errors]`'s timeout
+    pub fn auxiliary_error_config(&self) -> ErrorConfig {
+        ErrorConfig {
+            rate_limit_rate: self.auxiliary.rate_limit_rate,
+            server_error_rate: self.auxiliary.server_error_rate,
+            timeout_rate: self.auxiliary.timeout_rate,
+            ..self.error_config()
+        }
+    }
+
+    /// Delay before the auxiliary endpoints answer, if one is set and delays
+    /// are not disabled
+    pub fn auxiliary_latency(&self) -> Option<LatencyProfile> {
+        let mean = self.auxiliary.latency_mean_ms?;
+        (!self.simulation.disable_delays).then(|| {
+            LatencyProfile::new(mean, self.auxiliary.latency_stddev_ms, 0, 0)
+                .scaled(self.simulation.time_scale)
+        })
+    }
+
+    /// Create a tool call simulation config from the configuration
+    pub fn tool_call_config(&self) -> ToolCallConfig {
+        let config = ToolCallConfig::none()
+            .with_call_rate(self.tools.call_rate)
+            .with_count_weights(self.tools.parallel_weights.clone())
+            .with_arguments(self.tools.arguments);
+        match self.tools.arguments_seed {
+            Some(seed) => config.with_arguments_seed(seed),
+            None => config,
+        }
+    }
+
+    /// Create the load shedder from the configuration, if a limit is set
+    pub fn load_shedder(&self) -> Option<LoadShedder> {
+        let max_rps = self.capacity.max_rps?;
+        Some(LoadShedder::new(max_rps).with_retry_after(self.capacity.retry_after_secs))
+    }
+
+    /// Create the rate limiter from the configuration, if limits or a
+    /// `Retry-After` policy are set, or `[keys]` may give keys quotas
+    pub fn rate_limiter(&self) -> Option<RateLimiter> {
+        let limits = &self.rate_limits;
+        let mut limiter = RateLimiter::new();
+        if let Some(limit) = limits.requests_per_minute {
+            limiter = limiter.with_requests_per_minute(limit);
+        }
+        if let Some(limit) = limits.tokens_per_minute {
+            limiter = limiter.with_tokens_per_minute(limit);
+        }
+        if let Some(mode) = limits.retry_after {
+            let policy = match mode {
+                RetryAfterMode::Fixed => RetryAfterPolicy::fixed(limits.retry_after_secs),
+                RetryAfterMode::Exponential => RetryAfterPolicy::exponential(
+                    limits.retry_after_secs,
+                    limits.max_retry_after_secs,
+                ),
+            };
+            limiter = limiter.with_retry_after(policy.with_jitter(limits.retry_after_jitter));
+        }
+        (limiter.has_limits() || limiter.retry_after().is_some() || self.keys.enabled)
+            .then_some(limiter)
+    }
+
+    /// Create the tokenizer prompt and generated text are counted with
+    pub fn tokenizer(&self) -> Tokenizer {
+        let encoding = match self.tokens.encoding {
+            TokenEncoding::O200kBase => Encoding::O200kBase,
+            TokenEncoding::Cl100kBase => Encoding::Cl100kBase,
+            TokenEncoding::P50kBase => Encoding::P50kBase,
+            TokenEncoding::R50kBase => Encoding::R50kBase,
+            TokenEncoding::Estimate => {
+                return Tokenizer::Estimate {
+                    chars_per_token: self.tokens.chars_per_token,
+                }
+            }
+        };
+        Tokenizer::Bpe(encoding)
+    }
+
+    /// How model names map to encodings where a count is keyed by model
+    pub fn encoding_map(&self) -> EncodingMap {
+        self.tokens.models.iter().fold(
+            EncodingMap::new().with_fallback(self.tokens.fallback_encoding),
+            |map, (pattern, encoding)| map.with_model(pattern, *encoding),
+        )
+    }
+
+    /// Id formats and timestamp skew of the responses served
+    pub fn stamps(&self) -> Stamps {
+        let ids = IdFormats::new(&self.ids)
+            .inspect_err(|e| tracing::warn!("Not applying id formats: {}", e))
+            .unwrap_or_default();
+        Stamps::new(
+            ids,
+            ClockSkew::new(self.clock.skew_secs, self.clock.jitter_secs),
+        )
+    }
+
+    /// Create the system fingerprint schedule from the configuration
+    pub fn system_fingerprints(&self) -> SystemFingerprints {
+        let mut fingerprints = match self.fingerprint.rotate_every_secs {
+            Some(secs) => SystemFingerprints::rotating(
+                self.fingerprint.values.clone(),
+                Duration::from_secs(secs),
+            ),
+            None => SystemFingerprints {
+                values: self.fingerprint.values.clone(),
+                ..SystemFingerprints::default()
+            },
+        };
+        for (model, value) in &self.fingerprint.models {
+            fingerprints = fingerprints.with_model(model, value);
+        }
+        fingerprints
+    }
+
+    /// Price for a model: an exact `[cost.prices]` entry wins over the
+    /// built-in family table
+    pub fn model_price(&self, model: &str) -> Option<ModelPrice> {
+        self.cost
+            .prices
+            .get(model)
+            .copied()
+            .or_else(|| ModelPrice::for_model(model))
+    }
+
+    /// Configuration a region runs with: this one, with the region's
+    /// `latency` and `errors` in place of the top-level sections
+    pub fn for_region(&self, name: &str) -> Option<Config> {
+        let region = self.regions.get(name)?;
+        let mut config = self.clone();
+        config.regions.clear();
+        if let Some(port) = region.port {
+            config.server.port = port;
+        }
+        if let Some(latency) = &region.latency {
+            config.latency = latency.clone();
+        }
+        if let Some(errors) = &region.errors {
+            config.errors = errors.clone();
+        }
+        if let Some(clock) = &region.clock {
+            config.clock = clock.clone();
+        }
+        Some(config)
+    }
+
+    /// Tool catalogs of the `[mcp.servers]`
+    pub fn mcp_catalogs(&self) -> McpCatalogs {
+        McpCatalogs::new(
+            self.mcp
+                .servers
+                .iter()
+                .map(|(label, server)| (label.clone(), server.tools.clone()))
+                .collect(),
+        )
+    }
+
+    /// Model name served on the `/vllm/...` routes
+    pub fn vllm_served_model(&self) -> String {
+        self.vllm
+            .served_model_name
+            .clone()
+            .or_else(|| self.models.available.first().cloned())
+            .unwrap_or_else(|| "default".to_string())
+    }
 }
 
 /// Server network configuration
@@ -95,6 +538,9 @@ pub struct ServerConfig {
     /// Maximum number of active WebSocket connections allowed
     #[serde(default = "default_max_websocket_connections")]
     pub max_websocket_connections: u64,
+    /// Seconds the reported requests-per-second is averaged over
+    #[serde(default = "default_rps_window_secs")]
+    pub rps_window_secs: u64,
 }
 
 fn default_port() -> u16 {
@@ -109,16 +555,33 @@ fn default_max_websocket_connections() -> u64 {
     100
 }
 
+fn default_rps_window_secs() -> u64 {
+    crate::stats::DEFAULT_RPS_WINDOW_SECS
+}
+
 impl Default for ServerConfig {
     fn default() -> Self {
         Self {
             port: default_port(),
             host: default_host(),
             max_websocket_connections: default_max_websocket_connections(),
+            rps_window_secs: default_rps_window_secs(),
         }
     }
 }
 
+/// Sizing of the tokio runtime behind `llmsim serve`; unset values keep
+/// tokio's defaults
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RuntimeConfig {
+    /// Threads running tasks; default: one per CPU core
+    #[serde(default)]
+    pub worker_threads: Option<usize>,
+    /// Most threads kept for blocking work such as file reads; default: 512
+    #[serde(default)]
+    pub max_blocking_threads: Option<usize>,
+}
+
 /// Latency simulation configuration
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct LatencyConfig {
@@ -132,12 +595,20 @@ pub struct LatencyConfig {
     pub tbt_mean_ms: Option<u64>,
     /// Custom time between tokens stddev (ms)
     pub tbt_stddev_ms: Option<u64>,
+    /// Latency model registered by name with `AppState::with_latency_models`,
+    /// sampled instead of the profile
+    pub model: Option<String>,
+    /// Time the timer at startup, warn when it cannot sleep as briefly as
+    /// the time between tokens asks, and make up for how late it fires
+    #[serde(default)]
+    pub calibrate: bool,
 }
 
 /// Response generation configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ResponseConfig {
-    /// Generator type: "lorem", "echo", "random", "sequence", "fixed:..."
+    /// Generator type: "lorem", "echo", "echo:context", "random", "sequence",
+    /// "cutoff", "fixed:..."
     #[serde(default = "default_generator")]
     pub generator: String,
     /// Target number of tokens in response
@@ -148,6 +619,27 @@ pub struct ResponseConfig {
     /// `generator`. See `specs/scripted-mode.md`.
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub script_path: Option<String>,
+    /// Chance (0.0-1.0) that a Responses API answer is cut short and
+    /// reported as `status: incomplete`
+    #[serde(default)]
+    pub truncation_rate: f64,
+    /// Chance (0.0-1.0) that a Responses API answer is a `refusal` content
+    /// part instead of generated text
+    #[serde(default)]
+    pub refusal_rate: f64,
+    /// Chance (0.0-1.0) that each word of a Responses answer carries a fake
+    /// `url_citation` or `file_citation` annotation
+    #[serde(default)]
+    pub citation_rate: f64,
+    /// Let request `temperature` and `top_p` shape generated text:
+    /// temperature 0 repeats per system fingerprint, higher temperatures
+    /// vary the length more
+    #[serde(default)]
+    pub temperature_effects: bool,
+    /// Chance (0.0-1.0) that a request with `stop` sequences has its answer
+    /// end at one of them partway through
+    #[serde(default)]
+    pub stop_hit_rate: f64,
 }
 
 fn default_generator() -> String {
@@ -164,6 +656,11 @@ impl Default for ResponseConfig {
             generator: default_generator(),
             target_tokens: default_target_tokens(),
             script_path: None,
+            truncation_rate: 0.0,
+            refusal_rate: 0.0,
+            citation_rate: 0.0,
+            temperature_effects: false,
+            stop_hit_rate: 0.0,
         }
     }
 }
@@ -183,6 +680,28 @@ pub struct ErrorsConfig {
     /// Milliseconds before timeout (default 30000)
     #[serde(default = "default_timeout")]
     pub timeout_after_ms: u64,
+    /// Rate of requests accepted and then never answered, until the client
+    /// gives up (0.0-1.0)
+    #[serde(default)]
+    pub hang_rate: f64,
+    /// Answer a hung request with 408 after this many milliseconds, instead
+    /// of holding it until the client disconnects
+    #[serde(default)]
+    pub hang_respond_after_ms: Option<u64>,
+    /// Rate of requests answered by a simulated CDN in front of the API,
+    /// with an HTML, plain-text or empty error page (0.0-1.0)
+    #[serde(default)]
+    pub cdn_error_rate: f64,
+    /// CDN error pages to draw from; every kind if empty
+    #[serde(default)]
+    pub cdn_errors: Vec<CdnError>,
+    /// Errors for requests with matching content, checked in order before
+    /// the rates above
+    #[serde(default)]
+    pub rules: Vec<ErrorRule>,
+    /// Non-standard error responses by name, for rules to answer with
+    #[serde(default)]
+    pub responses: BTreeMap<String, ErrorTemplate>,
 }
 
 fn default_timeout() -> u64 {
@@ -195,6 +714,14 @@ pub struct ModelsConfig {
     /// List of available model IDs
     #[serde(default = "default_models")]
     pub available: Vec<String>,
+    /// Latency per model id, a profile name or explicit timing, overriding
+    /// the model registry and the guess from the model's name
+    #[serde(default)]
+    pub latency: BTreeMap<String, ModelLatency>,
+    /// Refuse tools, JSON mode and image inputs sent to a known model that
+    /// lacks them, with the API's error
+    #[serde(default)]
+    pub enforce_capabilities: bool,
 }
 
 fn default_models() -> Vec<String> {
@@ -272,94 +799,1784 @@ impl Default for ModelsConfig {
     fn default() -> Self {
         Self {
             available: default_models(),
+            latency: BTreeMap::new(),
+            enforce_capabilities: false,
         }
     }
 }
 
-/// Configuration errors
-#[derive(Debug, thiserror::Error)]
-pub enum ConfigError {
-    #[error("Failed to read configuration file: {0}")]
-    Io(String),
-    #[error("Failed to parse configuration: {0}")]
-    Parse(String),
-    #[error("Invalid configuration: {0}")]
-    Validation(String),
+/// vLLM compatibility configuration for the `/vllm/...` routes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VllmConfig {
+    /// Model name the simulated vLLM server serves (`--served-model-name`).
+    /// Defaults to the first entry of `models.available`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub served_model_name: Option<String>,
+    /// Batch capacity used to split in-flight requests into running and
+    /// waiting in `/vllm/metrics` (vLLM `--max-num-seqs`)
+    #[serde(default = "default_max_num_seqs")]
+    pub max_num_seqs: u64,
+    /// Attach running usage to every streamed chunk, as if each request
+    /// set `stream_options.continuous_usage_stats`
+    #[serde(default)]
+    pub continuous_usage_stats: bool,
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+fn default_max_num_seqs() -> u64 {
+    256
+}
 
-    #[test]
-    fn test_default_config() {
-        let config = Config::default();
-        assert_eq!(config.server.port, 8080);
-        assert_eq!(config.server.host, "0.0.0.0");
-        assert_eq!(config.response.generator, "lorem");
-        assert_eq!(config.response.target_tokens, 100);
+impl Default for VllmConfig {
+    fn default() -> Self {
+        Self {
+            served_model_name: None,
+            max_num_seqs: default_max_num_seqs(),
+            continuous_usage_stats: false,
+        }
     }
+}
 
-    #[test]
-    fn test_parse_toml() {
-        let toml_str = r#"
-[server]
-port = 9000
-host = "127.0.0.1"
+/// Simulated billing configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CostConfig {
+    /// Emit the cost header on every priced response
+    #[serde(default)]
+    pub enabled: bool,
+    /// Header carrying the request cost in USD (e.g. `x-litellm-response-cost`)
+    #[serde(default = "default_cost_header")]
+    pub header: String,
+    /// Per-model price overrides, keyed by exact model ID
+    #[serde(default)]
+    pub prices: HashMap<String, ModelPrice>,
+}
 
-[latency]
-profile = "gpt4"
+fn default_cost_header() -> String {
+    "x-llmsim-response-cost".to_string()
+}
 
-[response]
-generator = "echo"
-target_tokens = 50
+impl Default for CostConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            header: default_cost_header(),
+            prices: HashMap::new(),
+        }
+    }
+}
 
-[errors]
-rate_limit_rate = 0.01
-"#;
-        let config = Config::from_toml(toml_str).unwrap();
-        assert_eq!(config.server.port, 9000);
-        assert_eq!(config.server.host, "127.0.0.1");
-        assert_eq!(config.latency.profile, Some("gpt4".to_string()));
-        assert_eq!(config.response.generator, "echo");
-        assert_eq!(config.response.target_tokens, 50);
-        assert_eq!(config.errors.rate_limit_rate, 0.01);
+/// SSE transport configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StreamConfig {
+    /// How SSE body frames fall relative to UTF-8 character boundaries
+    #[serde(default)]
+    pub utf8_boundaries: Utf8Boundaries,
+    /// Hold SSE output until this many bytes are pending, like a reverse
+    /// proxy with response buffering left on
+    #[serde(default)]
+    pub buffer_bytes: Option<usize>,
+    /// Flush held SSE output this long after the first pending byte
+    #[serde(default)]
+    pub buffer_interval_ms: Option<u64>,
+    /// Validate every emitted chat chunk and Responses event against the
+    /// bundled JSON Schemas, ending the stream with an error on drift
+    #[serde(default)]
+    pub strict_schema: bool,
+    /// Repeat `response.in_progress` with the usage so far after every this
+    /// many Responses text deltas
+    #[serde(default)]
+    pub usage_updates_every: Option<u32>,
+    /// Events generated ahead of a client that reads slowly; generation
+    /// waits once this many are unread
+    #[serde(default = "default_pending_events")]
+    pub pending_events: usize,
+    /// Chance (0.0-1.0) that an SSE body frame is sent malformed
+    #[serde(default)]
+    pub malformed_rate: f64,
+    /// Malformations to draw from; every kind if empty
+    #[serde(default)]
+    pub malformed_kinds: Vec<MalformedFrame>,
+    /// Chance (0.0-1.0) that a delta event is sent twice
+    #[serde(default)]
+    pub duplicate_rate: f64,
+    /// Chance (0.0-1.0) that a delta event is swapped with the delta after it
+    #[serde(default)]
+    pub reorder_rate: f64,
+    /// Give every SSE event an `id:` line and resume a stream after the
+    /// event a reconnecting client names in `Last-Event-ID`
+    #[serde(default)]
+    pub event_ids: bool,
+    /// Reconnection delay sent first on every SSE stream as `retry:`
+    #[serde(default)]
+    pub retry_ms: Option<u64>,
+    /// Most recent numbered streams kept for resumption
+    #[serde(default = "default_resumable_streams")]
+    pub resumable_streams: usize,
+    /// Exact bytes of selected streams written to files
+    #[serde(default)]
+    pub tee: TeeConfig,
+    /// End a generated stream as cut short (`length`, `response.incomplete`
+    /// or `max_tokens`) once it has spent this long waiting out latency
+    #[serde(default)]
+    pub max_duration_secs: Option<u64>,
+}
+
+fn default_resumable_streams() -> usize {
+    256
+}
+
+fn default_pending_events() -> usize {
+    64
+}
+
+impl Default for StreamConfig {
+    fn default() -> Self {
+        Self {
+            utf8_boundaries: Utf8Boundaries::default(),
+            buffer_bytes: None,
+            buffer_interval_ms: None,
+            strict_schema: false,
+            usage_updates_every: None,
+            pending_events: default_pending_events(),
+            malformed_rate: 0.0,
+            malformed_kinds: Vec::new(),
+            duplicate_rate: 0.0,
+            reorder_rate: 0.0,
+            event_ids: false,
+            retry_ms: None,
+            resumable_streams: default_resumable_streams(),
+            tee: TeeConfig::default(),
+            max_duration_secs: None,
+        }
     }
+}
 
-    #[test]
-    fn test_custom_latency() {
-        let toml_str = r#"
-[latency]
-ttft_mean_ms = 500
-tbt_mean_ms = 25
-"#;
-        let config = Config::from_toml(toml_str).unwrap();
-        let profile = config.latency_profile();
-        assert_eq!(profile.ttft_mean_ms, 500);
-        assert_eq!(profile.tbt_mean_ms, 25);
+impl StreamConfig {
+    /// Whether SSE output is held back and flushed in batches
+    pub fn is_buffered(&self) -> bool {
+        self.buffer_bytes.is_some() || self.buffer_interval_ms.is_some()
     }
 
-    #[test]
-    fn test_latency_profile_from_name() {
-        let toml_str = r#"
-[latency]
-profile = "instant"
-"#;
-        let config = Config::from_toml(toml_str).unwrap();
-        let profile = config.latency_profile();
-        assert_eq!(profile.ttft_mean_ms, 0);
+    /// Longest a generated stream may run, if capped
+    pub fn max_duration(&self) -> Option<Duration> {
+        self.max_duration_secs.map(Duration::from_secs)
     }
+}
 
-    #[test]
-    fn test_error_config() {
-        let toml_str = r#"
-[errors]
-rate_limit_rate = 0.1
-server_error_rate = 0.05
-"#;
-        let config = Config::from_toml(toml_str).unwrap();
-        let error_config = config.error_config();
-        assert_eq!(error_config.rate_limit_rate, 0.1);
-        assert_eq!(error_config.server_error_rate, 0.05);
+/// SSE responses whose bytes are written to files as sent, frame by frame
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TeeConfig {
+    /// Directory captures are written to; nothing is captured when unset
+    #[serde(default)]
+    pub dir: Option<String>,
+    /// Chance (0.0-1.0) that any stream is captured
+    #[serde(default)]
+    pub sample_rate: f64,
+    /// `x-request-id` values, or globs of them, whose streams are always
+    /// captured
+    #[serde(default)]
+    pub request_ids: Vec<String>,
+}
+
+/// Placement of HTTP body frame boundaries relative to UTF-8 characters
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Utf8Boundaries {
+    /// Every body frame is a whole SSE event, so no frame ends inside a
+    /// character
+    #[default]
+    Preserve,
+    /// Cut every multi-byte character across two body frames, to exercise
+    /// client-side incremental UTF-8 decoding
+    Split,
+}
+
+/// An error page from an intermediary in front of the API, never the API's
+/// own JSON
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CdnError {
+    /// 502 Bad Gateway as an HTML page
+    #[serde(rename = "html_502")]
+    Html502,
+    /// Cloudflare's 520 "Web server is returning an unknown error" page
+    #[serde(rename = "html_520")]
+    Html520,
+    /// Cloudflare's 524 "A timeout occurred" page
+    #[serde(rename = "html_524")]
+    Html524,
+    /// 502 with a load balancer's plain-text reason
+    #[serde(rename = "text_502")]
+    Text502,
+    /// 502 with no body
+    #[serde(rename = "empty_502")]
+    Empty502,
+    /// 520 with no body
+    #[serde(rename = "empty_520")]
+    Empty520,
+    /// 524 with no body
+    #[serde(rename = "empty_524")]
+    Empty524,
+}
+
+impl CdnError {
+    /// Every kind of page
+    pub const ALL: [CdnError; 7] = [
+        CdnError::Html502,
+        CdnError::Html520,
+        CdnError::Html524,
+        CdnError::Text502,
+        CdnError::Empty502,
+        CdnError::Empty520,
+        CdnError::Empty524,
+    ];
+}
+
+/// A way of breaking an SSE body frame, for negative testing of stream
+/// parsers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MalformedFrame {
+    /// An event ends with one newline instead of a blank line, running into
+    /// the next event
+    MissingSeparator,
+    /// The first field name is cut across two body frames (`da` + `ta: ...`)
+    SplitPrefix,
+    /// A `: comment` line before the frame's first event
+    StrayComment,
+    /// `data: [DONE]` sent twice
+    DuplicateDone,
+}
+
+/// HTTP response compression, negotiated from `Accept-Encoding`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompressionConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_true")]
+    pub gzip: bool,
+    #[serde(default = "default_true")]
+    pub br: bool,
+    /// Content types (prefix match) that get compressed
+    #[serde(default = "default_compressed_types")]
+    pub content_types: Vec<String>,
+    /// Bodies smaller than this are sent as-is
+    #[serde(default = "default_compression_min_size")]
+    pub min_size_bytes: u64,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_compressed_types() -> Vec<String> {
+    vec!["application/json".to_string(), "text/plain".to_string()]
+}
+
+fn default_compression_min_size() -> u64 {
+    32
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            gzip: true,
+            br: true,
+            content_types: default_compressed_types(),
+            min_size_bytes: default_compression_min_size(),
+        }
+    }
+}
+
+impl CompressionConfig {
+    /// Whether a response with this content type is compressed.
+    ///
+    /// SSE is never compressed, even if listed: the encoder holds output back
+    /// until its block fills, which would buffer the stream and destroy the
+    /// token timing the simulator exists to produce.
+    pub fn compresses(&self, content_type: &str) -> bool {
+        if content_type.starts_with("text/event-stream") {
+            return false;
+        }
+        self.content_types
+            .iter()
+            .any(|t| content_type.starts_with(t.as_str()))
+    }
+}
+
+/// HTTP/1.1 body framing of responses, to reproduce client bugs that only
+/// show with one provider's framing
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FramingConfig {
+    /// How non-streaming bodies are delimited
+    #[serde(default)]
+    pub body: BodyFraming,
+    /// Largest chunk of a body sent with `body = "chunked"`; the whole body
+    /// in one chunk if unset
+    #[serde(default)]
+    pub chunk_bytes: Option<usize>,
+    /// End chunked responses, streams included, with trailer fields; only
+    /// sent to clients that ask with `TE: trailers`
+    #[serde(default)]
+    pub trailers: bool,
+}
+
+impl FramingConfig {
+    /// Whether responses are reframed at all
+    pub fn is_active(&self) -> bool {
+        self.body != BodyFraming::Auto || self.trailers
+    }
+}
+
+/// Variations in how JSON response bodies are labelled and padded, which
+/// differ between providers, to catch client parsers that accept only one
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct JsonBodyConfig {
+    /// `Content-Type` of JSON responses
+    #[serde(default)]
+    pub content_type: JsonContentType,
+    /// Share (0.0-1.0) of JSON bodies starting with a UTF-8 byte order mark
+    #[serde(default)]
+    pub bom_rate: f64,
+    /// Share (0.0-1.0) of JSON bodies ending with a newline
+    #[serde(default)]
+    pub trailing_newline_rate: f64,
+}
+
+impl JsonBodyConfig {
+    /// Whether JSON bodies are changed at all
+    pub fn is_active(&self) -> bool {
+        self.content_type != JsonContentType::Bare
+            || self.bom_rate > 0.0
+            || self.trailing_newline_rate > 0.0
+    }
+}
+
+/// `Content-Type` of a JSON response
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JsonContentType {
+    /// `application/json`, as produced
+    #[default]
+    Bare,
+    /// `application/json; charset=utf-8`
+    Charset,
+    /// Either, at random for each response
+    Mixed,
+}
+
+/// Framing of a non-streaming response body
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BodyFraming {
+    /// `Content-Length` when the size is known up front, as for plain JSON,
+    /// otherwise chunked (e.g. compressed bodies)
+    #[default]
+    Auto,
+    /// Always `Transfer-Encoding: chunked`, without `Content-Length`
+    Chunked,
+    /// Always `Content-Length`, buffering bodies whose size is not known
+    ContentLength,
+}
+
+/// A warm pool of pre-generated chat completions, for benchmarks where the
+/// simulator's own generation and tokenization would be the bottleneck
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PoolConfig {
+    /// Serve chat completions from the pool
+    #[serde(default)]
+    pub enabled: bool,
+    /// Responses generated at startup
+    #[serde(default = "default_pool_size")]
+    pub size: usize,
+}
+
+fn default_pool_size() -> usize {
+    64
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            size: default_pool_size(),
+        }
+    }
+}
+
+/// Simulated tool calls in generated chat completions
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolsConfig {
+    /// Chance (0.0-1.0) that a request offering tools gets tool calls back
+    /// instead of text when it leaves the choice to the model
+    #[serde(default)]
+    pub call_rate: f64,
+    /// Relative weights of 1, 2, 3, ... parallel calls per turn, used when
+    /// the request allows parallel tool calls
+    #[serde(default = "default_parallel_weights")]
+    pub parallel_weights: Vec<f64>,
+    /// Call arguments: `empty` for `{}`, or `schema` for values faked from
+    /// each tool's JSON Schema
+    #[serde(default)]
+    pub arguments: ToolArguments,
+    /// Seed for `schema` arguments, giving each tool the same arguments on
+    /// every call
+    #[serde(default)]
+    pub arguments_seed: Option<u64>,
+}
+
+fn default_parallel_weights() -> Vec<f64> {
+    vec![1.0]
+}
+
+impl Default for ToolsConfig {
+    fn default() -> Self {
+        Self {
+            call_rate: 0.0,
+            parallel_weights: default_parallel_weights(),
+            arguments: ToolArguments::Empty,
+            arguments_seed: None,
+        }
+    }
+}
+
+/// Provider-side load shedding
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CapacityConfig {
+    /// Requests per second admitted before the rest get 503; unlimited if unset
+    #[serde(default)]
+    pub max_rps: Option<u32>,
+    /// `Retry-After` seconds sent with shed requests
+    #[serde(default = "default_retry_after_secs")]
+    pub retry_after_secs: u32,
+}
+
+fn default_retry_after_secs() -> u32 {
+    1
+}
+
+impl Default for CapacityConfig {
+    fn default() -> Self {
+        Self {
+            max_rps: None,
+            retry_after_secs: default_retry_after_secs(),
+        }
+    }
+}
+
+/// Per-API-key rate limits and the `Retry-After` of 429s
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RateLimitsConfig {
+    /// Requests per minute each key may send; unlimited if unset
+    #[serde(default)]
+    pub requests_per_minute: Option<u32>,
+    /// Tokens (prompt plus requested maximum output) per minute each key may
+    /// use; unlimited if unset
+    #[serde(default)]
+    pub tokens_per_minute: Option<u32>,
+    /// How `Retry-After` is chosen for every 429: `fixed` or `exponential`
+    /// in the key's consecutive 429s; unset keeps each error's own
+    #[serde(default)]
+    pub retry_after: Option<RetryAfterMode>,
+    /// Fixed `Retry-After`, or the first step of the exponential one
+    #[serde(default = "default_retry_after_secs")]
+    pub retry_after_secs: u32,
+    /// Cap on the exponential `Retry-After`
+    #[serde(default = "default_max_retry_after_secs")]
+    pub max_retry_after_secs: u32,
+    /// Share (0.0-1.0) `Retry-After` may move up or down at random
+    #[serde(default)]
+    pub retry_after_jitter: f64,
+}
+
+fn default_max_retry_after_secs() -> u32 {
+    60
+}
+
+impl Default for RateLimitsConfig {
+    fn default() -> Self {
+        Self {
+            requests_per_minute: None,
+            tokens_per_minute: None,
+            retry_after: None,
+            retry_after_secs: default_retry_after_secs(),
+            max_retry_after_secs: default_max_retry_after_secs(),
+            retry_after_jitter: 0.0,
+        }
+    }
+}
+
+/// Latency and errors for the endpoints that generate nothing: model listing
+/// and lookup, stored responses, vLLM metrics
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AuxiliaryConfig {
+    /// Mean delay before answering (ms); none if unset
+    #[serde(default)]
+    pub latency_mean_ms: Option<u64>,
+    /// Standard deviation of the delay (ms)
+    #[serde(default)]
+    pub latency_stddev_ms: u64,
+    /// Rate of 429 rate limit errors (0.0-1.0)
+    #[serde(default)]
+    pub rate_limit_rate: f64,
+    /// Rate of 500/503 server errors (0.0-1.0)
+    #[serde(default)]
+    pub server_error_rate: f64,
+    /// Rate of 504 timeout errors (0.0-1.0)
+    #[serde(default)]
+    pub timeout_rate: f64,
+    /// Path globs (e.g. `/openai/v1/models*`) the section applies to; every
+    /// auxiliary endpoint when empty
+    #[serde(default)]
+    pub paths: Vec<String>,
+}
+
+impl AuxiliaryConfig {
+    /// Whether the section asks for any latency or error
+    pub fn is_active(&self) -> bool {
+        self.latency_mean_ms.is_some()
+            || self.rate_limit_rate > 0.0
+            || self.server_error_rate > 0.0
+            || self.timeout_rate > 0.0
+    }
+}
+
+/// Settings of the `serve --tui` dashboard
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TuiConfig {
+    /// Thresholds that raise alarms on the dashboard
+    #[serde(default)]
+    pub alerts: AlertsConfig,
+}
+
+/// Dashboard alarm thresholds; an unset threshold never alarms
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AlertsConfig {
+    /// Errors as a percentage of all requests
+    #[serde(default)]
+    pub error_rate_percent: Option<f64>,
+    /// 99th percentile latency (ms)
+    #[serde(default)]
+    pub p99_latency_ms: Option<f64>,
+    /// Requests in flight
+    #[serde(default)]
+    pub active_requests: Option<u64>,
+}
+
+impl AlertsConfig {
+    /// Whether any threshold is set
+    pub fn is_active(&self) -> bool {
+        self.error_rate_percent.is_some()
+            || self.p99_latency_ms.is_some()
+            || self.active_requests.is_some()
+    }
+}
+
+/// Switches that apply across the whole simulation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SimulationConfig {
+    /// Serve everything with the instant latency profile, whatever
+    /// `[latency]` or the requested model would use
+    #[serde(default)]
+    pub disable_delays: bool,
+    /// Factor applied to every sampled TTFT, inter-token gap and timeout
+    /// (0.1 is ten times faster, 1.0 is real time)
+    #[serde(default = "default_time_scale")]
+    pub time_scale: f64,
+}
+
+fn default_time_scale() -> f64 {
+    1.0
+}
+
+impl Default for SimulationConfig {
+    fn default() -> Self {
+        Self {
+            disable_delays: false,
+            time_scale: default_time_scale(),
+        }
+    }
+}
+
+/// How prompt and generated text are counted
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TokensConfig {
+    #[serde(default)]
+    pub encoding: TokenEncoding,
+    /// Characters per token for `encoding = "estimate"`
+    #[serde(default = "default_chars_per_token")]
+    pub chars_per_token: f64,
+    /// Encoding of models `models` and the built-in families do not cover,
+    /// where a count is keyed by model
+    #[serde(default = "default_fallback_encoding")]
+    pub fallback_encoding: Encoding,
+    /// Encodings of custom model names by glob (`"acme-*" = "o200k_base"`),
+    /// ahead of the built-in families
+    #[serde(default)]
+    pub models: BTreeMap<String, Encoding>,
+}
+
+fn default_fallback_encoding() -> Encoding {
+    EncodingMap::default().fallback()
+}
+
+fn default_chars_per_token() -> f64 {
+    Tokenizer::DEFAULT_CHARS_PER_TOKEN
+}
+
+impl Default for TokensConfig {
+    fn default() -> Self {
+        Self {
+            encoding: TokenEncoding::default(),
+            chars_per_token: default_chars_per_token(),
+            fallback_encoding: default_fallback_encoding(),
+            models: BTreeMap::new(),
+        }
+    }
+}
+
+/// Encoding every count uses, whatever the requested model
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenEncoding {
+    O200kBase,
+    #[default]
+    Cl100kBase,
+    P50kBase,
+    R50kBase,
+    /// `chars_per_token` instead of a BPE; cheapest at high request rates
+    Estimate,
+}
+
+/// `system_fingerprint` values reported on chat completions
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FingerprintConfig {
+    /// Fingerprints to report; only the first is used unless rotating
+    #[serde(default = "default_fingerprint_values")]
+    pub values: Vec<String>,
+    /// Move to the next value every this many seconds (wall clock)
+    #[serde(default)]
+    pub rotate_every_secs: Option<u64>,
+    /// Fixed fingerprint per model name, overriding `values`
+    #[serde(default)]
+    pub models: HashMap<String, String>,
+}
+
+fn default_fingerprint_values() -> Vec<String> {
+    vec![DEFAULT_SYSTEM_FINGERPRINT.to_string()]
+}
+
+impl Default for FingerprintConfig {
+    fn default() -> Self {
+        Self {
+            values: default_fingerprint_values(),
+            rotate_every_secs: None,
+            models: HashMap::new(),
+        }
+    }
+}
+
+/// One logical region: its own listener or path prefix, latency baseline,
+/// error rates and stats
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RegionConfig {
+    /// Serve the region on its own port, at the usual paths
+    #[serde(default)]
+    pub port: Option<u16>,
+    /// Mount the region on the main listener under this prefix (default
+    /// `/<name>`); ignored when `port` is set
+    #[serde(default)]
+    pub prefix: Option<String>,
+    /// Replaces `[latency]` for this region
+    #[serde(default)]
+    pub latency: Option<LatencyConfig>,
+    /// Replaces `[errors]` for this region
+    #[serde(default)]
+    pub errors: Option<ErrorsConfig>,
+    /// Replaces `[clock]` for this region
+    #[serde(default)]
+    pub clock: Option<ClockConfig>,
+}
+
+impl RegionConfig {
+    /// Path the region is nested under on the main listener, or `None` when
+    /// it has a port of its own
+    pub fn mount_path(&self, name: &str) -> Option<String> {
+        if self.port.is_some() {
+            return None;
+        }
+        let prefix = self.prefix.as_deref().unwrap_or(name).trim_matches('/');
+        Some(format!("/{}", prefix))
+    }
+}
+
+/// A real provider API requests can be forwarded to
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpstreamConfig {
+    /// `http(s)://host[:port][/path]`; `https://` needs the `tls` feature.
+    /// Request paths are appended without llmsim's provider
+    /// prefix, e.g. `/v1/chat/completions`.
+    pub base_url: String,
+    /// Sent instead of the client's credentials when set
+    #[serde(default)]
+    pub api_key: Option<String>,
+    #[serde(default = "default_upstream_timeout_secs")]
+    pub timeout_secs: u64,
+}
+
+fn default_upstream_timeout_secs() -> u64 {
+    60
+}
+
+/// Shadow traffic: copies of incoming requests sent to `[upstreams]`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MirrorConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Share (0.0-1.0) of requests that are mirrored
+    #[serde(default = "default_mirror_sample_rate")]
+    pub sample_rate: f64,
+}
+
+fn default_mirror_sample_rate() -> f64 {
+    1.0
+}
+
+impl Default for MirrorConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            sample_rate: default_mirror_sample_rate(),
+        }
+    }
+}
+
+/// Hybrid mode: requests matching a rule go to the real API in `[upstreams]`,
+/// everything else is simulated
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProxyConfig {
+    #[serde(default)]
+    pub rules: Vec<ProxyRule>,
+}
+
+/// Conditions for proxying a request. Every condition that is set must
+/// hold; a rule with none matches every request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProxyRule {
+    /// Glob over the requested model, e.g. `gpt-4*` (`*` and `?`)
+    #[serde(default)]
+    pub model: Option<String>,
+    /// Request header that must be present
+    #[serde(default)]
+    pub header: Option<String>,
+    /// Value `header` must have; any value when unset
+    #[serde(default)]
+    pub header_value: Option<String>,
+    /// Share (0.0-1.0) of matching requests that are proxied
+    #[serde(default = "default_proxy_rate")]
+    pub rate: f64,
+}
+
+fn default_proxy_rate() -> f64 {
+    1.0
+}
+
+/// An error for requests matching its conditions. Every condition that is
+/// set must hold; a rule with none matches every request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ErrorRule {
+    /// Glob over the requested model, e.g. `gpt-4o*` (`*` and `?`)
+    #[serde(default)]
+    pub model: Option<String>,
+    /// Text the request's messages (or input, prompt, instructions) must
+    /// contain
+    #[serde(default)]
+    pub contains: Option<String>,
+    /// Regular expression the request's message text must match
+    #[serde(default)]
+    pub matches: Option<String>,
+    /// Request header that must be present
+    #[serde(default)]
+    pub header: Option<String>,
+    /// Value `header` must have; any value when unset
+    #[serde(default)]
+    pub header_value: Option<String>,
+    /// Fewest prompt tokens a matching request has
+    #[serde(default)]
+    pub min_prompt_tokens: Option<u64>,
+    /// Most prompt tokens a matching request has
+    #[serde(default)]
+    pub max_prompt_tokens: Option<u64>,
+    /// Status to answer with: 400, 401, 404, 429, 500, 503 or 504
+    #[serde(default)]
+    pub status: Option<u16>,
+    /// Name of an `[errors.responses]` entry to answer with, in place of
+    /// `status`
+    #[serde(default)]
+    pub response: Option<String>,
+    /// Error message, for 400
+    #[serde(default)]
+    pub message: Option<String>,
+    /// `Retry-After` of a 429 (default 1)
+    #[serde(default)]
+    pub retry_after_seconds: Option<u32>,
+    /// Share (0.0-1.0) of matching requests that fail
+    #[serde(default = "default_proxy_rate")]
+    pub rate: f64,
+}
+
+/// An error response sent exactly as written, e.g. a CDN's HTML error page.
+/// `{model}`, `{status}` and `{timestamp}` in the body and header values are
+/// replaced with the request's model, the status and the Unix time.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ErrorTemplate {
+    /// Any 4xx or 5xx status, e.g. 520
+    pub status: u16,
+    /// Response headers; `content-type` defaults to `application/json` for a
+    /// table body and `text/plain` for a string
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    /// A string sent as is, or a table sent as JSON; no body when unset
+    #[serde(default, skip_serializing_if = "serde_json::Value::is_null")]
+    pub body: serde_json::Value,
+}
+
+/// Recording and replay of learned per-model profiles
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LearnedConfig {
+    /// Learn latency and response lengths from proxied and mirrored
+    /// responses and save them to this file
+    #[serde(default)]
+    pub record: Option<String>,
+    /// Seconds between saves while recording
+    #[serde(default = "default_save_interval_secs")]
+    pub save_interval_secs: u64,
+    /// Simulate observed models with the profiles saved in this file
+    #[serde(default)]
+    pub replay: Option<String>,
+}
+
+fn default_save_interval_secs() -> u64 {
+    30
+}
+
+impl Default for LearnedConfig {
+    fn default() -> Self {
+        Self {
+            record: None,
+            save_interval_secs: default_save_interval_secs(),
+            replay: None,
+        }
+    }
+}
+
+/// Generated artifacts (images, tool outputs) served under `/llmsim/files`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FilesConfig {
+    /// Scheme and authority for file URLs, e.g. `https://sim.example.com`;
+    /// by default taken from the request's `Host` header
+    #[serde(default)]
+    pub base_url: Option<String>,
+    /// Bytes kept in memory before the oldest files are dropped
+    #[serde(default = "default_files_max_bytes")]
+    pub max_bytes: usize,
+}
+
+fn default_files_max_bytes() -> usize {
+    256 * 1024 * 1024
+}
+
+impl Default for FilesConfig {
+    fn default() -> Self {
+        Self {
+            base_url: None,
+            max_bytes: default_files_max_bytes(),
+        }
+    }
+}
+
+/// Responses API responses kept for `GET /openai/v1/responses`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoreConfig {
+    /// Responses kept in memory before the oldest are dropped
+    #[serde(default = "default_store_max_responses")]
+    pub max_responses: usize,
+    /// Chat completions created with `store: true` kept in memory before
+    /// the oldest are dropped
+    #[serde(default = "default_store_max_responses")]
+    pub max_completions: usize,
+    /// Context window conversations chained with `previous_response_id` are
+    /// held to, in place of the model's; lets tests reach it in a few turns
+    #[serde(default)]
+    pub context_window: Option<u32>,
+}
+
+fn default_store_max_responses() -> usize {
+    10_000
+}
+
+impl Default for StoreConfig {
+    fn default() -> Self {
+        Self {
+            max_responses: default_store_max_responses(),
+            max_completions: default_store_max_responses(),
+            context_window: None,
+        }
+    }
+}
+
+/// Skew of the `created`/`created_at` timestamps on responses from the real
+/// time, as a provider with a drifting clock stamps them
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ClockConfig {
+    /// Seconds added to every timestamp; negative for a clock running behind
+    #[serde(default)]
+    pub skew_secs: i64,
+    /// Most seconds each timestamp strays, at random, either side of the skew
+    #[serde(default)]
+    pub jitter_secs: u64,
+}
+
+/// Summaries of the most recent requests, served at `/llmsim/requests`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RequestLogConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Requests kept before the oldest are dropped
+    #[serde(default = "default_request_log_capacity")]
+    pub capacity: usize,
+    /// Show the end of each request's prompt; left out by default
+    #[serde(default)]
+    pub include_content: bool,
+    /// What is hidden from listed requests, so a log from real traffic can
+    /// be shared
+    #[serde(default)]
+    pub redact: RedactConfig,
+}
+
+/// Redaction applied to `/llmsim/requests`; the stored decisions keep the
+/// original values
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RedactConfig {
+    /// How end user ids are shown
+    #[serde(default)]
+    pub user: UserRedaction,
+    /// Prefixed to a user id before hashing, so hashes cannot be matched
+    /// against guessed ids without it
+    #[serde(default)]
+    pub salt: String,
+    /// Shown prompts cut to their last this many characters
+    #[serde(default)]
+    pub max_content_chars: Option<usize>,
+}
+
+/// How an end user id appears in the request log
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UserRedaction {
+    /// As sent
+    Keep,
+    /// A salted hash, so one user's requests can still be told apart
+    #[default]
+    Hash,
+    /// Left out
+    Strip,
+}
+
+fn default_request_log_capacity() -> usize {
+    200
+}
+
+impl Default for RequestLogConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            capacity: default_request_log_capacity(),
+            include_content: false,
+            redact: RedactConfig::default(),
+        }
+    }
+}
+
+/// Usage of the simulator's own traffic, served at
+/// `/openai/v1/organization/usage/completions` and
+/// `/openai/v1/organization/costs`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Project all usage is reported under
+    #[serde(default = "default_usage_project_id")]
+    pub project_id: String,
+}
+
+fn default_usage_project_id() -> String {
+    "proj_llmsim".to_string()
+}
+
+impl Default for UsageConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            project_id: default_usage_project_id(),
+        }
+    }
+}
+
+/// API keys managed at runtime through `/llmsim/keys`, each with its own
+/// quotas and latency profile
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct KeysConfig {
+    /// Refuse generation requests without a live key created there
+    #[serde(default)]
+    pub enabled: bool,
+    /// JSON file the keys are kept in across restarts; in memory only when
+    /// unset
+    #[serde(default)]
+    pub file: Option<String>,
+    /// Bearer token `/llmsim/keys` requires; open to anyone when unset
+    #[serde(default)]
+    pub admin_key: Option<String>,
+}
+
+/// Several processes serving one benchmark, with stats combined at
+/// `/llmsim/cluster/stats`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClusterConfig {
+    /// Directory every process of the cluster writes its stats to; cluster
+    /// stats are off without one
+    #[serde(default)]
+    pub stats_dir: Option<String>,
+    /// Name of this process's stats file (default `pid-<process id>`)
+    #[serde(default)]
+    pub node: Option<String>,
+    /// How often this process's stats are written (ms)
+    #[serde(default = "default_cluster_publish_interval_ms")]
+    pub publish_interval_ms: u64,
+    /// Bind with SO_REUSEPORT, so processes can share the port (Unix only)
+    #[serde(default)]
+    pub reuse_port: bool,
+}
+
+fn default_cluster_publish_interval_ms() -> u64 {
+    1000
+}
+
+impl Default for ClusterConfig {
+    fn default() -> Self {
+        Self {
+            stats_dir: None,
+            node: None,
+            publish_interval_ms: default_cluster_publish_interval_ms(),
+            reuse_port: false,
+        }
+    }
+}
+
+/// Built-in load generator: requests the server sends itself at a rate that
+/// follows a daily curve, for demoing dashboards and testing alerts
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrafficConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Requests per second at the busiest time of day
+    #[serde(default = "default_traffic_peak_rps")]
+    pub peak_rps: f64,
+    /// Requests per second at the quietest time of day, twelve hours from
+    /// the peak
+    #[serde(default = "default_traffic_trough_rps")]
+    pub trough_rps: f64,
+    /// Time of day of the peak, `HH:MM` UTC
+    #[serde(default = "default_traffic_peak_at")]
+    pub peak_at: String,
+    /// Requests per second at given times of day (`"HH:MM" = rps`), joined
+    /// by straight lines; replaces the sine wave between `trough_rps` and
+    /// `peak_rps` when set
+    #[serde(default)]
+    pub schedule: BTreeMap<String, f64>,
+    /// Real seconds one day of the curve takes; less than 86400 replays a
+    /// day faster
+    #[serde(default = "default_traffic_day_secs")]
+    pub day_secs: u64,
+    /// Generation endpoints requests are sent to, picked at random
+    #[serde(default = "default_traffic_paths")]
+    pub paths: Vec<String>,
+    /// Models requests ask for, picked at random; image generation always
+    /// asks for `gpt-image-1`
+    #[serde(default = "default_traffic_models")]
+    pub models: Vec<String>,
+    /// Fraction of requests that stream
+    #[serde(default = "default_traffic_stream_share")]
+    pub stream_share: f64,
+    /// Key the requests carry, so they can be told apart in per-key stats
+    #[serde(default = "default_traffic_api_key")]
+    pub api_key: String,
+}
+
+fn default_traffic_peak_rps() -> f64 {
+    5.0
+}
+
+fn default_traffic_trough_rps() -> f64 {
+    0.5
+}
+
+fn default_traffic_peak_at() -> String {
+    "14:00".to_string()
+}
+
+fn default_traffic_day_secs() -> u64 {
+    86_400
+}
+
+fn default_traffic_paths() -> Vec<String> {
+    vec!["/openai/v1/chat/completions".to_string()]
+}
+
+fn default_traffic_models() -> Vec<String> {
+    vec!["gpt-5".to_string()]
+}
+
+fn default_traffic_stream_share() -> f64 {
+    0.5
+}
+
+fn default_traffic_api_key() -> String {
+    "llmsim-traffic".to_string()
+}
+
+impl Default for TrafficConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            peak_rps: default_traffic_peak_rps(),
+            trough_rps: default_traffic_trough_rps(),
+            peak_at: default_traffic_peak_at(),
+            schedule: BTreeMap::new(),
+            day_secs: default_traffic_day_secs(),
+            paths: default_traffic_paths(),
+            models: default_traffic_models(),
+            stream_share: default_traffic_stream_share(),
+            api_key: default_traffic_api_key(),
+        }
+    }
+}
+
+/// Fake MCP servers answering `mcp` tools in the Responses API
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct McpConfig {
+    /// Catalogs keyed by `server_label`; other labels list built-in tools
+    #[serde(default)]
+    pub servers: BTreeMap<String, McpServerConfig>,
+}
+
+/// One fake MCP server
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct McpServerConfig {
+    #[serde(default)]
+    pub tools: Vec<McpTool>,
+}
+
+/// Behavior overrides for clients that send a given API version
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ApiVersionBehavior {
+    /// Shape of assistant function calls in chat completions
+    #[serde(default)]
+    pub tool_call_format: Option<ToolCallFormat>,
+}
+
+/// Configuration errors
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    #[error("Failed to read configuration file: {0}")]
+    Io(String),
+    #[error("Failed to parse configuration: {0}")]
+    Parse(String),
+    #[error("Invalid configuration: {0}")]
+    Validation(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_config() {
+        let config = Config::default();
+        assert_eq!(config.server.port, 8080);
+        assert_eq!(config.server.host, "0.0.0.0");
+        assert_eq!(config.response.generator, "lorem");
+        assert_eq!(config.response.target_tokens, 100);
+    }
+
+    #[test]
+    fn test_parse_toml() {
+        let toml_str = r#"
+[server]
+port = 9000
+host = "127.0.0.1"
+
+[latency]
+profile = "gpt4"
+
+[response]
+generator = "echo"
+target_tokens = 50
+
+[errors]
+rate_limit_rate = 0.01
+"#;
+        let config = Config::from_toml(toml_str).unwrap();
+        assert_eq!(config.server.port, 9000);
+        assert_eq!(config.server.host, "127.0.0.1");
+        assert_eq!(config.latency.profile, Some("gpt4".to_string()));
+        assert_eq!(config.response.generator, "echo");
+        assert_eq!(config.response.target_tokens, 50);
+        assert_eq!(config.errors.rate_limit_rate, 0.01);
+    }
+
+    #[test]
+    fn test_custom_latency() {
+        let toml_str = r#"
+[latency]
+ttft_mean_ms = 500
+tbt_mean_ms = 25
+"#;
+        let config = Config::from_toml(toml_str).unwrap();
+        let profile = config.latency_profile();
+        assert_eq!(profile.ttft_mean_ms, 500);
+        assert_eq!(profile.tbt_mean_ms, 25);
+    }
+
+    #[test]
+    fn test_latency_profile_from_name() {
+        let toml_str = r#"
+[latency]
+profile = "instant"
+"#;
+        let config = Config::from_toml(toml_str).unwrap();
+        let profile = config.latency_profile();
+        assert_eq!(profile.ttft_mean_ms, 0);
+    }
+
+    #[test]
+    fn test_error_config() {
+        let toml_str = r#"
+[errors]
+rate_limit_rate = 0.1
+server_error_rate = 0.05
+"#;
+        let config = Config::from_toml(toml_str).unwrap();
+        let error_config = config.error_config();
+        assert_eq!(error_config.rate_limit_rate, 0.1);
+        assert_eq!(error_config.server_error_rate, 0.05);
+    }
+
+    #[test]
+    fn test_vllm_config() {
+        let config = Config::default();
+        assert_eq!(config.vllm.max_num_seqs, 256);
+        assert!(!config.vllm.continuous_usage_stats);
+        assert_eq!(config.vllm_served_model(), "gpt-5");
+
+        let toml_str = r#"
+[vllm]
+served_model_name = "meta-llama/Llama-3.1-8B-Instruct"
+max_num_seqs = 8
+continuous_usage_stats = true
+"#;
+        let config = Config::from_toml(toml_str).unwrap();
+        assert_eq!(
+            config.vllm_served_model(),
+            "meta-llama/Llama-3.1-8B-Instruct"
+        );
+        assert_eq!(config.vllm.max_num_seqs, 8);
+        assert!(config.vllm.continuous_usage_stats);
+    }
+
+    #[test]
+    fn test_cost_config() {
+        let config = Config::default();
+        assert!(!config.cost.enabled);
+        assert_eq!(config.cost.header, "x-llmsim-response-cost");
+        assert_eq!(config.model_price("gpt-5"), ModelPrice::for_model("gpt-5"));
+
+        let toml_str = r#"
+[cost]
+enabled = true
+header = "x-litellm-response-cost"
+
+[cost.prices."gpt-5"]
+input_per_mtok = 2.0
+output_per_mtok = 4.0
+
+[cost.prices."my-finetune"]
+input_per_mtok = 1.0
+output_per_mtok = 1.0
+"#;
+        let config = Config::from_toml(toml_str).unwrap();
+        assert!(config.cost.enabled);
+        assert_eq!(config.cost.header, "x-litellm-response-cost");
+        assert_eq!(config.model_price("gpt-5"), Some(ModelPrice::new(2.0, 4.0)));
+        assert_eq!(
+            config.model_price("my-finetune"),
+            Some(ModelPrice::new(1.0, 1.0))
+        );
+        assert!(config.model_price("unknown-model").is_none());
+    }
+
+    #[test]
+    fn test_stream_config() {
+        let config = Config::default();
+        assert_eq!(config.stream.utf8_boundaries, Utf8Boundaries::Preserve);
+
+        assert!(!config.stream.is_buffered());
+
+        let config = Config::from_toml("[stream]\nutf8_boundaries = \"split\"\n").unwrap();
+        assert_eq!(config.stream.utf8_boundaries, Utf8Boundaries::Split);
+
+        let config = Config::from_toml("[stream]\nbuffer_bytes = 4096\n").unwrap();
+        assert_eq!(config.stream.buffer_bytes, Some(4096));
+        assert!(config.stream.is_buffered());
+
+        let config = Config::from_toml("[stream]\nusage_updates_every = 10\n").unwrap();
+        assert_eq!(config.stream.usage_updates_every, Some(10));
+
+        assert_eq!(Config::default().stream.pending_events, 64);
+        let err = Config::from_toml("[stream]\npending_events = 0\n").unwrap_err();
+        assert!(err.to_string().contains("[stream] pending_events"));
+
+        let config = Config::from_toml(
+            "[stream]\nmalformed_rate = 0.1\nmalformed_kinds = [\"split_prefix\", \"duplicate_done\"]\n",
+        )
+        .unwrap();
+        assert_eq!(
+            config.stream.malformed_kinds,
+            vec![MalformedFrame::SplitPrefix, MalformedFrame::DuplicateDone]
+        );
+        let err = Config::from_toml("[stream]\nmalformed_rate = 2.0\n").unwrap_err();
+        assert!(err.to_string().contains("[stream] malformed_rate"));
+        let err = Config::from_toml("[stream]\nreorder_rate = -0.1\n").unwrap_err();
+        assert!(err.to_string().contains("[stream] reorder_rate"));
+
+        assert_eq!(Config::default().stream.max_duration(), None);
+        let config = Config::from_toml("[stream]\nmax_duration_secs = 600\n").unwrap();
+        assert_eq!(
+            config.stream.max_duration(),
+            Some(std::time::Duration::from_secs(600))
+        );
+        let err = Config::from_toml("[stream]\nmax_duration_secs = 0\n").unwrap_err();
+        assert!(err.to_string().contains("[stream] max_duration_secs"));
+    }
+
+    #[test]
+    fn test_tools_config() {
+        let config = Config::default();
+        assert_eq!(config.tools.call_rate, 0.0);
+        assert_eq!(config.tools.parallel_weights, vec![1.0]);
+
+        let config =
+            Config::from_toml("[tools]\ncall_rate = 0.5\nparallel_weights = [0.6, 0.3, 0.1]\n")
+                .unwrap();
+        let tool_calls = config.tool_call_config();
+        assert_eq!(tool_calls.call_rate, 0.5);
+        assert_eq!(tool_calls.count_weights, vec![0.6, 0.3, 0.1]);
+        assert_eq!(tool_calls.arguments, ToolArguments::Empty);
+
+        let config = Config::from_toml(
+            "[tools]
+arguments = \"schema\"\narguments_seed = 7\n",
+        )
+        .unwrap();
+        let tool_calls = config.tool_call_config();
+        assert_eq!(tool_calls.arguments, ToolArguments::Schema);
+        assert_eq!(tool_calls.arguments_seed, Some(7));
+    }
+
+    #[test]
+    fn test_capacity_config() {
+        assert!(Config::default().load_shedder().is_none());
+
+        let config = Config::from_toml("[capacity]\nmax_rps = 50\n").unwrap();
+        let shedder = config.load_shedder().unwrap();
+        assert_eq!(shedder.max_rps(), 50);
+        assert_eq!(shedder.retry_after_seconds(), 1);
+    }
+
+    #[test]
+    fn test_regions_config() {
+        let config = Config::from_toml(
+            r#"
+[latency]
+profile = "instant"
+
+[regions.us-east]
+
+[regions.eu-west]
+prefix = "/eu/"
+errors = { server_error_rate = 1.0 }
+
+[regions.ap-south]
+port = 9001
+latency = { ttft_mean_ms = 900 }
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.regions.keys().collect::<Vec<_>>(),
+            ["ap-south", "eu-west", "us-east"]
+        );
+
+        let us = &config.regions["us-east"];
+        assert_eq!(us.mount_path("us-east").as_deref(), Some("/us-east"));
+        let eu = &config.regions["eu-west"];
+        assert_eq!(eu.mount_path("eu-west").as_deref(), Some("/eu"));
+        let eu = config.for_region("eu-west").unwrap();
+        assert_eq!(eu.errors.server_error_rate, 1.0);
+        assert_eq!(eu.latency.profile.as_deref(), Some("instant"));
+        assert!(eu.regions.is_empty());
+
+        let ap = config.for_region("ap-south").unwrap();
+        assert_eq!(config.regions["ap-south"].mount_path("ap-south"), None);
+        assert_eq!(ap.server.port, 9001);
+        assert_eq!(ap.latency.ttft_mean_ms, Some(900));
+        assert_eq!(ap.latency.profile, None);
+
+        assert!(config.for_region("nowhere").is_none());
+    }
+
+    #[test]
+    fn test_upstreams_config() {
+        let config = Config::from_toml(
+            r#"
+[upstreams.openai]
+base_url = "http://gateway:9000"
+api_key = "sk-test"
+
+[mirror]
+enabled = true
+"#,
+        )
+        .unwrap();
+        let openai = &config.upstreams["openai"];
+        assert_eq!(openai.api_key.as_deref(), Some("sk-test"));
+        assert_eq!(openai.timeout_secs, 60);
+        assert!(config.mirror.enabled);
+        assert_eq!(config.mirror.sample_rate, 1.0);
+
+        let err = Config::from_toml("[upstreams.openai]\nbase_url = \"ftp://api.openai.com\"\n")
+            .unwrap_err();
+        assert!(matches!(err, ConfigError::Validation(_)));
+        assert!(err.to_string().contains("[upstreams.openai]"));
+    }
+
+    #[test]
+    fn test_proxy_config() {
+        let config = Config::from_toml(
+            r#"
+[upstreams.openai]
+base_url = "http://gateway:9000"
+
+[[proxy.rules]]
+model = "gpt-4*"
+
+[[proxy.rules]]
+header = "x-route"
+header_value = "real"
+rate = 0.25
+"#,
+        )
+        .unwrap();
+        let rules = &config.proxy.rules;
+        assert_eq!(rules.len(), 2);
+        assert_eq!(rules[0].model.as_deref(), Some("gpt-4*"));
+        assert_eq!(rules[0].rate, 1.0);
+        assert_eq!(rules[1].header_value.as_deref(), Some("real"));
+        assert_eq!(rules[1].rate, 0.25);
+
+        let err = Config::from_toml("[[proxy.rules]]\nmodel = \"*\"\n").unwrap_err();
+        assert!(err.to_string().contains("[upstreams]"));
+    }
+
+    #[test]
+    fn test_learned_config() {
+        let config = Config::default();
+        assert!(config.learned.record.is_none());
+        assert_eq!(config.learned.save_interval_secs, 30);
+
+        let config = Config::from_toml(
+            "[learned]\nrecord = \"profiles.json\"\nreplay = \"profiles.json\"\n",
+        )
+        .unwrap();
+        assert_eq!(config.learned.record.as_deref(), Some("profiles.json"));
+        assert_eq!(config.learned.replay.as_deref(), Some("profiles.json"));
+    }
+
+    #[test]
+    fn test_simulation_config() {
+        assert!(!Config::default().simulation.disable_delays);
+        let config = Config::from_toml("[simulation]\ndisable_delays = true\n").unwrap();
+        assert!(config.simulation.disable_delays);
+        assert_eq!(config.simulation.time_scale, 1.0);
+
+        let config = Config::from_toml(
+            "[simulation]\ntime_scale = 0.1\n[errors]\ntimeout_after_ms = 30000\n",
+        )
+        .unwrap();
+        assert_eq!(config.simulation.time_scale, 0.1);
+        assert_eq!(config.error_config().timeout_after_ms, 3000);
+    }
+
+    #[test]
+    fn test_tokens_config() {
+        let tokenizer = Config::default().tokenizer();
+        assert_eq!(tokenizer, Tokenizer::Bpe(Encoding::Cl100kBase));
+
+        let config = Config::from_toml("[tokens]\nencoding = \"o200k_base\"\n").unwrap();
+        assert_eq!(config.tokenizer(), Tokenizer::Bpe(Encoding::O200kBase));
+
+        let config =
+            Config::from_toml("[tokens]\nencoding = \"estimate\"\nchars_per_token = 3.0\n")
+                .unwrap();
+        assert_eq!(config.tokenizer().count("abcdefg"), 3);
+
+        assert!(Config::from_toml("[tokens]\nencoding = \"gpt2\"\n").is_err());
+
+        let map = Config::default().encoding_map();
+        assert_eq!(map.encoding_for("in-house-llm"), Encoding::Cl100kBase);
+        let config = Config::from_toml(
+            "[tokens]\nfallback_encoding = \"o200k_base\"\n[tokens.models]\n\"acme-*\" = \"p50k_base\"\n",
+        )
+        .unwrap();
+        let map = config.encoding_map();
+        assert_eq!(map.encoding_for("acme-large"), Encoding::P50kBase);
+        assert_eq!(map.encoding_for("in-house-llm"), Encoding::O200kBase);
+        assert_eq!(map.encoding_for("gpt-4"), Encoding::Cl100kBase);
+        assert!(Config::from_toml("[tokens.models]\n\"acme-*\" = \"estimate\"\n").is_err());
+    }
+
+    #[test]
+    fn test_fingerprint_config() {
+        let fingerprints = Config::default().system_fingerprints();
+        assert_eq!(fingerprints.for_model_at("gpt-5", 12_345), "fp_llmsim");
+
+        let config = Config::from_toml(
+            "[fingerprint]\nvalues = [\"fp_a\", \"fp_b\"]\nrotate_every_secs = 10\n\n[fingerprint.models]\n\"gpt-4o\" = \"fp_4o\"\n",
+        )
+        .unwrap();
+        let fingerprints = config.system_fingerprints();
+        assert_eq!(fingerprints.for_model_at("gpt-5", 5), "fp_a");
+        assert_eq!(fingerprints.for_model_at("gpt-5", 15), "fp_b");
+        assert_eq!(fingerprints.for_model_at("gpt-4o", 15), "fp_4o");
+    }
+
+    #[test]
+    fn test_api_versions_config() {
+        let config = Config::from_toml(
+            "[api_versions.\"2023-05-15\"]\ntool_call_format = \"function_call\"\n",
+        )
+        .unwrap();
+        assert_eq!(
+            config.api_versions["2023-05-15"].tool_call_format,
+            Some(ToolCallFormat::FunctionCall)
+        );
+        assert!(Config::default().api_versions.is_empty());
+    }
+
+    #[test]
+    fn test_compression_config() {
+        let config = Config::default();
+        assert!(!config.compression.enabled);
+        assert!(config.compression.compresses("application/json"));
+        assert!(!config.compression.compresses("text/event-stream"));
+
+        let config = Config::from_toml(
+            "[compression]\nenabled = true\nbr = false\ncontent_types = [\"text/\"]\n",
+        )
+        .unwrap();
+        assert!(config.compression.enabled);
+        assert!(config.compression.gzip);
+        assert!(!config.compression.br);
+        assert!(config.compression.compresses("text/plain; charset=utf-8"));
+        assert!(!config.compression.compresses("application/json"));
+        assert!(!config.compression.compresses("text/event-stream"));
+    }
+
+    #[test]
+    fn test_tui_alerts_config() {
+        assert!(!Config::default().tui.alerts.is_active());
+
+        let config =
+            Config::from_toml("[tui.alerts]\nerror_rate_percent = 5.0\np99_latency_ms = 2000\n")
+                .unwrap();
+        assert!(config.tui.alerts.is_active());
+        assert_eq!(config.tui.alerts.p99_latency_ms, Some(2000.0));
+        assert_eq!(config.tui.alerts.active_requests, None);
+
+        let err = Config::from_toml("[tui.alerts]\nerror_rate_percent = 120\n").unwrap_err();
+        assert!(err.to_string().contains("[tui.alerts]"));
+    }
+
+    #[test]
+    fn test_runtime_config() {
+        assert_eq!(Config::default().runtime, RuntimeConfig::default());
+
+        let config =
+            Config::from_toml("[runtime]\nworker_threads = 4\nmax_blocking_threads = 16\n")
+                .unwrap();
+        assert_eq!(config.runtime.worker_threads, Some(4));
+        assert_eq!(config.runtime.max_blocking_threads, Some(16));
+
+        let err = Config::from_toml("[runtime]\nworker_threads = 0\n").unwrap_err();
+        assert!(err.to_string().contains("[runtime]"));
+    }
+
+    #[test]
+    fn test_env_placeholders() {
+        std::env::set_var("LLMSIM_TEST_CONFIG_PORT", "9321");
+        let config = Config::from_toml(
+            "[server]\nport = \"${LLMSIM_TEST_CONFIG_PORT}\"\nhost = \"${LLMSIM_TEST_CONFIG_HOST:-127.0.0.1}\"\n\n[errors]\nrate_limit_rate = \"${LLMSIM_TEST_CONFIG_RATE:-0.5}\"\n",
+        )
+        .unwrap();
+        assert_eq!(config.server.port, 9321);
+        assert_eq!(config.server.host, "127.0.0.1");
+        assert_eq!(config.errors.rate_limit_rate, 0.5);
+
+        let err =
+            Config::from_toml("[server]\nport = \"${LLMSIM_TEST_CONFIG_UNSET}\"\n").unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("LLMSIM_TEST_CONFIG_UNSET is not set"));
+    }
+
+    #[test]
+    fn test_models_latency_config() {
+        assert!(Config::default().model_latencies().is_empty());
+
+        let config = Config::from_toml(
+            "[models.latency]\n\"acme-small\" = \"claude-haiku\"\n\"acme-reasoner\" = { ttft_mean_ms = 4000, tbt_mean_ms = 20 }\n",
+        )
+        .unwrap();
+        let latencies = config.model_latencies();
+        assert_eq!(latencies["acme-small"], LatencyProfile::claude_haiku());
+        assert_eq!(
+            latencies["acme-reasoner"],
+            LatencyProfile::new(4000, 0, 20, 0)
+        );
+
+        let err =
+            Config::from_toml("[models.latency]\n\"acme-small\" = \"warp-speed\"\n").unwrap_err();
+        assert!(err.to_string().contains("[models.latency]"));
+    }
+
+    #[test]
+    fn test_pool_config() {
+        let config = Config::default();
+        assert!(!config.pool.enabled);
+        assert_eq!(config.pool.size, 64);
+
+        let config = Config::from_toml("[pool]\nenabled = true\nsize = 8\n").unwrap();
+        assert!(config.pool.enabled);
+        assert_eq!(config.pool.size, 8);
+
+        let err = Config::from_toml("[pool]\nenabled = true\nsize = 0\n").unwrap_err();
+        assert!(err.to_string().contains("[pool]"));
+    }
+
+    #[test]
+    fn test_ids_config() {
+        assert!(Config::default().ids.is_empty());
+
+        let config = Config::from_toml(
+            "[ids.response]
+prefix = \"resp-\"
+alphabet = \"uuid\"
+",
+        )
+        .unwrap();
+        assert_eq!(
+            config.ids[&IdKind::Response].prefix.as_deref(),
+            Some("resp-")
+        );
+
+        let err = Config::from_toml(
+            "[ids.response]
+length = 0
+",
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("[ids.response]"));
+        assert!(Config::from_toml(
+            "[ids.completion]
+length = 8
+"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_clock_config() {
+        let config = Config::default();
+        assert_eq!(config.clock.skew_secs, 0);
+        assert_eq!(config.clock.jitter_secs, 0);
+
+        let config = Config::from_toml("[clock]\nskew_secs = -300\njitter_secs = 5\n").unwrap();
+        assert_eq!(config.clock.skew_secs, -300);
+        assert_eq!(config.clock.jitter_secs, 5);
+    }
+
+    #[test]
+    fn test_traffic_config() {
+        let config = Config::default();
+        assert!(!config.traffic.enabled);
+        assert_eq!(config.traffic.peak_at, "14:00");
+
+        let config = Config::from_toml(
+            "[traffic]\nenabled = true\nday_secs = 600\n\n[traffic.schedule]\n\"09:00\" = 2.0\n\"18:30\" = 0.0\n",
+        )
+        .unwrap();
+        assert!(config.traffic.enabled);
+        assert_eq!(config.traffic.schedule.len(), 2);
+
+        for bad in [
+            "peak_at = \"25:00\"",
+            "peak_rps = 1.0\ntrough_rps = 2.0",
+            "stream_share = 1.5",
+            "paths = [\"/llmsim/stats\"]",
+            "models = []",
+            "day_secs = 0",
+            "schedule = { \"noon\" = 1.0 }",
+            "schedule = { \"12:00\" = -1.0 }",
+        ] {
+            let err = Config::from_toml(&format!("[traffic]\n{}\n", bad)).unwrap_err();
+            assert!(err.to_string().contains("[traffic]"), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_keys_config() {
+        let config = Config::default();
+        assert!(!config.keys.enabled);
+        assert!(config.rate_limiter().is_none());
+
+        let config = Config::from_toml(
+            "[keys]\nenabled = true\nfile = \"keys.json\"\nadmin_key = \"admin\"\n",
+        )
+        .unwrap();
+        assert_eq!(config.keys.file.as_deref(), Some("keys.json"));
+        assert_eq!(config.keys.admin_key.as_deref(), Some("admin"));
+        // Keys may carry quotas, so their requests go through a limiter
+        assert!(config.rate_limiter().is_some());
+    }
+
+    #[test]
+    fn test_usage_config() {
+        let config = Config::default();
+        assert!(!config.usage.enabled);
+        assert_eq!(config.usage.project_id, "proj_llmsim");
+
+        let config =
+            Config::from_toml("[usage]\nenabled = true\nproject_id = \"proj_billing\"\n").unwrap();
+        assert!(config.usage.enabled);
+        assert_eq!(config.usage.project_id, "proj_billing");
     }
 }
@@ -0,0 +1,268 @@
+// Config Check Module
+// `llmsim config validate`: a config file checked the way the server loads
+// it, plus the keys the server would ignore, each issue reported with the
+// line and column it refers to.
+//
+// Decision: unknown keys are found by comparing the file with the config it
+// loads into, serialized back to TOML: a key the user wrote that does not come
+// back was dropped by serde. This keeps the schema in one place, the config
+// structs, rather than in a key list that drifts from them. Loading itself is
+// left alone, so a typo still starts the server; `validate` is for CI.
+
+use super::config::{Config, ConfigError};
+use std::fmt;
+use std::path::Path;
+use toml::de::{DeTable, DeValue};
+
+/// One problem with a config file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigIssue {
+    /// 1-based line and column, when the issue points at part of the file
+    pub position: Option<(usize, usize)>,
+    pub message: String,
+}
+
+impl fmt::Display for ConfigIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.position {
+            Some((line, column)) => write!(f, "line {}, column {}: {}", line, column, self.message),
+            None => f.write_str(&self.message),
+        }
+    }
+}
+
+/// Load a config from TOML, reporting every unknown key along with the first
+/// syntax, type or validation error. `dir` is where the files it includes
+/// are found; their own keys are checked by validating them in turn.
+pub fn check_config(source: &str, dir: &Path) -> Result<Config, Vec<ConfigIssue>> {
+    let document = DeTable::parse(source).map_err(|e| vec![issue_from_toml(source, &e)])?;
+    let config = Config::parse_toml(source, dir).map_err(|e| vec![issue_from_toml(source, &e)])?;
+
+    let mut issues = Vec::new();
+    // Serializing what was just deserialized cannot fail
+    let loaded = toml::Table::try_from(&config).unwrap_or_default();
+    let defaults = toml::Table::try_from(Config::default()).unwrap_or_default();
+    unknown_keys(
+        source,
+        document.get_ref(),
+        &loaded,
+        Some(&defaults),
+        "",
+        &mut issues,
+    );
+    // Tables iterate in key order; report in file order
+    issues.sort_by_key(|issue| issue.position);
+    if let Err(ConfigError::Validation(message)) = config.validate() {
+        issues.push(ConfigIssue {
+            position: section_span(document.get_ref(), &message)
+                .map(|offset| line_column(source, offset)),
+            message,
+        });
+    }
+
+    if issues.is_empty() {
+        Ok(config)
+    } else {
+        Err(issues)
+    }
+}
+
+/// The configuration, every default filled in, as TOML
+pub fn config_toml(config: &Config) -> Result<String, ConfigError> {
+    toml::to_string_pretty(config).map_err(|e| ConfigError::Parse(e.to_string()))
+}
+
+fn issue_from_toml(source: &str, error: &toml::de::Error) -> ConfigIssue {
+    ConfigIssue {
+        position: error.span().map(|span| line_column(source, span.start)),
+        message: error.message().trim_end().to_string(),
+    }
+}
+
+/// Report the keys of `written` that are missing from `loaded`, recursing
+/// into tables and arrays of tables. `defaults` is the matching part of the
+/// default config, which lists keys a suggestion can point at.
+fn unknown_keys(
+    source: &str,
+    written: &DeTable<'_>,
+    loaded: &toml::Table,
+    defaults: Option<&toml::Table>,
+    path: &str,
+    issues: &mut Vec<ConfigIssue>,
+) {
+    for (key, value) in written {
+        let name = key.get_ref().as_ref();
+        if path.is_empty() && name == "include" {
+            continue;
+        }
+        let full = if path.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}.{}", path, name)
+        };
+        let default = defaults.and_then(|d| d.get(name));
+        let Some(known) = loaded.get(name) else {
+            let mut message = format!("unknown key `{}`", full);
+            let candidates = loaded
+                .keys()
+                .chain(defaults.into_iter().flat_map(|d| d.keys()));
+            if let Some(suggestion) = closest(name, candidates) {
+                message.push_str(&format!("; did you mean `{}`?", suggestion));
+            }
+            issues.push(ConfigIssue {
+                position: Some(line_column(source, key.span().start)),
+                message,
+            });
+            continue;
+        };
+        match (value.get_ref(), known) {
+            (DeValue::Table(table), toml::Value::Table(known)) => {
+                let default = default.and_then(toml::Value::as_table);
+                unknown_keys(source, table, known, default, &full, issues);
+            }
+            (DeValue::Array(array), toml::Value::Array(known)) => {
+                for (i, (value, known)) in array.iter().zip(known).enumerate() {
+                    if let (DeValue::Table(table), toml::Value::Table(known)) =
+                        (value.get_ref(), known)
+                    {
+                        let full = format!("{}[{}]", full, i);
+                        unknown_keys(source, table, known, None, &full, issues);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The known key closest to a misspelt one, if any is close enough to be
+/// what was meant
+fn closest<'a>(key: &str, candidates: impl Iterator<Item = &'a String>) -> Option<&'a str> {
+    candidates
+        .map(|candidate| (edit_distance(key, candidate), candidate.as_str()))
+        .filter(|(distance, _)| *distance <= 2.max(key.len() / 3))
+        .min()
+        .map(|(_, candidate)| candidate)
+}
+
+/// Levenshtein distance between two keys
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(ca != *cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// Where the section named at the start of a validation message, such as
+/// `[rate_limits] ...` or `[upstreams.openai] ...`, is declared
+fn section_span(document: &DeTable<'_>, message: &str) -> Option<usize> {
+    let section = message
+        .strip_prefix('[')?
+        .trim_start_matches('[')
+        .split(']')
+        .next()?;
+    let mut table = document;
+    let mut offset = None;
+    for name in section.split('.') {
+        let Some((key, value)) = table.get_key_value(name) else {
+            break;
+        };
+        offset = Some(key.span().start);
+        match value.get_ref() {
+            DeValue::Table(inner) => table = inner,
+            _ => break,
+        }
+    }
+    offset
+}
+
+/// 1-based line and column of a byte offset
+fn line_column(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..offset.min(source.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before
+        .rsplit('\n')
+        .next()
+        .map_or(0, |line| line.chars().count())
+        + 1;
+    (line, column)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(source: &str) -> Result<Config, Vec<ConfigIssue>> {
+        check_config(source, Path::new("."))
+    }
+
+    #[test]
+    fn test_misspelt_keys_are_reported_where_they_are() {
+        let issues = check(
+            "[server]\nport = 9000\n\n[laency]\nttft_mean_ms = 10\n\n[response]\ntarget_tokns = 5\n",
+        )
+        .unwrap_err();
+        assert_eq!(
+            issues,
+            vec![
+                ConfigIssue {
+                    position: Some((4, 2)),
+                    message: "unknown key `laency`; did you mean `latency`?".to_string(),
+                },
+                ConfigIssue {
+                    position: Some((8, 1)),
+                    message: "unknown key `response.target_tokns`; did you mean `target_tokens`?"
+                        .to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_type_and_validation_errors_have_positions() {
+        let issues = check("[server]\nport = \"eighty\"\n").unwrap_err();
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].position, Some((2, 8)));
+        assert!(issues[0].message.contains("u16"), "{}", issues[0]);
+
+        let issues = check("[server]\nport = 8080\n\n[rate_limits]\nretry_after_jitter = 2.0\n")
+            .unwrap_err();
+        assert_eq!(issues[0].position, Some((4, 2)));
+        assert!(issues[0].message.starts_with("[rate_limits]"));
+        assert!(issues[0]
+            .to_string()
+            .starts_with("line 4, column 2: [rate_limits]"));
+
+        let issues = check("[server\n").unwrap_err();
+        assert_eq!(issues[0].position.map(|(line, _)| line), Some(1));
+    }
+
+    #[test]
+    fn test_known_configs_pass() {
+        assert!(check("").is_ok());
+        assert!(check("include = []\n").is_ok());
+        let config = check(
+            "[[errors.rules]]\nstatus = 503\nrate = 0.1\n\n[upstreams.openai]\nbase_url = \"http://127.0.0.1:9000\"\n",
+        )
+        .unwrap();
+        assert_eq!(config.errors.rules.len(), 1);
+        for preset in super::super::PRESETS {
+            assert!(check(preset.toml).is_ok(), "{}", preset.name);
+        }
+    }
+
+    #[test]
+    fn test_defaults_dump_round_trips() {
+        let dump = config_toml(&Config::default()).unwrap();
+        assert!(dump.contains("[server]"));
+        assert!(check(&dump).is_ok());
+    }
+}
@@ -0,0 +1,88 @@
+// Deprecation Module
+// Router middleware applying `[deprecations]`: generation requests for a
+// retired model are answered with its error, and responses for a model in
+// its warning period carry `Deprecation` and `Sunset` headers.
+//
+// Decision: a middleware, like the proxy and mirror, so every provider's
+// generation endpoint is covered in one place. It sits inside both: proxied
+// requests reach the real API untouched, and mirrored ones are compared
+// against the simulated error. The error is rendered in the format of the
+// endpoint the request was sent to.
+
+use super::handlers::injected_error_response;
+use super::proxy::endpoint_type;
+use super::request_body::ParsedBody;
+use super::state::AppState;
+use crate::decisions::ErrorCause;
+use crate::errors::SimulatedError;
+use crate::pipeline::{AnthropicFormat, OpenAIFormat, OpenResponsesFormat, ResponsesFormat};
+use crate::stats::EndpointType;
+use axum::{
+    extract::{Request, State},
+    http::{HeaderName, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use std::sync::Arc;
+
+/// Middleware: retire and warn about models on a deprecation schedule
+pub async fn deprecate_models(
+    State(state): State<Arc<AppState>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let Some(deprecations) = state.deprecations.clone() else {
+        return next.run(request).await;
+    };
+    let path = request.uri().path().to_string();
+    let endpoint = (request.method() == "POST")
+        .then(|| endpoint_type(&path))
+        .flatten();
+    let Some(endpoint) = endpoint else {
+        return next.run(request).await;
+    };
+
+    let body = match ParsedBody::read(&mut request).await {
+        Ok(body) => body,
+        Err(response) => return response,
+    };
+    let model = body.model.as_str();
+    let Some(status) = deprecations.check(model) else {
+        return next.run(request).await;
+    };
+
+    let mut response = match status.error(model) {
+        Some(error) => {
+            tracing::info!(model, "Request for retired model");
+            state
+                .begin(model, body.stream, endpoint)
+                .fail_with(ErrorCause::Deprecation, error.clone());
+            error_response(&path, endpoint, &error)
+        }
+        None => next.run(request).await,
+    };
+    for (name, value) in status.headers() {
+        if let Ok(value) = HeaderValue::from_str(&value) {
+            response
+                .headers_mut()
+                .insert(HeaderName::from_static(name), value);
+        }
+    }
+    response
+}
+
+/// `error` in the wire format of the endpoint at `path`
+pub(super) fn error_response(
+    path: &str,
+    endpoint: EndpointType,
+    error: &SimulatedError,
+) -> Response {
+    match endpoint {
+        EndpointType::Messages => injected_error_response::<AnthropicFormat>(error),
+        EndpointType::Responses if path.starts_with("/openresponses/") => {
+            injected_error_response::<OpenResponsesFormat>(error)
+        }
+        EndpointType::Responses => injected_error_response::<ResponsesFormat>(error),
+        _ => injected_error_response::<OpenAIFormat>(error),
+    }
+}
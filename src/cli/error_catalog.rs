@@ -0,0 +1,313 @@
+// Error Catalog Module
+// `/llmsim/errors/catalog`, every error the simulator can answer with as
+// each provider format renders it, and `/llmsim/errors/force`, errors armed
+// to answer the next matching generation requests, for walking through a
+// client's error states by hand.
+//
+// Decision: catalog samples are rendered by the same `error_response` that
+// answers real requests, so the catalog cannot drift from the wire; the CDN
+// pages of `[errors] cdn_errors` are listed too, the same in every format
+// since they never come from the API itself. An armed
+// error is handed to the request as its `llmsim` `force_error` override, so
+// it takes the path a forced error always takes: counted, logged and
+// rendered by the pipeline in the endpoint's format. Errors are taken
+// oldest first, and a request forcing an error of its own keeps it and
+// leaves the armed ones for the next.
+
+use super::config::CdnError;
+use super::deprecation::error_response;
+use super::error_rules::template_response;
+use super::proxy::endpoint_type;
+use crate::errors::SimulatedError;
+use crate::glob::glob_match;
+use crate::overrides::ForcedError;
+use axum::{body::to_bytes, response::Response};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Paths the catalog's samples are rendered for, by provider format
+const FORMATS: [(&str, &str); 4] = [
+    ("openai", "/openai/v1/chat/completions"),
+    ("responses", "/openai/v1/responses"),
+    ("openresponses", "/openresponses/v1/responses"),
+    ("anthropic", "/anthropic/v1/messages"),
+];
+
+/// Errors that are not forced but answered when config calls for them, with
+/// the setting that does
+fn configured_errors() -> [(&'static str, &'static str, SimulatedError); 3] {
+    [
+        (
+            "request_timeout",
+            "[errors] hang_rate",
+            SimulatedError::RequestTimeout,
+        ),
+        (
+            "model_not_found",
+            "[[deprecations]] past its sunset",
+            SimulatedError::ModelNotFound {
+                model: "gpt-4-0314".to_string(),
+            },
+        ),
+        (
+            "model_deprecated",
+            "[[deprecations]] with status = 410",
+            SimulatedError::ModelDeprecated {
+                model: "gpt-4-0314".to_string(),
+                sunset: "2025-01-01".to_string(),
+            },
+        ),
+    ]
+}
+
+/// Every simulated error, with its status, headers and body in each
+/// provider format; `timeout` is what a 504 reports waiting
+pub async fn catalog(timeout: Duration) -> Value {
+    let forced = [
+        ForcedError::RateLimit,
+        ForcedError::ServerError,
+        ForcedError::ServiceUnavailable,
+        ForcedError::Overloaded,
+        ForcedError::Timeout,
+        ForcedError::InvalidRequest,
+        ForcedError::Authentication,
+    ]
+    .map(|forced| {
+        let name = serde_json::to_value(forced).unwrap_or_default();
+        (name, Value::Null, forced.error(timeout))
+    });
+    let configured = configured_errors()
+        .map(|(name, source, error)| (Value::from(name), Value::from(source), error));
+
+    let mut data = Vec::new();
+    for (name, source, error) in forced.into_iter().chain(configured) {
+        let mut formats = Map::new();
+        for (format, path) in FORMATS {
+            let Some(endpoint) = endpoint_type(path) else {
+                continue;
+            };
+            let response = error_response(path, endpoint, &error);
+            formats.insert(format.to_string(), sample(response).await);
+        }
+        data.push(json!({
+            "type": name,
+            "status": error.status_code(),
+            "forceable": source.is_null(),
+            "source": source,
+            "formats": formats,
+        }));
+    }
+    for kind in CdnError::ALL {
+        let template = kind.template();
+        let page = sample(template_response(&template, "gpt-5")).await;
+        let formats: Map<String, Value> = FORMATS
+            .iter()
+            .map(|(format, _)| (format.to_string(), page.clone()))
+            .collect();
+        data.push(json!({
+            "type": kind,
+            "status": template.status,
+            "forceable": false,
+            "source": "[errors] cdn_error_rate",
+            "formats": formats,
+        }));
+    }
+    json!({ "object": "list", "data": data })
+}
+
+/// The headers and body of `response`; a body that is not JSON is given as
+/// a string
+async fn sample(response: Response) -> Value {
+    let headers: Map<String, Value> = response
+        .headers()
+        .iter()
+        .map(|(name, value)| {
+            let value = String::from_utf8_lossy(value.as_bytes()).into_owned();
+            (name.to_string(), Value::from(value))
+        })
+        .collect();
+    let bytes = to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap_or_default();
+    let body = serde_json::from_slice::<Value>(&bytes)
+        .unwrap_or_else(|_| Value::from(String::from_utf8_lossy(&bytes).into_owned()));
+    json!({ "headers": headers, "body": body })
+}
+
+/// An error to arm, the body of `POST /llmsim/errors/force`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ForceSpec {
+    pub error: ForcedError,
+    /// Glob the request's model must match; any model when unset
+    #[serde(default)]
+    pub model: Option<String>,
+    /// Glob the request's path must match; any generation endpoint when
+    /// unset
+    #[serde(default)]
+    pub path: Option<String>,
+    /// How many requests it answers
+    #[serde(default = "default_count")]
+    pub count: u32,
+}
+
+fn default_count() -> u32 {
+    1
+}
+
+/// An armed error and the requests it has left to answer
+#[derive(Debug, Clone, Serialize)]
+pub struct ArmedError {
+    pub id: String,
+    pub error: ForcedError,
+    pub model: Option<String>,
+    pub path: Option<String>,
+    pub remaining: u32,
+}
+
+impl ArmedError {
+    fn matches(&self, path: &str, model: &str) -> bool {
+        self.model
+            .as_deref()
+            .is_none_or(|glob| glob_match(glob, model))
+            && self
+                .path
+                .as_deref()
+                .is_none_or(|glob| glob_match(glob, path))
+    }
+}
+
+/// Errors armed for the next matching requests, oldest first
+#[derive(Default)]
+pub struct ForcedErrors {
+    armed: Mutex<Vec<ArmedError>>,
+    next_id: AtomicU64,
+}
+
+impl ForcedErrors {
+    /// Arm an error
+    pub fn arm(&self, spec: ForceSpec) -> Result<ArmedError, String> {
+        if spec.count == 0 {
+            return Err("count must be at least 1".to_string());
+        }
+        let n = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let armed = ArmedError {
+            id: format!("force_{}", n),
+            error: spec.error,
+            model: spec.model,
+            path: spec.path,
+            remaining: spec.count,
+        };
+        self.lock().push(armed.clone());
+        Ok(armed)
+    }
+
+    /// Errors still armed
+    pub fn armed(&self) -> Vec<ArmedError> {
+        self.lock().clone()
+    }
+
+    /// Whether any error is armed, checked before a body is parsed for its
+    /// model
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+
+    /// Disarm an error; `false` if there is none with `id`
+    pub fn cancel(&self, id: &str) -> bool {
+        let mut armed = self.lock();
+        let before = armed.len();
+        armed.retain(|armed| armed.id != id);
+        armed.len() != before
+    }
+
+    /// The error the oldest match arms for a request for `model` at `path`,
+    /// used up once it has answered its count
+    pub fn take(&self, path: &str, model: &str) -> Option<ForcedError> {
+        let mut armed = self.lock();
+        let index = armed.iter().position(|armed| armed.matches(path, model))?;
+        let error = armed[index].error;
+        armed[index].remaining -= 1;
+        if armed[index].remaining == 0 {
+            armed.remove(index);
+        }
+        Some(error)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<ArmedError>> {
+        self.armed.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec(error: ForcedError, model: Option<&str>, count: u32) -> ForceSpec {
+        ForceSpec {
+            error,
+            model: model.map(str::to_string),
+            path: None,
+            count,
+        }
+    }
+
+    #[test]
+    fn test_take_oldest_match() {
+        let forced = ForcedErrors::default();
+        assert!(forced.is_empty());
+        forced
+            .arm(spec(ForcedError::RateLimit, Some("claude-*"), 1))
+            .unwrap();
+        forced.arm(spec(ForcedError::ServerError, None, 2)).unwrap();
+
+        let path = "/openai/v1/chat/completions";
+        assert_eq!(forced.take(path, "gpt-5"), Some(ForcedError::ServerError));
+        assert_eq!(
+            forced.take(path, "claude-sonnet-4"),
+            Some(ForcedError::RateLimit)
+        );
+        assert_eq!(forced.take(path, "gpt-5"), Some(ForcedError::ServerError));
+        assert_eq!(forced.take(path, "gpt-5"), None);
+        assert!(forced.is_empty());
+    }
+
+    #[test]
+    fn test_cancel() {
+        let forced = ForcedErrors::default();
+        assert!(forced.arm(spec(ForcedError::Timeout, None, 0)).is_err());
+        let armed = forced.arm(spec(ForcedError::Timeout, None, 1)).unwrap();
+        assert_eq!(armed.id, "force_1");
+        assert!(forced.cancel("force_1"));
+        assert!(!forced.cancel("force_1"));
+        assert_eq!(forced.take("/anthropic/v1/messages", "x"), None);
+    }
+
+    #[tokio::test]
+    async fn test_catalog_matches_the_wire() {
+        let catalog = catalog(Duration::from_secs(30)).await;
+        let data = catalog["data"].as_array().unwrap();
+        assert_eq!(data.len(), 17);
+
+        let rate_limit = &data[0];
+        assert_eq!(rate_limit["type"], "rate_limit");
+        assert_eq!(rate_limit["status"], 429);
+        assert_eq!(rate_limit["forceable"], true);
+        let openai = &rate_limit["formats"]["openai"];
+        assert_eq!(openai["headers"]["retry-after"], "1");
+        assert_eq!(openai["body"]["error"]["type"], "rate_limit_error");
+        let anthropic = &rate_limit["formats"]["anthropic"];
+        assert_eq!(anthropic["body"]["type"], "error");
+
+        let deprecated = &data[9];
+        assert_eq!(deprecated["status"], 410);
+        assert_eq!(deprecated["forceable"], false);
+
+        let empty = data.iter().find(|e| e["type"] == "empty_520").unwrap();
+        assert_eq!(empty["status"], 520);
+        assert_eq!(empty["formats"]["anthropic"]["body"], "");
+    }
+}
@@ -0,0 +1,452 @@
+// Error Rules Module
+// Router middleware applying `[[errors.rules]]`: a generation request whose
+// model, message text, headers or prompt size match a rule is answered with
+// that rule's error, e.g. 400 for prompts over 100k tokens or 429 only for
+// `gpt-4o`.
+//
+// Decision: a middleware, like deprecations, because conditions on the
+// message text and prompt size need the request body, which each protocol's
+// handler parses differently; here it is read once as JSON for all of them.
+// It sits inside the deprecation middleware, so a retired model keeps its
+// retirement error, and inside the proxy and mirror like it. Rules are
+// checked in order and the first match decides, by its rate, as with proxy
+// rules. A rule answering with an `[errors.responses]` template sends it
+// byte for byte, bypassing the provider's error format, since the point is
+// a payload no provider format produces (a CDN's HTML page, a bare 520).
+
+use super::config::{ErrorRule, ErrorTemplate, ErrorsConfig};
+use super::deprecation::error_response;
+use super::proxy::endpoint_type;
+use super::request_body::ParsedBody;
+use super::state::AppState;
+use crate::decisions::ErrorCause;
+use crate::errors::SimulatedError;
+use crate::glob::glob_match;
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderMap, HeaderName, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use rand::RngExt;
+use regex::Regex;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Request fields holding the prompt, across the providers' formats
+const PROMPT_FIELDS: [&str; 5] = ["system", "instructions", "messages", "input", "prompt"];
+
+/// `[[errors.rules]]` with their patterns compiled
+#[derive(Debug)]
+pub struct ErrorRules {
+    rules: Vec<(ErrorRule, Option<Regex>)>,
+    templates: BTreeMap<String, ErrorTemplate>,
+    timeout: Duration,
+}
+
+/// What a matching rule answers with
+#[derive(Debug, PartialEq)]
+enum RuleError<'a> {
+    /// A simulated error, in the endpoint's format
+    Simulated(SimulatedError),
+    /// An `[errors.responses]` template, as written
+    Template(&'a ErrorTemplate),
+}
+
+impl ErrorRules {
+    /// Compile the rules of `errors`, rejecting bad patterns, statuses and
+    /// templates
+    pub fn new(errors: &ErrorsConfig) -> Result<Self, String> {
+        for (name, template) in &errors.responses {
+            check_template(template).map_err(|e| format!("[errors.responses.{}] {}", name, e))?;
+        }
+        let rules = errors
+            .rules
+            .iter()
+            .enumerate()
+            .map(|(i, rule)| {
+                check_rule(rule, errors)
+                    .map_err(|e| format!("[[errors.rules]] rule {}: {}", i, e))?;
+                let pattern = rule
+                    .matches
+                    .as_deref()
+                    .map(Regex::new)
+                    .transpose()
+                    .map_err(|e| format!("[[errors.rules]] rule {}: {}", i, e))?;
+                Ok((rule.clone(), pattern))
+            })
+            .collect::<Result<_, String>>()?;
+        Ok(Self {
+            rules,
+            templates: errors.responses.clone(),
+            timeout: Duration::from_millis(errors.timeout_after_ms),
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Error for a request, from the first matching rule if its rate draws
+    /// it. `prompt_tokens` is only called when a rule bounds the prompt size.
+    fn check(
+        &self,
+        model: &str,
+        text: &str,
+        headers: &HeaderMap,
+        prompt_tokens: impl Fn() -> u64,
+    ) -> Option<RuleError<'_>> {
+        let (rule, _) = self.rules.iter().find(|(rule, pattern)| {
+            rule_matches(rule, pattern.as_ref(), model, text, headers, &prompt_tokens)
+        })?;
+        let fails = rule.rate >= 1.0 || rand::rng().random_bool(rule.rate.clamp(0.0, 1.0));
+        if !fails {
+            return None;
+        }
+        // Names were checked against the templates when the rules were built
+        let template = rule
+            .response
+            .as_ref()
+            .and_then(|name| self.templates.get(name));
+        Some(template.map_or_else(
+            || RuleError::Simulated(self.error(rule, model)),
+            RuleError::Template,
+        ))
+    }
+
+    fn error(&self, rule: &ErrorRule, model: &str) -> SimulatedError {
+        match rule.status.unwrap_or(500) {
+            400 => SimulatedError::InvalidRequest {
+                message: rule
+                    .message
+                    .clone()
+                    .unwrap_or_else(|| "Invalid request".to_string()),
+            },
+            401 => SimulatedError::AuthenticationError,
+            404 => SimulatedError::ModelNotFound {
+                model: model.to_string(),
+            },
+            429 => SimulatedError::RateLimit {
+                retry_after_seconds: rule.retry_after_seconds.unwrap_or(1),
+            },
+            503 => SimulatedError::ServiceUnavailable,
+            504 => SimulatedError::Timeout {
+                after: self.timeout,
+            },
+            _ => SimulatedError::ServerError,
+        }
+    }
+}
+
+/// A rule answers with either a supported status or a template that exists
+fn check_rule(rule: &ErrorRule, errors: &ErrorsConfig) -> Result<(), String> {
+    match (rule.status, rule.response.as_deref()) {
+        (Some(_), Some(_)) | (None, None) => Err("set one of status and response".to_string()),
+        (Some(status), None) if !matches!(status, 400 | 401 | 404 | 429 | 500 | 503 | 504) => {
+            Err(format!("unsupported status {}", status))
+        }
+        (None, Some(name)) if !errors.responses.contains_key(name) => {
+            Err(format!("no [errors.responses.{}]", name))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// A template is an error with headers that can be sent
+fn check_template(template: &ErrorTemplate) -> Result<(), String> {
+    if !(400..=599).contains(&template.status) {
+        return Err(format!("status {} is not an error", template.status));
+    }
+    if !matches!(
+        template.body,
+        Value::Null | Value::String(_) | Value::Object(_) | Value::Array(_)
+    ) {
+        return Err("body must be a string or a table".to_string());
+    }
+    for (name, value) in &template.headers {
+        HeaderName::try_from(name.as_str()).map_err(|_| format!("bad header name {:?}", name))?;
+        HeaderValue::try_from(value.as_str())
+            .map_err(|_| format!("bad value for header {}", name))?;
+    }
+    Ok(())
+}
+
+/// `template` with its placeholders filled in for a request for `model`
+pub(super) fn template_response(template: &ErrorTemplate, model: &str) -> Response {
+    let fill = |text: &str| {
+        text.replace("{model}", model)
+            .replace("{status}", &template.status.to_string())
+            .replace("{timestamp}", &crate::ids::unix_timestamp().to_string())
+    };
+    let (body, content_type) = match &template.body {
+        Value::Null => (Vec::new(), None),
+        Value::String(text) => (fill(text).into_bytes(), Some("text/plain; charset=utf-8")),
+        json => {
+            let json = fill_strings(json.clone(), &fill);
+            (
+                serde_json::to_vec(&json).unwrap_or_default(),
+                Some("application/json"),
+            )
+        }
+    };
+    let status = StatusCode::from_u16(template.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+    let mut response = (status, body).into_response();
+    let headers = response.headers_mut();
+    headers.remove(header::CONTENT_TYPE);
+    if let Some(content_type) = content_type {
+        headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(content_type));
+    }
+    for (name, value) in &template.headers {
+        let name = HeaderName::try_from(name.as_str());
+        let value = HeaderValue::try_from(fill(value));
+        if let (Ok(name), Ok(value)) = (name, value) {
+            headers.insert(name, value);
+        }
+    }
+    response
+}
+
+/// `json` with `fill` applied to every string in it, keys aside, so a
+/// filled-in value can never break the JSON
+fn fill_strings(json: Value, fill: &impl Fn(&str) -> String) -> Value {
+    match json {
+        Value::String(text) => Value::String(fill(&text)),
+        Value::Array(items) => {
+            Value::Array(items.into_iter().map(|v| fill_strings(v, fill)).collect())
+        }
+        Value::Object(object) => Value::Object(
+            object
+                .into_iter()
+                .map(|(key, value)| (key, fill_strings(value, fill)))
+                .collect(),
+        ),
+        other => other,
+    }
+}
+
+/// A rule's conditions must all hold
+fn rule_matches(
+    rule: &ErrorRule,
+    pattern: Option<&Regex>,
+    model: &str,
+    text: &str,
+    headers: &HeaderMap,
+    prompt_tokens: impl Fn() -> u64,
+) -> bool {
+    let model_ok = rule
+        .model
+        .as_deref()
+        .is_none_or(|glob| glob_match(glob, model));
+    let header_ok = rule.header.as_deref().is_none_or(|name| {
+        headers.get(name).is_some_and(|value| {
+            rule.header_value
+                .as_deref()
+                .is_none_or(|expected| value.as_bytes() == expected.as_bytes())
+        })
+    });
+    let text_ok = rule
+        .contains
+        .as_deref()
+        .is_none_or(|needle| text.contains(needle))
+        && pattern.is_none_or(|pattern| pattern.is_match(text));
+    let size_ok = (rule.min_prompt_tokens.is_none() && rule.max_prompt_tokens.is_none()) || {
+        let tokens = prompt_tokens();
+        rule.min_prompt_tokens.is_none_or(|min| tokens >= min)
+            && rule.max_prompt_tokens.is_none_or(|max| tokens <= max)
+    };
+    model_ok && header_ok && text_ok && size_ok
+}
+
+/// The prompt's text: every string in the prompt fields, one per line
+pub(super) fn prompt_text(json: &Value) -> String {
+    let mut text = String::new();
+    for field in PROMPT_FIELDS {
+        collect_text(&json[field], &mut text);
+    }
+    text
+}
+
+/// Strings in message content, skipping roles, ids and other metadata
+fn collect_text(value: &Value, text: &mut String) {
+    match value {
+        Value::String(s) => {
+            text.push_str(s);
+            text.push('\n');
+        }
+        Value::Array(items) => items.iter().for_each(|item| collect_text(item, text)),
+        Value::Object(object) => {
+            for key in ["content", "text"] {
+                if let Some(value) = object.get(key) {
+                    collect_text(value, text);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Middleware: fail generation requests matching an error rule
+pub async fn inject_rule_errors(
+    State(state): State<Arc<AppState>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let Some(rules) = state.error_rules.clone() else {
+        return next.run(request).await;
+    };
+    let path = request.uri().path().to_string();
+    let endpoint = (request.method() == "POST")
+        .then(|| endpoint_type(&path))
+        .flatten();
+    let Some(endpoint) = endpoint else {
+        return next.run(request).await;
+    };
+
+    let body = match ParsedBody::read(&mut request).await {
+        Ok(body) => body,
+        Err(response) => return response,
+    };
+    let model = body.model.as_str();
+    let text = prompt_text(&body.json);
+    let prompt_tokens = || state.pipeline.count_tokens(&text) as u64;
+    let Some(error) = rules.check(model, &text, request.headers(), prompt_tokens) else {
+        return next.run(request).await;
+    };
+
+    let request = state.begin(model, body.stream, endpoint);
+    match error {
+        RuleError::Simulated(error) => {
+            tracing::info!(
+                model,
+                status = error.status_code(),
+                "Request matched an error rule"
+            );
+            request.fail_with(ErrorCause::Rule, error.clone());
+            error_response(&path, endpoint, &error)
+        }
+        RuleError::Template(template) => {
+            tracing::info!(
+                model,
+                status = template.status,
+                "Request matched an error rule with a custom response"
+            );
+            request.fail(template.status);
+            template_response(template, model)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn rules_for(toml: &str) -> ErrorRules {
+        let errors: ErrorsConfig = toml::from_str(toml).unwrap();
+        ErrorRules::new(&errors).unwrap()
+    }
+
+    #[test]
+    fn test_first_matching_rule_decides() {
+        let rules = rules_for(
+            r#"
+            [[rules]]
+            model = "gpt-4o*"
+            status = 429
+            retry_after_seconds = 7
+
+            [[rules]]
+            contains = "boom"
+            status = 500
+            "#,
+        );
+        let headers = HeaderMap::new();
+        assert_eq!(
+            rules.check("gpt-4o-mini", "boom", &headers, || 0),
+            Some(RuleError::Simulated(SimulatedError::RateLimit {
+                retry_after_seconds: 7
+            }))
+        );
+        assert_eq!(
+            rules.check("gpt-5", "say boom", &headers, || 0),
+            Some(RuleError::Simulated(SimulatedError::ServerError))
+        );
+        assert_eq!(rules.check("gpt-5", "hello", &headers, || 0), None);
+    }
+
+    #[test]
+    fn test_prompt_tokens_counted_only_when_bounded() {
+        let rules = rules_for("[[rules]]\nmin_prompt_tokens = 100\nstatus = 400\n");
+        let headers = HeaderMap::new();
+        assert!(rules.check("gpt-5", "", &headers, || 100).is_some());
+        assert!(rules.check("gpt-5", "", &headers, || 99).is_none());
+
+        let unbounded = rules_for("[[rules]]\nstatus = 503\n");
+        let error = unbounded.check("gpt-5", "", &headers, || panic!("counted"));
+        assert_eq!(
+            error,
+            Some(RuleError::Simulated(SimulatedError::ServiceUnavailable))
+        );
+    }
+
+    #[test]
+    fn test_prompt_text_skips_metadata() {
+        let body = json!({
+            "model": "claude-sonnet-4",
+            "system": "Be brief",
+            "messages": [
+                {"role": "user", "content": [{"type": "text", "text": "What is 2+2?"}]},
+                {"role": "assistant", "content": "4"}
+            ]
+        });
+        assert_eq!(prompt_text(&body), "Be brief\nWhat is 2+2?\n4\n");
+    }
+
+    #[test]
+    fn test_template_rules() {
+        let rules = rules_for(
+            r#"
+            [[rules]]
+            model = "gpt-5"
+            response = "bare"
+
+            [responses.bare]
+            status = 502
+            "#,
+        );
+        let headers = HeaderMap::new();
+        let Some(RuleError::Template(template)) = rules.check("gpt-5", "", &headers, || 0) else {
+            panic!("expected the template");
+        };
+        assert_eq!(template.status, 502);
+        let response = template_response(template, "gpt-5");
+        assert_eq!(response.status().as_u16(), 502);
+        assert!(response.headers().get(header::CONTENT_TYPE).is_none());
+
+        let both: ErrorsConfig = toml::from_str(
+            "[[rules]]\nstatus = 500\nresponse = \"bare\"\n[responses.bare]\nstatus = 502\n",
+        )
+        .unwrap();
+        assert!(ErrorRules::new(&both).unwrap_err().contains("one of"));
+        let neither: ErrorsConfig = toml::from_str("[[rules]]\nmodel = \"x\"\n").unwrap();
+        assert!(ErrorRules::new(&neither).is_err());
+    }
+
+    #[test]
+    fn test_fill_strings_leaves_json_intact() {
+        let json = json!({"error": {"message": "{model}", "code": 7}});
+        let filled = fill_strings(json, &|text: &str| text.replace("{model}", "a\"b"));
+        assert_eq!(filled, json!({"error": {"message": "a\"b", "code": 7}}));
+    }
+
+    #[test]
+    fn test_bad_rules_rejected() {
+        let errors: ErrorsConfig =
+            toml::from_str("[[rules]]\nmatches = \"(\"\nstatus = 400\n").unwrap();
+        assert!(ErrorRules::new(&errors).is_err());
+        let errors: ErrorsConfig = toml::from_str("[[rules]]\nstatus = 418\n").unwrap();
+        assert!(ErrorRules::new(&errors).unwrap_err().contains("418"));
+    }
+}
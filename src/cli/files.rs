@@ -0,0 +1,180 @@
+// File Hosting Module
+// In-memory store for generated artifacts (images, tool outputs), served at
+// `/llmsim/files/{id}` so responses can carry URLs that actually download.
+//
+// Decision: ids are derived from the content, so storing the same bytes twice
+// yields one file and one URL, and a URL stays valid for as long as its bytes
+// are kept. The store is bounded by total size and drops the oldest files
+// first; a simulator restart forgets everything, which matches short-lived
+// download links from the real APIs. URLs are absolute because SDKs fetch
+// them as-is, with the authority taken from `[files] base_url` or the
+// request's `Host`.
+
+use super::config::FilesConfig;
+use axum::body::Bytes;
+use axum::http::{header, HeaderMap};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+/// One hosted file
+#[derive(Debug, Clone)]
+pub struct StoredFile {
+    pub content_type: String,
+    pub bytes: Bytes,
+}
+
+/// Content-addressed files, oldest dropped first beyond `max_bytes`
+pub struct FileStore {
+    base_url: Option<String>,
+    max_bytes: usize,
+    inner: Mutex<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    files: HashMap<String, StoredFile>,
+    /// Ids in the order they were stored
+    order: VecDeque<String>,
+    bytes: usize,
+}
+
+impl FileStore {
+    pub fn new(config: &FilesConfig) -> Self {
+        Self {
+            base_url: config
+                .base_url
+                .as_ref()
+                .map(|url| url.trim_end_matches('/').to_string()),
+            max_bytes: config.max_bytes,
+            inner: Mutex::new(Inner::default()),
+        }
+    }
+
+    /// Store `bytes` and return their id; the same content always gets
+    /// the same id
+    pub fn put(&self, bytes: impl Into<Bytes>, content_type: &str) -> String {
+        let bytes = bytes.into();
+        let id = content_id(&bytes, content_type);
+        let mut inner = self.inner.lock().unwrap();
+        if inner.files.contains_key(&id) {
+            return id;
+        }
+        inner.bytes += bytes.len();
+        inner.order.push_back(id.clone());
+        inner.files.insert(
+            id.clone(),
+            StoredFile {
+                content_type: content_type.to_string(),
+                bytes,
+            },
+        );
+        // Keep at least the file just stored, even when it alone is too big
+        while inner.bytes > self.max_bytes && inner.order.len() > 1 {
+            if let Some(oldest) = inner.order.pop_front() {
+                if let Some(file) = inner.files.remove(&oldest) {
+                    inner.bytes -= file.bytes.len();
+                }
+            }
+        }
+        id
+    }
+
+    pub fn get(&self, id: &str) -> Option<StoredFile> {
+        self.inner.lock().unwrap().files.get(id).cloned()
+    }
+
+    /// Absolute download URL for `id`, as seen by the client that sent
+    /// `headers`
+    pub fn url(&self, headers: &HeaderMap, id: &str) -> String {
+        let base = self.base_url.clone().unwrap_or_else(|| {
+            let host = headers
+                .get(header::HOST)
+                .and_then(|host| host.to_str().ok())
+                .unwrap_or("localhost");
+            format!("http://{}", host)
+        });
+        format!("{}/llmsim/files/{}", base, id)
+    }
+
+    /// Store `bytes` and return their download URL
+    pub fn host(&self, headers: &HeaderMap, bytes: impl Into<Bytes>, content_type: &str) -> String {
+        let id = self.put(bytes, content_type);
+        self.url(headers, &id)
+    }
+}
+
+/// `file-` and the FNV-1a hash of the content type and bytes, in hex
+fn content_id(bytes: &[u8], content_type: &str) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    let content = content_type
+        .as_bytes()
+        .iter()
+        .chain([0].iter())
+        .chain(bytes);
+    for byte in content {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    format!("file-{:016x}{:08x}", hash, bytes.len() as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store(max_bytes: usize) -> FileStore {
+        FileStore::new(&FilesConfig {
+            base_url: None,
+            max_bytes,
+        })
+    }
+
+    #[test]
+    fn test_content_addressed() {
+        let files = store(1024);
+        let a = files.put(&b"hello"[..], "text/plain");
+        assert_eq!(files.put(&b"hello"[..], "text/plain"), a);
+        assert_ne!(files.put(&b"hello"[..], "text/csv"), a);
+        assert_ne!(files.put(&b"world"[..], "text/plain"), a);
+
+        let file = files.get(&a).unwrap();
+        assert_eq!(file.content_type, "text/plain");
+        assert_eq!(&file.bytes[..], b"hello");
+        assert!(files.get("file-missing").is_none());
+    }
+
+    #[test]
+    fn test_oldest_dropped_beyond_limit() {
+        let files = store(10);
+        let first = files.put(&b"12345"[..], "text/plain");
+        let second = files.put(&b"67890"[..], "text/plain");
+        let third = files.put(&b"abcde"[..], "text/plain");
+        assert!(files.get(&first).is_none());
+        assert!(files.get(&second).is_some());
+        assert!(files.get(&third).is_some());
+
+        // A single file over the limit is still kept
+        let big = files.put(vec![0u8; 64], "application/octet-stream");
+        assert!(files.get(&big).is_some());
+    }
+
+    #[test]
+    fn test_url() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::HOST, "127.0.0.1:8080".parse().unwrap());
+        let files = store(1024);
+        assert_eq!(
+            files.url(&headers, "file-1"),
+            "http://127.0.0.1:8080/llmsim/files/file-1"
+        );
+
+        let files = FileStore::new(&FilesConfig {
+            base_url: Some("https://sim.example.com/".to_string()),
+            max_bytes: 1024,
+        });
+        assert_eq!(
+            files.url(&headers, "file-1"),
+            "https://sim.example.com/llmsim/files/file-1"
+        );
+    }
+}
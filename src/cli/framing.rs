@@ -0,0 +1,182 @@
+// Framing Module
+// Router middleware applying `[framing]`: non-streaming bodies sent chunked
+// or with an explicit `Content-Length` whatever their size is known to be,
+// and chunked responses ended with trailer fields, to reproduce client bugs
+// that only show with one provider's framing.
+//
+// Decision: a middleware outside compression that rewraps the finished
+// response body, so it frames the bytes actually sent and no handler knows
+// about it. Hyper picks the framing from the headers and the body: without a
+// `Content-Length` and with an unknown size it chunks, and it only writes
+// trailers declared in a `Trailer` header to clients that sent
+// `TE: trailers`, as RFC 9110 asks. Streams are never buffered or re-chunked,
+// which would change their timing; they only get the trailers.
+
+use super::config::BodyFraming;
+use super::state::AppState;
+use axum::{
+    body::{to_bytes, Body, Bytes, HttpBody},
+    extract::{Request, State},
+    http::{header, HeaderMap, HeaderName, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use http_body::Frame;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{ready, Context, Poll};
+
+/// Trailer field carrying the number of body bytes sent
+pub const BODY_BYTES_TRAILER: &str = "x-llmsim-body-bytes";
+
+/// Middleware: reframe response bodies as `[framing]` asks
+pub async fn frame_responses(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let framing = state.config.framing.clone();
+    let response = next.run(request).await;
+    if matches!(
+        response.status(),
+        StatusCode::SWITCHING_PROTOCOLS | StatusCode::NO_CONTENT | StatusCode::NOT_MODIFIED
+    ) {
+        return response;
+    }
+
+    let content_type = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    // gRPC carries its status in trailers of its own, which ours would replace
+    if content_type.starts_with("application/grpc") {
+        return response;
+    }
+    let streaming = content_type.starts_with("text/event-stream");
+    let (mut parts, body) = response.into_parts();
+    let body = match framing.body {
+        BodyFraming::ContentLength if !streaming => {
+            let Ok(bytes) = to_bytes(body, usize::MAX).await else {
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            };
+            parts
+                .headers
+                .insert(header::CONTENT_LENGTH, bytes.len().into());
+            return Response::from_parts(parts, Body::from(bytes));
+        }
+        BodyFraming::Chunked if !streaming => {
+            FramedBody::new(body, framing.chunk_bytes, framing.trailers)
+        }
+        _ if framing.trailers && !parts.headers.contains_key(header::CONTENT_LENGTH) => {
+            FramedBody::new(body, None, true)
+        }
+        _ => return Response::from_parts(parts, body),
+    };
+    parts.headers.remove(header::CONTENT_LENGTH);
+    if body.trailers {
+        parts.headers.insert(
+            header::TRAILER,
+            HeaderValue::from_static(BODY_BYTES_TRAILER),
+        );
+    }
+    Response::from_parts(parts, Body::new(body))
+}
+
+/// A body of unknown size, so it is sent chunked: the inner body's data cut
+/// into chunks of at most `chunk_bytes`, then optionally trailers
+struct FramedBody {
+    inner: Body,
+    chunk_bytes: Option<usize>,
+    trailers: bool,
+    pending: Bytes,
+    sent: u64,
+    done: bool,
+}
+
+impl FramedBody {
+    fn new(inner: Body, chunk_bytes: Option<usize>, trailers: bool) -> Self {
+        Self {
+            inner,
+            chunk_bytes,
+            trailers,
+            pending: Bytes::new(),
+            sent: 0,
+            done: false,
+        }
+    }
+}
+
+impl HttpBody for FramedBody {
+    type Data = Bytes;
+    type Error = axum::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, axum::Error>>> {
+        let this = self.get_mut();
+        loop {
+            if !this.pending.is_empty() {
+                let size = this
+                    .chunk_bytes
+                    .map_or(this.pending.len(), |max| max.min(this.pending.len()));
+                this.sent += size as u64;
+                return Poll::Ready(Some(Ok(Frame::data(this.pending.split_to(size)))));
+            }
+            if this.done {
+                return Poll::Ready(None);
+            }
+            match ready!(Pin::new(&mut this.inner).poll_frame(cx)) {
+                // The inner body's own trailers are replaced by ours
+                Some(Ok(frame)) => {
+                    if let Ok(data) = frame.into_data() {
+                        this.pending = data;
+                    }
+                }
+                Some(Err(e)) => return Poll::Ready(Some(Err(e))),
+                None => {
+                    this.done = true;
+                    if this.trailers {
+                        let mut trailers = HeaderMap::new();
+                        trailers.insert(
+                            HeaderName::from_static(BODY_BYTES_TRAILER),
+                            this.sent.into(),
+                        );
+                        return Poll::Ready(Some(Ok(Frame::trailers(trailers))));
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn frames(mut body: FramedBody) -> (Vec<usize>, Option<HeaderMap>) {
+        let mut sizes = Vec::new();
+        let mut trailers = None;
+        while let Some(frame) = std::future::poll_fn(|cx| Pin::new(&mut body).poll_frame(cx)).await
+        {
+            match frame.unwrap().into_data() {
+                Ok(data) => sizes.push(data.len()),
+                Err(frame) => trailers = frame.into_trailers().ok(),
+            }
+        }
+        (sizes, trailers)
+    }
+
+    #[tokio::test]
+    async fn test_chunks_and_trailers() {
+        let body = FramedBody::new(Body::from(vec![b'x'; 10]), Some(4), true);
+        let (sizes, trailers) = frames(body).await;
+        assert_eq!(sizes, [4, 4, 2]);
+        assert_eq!(trailers.unwrap()[BODY_BYTES_TRAILER], "10");
+
+        let (sizes, trailers) = frames(FramedBody::new(Body::from("abc"), None, false)).await;
+        assert_eq!(sizes, [3]);
+        assert!(trailers.is_none());
+    }
+}
@@ -0,0 +1,578 @@
+// gRPC Module
+// The `llmsim.v1.Simulator` service of `proto/llmsim/v1/simulator.proto`:
+// Generate and GenerateStream, for gateways that speak gRPC internally.
+//
+// Decision: the service is routes on the HTTP router, not a second server.
+// Hyper tells a gRPC client's HTTP/2 preface from HTTP/1.1 on the same port,
+// and the handlers begin requests in the same pipeline as the HTTP APIs, so
+// the generator, latency profiles, error injection and stats are shared
+// without a shim. The few protobuf messages are encoded by hand, as the
+// upstream client writes its own HTTP/1.1: they are small and fixed, and a
+// generated gRPC stack would add a code generator and a second HTTP stack to
+// the build for two RPCs. Messages are never compressed. Errors are answered
+// the gRPC way, as HTTP 200 with the status in `grpc-status` and no body.
+
+use super::handlers::{apply_cost, count_request_tokens};
+use super::state::AppState;
+use crate::ids::{new_id, IdKind};
+use crate::openai::{ChatCompletionRequest, Message, StopCondition};
+use crate::pipeline::PipelineRequest;
+use crate::stream::TokenStreamBuilder;
+use crate::EndpointType;
+use async_stream::stream;
+use axum::{
+    body::{Body, Bytes, HttpBody},
+    extract::State,
+    http::{header, HeaderMap, HeaderName, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use futures_core::Stream;
+use futures_util::StreamExt;
+use http_body::Frame;
+use std::convert::Infallible;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{ready, Context, Poll};
+
+const GRPC_CONTENT_TYPE: &str = "application/grpc";
+const GRPC_STATUS: HeaderName = HeaderName::from_static("grpc-status");
+const GRPC_MESSAGE: HeaderName = HeaderName::from_static("grpc-message");
+
+/// gRPC status codes the service answers with
+mod code {
+    pub const INVALID_ARGUMENT: u16 = 3;
+    pub const DEADLINE_EXCEEDED: u16 = 4;
+    pub const NOT_FOUND: u16 = 5;
+    pub const RESOURCE_EXHAUSTED: u16 = 8;
+    pub const FAILED_PRECONDITION: u16 = 9;
+    pub const UNIMPLEMENTED: u16 = 12;
+    pub const INTERNAL: u16 = 13;
+    pub const UNAVAILABLE: u16 = 14;
+    pub const UNAUTHENTICATED: u16 = 16;
+}
+
+/// POST /llmsim.v1.Simulator/Generate
+pub async fn generate(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    if !is_grpc(&headers) {
+        return StatusCode::UNSUPPORTED_MEDIA_TYPE.into_response();
+    }
+    let call = match Call::begin(&state, &body, false) {
+        Ok(call) => call,
+        Err(status) => return status.into_response(),
+    };
+    let answer = call.answer(&state);
+    call.req.simulate_generation(answer.input_tokens).await;
+    call.req.finish(answer.input_tokens, answer.output_tokens);
+
+    let id = new_id(IdKind::Generation);
+    let mut message = Vec::new();
+    put_string(&mut message, 1, &id);
+    put_string(&mut message, 2, &call.request.model);
+    put_string(&mut message, 3, &answer.text);
+    put_string(&mut message, 4, answer.finish_reason);
+    put_message(&mut message, 5, &answer.usage());
+    let messages = futures_util::stream::once(async move { frame(&message) });
+    let response = grpc_response(Box::pin(messages));
+    apply_cost(
+        &state,
+        response,
+        &call.request.model,
+        answer.input_tokens,
+        answer.output_tokens,
+    )
+}
+
+/// POST /llmsim.v1.Simulator/GenerateStream
+pub async fn generate_stream(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    if !is_grpc(&headers) {
+        return StatusCode::UNSUPPORTED_MEDIA_TYPE.into_response();
+    }
+    let call = match Call::begin(&state, &body, true) {
+        Ok(call) => call,
+        Err(status) => return status.into_response(),
+    };
+    let answer = call.answer(&state);
+    let id = new_id(IdKind::Generation);
+    let mut chunks = TokenStreamBuilder::new(&call.request.model, answer.text.clone())
+        .id(id.clone())
+        .latency(call.req.request_latency(answer.input_tokens))
+        .sleeper(call.req.sleeper().clone())
+        .watchdog(call.req.stream_watchdog())
+        .build()
+        .into_chunk_stream();
+    let finish = call
+        .req
+        .finish_callback(answer.input_tokens, answer.output_tokens);
+    let (mut finish_reason, usage) = (answer.finish_reason, answer.usage());
+
+    let messages = stream! {
+        while let Some(chunk) = chunks.next().await {
+            // A stream out of time ends as cut short
+            if chunk.choices.iter().any(|c| c.finish_reason.as_deref() == Some("length")) {
+                finish_reason = "length";
+            }
+            let text = chunk.choices.into_iter().find_map(|choice| choice.delta.content);
+            if let Some(text) = text.filter(|text| !text.is_empty()) {
+                let mut message = Vec::new();
+                put_string(&mut message, 1, &id);
+                put_string(&mut message, 2, &text);
+                yield frame(&message);
+            }
+        }
+        let mut message = Vec::new();
+        put_string(&mut message, 1, &id);
+        put_string(&mut message, 3, finish_reason);
+        put_message(&mut message, 4, &usage);
+        yield frame(&message);
+        finish();
+    };
+    let response = grpc_response(Box::pin(call.req.guard_stream(Box::pin(messages))));
+    apply_cost(
+        &state,
+        response,
+        &call.request.model,
+        answer.input_tokens,
+        answer.output_tokens,
+    )
+}
+
+/// A `GenerateRequest` begun in the pipeline
+struct Call {
+    request: GenerateRequest,
+    chat: ChatCompletionRequest,
+    req: PipelineRequest,
+}
+
+/// What the simulated model answers a call with
+struct Answer {
+    text: String,
+    finish_reason: &'static str,
+    input_tokens: u32,
+    output_tokens: u32,
+}
+
+impl Answer {
+    /// The encoded `Usage` message
+    fn usage(&self) -> Vec<u8> {
+        let mut usage = Vec::new();
+        put_uint(&mut usage, 1, self.input_tokens);
+        put_uint(&mut usage, 2, self.output_tokens);
+        usage
+    }
+}
+
+impl Call {
+    /// Decode the request body and begin it, or fail it at once: malformed,
+    /// or failed by error injection
+    fn begin(state: &AppState, body: &[u8], streaming: bool) -> Result<Self, Status> {
+        let request = match body {
+            [1, ..] => {
+                return Err(Status::new(
+                    code::UNIMPLEMENTED,
+                    "compressed messages are not supported",
+                ))
+            }
+            [0, a, b, c, d, message @ ..] => {
+                let size = u32::from_be_bytes([*a, *b, *c, *d]) as usize;
+                message.get(..size).and_then(GenerateRequest::decode)
+            }
+            _ => None,
+        };
+        let Some(request) = request else {
+            return Err(Status::new(
+                code::INVALID_ARGUMENT,
+                "malformed GenerateRequest",
+            ));
+        };
+        tracing::info!(
+            model = %request.model,
+            stream = streaming,
+            messages = request.messages.len(),
+            "gRPC generate request"
+        );
+
+        let req = state.begin(&request.model, streaming, EndpointType::Grpc);
+        state.stats.record_request_messages(request.messages.len());
+        if let Some(message) = request.messages.last() {
+            req.set_prompt(&message.content);
+        }
+        if !request.user.is_empty() {
+            req.set_user(&request.user);
+        }
+        if let Some(error) = req.inject_error() {
+            return Err(Status {
+                code: status_code(error.status_code()),
+                message: error.to_error_response().error.message,
+                retry_after: error.retry_after(),
+            });
+        }
+        let chat = request.chat_request(streaming);
+        Ok(Self { request, chat, req })
+    }
+
+    /// Generate the answer, cut to `max_tokens`
+    fn answer(&self, state: &AppState) -> Answer {
+        let (text, _) = state.pipeline.generate_with_stop(&self.chat);
+        let max_tokens = (self.request.max_tokens > 0).then_some(self.request.max_tokens);
+        let (text, truncated) = state.pipeline.limit_output(text, max_tokens);
+        Answer {
+            input_tokens: count_request_tokens(&state.pipeline, &self.chat) as u32,
+            output_tokens: state.pipeline.count_tokens(&text) as u32,
+            text,
+            finish_reason: if truncated { "length" } else { "stop" },
+        }
+    }
+}
+
+/// The gRPC status for an HTTP status the pipeline failed a request with
+fn status_code(http_status: u16) -> u16 {
+    match http_status {
+        400 => code::INVALID_ARGUMENT,
+        401 => code::UNAUTHENTICATED,
+        404 => code::NOT_FOUND,
+        408 | 504 => code::DEADLINE_EXCEEDED,
+        410 => code::FAILED_PRECONDITION,
+        429 => code::RESOURCE_EXHAUSTED,
+        503 => code::UNAVAILABLE,
+        _ => code::INTERNAL,
+    }
+}
+
+/// A gRPC status an RPC failed with
+struct Status {
+    code: u16,
+    message: String,
+    retry_after: Option<u32>,
+}
+
+impl Status {
+    fn new(code: u16, message: &str) -> Self {
+        Self {
+            code,
+            message: message.to_string(),
+            retry_after: None,
+        }
+    }
+}
+
+impl IntoResponse for Status {
+    /// A response with no messages: only the status, in the headers
+    fn into_response(self) -> Response {
+        let mut response = Response::new(Body::empty());
+        let headers = response.headers_mut();
+        headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static(GRPC_CONTENT_TYPE),
+        );
+        headers.insert(GRPC_STATUS, self.code.into());
+        if let Ok(value) = HeaderValue::from_str(&percent_encode(&self.message)) {
+            headers.insert(GRPC_MESSAGE, value);
+        }
+        if let Some(seconds) = self.retry_after {
+            headers.insert(header::RETRY_AFTER, seconds.into());
+        }
+        response
+    }
+}
+
+fn is_grpc(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with(GRPC_CONTENT_TYPE))
+}
+
+/// `grpc-message` encoding: printable ASCII other than `%` is sent as is
+fn percent_encode(message: &str) -> String {
+    message
+        .bytes()
+        .map(|byte| match byte {
+            b' '..=b'~' if byte != b'%' => (byte as char).to_string(),
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+/// A successful response carrying `messages`, already framed
+fn grpc_response(messages: Pin<Box<dyn Stream<Item = Bytes> + Send>>) -> Response {
+    let mut response = Response::new(Body::new(GrpcBody {
+        messages,
+        done: false,
+    }));
+    response.headers_mut().insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static(GRPC_CONTENT_TYPE),
+    );
+    response
+}
+
+/// Response messages, then the trailers with `grpc-status: 0`
+struct GrpcBody {
+    messages: Pin<Box<dyn Stream<Item = Bytes> + Send>>,
+    done: bool,
+}
+
+impl HttpBody for GrpcBody {
+    type Data = Bytes;
+    type Error = Infallible;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, Infallible>>> {
+        if self.done {
+            return Poll::Ready(None);
+        }
+        match ready!(self.messages.as_mut().poll_next(cx)) {
+            Some(message) => Poll::Ready(Some(Ok(Frame::data(message)))),
+            None => {
+                self.done = true;
+                let mut trailers = HeaderMap::new();
+                trailers.insert(GRPC_STATUS, HeaderValue::from_static("0"));
+                Poll::Ready(Some(Ok(Frame::trailers(trailers))))
+            }
+        }
+    }
+}
+
+/// A message behind the uncompressed length prefix of the gRPC framing
+fn frame(message: &[u8]) -> Bytes {
+    let mut framed = Vec::with_capacity(5 + message.len());
+    framed.push(0);
+    framed.extend_from_slice(&(message.len() as u32).to_be_bytes());
+    framed.extend_from_slice(message);
+    Bytes::from(framed)
+}
+
+/// `llmsim.v1.GenerateRequest`
+#[derive(Debug, Default, PartialEq)]
+struct GenerateRequest {
+    model: String,
+    messages: Vec<GenerateMessage>,
+    max_tokens: u32,
+    stop: Vec<String>,
+    user: String,
+}
+
+/// `llmsim.v1.Message`
+#[derive(Debug, Default, PartialEq)]
+struct GenerateMessage {
+    role: String,
+    content: String,
+}
+
+impl GenerateRequest {
+    fn decode(buf: &[u8]) -> Option<Self> {
+        let mut request = Self::default();
+        for (field, value) in fields(buf)? {
+            match (field, value) {
+                (1, Wire::Bytes(bytes)) => request.model = string(bytes)?,
+                (2, Wire::Bytes(bytes)) => {
+                    let mut message = GenerateMessage::default();
+                    for (field, value) in fields(bytes)? {
+                        match (field, value) {
+                            (1, Wire::Bytes(bytes)) => message.role = string(bytes)?,
+                            (2, Wire::Bytes(bytes)) => message.content = string(bytes)?,
+                            _ => {}
+                        }
+                    }
+                    request.messages.push(message);
+                }
+                (3, Wire::Varint(value)) => request.max_tokens = value as u32,
+                (4, Wire::Bytes(bytes)) => request.stop.push(string(bytes)?),
+                (5, Wire::Bytes(bytes)) => request.user = string(bytes)?,
+                _ => {}
+            }
+        }
+        Some(request)
+    }
+
+    /// The chat completion request the pipeline generates for
+    fn chat_request(&self, stream: bool) -> ChatCompletionRequest {
+        let messages = self
+            .messages
+            .iter()
+            .map(|message| match message.role.as_str() {
+                "system" | "developer" => Message::system(&message.content),
+                "assistant" => Message::assistant(&message.content),
+                _ => Message::user(&message.content),
+            })
+            .collect();
+        let max_tokens = (self.max_tokens > 0).then_some(self.max_tokens);
+        ChatCompletionRequest {
+            model: self.model.clone(),
+            messages,
+            temperature: None,
+            top_p: None,
+            n: None,
+            stream,
+            stop: (!self.stop.is_empty()).then(|| StopCondition::Multiple(self.stop.clone())),
+            max_tokens,
+            max_completion_tokens: max_tokens,
+            presence_penalty: None,
+            frequency_penalty: None,
+            logit_bias: None,
+            user: (!self.user.is_empty()).then(|| self.user.clone()),
+            tools: None,
+            tool_choice: None,
+            parallel_tool_calls: None,
+            response_format: None,
+            seed: None,
+            stream_options: None,
+            functions: None,
+            function_call: None,
+            store: None,
+            metadata: None,
+        }
+    }
+}
+
+/// A protobuf field value, for the wire types the service reads
+enum Wire<'a> {
+    Varint(u64),
+    Bytes(&'a [u8]),
+    Fixed,
+}
+
+/// The fields of a protobuf message, in order; None if it is malformed
+fn fields(mut buf: &[u8]) -> Option<Vec<(u64, Wire<'_>)>> {
+    let mut fields = Vec::new();
+    while !buf.is_empty() {
+        let key = read_varint(&mut buf)?;
+        let value = match key & 7 {
+            0 => Wire::Varint(read_varint(&mut buf)?),
+            1 => {
+                buf = buf.get(8..)?;
+                Wire::Fixed
+            }
+            2 => {
+                let len = usize::try_from(read_varint(&mut buf)?).ok()?;
+                let bytes = buf.get(..len)?;
+                buf = &buf[len..];
+                Wire::Bytes(bytes)
+            }
+            5 => {
+                buf = buf.get(4..)?;
+                Wire::Fixed
+            }
+            _ => return None,
+        };
+        fields.push((key >> 3, value));
+    }
+    Some(fields)
+}
+
+fn read_varint(buf: &mut &[u8]) -> Option<u64> {
+    let mut value = 0;
+    for shift in (0..64).step_by(7) {
+        let (&byte, rest) = buf.split_first()?;
+        *buf = rest;
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+fn string(bytes: &[u8]) -> Option<String> {
+    std::str::from_utf8(bytes).ok().map(str::to_string)
+}
+
+fn put_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn put_uint(out: &mut Vec<u8>, field: u64, value: u32) {
+    if value != 0 {
+        put_varint(out, field << 3);
+        put_varint(out, value.into());
+    }
+}
+
+fn put_message(out: &mut Vec<u8>, field: u64, message: &[u8]) {
+    put_varint(out, field << 3 | 2);
+    put_varint(out, message.len() as u64);
+    out.extend_from_slice(message);
+}
+
+/// Proto3 leaves empty strings out
+fn put_string(out: &mut Vec<u8>, field: u64, value: &str) {
+    if !value.is_empty() {
+        put_message(out, field, value.as_bytes());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_request() {
+        let mut message = Vec::new();
+        put_string(&mut message, 1, "user");
+        put_string(&mut message, 2, "Hello");
+        let mut buf = Vec::new();
+        put_string(&mut buf, 1, "gpt-5");
+        put_message(&mut buf, 2, &message);
+        put_uint(&mut buf, 3, 300);
+        // Unknown fields of every wire type are skipped
+        put_uint(&mut buf, 9, 1);
+        buf.extend_from_slice(&[9 << 3 | 1, 0, 0, 0, 0, 0, 0, 0, 0]);
+        buf.extend_from_slice(&[9 << 3 | 5, 0, 0, 0, 0]);
+        put_string(&mut buf, 4, "END");
+        put_string(&mut buf, 5, "alice");
+
+        let request = GenerateRequest::decode(&buf).unwrap();
+        assert_eq!(
+            request,
+            GenerateRequest {
+                model: "gpt-5".to_string(),
+                messages: vec![GenerateMessage {
+                    role: "user".to_string(),
+                    content: "Hello".to_string(),
+                }],
+                max_tokens: 300,
+                stop: vec!["END".to_string()],
+                user: "alice".to_string(),
+            }
+        );
+        let chat = request.chat_request(false);
+        assert_eq!(chat.max_tokens, Some(300));
+        assert_eq!(chat.stop_sequences(), vec!["END"]);
+
+        assert!(GenerateRequest::decode(&buf[..buf.len() - 1]).is_none());
+        assert_eq!(
+            GenerateRequest::decode(&[]),
+            Some(GenerateRequest::default())
+        );
+    }
+
+    #[test]
+    fn test_varints() {
+        for value in [0, 1, 127, 128, 300, u64::from(u32::MAX), u64::MAX] {
+            let mut buf = Vec::new();
+            put_varint(&mut buf, value);
+            assert_eq!(read_varint(&mut buf.as_slice()), Some(value));
+        }
+        assert_eq!(read_varint(&mut [0x80].as_slice()), None);
+    }
+
+    #[test]
+    fn test_percent_encode() {
+        assert_eq!(percent_encode("Rate limit: 100%"), "Rate limit: 100%25");
+        assert_eq!(percent_encode("naïve\n"), "na%C3%AFve%0A");
+    }
+}
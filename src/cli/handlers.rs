@@ -1,10 +1,23 @@
 // HTTP Handlers Module
 // Implements OpenAI-compatible and OpenResponses-compatible API endpoints.
 
+use super::api_version::{ApiBehavior, ApiVersion};
+use super::chunk_faults::fault_events;
+use super::completions_store::{self, validate_metadata, CompletionFilter, MetadataUpdate};
+use super::config::Utf8Boundaries;
+use super::error_catalog::{self, ForceSpec};
+use super::keys::{is_admin, KeyError, KeyStore, NewKey};
+use super::malformed::FrameMalformer;
+use super::responses_store::{self, paginate, ListParams, StreamParams};
+use super::resume::{number_events, sequence_of, StreamLog, StreamLogs};
 use super::state::AppState;
-use crate::ids::{prefixed_id, unix_timestamp};
+use super::usage::{UsageEndpoint, UsageLedger, UsageQuery};
+use crate::ids::{new_id, unix_timestamp, IdKind};
+use crate::schema::{validate_stream, PayloadSchema};
 use crate::{
-    create_generator,
+    code_interpreter::{self, CodeRun},
+    drill::DrillSpec,
+    errors::SimulatedError,
     image_stream::ImageStream,
     imagegen::{base64_encode, render_png, PlaceholderSpec},
     openai::{
@@ -13,29 +26,38 @@ use crate::{
             ImageGenerationResponse, ImageInputTokensDetails, ImagesUsage,
         },
         ChatCompletionRequest, ChatCompletionResponse, ErrorResponse, InputItem, InputRole,
-        MessageContent, Model, ModelsResponse, OutputContentPart, OutputItem, OutputRole,
-        OutputTokensDetails, ReasoningConfig, ResponseStatus, ResponsesErrorResponse,
-        ResponsesInput, ResponsesRequest, ResponsesResponse, ResponsesUsage, Usage,
+        ListResponse, MessageContent, Model, ModelsResponse, OutputContentPart, OutputItem,
+        OutputRole, OutputTokensDetails, ReasoningConfig, ResponseStatus, ResponsesError,
+        ResponsesErrorResponse, ResponsesInput, ResponsesRequest, ResponsesResponse, ResponsesTool,
+        ResponsesToolChoice, ResponsesUsage, StreamOptions, ToolCallFormat, Truncation, Usage,
     },
     openresponses::{
         self, OpenResponsesStreamBuilder, Response as OpenResponsesResponse, ResponseRequest,
         Usage as OpenResponsesUsage,
     },
-    script::{ScriptedResponse, SimError, SimTurn},
+    pipeline::{
+        json_mode, FormatAdapter, OpenAIFormat, OpenResponsesFormat, PipelineRequest,
+        RequestPipeline, ResponsesFormat,
+    },
+    pool::{PoolFill, PooledResponse},
+    script::{ScriptedResponse, SimError, SimToolCall},
     script_stream::{build_chat_completion_response, materialize_tool_calls, ScriptedChatStream},
-    EndpointType, ErrorInjector, LatencyProfile, ResponsesTokenStreamBuilder, TokenStreamBuilder,
+    EndpointType, RequestOverrides, ResponsesTokenStreamBuilder, Stamps, TokenStreamBuilder,
 };
 use axum::{
     body::Body,
-    extract::{Path, State},
-    http::{header, StatusCode},
+    extract::{
+        ws::{rejection::WebSocketUpgradeRejection, WebSocketUpgrade},
+        Path, State,
+    },
+    http::{header, HeaderMap, StatusCode, Uri},
     response::{IntoResponse, Response},
     Json,
 };
 use futures_util::StreamExt;
 use rand::prelude::IndexedRandom;
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::Duration;
 
 /// Result of response generation for the Responses API.
 /// Shared between the HTTP and WebSocket handlers.
@@ -44,7 +66,16 @@ pub(crate) struct ResponseGenerationResult {
     pub usage: ResponsesUsage,
     pub reasoning_tokens: usize,
     pub reasoning_summary: Option<String>,
-    pub latency: LatencyProfile,
+    /// Output was cut off by `max_output_tokens` (`status: incomplete`)
+    pub truncated: bool,
+    /// `content` is a refusal message, sent as a `refusal` content part
+    pub refused: bool,
+    /// Citation annotations on the generated text
+    pub annotations: Vec<serde_json::Value>,
+    /// MCP listings, approval requests and calls made before answering
+    pub mcp_items: Vec<OutputItem>,
+    /// Call made before answering, when the code interpreter is offered
+    pub code_interpreter_call: Option<OutputItem>,
 }
 
 /// Parameters for response generation.
@@ -56,6 +87,11 @@ pub(crate) struct ResponseGenerationParams<'a> {
     pub top_p: Option<f32>,
     pub max_output_tokens: Option<u32>,
     pub reasoning: &'a Option<ReasoningConfig>,
+    pub tools: Option<&'a [ResponsesTool]>,
+    pub tool_choice: Option<&'a ResponsesToolChoice>,
+    pub include: Option<&'a [String]>,
+    /// Request headers, for the host in generated file URLs
+    pub headers: &'a HeaderMap,
 }
 
 /// Generate a response for the Responses API.
@@ -64,35 +100,33 @@ pub(crate) fn generate_responses_result(
     state: &AppState,
     params: &ResponseGenerationParams<'_>,
 ) -> ResponseGenerationResult {
-    // Get latency profile
-    let latency =
-        if state.config.latency.profile.is_some() || state.config.latency.ttft_mean_ms.is_some() {
-            state.config.latency_profile()
-        } else {
-            LatencyProfile::from_model(params.model)
-        };
-
     // Extract text from input
     let input_text = extract_input_text(params.input, params.instructions);
 
     // Scripted mode: take the next turn and reduce it to a text body.
     // (Tool calls in streaming Responses API aren't implemented in v1;
     // see specs/scripted-mode.md.)
-    let content = if let Some(script) = state.script.as_ref() {
-        match script.next_turn() {
-            ScriptedResponse::Turn(SimTurn::Assistant { text }) => text,
-            ScriptedResponse::Turn(SimTurn::Mixed { text, .. }) => text,
-            ScriptedResponse::Turn(SimTurn::ToolCalls { .. }) => String::new(),
-            ScriptedResponse::Turn(SimTurn::Error(err)) => {
+    let refusal = state
+        .script
+        .is_none()
+        .then(|| state.pipeline.refusal())
+        .flatten();
+    let (content, truncated) = if let Some(script) = state.script.as_ref() {
+        let content = match script.next_turn_with_tool_results(params.input.tool_result_ids()) {
+            ScriptedResponse::Turn(turn) => match turn.into_parts() {
+                Ok((text, _)) => text.unwrap_or_default(),
                 // Surface the error as the response text in this code
                 // path; streaming Responses API doesn't have a clean
                 // way to abort mid-stream from this helper. Callers
                 // who need error semantics on this endpoint should use
                 // non-streaming requests.
-                format!("[llmsim scripted error: {}]", err.message())
-            }
+                Err(err) => format!("[llmsim scripted error: {}]", err.message()),
+            },
             ScriptedResponse::Exhausted => "[llmsim script exhausted]".to_string(),
-        }
+        };
+        (content, false)
+    } else if let Some(refusal) = refusal {
+        (refusal.to_string(), false)
     } else {
         // Create a minimal ChatCompletionRequest for the generator
         let chat_request = crate::openai::ChatCompletionRequest {
@@ -111,23 +145,76 @@ pub(crate) fn generate_responses_result(
             user: None,
             tools: None,
             tool_choice: None,
+            parallel_tool_calls: None,
             response_format: None,
             seed: None,
+            stream_options: None,
+            functions: None,
+            function_call: None,
+            store: None,
+            metadata: None,
         };
 
-        let generator = create_generator(
-            &state.config.response.generator,
-            state.config.response.target_tokens,
-        );
-        generator.generate(&chat_request)
+        let content = state.pipeline.generate(&chat_request);
+        state
+            .pipeline
+            .limit_output(content, params.max_output_tokens)
+    };
+    // Scripted turns and refusals are sent as they are, without citations
+    let annotations = if state.script.is_none() && refusal.is_none() {
+        state.pipeline.citations(&content)
+    } else {
+        Vec::new()
     };
 
+    // The code the model writes for the interpreter is output too
+    let code_run = (state.script.is_none()
+        && refusal.is_none()
+        && code_interpreter::is_called(params.tools, params.tool_choice))
+    .then(|| CodeRun::for_prompt(&input_text));
+    let code_tokens = code_run
+        .as_ref()
+        .map_or(0, |run| state.pipeline.count_tokens(&run.code));
+    let code_interpreter_call = code_run.map(|run| {
+        let include_outputs = params
+            .include
+            .is_some_and(|include| include.iter().any(|i| i == "code_interpreter_call.outputs"));
+        // A "chart" the code drew, downloadable from the file store
+        let image_url = include_outputs.then(|| {
+            let png = render_png(&PlaceholderSpec {
+                width: 256,
+                height: 256,
+                prompt: &input_text,
+                model: params.model,
+                quality: "low",
+                blockiness: 1,
+            });
+            state.files.host(params.headers, png, "image/png")
+        });
+        run.into_item(image_url, include_outputs)
+    });
+
+    // MCP call arguments are written by the model as well
+    let mcp_items = if state.script.is_none() && refusal.is_none() {
+        state
+            .mcp
+            .items_for(params.tools, params.tool_choice, params.input, &input_text)
+    } else {
+        Vec::new()
+    };
+    let mcp_tokens: usize = mcp_items
+        .iter()
+        .map(|item| match item {
+            OutputItem::McpApprovalRequest { arguments, .. }
+            | OutputItem::McpCall { arguments, .. } => state.pipeline.count_tokens(arguments),
+            _ => 0,
+        })
+        .sum();
+
     // Count tokens
-    let input_tokens = crate::count_tokens_default(&input_text)
-        .unwrap_or(input_text.split_whitespace().count())
-        + count_responses_input_image_tokens(params.input);
-    let output_tokens =
-        crate::count_tokens_default(&content).unwrap_or(content.split_whitespace().count());
+    let input_tokens =
+        state.pipeline.count_tokens(&input_text) + count_responses_input_image_tokens(params.input);
+    let output_tokens = state.pipeline.count_tokens(&content) + code_tokens + mcp_tokens;
 
     // Reasoning tokens
     let reasoning_tokens =
@@ -150,8 +237,108 @@ pub(crate) fn generate_responses_result(
         usage,
         reasoning_tokens,
         reasoning_summary,
-        latency,
+        truncated,
+        refused: refusal.is_some(),
+        annotations,
+        mcp_items,
+        code_interpreter_call,
+    }
+}
+
+/// Count the conversation a response is chained to, `context` tokens of it,
+/// as input too: the model reads it all again. Beyond the context window
+/// (`[store] context_window`, or the model's), `truncation: "auto"` leaves
+/// out the oldest of it; otherwise the request fails as the API fails it.
+pub(crate) fn carry_context(
+    state: &AppState,
+    model: &str,
+    context: u32,
+    truncation: Option<Truncation>,
+    usage: &mut ResponsesUsage,
+) -> Result<(), ResponsesError> {
+    let window = state
+        .config
+        .store
+        .context_window
+        .or_else(|| crate::openai::get_model_profile(model).map(|p| p.context_window));
+    let mut input = usage.input_tokens.saturating_add(context);
+    if let Some(window) = window.filter(|window| input > *window) {
+        if truncation != Some(Truncation::Auto) {
+            return Err(ResponsesError {
+                code: Some("context_length_exceeded".to_string()),
+                ..ResponsesError::new(
+                    "invalid_request_error",
+                    "Your input exceeds the context window of this model. \
+                     Please adjust your input and try again.",
+                )
+            });
+        }
+        input = window;
     }
+    usage.input_tokens = input;
+    usage.total_tokens = input + usage.output_tokens;
+    Ok(())
+}
+
+/// A Responses API request refused with 400 before it was answered
+fn responses_bad_request(error: ResponsesError) -> Response {
+    responses_refusal(StatusCode::BAD_REQUEST, error)
+}
+
+fn responses_refusal(status: StatusCode, error: ResponsesError) -> Response {
+    let mut response = Json(ResponsesErrorResponse { error }).into_response();
+    *response.status_mut() = status;
+    response
+}
+
+/// Whether `include` asks for reasoning items' `encrypted_content`
+pub(crate) fn includes_encrypted_reasoning(include: Option<&[String]>) -> bool {
+    include.is_some_and(|include| include.iter().any(|i| i == "reasoning.encrypted_content"))
+}
+
+/// Reasoning items passed back as input must carry encrypted content this
+/// simulator minted, unless `stored`, when the API could look them up by id
+/// (which is not simulated: any id is taken as found)
+pub(crate) fn check_reasoning_items(
+    input: &ResponsesInput,
+    stored: bool,
+) -> Result<(), (StatusCode, ResponsesError)> {
+    for (id, encrypted_content) in input.reasoning_items() {
+        match encrypted_content {
+            Some(blob) if crate::encrypted_reasoning::verify(blob) => {}
+            Some(_) => {
+                return Err((
+                    StatusCode::BAD_REQUEST,
+                    ResponsesError {
+                        code: Some("invalid_encrypted_content".to_string()),
+                        ..ResponsesError::new(
+                            "invalid_request_error",
+                            format!(
+                                "The encrypted content for item {} could not be verified.",
+                                id
+                            ),
+                        )
+                    },
+                ))
+            }
+            None if !stored => {
+                return Err((
+                    StatusCode::NOT_FOUND,
+                    ResponsesError::new(
+                        "invalid_request_error",
+                        format!(
+                            "Item with id '{}' not found. Items are not persisted when `store` \
+                             is set to false. Try again with `store` set to true, or remove \
+                             this item from your input.",
+                            id
+                        ),
+                    ),
+                ))
+            }
+            None => {}
+        }
+    }
+    Ok(())
 }
 
 /// Health check endpoint
@@ -165,10 +352,28 @@ pub async fn health() -> impl IntoResponse {
 /// POST /openai/v1/chat/completions
 pub async fn chat_completions(
     State(state): State<Arc<AppState>>,
+    version: ApiVersion,
+    overrides: RequestOverrides,
     Json(request): Json<ChatCompletionRequest>,
 ) -> Result<Response, AppError> {
-    let request_start = Instant::now();
+    let metadata = request.metadata.clone().unwrap_or_default();
+    validate_metadata(&metadata).map_err(AppError::BadRequest)?;
+    let store = request.store == Some(true);
+    let completions = state.completions.clone();
+    let response = create_chat_completion(state, version, overrides, request).await?;
+    Ok(if store {
+        completions.capture(response, metadata)
+    } else {
+        response
+    })
+}
 
+async fn create_chat_completion(
+    state: Arc<AppState>,
+    version: ApiVersion,
+    overrides: RequestOverrides,
+    request: ChatCompletionRequest,
+) -> Result<Response, AppError> {
     tracing::info!(
         model = %request.model,
         stream = request.stream,
@@ -176,76 +381,74 @@ pub async fn chat_completions(
         "Chat completion request"
     );
 
-    // Record request start in stats
-    state.stats.record_request_start(
-        &request.model,
-        request.stream,
-        EndpointType::ChatCompletions,
-    );
-
-    // Check for error injection
-    let error_injector = ErrorInjector::new(state.config.error_config());
-    if let Some(error) = error_injector.maybe_inject() {
-        tracing::warn!("Injecting error: {:?}", error);
-
-        let status_code = error.status_code();
-        let status = match status_code {
-            429 => StatusCode::TOO_MANY_REQUESTS,
-            500 => StatusCode::INTERNAL_SERVER_ERROR,
-            503 => StatusCode::SERVICE_UNAVAILABLE,
-            504 => StatusCode::GATEWAY_TIMEOUT,
-            400 => StatusCode::BAD_REQUEST,
-            401 => StatusCode::UNAUTHORIZED,
-            _ => StatusCode::INTERNAL_SERVER_ERROR,
-        };
-
-        // Record error in stats
-        state.stats.record_error(status_code);
-
-        let mut response = Json(error.to_error_response()).into_response();
-        *response.status_mut() = status;
-
-        if let Some(retry_after) = error.retry_after() {
-            response.headers_mut().insert(
-                header::RETRY_AFTER,
-                retry_after.to_string().parse().unwrap(),
-            );
-        }
+    let req = state
+        .begin(
+            &request.model,
+            request.stream,
+            EndpointType::ChatCompletions,
+        )
+        .with_overrides(&overrides);
+    state.stats.record_request_messages(request.messages.len());
+    if let Some(content) = request.messages.last().and_then(|m| m.content.as_ref()) {
+        req.set_prompt(&content.text());
+    }
+    if let Some(user) = &request.user {
+        req.set_user(user);
+    }
 
-        return Ok(response);
+    if let Some(error) = req.inject_error() {
+        return Ok(injected_error_response::<OpenAIFormat>(&error));
     }
 
     // Reject image inputs to non-vision models before doing any work.
     if let Err(err) = validate_input_modalities(&request) {
-        state.stats.record_error(400);
+        req.fail(400);
         return Ok(err.into_response());
     }
+    if let Err(error) = check_chat_capabilities(&state, &request) {
+        req.fail(400);
+        return Ok((StatusCode::BAD_REQUEST, Json(error)).into_response());
+    }
 
-    // Get latency profile (use model-specific if not configured)
-    let latency =
-        if state.config.latency.profile.is_some() || state.config.latency.ttft_mean_ms.is_some() {
-            state.config.latency_profile()
-        } else {
-            LatencyProfile::from_model(&request.model)
-        };
+    // A request written against the deprecated functions API gets answers in
+    // that shape whatever version it claims.
+    let format = if request.uses_legacy_functions() {
+        ToolCallFormat::FunctionCall
+    } else {
+        ApiBehavior::resolve(&state.config.api_versions, &version).tool_call_format
+    };
 
     // Scripted mode short-circuits the generator.
     if let Some(script) = state.script.clone() {
-        return handle_scripted_chat_completions(state, request, request_start, latency, script)
-            .await;
+        return handle_scripted_chat_completions(state, request, req, script, format).await;
     }
 
-    // Generate response
-    let generator = create_generator(
-        &state.config.response.generator,
-        state.config.response.target_tokens,
-    );
-    let content = generator.generate(&request);
+    // Requests offering tools may be answered with calls instead of text.
+    let tool_calls = state.pipeline.simulate_tool_calls(&request);
+    if !tool_calls.is_empty() {
+        return chat_turn_response(state, request, req, None, tool_calls, 0, format).await;
+    }
+
+    // A warm pool answers plain text completions without generating. Its
+    // responses are not JSON objects and carry usage only at the end.
+    if let Some(pool) = &state.pool {
+        if !json_mode(&request) && !request.wants_continuous_usage() {
+            let pooled = pool.next();
+            return Ok(pooled_chat_response(&state, &request, req, pooled).await);
+        }
+    }
+
+    // Nothing needs the completion size before the body starts unless a cost
+    // header is sent, so stream the generator's pieces as they are produced.
+    if request.stream && !state.config.cost.enabled {
+        return Ok(stream_generated_chat(state, request, req));
+    }
+
+    let content = state.pipeline.generate(&request);
 
     // Count tokens
-    let prompt_tokens = count_request_tokens(&request);
-    let completion_tokens =
-        crate::count_tokens_default(&content).unwrap_or(content.split_whitespace().count());
+    let prompt_tokens = count_request_tokens(&state.pipeline, &request);
+    let completion_tokens = state.pipeline.count_tokens(&content);
     let usage = Usage {
         prompt_tokens: prompt_tokens as u32,
         completion_tokens: completion_tokens as u32,
@@ -254,62 +457,137 @@ pub async fn chat_completions(
 
     if request.stream {
         // Streaming response
-        // Clone stats for the streaming completion callback
-        let stats = state.stats.clone();
         let prompt_tok = usage.prompt_tokens;
         let completion_tok = usage.completion_tokens;
 
         let stream = TokenStreamBuilder::new(&request.model, content)
-            .latency(latency)
+            .latency(req.request_latency(prompt_tok))
+            .sleeper(req.sleeper().clone())
+            .watchdog(req.stream_watchdog())
+            .system_fingerprint(req.system_fingerprint())
             .usage(usage)
-            .on_complete(move || {
-                stats.record_request_end(request_start.elapsed(), prompt_tok, completion_tok);
-            })
+            .continuous_usage(request.wants_continuous_usage())
+            .on_complete(req.finish_callback(prompt_tok, completion_tok))
             .build();
 
-        let body = Body::from_stream(stream.into_stream().map(Ok::<_, std::io::Error>));
-
-        Ok(Response::builder()
-            .status(StatusCode::OK)
-            .header(header::CONTENT_TYPE, "text/event-stream")
-            .header(header::CACHE_CONTROL, "no-cache")
-            .header(header::CONNECTION, "keep-alive")
-            .body(body)
-            .unwrap())
+        let response = sse_response(&state, &req, stream.into_stream());
+        Ok(apply_cost(
+            &state,
+            response,
+            &request.model,
+            prompt_tok,
+            completion_tok,
+        ))
     } else {
         // Non-streaming response - simulate time to generate
-        let delay = latency.sample_ttft();
-        if !delay.is_zero() {
-            tokio::time::sleep(delay).await;
-        }
+        req.simulate_generation(usage.prompt_tokens).await;
+        req.finish(usage.prompt_tokens, usage.completion_tokens);
+
+        let (prompt_tok, completion_tok) = (usage.prompt_tokens, usage.completion_tokens);
+        let mut response = ChatCompletionResponse::new(request.model.clone(), content, usage);
+        response.system_fingerprint = Some(req.system_fingerprint().to_string());
+        Ok(apply_cost(
+            &state,
+            Json(response).into_response(),
+            &request.model,
+            prompt_tok,
+            completion_tok,
+        ))
+    }
+}
 
-        // Record request completion
-        state.stats.record_request_end(
-            request_start.elapsed(),
-            usage.prompt_tokens,
-            usage.completion_tokens,
-        );
+/// Stream a generated chat completion incrementally. Completion tokens are
+/// counted as pieces go out; stats and spend are settled when the stream ends.
+fn stream_generated_chat(
+    state: Arc<AppState>,
+    request: ChatCompletionRequest,
+    req: PipelineRequest,
+) -> Response {
+    let prompt_tokens = count_request_tokens(&state.pipeline, &request) as u32;
+    let pieces = state.pipeline.generate_pieces(&request);
+    let finish = req.usage_callback();
+    let model = request.model.clone();
+    let cost_state = state.clone();
+
+    let stream = TokenStreamBuilder::from_pieces(&request.model, pieces)
+        .latency(req.request_latency(prompt_tokens))
+        .system_fingerprint(req.system_fingerprint())
+        .tokenizer(state.pipeline.tokenizer().clone())
+        .sleeper(req.sleeper().clone())
+        .watchdog(req.stream_watchdog())
+        .usage(Usage {
+            prompt_tokens,
+            completion_tokens: 0,
+            total_tokens: prompt_tokens,
+        })
+        .continuous_usage(request.wants_continuous_usage())
+        .on_usage(move |usage| {
+            finish(usage.prompt_tokens, usage.completion_tokens);
+            record_cost(
+                &cost_state,
+                &model,
+                usage.prompt_tokens,
+                usage.completion_tokens,
+            );
+        })
+        .build();
 
-        let response = ChatCompletionResponse::new(request.model.clone(), content, usage);
-        Ok(Json(response).into_response())
-    }
+    sse_response(&state, &req, stream.into_stream())
+}
+
+/// Answer a chat completion with a pre-generated response. The prompt is
+/// estimated rather than tokenized, so no request pays for the tokenizer.
+async fn pooled_chat_response(
+    state: &AppState,
+    request: &ChatCompletionRequest,
+    req: PipelineRequest,
+    pooled: Arc<PooledResponse>,
+) -> Response {
+    let prompt_tokens = estimate_request_tokens(request) as u32;
+    let completion_tokens = pooled.completion_tokens();
+    let fill = PoolFill::new(&request.model, req.system_fingerprint(), prompt_tokens);
+
+    let response = if request.stream {
+        let stream = pooled.stream(
+            fill,
+            req.request_latency(prompt_tokens),
+            req.sleeper().clone(),
+            req.finish_callback(prompt_tokens, completion_tokens),
+        );
+        sse_response(state, &req, stream)
+    } else {
+        req.simulate_generation(prompt_tokens).await;
+        req.finish(prompt_tokens, completion_tokens);
+        (
+            [(header::CONTENT_TYPE, "application/json")],
+            pooled.body(&fill),
+        )
+            .into_response()
+    };
+    apply_cost(
+        state,
+        response,
+        &request.model,
+        prompt_tokens,
+        completion_tokens,
+    )
 }
 
 /// Drive the chat completions handler from the configured script.
 async fn handle_scripted_chat_completions(
     state: Arc<AppState>,
     request: ChatCompletionRequest,
-    request_start: Instant,
-    latency: LatencyProfile,
+    req: PipelineRequest,
     script: Arc<crate::script::Script>,
+    format: ToolCallFormat,
 ) -> Result<Response, AppError> {
     let turn_index = script.cursor();
-    let next = script.next_turn();
+    let next = script.next_turn_with_tool_results(request.tool_result_ids());
 
     let turn = match next {
         ScriptedResponse::Turn(t) => t,
         ScriptedResponse::Exhausted => {
-            state.stats.record_error(500);
+            req.fail(500);
             return Ok(sim_error_to_response(&SimError::Other {
                 message: "llmsim script exhausted (on_exhausted=error)".to_string(),
                 status_code: Some(500),
@@ -317,20 +595,41 @@ async fn handle_scripted_chat_completions(
         }
     };
 
-    let (text, tool_calls) = match turn {
-        SimTurn::Assistant { text } => (Some(text), Vec::new()),
-        SimTurn::ToolCalls { calls } => (None, calls),
-        SimTurn::Mixed { text, calls } => (Some(text), calls),
-        SimTurn::Error(err) => {
-            state.stats.record_error(err.status_code());
+    let (text, mut tool_calls) = match turn.into_parts() {
+        Ok(parts) => parts,
+        Err(err) => {
+            req.fail(err.status_code());
             return Ok(sim_error_to_response(&err));
         }
     };
 
-    let prompt_tokens = count_request_tokens(&request);
-    let text_for_usage = text.clone().unwrap_or_default();
-    let completion_tokens = crate::count_tokens_default(&text_for_usage)
-        .unwrap_or(text_for_usage.split_whitespace().count());
+    // Fix the ids up front so streamed and non-streamed turns agree, and so
+    // a following expect_tool_results turn knows what to check. Legacy
+    // function_call responses carry no ids for the client to echo.
+    crate::script::resolve_tool_call_ids(turn_index, &mut tool_calls);
+    if format == ToolCallFormat::ToolCalls {
+        script.record_tool_call_ids(tool_calls.iter().filter_map(|c| c.id.clone()).collect());
+    }
+
+    chat_turn_response(state, request, req, text, tool_calls, turn_index, format).await
+}
+
+/// Answer a chat completion with a fixed assistant turn: optional text and
+/// tool calls, in `format`. Call ids missing from `tool_calls` are derived
+/// from `turn_index`.
+async fn chat_turn_response(
+    state: Arc<AppState>,
+    request: ChatCompletionRequest,
+    req: PipelineRequest,
+    text: Option<String>,
+    tool_calls: Vec<SimToolCall>,
+    turn_index: usize,
+    format: ToolCallFormat,
+) -> Result<Response, AppError> {
+    let prompt_tokens = count_request_tokens(&state.pipeline, &request);
+    let completion_tokens = state
+        .pipeline
+        .count_tokens(text.as_deref().unwrap_or_default());
     let usage = Usage {
         prompt_tokens: prompt_tokens as u32,
         completion_tokens: completion_tokens as u32,
@@ -340,7 +639,6 @@ async fn handle_scripted_chat_completions(
     let wire_calls = materialize_tool_calls(turn_index, &tool_calls);
 
     if request.stream {
-        let stats = state.stats.clone();
         let prompt_tok = usage.prompt_tokens;
         let completion_tok = usage.completion_tokens;
 
@@ -348,33 +646,36 @@ async fn handle_scripted_chat_completions(
             &request.model,
             text.unwrap_or_default(),
             tool_calls,
-            latency,
+            req.request_latency(prompt_tok),
         )
+        .with_tool_call_format(format)
+        .with_system_fingerprint(req.system_fingerprint())
+        .with_sleeper(req.sleeper().clone())
         .with_usage(usage)
-        .with_on_complete(move || {
-            stats.record_request_end(request_start.elapsed(), prompt_tok, completion_tok);
-        });
+        .with_on_complete(req.finish_callback(prompt_tok, completion_tok));
 
-        let body = Body::from_stream(stream.into_stream().map(Ok::<_, std::io::Error>));
-        Ok(Response::builder()
-            .status(StatusCode::OK)
-            .header(header::CONTENT_TYPE, "text/event-stream")
-            .header(header::CACHE_CONTROL, "no-cache")
-            .header(header::CONNECTION, "keep-alive")
-            .body(body)
-            .unwrap())
+        let response = sse_response(&state, &req, stream.into_stream());
+        Ok(apply_cost(
+            &state,
+            response,
+            &request.model,
+            prompt_tok,
+            completion_tok,
+        ))
     } else {
-        let delay = latency.sample_ttft();
-        if !delay.is_zero() {
-            tokio::time::sleep(delay).await;
-        }
-        state.stats.record_request_end(
-            request_start.elapsed(),
-            usage.prompt_tokens,
-            usage.completion_tokens,
-        );
-        let resp = build_chat_completion_response(request.model.clone(), text, wire_calls, usage);
-        Ok(Json(resp).into_response())
+        req.simulate_generation(usage.prompt_tokens).await;
+        req.finish(usage.prompt_tokens, usage.completion_tokens);
+        let (prompt_tok, completion_tok) = (usage.prompt_tokens, usage.completion_tokens);
+        let mut resp =
+            build_chat_completion_response(request.model.clone(), text, wire_calls, format, usage);
+        resp.system_fingerprint = Some(req.system_fingerprint().to_string());
+        Ok(apply_cost(
+            &state,
+            Json(resp).into_response(),
+            &request.model,
+            prompt_tok,
+            completion_tok,
+        ))
     }
 }
 
@@ -384,16 +685,17 @@ async fn handle_scripted_chat_completions(
 async fn handle_scripted_responses_api(
     state: Arc<AppState>,
     request: ResponsesRequest,
-    request_start: Instant,
+    req: PipelineRequest,
     script: Arc<crate::script::Script>,
+    context: u32,
 ) -> Response {
     let turn_index = script.cursor();
-    let next = script.next_turn();
+    let next = script.next_turn_with_tool_results(request.input.tool_result_ids());
 
     let turn = match next {
         ScriptedResponse::Turn(t) => t,
         ScriptedResponse::Exhausted => {
-            state.stats.record_error(500);
+            req.fail(500);
             return sim_error_to_responses_api_response(&SimError::Other {
                 message: "llmsim script exhausted (on_exhausted=error)".to_string(),
                 status_code: Some(500),
@@ -401,34 +703,32 @@ async fn handle_scripted_responses_api(
         }
     };
 
-    let (text, tool_calls) = match turn {
-        SimTurn::Assistant { text } => (Some(text), Vec::new()),
-        SimTurn::ToolCalls { calls } => (None, calls),
-        SimTurn::Mixed { text, calls } => (Some(text), calls),
-        SimTurn::Error(err) => {
-            state.stats.record_error(err.status_code());
+    let (text, mut tool_calls) = match turn.into_parts() {
+        Ok(parts) => parts,
+        Err(err) => {
+            req.fail(err.status_code());
             return sim_error_to_responses_api_response(&err);
         }
     };
+    crate::script::resolve_tool_call_ids(turn_index, &mut tool_calls);
+    script.record_tool_call_ids(tool_calls.iter().filter_map(|c| c.id.clone()).collect());
 
     let input_text = extract_input_text(&request.input, &request.instructions);
-    let input_tokens = crate::count_tokens_default(&input_text)
-        .unwrap_or(input_text.split_whitespace().count())
+    let input_tokens = state.pipeline.count_tokens(&input_text)
         + count_responses_input_image_tokens(&request.input);
-    let text_for_usage = text.clone().unwrap_or_default();
-    let output_text_tokens = crate::count_tokens_default(&text_for_usage)
-        .unwrap_or(text_for_usage.split_whitespace().count());
+    let output_text_tokens = state
+        .pipeline
+        .count_tokens(text.as_deref().unwrap_or_default());
     let tool_call_tokens: usize = tool_calls
         .iter()
         .map(|c| {
             let args = serde_json::to_string(&c.arguments).unwrap_or_default();
-            crate::count_tokens_default(&args).unwrap_or(args.split_whitespace().count())
-                + c.name.split_whitespace().count()
+            state.pipeline.count_tokens(&args) + c.name.split_whitespace().count()
         })
         .sum();
     let output_tokens = output_text_tokens + tool_call_tokens;
 
-    let usage = ResponsesUsage {
+    let mut usage = ResponsesUsage {
         input_tokens: input_tokens as u32,
         output_tokens: output_tokens as u32,
         total_tokens: (input_tokens + output_tokens) as u32,
@@ -436,12 +736,22 @@ async fn handle_scripted_responses_api(
             reasoning_tokens: 0,
         }),
     };
+    if let Err(error) = carry_context(
+        &state,
+        &request.model,
+        context,
+        request.truncation,
+        &mut usage,
+    ) {
+        req.fail(400);
+        return responses_bad_request(error);
+    }
 
     let mut output: Vec<OutputItem> = Vec::new();
     let output_text_value: Option<String> = text.clone();
     if let Some(t) = text {
         output.push(OutputItem::Message {
-            id: prefixed_id("msg_"),
+            id: new_id(IdKind::Message),
             role: OutputRole::Assistant,
             status: crate::openai::ItemStatus::Completed,
             content: vec![OutputContentPart::OutputText {
@@ -450,14 +760,11 @@ async fn handle_scripted_responses_api(
             }],
         });
     }
-    for (i, call) in tool_calls.iter().enumerate() {
-        let call_id = call
-            .id
-            .clone()
-            .unwrap_or_else(|| crate::script::auto_tool_call_id(turn_index, i));
+    for call in &tool_calls {
+        let call_id = call.id.clone().unwrap_or_default();
         let args = serde_json::to_string(&call.arguments).unwrap_or_else(|_| "{}".to_string());
         output.push(OutputItem::FunctionCall {
-            id: prefixed_id("fc_"),
+            id: new_id(IdKind::FunctionCall),
             call_id,
             name: call.name.clone(),
             arguments: args,
@@ -465,26 +772,305 @@ async fn handle_scripted_responses_api(
         });
     }
 
-    state.stats.record_request_end(
-        request_start.elapsed(),
-        usage.input_tokens,
-        usage.output_tokens,
-    );
+    req.finish(usage.input_tokens, usage.output_tokens);
 
+    let (input_tok, output_tok) = (usage.input_tokens, usage.output_tokens);
     let resp = ResponsesResponse {
-        id: prefixed_id("resp_"),
+        id: new_id(IdKind::Response),
         object: "response".to_string(),
         created_at: unix_timestamp(),
-        model: request.model,
+        model: request.model.clone(),
         status: ResponseStatus::Completed,
         output,
         output_text: output_text_value,
         usage: Some(usage),
         error: None,
+        incomplete_details: None,
         metadata: None,
     };
+    if request.store != Some(false) {
+        state
+            .responses
+            .put(&resp, responses_store::input_items(&request.input));
+    }
+
+    apply_cost(
+        &state,
+        Json(resp).into_response(),
+        &request.model,
+        input_tok,
+        output_tok,
+    )
+}
+
+/// Serve an SSE body. Events are generated on their own clock, ahead of a
+/// slow reader by at most `pending_events`. If the client disconnects before
+/// the stream ends, the stream is dropped (stopping generation) and counted
+/// as abandoned. With `[stream] event_ids`, events are numbered and generated
+/// to the end for a reconnecting client to resume instead.
+pub(crate) fn sse_response<S>(state: &AppState, req: &PipelineRequest, stream: S) -> Response
+where
+    S: futures_core::Stream<Item = String> + Send + Unpin + 'static,
+{
+    events_response(state, req, stream, None)
+}
+
+/// Serve a background response's SSE body: its events are generated to the
+/// end into `log` whether or not the client stays, for
+/// `GET /openai/v1/responses/{id}?stream=true` to pick up
+pub(crate) fn background_sse_response<S>(
+    state: &AppState,
+    req: &PipelineRequest,
+    stream: S,
+    log: Arc<StreamLog>,
+) -> Response
+where
+    S: futures_core::Stream<Item = String> + Send + Unpin + 'static,
+{
+    events_response(state, req, stream, Some(log))
+}
+
+fn events_response<S>(
+    state: &AppState,
+    req: &PipelineRequest,
+    stream: S,
+    log: Option<Arc<StreamLog>>,
+) -> Response
+where
+    S: futures_core::Stream<Item = String> + Send + Unpin + 'static,
+{
+    let config = &state.config.stream;
+    let stream = match PayloadSchema::for_endpoint(req.endpoint()) {
+        Some(schema) if config.strict_schema => validate_stream(stream, schema).boxed(),
+        _ => stream.boxed(),
+    };
+    // A client leaving a detached stream does not stop it, so it is not
+    // abandoned
+    let detached = log.is_some();
+    let stream = match log {
+        Some(log) => log.record(stream).boxed(),
+        None => stream,
+    };
+    let key = config.event_ids.then(StreamLogs::key);
+    let stream = match &key {
+        Some(key) => number_events(stream, key.clone()).boxed(),
+        None => stream,
+    };
+    let stream = if config.duplicate_rate > 0.0 || config.reorder_rate > 0.0 {
+        fault_events(stream, config.duplicate_rate, config.reorder_rate).boxed()
+    } else {
+        stream
+    };
+    let stream = match key {
+        Some(key) => state
+            .stream_logs
+            .open(key, sequence_of)
+            .record(stream)
+            .boxed(),
+        None if detached => bounded_events(stream, config.pending_events).boxed(),
+        // Guarded on the generating side: a stream generated to its end has
+        // completed, even if its client leaves before reading the tail
+        None => bounded_events(req.guard_stream(stream), config.pending_events).boxed(),
+    };
+    sse_body(state, stream)
+}
+
+/// Send SSE frames with the configured transport: a leading `retry:`,
+/// malformed frames, UTF-8 splitting and proxy buffering
+pub(crate) fn sse_body(
+    state: &AppState,
+    mut stream: futures_util::stream::BoxStream<'static, String>,
+) -> Response {
+    let config = &state.config.stream;
+    if config.malformed_rate > 0.0 {
+        let mut malformer = FrameMalformer::new(config.malformed_rate, &config.malformed_kinds);
+        stream = stream
+            .flat_map(move |frame| futures_util::stream::iter(malformer.apply(frame)))
+            .boxed();
+    }
+    if let Some(retry_ms) = config.retry_ms {
+        let retry = format!("retry: {}\n\n", retry_ms);
+        stream = futures_util::stream::once(async move { retry })
+            .chain(stream)
+            .boxed();
+    }
+    let mut frames = match config.utf8_boundaries {
+        Utf8Boundaries::Preserve => stream.map(String::into_bytes).boxed(),
+        Utf8Boundaries::Split => stream
+            .flat_map(|event| futures_util::stream::iter(split_inside_chars(&event)))
+            .boxed(),
+    };
+    if config.is_buffered() {
+        frames = buffer_frames(
+            frames,
+            config.buffer_bytes,
+            config.buffer_interval_ms.map(Duration::from_millis),
+        )
+        .boxed();
+    }
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "text/event-stream")
+        .header(header::CACHE_CONTROL, "no-cache")
+        .header(header::CONNECTION, "keep-alive")
+        .body(Body::from_stream(frames.map(Ok::<_, std::io::Error>)))
+        .unwrap()
+}
 
-    Json(resp).into_response()
+/// Generate `events` in a task of their own, so their delays run whether or
+/// not the client is reading. At most `capacity` events wait in between;
+/// beyond that generation pauses until the client catches up. Whatever is
+/// waiting when the client reads goes out as one frame, the way a model
+/// server's tokens pile up in the socket behind a slow reader.
+fn bounded_events<S>(mut events: S, capacity: usize) -> impl futures_core::Stream<Item = String>
+where
+    S: futures_core::Stream<Item = String> + Send + Unpin + 'static,
+{
+    let (tx, mut rx) = tokio::sync::mpsc::channel::<String>(capacity.max(1));
+    tokio::spawn(Stamps::current().scope(Box::pin(async move {
+        loop {
+            let event = tokio::select! {
+                event = events.next() => event,
+                // The client went away
+                _ = tx.closed() => break,
+            };
+            let Some(event) = event else { break };
+            if tx.send(event).await.is_err() {
+                break;
+            }
+        }
+    })));
+    async_stream::stream! {
+        while let Some(mut frame) = rx.recv().await {
+            while let Ok(event) = rx.try_recv() {
+                frame.push_str(&event);
+            }
+            yield frame;
+        }
+    }
+}
+
+/// Coalesce body frames the way a buffering reverse proxy does: output is
+/// held until `max_bytes` are pending or `interval` has passed since the
+/// first held byte, and whatever is left goes out when the stream ends.
+fn buffer_frames<S>(
+    mut frames: S,
+    max_bytes: Option<usize>,
+    interval: Option<Duration>,
+) -> impl futures_core::Stream<Item = Vec<u8>>
+where
+    S: futures_core::Stream<Item = Vec<u8>> + Send + Unpin,
+{
+    async_stream::stream! {
+        let mut pending = Vec::new();
+        let mut deadline = None;
+        loop {
+            let next = match deadline {
+                Some(at) => tokio::select! {
+                    next = frames.next() => Some(next),
+                    _ = tokio::time::sleep_until(at) => None,
+                },
+                None => Some(frames.next().await),
+            };
+            let frame = match next {
+                Some(Some(frame)) => frame,
+                Some(None) => break,
+                // Interval elapsed
+                None => {
+                    deadline = None;
+                    yield std::mem::take(&mut pending);
+                    continue;
+                }
+            };
+            if frame.is_empty() {
+                continue;
+            }
+            if pending.is_empty() {
+                deadline = interval.map(|i| tokio::time::Instant::now() + i);
+            }
+            pending.extend_from_slice(&frame);
+            if max_bytes.is_some_and(|max| pending.len() >= max) {
+                deadline = None;
+                yield std::mem::take(&mut pending);
+            }
+        }
+        if !pending.is_empty() {
+            yield pending;
+        }
+    }
+}
+
+/// Break an event into body frames so that every multi-byte character is cut
+/// after its first byte
+fn split_inside_chars(event: &str) -> Vec<Vec<u8>> {
+    let bytes = event.as_bytes();
+    let mut frames = Vec::new();
+    let mut start = 0;
+    for (i, ch) in event.char_indices() {
+        if ch.len_utf8() > 1 {
+            frames.push(bytes[start..=i].to_vec());
+            start = i + 1;
+        }
+    }
+    frames.push(bytes[start..].to_vec());
+    frames
+}
+
+/// Render an injected error in a protocol's wire format, with `Retry-After`
+/// for rate limits.
+pub(crate) fn injected_error_response<F: FormatAdapter>(error: &SimulatedError) -> Response {
+    let status =
+        StatusCode::from_u16(error.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+    let mut response = (status, Json(F::error_body(error))).into_response();
+    if let Some(retry_after) = error.retry_after() {
+        response
+            .headers_mut()
+            .insert(header::RETRY_AFTER, retry_after.into());
+    }
+    response
+}
+
+/// Attach the simulated cost of a request to its response.
+///
+/// The cost is priced from `[cost.prices]` or the built-in table and always
+/// added to the spend stats; the header is only set when `[cost] enabled`.
+/// Models without a known price get no header.
+pub(crate) fn apply_cost(
+    state: &AppState,
+    mut response: Response,
+    model: &str,
+    input_tokens: u32,
+    output_tokens: u32,
+) -> Response {
+    let Some(cost) = record_cost(state, model, input_tokens, output_tokens) else {
+        return response;
+    };
+
+    if state.config.cost.enabled {
+        if let (Ok(name), Ok(value)) = (
+            header::HeaderName::from_bytes(state.config.cost.header.as_bytes()),
+            header::HeaderValue::from_str(&cost.to_string()),
+        ) {
+            response.headers_mut().insert(name, value);
+        }
+    }
+    response
+}
+
+/// Add the simulated cost of a request to the spend stats, returning it.
+/// Returns None for models without a known price.
+fn record_cost(
+    state: &AppState,
+    model: &str,
+    input_tokens: u32,
+    output_tokens: u32,
+) -> Option<f64> {
+    let cost = state
+        .config
+        .model_price(model)?
+        .cost(input_tokens, output_tokens);
+    state.stats.record_cost(cost);
+    Some(cost)
 }
 
 /// Build an OpenAI Responses-API-shaped error response for a SimError.
@@ -547,70 +1133,299 @@ fn sim_error_to_response(err: &SimError) -> Response {
 
 /// GET /llmsim/stats - Get server statistics
 pub async fn get_stats(State(state): State<Arc<AppState>>) -> impl IntoResponse {
-    Json(state.stats.snapshot())
+    let mut snapshot = state.stats.snapshot();
+    snapshot.active_drills = state.drills.active();
+    snapshot.runtime = super::runtime_snapshot();
+    Json(snapshot)
+}
+
+/// GET /llmsim/cluster/stats - Stats combined across the cluster's processes
+pub async fn get_cluster_stats(State(state): State<Arc<AppState>>) -> Result<Response, AppError> {
+    let config = &state.config.cluster;
+    let dir = config.stats_dir.as_deref().ok_or_else(|| {
+        AppError::NotFound("Cluster stats are not enabled ([cluster] stats_dir)".into())
+    })?;
+    let mut own = state.stats.snapshot();
+    own.active_drills = state.drills.active();
+    let cluster = super::cluster::collect(config, std::path::Path::new(dir), own);
+    Ok(Json(cluster).into_response())
+}
+
+/// GET /llmsim/openapi.json - OpenAPI document for this listener
+pub async fn get_openapi(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    Json(super::openapi::document_for(&state))
+}
+
+/// GET /llmsim/mirror - Simulated vs. upstream comparison of mirrored
+/// requests
+pub async fn get_mirror(State(state): State<Arc<AppState>>) -> Result<Response, AppError> {
+    let mirror = state.mirror.as_ref().ok_or_else(|| {
+        AppError::NotFound("Request mirroring is not enabled ([mirror] enabled = true)".into())
+    })?;
+    Ok(Json(mirror.summary()).into_response())
+}
+
+/// GET /llmsim/proxy - Simulated and proxied request stats
+pub async fn get_proxy(State(state): State<Arc<AppState>>) -> Result<Response, AppError> {
+    let proxy = state.proxy.as_ref().ok_or_else(|| {
+        AppError::NotFound("Proxy mode is not enabled (no [[proxy.rules]])".into())
+    })?;
+    Ok(Json(proxy.summary(state.stats.snapshot())).into_response())
+}
+
+/// GET /llmsim/profiles - Profiles learned so far while recording
+pub async fn get_profiles(State(state): State<Arc<AppState>>) -> Result<Response, AppError> {
+    let recorder = state.recorder.as_ref().ok_or_else(|| {
+        AppError::NotFound("Profile recording is not enabled ([learned] record)".into())
+    })?;
+    Ok(Json(recorder.profiles()).into_response())
+}
+
+/// GET /llmsim/requests - Summaries of the most recent requests
+pub async fn get_requests(
+    State(state): State<Arc<AppState>>,
+    uri: Uri,
+) -> Result<Response, AppError> {
+    let config = &state.config.request_log;
+    if !config.enabled {
+        return Err(AppError::NotFound(
+            "The request log is not enabled ([request_log] enabled = true)".into(),
+        ));
+    }
+    let limit = super::request_log::parse_limit(uri.query()).map_err(AppError::BadRequest)?;
+    let requests = super::request_log::recent(&state.decisions, config, limit);
+    Ok(Json(serde_json::json!({ "requests": requests })).into_response())
+}
+
+/// GET /llmsim/files/{id} - Download a generated artifact
+pub async fn get_file(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<Response, AppError> {
+    let file = state
+        .files
+        .get(&id)
+        .ok_or_else(|| AppError::NotFound(format!("No file with id '{}'", id)))?;
+    Ok((
+        [
+            (header::CONTENT_TYPE, file.content_type),
+            // Ids are content hashes, so a file never changes
+            (
+                header::CACHE_CONTROL,
+                "public, max-age=31536000, immutable".to_string(),
+            ),
+        ],
+        file.bytes,
+    )
+        .into_response())
+}
+
+/// Most bytes of a request body `/llmsim/debug/echo` echoes
+const ECHO_BODY_BYTES: usize = 64 * 1024;
+
+/// ANY /llmsim/debug/echo - The request as received: method, path, query,
+/// headers (repeated ones as arrays) and the body, cut at `ECHO_BODY_BYTES`
+pub async fn debug_echo(request: axum::extract::Request) -> impl IntoResponse {
+    let (parts, body) = request.into_parts();
+    let mut headers = serde_json::Map::new();
+    for (name, value) in &parts.headers {
+        let value = serde_json::Value::from(String::from_utf8_lossy(value.as_bytes()));
+        match headers.get_mut(name.as_str()) {
+            Some(serde_json::Value::Array(values)) => values.push(value),
+            Some(first) => *first = serde_json::Value::Array(vec![first.take(), value]),
+            None => {
+                headers.insert(name.to_string(), value);
+            }
+        }
+    }
+
+    // Read to the end, so the size is the whole body's, keeping the start
+    let mut kept = Vec::new();
+    let mut size = 0;
+    let mut frames = body.into_data_stream();
+    while let Some(Ok(bytes)) = frames.next().await {
+        size += bytes.len();
+        let room = ECHO_BODY_BYTES.saturating_sub(kept.len());
+        kept.extend_from_slice(&bytes[..bytes.len().min(room)]);
+    }
+
+    Json(serde_json::json!({
+        "method": parts.method.as_str(),
+        "path": parts.uri.path(),
+        "query": parts.uri.query(),
+        "headers": headers,
+        "body": String::from_utf8_lossy(&kept),
+        "body_bytes": size,
+        "body_truncated": size > kept.len(),
+    }))
+}
+
+/// POST /llmsim/drill - Start a failover drill
+pub async fn start_drill(
+    State(state): State<Arc<AppState>>,
+    Json(spec): Json<DrillSpec>,
+) -> Result<Response, AppError> {
+    let drill = state
+        .drills
+        .start(spec)
+        .map_err(|e| AppError::BadRequest(e.to_string()))?;
+    tracing::warn!(id = %drill.id, "Drill started: {:?}", drill.spec);
+    Ok((StatusCode::CREATED, Json(drill)).into_response())
+}
+
+/// GET /llmsim/drill - Drills still running
+pub async fn list_drills(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    Json(serde_json::json!({ "drills": state.drills.active() }))
+}
+
+/// DELETE /llmsim/drill/{id} - Stop a drill early
+pub async fn cancel_drill(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, AppError> {
+    if state.drills.cancel(&id) {
+        tracing::info!(id = %id, "Drill cancelled");
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(AppError::NotFound(format!("No running drill '{}'", id)))
+    }
+}
+
+/// GET /llmsim/errors/catalog - Every simulated error as each provider
+/// format renders it
+pub async fn get_error_catalog(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let timeout = std::time::Duration::from_millis(state.config.errors.timeout_after_ms);
+    Json(error_catalog::catalog(timeout).await)
+}
+
+/// POST /llmsim/errors/force - Arm an error for the next matching requests
+pub async fn force_error(
+    State(state): State<Arc<AppState>>,
+    Json(spec): Json<ForceSpec>,
+) -> Result<Response, AppError> {
+    let armed = state
+        .forced_errors
+        .arm(spec)
+        .map_err(AppError::BadRequest)?;
+    tracing::info!(id = %armed.id, "Error armed: {:?}", armed.error);
+    Ok((StatusCode::CREATED, Json(armed)).into_response())
+}
+
+/// GET /llmsim/errors/force - Errors still armed
+pub async fn list_forced_errors(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    Json(serde_json::json!({ "object": "list", "data": state.forced_errors.armed() }))
+}
+
+/// DELETE /llmsim/errors/force/{id} - Disarm an error
+pub async fn cancel_forced_error(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, AppError> {
+    if state.forced_errors.cancel(&id) {
+        tracing::info!(id = %id, "Error disarmed");
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(AppError::NotFound(format!("No armed error '{}'", id)))
+    }
+}
+
+/// GET /llmsim/keys - Every API key, revoked ones included, values masked
+pub async fn list_keys(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let keys = key_store(&state, &headers)?;
+    let data: Vec<_> = keys.list().iter().map(|key| key.to_json(false)).collect();
+    Ok(Json(serde_json::json!({ "object": "list", "data": data })).into_response())
+}
+
+/// POST /llmsim/keys - Create an API key; its value is shown in full only
+/// here
+pub async fn create_key(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Json(new): Json<NewKey>,
+) -> Result<Response, AppError> {
+    let keys = key_store(&state, &headers)?;
+    let key = keys.create(new).map_err(|e| match e {
+        KeyError::Invalid(msg) => AppError::BadRequest(msg),
+        KeyError::Save(msg) => AppError::Internal(msg),
+    })?;
+    tracing::info!(id = %key.id, "API key created");
+    Ok((StatusCode::CREATED, Json(key.to_json(true))).into_response())
+}
+
+/// DELETE /llmsim/keys/{id} - Revoke an API key; its requests are refused
+/// from now on
+pub async fn revoke_key(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Result<Response, AppError> {
+    let keys = key_store(&state, &headers)?;
+    let key = keys
+        .revoke(&id)
+        .map_err(AppError::Internal)?
+        .ok_or_else(|| AppError::NotFound(format!("No API key '{}'", id)))?;
+    tracing::info!(id = %key.id, "API key revoked");
+    Ok(Json(key.to_json(false)).into_response())
+}
+
+/// The key store, for callers with `[keys] admin_key`
+fn key_store<'a>(state: &'a AppState, headers: &HeaderMap) -> Result<&'a KeyStore, AppError> {
+    let keys = state.keys.as_deref().ok_or_else(|| {
+        AppError::NotFound("API keys are not managed ([keys] enabled = true)".into())
+    })?;
+    if !is_admin(&state.config.keys, headers) {
+        return Err(AppError::Unauthorized("Invalid admin key".into()));
+    }
+    Ok(keys)
+}
+
+/// GET /llmsim/regions - Where each configured region is served, with its
+/// stats
+pub async fn get_regions(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let regions: Vec<_> = state
+        .regions
+        .iter()
+        .map(|region| {
+            serde_json::json!({
+                "name": region.name,
+                "prefix": region.mount_path,
+                "port": region.mount_path.is_none().then_some(region.state.config.server.port),
+                "stats": region.state.stats.snapshot(),
+            })
+        })
+        .collect();
+    Json(serde_json::json!({ "regions": regions }))
 }
 
 /// POST /openresponses/v1/responses - OpenResponses API endpoint
 pub async fn create_openresponses_response(
     State(state): State<Arc<AppState>>,
+    overrides: RequestOverrides,
     Json(request): Json<ResponseRequest>,
 ) -> Result<Response, AppError> {
-    let request_start = Instant::now();
-
     tracing::info!(
         model = %request.model,
         stream = request.stream,
         "OpenResponses request"
     );
 
-    // Record request start in stats
+    let req = state
+        .begin(&request.model, request.stream, EndpointType::Responses)
+        .with_overrides(&overrides);
     state
         .stats
-        .record_request_start(&request.model, request.stream, EndpointType::Responses);
-
-    // Check for error injection
-    let error_injector = ErrorInjector::new(state.config.error_config());
-    if let Some(error) = error_injector.maybe_inject() {
-        tracing::warn!("Injecting error: {:?}", error);
-
-        let status_code = error.status_code();
-        let status = match status_code {
-            429 => StatusCode::TOO_MANY_REQUESTS,
-            500 => StatusCode::INTERNAL_SERVER_ERROR,
-            503 => StatusCode::SERVICE_UNAVAILABLE,
-            504 => StatusCode::GATEWAY_TIMEOUT,
-            400 => StatusCode::BAD_REQUEST,
-            401 => StatusCode::UNAUTHORIZED,
-            _ => StatusCode::INTERNAL_SERVER_ERROR,
-        };
-
-        // Record error in stats
-        state.stats.record_error(status_code);
-
-        let error_response = openresponses::ErrorResponse::new(
-            error.to_error_response().error.message,
-            error.to_error_response().error.error_type,
-        );
-        let mut response = Json(error_response).into_response();
-        *response.status_mut() = status;
-
-        if let Some(retry_after) = error.retry_after() {
-            response.headers_mut().insert(
-                header::RETRY_AFTER,
-                retry_after.to_string().parse().unwrap(),
-            );
-        }
-
-        return Ok(response);
+        .record_request_messages(request.input.message_count());
+    req.set_prompt(&request.input.extract_text());
+    if let Some(user) = &request.user {
+        req.set_user(user);
     }
 
-    // Get latency profile (use model-specific if not configured)
-    let latency =
-        if state.config.latency.profile.is_some() || state.config.latency.ttft_mean_ms.is_some() {
-            state.config.latency_profile()
-        } else {
-            LatencyProfile::from_model(&request.model)
-        };
+    if let Some(error) = req.inject_error() {
+        return Ok(injected_error_response::<OpenResponsesFormat>(&error));
+    }
 
     // Generate response using the input text
     let input_text = request.input.extract_text();
@@ -619,17 +1434,17 @@ pub async fn create_openresponses_response(
     // Error turns are surfaced as HTTP errors here (works for both
     // streaming and non-streaming); tool-call turns are not yet
     // represented in OpenResponses output items (see specs/scripted-mode.md).
-    let content = if let Some(script) = state.script.as_ref() {
-        match script.next_turn() {
-            ScriptedResponse::Turn(SimTurn::Assistant { text }) => text,
-            ScriptedResponse::Turn(SimTurn::Mixed { text, .. }) => text,
-            ScriptedResponse::Turn(SimTurn::ToolCalls { .. }) => String::new(),
-            ScriptedResponse::Turn(SimTurn::Error(err)) => {
-                state.stats.record_error(err.status_code());
-                return Ok(sim_error_to_openresponses_response(&err));
-            }
+    let (content, truncated) = if let Some(script) = state.script.as_ref() {
+        match script.next_turn_with_tool_results(std::iter::empty()) {
+            ScriptedResponse::Turn(turn) => match turn.into_parts() {
+                Ok((text, _)) => (text.unwrap_or_default(), false),
+                Err(err) => {
+                    req.fail(err.status_code());
+                    return Ok(sim_error_to_openresponses_response(&err));
+                }
+            },
             ScriptedResponse::Exhausted => {
-                state.stats.record_error(500);
+                req.fail(500);
                 return Ok(sim_error_to_openresponses_response(&SimError::Other {
                     message: "llmsim script exhausted (on_exhausted=error)".to_string(),
                     status_code: Some(500),
@@ -637,10 +1452,6 @@ pub async fn create_openresponses_response(
             }
         }
     } else {
-        let generator = create_generator(
-            &state.config.response.generator,
-            state.config.response.target_tokens,
-        );
         let chat_request = ChatCompletionRequest {
             model: request.model.clone(),
             messages: vec![crate::openai::Message::user(&input_text)],
@@ -657,16 +1468,24 @@ pub async fn create_openresponses_response(
             user: request.user.clone(),
             tools: None,
             tool_choice: None,
+            parallel_tool_calls: None,
             response_format: None,
             seed: None,
+            stream_options: None,
+            functions: None,
+            function_call: None,
+            store: None,
+            metadata: None,
         };
-        generator.generate(&chat_request)
+        let content = state.pipeline.generate(&chat_request);
+        state
+            .pipeline
+            .limit_output(content, request.max_output_tokens)
     };
 
     // Count tokens
-    let input_tokens = count_openresponses_input_tokens(&request);
-    let output_tokens =
-        crate::count_tokens_default(&content).unwrap_or(content.split_whitespace().count());
+    let input_tokens = count_openresponses_input_tokens(&state.pipeline, &request);
+    let output_tokens = state.pipeline.count_tokens(&content);
     let usage = OpenResponsesUsage {
         input_tokens: input_tokens as u32,
         output_tokens: output_tokens as u32,
@@ -677,50 +1496,55 @@ pub async fn create_openresponses_response(
 
     if request.stream {
         // Streaming response
-        let stats = state.stats.clone();
         let input_tok = usage.input_tokens;
         let output_tok = usage.output_tokens;
 
         let stream = OpenResponsesStreamBuilder::new(&request.model, content)
-            .latency(latency)
+            .latency(req.request_latency(input_tok))
+            .sleeper(req.sleeper().clone())
+            .watchdog(req.stream_watchdog())
             .usage(usage)
-            .on_complete(move || {
-                stats.record_request_end(request_start.elapsed(), input_tok, output_tok);
-            })
+            .truncated(truncated)
+            .on_complete(req.finish_callback(input_tok, output_tok))
             .build();
 
-        let body = Body::from_stream(stream.into_stream().map(Ok::<_, std::io::Error>));
-
-        Ok(Response::builder()
-            .status(StatusCode::OK)
-            .header(header::CONTENT_TYPE, "text/event-stream")
-            .header(header::CACHE_CONTROL, "no-cache")
-            .header(header::CONNECTION, "keep-alive")
-            .body(body)
-            .unwrap())
+        let response = sse_response(&state, &req, stream.into_stream());
+        Ok(apply_cost(
+            &state,
+            response,
+            &request.model,
+            input_tok,
+            output_tok,
+        ))
     } else {
         // Non-streaming response - simulate time to generate
-        let delay = latency.sample_ttft();
-        if !delay.is_zero() {
-            tokio::time::sleep(delay).await;
-        }
-
-        // Record request completion
-        state.stats.record_request_end(
-            request_start.elapsed(),
-            usage.input_tokens,
-            usage.output_tokens,
-        );
+        req.simulate_generation(usage.input_tokens).await;
+        req.finish(usage.input_tokens, usage.output_tokens);
 
+        let (input_tok, output_tok) = (usage.input_tokens, usage.output_tokens);
         let response = OpenResponsesResponse::new(request.model.clone(), content, usage);
-        Ok(Json(response).into_response())
+        let response = if truncated {
+            response.truncated()
+        } else {
+            response
+        };
+        Ok(apply_cost(
+            &state,
+            Json(response).into_response(),
+            &request.model,
+            input_tok,
+            output_tok,
+        ))
     }
 }
 
 /// Count tokens in an OpenResponses request
-fn count_openresponses_input_tokens(request: &ResponseRequest) -> usize {
+fn count_openresponses_input_tokens(
+    pipeline: &RequestPipeline,
+    request: &ResponseRequest,
+) -> usize {
     let text = request.input.extract_text();
-    let mut total = crate::count_tokens_default(&text).unwrap_or(text.split_whitespace().count());
+    let mut total = pipeline.count_tokens(&text);
     // Account for image inputs (simulator approximation; see estimate_image_tokens).
     total += count_openresponses_input_image_tokens(&request.input);
     // Add overhead for request formatting
@@ -805,70 +1629,73 @@ pub async fn get_model(
 /// POST /openai/v1/responses
 pub async fn create_response(
     State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    overrides: RequestOverrides,
     Json(request): Json<ResponsesRequest>,
 ) -> Result<Response, AppError> {
-    let request_start = Instant::now();
-
     tracing::info!(
         model = %request.model,
         stream = request.stream,
         "Responses API request"
     );
 
-    // Record request start in stats
+    let req = state
+        .begin(&request.model, request.stream, EndpointType::Responses)
+        .with_overrides(&overrides);
     state
         .stats
-        .record_request_start(&request.model, request.stream, EndpointType::Responses);
-
-    // Check for error injection
-    let error_injector = ErrorInjector::new(state.config.error_config());
-    if let Some(error) = error_injector.maybe_inject() {
-        tracing::warn!("Injecting error: {:?}", error);
-
-        let status = match error.status_code() {
-            429 => StatusCode::TOO_MANY_REQUESTS,
-            500 => StatusCode::INTERNAL_SERVER_ERROR,
-            503 => StatusCode::SERVICE_UNAVAILABLE,
-            504 => StatusCode::GATEWAY_TIMEOUT,
-            400 => StatusCode::BAD_REQUEST,
-            401 => StatusCode::UNAUTHORIZED,
-            _ => StatusCode::INTERNAL_SERVER_ERROR,
-        };
-
-        // Record error in stats
-        state.stats.record_error(error.status_code());
-
-        let error_response = ResponsesErrorResponse {
-            error: crate::openai::ResponsesError::new(
-                error.to_error_response().error.error_type,
-                error.to_error_response().error.message,
-            ),
-        };
-
-        let mut response = Json(error_response).into_response();
-        *response.status_mut() = status;
+        .record_request_messages(request.input.item_count());
+    req.set_prompt(&extract_input_text(&request.input, &request.instructions));
 
-        if let Some(retry_after) = error.retry_after() {
-            response.headers_mut().insert(
-                header::RETRY_AFTER,
-                retry_after.to_string().parse().unwrap(),
-            );
-        }
+    if let Some(error) = req.inject_error() {
+        return Ok(injected_error_response::<ResponsesFormat>(&error));
+    }
 
-        return Ok(response);
+    if let Err(error) = check_responses_capabilities(
+        &state,
+        &request.model,
+        &request.input,
+        request.tools.as_deref(),
+    ) {
+        req.fail(400);
+        return Ok(responses_bad_request(error));
+    }
+    if let Err((status, error)) =
+        check_reasoning_items(&request.input, request.store != Some(false))
+    {
+        req.fail(status.as_u16());
+        return Ok(responses_refusal(status, error));
     }
 
+    // A chained response continues a stored conversation
+    let context = match &request.previous_response_id {
+        Some(id) => match state.responses.context_tokens(id) {
+            Some(tokens) => tokens,
+            None => {
+                req.fail(400);
+                return Ok(responses_bad_request(ResponsesError {
+                    code: Some("previous_response_not_found".to_string()),
+                    ..ResponsesError::new(
+                        "invalid_request_error",
+                        format!("Previous response with id '{}' not found.", id),
+                    )
+                }));
+            }
+        },
+        None => 0,
+    };
+
     // Scripted mode: handle non-streaming with full tool-call support;
     // streaming falls through to the text-based scripted result built
     // by generate_responses_result (text + error turns only).
     if let Some(script) = state.script.clone() {
         if !request.stream {
-            return Ok(handle_scripted_responses_api(state, request, request_start, script).await);
+            return Ok(handle_scripted_responses_api(state, request, req, script, context).await);
         }
     }
 
     // Generate response using shared logic
-    let result = generate_responses_result(
+    let mut result = generate_responses_result(
         &state,
         &ResponseGenerationParams {
             model: &request.model,
@@ -878,64 +1705,276 @@ pub async fn create_response(
             top_p: request.top_p,
             max_output_tokens: request.max_output_tokens,
             reasoning: &request.reasoning,
+            tools: request.tools.as_deref(),
+            tool_choice: request.tool_choice.as_ref(),
+            include: request.include.as_deref(),
+            headers: &headers,
         },
     );
+    if let Err(error) = carry_context(
+        &state,
+        &request.model,
+        context,
+        request.truncation,
+        &mut result.usage,
+    ) {
+        req.fail(400);
+        return Ok(responses_bad_request(error));
+    }
 
     if request.stream {
         // Streaming response
-        // Clone stats for the streaming completion callback
-        let stats = state.stats.clone();
         let input_tok = result.usage.input_tokens;
         let output_tok = result.usage.output_tokens;
 
         let mut builder = ResponsesTokenStreamBuilder::new(&request.model, result.content)
-            .latency(result.latency)
+            .latency(req.request_latency(input_tok))
+            .sleeper(req.sleeper().clone())
+            .watchdog(req.stream_watchdog())
             .usage(result.usage)
-            .on_complete(move || {
-                stats.record_request_end(request_start.elapsed(), input_tok, output_tok);
-            });
+            .truncated(result.truncated)
+            .refusal(result.refused)
+            .annotations(result.annotations)
+            .mcp_items(result.mcp_items)
+            .code_interpreter_call(result.code_interpreter_call)
+            .obfuscation(
+                request
+                    .stream_options
+                    .as_ref()
+                    .is_none_or(StreamOptions::obfuscation_enabled),
+            )
+            .usage_updates(state.config.stream.usage_updates_every)
+            .on_complete(req.finish_callback(input_tok, output_tok));
 
         if result.reasoning_tokens > 0 {
-            builder = builder.reasoning(result.reasoning_summary);
+            builder = builder
+                .reasoning(result.reasoning_summary)
+                .encrypted_reasoning(includes_encrypted_reasoning(request.include.as_deref()));
+        }
+        if request.store != Some(false) {
+            let responses = state.responses.clone();
+            let input_items = responses_store::input_items(&request.input);
+            builder = builder.on_response(move |response| responses.put(response, input_items));
         }
 
         let stream = builder.build();
 
-        let body = Body::from_stream(stream.into_stream().map(Ok::<_, std::io::Error>));
-
-        Ok(Response::builder()
-            .status(StatusCode::OK)
-            .header(header::CONTENT_TYPE, "text/event-stream")
-            .header(header::CACHE_CONTROL, "no-cache")
-            .header(header::CONNECTION, "keep-alive")
-            .body(body)
-            .unwrap())
+        let response = if request.background && request.store != Some(false) {
+            let log = state.responses.open_stream(stream.response_id());
+            background_sse_response(&state, &req, stream.into_stream(), log)
+        } else {
+            sse_response(&state, &req, stream.into_stream())
+        };
+        Ok(apply_cost(
+            &state,
+            response,
+            &request.model,
+            input_tok,
+            output_tok,
+        ))
     } else {
         // Non-streaming response - simulate time to generate
-        let delay = result.latency.sample_ttft();
-        if !delay.is_zero() {
-            tokio::time::sleep(delay).await;
-        }
-
-        // Record request completion
-        state.stats.record_request_end(
-            request_start.elapsed(),
-            result.usage.input_tokens,
-            result.usage.output_tokens,
-        );
+        req.simulate_generation(result.usage.input_tokens).await;
+        req.finish(result.usage.input_tokens, result.usage.output_tokens);
 
+        let (input_tok, output_tok) = (result.usage.input_tokens, result.usage.output_tokens);
         let response = if result.reasoning_tokens > 0 {
-            ResponsesResponse::with_reasoning(
+            let response = ResponsesResponse::with_reasoning(
                 request.model.clone(),
                 result.content,
                 result.reasoning_summary,
                 result.usage,
-            )
+            );
+            if includes_encrypted_reasoning(request.include.as_deref()) {
+                response.with_encrypted_reasoning(result.reasoning_tokens as u32)
+            } else {
+                response
+            }
         } else {
             ResponsesResponse::new(request.model.clone(), result.content, result.usage)
         };
-        Ok(Json(response).into_response())
+        let response = if result.truncated {
+            response.truncated()
+        } else if result.refused {
+            response.refused()
+        } else {
+            response
+        };
+        let response = result.mcp_items.into_iter().fold(
+            response.with_annotations(result.annotations),
+            |response, item| response.with_tool_item(item),
+        );
+        let response = match result.code_interpreter_call {
+            Some(call) => response.with_tool_item(call),
+            None => response,
+        };
+        if request.store != Some(false) {
+            state
+                .responses
+                .put(&response, responses_store::input_items(&request.input));
+        }
+        Ok(apply_cost(
+            &state,
+            Json(response).into_response(),
+            &request.model,
+            input_tok,
+            output_tok,
+        ))
+    }
+}
+
+/// GET /openai/v1/chat/completions - A page of the completions created with
+/// `store: true`, filtered by `model` and `metadata[key]=value`
+pub async fn list_chat_completions(
+    State(state): State<Arc<AppState>>,
+    uri: Uri,
+) -> Result<Response, AppError> {
+    let params = completions_store::list_params(uri.query()).map_err(AppError::BadRequest)?;
+    let filter = CompletionFilter::from_query(uri.query());
+    let page = state
+        .completions
+        .list(&params, &filter)
+        .map_err(AppError::BadRequest)?;
+    Ok(Json(page).into_response())
+}
+
+/// GET /openai/v1/chat/completions/{id} - A stored chat completion
+pub async fn get_chat_completion(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<Response, AppError> {
+    state
+        .completions
+        .get(&id)
+        .map(|completion| Json(completion).into_response())
+        .ok_or_else(|| no_chat_completion(&id))
+}
+
+/// POST /openai/v1/chat/completions/{id} - Replace the metadata of a stored
+/// chat completion
+pub async fn update_chat_completion(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Json(update): Json<MetadataUpdate>,
+) -> Result<Response, AppError> {
+    let metadata = update.metadata.unwrap_or_default();
+    validate_metadata(&metadata).map_err(AppError::BadRequest)?;
+    state
+        .completions
+        .set_metadata(&id, metadata)
+        .map(|completion| Json(completion).into_response())
+        .ok_or_else(|| no_chat_completion(&id))
+}
+
+/// DELETE /openai/v1/chat/completions/{id} - Forget a stored chat completion
+pub async fn delete_chat_completion(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<Response, AppError> {
+    if !state.completions.delete(&id) {
+        return Err(no_chat_completion(&id));
+    }
+    Ok(Json(completions_store::deleted(&id)).into_response())
+}
+
+fn no_chat_completion(id: &str) -> AppError {
+    AppError::NotFound(format!("No chat completion found with id '{}'", id))
+}
+
+/// GET /openai/v1/organization/usage/completions - Tokens and requests of
+/// the simulator's completed requests, in time buckets
+pub async fn get_completions_usage(
+    State(state): State<Arc<AppState>>,
+    uri: Uri,
+) -> Result<Response, AppError> {
+    let usage = usage_ledger(&state)?;
+    let query = UsageQuery::from_query(uri.query(), UsageEndpoint::Completions)
+        .map_err(AppError::BadRequest)?;
+    Ok(Json(usage.completions(&query)).into_response())
+}
+
+/// GET /openai/v1/organization/costs - What the simulator's completed
+/// requests cost, in daily buckets
+pub async fn get_costs(State(state): State<Arc<AppState>>, uri: Uri) -> Result<Response, AppError> {
+    let usage = usage_ledger(&state)?;
+    let query =
+        UsageQuery::from_query(uri.query(), UsageEndpoint::Costs).map_err(AppError::BadRequest)?;
+    Ok(Json(usage.costs(&query, &state.config)).into_response())
+}
+
+fn usage_ledger(state: &AppState) -> Result<&UsageLedger, AppError> {
+    state
+        .usage
+        .as_deref()
+        .ok_or_else(|| AppError::NotFound("Usage is not recorded ([usage] enabled = true)".into()))
+}
+
+/// GET /openai/v1/responses - WebSocket upgrade, or a page of the stored
+/// responses for a plain request
+pub async fn get_responses(
+    ws: Result<WebSocketUpgrade, WebSocketUpgradeRejection>,
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    uri: Uri,
+) -> Result<Response, AppError> {
+    if let Ok(ws) = ws {
+        return Ok(super::ws_handler::ws_responses(ws, State(state), headers).await);
+    }
+    let params = ListParams::from_query(uri.query()).map_err(AppError::BadRequest)?;
+    let page = state
+        .responses
+        .list(&params)
+        .map_err(AppError::BadRequest)?;
+    Ok(Json(page).into_response())
+}
+
+/// GET /openai/v1/responses/{id} - A stored response, or with `stream=true`
+/// the events of a background one, after `starting_after`
+pub async fn get_response(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    uri: Uri,
+) -> Result<Response, AppError> {
+    let params = StreamParams::from_query(uri.query()).map_err(AppError::BadRequest)?;
+    if !params.stream {
+        return state
+            .responses
+            .get(&id)
+            .map(|response| Json(response).into_response())
+            .ok_or_else(|| AppError::NotFound(format!("No response with id '{}'", id)));
     }
+    match state.responses.stream(&id) {
+        Some(log) => Ok(sse_body(
+            &state,
+            log.follow(0, params.starting_after).boxed(),
+        )),
+        None if state.responses.get(&id).is_some() => Err(AppError::BadRequest(
+            "This response cannot be streamed because it was not created with background=true."
+                .to_string(),
+        )),
+        None => Err(AppError::NotFound(format!("No response with id '{}'", id))),
+    }
+}
+
+/// GET /openai/v1/responses/{id}/input_items - A page of the input items a
+/// stored response was created from
+pub async fn list_input_items(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    uri: Uri,
+) -> Result<Json<ListResponse<serde_json::Value>>, AppError> {
+    let params = ListParams::from_query(uri.query()).map_err(AppError::BadRequest)?;
+    let items = state
+        .responses
+        .input_items(&id)
+        .ok_or_else(|| AppError::NotFound(format!("No response with id '{}'", id)))?;
+    let page = paginate(
+        &items,
+        |item| item["id"].as_str().unwrap_or_default(),
+        &params,
+    )
+    .map_err(AppError::BadRequest)?;
+    Ok(Json(page.cloned()))
 }
 
 /// POST /openai/v1/images/generations
@@ -946,64 +1985,36 @@ pub async fn create_response(
 /// the non-streaming JSON response and SSE streaming with partial images.
 pub async fn create_image(
     State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    overrides: RequestOverrides,
     Json(request): Json<ImageGenerationRequest>,
 ) -> Result<Response, AppError> {
-    let request_start = Instant::now();
-
     tracing::info!(
         model = %request.model,
         stream = request.stream,
         "Image generation request"
     );
 
-    state
-        .stats
-        .record_request_start(&request.model, request.stream, EndpointType::Images);
-
-    // Check for error injection (shares the configured error model).
-    let error_injector = ErrorInjector::new(state.config.error_config());
-    if let Some(error) = error_injector.maybe_inject() {
-        tracing::warn!("Injecting error: {:?}", error);
-
-        let status_code = error.status_code();
-        let status = match status_code {
-            429 => StatusCode::TOO_MANY_REQUESTS,
-            500 => StatusCode::INTERNAL_SERVER_ERROR,
-            503 => StatusCode::SERVICE_UNAVAILABLE,
-            504 => StatusCode::GATEWAY_TIMEOUT,
-            400 => StatusCode::BAD_REQUEST,
-            401 => StatusCode::UNAUTHORIZED,
-            _ => StatusCode::INTERNAL_SERVER_ERROR,
-        };
+    let req = state
+        .pipeline
+        .begin(&request.model, request.stream, EndpointType::Images)
+        .with_overrides(&overrides);
+    req.set_generator("imagegen");
 
-        state.stats.record_error(status_code);
-
-        let mut response = Json(error.to_error_response()).into_response();
-        *response.status_mut() = status;
-        if let Some(retry_after) = error.retry_after() {
-            response.headers_mut().insert(
-                header::RETRY_AFTER,
-                retry_after.to_string().parse().unwrap(),
-            );
-        }
-        return Ok(response);
+    // Error injection shares the configured error model.
+    if let Some(error) = req.inject_error() {
+        return Ok(injected_error_response::<OpenAIFormat>(&error));
     }
 
     // Image-generation timing is anchored to the configured latency profile;
     // model-derived latency (the default) yields realistic multi-second waits,
     // while `instant`/`fast` profiles collapse it for tests and load runs.
-    let latency =
-        if state.config.latency.profile.is_some() || state.config.latency.ttft_mean_ms.is_some() {
-            state.config.latency_profile()
-        } else {
-            LatencyProfile::from_model(&request.model)
-        };
+    let latency = req.latency().clone();
 
     let params = request.resolve();
 
     // Usage: text tokens from the prompt + estimated image (output) tokens.
-    let text_tokens = crate::count_tokens_default(&request.prompt)
-        .unwrap_or(request.prompt.split_whitespace().count()) as u32;
+    let text_tokens = state.pipeline.count_tokens(&request.prompt) as u32;
     let image_tokens =
         estimate_image_tokens(params.width, params.height, &params.quality) * params.n;
     let usage = ImagesUsage {
@@ -1017,10 +2028,6 @@ pub async fn create_image(
     };
 
     if request.stream {
-        let stats = state.stats.clone();
-        let input_tok = usage.input_tokens;
-        let output_tok = usage.output_tokens;
-
         let stream = ImageStream::new(
             &request.model,
             &request.prompt,
@@ -1028,19 +2035,10 @@ pub async fn create_image(
             latency,
             usage.clone(),
         )
-        .with_on_complete(move || {
-            stats.record_request_end(request_start.elapsed(), input_tok, output_tok);
-        });
+        .with_sleeper(req.sleeper().clone())
+        .with_on_complete(req.finish_callback(usage.input_tokens, usage.output_tokens));
 
-        let body = Body::from_stream(stream.into_stream().map(Ok::<_, std::io::Error>));
-
-        Ok(Response::builder()
-            .status(StatusCode::OK)
-            .header(header::CONTENT_TYPE, "text/event-stream")
-            .header(header::CACHE_CONTROL, "no-cache")
-            .header(header::CONNECTION, "keep-alive")
-            .body(body)
-            .unwrap())
+        Ok(sse_response(&state, &req, stream.into_stream()))
     } else {
         // Non-streaming: simulate the full generation time, then return all
         // images at once.
@@ -1052,7 +2050,7 @@ pub async fn create_image(
             params.n,
         ));
         if !total.is_zero() {
-            tokio::time::sleep(total).await;
+            req.sleeper().sleep(total).await;
         }
 
         let mut data = Vec::with_capacity(params.n as usize);
@@ -1065,18 +2063,20 @@ pub async fn create_image(
                 quality: &params.quality,
                 blockiness: 1,
             });
+            // DALL·E-style `response_format: "url"` links to a hosted copy
+            let (b64_json, url) = if request.response_format.as_deref() == Some("url") {
+                (None, Some(state.files.host(&headers, png, "image/png")))
+            } else {
+                (Some(base64_encode(&png)), None)
+            };
             data.push(ImageData {
-                b64_json: Some(base64_encode(&png)),
-                url: None,
+                b64_json,
+                url,
                 revised_prompt: None,
             });
         }
 
-        state.stats.record_request_end(
-            request_start.elapsed(),
-            usage.input_tokens,
-            usage.output_tokens,
-        );
+        req.finish(usage.input_tokens, usage.output_tokens);
 
         let response = ImageGenerationResponse {
             created: unix_timestamp(),
@@ -1092,7 +2092,7 @@ pub async fn create_image(
 }
 
 /// Extract text content from ResponsesInput for processing
-fn extract_input_text(input: &ResponsesInput, instructions: &Option<String>) -> String {
+pub(crate) fn extract_input_text(input: &ResponsesInput, instructions: &Option<String>) -> String {
     let mut parts = Vec::new();
 
     // Add instructions if present
@@ -1342,13 +2342,101 @@ fn validate_input_modalities(request: &ChatCompletionRequest) -> Result<(), AppE
     Ok(())
 }
 
+/// With `[models] enforce_capabilities`, refuse tools and JSON mode a known
+/// model lacks, with the error body the API sends. Images are refused by
+/// `validate_input_modalities` whether enforced or not.
+fn check_chat_capabilities(
+    state: &AppState,
+    request: &ChatCompletionRequest,
+) -> Result<(), ErrorResponse> {
+    let Some(capabilities) = enforced_capabilities(state, &request.model) else {
+        return Ok(());
+    };
+    if !capabilities.function_calling {
+        let param = if request.tools.as_ref().is_some_and(|t| !t.is_empty()) {
+            Some("tools")
+        } else if request.functions.as_ref().is_some_and(|f| !f.is_empty()) {
+            Some("functions")
+        } else {
+            None
+        };
+        if let Some(param) = param {
+            return Err(unsupported_parameter(param));
+        }
+    }
+    if let Some(format) = &request.response_format {
+        let json = matches!(format.format_type.as_str(), "json_object" | "json_schema");
+        if json && !capabilities.json_mode {
+            let mut error = ErrorResponse::invalid_request(format!(
+                "Invalid parameter: 'response_format' of type '{}' is not supported with this model.",
+                format.format_type
+            ));
+            error.error.param = Some("response_format".to_string());
+            return Err(error);
+        }
+    }
+    Ok(())
+}
+
+/// With `[models] enforce_capabilities`, refuse tools and image inputs a
+/// known model lacks, as the Responses API does
+pub(crate) fn check_responses_capabilities(
+    state: &AppState,
+    model: &str,
+    input: &ResponsesInput,
+    tools: Option<&[ResponsesTool]>,
+) -> Result<(), ResponsesError> {
+    let Some(capabilities) = enforced_capabilities(state, model) else {
+        return Ok(());
+    };
+    if !capabilities.function_calling && tools.is_some_and(|t| !t.is_empty()) {
+        let error = unsupported_parameter("tools").error;
+        return Err(ResponsesError {
+            code: error.code,
+            ..ResponsesError::new(error.error_type, error.message)
+        });
+    }
+    if !capabilities.vision && input.has_images() {
+        return Err(ResponsesError::new(
+            "invalid_request_error",
+            "Invalid content type. input_image is only supported by certain models.",
+        ));
+    }
+    Ok(())
+}
+
+/// Capabilities of `model` when they are enforced and known; unknown ids
+/// are let through, as by `validate_input_modalities`
+fn enforced_capabilities(
+    state: &AppState,
+    model: &str,
+) -> Option<&'static crate::openai::ModelCapabilities> {
+    if !state.config.models.enforce_capabilities {
+        return None;
+    }
+    crate::openai::get_model_profile(model).map(|profile| &profile.capabilities)
+}
+
+fn unsupported_parameter(param: &str) -> ErrorResponse {
+    let mut error = ErrorResponse::invalid_request(format!(
+        "Unsupported parameter: '{}' is not supported with this model.",
+        param
+    ))
+    .with_code("unsupported_parameter");
+    error.error.param = Some(param.to_string());
+    error
+}
+
 /// Count tokens in a chat request
-fn count_request_tokens(request: &ChatCompletionRequest) -> usize {
+pub(super) fn count_request_tokens(
+    pipeline: &RequestPipeline,
+    request: &ChatCompletionRequest,
+) -> usize {
     let mut total = 0;
     for message in &request.messages {
         if let Some(content) = &message.content {
             let text = content.text();
-            total += crate::count_tokens_default(&text).unwrap_or(text.split_whitespace().count());
+            total += pipeline.count_tokens(&text);
             // Account for image inputs (simulator approximation; see estimate_image_tokens).
             for image in content.images() {
                 total += crate::estimate_image_tokens(image.detail.as_deref());
@@ -1362,6 +2450,22 @@ fn count_request_tokens(request: &ChatCompletionRequest) -> usize {
     total
 }
 
+/// Prompt tokens of a chat request at about four characters a token, with
+/// the same overheads as `count_request_tokens`
+fn estimate_request_tokens(request: &ChatCompletionRequest) -> usize {
+    let mut total = 0;
+    for message in &request.messages {
+        if let Some(content) = &message.content {
+            total += content.text().chars().count().div_ceil(4);
+            for image in content.images() {
+                total += crate::estimate_image_tokens(image.detail.as_deref());
+            }
+        }
+        total += 4;
+    }
+    total + 3
+}
+
 /// Application error type
 #[derive(Debug)]
 #[allow(dead_code)]
@@ -1369,6 +2473,10 @@ pub enum AppError {
     NotFound(String),
     BadRequest(String),
     Internal(String),
+    /// The real upstream could not be reached (proxy mode)
+    BadGateway(String),
+    /// An admin endpoint called without `[keys] admin_key`
+    Unauthorized(String),
 }
 
 impl IntoResponse for AppError {
@@ -1385,6 +2493,14 @@ impl IntoResponse for AppError {
                 StatusCode::INTERNAL_SERVER_ERROR,
                 ErrorResponse::new(msg, "internal_error"),
             ),
+            AppError::BadGateway(msg) => (
+                StatusCode::BAD_GATEWAY,
+                ErrorResponse::new(msg, "upstream_error"),
+            ),
+            AppError::Unauthorized(msg) => (
+                StatusCode::UNAUTHORIZED,
+                ErrorResponse::new(msg, "authentication_error"),
+            ),
         };
 
         let mut response = Json(error_response).into_response();
@@ -1398,6 +2514,38 @@ mod tests {
     use super::*;
     use crate::openai::Message;
 
+    #[test]
+    fn test_split_inside_chars() {
+        let frames = split_inside_chars("hé👋!");
+        assert_eq!(
+            frames,
+            vec![
+                b"h\xc3".to_vec(),
+                b"\xa9\xf0".to_vec(),
+                b"\x9f\x91\x8b!".to_vec()
+            ]
+        );
+        assert!(frames.iter().all(|f| std::str::from_utf8(f).is_err()));
+        assert_eq!(frames.concat(), "hé👋!".as_bytes());
+
+        // ASCII-only events stay whole
+        assert_eq!(split_inside_chars("data: hi"), vec![b"data: hi".to_vec()]);
+    }
+
+    #[tokio::test]
+    async fn test_buffer_frames_by_size() {
+        let frames = futures_util::stream::iter((0..10).map(|_| b"abc".to_vec()));
+        let sizes: Vec<usize> = buffer_frames(frames, Some(8), None)
+            .map(|f| f.len())
+            .collect()
+            .await;
+        assert_eq!(sizes, vec![9, 9, 9, 3]);
+    }
+
+    fn pipeline() -> RequestPipeline {
+        RequestPipeline::new(crate::stats::new_shared_stats())
+    }
+
     #[test]
     fn test_count_request_tokens() {
         let request = ChatCompletionRequest {
@@ -1419,11 +2567,17 @@ mod tests {
             user: None,
             tools: None,
             tool_choice: None,
+            parallel_tool_calls: None,
             response_format: None,
             seed: None,
+            stream_options: None,
+            functions: None,
+            function_call: None,
+            store: None,
+            metadata: None,
         };
 
-        let tokens = count_request_tokens(&request);
+        let tokens = count_request_tokens(&pipeline(), &request);
         assert!(tokens > 0);
     }
 
@@ -1452,7 +2606,8 @@ mod tests {
         )
         .unwrap();
 
-        let delta = count_request_tokens(&with_image) - count_request_tokens(&text_only);
+        let delta = count_request_tokens(&pipeline(), &with_image)
+            - count_request_tokens(&pipeline(), &text_only);
         assert_eq!(delta, crate::tokens::IMAGE_TOKENS_HIGH);
     }
 
@@ -1465,7 +2620,8 @@ mod tests {
         let none: ChatCompletionRequest =
             serde_json::from_str(r#"{"model":"gpt-4o","messages":[{"role":"user","content":[]}]}"#)
                 .unwrap();
-        let delta = count_request_tokens(&low) - count_request_tokens(&none);
+        let delta =
+            count_request_tokens(&pipeline(), &low) - count_request_tokens(&pipeline(), &none);
         assert_eq!(delta, crate::tokens::IMAGE_TOKENS_LOW);
     }
 
@@ -1542,12 +2698,42 @@ mod tests {
             user: None,
             tools: None,
             tool_choice: None,
+            parallel_tool_calls: None,
             response_format: None,
             seed: None,
+            stream_options: None,
+            functions: None,
+            function_call: None,
+            store: None,
+            metadata: None,
         };
         assert!(validate_input_modalities(&request).is_ok());
     }
 
+    #[test]
+    fn test_apply_cost_header() {
+        let mut config = crate::cli::Config::default();
+        config.cost.enabled = true;
+        config.cost.header = "x-litellm-response-cost".to_string();
+        let state = AppState::new(config, crate::new_shared_stats());
+
+        let response = apply_cost(&state, StatusCode::OK.into_response(), "gpt-5", 1_000, 500);
+        assert_eq!(response.headers()["x-litellm-response-cost"], "0.00625");
+        assert!((state.stats.total_cost_usd() - 0.00625).abs() < 1e-9);
+
+        // Unpriced models get no header and add nothing to spend.
+        let response = apply_cost(&state, StatusCode::OK.into_response(), "custom", 10, 10);
+        assert!(response.headers().get("x-litellm-response-cost").is_none());
+    }
+
+    #[test]
+    fn test_apply_cost_disabled_still_tracks_spend() {
+        let state = AppState::new(crate::cli::Config::default(), crate::new_shared_stats());
+        let response = apply_cost(&state, StatusCode::OK.into_response(), "gpt-5", 1_000, 500);
+        assert!(response.headers().get("x-llmsim-response-cost").is_none());
+        assert!(state.stats.total_cost_usd() > 0.0);
+    }
+
     #[tokio::test]
     async fn test_health_endpoint() {
         let response = health().await.into_response();
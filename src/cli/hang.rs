@@ -0,0 +1,91 @@
+// Hung Requests Module
+// Router middleware applying `[errors] hang_rate`: a generation request is
+// accepted and then never answered, until the client gives up, or until
+// `hang_respond_after_ms` when it is answered with 408 Request Timeout.
+//
+// Decision: this is a client timeout, where the 504 of `timeout_rate` is a
+// server one: a 504 comes back at once, so a client's own deadline never
+// fires. The hold is a middleware, like the error rules, so every protocol
+// hangs the same way before its handler does any work, and it sits inside the
+// error rules so a request a rule fails is still answered. How long each
+// client waited is measured from acceptance to the 408 or to the disconnect,
+// which is when the held future is dropped; a drop is the only way the server
+// sees a client give up.
+
+use super::deprecation::error_response;
+use super::proxy::endpoint_type;
+use super::request_body::ParsedBody;
+use super::state::AppState;
+use crate::clock;
+use crate::decisions::ErrorCause;
+use crate::errors::SimulatedError;
+use crate::stats::SharedStats;
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::Response,
+};
+use rand::RngExt;
+use std::sync::Arc;
+use std::time::Instant;
+
+/// A client waiting on a hung request; dropped unanswered, it gave up
+struct Waiting {
+    stats: SharedStats,
+    started: Instant,
+    answered: bool,
+}
+
+impl Drop for Waiting {
+    fn drop(&mut self) {
+        let waited = clock::elapsed(self.started);
+        if self.answered {
+            self.stats.record_client_wait(waited);
+        } else {
+            tracing::info!(
+                waited_ms = waited.as_millis() as u64,
+                "Client gave up on a hung request"
+            );
+            self.stats.record_client_timeout(waited);
+        }
+    }
+}
+
+/// Middleware: hold generation requests at `[errors] hang_rate`
+pub async fn hang_requests(
+    State(state): State<Arc<AppState>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let rate = state.config.errors.hang_rate;
+    let path = request.uri().path().to_string();
+    let endpoint = (request.method() == "POST")
+        .then(|| endpoint_type(&path))
+        .flatten();
+    let Some(endpoint) = endpoint.filter(|_| rand::rng().random_bool(rate.clamp(0.0, 1.0))) else {
+        return next.run(request).await;
+    };
+
+    let body = match ParsedBody::read(&mut request).await {
+        Ok(body) => body,
+        Err(response) => return response,
+    };
+    let model = body.model.as_str();
+    tracing::info!(model, "Holding request without an answer");
+
+    let req = state.begin(model, body.stream, endpoint);
+    state.stats.record_hang();
+    let mut waiting = Waiting {
+        stats: state.stats.clone(),
+        started: clock::now(),
+        answered: false,
+    };
+    let Some(after) = state.config.hang_respond_after() else {
+        return std::future::pending().await;
+    };
+    req.sleeper().sleep(after).await;
+
+    waiting.answered = true;
+    let error = req.fail_with(ErrorCause::Injected, SimulatedError::RequestTimeout);
+    error_response(&path, endpoint, &error)
+}
@@ -0,0 +1,160 @@
+// Include Module
+// `include = ["base.toml", "overrides/staging.toml"]` at the top of a config
+// file: the listed files are loaded first, each layered over the one before,
+// and the including file is layered over them all.
+//
+// Decision: layering is the same deep merge as `--preset` with `--config`:
+// tables merge key by key and anything else, arrays included, replaces what
+// was there. Replacing arrays keeps `[[errors.rules]]` and model lists
+// predictable: the last file to set one has the whole list. Paths are relative
+// to the file that names them, so a scenario library can move as a directory.
+// Placeholders are expanded after merging, so a base file can use variables
+// that only some environments set.
+
+use std::path::{Path, PathBuf};
+
+/// Replace a parsed config file's `include` key with the files it names,
+/// merged under it. `dir` is the directory relative paths start from.
+pub(crate) fn resolve_includes(table: &mut toml::Table, dir: &Path) -> Result<(), String> {
+    resolve(table, dir, &mut Vec::new())
+}
+
+fn resolve(table: &mut toml::Table, dir: &Path, chain: &mut Vec<PathBuf>) -> Result<(), String> {
+    let Some(include) = table.remove("include") else {
+        return Ok(());
+    };
+    let paths = match include {
+        toml::Value::String(path) => vec![path],
+        toml::Value::Array(paths) => paths
+            .into_iter()
+            .map(|path| match path {
+                toml::Value::String(path) => Ok(path),
+                other => Err(format!("include paths must be strings, found {}", other)),
+            })
+            .collect::<Result<_, _>>()?,
+        other => return Err(format!("include must be a list of paths, found {}", other)),
+    };
+
+    let mut merged = toml::Table::new();
+    for path in paths {
+        let path = dir.join(path);
+        let canonical = path
+            .canonicalize()
+            .map_err(|e| format!("include {}: {}", path.display(), e))?;
+        if chain.contains(&canonical) {
+            let cycle: Vec<String> = chain
+                .iter()
+                .chain(std::iter::once(&canonical))
+                .map(|p| p.display().to_string())
+                .collect();
+            return Err(format!("include cycle: {}", cycle.join(" -> ")));
+        }
+        let text = std::fs::read_to_string(&canonical)
+            .map_err(|e| format!("include {}: {}", path.display(), e))?;
+        let mut included: toml::Table = text
+            .parse()
+            .map_err(|e| format!("include {}: {}", path.display(), e))?;
+        chain.push(canonical.clone());
+        resolve(
+            &mut included,
+            canonical.parent().unwrap_or(Path::new(".")),
+            chain,
+        )?;
+        chain.pop();
+        merge(&mut merged, included);
+    }
+    merge(&mut merged, std::mem::take(table));
+    *table = merged;
+    Ok(())
+}
+
+/// Layer `top` over `base`: tables merge key by key, anything else in `top`
+/// replaces what `base` has
+pub(crate) fn merge(base: &mut toml::Table, top: toml::Table) {
+    for (key, value) in top {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(top)) => merge(base, top),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cli::Config;
+    use std::path::PathBuf;
+
+    fn scenario_dir(tag: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("llmsim_include_{}_{}", std::process::id(), tag));
+        for (name, contents) in files {
+            let path = dir.join(name);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, contents).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn test_includes_layer_in_order() {
+        let dir = scenario_dir(
+            "layers",
+            &[
+                (
+                    "base.toml",
+                    "[latency]\nttft_mean_ms = 500\ntbt_mean_ms = 20\n\n[models]\navailable = [\"gpt-5\", \"o3\"]\n",
+                ),
+                (
+                    "overrides/staging.toml",
+                    "include = [\"errors.toml\"]\n\n[latency]\nttft_mean_ms = 900\n",
+                ),
+                (
+                    "overrides/errors.toml",
+                    "[errors]\nrate_limit_rate = 0.1\n\n[[errors.rules]]\nstatus = 503\nrate = 0.5\n",
+                ),
+                (
+                    "scenario.toml",
+                    "include = [\"base.toml\", \"overrides/staging.toml\"]\n\n[server]\nport = 9100\n\n[errors]\nserver_error_rate = 0.2\n",
+                ),
+            ],
+        );
+
+        let config = Config::from_file(dir.join("scenario.toml")).unwrap();
+        assert_eq!(config.latency.ttft_mean_ms, Some(900));
+        assert_eq!(config.latency.tbt_mean_ms, Some(20));
+        assert_eq!(config.models.available, vec!["gpt-5", "o3"]);
+        assert_eq!(config.errors.rate_limit_rate, 0.1);
+        assert_eq!(config.errors.server_error_rate, 0.2);
+        assert_eq!(config.errors.rules.len(), 1);
+        assert_eq!(config.server.port, 9100);
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_include_errors() {
+        let dir = scenario_dir(
+            "errors",
+            &[
+                ("a.toml", "include = [\"b.toml\"]\n"),
+                ("b.toml", "include = \"a.toml\"\n"),
+                ("missing.toml", "include = [\"nowhere.toml\"]\n"),
+            ],
+        );
+
+        let err = Config::from_file(dir.join("a.toml"))
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("include cycle"), "{}", err);
+        let err = Config::from_file(dir.join("missing.toml"))
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("nowhere.toml"), "{}", err);
+        let err = Config::from_toml("include = 3\n").unwrap_err().to_string();
+        assert!(err.contains("list of paths"), "{}", err);
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,290 @@
+// Interpolate Module
+// `${VAR}` and `${VAR:-fallback}` in config file strings, replaced with
+// environment variables so one file serves every environment.
+//
+// Decision: placeholders live inside TOML strings rather than being spliced
+// into the text before parsing, so a config file stays valid TOML that editors
+// and `llmsim config validate` can read. Expansions stay strings; a string
+// that is nothing but a placeholder is read as a TOML value only where the
+// field it fills refuses a string: `port = "${PORT:-8080}"` becomes an
+// integer and `available = "${MODELS}"` with `MODELS='["gpt-5", "o3"]'` an
+// array, while an API key or model name made of digits stays the string it
+// is. Which fields refuse one is learned from the deserializer's errors, one
+// field at a time, so no field types are listed here.
+
+use serde::de::DeserializeOwned;
+use serde_path_to_error::Segment;
+
+/// A step into a config table: a key or an array index
+#[derive(Debug, Clone, PartialEq)]
+enum Step {
+    Key(String),
+    Index(usize),
+}
+
+/// Whole-placeholder expansions that also read as numbers, booleans or
+/// arrays, by where they are in the table
+#[derive(Debug, Default)]
+pub(crate) struct Expansions {
+    typed: Vec<(Vec<Step>, toml::Value)>,
+}
+
+impl Expansions {
+    /// Deserialize the expanded `table`, giving each expansion its typed
+    /// reading where its field refuses the string
+    pub(crate) fn deserialize<T: DeserializeOwned>(
+        mut self,
+        mut table: toml::Table,
+    ) -> Result<T, toml::de::Error> {
+        loop {
+            let error = match serde_path_to_error::deserialize(table.clone()) {
+                Ok(value) => return Ok(value),
+                Err(error) => error,
+            };
+            let path: Option<Vec<Step>> = error
+                .path()
+                .iter()
+                .map(|segment| match segment {
+                    Segment::Map { key } => Some(Step::Key(key.clone())),
+                    Segment::Seq { index } => Some(Step::Index(*index)),
+                    _ => None,
+                })
+                .collect();
+            let typed = path.and_then(|path| {
+                let i = self.typed.iter().position(|(at, _)| *at == path)?;
+                Some(self.typed.swap_remove(i))
+            });
+            let Some((path, typed)) = typed else {
+                return Err(error.into_inner());
+            };
+            if let Some(value) = value_at(&mut table, &path) {
+                *value = typed;
+            }
+        }
+    }
+}
+
+/// Replace the placeholders in every string of a parsed config file with
+/// environment variables
+pub(crate) fn expand_env(table: &mut toml::Table) -> Result<Expansions, String> {
+    let mut expansions = Expansions::default();
+    expand_table(
+        table,
+        &|name| std::env::var(name).ok(),
+        &mut Vec::new(),
+        &mut expansions,
+    )?;
+    Ok(expansions)
+}
+
+fn expand_table(
+    table: &mut toml::Table,
+    lookup: &dyn Fn(&str) -> Option<String>,
+    path: &mut Vec<Step>,
+    expansions: &mut Expansions,
+) -> Result<(), String> {
+    for (key, value) in table.iter_mut() {
+        path.push(Step::Key(key.clone()));
+        expand_value(value, lookup, path, expansions)?;
+        path.pop();
+    }
+    Ok(())
+}
+
+fn expand_value(
+    value: &mut toml::Value,
+    lookup: &dyn Fn(&str) -> Option<String>,
+    path: &mut Vec<Step>,
+    expansions: &mut Expansions,
+) -> Result<(), String> {
+    match value {
+        toml::Value::String(text) if text.contains('$') => {
+            let whole = is_single_placeholder(text);
+            let expanded = expand_str(text, lookup)?;
+            if let Some(typed) = whole.then(|| typed(&expanded)).flatten() {
+                expansions.typed.push((path.clone(), typed));
+            }
+            *value = toml::Value::String(expanded);
+        }
+        toml::Value::Array(values) => {
+            for (i, value) in values.iter_mut().enumerate() {
+                path.push(Step::Index(i));
+                expand_value(value, lookup, path, expansions)?;
+                path.pop();
+            }
+        }
+        toml::Value::Table(table) => expand_table(table, lookup, path, expansions)?,
+        _ => {}
+    }
+    Ok(())
+}
+
+/// The value at `path` in `table`
+fn value_at<'a>(table: &'a mut toml::Table, path: &[Step]) -> Option<&'a mut toml::Value> {
+    let (Step::Key(first), rest) = path.split_first()? else {
+        return None;
+    };
+    let mut value = table.get_mut(first)?;
+    for step in rest {
+        value = match step {
+            Step::Key(key) => value.get_mut(key.as_str())?,
+            Step::Index(i) => value.get_mut(*i)?,
+        };
+    }
+    Some(value)
+}
+
+/// Expand the placeholders of one string; `$${` writes a literal `${`
+fn expand_str(text: &str, lookup: &dyn Fn(&str) -> Option<String>) -> Result<String, String> {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('$') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        if let Some(after) = rest.strip_prefix("$${") {
+            out.push_str("${");
+            rest = after;
+            continue;
+        }
+        let Some(after) = rest.strip_prefix("${") else {
+            out.push('$');
+            rest = &rest[1..];
+            continue;
+        };
+        let end = after
+            .find('}')
+            .ok_or_else(|| format!("unclosed placeholder in {:?}", text))?;
+        let (name, fallback) = match after[..end].split_once(":-") {
+            Some((name, fallback)) => (name, Some(fallback)),
+            None => (&after[..end], None),
+        };
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err(format!("invalid variable name {:?} in {:?}", name, text));
+        }
+        match (lookup(name).filter(|v| !v.is_empty()), fallback) {
+            (Some(value), _) => out.push_str(&value),
+            (None, Some(fallback)) => out.push_str(fallback),
+            (None, None) => {
+                return Err(format!(
+                    "environment variable {} is not set (use ${{{}:-fallback}} for a default)",
+                    name, name
+                ))
+            }
+        }
+        rest = &after[end + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+fn is_single_placeholder(text: &str) -> bool {
+    text.strip_prefix("${")
+        .and_then(|t| t.strip_suffix('}'))
+        .is_some_and(|inner| !inner.contains('}'))
+}
+
+/// An expansion read as a number, boolean or array, if it is one
+fn typed(expanded: &str) -> Option<toml::Value> {
+    let value = format!("value = {}", expanded)
+        .parse::<toml::Table>()
+        .ok()?
+        .remove("value")?;
+    match value {
+        toml::Value::Integer(_)
+        | toml::Value::Float(_)
+        | toml::Value::Boolean(_)
+        | toml::Value::Array(_) => Some(value),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    fn lookup(name: &str) -> Option<String> {
+        match name {
+            "PORT" => Some("9001".to_string()),
+            "RATE" => Some("0.25".to_string()),
+            "MODELS" => Some(r#"["gpt-5", "o3"]"#.to_string()),
+            "DIR" => Some("/var/llmsim".to_string()),
+            "KEY" => Some("12345".to_string()),
+            "EMPTY" => Some(String::new()),
+            _ => None,
+        }
+    }
+
+    fn expand(toml_str: &str) -> Result<(toml::Table, Expansions), String> {
+        let mut table: toml::Table = toml_str.parse().unwrap();
+        let mut expansions = Expansions::default();
+        expand_table(&mut table, &lookup, &mut Vec::new(), &mut expansions)?;
+        Ok((table, expansions))
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct Server {
+        port: u16,
+        host: String,
+        api_key: Option<String>,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct Settings {
+        server: Server,
+        rate: f64,
+        models: Vec<String>,
+        names: Vec<String>,
+        ports: Vec<u16>,
+    }
+
+    #[test]
+    fn test_placeholders_take_the_type_of_their_field() {
+        let (table, expansions) = expand(
+            "rate = \"${RATE}\"\nmodels = \"${MODELS}\"\nnames = [\"${PORT}\", \"b\"]\nports = [\"${PORT}\"]\n\n\
+             [server]\nport = \"${PORT}\"\nhost = \"${HOST:-127.0.0.1}\"\napi_key = \"${KEY}\"\n",
+        )
+        .unwrap();
+        // Expanded, everything is still a string
+        assert_eq!(table["server"]["port"].as_str(), Some("9001"));
+
+        let settings: Settings = expansions.deserialize(table).unwrap();
+        assert_eq!(settings.server.port, 9001);
+        assert_eq!(settings.server.host, "127.0.0.1");
+        assert_eq!(settings.server.api_key.as_deref(), Some("12345"));
+        assert_eq!(settings.rate, 0.25);
+        assert_eq!(settings.models, ["gpt-5", "o3"]);
+        assert_eq!(settings.names, ["9001", "b"]);
+        assert_eq!(settings.ports, [9001]);
+    }
+
+    #[test]
+    fn test_placeholders_within_strings() {
+        let (table, _) = expand(
+            "path = \"${DIR}/script-${PORT}.json\"\nfallback = \"${EMPTY:-x}\"\nliteral = \"$${DIR} costs $5\"\n",
+        )
+        .unwrap();
+        assert_eq!(table["path"].as_str(), Some("/var/llmsim/script-9001.json"));
+        assert_eq!(table["fallback"].as_str(), Some("x"));
+        assert_eq!(table["literal"].as_str(), Some("${DIR} costs $5"));
+    }
+
+    #[test]
+    fn test_a_string_that_reads_as_nothing_else_still_fails() {
+        let (table, expansions) = expand("port = \"${DIR}\"\nhost = \"h\"\n").unwrap();
+        let err = expansions.deserialize::<Server>(table).unwrap_err();
+        assert!(err.to_string().contains("invalid type"), "{}", err);
+    }
+
+    #[test]
+    fn test_unset_and_malformed_placeholders_fail() {
+        let err = expand("port = \"${MISSING}\"\n").unwrap_err();
+        assert!(err.contains("MISSING is not set"), "{}", err);
+        assert!(expand("port = \"${PORT\"\n")
+            .unwrap_err()
+            .contains("unclosed"));
+        assert!(expand("port = \"${A B}\"\n")
+            .unwrap_err()
+            .contains("invalid variable name"));
+    }
+}
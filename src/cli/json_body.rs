@@ -0,0 +1,136 @@
+// JSON Body Module
+// Router middleware applying `[json_body]`: JSON responses labelled
+// `application/json; charset=utf-8` instead of bare `application/json`, and
+// bodies starting with a UTF-8 byte order mark or ending with a newline, the
+// ways providers' JSON differs on the wire, to catch strict client parsers.
+//
+// Decision: a middleware inside compression and framing, so the bytes it adds
+// are compressed and counted in `Content-Length` like the rest of the body,
+// and outside every request layer, so error bodies vary the same way as
+// answers. Only non-streaming `application/json` bodies are touched; SSE
+// events are another parser's problem, with faults of their own in
+// `[stream]`. A region's responses are changed by its own router.
+
+use super::config::{JsonBodyConfig, JsonContentType};
+use super::state::AppState;
+use axum::{
+    body::{to_bytes, Body},
+    extract::{Request, State},
+    http::{header, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use rand::RngExt;
+use std::sync::Arc;
+
+/// UTF-8 byte order mark
+const BOM: &[u8] = b"\xEF\xBB\xBF";
+const CHARSET_JSON: &str = "application/json; charset=utf-8";
+
+/// What is done to one JSON body
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Variation {
+    charset: bool,
+    bom: bool,
+    trailing_newline: bool,
+}
+
+impl Variation {
+    fn draw(config: &JsonBodyConfig) -> Self {
+        let mut rng = rand::rng();
+        let charset = match config.content_type {
+            JsonContentType::Bare => false,
+            JsonContentType::Charset => true,
+            JsonContentType::Mixed => rng.random_bool(0.5),
+        };
+        Self {
+            charset,
+            bom: rng.random_bool(config.bom_rate.clamp(0.0, 1.0)),
+            trailing_newline: rng.random_bool(config.trailing_newline_rate.clamp(0.0, 1.0)),
+        }
+    }
+
+    /// Whether the body itself changes, so it has to be buffered
+    fn pads(self) -> bool {
+        self.bom || self.trailing_newline
+    }
+
+    fn apply(self, body: &[u8]) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(body.len() + BOM.len() + 1);
+        if self.bom {
+            bytes.extend_from_slice(BOM);
+        }
+        bytes.extend_from_slice(body);
+        if self.trailing_newline {
+            bytes.push(b'\n');
+        }
+        bytes
+    }
+}
+
+/// Middleware: vary JSON response bodies as `[json_body]` asks
+pub async fn vary_json_bodies(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let response = next.run(request).await;
+    let json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/json"));
+    if !json {
+        return response;
+    }
+
+    let variation = Variation::draw(&state.config.json_body);
+    let (mut parts, body) = response.into_parts();
+    if variation.charset {
+        parts
+            .headers
+            .insert(header::CONTENT_TYPE, HeaderValue::from_static(CHARSET_JSON));
+    }
+    if !variation.pads() {
+        return Response::from_parts(parts, body);
+    }
+    let Ok(bytes) = to_bytes(body, usize::MAX).await else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+    parts.headers.remove(header::CONTENT_LENGTH);
+    Response::from_parts(parts, Body::from(variation.apply(&bytes)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply() {
+        let body = br#"{"ok":true}"#;
+        let plain = Variation {
+            charset: false,
+            bom: false,
+            trailing_newline: false,
+        };
+        assert!(!plain.pads());
+        let padded = Variation {
+            bom: true,
+            trailing_newline: true,
+            ..plain
+        };
+        assert_eq!(padded.apply(body), b"\xEF\xBB\xBF{\"ok\":true}\n");
+    }
+
+    #[test]
+    fn test_draw() {
+        let config = JsonBodyConfig {
+            content_type: JsonContentType::Charset,
+            bom_rate: 1.0,
+            trailing_newline_rate: 0.0,
+        };
+        let variation = Variation::draw(&config);
+        assert!(variation.charset && variation.bom && !variation.trailing_newline);
+        assert!(!Variation::draw(&JsonBodyConfig::default()).pads());
+    }
+}
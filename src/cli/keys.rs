@@ -0,0 +1,391 @@
+// Keys Module
+// API keys created and revoked at runtime through `/llmsim/keys`, each with
+// its own quotas and latency profile, and the router middleware refusing
+// generation requests without a live one when `[keys]` is enabled.
+//
+// Decision: a shared simulator on-boards a new test client with one admin
+// call instead of a config edit and a restart. Keys are kept in a JSON file
+// rewritten whole on every change (temp file, then rename), so a restart
+// keeps them and a crash never leaves half a file; the list is small and
+// changes rarely. Revoked keys stay in the file with their revocation time,
+// so a refused client can be told apart from one never on-boarded. A key's
+// quotas replace the `[rate_limits]` ones for its requests, and its latency
+// profile reaches the pipeline as a request override, the way the request's
+// own `llmsim` extension does. Key values are shown in full only in the
+// response creating them; listings mask them like the stats do.
+
+use super::clients::mask_key;
+use super::config::KeysConfig;
+use super::deprecation::error_response;
+use super::proxy::endpoint_type;
+use super::rate_limits::api_key;
+use super::request_body::ParsedBody;
+use super::state::AppState;
+use crate::clock;
+use crate::decisions::ErrorCause;
+use crate::errors::SimulatedError;
+use crate::latency::LatencyProfile;
+use crate::overrides::RequestOverrides;
+use crate::ratelimit::KeyLimits;
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderMap},
+    middleware::Next,
+    response::Response,
+};
+use rand::RngExt;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+/// Prefix of the key values the simulator mints
+const KEY_PREFIX: &str = "sk-sim-";
+/// Shortest custom key value accepted
+const MIN_KEY_CHARS: usize = 8;
+
+/// One API key
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ApiKey {
+    /// `key_...`, the handle revoking it
+    pub id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// The secret clients send as `Authorization: Bearer` or `x-api-key`
+    pub key: String,
+    pub created_at: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requests_per_minute: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tokens_per_minute: Option<u32>,
+    /// Latency profile its requests get in place of the model's
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latency_profile: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revoked_at: Option<u64>,
+}
+
+impl ApiKey {
+    /// Quotas of the key's requests; unset ones fall back to `[rate_limits]`
+    pub fn limits(&self) -> KeyLimits {
+        KeyLimits {
+            requests_per_minute: self.requests_per_minute,
+            tokens_per_minute: self.tokens_per_minute,
+        }
+    }
+
+    /// The key as the admin endpoints show it, its value masked unless
+    /// `reveal`
+    pub fn to_json(&self, reveal: bool) -> Value {
+        let mut key = serde_json::to_value(self).unwrap_or_default();
+        key["object"] = json!("llmsim.api_key");
+        if !reveal {
+            key["key"] = json!(mask_key(&self.key));
+        }
+        key
+    }
+}
+
+/// Body of `POST /llmsim/keys`
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NewKey {
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Value to use instead of a minted `sk-sim-...` one, for clients whose
+    /// key is already baked into their configuration
+    #[serde(default)]
+    pub key: Option<String>,
+    #[serde(default)]
+    pub requests_per_minute: Option<u32>,
+    #[serde(default)]
+    pub tokens_per_minute: Option<u32>,
+    #[serde(default)]
+    pub latency_profile: Option<String>,
+}
+
+/// Why a key could not be created
+#[derive(Debug)]
+pub enum KeyError {
+    /// The request asked for something impossible
+    Invalid(String),
+    /// The key file could not be written; the key was not created
+    Save(String),
+}
+
+/// The keys, persisted to `[keys] file` when set
+pub struct KeyStore {
+    path: Option<PathBuf>,
+    keys: Mutex<Vec<ApiKey>>,
+}
+
+impl KeyStore {
+    /// Store holding the keys of `[keys] file`; an unreadable file is
+    /// reported and starts the store empty, and is replaced on the first
+    /// change
+    pub fn open(config: &KeysConfig) -> Self {
+        let path = config.file.as_ref().map(PathBuf::from);
+        let keys = match &path {
+            Some(path) if path.exists() => std::fs::read_to_string(path)
+                .map_err(|e| e.to_string())
+                .and_then(|json| serde_json::from_str(&json).map_err(|e| e.to_string()))
+                .inspect_err(|e| tracing::warn!("Not loading keys from {}: {}", path.display(), e))
+                .unwrap_or_default(),
+            _ => Vec::new(),
+        };
+        Self {
+            path,
+            keys: Mutex::new(keys),
+        }
+    }
+
+    /// Create a key; on success its value is the only copy shown in full
+    pub fn create(&self, new: NewKey) -> Result<ApiKey, KeyError> {
+        if let Some(profile) = &new.latency_profile {
+            if LatencyProfile::named(profile).is_none() {
+                return Err(KeyError::Invalid(format!(
+                    "Unknown latency_profile '{}'",
+                    profile
+                )));
+            }
+        }
+        if [new.requests_per_minute, new.tokens_per_minute].contains(&Some(0)) {
+            return Err(KeyError::Invalid(
+                "requests_per_minute and tokens_per_minute must be at least 1".into(),
+            ));
+        }
+        if new
+            .key
+            .as_ref()
+            .is_some_and(|key| key.chars().count() < MIN_KEY_CHARS)
+        {
+            return Err(KeyError::Invalid(format!(
+                "key must be at least {} characters",
+                MIN_KEY_CHARS
+            )));
+        }
+
+        let mut keys = self.keys.lock().unwrap();
+        let value = new.key.unwrap_or_else(mint_key);
+        if keys.iter().any(|key| key.key == value) {
+            return Err(KeyError::Invalid("key is already in use".into()));
+        }
+        let key = ApiKey {
+            id: format!("key_{:016x}", rand::rng().random::<u64>()),
+            name: new.name,
+            key: value,
+            created_at: clock::unix_seconds(),
+            requests_per_minute: new.requests_per_minute,
+            tokens_per_minute: new.tokens_per_minute,
+            latency_profile: new.latency_profile,
+            revoked_at: None,
+        };
+        keys.push(key.clone());
+        if let Err(e) = self.save(&keys) {
+            keys.pop();
+            return Err(KeyError::Save(e));
+        }
+        Ok(key)
+    }
+
+    /// Revoke the key `id`, returning it; `None` if there is no such key.
+    /// Revoking a revoked key keeps its first revocation time
+    pub fn revoke(&self, id: &str) -> Result<Option<ApiKey>, String> {
+        let mut keys = self.keys.lock().unwrap();
+        let Some(index) = keys.iter().position(|key| key.id == id) else {
+            return Ok(None);
+        };
+        if keys[index].revoked_at.is_some() {
+            return Ok(Some(keys[index].clone()));
+        }
+        keys[index].revoked_at = Some(clock::unix_seconds());
+        if let Err(e) = self.save(&keys) {
+            keys[index].revoked_at = None;
+            return Err(e);
+        }
+        Ok(Some(keys[index].clone()))
+    }
+
+    /// Every key, revoked ones included, oldest first
+    pub fn list(&self) -> Vec<ApiKey> {
+        self.keys.lock().unwrap().clone()
+    }
+
+    /// The live key with value `value`
+    pub fn lookup(&self, value: &str) -> Option<ApiKey> {
+        self.keys
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|key| key.key == value && key.revoked_at.is_none())
+            .cloned()
+    }
+
+    /// Write the keys, replacing the file in one step
+    fn save(&self, keys: &[ApiKey]) -> Result<(), String> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        let json = serde_json::to_string_pretty(keys).map_err(|e| e.to_string())?;
+        std::fs::write(&tmp, json)
+            .and_then(|()| std::fs::rename(&tmp, path))
+            .map_err(|e| format!("Could not save keys to {}: {}", path.display(), e))
+    }
+}
+
+/// A fresh `sk-sim-...` key value
+fn mint_key() -> String {
+    format!("{}{:032x}", KEY_PREFIX, rand::rng().random::<u128>())
+}
+
+/// Whether `headers` carry `[keys] admin_key`; any request does without one
+pub fn is_admin(config: &KeysConfig, headers: &HeaderMap) -> bool {
+    let Some(admin_key) = &config.admin_key else {
+        return true;
+    };
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|token| token == admin_key)
+}
+
+/// Middleware: refuse generation requests without a live key, and give the
+/// others their key's quotas and latency profile
+pub async fn require_keys(
+    State(state): State<Arc<AppState>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let Some(keys) = state.keys.clone() else {
+        return next.run(request).await;
+    };
+    let path = request.uri().path().to_string();
+    let endpoint = (request.method() == "POST")
+        .then(|| endpoint_type(&path))
+        .flatten();
+    let Some(endpoint) = endpoint else {
+        return next.run(request).await;
+    };
+
+    if let Some(key) = keys.lookup(&api_key(request.headers())) {
+        let extensions = request.extensions_mut();
+        let mut overrides = extensions.remove::<RequestOverrides>().unwrap_or_default();
+        if key.latency_profile.is_some() {
+            overrides.profile = key.latency_profile.clone();
+        }
+        extensions.insert(overrides);
+        extensions.insert(key.limits());
+        return next.run(request).await;
+    }
+
+    let body = match ParsedBody::read(&mut request).await {
+        Ok(body) => body,
+        Err(response) => return response,
+    };
+    let model = body.model.as_str();
+    tracing::info!(model, "Request without a live API key");
+    let error = SimulatedError::AuthenticationError;
+    state
+        .begin(model, body.stream, endpoint)
+        .fail_with(ErrorCause::Unauthorized, error.clone());
+    error_response(&path, endpoint, &error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store(file: Option<&std::path::Path>) -> KeyStore {
+        KeyStore::open(&KeysConfig {
+            enabled: true,
+            file: file.map(|path| path.display().to_string()),
+            admin_key: None,
+        })
+    }
+
+    #[test]
+    fn test_create_lookup_and_revoke() {
+        let keys = store(None);
+        let key = keys
+            .create(NewKey {
+                name: Some("ci".into()),
+                requests_per_minute: Some(5),
+                ..Default::default()
+            })
+            .unwrap();
+        assert!(key.key.starts_with(KEY_PREFIX));
+        assert_eq!(keys.lookup(&key.key), Some(key.clone()));
+        assert_eq!(key.limits().requests_per_minute, Some(5));
+        assert_eq!(key.to_json(false)["key"], mask_key(&key.key));
+        assert_eq!(key.to_json(true)["key"], key.key);
+
+        let revoked = keys.revoke(&key.id).unwrap().unwrap();
+        assert!(revoked.revoked_at.is_some());
+        assert!(keys.lookup(&key.key).is_none());
+        assert_eq!(keys.list().len(), 1);
+        assert!(keys.revoke("key_missing").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_create_refuses_bad_keys() {
+        let keys = store(None);
+        let bad = [
+            NewKey {
+                latency_profile: Some("warp-speed".into()),
+                ..Default::default()
+            },
+            NewKey {
+                tokens_per_minute: Some(0),
+                ..Default::default()
+            },
+            NewKey {
+                key: Some("short".into()),
+                ..Default::default()
+            },
+        ];
+        for new in bad {
+            assert!(matches!(keys.create(new), Err(KeyError::Invalid(_))));
+        }
+        let custom = || NewKey {
+            key: Some("sk-team-search".into()),
+            ..Default::default()
+        };
+        keys.create(custom()).unwrap();
+        assert!(matches!(keys.create(custom()), Err(KeyError::Invalid(_))));
+    }
+
+    #[test]
+    fn test_keys_survive_a_restart() {
+        let path = std::env::temp_dir().join(format!("llmsim-keys-{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let first = store(Some(&path)).create(NewKey::default()).unwrap();
+        let keys = store(Some(&path));
+        let second = keys.create(NewKey::default()).unwrap();
+        keys.revoke(&second.id).unwrap();
+
+        let keys = store(Some(&path));
+        assert_eq!(keys.list().len(), 2);
+        assert_eq!(keys.lookup(&first.key), Some(first));
+        assert!(keys.lookup(&second.key).is_none());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_admin_key() {
+        let mut config = KeysConfig::default();
+        let mut headers = HeaderMap::new();
+        assert!(is_admin(&config, &headers));
+        config.admin_key = Some("admin-secret".into());
+        assert!(!is_admin(&config, &headers));
+        headers.insert(header::AUTHORIZATION, "Bearer wrong".parse().unwrap());
+        assert!(!is_admin(&config, &headers));
+        headers.insert(
+            header::AUTHORIZATION,
+            "Bearer admin-secret".parse().unwrap(),
+        );
+        assert!(is_admin(&config, &headers));
+    }
+}
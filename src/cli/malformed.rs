@@ -0,0 +1,146 @@
+// Malformed Frames Module
+// `[stream] malformed_rate`: SSE body frames occasionally sent broken the
+// ways real proxies and servers break them, as an adversarial source for
+// client stream parsers.
+//
+// Decision: faults are applied to whole body frames after slow-reader
+// coalescing and before UTF-8 splitting and proxy buffering, so they compose
+// with the other transport settings and a frame is only ever broken once.
+// Events stay otherwise intact: a parser that tolerates the fault recovers
+// every event, which is what a test wants to assert. The RNG is per stream
+// and unseeded; these are transport faults, not generated content.
+
+use super::config::MalformedFrame;
+use rand::prelude::IndexedRandom;
+use rand::rngs::StdRng;
+use rand::RngExt;
+
+/// Comment line injected by `stray_comment`
+const STRAY_COMMENT: &str = ": llmsim\n";
+const DONE_EVENT: &str = "data: [DONE]\n\n";
+
+/// Breaks a share of a stream's body frames
+pub(crate) struct FrameMalformer {
+    rate: f64,
+    kinds: Vec<MalformedFrame>,
+    rng: StdRng,
+}
+
+impl FrameMalformer {
+    /// Malform frames at `rate`, drawing from `kinds`, or from every kind
+    /// when it is empty
+    pub(crate) fn new(rate: f64, kinds: &[MalformedFrame]) -> Self {
+        let kinds = if kinds.is_empty() {
+            MalformedFrame::ALL.to_vec()
+        } else {
+            kinds.to_vec()
+        };
+        Self {
+            rate: rate.clamp(0.0, 1.0),
+            kinds,
+            rng: rand::make_rng(),
+        }
+    }
+
+    /// The body frames to send for one frame: usually the frame itself,
+    /// sometimes a broken version of it, possibly cut in two
+    pub(crate) fn apply(&mut self, frame: String) -> Vec<String> {
+        if !self.rng.random_bool(self.rate) {
+            return vec![frame];
+        }
+        let applicable: Vec<MalformedFrame> = self
+            .kinds
+            .iter()
+            .copied()
+            .filter(|kind| kind.applies_to(&frame))
+            .collect();
+        match applicable.choose(&mut self.rng) {
+            Some(kind) => kind.apply(frame),
+            None => vec![frame],
+        }
+    }
+}
+
+impl MalformedFrame {
+    const ALL: [MalformedFrame; 4] = [
+        MalformedFrame::MissingSeparator,
+        MalformedFrame::SplitPrefix,
+        MalformedFrame::StrayComment,
+        MalformedFrame::DuplicateDone,
+    ];
+
+    fn applies_to(self, frame: &str) -> bool {
+        match self {
+            MalformedFrame::MissingSeparator => frame.contains("\n\n"),
+            MalformedFrame::SplitPrefix => frame.len() > 2 && frame.is_char_boundary(2),
+            MalformedFrame::StrayComment => true,
+            MalformedFrame::DuplicateDone => frame.contains(DONE_EVENT),
+        }
+    }
+
+    /// Break `frame`, which `applies_to` accepted
+    fn apply(self, mut frame: String) -> Vec<String> {
+        match self {
+            // The first event runs into the next one
+            MalformedFrame::MissingSeparator => {
+                if let Some(end) = frame.find("\n\n") {
+                    frame.remove(end);
+                }
+                vec![frame]
+            }
+            // `da` in one body frame, `ta: ...` in the next
+            MalformedFrame::SplitPrefix => {
+                let rest = frame.split_off(2);
+                vec![frame, rest]
+            }
+            MalformedFrame::StrayComment => vec![format!("{}{}", STRAY_COMMENT, frame)],
+            MalformedFrame::DuplicateDone => {
+                frame.push_str(DONE_EVENT);
+                vec![frame]
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn always(kind: MalformedFrame) -> FrameMalformer {
+        FrameMalformer::new(1.0, &[kind])
+    }
+
+    #[test]
+    fn test_each_kind() {
+        let frame = "data: {\"a\":1}\n\ndata: {\"b\":2}\n\n".to_string();
+        assert_eq!(
+            always(MalformedFrame::MissingSeparator).apply(frame.clone()),
+            vec!["data: {\"a\":1}\ndata: {\"b\":2}\n\n"]
+        );
+        assert_eq!(
+            always(MalformedFrame::SplitPrefix).apply(frame.clone()),
+            vec!["da", "ta: {\"a\":1}\n\ndata: {\"b\":2}\n\n"]
+        );
+        assert_eq!(
+            always(MalformedFrame::StrayComment).apply(frame.clone()),
+            vec![format!(": llmsim\n{}", frame)]
+        );
+        assert_eq!(
+            always(MalformedFrame::DuplicateDone).apply("data: [DONE]\n\n".to_string()),
+            vec!["data: [DONE]\n\ndata: [DONE]\n\n"]
+        );
+    }
+
+    #[test]
+    fn test_inapplicable_and_disabled_frames_pass_through() {
+        let frame = "data: {\"a\":1}\n\n".to_string();
+        assert_eq!(
+            always(MalformedFrame::DuplicateDone).apply(frame.clone()),
+            vec![frame.clone()]
+        );
+        assert_eq!(
+            FrameMalformer::new(0.0, &[]).apply(frame.clone()),
+            vec![frame]
+        );
+    }
+}
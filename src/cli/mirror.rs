@@ -0,0 +1,361 @@
+// Request Mirroring Module
+// Shadow traffic: a copy of each incoming request is sent to the configured
+// real upstream in the background while the client still gets the simulated
+// response, and the two are compared on token counts and latency.
+//
+// Decision: mirroring is a router middleware rather than a pipeline step, so
+// every provider route is covered without touching the handlers, and the
+// simulated side is measured from the bytes actually sent to the client.
+// Usage is read back out of those bytes (JSON body or SSE events) the same
+// way for both sides. Whichever side finishes second records the
+// comparison; a client that disconnects early or an upstream failure leaves
+// the pair uncompared.
+
+use super::config::Config;
+use super::request_body::ParsedBody;
+use super::state::AppState;
+use super::upstream::{self, Upstream, UpstreamError};
+use axum::{
+    body::Body,
+    extract::{Request, State},
+    middleware::Next,
+    response::Response,
+};
+use futures_util::StreamExt;
+use rand::RngExt;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// Simulated response bytes kept for reading usage back out
+const MAX_CAPTURE_BYTES: usize = 4 * 1024 * 1024;
+/// Comparisons kept for `/llmsim/mirror`
+const RECENT: usize = 50;
+
+/// Token counts and timing of one side of a mirrored request
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Measurement {
+    pub status: u16,
+    pub prompt_tokens: Option<u64>,
+    pub completion_tokens: Option<u64>,
+    pub latency_ms: f64,
+}
+
+/// A simulated response next to the real one for the same request
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Comparison {
+    pub path: String,
+    pub model: Option<String>,
+    pub simulated: Measurement,
+    pub upstream: Measurement,
+}
+
+#[derive(Debug, Default, Clone, Copy, Serialize)]
+struct SideTotals {
+    prompt_tokens: u64,
+    completion_tokens: u64,
+    total_latency_ms: f64,
+}
+
+impl SideTotals {
+    fn add(&mut self, m: &Measurement) {
+        self.prompt_tokens += m.prompt_tokens.unwrap_or(0);
+        self.completion_tokens += m.completion_tokens.unwrap_or(0);
+        self.total_latency_ms += m.latency_ms;
+    }
+}
+
+#[derive(Default)]
+struct Log {
+    compared: u64,
+    simulated: SideTotals,
+    upstream: SideTotals,
+    recent: VecDeque<Comparison>,
+}
+
+/// Upstreams to mirror to and the comparisons recorded so far
+pub struct Mirror {
+    upstreams: BTreeMap<String, Arc<Upstream>>,
+    sample_rate: f64,
+    mirrored: AtomicU64,
+    failed: AtomicU64,
+    log: Mutex<Log>,
+}
+
+impl Mirror {
+    /// Mirror for `config`, or `None` when mirroring is off or no upstream
+    /// is configured
+    pub fn from_config(config: &Config) -> Option<Self> {
+        if !config.mirror.enabled {
+            return None;
+        }
+        let upstreams = upstream::from_config(config);
+        if upstreams.is_empty() {
+            tracing::warn!("[mirror] is enabled but no [upstreams] are configured");
+            return None;
+        }
+        Some(Self {
+            upstreams,
+            sample_rate: config.mirror.sample_rate.clamp(0.0, 1.0),
+            mirrored: AtomicU64::new(0),
+            failed: AtomicU64::new(0),
+            log: Mutex::new(Log::default()),
+        })
+    }
+
+    fn record(&self, comparison: Comparison) {
+        let Ok(mut log) = self.log.lock() else {
+            return;
+        };
+        log.compared += 1;
+        log.simulated.add(&comparison.simulated);
+        log.upstream.add(&comparison.upstream);
+        if log.recent.len() == RECENT {
+            log.recent.pop_front();
+        }
+        log.recent.push_back(comparison);
+    }
+
+    /// Counters, per-side totals and the most recent comparisons
+    pub fn summary(&self) -> Value {
+        let log = self.log.lock().map(|l| {
+            (
+                l.compared,
+                l.simulated,
+                l.upstream,
+                l.recent.iter().cloned().collect::<Vec<_>>(),
+            )
+        });
+        let (compared, simulated, upstream, recent) = log.unwrap_or_default();
+        let side = |t: SideTotals| {
+            serde_json::json!({
+                "prompt_tokens": t.prompt_tokens,
+                "completion_tokens": t.completion_tokens,
+                "avg_latency_ms": if compared > 0 { t.total_latency_ms / compared as f64 } else { 0.0 },
+            })
+        };
+        serde_json::json!({
+            "mirrored": self.mirrored.load(Ordering::Relaxed),
+            "failed": self.failed.load(Ordering::Relaxed),
+            "compared": compared,
+            "simulated": side(simulated),
+            "upstream": side(upstream),
+            "recent": recent,
+        })
+    }
+}
+
+/// The two halves of one mirrored request, filled in as each finishes
+struct Pending {
+    mirror: Arc<Mirror>,
+    path: String,
+    model: Option<String>,
+    sides: Mutex<(Option<Measurement>, Option<Measurement>)>,
+}
+
+impl Pending {
+    fn simulated(&self, m: Measurement) {
+        self.complete(|sides| sides.0 = Some(m));
+    }
+
+    fn upstream(&self, m: Measurement) {
+        self.complete(|sides| sides.1 = Some(m));
+    }
+
+    fn complete(&self, fill: impl FnOnce(&mut (Option<Measurement>, Option<Measurement>))) {
+        let Ok(mut sides) = self.sides.lock() else {
+            return;
+        };
+        fill(&mut sides);
+        if let (Some(simulated), Some(upstream)) = &*sides {
+            self.mirror.record(Comparison {
+                path: self.path.clone(),
+                model: self.model.clone(),
+                simulated: simulated.clone(),
+                upstream: upstream.clone(),
+            });
+        }
+    }
+}
+
+/// Middleware: mirror POST requests for providers with an upstream
+pub async fn mirror_requests(
+    State(state): State<Arc<AppState>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let Some(mirror) = state.mirror.clone() else {
+        return next.run(request).await;
+    };
+    let path = request.uri().path().to_string();
+    let sampled = mirror.sample_rate >= 1.0 || rand::rng().random_bool(mirror.sample_rate);
+    let target = (request.method() == "POST" && sampled)
+        .then(|| upstream::route(&mirror.upstreams, &path).map(|(u, p)| (u, p.to_string())))
+        .flatten();
+    let Some((upstream, upstream_path)) = target else {
+        return next.run(request).await;
+    };
+
+    let body = match ParsedBody::read(&mut request).await {
+        Ok(body) => body,
+        Err(response) => return response,
+    };
+    let model = body.json["model"].as_str().map(str::to_string);
+    let pending = Arc::new(Pending {
+        mirror: mirror.clone(),
+        path: path.clone(),
+        model,
+        sides: Mutex::new((None, None)),
+    });
+
+    mirror.mirrored.fetch_add(1, Ordering::Relaxed);
+    let headers = request.headers().clone();
+    let forwarded = body.bytes;
+    let upstream_pending = pending.clone();
+    let recorder = state.recorder.clone();
+    tokio::spawn(async move {
+        let started = Instant::now();
+        match upstream
+            .send("POST", &upstream_path, &headers, &forwarded)
+            .await
+        {
+            Ok(response) => {
+                let (prompt_tokens, completion_tokens) = usage_from_body(&response.body);
+                if let (Some(recorder), Some(model)) = (&recorder, &upstream_pending.model) {
+                    if (200..300).contains(&response.status) {
+                        let streamed = response.is_event_stream();
+                        recorder.record(
+                            model,
+                            &response.timing.observation(streamed, completion_tokens),
+                        );
+                    }
+                }
+                upstream_pending.upstream(Measurement {
+                    status: response.status,
+                    prompt_tokens,
+                    completion_tokens,
+                    latency_ms: started.elapsed().as_secs_f64() * 1000.0,
+                });
+            }
+            Err(e) => record_failure(&upstream_pending.mirror, &upstream, e),
+        }
+    });
+
+    let started = Instant::now();
+    let response = next.run(request).await;
+    let status = response.status().as_u16();
+    let (parts, body) = response.into_parts();
+    let mut data = body.into_data_stream();
+    let measured = async_stream::stream! {
+        let mut captured = Vec::new();
+        while let Some(chunk) = data.next().await {
+            if let Ok(bytes) = &chunk {
+                if captured.len() < MAX_CAPTURE_BYTES {
+                    captured.extend_from_slice(bytes);
+                }
+            }
+            yield chunk;
+        }
+        let (prompt_tokens, completion_tokens) = usage_from_body(&captured);
+        pending.simulated(Measurement {
+            status,
+            prompt_tokens,
+            completion_tokens,
+            latency_ms: started.elapsed().as_secs_f64() * 1000.0,
+        });
+    };
+    Response::from_parts(parts, Body::from_stream(measured))
+}
+
+fn record_failure(mirror: &Mirror, upstream: &Upstream, error: UpstreamError) {
+    mirror.failed.fetch_add(1, Ordering::Relaxed);
+    tracing::warn!("Mirror to {} failed: {}", upstream.authority(), error);
+}
+
+/// Prompt and completion tokens reported in a JSON body or SSE stream. Each
+/// is the largest value seen, since Anthropic streams input and output
+/// usage in separate events and the final count is the highest.
+pub fn usage_from_body(body: &[u8]) -> (Option<u64>, Option<u64>) {
+    let text = String::from_utf8_lossy(body);
+    let payloads: Vec<Value> = if text.trim_start().starts_with('{') {
+        serde_json::from_str(&text).into_iter().collect()
+    } else {
+        text.lines()
+            .filter_map(|l| l.strip_prefix("data:"))
+            .filter_map(|d| serde_json::from_str(d.trim()).ok())
+            .collect()
+    };
+    let mut prompt = None;
+    let mut completion = None;
+    for payload in &payloads {
+        for usage in [
+            &payload["usage"],
+            &payload["response"]["usage"],
+            &payload["message"]["usage"],
+        ] {
+            let input = usage["prompt_tokens"]
+                .as_u64()
+                .or_else(|| usage["input_tokens"].as_u64());
+            let output = usage["completion_tokens"]
+                .as_u64()
+                .or_else(|| usage["output_tokens"].as_u64());
+            prompt = prompt.max(input);
+            completion = completion.max(output);
+        }
+    }
+    (prompt, completion)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_usage_from_json() {
+        let body =
+            br#"{"id":"x","usage":{"prompt_tokens":12,"completion_tokens":34,"total_tokens":46}}"#;
+        assert_eq!(usage_from_body(body), (Some(12), Some(34)));
+        assert_eq!(usage_from_body(b"{\"error\":{}}"), (None, None));
+    }
+
+    #[test]
+    fn test_usage_from_sse() {
+        let anthropic = "event: message_start\n\
+            data: {\"type\":\"message_start\",\"message\":{\"usage\":{\"input_tokens\":9,\"output_tokens\":1}}}\n\n\
+            event: message_delta\n\
+            data: {\"type\":\"message_delta\",\"usage\":{\"output_tokens\":40}}\n\n";
+        assert_eq!(usage_from_body(anthropic.as_bytes()), (Some(9), Some(40)));
+
+        let responses = "event: response.completed\n\
+            data: {\"type\":\"response.completed\",\"response\":{\"usage\":{\"input_tokens\":5,\"output_tokens\":7}}}\n\n";
+        assert_eq!(usage_from_body(responses.as_bytes()), (Some(5), Some(7)));
+
+        let chat = "data: {\"choices\":[]}\n\ndata: [DONE]\n\n";
+        assert_eq!(usage_from_body(chat.as_bytes()), (None, None));
+    }
+
+    #[test]
+    fn test_route_strips_provider_prefix() {
+        let config = Config::from_toml(
+            "[mirror]\nenabled = true\n[upstreams.openai]\nbase_url = \"http://gw:9000/base\"\n",
+        )
+        .unwrap();
+        let mirror = Mirror::from_config(&config).unwrap();
+        let (upstream, path) =
+            upstream::route(&mirror.upstreams, "/openai/v1/chat/completions").unwrap();
+        assert_eq!(upstream.authority(), "gw:9000");
+        assert_eq!(path, "/v1/chat/completions");
+        assert!(upstream::route(&mirror.upstreams, "/anthropic/v1/messages").is_none());
+        assert!(upstream::route(&mirror.upstreams, "/health").is_none());
+    }
+
+    #[test]
+    fn test_disabled_without_upstreams() {
+        let config = Config::from_toml("[mirror]\nenabled = true\n").unwrap();
+        assert!(Mirror::from_config(&config).is_none());
+        assert!(Mirror::from_config(&Config::default()).is_none());
+    }
+}
@@ -3,46 +3,153 @@
 //! This module provides the `llmsim serve` command implementation.
 
 mod anthropic_handlers;
+mod api_version;
+mod auxiliary;
+mod cdn_errors;
+mod chunk_faults;
+mod clients;
+mod cluster;
+mod completions_store;
 mod config;
+mod config_check;
+mod deprecation;
+mod error_catalog;
+mod error_rules;
+mod files;
+mod framing;
+mod grpc;
 mod handlers;
+mod hang;
+mod include;
+mod interpolate;
+mod json_body;
+mod keys;
+mod malformed;
+mod mirror;
+mod openapi;
+mod overrides;
+mod presets;
+mod proxy;
+mod rate_limits;
+mod request_body;
+mod request_log;
+mod responses_store;
+mod resume;
+mod runtime;
 mod state;
+mod tee;
+mod traffic;
+mod upstream;
+mod usage;
+mod verify;
+mod vllm_handlers;
 mod ws_handler;
 
-pub use config::{Config, ConfigError};
-pub use state::AppState;
+pub use api_version::{ApiBehavior, ApiVersion};
+pub use cluster::{ClusterNode, ClusterStats};
+pub use config::{
+    AlertsConfig, ApiVersionBehavior, AuxiliaryConfig, BodyFraming, CapacityConfig, CdnError,
+    ClockConfig, ClusterConfig, CompressionConfig, Config, ConfigError, ErrorRule, ErrorTemplate,
+    FilesConfig, FingerprintConfig, FramingConfig, JsonBodyConfig, JsonContentType, KeysConfig,
+    LearnedConfig, MalformedFrame, MirrorConfig, PoolConfig, ProxyConfig, ProxyRule,
+    RateLimitsConfig, RedactConfig, RegionConfig, RequestLogConfig, RuntimeConfig,
+    SimulationConfig, StoreConfig, TokenEncoding, TokensConfig, TrafficConfig, TuiConfig,
+    UpstreamConfig, UsageConfig, UserRedaction, Utf8Boundaries,
+};
+pub use config_check::{check_config, config_toml, ConfigIssue};
+pub use presets::{find_preset, Preset, PRESETS};
+pub use request_log::RequestSummary;
+pub use runtime::{build_runtime, runtime_snapshot};
+pub use state::{AppState, Region};
+pub use upstream::UpstreamError;
+pub use verify::{verify, verify_report, CheckResult, VerifyOptions};
 pub use ws_handler::ws_responses;
 
 use crate::script::Script;
-use crate::stats::{new_shared_stats, SharedStats};
+use crate::sleep::{measure_shortest_sleep, TokioSleeper};
+use crate::stats::{SharedStats, Stats};
 use axum::{
-    routing::{get, post},
+    body::Body,
+    extract::{Request, State},
+    http::{header, Extensions, HeaderMap, StatusCode, Version},
+    middleware::Next,
+    response::Response,
+    routing::{any, delete, get, post},
     Router,
 };
-use std::{net::SocketAddr, sync::Arc};
+use std::{net::SocketAddr, sync::Arc, time::Duration};
 use tokio::signal;
-use tower_http::{cors::CorsLayer, trace::TraceLayer};
+use tower_http::{
+    compression::{predicate::SizeAbove, CompressionLayer, Predicate},
+    cors::CorsLayer,
+    trace::TraceLayer,
+};
 
 /// Build the Axum router with all endpoints.
 /// Exposed for integration testing.
 pub fn build_router(state: Arc<AppState>) -> Router {
-    Router::new()
+    let mut router = Router::new()
         .route("/health", get(handlers::health))
         .route("/llmsim/stats", get(handlers::get_stats))
+        .route("/llmsim/cluster/stats", get(handlers::get_cluster_stats))
+        .route("/llmsim/regions", get(handlers::get_regions))
+        .route(
+            "/llmsim/drill",
+            get(handlers::list_drills).post(handlers::start_drill),
+        )
+        .route("/llmsim/drill/{id}", delete(handlers::cancel_drill))
+        .route(
+            "/llmsim/keys",
+            get(handlers::list_keys).post(handlers::create_key),
+        )
+        .route("/llmsim/keys/{id}", delete(handlers::revoke_key))
+        .route("/llmsim/errors/catalog", get(handlers::get_error_catalog))
+        .route(
+            "/llmsim/errors/force",
+            get(handlers::list_forced_errors).post(handlers::force_error),
+        )
+        .route(
+            "/llmsim/errors/force/{id}",
+            delete(handlers::cancel_forced_error),
+        )
+        .route("/llmsim/openapi.json", get(handlers::get_openapi))
+        .route("/llmsim/mirror", get(handlers::get_mirror))
+        .route("/llmsim/proxy", get(handlers::get_proxy))
+        .route("/llmsim/profiles", get(handlers::get_profiles))
+        .route("/llmsim/requests", get(handlers::get_requests))
+        .route("/llmsim/files/{id}", get(handlers::get_file))
+        .route("/llmsim/debug/echo", any(handlers::debug_echo))
         // OpenAI API routes
         .route(
             "/openai/v1/chat/completions",
-            post(handlers::chat_completions),
+            post(handlers::chat_completions).get(handlers::list_chat_completions),
+        )
+        .route(
+            "/openai/v1/chat/completions/{id}",
+            get(handlers::get_chat_completion)
+                .post(handlers::update_chat_completion)
+                .delete(handlers::delete_chat_completion),
         )
         .route("/openai/v1/models", get(handlers::list_models))
         .route("/openai/v1/models/{model_id}", get(handlers::get_model))
         .route(
             "/openai/v1/responses",
-            post(handlers::create_response).get(ws_handler::ws_responses),
+            post(handlers::create_response).get(handlers::get_responses),
+        )
+        .route("/openai/v1/responses/{id}", get(handlers::get_response))
+        .route(
+            "/openai/v1/responses/{id}/input_items",
+            get(handlers::list_input_items),
         )
         .route(
             "/openai/v1/images/generations",
             post(handlers::create_image),
         )
+        .route(
+            "/openai/v1/organization/usage/completions",
+            get(handlers::get_completions_usage),
+        )
+        .route("/openai/v1/organization/costs", get(handlers::get_costs))
         // OpenResponses API routes (https://www.openresponses.org)
         .route(
             "/openresponses/v1/responses",
@@ -58,14 +165,178 @@ pub fn build_router(state: Arc<AppState>) -> Router {
             "/anthropic/v1/models/{model_id}",
             get(anthropic_handlers::get_model),
         )
+        // vLLM OpenAI-compatible server routes
+        .route(
+            "/vllm/v1/chat/completions",
+            post(vllm_handlers::chat_completions),
+        )
+        .route("/vllm/v1/models", get(vllm_handlers::list_models))
+        .route("/vllm/v1/models/{model_id}", get(vllm_handlers::get_model))
+        .route("/vllm/metrics", get(vllm_handlers::metrics))
+        // gRPC service (proto/llmsim/v1/simulator.proto), over HTTP/2
+        .route("/llmsim.v1.Simulator/Generate", post(grpc::generate))
+        .route(
+            "/llmsim.v1.Simulator/GenerateStream",
+            post(grpc::generate_stream),
+        );
+    if state.auxiliary.is_some() {
+        router = router.layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            auxiliary::apply_auxiliary,
+        ));
+    }
+    if state.config.errors.hang_rate > 0.0 {
+        router = router.layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            hang::hang_requests,
+        ));
+    }
+    // Outside the hold: a request a rule fails is answered
+    if state.error_rules.is_some() {
+        router = router.layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            error_rules::inject_rule_errors,
+        ));
+    }
+    // Outside the error rules: a retired model keeps its retirement error
+    if state.deprecations.is_some() {
+        router = router.layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            deprecation::deprecate_models,
+        ));
+    }
+    // Outside both: every 429 the simulator answers with gets the policy's
+    // Retry-After
+    if state.rate_limiter.is_some() {
+        router = router.layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            rate_limits::apply_rate_limits,
+        ));
+    }
+    if state.mirror.is_some() {
+        router = router.layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            mirror::mirror_requests,
+        ));
+    }
+    // Outside the mirror: proxied requests are not mirrored as well
+    if state.proxy.is_some() {
+        router = router.layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            proxy::proxy_requests,
+        ));
+    }
+    // Outside the rate limits: a key's quotas come with its key
+    if state.keys.is_some() {
+        router = router.layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            keys::require_keys,
+        ));
+    }
+    // Outside the API's own layers: a CDN answers before the origin sees
+    // the request
+    if state.config.errors.cdn_error_rate > 0.0 {
+        router = router.layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            cdn_errors::inject_cdn_errors,
+        ));
+    }
+    // Outside every layer that answers on its own, so refused and proxied
+    // requests count toward their client's concurrency too
+    router = router.layer(axum::middleware::from_fn_with_state(
+        state.clone(),
+        clients::track_clients,
+    ));
+    // Outermost: no other layer sees a body with the `llmsim` extension
+    router = router.layer(axum::middleware::from_fn_with_state(
+        state.clone(),
+        overrides::extract_overrides,
+    ));
+    // Outside every request layer: a resumed stream continues an earlier
+    // request rather than making a new one
+    if state.config.stream.event_ids {
+        router = router.layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            resume::resume_streams,
+        ));
+    }
+    // Outside every request layer, so a capture has the bytes as sent; a
+    // region's streams are captured by its own router
+    if state.tee.is_some() {
+        router = router.layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            tee::tee_streams,
+        ));
+    }
+    // Inside compression and framing, so the bytes it adds are compressed
+    // and counted like the rest of the body
+    if state.config.json_body.is_active() {
+        router = router.layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            json_body::vary_json_bodies,
+        ));
+    }
+    for region in &state.regions {
+        if let Some(path) = &region.mount_path {
+            router = router.nest_service(path, build_router(region.state.clone()));
+        }
+    }
+    if state.config.compression.enabled {
+        router = router.layer(compression_layer(&state.config.compression));
+    }
+    // Outside compression, so it frames the bytes actually sent
+    if state.config.framing.is_active() {
+        router = router.layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            framing::frame_responses,
+        ));
+    }
+    // Outermost: every layer builds its responses with the listener's ids
+    // and timestamps; a region mounted on the main listener scopes its own
+    // requests again inside
+    router = router.layer(axum::middleware::from_fn_with_state(
+        state.clone(),
+        scope_stamps,
+    ));
+    router
         .layer(TraceLayer::new_for_http())
         .layer(CorsLayer::permissive())
         .with_state(state)
 }
 
+/// Compression for the configured content types. Streaming responses are
+/// excluded by `CompressionConfig::compresses`, so SSE frames still reach the
+/// client as they are produced.
+fn compression_layer(config: &CompressionConfig) -> CompressionLayer<impl Predicate> {
+    let types = config.clone();
+    let by_type = move |_: StatusCode, _: Version, headers: &HeaderMap, _: &Extensions| {
+        headers
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|ct| types.compresses(ct))
+    };
+    CompressionLayer::new()
+        .gzip(config.gzip)
+        .br(config.br)
+        .compress_when(SizeAbove::new(config.min_size_bytes).and(by_type))
+}
+
+/// Middleware: scope the request, and the body of its response, in the
+/// listener's `Stamps`
+async fn scope_stamps(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let stamps = state.pipeline.stamps();
+    let response = stamps.scope(Box::pin(next.run(request))).await;
+    response.map(|body| Body::new(stamps.scope(body)))
+}
+
 /// Run the LLMSim server with the given configuration
 pub async fn run_server(config: Config) -> Result<(), Box<dyn std::error::Error>> {
-    run_server_with_stats(config, new_shared_stats()).await
+    let stats = Arc::new(Stats::with_rps_window(config.server.rps_window_secs));
+    run_server_with_stats(config, stats).await
 }
 
 /// Run the LLMSim server with the given configuration and shared stats
@@ -94,9 +365,23 @@ pub async fn run_server_with_stats(
         "OpenResponses endpoint: /openresponses/v1/responses (https://www.openresponses.org)"
     );
     tracing::info!("Anthropic endpoints: /anthropic/v1/messages, /anthropic/v1/models");
+    tracing::info!(
+        "vLLM endpoints: /vllm/v1/..., /vllm/metrics (serving {})",
+        config.vllm_served_model()
+    );
     tracing::info!("Stats endpoint: /llmsim/stats");
 
-    let mut state = AppState::new(config, stats);
+    let shortest_sleep = if config.latency.calibrate {
+        Some(calibrate_timer().await)
+    } else {
+        None
+    };
+    let mut state = match shortest_sleep {
+        Some(shortest) => {
+            AppState::with_sleeper(config, stats, Arc::new(TokioSleeper::calibrated(shortest)))
+        }
+        None => AppState::new(config, stats),
+    };
     if let Some(script_path) = state.config.response.script_path.clone() {
         let script =
             Script::from_file(&script_path).map_err(|e| -> Box<dyn std::error::Error> {
@@ -113,19 +398,106 @@ pub async fn run_server_with_stats(
         );
         state = state.with_script(Arc::new(script));
     }
-    let app = build_router(Arc::new(state));
+    let state = Arc::new(state);
+    if let Some(shortest) = shortest_sleep {
+        warn_of_short_tbt(&state, shortest);
+    }
 
-    let listener = tokio::net::TcpListener::bind(addr).await?;
+    let cluster = state.config.cluster.clone();
+    let reuse_port = cluster.reuse_port;
+    if let Some(dir) = &cluster.stats_dir {
+        std::fs::create_dir_all(dir)?;
+        tracing::info!(
+            "Cluster stats: /llmsim/cluster/stats, shared through {} as {}",
+            dir,
+            cluster::node_name(&cluster)
+        );
+        tokio::spawn(cluster::publish(state.stats.clone(), cluster.clone()));
+    }
+    if state.config.traffic.enabled {
+        tracing::info!(
+            "Synthetic traffic: up to {} req/s to {}",
+            state.config.traffic.peak_rps,
+            state.config.traffic.paths.join(", ")
+        );
+        tokio::spawn(traffic::generate(state.config.traffic.clone(), addr));
+    }
 
-    axum::serve(listener, app)
-        .with_graceful_shutdown(shutdown_signal())
-        .await?;
+    let mut servers = vec![serve(addr, build_router(state.clone()), reuse_port)];
+    for region in &state.regions {
+        if let Some(path) = &region.mount_path {
+            tracing::info!("Region {} at {}/...", region.name, path);
+            continue;
+        }
+        let config = &region.state.config;
+        let addr: SocketAddr = format!("{}:{}", config.server.host, config.server.port)
+            .parse()
+            .expect("Invalid address");
+        tracing::info!("Region {} on {}", region.name, addr);
+        servers.push(serve(addr, build_router(region.state.clone()), reuse_port));
+    }
+    let served = futures_util::future::try_join_all(servers).await;
+    cluster::withdraw(&cluster);
+    served?;
 
     tracing::info!("Server shutdown complete");
     Ok(())
 }
 
-async fn shutdown_signal() {
+/// Time the tokio timer: the shortest sleep it can do, for sleepers to make
+/// up for how late it fires
+async fn calibrate_timer() -> Duration {
+    let shortest = measure_shortest_sleep().await;
+    tracing::info!(
+        "Timer calibrated: a 1ms sleep takes {:.2}ms",
+        shortest.as_secs_f64() * 1000.0
+    );
+    shortest
+}
+
+/// Warn when the shortest time between tokens served, by any model on the
+/// main listener or a region, is shorter than the `shortest` sleep the timer
+/// can do
+fn warn_of_short_tbt(state: &AppState, shortest: Duration) {
+    let shortest_ms = shortest.as_secs_f64() * 1000.0;
+    let tbt = std::iter::once(state)
+        .chain(state.regions.iter().map(|region| region.state.as_ref()))
+        .filter_map(|state| {
+            let models = state.config.models.available.iter().map(String::as_str);
+            state.pipeline.shortest_tbt(models)
+        })
+        .min();
+    let Some(tbt) = tbt else {
+        return;
+    };
+    let tbt_ms = tbt.as_secs_f64() * 1000.0;
+    if tbt_ms < shortest_ms {
+        tracing::warn!(
+            "Time between tokens of {:.2}ms is shorter than the timer can sleep ({:.2}ms); \
+             gaps will be either none or one short sleep, averaging {:.2}ms",
+            tbt_ms,
+            shortest_ms,
+            tbt_ms
+        );
+    }
+}
+
+async fn serve(addr: SocketAddr, app: Router, reuse_port: bool) -> std::io::Result<()> {
+    let listener = if reuse_port {
+        cluster::bind_shared(addr)?
+    } else {
+        tokio::net::TcpListener::bind(addr).await?
+    };
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(shutdown_signal())
+    .await
+}
+
+/// Wait for Ctrl+C or, on Unix, SIGTERM
+pub async fn shutdown_signal() {
     let ctrl_c = async {
         signal::ctrl_c()
             .await
@@ -0,0 +1,142 @@
+// OpenAPI Module
+// The OpenAPI 3.1 document served at `/llmsim/openapi.json`, covering the
+// admin/stats endpoints and the simulated provider endpoints.
+//
+// Decision: the document is hand-written JSON (`openapi.json`) rather than
+// derived from the handler types. The wire types are shared with the library
+// and carry many provider fields llmsim accepts but ignores; a curated
+// document says what the simulator actually reads and returns. The streamed
+// payload schemas bundled for `--strict-schema` are spliced into
+// `components`, so SSE responses get item schemas without a second copy, and
+// the integration tests walk every documented operation against the router.
+
+use super::state::AppState;
+use crate::schema::PayloadSchema;
+use serde_json::{json, Value};
+use std::sync::OnceLock;
+
+/// The document, with the crate version and bundled stream schemas filled in
+pub fn document() -> &'static Value {
+    static DOCUMENT: OnceLock<Value> = OnceLock::new();
+    DOCUMENT.get_or_init(|| {
+        let mut doc: Value = serde_json::from_str(include_str!("openapi.json"))
+            .expect("bundled OpenAPI document is valid JSON");
+        doc["info"]["version"] = json!(env!("CARGO_PKG_VERSION"));
+        let schemas = &mut doc["components"]["schemas"];
+        for (name, schema) in [
+            ("ChatCompletionChunk", PayloadSchema::ChatCompletionChunk),
+            ("ResponsesStreamEvent", PayloadSchema::ResponsesStreamEvent),
+        ] {
+            schemas[name] = embed(name, schema.schema());
+        }
+        doc
+    })
+}
+
+/// The document as served by one listener: mounted regions are listed as
+/// extra `servers`
+pub fn document_for(state: &AppState) -> Value {
+    let mut doc = document().clone();
+    let mut servers = vec![json!({"url": "/"})];
+    for region in &state.regions {
+        if let Some(path) = &region.mount_path {
+            servers.push(json!({
+                "url": path,
+                "description": format!("Region {}", region.name),
+            }));
+        }
+    }
+    doc["servers"] = Value::Array(servers);
+    doc
+}
+
+/// A standalone schema re-rooted under `components/schemas/<name>`: its
+/// local `#/$defs/...` references are rewritten to the new location
+fn embed(name: &str, schema: &Value) -> Value {
+    let prefix = format!("#/components/schemas/{}/$defs/", name);
+    let mut schema = schema.clone();
+    if let Some(obj) = schema.as_object_mut() {
+        obj.remove("$schema");
+    }
+    rewrite_refs(&mut schema, &prefix);
+    schema
+}
+
+fn rewrite_refs(value: &mut Value, prefix: &str) {
+    match value {
+        Value::Object(obj) => {
+            for (key, v) in obj.iter_mut() {
+                match v {
+                    Value::String(target) if key == "$ref" => {
+                        if let Some(rest) = target.strip_prefix("#/$defs/") {
+                            *target = format!("{}{}", prefix, rest);
+                        }
+                    }
+                    _ => rewrite_refs(v, prefix),
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(|v| rewrite_refs(v, prefix)),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn collect_refs<'a>(value: &'a Value, refs: &mut Vec<&'a str>) {
+        match value {
+            Value::Object(obj) => {
+                for (key, v) in obj {
+                    match v {
+                        Value::String(target) if key == "$ref" => refs.push(target),
+                        _ => collect_refs(v, refs),
+                    }
+                }
+            }
+            Value::Array(items) => items.iter().for_each(|v| collect_refs(v, refs)),
+            _ => {}
+        }
+    }
+
+    #[test]
+    fn test_every_ref_resolves() {
+        let doc = document();
+        let mut refs = Vec::new();
+        collect_refs(doc, &mut refs);
+        assert!(refs.len() > 20);
+        for target in refs {
+            let pointer = target.strip_prefix('#').expect("only local refs");
+            assert!(doc.pointer(pointer).is_some(), "dangling $ref {}", target);
+        }
+    }
+
+    #[test]
+    fn test_document_metadata() {
+        let doc = document();
+        assert_eq!(doc["openapi"], "3.1.0");
+        assert_eq!(doc["info"]["version"], env!("CARGO_PKG_VERSION"));
+        let chunk = &doc["components"]["schemas"]["ChatCompletionChunk"];
+        assert!(chunk.get("$schema").is_none());
+        assert_eq!(
+            chunk["properties"]["object"]["const"],
+            "chat.completion.chunk"
+        );
+    }
+
+    #[test]
+    fn test_operation_ids_are_unique() {
+        let mut ids: Vec<&str> = document()["paths"]
+            .as_object()
+            .unwrap()
+            .values()
+            .flat_map(|item| item.as_object().unwrap().values())
+            .map(|op| op["operationId"].as_str().unwrap())
+            .collect();
+        let total = ids.len();
+        ids.sort_unstable();
+        ids.dedup();
+        assert_eq!(ids.len(), total);
+    }
+}
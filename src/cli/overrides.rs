@@ -0,0 +1,102 @@
+// Request Overrides Module
+// Router middleware taking the `llmsim` extension object out of generation
+// request bodies, and the extractor handing it to the handlers.
+//
+// Decision: the object is removed from the body before anything else reads
+// it, so the handlers, the echo generator, mirrored copies and proxied
+// requests only ever see a body the real API would accept. The middleware is
+// outermost for that reason, so it is the one that buffers the body, and it
+// replaces the parsed copy with the stripped one; the parsed overrides travel
+// in the request extensions like the rest of a request's metadata. Requests
+// without the field pass untouched, unless errors are armed at
+// `/llmsim/errors/force` (which take the place of a `force_error` the body
+// does not set).
+
+use super::deprecation::error_response;
+use super::proxy::endpoint_type;
+use super::request_body::ParsedBody;
+use super::state::AppState;
+use crate::errors::SimulatedError;
+use crate::overrides::{RequestOverrides, OVERRIDES_FIELD};
+use axum::{
+    body::Body,
+    extract::{FromRequestParts, Request, State},
+    http::{header, request::Parts},
+    middleware::Next,
+    response::Response,
+};
+use serde_json::Value;
+use std::convert::Infallible;
+use std::sync::Arc;
+
+/// Middleware: strip the `llmsim` object from generation requests
+pub async fn extract_overrides(
+    State(state): State<Arc<AppState>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let path = request.uri().path().to_string();
+    let endpoint = (request.method() == "POST")
+        .then(|| endpoint_type(&path))
+        .flatten();
+    let Some(endpoint) = endpoint else {
+        return next.run(request).await;
+    };
+
+    let body = match ParsedBody::read(&mut request).await {
+        Ok(body) => body,
+        Err(response) => return response,
+    };
+    let extension = body.json.get(OVERRIDES_FIELD).cloned();
+    let armed = !state.forced_errors.is_empty();
+    if extension.is_none() && !armed {
+        return next.run(request).await;
+    }
+    let stripped = extension.is_some();
+
+    let mut overrides = match extension.map(RequestOverrides::from_value) {
+        None => None,
+        Some(Ok(overrides)) => Some(overrides),
+        Some(Err(message)) => {
+            state.begin(&body.model, body.stream, endpoint).fail(400);
+            return error_response(&path, endpoint, &SimulatedError::InvalidRequest { message });
+        }
+    };
+    if armed && overrides.as_ref().is_none_or(|o| o.force_error.is_none()) {
+        let model = body.model.as_str();
+        if let Some(error) = state.forced_errors.take(&path, model) {
+            tracing::info!(path = %path, model = %model, "Answering with armed error {:?}", error);
+            overrides.get_or_insert_default().force_error = Some(error);
+        }
+    }
+    let Some(overrides) = overrides else {
+        return next.run(request).await;
+    };
+    tracing::debug!(?overrides, "Request carries llmsim overrides");
+    request.extensions_mut().insert(overrides);
+    if !stripped {
+        return next.run(request).await;
+    }
+    let mut json = Value::clone(&body.json);
+    if let Some(json) = json.as_object_mut() {
+        json.remove(OVERRIDES_FIELD);
+    }
+    let body = ParsedBody {
+        bytes: serde_json::to_vec(&json).unwrap_or_default().into(),
+        json: Arc::new(json),
+        ..body
+    };
+    let (mut parts, _) = request.into_parts();
+    parts.headers.remove(header::CONTENT_LENGTH);
+    parts.extensions.insert(body.clone());
+    next.run(Request::from_parts(parts, Body::from(body.bytes)))
+        .await
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for RequestOverrides {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(parts.extensions.get::<Self>().cloned().unwrap_or_default())
+    }
+}
@@ -0,0 +1,135 @@
+// Presets Module
+// Named configurations bundled with the binary (`llmsim serve --preset`),
+// each a complete scenario: latency, generator, error rates and models.
+//
+// Decision: presets are ordinary config TOML, kept as files next to this
+// module and compiled in, so each one reads like the config a user would
+// write and `llmsim presets <name>` can print it as a starting point. A
+// `--config` file is layered on top of the preset, table by table, so a team
+// can start from a preset and override only what differs.
+
+use super::config::{Config, ConfigError};
+use super::include::{merge, resolve_includes};
+use super::interpolate::expand_env;
+use std::path::Path;
+
+/// A bundled configuration
+#[derive(Debug, Clone, Copy)]
+pub struct Preset {
+    pub name: &'static str,
+    /// One line for `llmsim presets`
+    pub description: &'static str,
+    /// The configuration, as TOML
+    pub toml: &'static str,
+}
+
+/// Every bundled preset
+pub const PRESETS: &[Preset] = &[
+    Preset {
+        name: "openai-prod-like",
+        description: "OpenAI on a normal day: per-model latency, rare 429s and 500s",
+        toml: include_str!("presets/openai-prod-like.toml"),
+    },
+    Preset {
+        name: "flaky-provider",
+        description: "Erratic latency, 10% errors and timeouts, load shedding above 50 rps",
+        toml: include_str!("presets/flaky-provider.toml"),
+    },
+    Preset {
+        name: "slow-reasoning",
+        description: "Reasoning models: ~8s to the first token, long answers",
+        toml: include_str!("presets/slow-reasoning.toml"),
+    },
+];
+
+/// The bundled preset called `name`
+pub fn find_preset(name: &str) -> Option<&'static Preset> {
+    PRESETS.iter().find(|preset| preset.name == name)
+}
+
+impl Preset {
+    /// The preset's configuration, with the `overrides` TOML layered on top.
+    /// Files the overrides include are found relative to the working
+    /// directory.
+    pub fn config(&self, overrides: Option<&str>) -> Result<Config, ConfigError> {
+        self.layered(overrides, Path::new("."))
+    }
+
+    /// The preset's configuration, with a config file layered on top
+    pub fn config_with_file(&self, path: impl AsRef<Path>) -> Result<Config, ConfigError> {
+        let path = path.as_ref();
+        let overrides =
+            std::fs::read_to_string(path).map_err(|e| ConfigError::Io(e.to_string()))?;
+        self.layered(Some(&overrides), path.parent().unwrap_or(Path::new(".")))
+    }
+
+    fn layered(&self, overrides: Option<&str>, dir: &Path) -> Result<Config, ConfigError> {
+        let parse = |text: &str| {
+            text.parse::<toml::Table>()
+                .map_err(|e| ConfigError::Parse(e.to_string()))
+        };
+        let mut table = parse(self.toml)?;
+        if let Some(overrides) = overrides {
+            let mut overrides = parse(overrides)?;
+            resolve_includes(&mut overrides, dir).map_err(ConfigError::Parse)?;
+            merge(&mut table, overrides);
+        }
+        let expansions = expand_env(&mut table).map_err(ConfigError::Parse)?;
+        let config: Config = expansions
+            .deserialize(table)
+            .map_err(|e: toml::de::Error| ConfigError::Parse(e.to_string()))?;
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_every_preset_loads() {
+        for preset in PRESETS {
+            let config = preset
+                .config(None)
+                .unwrap_or_else(|e| panic!("{}: {}", preset.name, e));
+            assert!(!config.models.available.is_empty(), "{}", preset.name);
+            // Catch keys the config would silently ignore
+            let table: toml::Table = preset.toml.parse().unwrap();
+            let loaded = toml::Table::try_from(&config).unwrap();
+            for (section, values) in &table {
+                let loaded = &loaded[section];
+                for key in values.as_table().unwrap().keys() {
+                    assert!(
+                        loaded.get(key).is_some(),
+                        "{}: unknown key [{}] {}",
+                        preset.name,
+                        section,
+                        key
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_overrides_layer_on_the_preset() {
+        let preset = find_preset("flaky-provider").unwrap();
+        let config = preset
+            .config(Some(
+                "[errors]\ntimeout_rate = 0.0\n\n[server]\nport = 9000\n",
+            ))
+            .unwrap();
+        assert_eq!(config.errors.timeout_rate, 0.0);
+        // The rest of the preset's section stays
+        assert_eq!(config.errors.rate_limit_rate, 0.04);
+        assert_eq!(config.errors.rules.len(), 1);
+        assert_eq!(config.server.port, 9000);
+        assert_eq!(config.capacity.max_rps, Some(50));
+
+        assert!(find_preset("missing").is_none());
+        assert!(preset
+            .config(Some("[errors]\nrate_limit_rate = \"high\"\n"))
+            .is_err());
+    }
+}
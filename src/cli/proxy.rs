@@ -0,0 +1,332 @@
+// Proxy Module
+// Hybrid mode: requests matching a `[[proxy.rules]]` entry are answered by
+// the real API in `[upstreams]`, all others by the simulator, behind the
+// same endpoint.
+//
+// Decision: like mirroring this is a router middleware, placed outside the
+// mirror so a proxied request is never also mirrored. Proxied traffic is
+// counted in a `Stats` of its own instead of the main one, so
+// `/llmsim/stats` keeps describing the simulator and `/llmsim/proxy` shows
+// both modes side by side with the same fields. Upstream responses are
+// relayed as they arrive, so proxied streams keep the real pacing.
+
+use super::config::{Config, ProxyRule};
+use super::handlers::AppError;
+use super::mirror::usage_from_body;
+use super::request_body::ParsedBody;
+use super::state::AppState;
+use super::upstream::{self, Upstream};
+use crate::glob::glob_match;
+use crate::learned::{ChunkTiming, Recorder};
+use crate::stats::{EndpointType, Stats, StatsSnapshot};
+use axum::{
+    body::Body,
+    extract::{Request, State},
+    http::{HeaderMap, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use futures_util::StreamExt;
+use rand::RngExt;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Proxied response bytes kept for reading usage back out
+const MAX_CAPTURE_BYTES: usize = 4 * 1024 * 1024;
+/// Upstream response headers that describe that connection, not the response
+const SKIPPED_HEADERS: [&str; 4] = [
+    "connection",
+    "content-length",
+    "transfer-encoding",
+    "keep-alive",
+];
+
+/// Routing rules, their upstreams, and stats for proxied traffic
+pub struct Proxy {
+    upstreams: BTreeMap<String, Arc<Upstream>>,
+    rules: Vec<(ProxyRule, AtomicU64)>,
+    stats: Arc<Stats>,
+}
+
+impl Proxy {
+    /// Proxy for `config`, or `None` without rules or upstreams
+    pub fn from_config(config: &Config) -> Option<Self> {
+        let upstreams = upstream::from_config(config);
+        if config.proxy.rules.is_empty() || upstreams.is_empty() {
+            return None;
+        }
+        let rules = config
+            .proxy
+            .rules
+            .iter()
+            .map(|rule| (rule.clone(), AtomicU64::new(0)))
+            .collect();
+        Some(Self {
+            upstreams,
+            rules,
+            stats: Arc::new(Stats::with_rps_window(config.server.rps_window_secs)),
+        })
+    }
+
+    /// Whether this request goes upstream: the first matching rule
+    /// decides, by its rate
+    fn proxies(&self, model: &str, headers: &HeaderMap) -> bool {
+        let Some((rule, matched)) = self
+            .rules
+            .iter()
+            .find(|(rule, _)| rule_matches(rule, model, headers))
+        else {
+            return false;
+        };
+        matched.fetch_add(1, Ordering::Relaxed);
+        rule.rate >= 1.0 || rand::rng().random_bool(rule.rate.clamp(0.0, 1.0))
+    }
+
+    /// Proxied stats next to the simulated ones, and per-rule match counts
+    pub fn summary(&self, simulated: StatsSnapshot) -> Value {
+        let rules: Vec<Value> = self
+            .rules
+            .iter()
+            .map(|(rule, matched)| {
+                serde_json::json!({
+                    "model": rule.model,
+                    "header": rule.header,
+                    "header_value": rule.header_value,
+                    "rate": rule.rate,
+                    "matched": matched.load(Ordering::Relaxed),
+                })
+            })
+            .collect();
+        serde_json::json!({
+            "simulated": simulated,
+            "proxied": self.stats.snapshot(),
+            "rules": rules,
+        })
+    }
+}
+
+/// Model conditions are globs; a rule's conditions must all hold
+fn rule_matches(rule: &ProxyRule, model: &str, headers: &HeaderMap) -> bool {
+    let model_ok = rule
+        .model
+        .as_deref()
+        .is_none_or(|pattern| glob_match(pattern, model));
+    let header_ok = rule.header.as_deref().is_none_or(|name| {
+        headers.get(name).is_some_and(|value| {
+            rule.header_value
+                .as_deref()
+                .is_none_or(|expected| value.as_bytes() == expected.as_bytes())
+        })
+    });
+    model_ok && header_ok
+}
+
+/// Endpoint a provider path is counted under; only generation endpoints
+/// are proxied
+pub(super) fn endpoint_type(path: &str) -> Option<EndpointType> {
+    if path.ends_with("/v1/chat/completions") {
+        Some(EndpointType::ChatCompletions)
+    } else if path.ends_with("/v1/responses") {
+        Some(EndpointType::Responses)
+    } else if path.ends_with("/v1/messages") {
+        Some(EndpointType::Messages)
+    } else if path.ends_with("/v1/images/generations") {
+        Some(EndpointType::Images)
+    } else {
+        None
+    }
+}
+
+/// Middleware: send requests that match a rule to their upstream
+pub async fn proxy_requests(
+    State(state): State<Arc<AppState>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let Some(proxy) = state.proxy.clone() else {
+        return next.run(request).await;
+    };
+    let path = request.uri().path().to_string();
+    let target = (request.method() == "POST")
+        .then(|| endpoint_type(&path))
+        .flatten()
+        .and_then(|endpoint| {
+            upstream::route(&proxy.upstreams, &path).map(|(u, p)| (endpoint, u, p.to_string()))
+        });
+    let Some((endpoint, upstream, upstream_path)) = target else {
+        return next.run(request).await;
+    };
+
+    let body = match ParsedBody::read(&mut request).await {
+        Ok(body) => body,
+        Err(response) => return response,
+    };
+    let model = body.model.as_str();
+    if !proxy.proxies(model, request.headers()) {
+        return next.run(request).await;
+    }
+
+    let stats = proxy.stats.clone();
+    stats.record_request_start(model, body.stream, endpoint);
+    let started = Instant::now();
+    let response = match upstream
+        .open("POST", &upstream_path, request.headers(), &body.bytes)
+        .await
+    {
+        Ok(response) => response,
+        Err(e) => {
+            tracing::warn!("Proxy to {} failed: {}", upstream.authority(), e);
+            stats.record_error(StatusCode::BAD_GATEWAY.as_u16());
+            stats.record_request_end(started.elapsed(), 0, 0);
+            return AppError::BadGateway(e.to_string()).into_response();
+        }
+    };
+    if response.status >= 400 {
+        stats.record_error(response.status);
+    }
+
+    let mut builder = Response::builder()
+        .status(response.status)
+        .header("x-llmsim-mode", HeaderValue::from_static("proxied"));
+    for (name, value) in &response.headers {
+        if !SKIPPED_HEADERS.contains(&name.as_str()) {
+            builder = builder.header(name, value);
+        }
+    }
+    let recorder = (200..300)
+        .contains(&response.status)
+        .then(|| state.recorder.clone())
+        .flatten();
+    let mut tracker = Tracker {
+        stats,
+        started,
+        captured: Vec::new(),
+        finished: false,
+        timing: ChunkTiming::new(started),
+        streamed: response.is_event_stream(),
+        model: model.to_string(),
+        recorder,
+    };
+    let mut chunks = Box::pin(response.into_body());
+    let relayed = async_stream::stream! {
+        while let Some(chunk) = chunks.next().await {
+            if let Ok(bytes) = &chunk {
+                tracker.timing.chunk();
+                if tracker.captured.len() < MAX_CAPTURE_BYTES {
+                    tracker.captured.extend_from_slice(bytes);
+                }
+            }
+            yield chunk;
+        }
+        tracker.finish();
+    };
+    builder
+        .body(Body::from_stream(relayed))
+        .unwrap_or_else(|e| AppError::BadGateway(e.to_string()).into_response())
+}
+
+/// Records a proxied request as done when its body is dropped, whether
+/// fully sent or abandoned by the client. Complete successful responses are
+/// also handed to the recorder.
+struct Tracker {
+    stats: Arc<Stats>,
+    started: Instant,
+    captured: Vec<u8>,
+    finished: bool,
+    timing: ChunkTiming,
+    streamed: bool,
+    model: String,
+    recorder: Option<Arc<Recorder>>,
+}
+
+impl Tracker {
+    fn finish(&mut self) {
+        self.finished = true;
+    }
+}
+
+impl Drop for Tracker {
+    fn drop(&mut self) {
+        if !self.finished {
+            self.stats.record_stream_abandoned();
+        }
+        let (prompt, completion) = usage_from_body(&self.captured);
+        if let (true, Some(recorder)) = (self.finished, &self.recorder) {
+            recorder.record(
+                &self.model,
+                &self.timing.observation(self.streamed, completion),
+            );
+        }
+        self.stats.record_request_end(
+            self.started.elapsed(),
+            prompt.unwrap_or(0) as u32,
+            completion.unwrap_or(0) as u32,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rule_conditions() {
+        let rule = |model: Option<&str>, header: Option<&str>, value: Option<&str>| ProxyRule {
+            model: model.map(str::to_string),
+            header: header.map(str::to_string),
+            header_value: value.map(str::to_string),
+            rate: 1.0,
+        };
+        let mut headers = HeaderMap::new();
+        headers.insert("x-route", "real".parse().unwrap());
+
+        assert!(rule_matches(&rule(None, None, None), "gpt-5", &headers));
+        assert!(rule_matches(
+            &rule(Some("gpt-*"), None, None),
+            "gpt-5",
+            &headers
+        ));
+        assert!(rule_matches(
+            &rule(None, Some("x-route"), None),
+            "gpt-5",
+            &headers
+        ));
+        assert!(rule_matches(
+            &rule(Some("gpt-5"), Some("x-route"), Some("real")),
+            "gpt-5",
+            &headers
+        ));
+        assert!(!rule_matches(
+            &rule(None, Some("x-route"), Some("sim")),
+            "gpt-5",
+            &headers
+        ));
+        assert!(!rule_matches(
+            &rule(Some("claude-*"), Some("x-route"), None),
+            "gpt-5",
+            &headers
+        ));
+        assert!(!rule_matches(
+            &rule(None, Some("x-other"), None),
+            "gpt-5",
+            &headers
+        ));
+    }
+
+    #[test]
+    fn test_endpoint_type() {
+        assert!(matches!(
+            endpoint_type("/openai/v1/chat/completions"),
+            Some(EndpointType::ChatCompletions)
+        ));
+        assert!(matches!(
+            endpoint_type("/anthropic/v1/messages"),
+            Some(EndpointType::Messages)
+        ));
+        assert!(endpoint_type("/anthropic/v1/messages/count_tokens").is_none());
+        assert!(endpoint_type("/openai/v1/models").is_none());
+    }
+}
@@ -0,0 +1,211 @@
+// Rate Limits Module
+// Router middleware applying `[rate_limits]`: generation requests are charged
+// to their API key's budgets, refused with 429 once a budget is spent, and
+// answered with the rate limit headers of their provider; every 429 gets its
+// `Retry-After` from the configured policy.
+//
+// Decision: a middleware outside the error rules and deprecations, so it sees
+// every 429 the simulator answers with (injected, rule-made or its own) and
+// can count a key's consecutive ones. OpenAI-style endpoints get
+// `x-ratelimit-*` headers with resets as durations (`6m0s`), the Anthropic
+// endpoint `anthropic-ratelimit-*` headers with resets as RFC 3339
+// timestamps, as the real APIs send them. Requests without a key share one
+// anonymous budget.
+
+use super::deprecation::error_response;
+use super::error_rules::prompt_text;
+use super::proxy::endpoint_type;
+use super::request_body::ParsedBody;
+use super::state::AppState;
+use crate::clock;
+use crate::decisions::ErrorCause;
+use crate::deprecation::rfc3339;
+use crate::errors::SimulatedError;
+use crate::ratelimit::{BudgetStatus, KeyLimits, RateLimitStatus};
+use crate::stats::EndpointType;
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderMap, HeaderName, HeaderValue, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Request fields holding the maximum output, across the providers' formats
+const MAX_OUTPUT_FIELDS: [&str; 3] = ["max_completion_tokens", "max_output_tokens", "max_tokens"];
+
+/// Middleware: charge generation requests to their key's rate limits
+pub async fn apply_rate_limits(
+    State(state): State<Arc<AppState>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let Some(limiter) = state.rate_limiter.clone() else {
+        return next.run(request).await;
+    };
+    let path = request.uri().path().to_string();
+    let endpoint = (request.method() == "POST")
+        .then(|| endpoint_type(&path))
+        .flatten();
+    let Some(endpoint) = endpoint else {
+        return next.run(request).await;
+    };
+
+    let body = match ParsedBody::read(&mut request).await {
+        Ok(body) => body,
+        Err(response) => return response,
+    };
+    let json = &body.json;
+    let key = api_key(request.headers());
+    // A `[keys]` key's own quotas, where it sets them
+    let limits = request
+        .extensions()
+        .get::<KeyLimits>()
+        .copied()
+        .unwrap_or_default()
+        .or(limiter.limits());
+    let tokens = if limits.tokens_per_minute.is_some() {
+        let prompt = state.pipeline.count_tokens(&prompt_text(json));
+        let output = MAX_OUTPUT_FIELDS
+            .iter()
+            .find_map(|field| json[field].as_u64())
+            .unwrap_or(0);
+        u32::try_from(prompt as u64 + output).unwrap_or(u32::MAX)
+    } else {
+        0
+    };
+
+    let status = limiter.acquire_with(&key, tokens, limits);
+    let mut response = if status.admitted {
+        next.run(request).await
+    } else {
+        let model = body.model.as_str();
+        tracing::info!(model, "Request over its key's rate limit");
+        let error = SimulatedError::RateLimit {
+            retry_after_seconds: status.retry_after_seconds(),
+        };
+        state
+            .begin(model, body.stream, endpoint)
+            .fail_with(ErrorCause::RateLimited, error.clone());
+        error_response(&path, endpoint, &error)
+    };
+
+    let rate_limited = response.status() == StatusCode::TOO_MANY_REQUESTS;
+    if let Some(seconds) = limiter.note_response(&key, rate_limited) {
+        response
+            .headers_mut()
+            .insert(header::RETRY_AFTER, seconds.into());
+    }
+    for (name, value) in rate_limit_headers(endpoint, &status) {
+        if let Ok(value) = HeaderValue::from_str(&value) {
+            response
+                .headers_mut()
+                .insert(HeaderName::from_static(name), value);
+        }
+    }
+    response
+}
+
+/// The caller's API key, from `Authorization: Bearer` or `x-api-key`
+pub(super) fn api_key(headers: &HeaderMap) -> String {
+    let header = |name| headers.get(name).and_then(|value| value.to_str().ok());
+    header(header::AUTHORIZATION.as_str())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .or_else(|| header("x-api-key"))
+        .unwrap_or_default()
+        .to_string()
+}
+
+/// Rate limit headers in the format of the endpoint's provider
+fn rate_limit_headers(
+    endpoint: EndpointType,
+    status: &RateLimitStatus,
+) -> Vec<(&'static str, String)> {
+    let mut headers = Vec::new();
+    let budgets = [("requests", status.requests), ("tokens", status.tokens)];
+    for (kind, budget) in budgets {
+        let Some(budget) = budget else { continue };
+        let BudgetStatus {
+            limit,
+            remaining,
+            reset,
+            ..
+        } = budget;
+        if endpoint == EndpointType::Messages {
+            let names = match kind {
+                "requests" => [
+                    "anthropic-ratelimit-requests-limit",
+                    "anthropic-ratelimit-requests-remaining",
+                    "anthropic-ratelimit-requests-reset",
+                ],
+                _ => [
+                    "anthropic-ratelimit-tokens-limit",
+                    "anthropic-ratelimit-tokens-remaining",
+                    "anthropic-ratelimit-tokens-reset",
+                ],
+            };
+            let reset_at = clock::unix_seconds() + reset.as_secs_f64().ceil() as u64;
+            headers.push((names[0], limit.to_string()));
+            headers.push((names[1], remaining.to_string()));
+            headers.push((names[2], rfc3339(reset_at as i64)));
+        } else {
+            let names = match kind {
+                "requests" => [
+                    "x-ratelimit-limit-requests",
+                    "x-ratelimit-remaining-requests",
+                    "x-ratelimit-reset-requests",
+                ],
+                _ => [
+                    "x-ratelimit-limit-tokens",
+                    "x-ratelimit-remaining-tokens",
+                    "x-ratelimit-reset-tokens",
+                ],
+            };
+            headers.push((names[0], limit.to_string()));
+            headers.push((names[1], remaining.to_string()));
+            headers.push((names[2], reset_duration(reset)));
+        }
+    }
+    headers
+}
+
+/// A reset in OpenAI's duration format: `20ms`, `1.5s`, `6m0s`
+fn reset_duration(reset: Duration) -> String {
+    let millis = reset.as_millis();
+    if millis < 1000 {
+        return format!("{}ms", millis);
+    }
+    let minutes = millis / 60_000;
+    let seconds = format!("{:.3}", (millis % 60_000) as f64 / 1000.0);
+    let seconds = seconds.trim_end_matches('0').trim_end_matches('.');
+    if minutes > 0 {
+        format!("{}m{}s", minutes, seconds)
+    } else {
+        format!("{}s", seconds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reset_duration() {
+        assert_eq!(reset_duration(Duration::from_millis(20)), "20ms");
+        assert_eq!(reset_duration(Duration::from_millis(1500)), "1.5s");
+        assert_eq!(reset_duration(Duration::from_secs(1)), "1s");
+        assert_eq!(reset_duration(Duration::from_secs(360)), "6m0s");
+        assert_eq!(reset_duration(Duration::from_millis(61_250)), "1m1.25s");
+    }
+
+    #[test]
+    fn test_api_key() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(api_key(&headers), "");
+        headers.insert("x-api-key", "sk-ant".parse().unwrap());
+        assert_eq!(api_key(&headers), "sk-ant");
+        headers.insert(header::AUTHORIZATION, "Bearer sk-oai".parse().unwrap());
+        assert_eq!(api_key(&headers), "sk-oai");
+    }
+}
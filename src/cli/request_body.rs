@@ -0,0 +1,109 @@
+// Request Body Module
+// POST request bodies buffered and parsed once, by whichever layer needs one
+// first, for every layer after it: the overrides, keys, proxy and mirror,
+// rate limits, deprecations, error rules, hung requests and CDN errors.
+//
+// Decision: the parsed body travels in the request extensions, like the rest
+// of a request's metadata, and the body itself is handed on from the same
+// bytes, so each layer reads its model, stream flag or prompt without
+// buffering and parsing a copy of its own. Nothing is buffered for a request
+// no layer reads, such as a file upload. A body that is not JSON (a gRPC
+// frame) parses as null, which reads as no model and no stream, the way each
+// layer read it before. The overrides layer, which rewrites the body,
+// replaces the extension along with it.
+
+use axum::{
+    body::{to_bytes, Body, Bytes},
+    extract::Request,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde_json::Value;
+use std::sync::Arc;
+
+/// Largest request body that is buffered
+const MAX_BODY_BYTES: usize = 16 * 1024 * 1024;
+
+/// A POST request's body, as buffered by `ParsedBody::read`
+#[derive(Debug, Clone, Default)]
+pub(super) struct ParsedBody {
+    /// The `model` field; empty if missing
+    pub model: String,
+    /// The `stream` field; false if missing
+    pub stream: bool,
+    /// The body as JSON; null if it is not JSON
+    pub json: Arc<Value>,
+    /// The body as received
+    pub bytes: Bytes,
+}
+
+impl ParsedBody {
+    /// Parse `bytes`
+    pub fn new(bytes: Bytes) -> Self {
+        let json = serde_json::from_slice::<Value>(&bytes).unwrap_or_default();
+        Self {
+            model: json["model"].as_str().unwrap_or_default().to_string(),
+            stream: json["stream"].as_bool().unwrap_or(false),
+            json: Arc::new(json),
+            bytes,
+        }
+    }
+
+    /// The body of a POST `request`, buffering and parsing it unless an
+    /// earlier layer did; the body is left in place for the next. Empty for
+    /// other methods, and a 413 response for a body too large to buffer.
+    pub async fn read(request: &mut Request) -> Result<Self, Response> {
+        if request.method() != "POST" {
+            return Ok(Self::default());
+        }
+        if let Some(body) = request.extensions().get::<Self>() {
+            return Ok(body.clone());
+        }
+        let body = std::mem::take(request.body_mut());
+        let Ok(bytes) = to_bytes(body, MAX_BODY_BYTES).await else {
+            return Err(StatusCode::PAYLOAD_TOO_LARGE.into_response());
+        };
+        let parsed = Self::new(bytes.clone());
+        *request.body_mut() = Body::from(bytes);
+        request.extensions_mut().insert(parsed.clone());
+        Ok(parsed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let body = ParsedBody::new(Bytes::from_static(
+            br#"{"model":"gpt-5","stream":true,"messages":[]}"#,
+        ));
+        assert_eq!(body.model, "gpt-5");
+        assert!(body.stream);
+        assert!(body.json["messages"].is_array());
+
+        let frame = ParsedBody::new(Bytes::from_static(b"\x00\x00\x00\x00\x02\x0a\x00"));
+        assert_eq!(frame.model, "");
+        assert!(!frame.stream);
+        assert!(frame.json.is_null());
+    }
+
+    #[tokio::test]
+    async fn test_read_once() {
+        let mut request = Request::post("/openai/v1/chat/completions")
+            .body(Body::from(r#"{"model":"gpt-5"}"#))
+            .unwrap();
+        let body = ParsedBody::read(&mut request).await.unwrap();
+        assert_eq!(body.model, "gpt-5");
+        // Later readers get the parsed copy, and the body is still there
+        let again = ParsedBody::read(&mut request).await.unwrap();
+        assert!(Arc::ptr_eq(&body.json, &again.json));
+        let bytes = to_bytes(request.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(bytes, body.bytes);
+
+        let mut get = Request::get("/llmsim/stats").body(Body::empty()).unwrap();
+        assert!(ParsedBody::read(&mut get).await.unwrap().json.is_null());
+        assert!(get.extensions().get::<ParsedBody>().is_none());
+    }
+}
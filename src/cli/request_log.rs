@@ -0,0 +1,227 @@
+// Request Log Module
+// `/llmsim/requests`: summaries of the most recent requests, newest first,
+// to see what a misbehaving client sent and what the simulator did with it
+// without turning on verbose tracing.
+//
+// Decision: the log is the decision log's history (`DecisionLog::
+// with_history`), summarized when asked for, so every way a request can end
+// (injected error, finished stream, client gone mid-stream) is covered by the
+// one place that already tracks it. Regions share the decision log, so their
+// requests are listed too. Prompt excerpts are always kept in the decisions
+// but only shown with `include_content`, so a log turned on to debug timing
+// does not echo user content by default.
+//
+// Redaction (`[request_log.redact]`) happens here, as summaries are made, not
+// when decisions are recorded: library subscribers see what was sent, and a
+// config reload cannot leave unredacted entries behind in the log. User ids
+// are hashed by default with FNV-1a, which is enough to stop a shared log
+// naming anyone; the salt keeps short ids from being found by hashing guesses.
+
+use super::config::{RequestLogConfig, UserRedaction};
+use crate::decisions::{Decision, DecisionLog, ErrorCause, Outcome};
+use crate::stats::EndpointType;
+use serde::{Deserialize, Serialize};
+use std::time::UNIX_EPOCH;
+
+/// Summaries returned when `limit` is not given
+const DEFAULT_LIMIT: usize = 50;
+
+/// One request as listed by `/llmsim/requests`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RequestSummary {
+    /// Sequence number of the request since the server started, from 1
+    pub id: u64,
+    /// When the request began, in milliseconds since the Unix epoch
+    pub started_at_ms: u64,
+    pub model: String,
+    /// `chat_completions`, `responses`, `websocket_responses`, `messages` or
+    /// `images`
+    pub endpoint: String,
+    pub stream: bool,
+    pub generator: String,
+    /// `completed`, `failed` or `abandoned`
+    pub outcome: String,
+    /// HTTP status the request was answered with; none when abandoned
+    pub status: Option<u16>,
+    /// Who decided the request should fail, when the simulator did
+    pub error_cause: Option<String>,
+    pub input_tokens: Option<u32>,
+    pub output_tokens: Option<u32>,
+    /// Wall time from arrival until the request was over
+    pub elapsed_ms: f64,
+    /// Time the simulator spent sleeping to model latency
+    pub simulated_delay_ms: f64,
+    /// End of the prompt, with `[request_log] include_content`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt: Option<String>,
+    /// End user the client named, hashed unless `[request_log.redact] user`
+    /// says otherwise
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+}
+
+/// The server's decision log, keeping a history when `[request_log]` is
+/// enabled
+pub(crate) fn decision_log(config: &RequestLogConfig) -> DecisionLog {
+    if config.enabled {
+        DecisionLog::with_history(config.capacity)
+    } else {
+        DecisionLog::new()
+    }
+}
+
+/// Up to `limit` of the most recent requests, newest first
+pub(crate) fn recent(
+    decisions: &DecisionLog,
+    config: &RequestLogConfig,
+    limit: usize,
+) -> Vec<RequestSummary> {
+    decisions
+        .recent(limit)
+        .into_iter()
+        .map(|decision| summarize(decision, config))
+        .collect()
+}
+
+/// `limit` from a query string, defaulting to `DEFAULT_LIMIT`
+pub(crate) fn parse_limit(query: Option<&str>) -> Result<usize, String> {
+    let value = query
+        .into_iter()
+        .flat_map(|q| q.split('&'))
+        .filter_map(|pair| pair.split_once('='))
+        .find_map(|(name, value)| (name == "limit").then_some(value));
+    match value {
+        None => Ok(DEFAULT_LIMIT),
+        Some(value) => value
+            .parse()
+            .ok()
+            .filter(|limit| *limit >= 1)
+            .ok_or_else(|| {
+                format!(
+                    "Invalid 'limit': expected a positive integer, got '{}'",
+                    value
+                )
+            }),
+    }
+}
+
+fn summarize(decision: Decision, config: &RequestLogConfig) -> RequestSummary {
+    let (outcome, status, input_tokens, output_tokens) = match decision.outcome {
+        Outcome::Completed {
+            input_tokens,
+            output_tokens,
+        } => (
+            "completed",
+            Some(200),
+            Some(input_tokens),
+            Some(output_tokens),
+        ),
+        Outcome::Failed { status } => ("failed", Some(status), None, None),
+        Outcome::Abandoned => ("abandoned", None, None, None),
+    };
+    RequestSummary {
+        id: decision.request,
+        started_at_ms: decision
+            .started_at
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_millis() as u64),
+        model: decision.model,
+        endpoint: endpoint_name(decision.endpoint).to_string(),
+        stream: decision.streaming,
+        generator: decision.generator,
+        outcome: outcome.to_string(),
+        status,
+        error_cause: decision
+            .error
+            .map(|(cause, _)| cause_name(cause).to_string()),
+        input_tokens,
+        output_tokens,
+        elapsed_ms: decision.elapsed.as_secs_f64() * 1000.0,
+        simulated_delay_ms: decision
+            .delays
+            .iter()
+            .map(|delay| delay.as_secs_f64() * 1000.0)
+            .sum(),
+        prompt: decision
+            .prompt
+            .filter(|_| config.include_content)
+            .map(|prompt| truncate(prompt, config.redact.max_content_chars)),
+        user: decision.user.and_then(|user| match config.redact.user {
+            UserRedaction::Keep => Some(user),
+            UserRedaction::Hash => Some(hash_user(&config.redact.salt, &user)),
+            UserRedaction::Strip => None,
+        }),
+    }
+}
+
+/// The last `max_chars` characters of `text`, all of it without a limit
+fn truncate(text: String, max_chars: Option<usize>) -> String {
+    match max_chars {
+        Some(max) => {
+            let skip = text.chars().count().saturating_sub(max);
+            text.chars().skip(skip).collect()
+        }
+        None => text,
+    }
+}
+
+/// `user-` and the FNV-1a hash of the salt and id, in hex
+fn hash_user(salt: &str, user: &str) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in salt.bytes().chain(user.bytes()) {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    format!("user-{:016x}", hash)
+}
+
+fn endpoint_name(endpoint: EndpointType) -> &'static str {
+    match endpoint {
+        EndpointType::ChatCompletions => "chat_completions",
+        EndpointType::Responses => "responses",
+        EndpointType::WebSocketResponses => "websocket_responses",
+        EndpointType::Messages => "messages",
+        EndpointType::Images => "images",
+        EndpointType::Grpc => "grpc",
+    }
+}
+
+fn cause_name(cause: ErrorCause) -> &'static str {
+    match cause {
+        ErrorCause::Drill => "drill",
+        ErrorCause::LoadShedding => "load_shedding",
+        ErrorCause::Injected => "injected",
+        ErrorCause::Deprecation => "deprecation",
+        ErrorCause::Rule => "rule",
+        ErrorCause::RateLimited => "rate_limited",
+        ErrorCause::Requested => "requested",
+        ErrorCause::Unauthorized => "unauthorized",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_limit() {
+        assert_eq!(parse_limit(None), Ok(DEFAULT_LIMIT));
+        assert_eq!(parse_limit(Some("limit=5")), Ok(5));
+        assert_eq!(parse_limit(Some("x=1&limit=7")), Ok(7));
+        assert!(parse_limit(Some("limit=0")).is_err());
+        assert!(parse_limit(Some("limit=many")).is_err());
+    }
+
+    #[test]
+    fn test_redaction() {
+        assert_eq!(truncate("abcdef".to_string(), Some(3)), "def");
+        assert_eq!(truncate("abc".to_string(), Some(5)), "abc");
+        assert_eq!(truncate("abc".to_string(), None), "abc");
+
+        let hashed = hash_user("", "alice");
+        assert_eq!(hashed, hash_user("", "alice"));
+        assert!(hashed.starts_with("user-") && !hashed.contains("alice"));
+        assert_ne!(hashed, hash_user("", "bob"));
+        assert_ne!(hashed, hash_user("pepper", "alice"));
+    }
+}
@@ -0,0 +1,450 @@
+// Response Store Module
+// In-memory store of the Responses API responses the simulator created,
+// listed at `GET /openai/v1/responses` and retrieved by id, with the input
+// items each was created from.
+//
+// Decision: responses are stored as sent to the client and paged in creation
+// order with the API's `limit`/`after`/`before`/`order` parameters, so client
+// pagination helpers walk a real list. Requests with `store: false` are not
+// kept. The store is bounded by count and drops the oldest responses first;
+// a simulator restart forgets everything. Cursors must name a stored
+// response: an unknown one is a client error rather than an empty page.
+// Input items are kept in the shape the API lists them: every item gets an
+// id, and message text becomes a content part. A response chained to a
+// stored one with `previous_response_id` reads the whole conversation again,
+// so the store also answers how many tokens that conversation holds. The
+// events of streamed background responses are logged as they are generated,
+// for `GET /openai/v1/responses/{id}?stream=true&starting_after=N` to replay
+// and follow by `sequence_number`, as many as responses are kept.
+
+use super::config::StoreConfig;
+use super::resume::{StreamLog, StreamLogs};
+use crate::ids::{new_id, IdKind};
+use crate::openai::{
+    InputItem, InputRole, ListResponse, MessageContent, ResponsesInput, ResponsesResponse,
+};
+use serde_json::{json, Value};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+/// Page size when `limit` is not given
+const DEFAULT_LIMIT: usize = 20;
+/// Largest page a client may ask for
+const MAX_LIMIT: usize = 100;
+
+/// Responses by id, oldest dropped first beyond `max_responses`
+pub struct ResponseStore {
+    max_responses: usize,
+    inner: Mutex<Inner>,
+    /// Events of background responses, by response id
+    streams: StreamLogs,
+}
+
+/// A response with the input items it was created from
+struct Stored {
+    response: ResponsesResponse,
+    input_items: Vec<Value>,
+}
+
+#[derive(Default)]
+struct Inner {
+    responses: HashMap<String, Stored>,
+    /// Ids in the order they were stored
+    order: VecDeque<String>,
+}
+
+impl ResponseStore {
+    pub fn new(config: &StoreConfig) -> Self {
+        Self {
+            max_responses: config.max_responses,
+            inner: Mutex::new(Inner::default()),
+            streams: StreamLogs::new(config.max_responses),
+        }
+    }
+
+    /// Keep `response` and its `input_items` (see [`input_items`]),
+    /// replacing an earlier one with the same id
+    pub fn put(&self, response: &ResponsesResponse, input_items: Vec<Value>) {
+        if self.max_responses == 0 {
+            return;
+        }
+        let mut inner = self.inner.lock().unwrap();
+        let stored = Stored {
+            response: response.clone(),
+            input_items,
+        };
+        let previous = inner.responses.insert(response.id.clone(), stored);
+        if previous.is_none() {
+            inner.order.push_back(response.id.clone());
+        }
+        while inner.order.len() > self.max_responses {
+            if let Some(oldest) = inner.order.pop_front() {
+                inner.responses.remove(&oldest);
+            }
+        }
+    }
+
+    pub fn get(&self, id: &str) -> Option<ResponsesResponse> {
+        let inner = self.inner.lock().unwrap();
+        inner
+            .responses
+            .get(id)
+            .map(|stored| stored.response.clone())
+    }
+
+    /// Input items of the response `id`, in the order they were sent
+    pub fn input_items(&self, id: &str) -> Option<Vec<Value>> {
+        let inner = self.inner.lock().unwrap();
+        inner
+            .responses
+            .get(id)
+            .map(|stored| stored.input_items.clone())
+    }
+
+    /// Tokens of the conversation ending with the response `id`, which a
+    /// response chained to it reads again: its input, which already holds
+    /// the turns before it, and its output
+    pub fn context_tokens(&self, id: &str) -> Option<u32> {
+        let inner = self.inner.lock().unwrap();
+        let stored = inner.responses.get(id)?;
+        Some(conversation_tokens(&stored.response))
+    }
+
+    /// Start logging the events of the background response `id`
+    pub(crate) fn open_stream(&self, id: &str) -> Arc<StreamLog> {
+        self.streams.open(id.to_string(), sequence_number)
+    }
+
+    /// Events of the background response `id`, if it was streamed
+    pub(crate) fn stream(&self, id: &str) -> Option<Arc<StreamLog>> {
+        self.streams.get(id)
+    }
+
+    /// A page of the stored responses
+    pub fn list(&self, params: &ListParams) -> Result<ListResponse<ResponsesResponse>, String> {
+        let inner = self.inner.lock().unwrap();
+        let responses: Vec<&ResponsesResponse> = inner
+            .order
+            .iter()
+            .filter_map(|id| inner.responses.get(id))
+            .map(|stored| &stored.response)
+            .collect();
+        let page = paginate(&responses, |response| &response.id, params)?;
+        Ok(page.cloned().cloned())
+    }
+}
+
+/// Input and output tokens of `response`
+pub fn conversation_tokens(response: &ResponsesResponse) -> u32 {
+    response
+        .usage
+        .as_ref()
+        .map_or(0, |usage| usage.input_tokens + usage.output_tokens)
+}
+
+/// `sequence_number` of a Responses stream event
+fn sequence_number(event: &str) -> Option<u64> {
+    let data = event.lines().find_map(|line| line.strip_prefix("data: "))?;
+    serde_json::from_str::<Value>(data).ok()?["sequence_number"].as_u64()
+}
+
+/// `input` as the API lists it: each item with an id, and messages with
+/// their text as an `input_text` (or, from the assistant, `output_text`) part
+pub fn input_items(input: &ResponsesInput) -> Vec<Value> {
+    let items = match input {
+        ResponsesInput::Text(text) => {
+            return vec![message_item(
+                &InputRole::User,
+                &MessageContent::Text(text.clone()),
+            )]
+        }
+        ResponsesInput::Items(items) => items,
+    };
+    items
+        .iter()
+        .map(|item| match item {
+            InputItem::Message { role, content } => message_item(role, content),
+            _ => {
+                let mut value = serde_json::to_value(item).unwrap_or_default();
+                if value.get("id").is_none() {
+                    let kind = match item {
+                        InputItem::FunctionCallOutput { .. } => IdKind::FunctionCallOutput,
+                        InputItem::McpListTools { .. } => IdKind::McpListTools,
+                        InputItem::McpApprovalResponse { .. } => IdKind::McpApprovalResponse,
+                        _ => IdKind::InputItem,
+                    };
+                    value["id"] = new_id(kind).into();
+                }
+                value
+            }
+        })
+        .collect()
+}
+
+fn message_item(role: &InputRole, content: &MessageContent) -> Value {
+    let content = match content {
+        MessageContent::Text(text) => {
+            let part = if *role == InputRole::Assistant {
+                "output_text"
+            } else {
+                "input_text"
+            };
+            json!([{"type": part, "text": text}])
+        }
+        MessageContent::Parts(parts) => serde_json::to_value(parts).unwrap_or_default(),
+    };
+    json!({
+        "type": "message",
+        "id": new_id(IdKind::Message),
+        "role": role,
+        "content": content,
+    })
+}
+
+/// Sort direction of a list, by creation
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Order {
+    Asc,
+    /// Newest first, the API default
+    #[default]
+    Desc,
+}
+
+/// Pagination parameters of a list request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListParams {
+    pub limit: usize,
+    pub order: Order,
+    /// Start after the item with this id
+    pub after: Option<String>,
+    /// End before the item with this id
+    pub before: Option<String>,
+}
+
+impl Default for ListParams {
+    fn default() -> Self {
+        Self {
+            limit: DEFAULT_LIMIT,
+            order: Order::default(),
+            after: None,
+            before: None,
+        }
+    }
+}
+
+impl ListParams {
+    /// Parameters from a query string; unknown parameters are ignored
+    pub fn from_query(query: Option<&str>) -> Result<Self, String> {
+        let mut params = Self::default();
+        let pairs = query
+            .into_iter()
+            .flat_map(|q| q.split('&'))
+            .filter_map(|pair| pair.split_once('='));
+        for (name, value) in pairs {
+            match name {
+                "limit" => {
+                    params.limit = value
+                        .parse()
+                        .ok()
+                        .filter(|limit| (1..=MAX_LIMIT).contains(limit))
+                        .ok_or_else(|| {
+                            format!(
+                                "Invalid 'limit': expected an integer from 1 to {}, got '{}'",
+                                MAX_LIMIT, value
+                            )
+                        })?;
+                }
+                "order" => {
+                    params.order = match value {
+                        "asc" => Order::Asc,
+                        "desc" => Order::Desc,
+                        _ => {
+                            return Err(format!(
+                                "Invalid 'order': expected 'asc' or 'desc', got '{}'",
+                                value
+                            ))
+                        }
+                    };
+                }
+                "after" if !value.is_empty() => params.after = Some(value.to_string()),
+                "before" if !value.is_empty() => params.before = Some(value.to_string()),
+                _ => {}
+            }
+        }
+        Ok(params)
+    }
+}
+
+/// Query parameters of `GET /openai/v1/responses/{id}`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StreamParams {
+    /// Send the response's events rather than the response
+    pub stream: bool,
+    /// Leave out events with this sequence number or lower
+    pub starting_after: Option<u64>,
+}
+
+impl StreamParams {
+    /// Parameters from a query string; unknown parameters are ignored
+    pub fn from_query(query: Option<&str>) -> Result<Self, String> {
+        let mut params = Self::default();
+        let pairs = query
+            .into_iter()
+            .flat_map(|q| q.split('&'))
+            .filter_map(|pair| pair.split_once('='));
+        for (name, value) in pairs {
+            match name {
+                "stream" => params.stream = value == "true",
+                "starting_after" => {
+                    params.starting_after = Some(value.parse().map_err(|_| {
+                        format!(
+                            "Invalid 'starting_after': expected a sequence number, got '{}'",
+                            value
+                        )
+                    })?);
+                }
+                _ => {}
+            }
+        }
+        Ok(params)
+    }
+}
+
+/// The page of `items` (oldest first) selected by `params`
+pub fn paginate<'a, T>(
+    items: &'a [T],
+    id: impl Fn(&T) -> &str,
+    params: &ListParams,
+) -> Result<ListResponse<&'a T>, String> {
+    let mut ordered: Vec<&T> = items.iter().collect();
+    if params.order == Order::Desc {
+        ordered.reverse();
+    }
+    let position = |cursor: &str| {
+        ordered
+            .iter()
+            .position(|item| id(item) == cursor)
+            .ok_or_else(|| format!("No item with id '{}' to page from", cursor))
+    };
+    let start = match &params.after {
+        Some(cursor) => position(cursor)? + 1,
+        None => 0,
+    };
+    let end = match &params.before {
+        Some(cursor) => position(cursor)?,
+        None => ordered.len(),
+    };
+    let window = ordered.get(start..end).unwrap_or_default();
+
+    // Paging backwards takes the items closest to the `before` cursor
+    let has_more = window.len() > params.limit;
+    let data: Vec<&T> = if params.before.is_some() && params.after.is_none() {
+        window[window.len().saturating_sub(params.limit)..].to_vec()
+    } else {
+        window.iter().take(params.limit).copied().collect()
+    };
+    Ok(ListResponse {
+        object: "list".to_string(),
+        first_id: data.first().map(|item| id(item).to_string()),
+        last_id: data.last().map(|item| id(item).to_string()),
+        has_more,
+        data,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids<'a>(page: &ListResponse<&'a String>) -> Vec<&'a str> {
+        page.data.iter().map(|id| id.as_str()).collect()
+    }
+
+    fn params(query: &str) -> ListParams {
+        ListParams::from_query(Some(query)).unwrap()
+    }
+
+    #[test]
+    fn test_from_query() {
+        assert_eq!(ListParams::from_query(None).unwrap(), ListParams::default());
+        let parsed = params("limit=5&order=asc&after=resp_1&api-version=1");
+        assert_eq!(parsed.limit, 5);
+        assert_eq!(parsed.order, Order::Asc);
+        assert_eq!(parsed.after.as_deref(), Some("resp_1"));
+        assert!(parsed.before.is_none());
+
+        assert!(ListParams::from_query(Some("limit=0")).is_err());
+        assert!(ListParams::from_query(Some("limit=101")).is_err());
+        assert!(ListParams::from_query(Some("order=newest")).is_err());
+    }
+
+    #[test]
+    fn test_paginate() {
+        let items = ["a", "b", "c", "d", "e"].map(String::from);
+        let id = String::as_str;
+
+        let page = paginate(&items, id, &params("limit=2")).unwrap();
+        assert_eq!(ids(&page), ["e", "d"]);
+        assert!(page.has_more);
+        assert_eq!(page.last_id.as_deref(), Some("d"));
+
+        let page = paginate(&items, id, &params("limit=2&after=d")).unwrap();
+        assert_eq!(ids(&page), ["c", "b"]);
+        assert!(page.has_more);
+        let page = paginate(&items, id, &params("limit=2&after=b")).unwrap();
+        assert_eq!(ids(&page), ["a"]);
+        assert!(!page.has_more);
+
+        // Backwards from a cursor keeps the nearest items
+        let page = paginate(&items, id, &params("limit=2&order=asc&before=e")).unwrap();
+        assert_eq!(ids(&page), ["c", "d"]);
+        assert!(page.has_more);
+
+        assert!(paginate(&items, id, &params("after=z")).is_err());
+    }
+
+    #[test]
+    fn test_oldest_dropped_beyond_limit() {
+        let store = ResponseStore::new(&StoreConfig {
+            max_responses: 2,
+            ..StoreConfig::default()
+        });
+        for text in ["one", "two", "three"] {
+            let mut response = ResponsesResponse::warmup("gpt-5".to_string());
+            response.id = format!("resp_{}", text);
+            let input = ResponsesInput::Text(text.to_string());
+            store.put(&response, input_items(&input));
+        }
+        assert!(store.get("resp_one").is_none());
+        let page = store.list(&ListParams::default()).unwrap();
+        let ids: Vec<_> = page.data.iter().map(|r| r.id.as_str()).collect();
+        assert_eq!(ids, ["resp_three", "resp_two"]);
+        assert_eq!(
+            store.input_items("resp_two").unwrap()[0]["content"][0]["text"],
+            "two"
+        );
+    }
+
+    #[test]
+    fn test_input_items() {
+        let input: ResponsesInput = serde_json::from_value(json!([
+            {"role": "user", "content": "What is 2+2?"},
+            {"role": "assistant", "content": "4"},
+            {"type": "function_call_output", "call_id": "call_1", "output": "{}"},
+            {"type": "mcp_list_tools", "id": "mcpl_1", "server_label": "docs", "tools": []}
+        ]))
+        .unwrap();
+        let items = input_items(&input);
+        assert_eq!(items[0]["type"], "message");
+        assert!(items[0]["id"].as_str().unwrap().starts_with("msg_"));
+        assert_eq!(
+            items[0]["content"],
+            json!([{"type": "input_text", "text": "What is 2+2?"}])
+        );
+        assert_eq!(items[1]["content"][0]["type"], "output_text");
+        assert!(items[2]["id"].as_str().unwrap().starts_with("fco_"));
+        assert_eq!(items[2]["call_id"], "call_1");
+        assert_eq!(items[3]["type"], "mcp_list_tools");
+        assert!(items[3]["id"].as_str().is_some());
+    }
+}
@@ -0,0 +1,242 @@
+// Stream Resumption Module
+// `[stream] event_ids`: every SSE event carries an `id:` line, and a client
+// that reconnects with `Last-Event-ID` picks the stream up after that event,
+// as EventSource-style clients expect of a server that numbers its events.
+//
+// Decision: an id is `<stream>:<sequence>`, the stream part a random key, so
+// the id alone finds the stream whichever endpoint or region served it. Ids
+// are assigned to events as generated, before duplicate and reorder faults,
+// so a duplicated event repeats its id and a swapped pair shows it. A
+// numbered stream is generated to its end in a task of its own, whether or
+// not the client is still reading, into a log the first connection and any
+// reconnection follow; so the request is not abandoned when its client goes
+// away, and it forgoes the `pending_events` backpressure. The most recent
+// `resumable_streams` logs are kept. A reconnection is served from the log
+// before any other layer sees it: it is the earlier request continued, not a
+// new one to count, limit or fail. An id no kept log knows is passed on as an
+// ordinary request.
+
+use super::state::AppState;
+use crate::Stamps;
+use axum::{
+    extract::{Request, State},
+    http::HeaderMap,
+    middleware::Next,
+    response::Response,
+};
+use futures_core::Stream;
+use futures_util::StreamExt;
+use rand::RngExt;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use tokio::sync::watch;
+
+/// Request header a reconnecting client names its last event with
+const LAST_EVENT_ID: &str = "last-event-id";
+
+/// Logs of the most recent streams, by key
+pub(crate) struct StreamLogs {
+    capacity: usize,
+    logs: Mutex<VecDeque<(String, Arc<StreamLog>)>>,
+}
+
+/// Events of one stream as generated, each with its sequence number, and
+/// whether it has ended
+pub(crate) struct StreamLog {
+    sequence: fn(&str) -> Option<u64>,
+    events: Mutex<Vec<(u64, String)>>,
+    progress: watch::Sender<(usize, bool)>,
+}
+
+impl StreamLogs {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            logs: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// A new stream's key
+    pub(crate) fn key() -> String {
+        format!("{:016x}", rand::rng().random::<u64>())
+    }
+
+    /// Keep a new, empty log under `key`, its events numbered by `sequence`,
+    /// dropping the oldest beyond capacity
+    pub(crate) fn open(&self, key: String, sequence: fn(&str) -> Option<u64>) -> Arc<StreamLog> {
+        let log = Arc::new(StreamLog {
+            sequence,
+            events: Mutex::new(Vec::new()),
+            progress: watch::channel((0, false)).0,
+        });
+        let mut logs = self.logs.lock().unwrap();
+        logs.push_front((key, log.clone()));
+        logs.truncate(self.capacity);
+        log
+    }
+
+    pub(crate) fn get(&self, key: &str) -> Option<Arc<StreamLog>> {
+        let logs = self.logs.lock().unwrap();
+        logs.iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, log)| log.clone())
+    }
+
+    /// The rest of the numbered stream after event `id`, if it is still kept
+    fn resume(&self, id: &str) -> Option<impl Stream<Item = String>> {
+        let (key, sequence) = id.trim().rsplit_once(':')?;
+        let sequence: u64 = sequence.parse().ok()?;
+        let log = self.get(key)?;
+        let after = log
+            .events
+            .lock()
+            .unwrap()
+            .iter()
+            .rposition(|(n, _)| *n == sequence)?;
+        Some(log.follow(after + 1, None))
+    }
+}
+
+impl StreamLog {
+    /// Generate `events` into the log in a task of their own, to the end
+    /// whoever is reading, and follow it from the start
+    pub(crate) fn record<S>(self: &Arc<Self>, mut events: S) -> impl Stream<Item = String>
+    where
+        S: Stream<Item = String> + Send + Unpin + 'static,
+    {
+        let log = self.clone();
+        tokio::spawn(Stamps::current().scope(Box::pin(async move {
+            while let Some(event) = events.next().await {
+                log.push(event);
+            }
+            log.progress.send_modify(|(_, done)| *done = true);
+        })));
+        self.follow(0, None)
+    }
+
+    fn push(&self, event: String) {
+        let sequence = (self.sequence)(&event).unwrap_or_default();
+        let len = {
+            let mut events = self.events.lock().unwrap();
+            events.push((sequence, event));
+            events.len()
+        };
+        self.progress.send_modify(|(sent, _)| *sent = len);
+    }
+
+    /// Events from index `from` on, as they are logged, leaving out those
+    /// numbered `after` or lower; whatever is waiting goes out as one frame
+    pub(crate) fn follow(
+        self: &Arc<Self>,
+        mut from: usize,
+        after: Option<u64>,
+    ) -> impl Stream<Item = String> {
+        let log = self.clone();
+        let mut progress = self.progress.subscribe();
+        async_stream::stream! {
+            loop {
+                let (sent, done) = *progress.borrow_and_update();
+                if from < sent {
+                    let frame: String = log.events.lock().unwrap()[from..sent]
+                        .iter()
+                        .filter(|(n, _)| after.is_none_or(|after| *n > after))
+                        .map(|(_, event)| event.as_str())
+                        .collect();
+                    from = sent;
+                    if !frame.is_empty() {
+                        yield frame;
+                    }
+                } else if done || progress.changed().await.is_err() {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// Prefix each of `events` with its id, `<key>:<sequence>` counting from 1.
+/// Comments are not events and get none.
+pub(crate) fn number_events<S>(events: S, key: String) -> impl Stream<Item = String>
+where
+    S: Stream<Item = String> + Send + Unpin + 'static,
+{
+    let mut sequence = 0u64;
+    events.map(move |event| {
+        if event.starts_with(':') {
+            return event;
+        }
+        sequence += 1;
+        format!("id: {}:{}\n{}", key, sequence, event)
+    })
+}
+
+/// Sequence number of an event numbered by `number_events`
+pub(crate) fn sequence_of(event: &str) -> Option<u64> {
+    let id = event.strip_prefix("id: ")?.split_once('\n')?.0;
+    id.rsplit_once(':')?.1.parse().ok()
+}
+
+/// Serve a reconnection naming a kept stream's event with `Last-Event-ID`
+/// from the log, and pass everything else on
+pub(crate) async fn resume_streams(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let resumed = last_event_id(request.headers()).and_then(|id| state.stream_logs.resume(id));
+    match resumed {
+        Some(events) => super::handlers::sse_body(&state, events.boxed()),
+        None => next.run(request).await,
+    }
+}
+
+fn last_event_id(headers: &HeaderMap) -> Option<&str> {
+    headers.get(LAST_EVENT_ID)?.to_str().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_resume_after_last_event() {
+        let logs = StreamLogs::new(2);
+        let events = futures_util::stream::iter(["a\n\n", ": ping\n\n", "b\n\n", "c\n\n"])
+            .map(str::to_string);
+        let key = StreamLogs::key();
+        let sent: String = logs
+            .open(key.clone(), sequence_of)
+            .record(number_events(events, key.clone()))
+            .collect::<Vec<_>>()
+            .await
+            .concat();
+        assert_eq!(
+            sent,
+            format!(
+                "id: {0}:1\na\n\n: ping\n\nid: {0}:2\nb\n\nid: {0}:3\nc\n\n",
+                key
+            )
+        );
+
+        let rest: String = logs
+            .resume(&format!("{}:2", key))
+            .unwrap()
+            .collect::<Vec<_>>()
+            .await
+            .concat();
+        assert_eq!(rest, format!("id: {}:3\nc\n\n", key));
+        assert!(logs.resume(&format!("{}:9", key)).is_none());
+        assert!(logs.resume("unknown:1").is_none());
+
+        // Only the newest logs are kept
+        for _ in 0..2 {
+            let key = StreamLogs::key();
+            let empty = futures_util::stream::iter(Vec::<String>::new());
+            logs.open(key, sequence_of)
+                .record(empty)
+                .collect::<Vec<_>>()
+                .await;
+        }
+        assert!(logs.resume(&format!("{}:2", key)).is_none());
+    }
+}
@@ -0,0 +1,72 @@
+// Runtime Module
+// Builds the tokio runtime `llmsim serve` runs on from `[runtime]`, and reads
+// its scheduler metrics for `/llmsim/stats`, so a gateway benchmark can tell
+// when the simulator itself is the bottleneck.
+//
+// Decision: only tokio's stable metrics are read unconditionally. The depth
+// of each worker's local queue is behind `--cfg tokio_unstable`, so it is
+// reported by builds with that flag and left out otherwise; the shared queue
+// depth and per-worker busy time are always there.
+
+use super::config::RuntimeConfig;
+use crate::stats::{RuntimeSnapshot, WorkerSnapshot};
+use tokio::runtime::{Builder, Handle, Runtime};
+
+/// A multi-threaded runtime sized by `config`
+pub fn build_runtime(config: &RuntimeConfig) -> std::io::Result<Runtime> {
+    let mut builder = Builder::new_multi_thread();
+    builder.enable_all();
+    if let Some(threads) = config.worker_threads {
+        builder.worker_threads(threads);
+    }
+    if let Some(threads) = config.max_blocking_threads {
+        builder.max_blocking_threads(threads);
+    }
+    builder.build()
+}
+
+/// Scheduler figures of the current runtime, if called on one
+pub fn runtime_snapshot() -> Option<RuntimeSnapshot> {
+    let metrics = Handle::try_current().ok()?.metrics();
+    let worker_metrics = (0..metrics.num_workers())
+        .map(|worker| WorkerSnapshot {
+            busy_ms: metrics.worker_total_busy_duration(worker).as_secs_f64() * 1000.0,
+            park_count: metrics.worker_park_count(worker),
+            local_queue_depth: local_queue_depth(&metrics, worker),
+        })
+        .collect();
+    Some(RuntimeSnapshot {
+        workers: metrics.num_workers(),
+        alive_tasks: metrics.num_alive_tasks(),
+        global_queue_depth: metrics.global_queue_depth(),
+        worker_metrics,
+    })
+}
+
+#[cfg(tokio_unstable)]
+fn local_queue_depth(metrics: &tokio::runtime::RuntimeMetrics, worker: usize) -> Option<usize> {
+    Some(metrics.worker_local_queue_depth(worker))
+}
+
+#[cfg(not(tokio_unstable))]
+fn local_queue_depth(_metrics: &tokio::runtime::RuntimeMetrics, _worker: usize) -> Option<usize> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sized_runtime_reports_its_workers() {
+        let runtime = build_runtime(&RuntimeConfig {
+            worker_threads: Some(3),
+            max_blocking_threads: Some(2),
+        })
+        .unwrap();
+        let snapshot = runtime.block_on(async { runtime_snapshot() }).unwrap();
+        assert_eq!(snapshot.workers, 3);
+        assert_eq!(snapshot.worker_metrics.len(), 3);
+        assert!(runtime_snapshot().is_none());
+    }
+}
@@ -1,25 +1,296 @@
 // Application State Module
 
+use super::auxiliary::Auxiliary;
+use super::completions_store::CompletionStore;
 use super::config::Config;
+use super::error_catalog::ForcedErrors;
+use super::error_rules::ErrorRules;
+use super::files::FileStore;
+use super::keys::KeyStore;
+use super::mirror::Mirror;
+use super::proxy::Proxy;
+use super::responses_store::ResponseStore;
+use super::resume::StreamLogs;
+use super::tee::StreamTee;
+use super::usage::UsageLedger;
+use crate::decisions::DecisionLog;
+use crate::deprecation::Deprecations;
+use crate::drill::Drills;
+use crate::generator::create_generator;
+use crate::latency::LatencyModels;
+use crate::learned::{LearnedProfiles, Recorder};
+use crate::mcp::McpCatalogs;
+use crate::pipeline::{PipelineRequest, RequestPipeline};
+use crate::pool::ResponsePool;
+use crate::ratelimit::RateLimiter;
 use crate::script::Script;
-use crate::stats::SharedStats;
+use crate::sleep::{default_sleeper, Sleeper};
+use crate::stats::{EndpointType, SharedStats, Stats};
 use std::sync::Arc;
+use std::time::Duration;
 
 /// Shared application state
 pub struct AppState {
     pub config: Config,
     pub stats: SharedStats,
+    /// Request lifecycle shared by all protocol handlers, built from `config`
+    pub pipeline: RequestPipeline,
     /// Optional scripted-response source. When set, handlers replay
     /// scripted turns instead of using the configured generator.
     pub script: Option<Arc<Script>>,
+    /// States of the `[regions]`, each with its own pipeline and stats
+    pub regions: Vec<Region>,
+    /// Failover drills, one board shared with every region
+    pub drills: Arc<Drills>,
+    /// Errors armed at `/llmsim/errors/force`, shared with every region
+    pub forced_errors: Arc<ForcedErrors>,
+    /// Shadow traffic to `[upstreams]`, when `[mirror]` is enabled
+    pub mirror: Option<Arc<Mirror>>,
+    /// Rules sending requests to `[upstreams]` instead of the simulator
+    pub proxy: Option<Arc<Proxy>>,
+    /// Learns profiles from upstream responses, with `[learned] record`
+    pub recorder: Option<Arc<Recorder>>,
+    /// Generated artifacts served under `/llmsim/files`, shared by regions
+    pub files: Arc<FileStore>,
+    /// Responses API responses kept for listing, shared by regions
+    pub responses: Arc<ResponseStore>,
+    /// Chat completions created with `store: true`, shared by regions
+    pub completions: Arc<CompletionStore>,
+    /// Usage of completed requests, when `[usage]` is enabled; shared by
+    /// regions
+    pub usage: Option<Arc<UsageLedger>>,
+    /// API keys managed at `/llmsim/keys`, when `[keys]` is enabled; shared
+    /// by regions
+    pub keys: Option<Arc<KeyStore>>,
+    /// Numbered SSE streams kept for `Last-Event-ID` resumption, shared by
+    /// regions
+    pub(crate) stream_logs: Arc<StreamLogs>,
+    /// Captures of selected streams, when `[stream.tee] dir` is set; shared
+    /// by regions
+    pub tee: Option<Arc<StreamTee>>,
+    /// Tool catalogs of the fake MCP servers
+    pub mcp: McpCatalogs,
+    /// Model retirement schedules, shared by regions; `None` without any
+    pub deprecations: Option<Arc<Deprecations>>,
+    /// Subscribers to the simulator's per-request decisions, shared by
+    /// regions
+    pub decisions: Arc<DecisionLog>,
+    /// Content-keyed `[[errors.rules]]`; `None` without any
+    pub error_rules: Option<Arc<ErrorRules>>,
+    /// Per-key `[rate_limits]` budgets; `None` without limits or policy
+    pub rate_limiter: Option<Arc<RateLimiter>>,
+    /// `[auxiliary]` latency and errors; `None` when it sets neither
+    pub auxiliary: Option<Arc<Auxiliary>>,
+    /// Pre-generated chat completions, when `[pool]` is enabled
+    pub pool: Option<Arc<ResponsePool>>,
+}
+
+/// State every listener shares with the regions
+#[derive(Clone)]
+struct Shared {
+    drills: Arc<Drills>,
+    forced_errors: Arc<ForcedErrors>,
+    recorder: Option<Arc<Recorder>>,
+    replay: Option<Arc<LearnedProfiles>>,
+    files: Arc<FileStore>,
+    responses: Arc<ResponseStore>,
+    completions: Arc<CompletionStore>,
+    usage: Option<Arc<UsageLedger>>,
+    keys: Option<Arc<KeyStore>>,
+    stream_logs: Arc<StreamLogs>,
+    tee: Option<Arc<StreamTee>>,
+    deprecations: Option<Arc<Deprecations>>,
+    decisions: Arc<DecisionLog>,
+    latency_models: LatencyModels,
+    sleeper: Arc<dyn Sleeper>,
+}
+
+/// A logical region served next to the main listener
+pub struct Region {
+    pub name: String,
+    /// Prefix on the main listener, or `None` for a region on its own port
+    pub mount_path: Option<String>,
+    pub state: Arc<AppState>,
 }
 
 impl AppState {
     pub fn new(config: Config, stats: SharedStats) -> Self {
+        Self::with_latency_models(config, stats, LatencyModels::default())
+    }
+
+    /// State whose `[latency] model` (and each region's) is picked from
+    /// `latency_models`
+    pub fn with_latency_models(
+        config: Config,
+        stats: SharedStats,
+        latency_models: LatencyModels,
+    ) -> Self {
+        Self::build(config, stats, latency_models, default_sleeper())
+    }
+
+    /// State whose streams, on the main listener and every region, wait out
+    /// latency on `sleeper` (a calibrated `TokioSleeper`, say)
+    pub fn with_sleeper(config: Config, stats: SharedStats, sleeper: Arc<dyn Sleeper>) -> Self {
+        Self::build(config, stats, LatencyModels::default(), sleeper)
+    }
+
+    fn build(
+        config: Config,
+        stats: SharedStats,
+        latency_models: LatencyModels,
+        sleeper: Arc<dyn Sleeper>,
+    ) -> Self {
+        let learned = &config.learned;
+        let replay = learned.replay.as_ref().and_then(|path| {
+            LearnedProfiles::load(path)
+                .inspect_err(|e| tracing::warn!("Not replaying learned profiles {}: {}", path, e))
+                .ok()
+                .map(Arc::new)
+        });
+        let decisions = Arc::new(super::request_log::decision_log(&config.request_log));
+        let usage = config
+            .usage
+            .enabled
+            .then(|| UsageLedger::subscribe(&config.usage, &decisions));
+        let shared = Shared {
+            drills: Arc::new(Drills::new(config.regions.keys().cloned().collect())),
+            forced_errors: Arc::new(ForcedErrors::default()),
+            recorder: learned.record.as_ref().map(|path| {
+                Arc::new(Recorder::new(
+                    path,
+                    Duration::from_secs(learned.save_interval_secs),
+                ))
+            }),
+            replay,
+            files: Arc::new(FileStore::new(&config.files)),
+            responses: Arc::new(ResponseStore::new(&config.store)),
+            completions: Arc::new(CompletionStore::new(&config.store)),
+            usage,
+            keys: config
+                .keys
+                .enabled
+                .then(|| Arc::new(KeyStore::open(&config.keys))),
+            stream_logs: Arc::new(StreamLogs::new(config.stream.resumable_streams)),
+            tee: StreamTee::new(&config.stream.tee).map(Arc::new),
+            deprecations: Deprecations::new(&config.deprecations)
+                .inspect_err(|e| tracing::warn!("Not applying deprecations: {}", e))
+                .ok()
+                .filter(|deprecations| !deprecations.is_empty())
+                .map(Arc::new),
+            decisions,
+            latency_models,
+            sleeper,
+        };
+        Self::for_listener(config, stats, shared, None)
+    }
+
+    /// State for the main listener (`region: None`) or one region
+    fn for_listener(
+        config: Config,
+        stats: SharedStats,
+        shared: Shared,
+        region: Option<String>,
+    ) -> Self {
+        let mut pipeline = RequestPipeline::new(stats.clone())
+            .with_errors(config.error_config())
+            .with_tool_calls(config.tool_call_config())
+            .with_fingerprints(config.system_fingerprints())
+            .with_truncation_rate(config.response.truncation_rate)
+            .with_refusal_rate(config.response.refusal_rate)
+            .with_citation_rate(config.response.citation_rate)
+            .with_temperature_effects(config.response.temperature_effects)
+            .with_stop_hit_rate(config.response.stop_hit_rate)
+            .with_max_stream_duration(config.stream.max_duration())
+            .with_tokenizer(Arc::new(config.tokenizer()))
+            .with_sleeper(shared.sleeper.clone())
+            .with_stamps(config.stamps())
+            .with_time_scale(config.simulation.time_scale)
+            .with_model_latency(config.model_latencies())
+            .with_drills(shared.drills.clone(), region)
+            .with_decision_log(shared.decisions.clone())
+            .with_generator(Arc::from(create_generator(
+                &config.response.generator,
+                config.response.target_tokens,
+            )));
+        // Without an explicit profile, latency follows the requested model.
+        if config.simulation.disable_delays {
+            pipeline = pipeline.without_delays();
+        } else if config.latency.profile.is_some() || config.latency.ttft_mean_ms.is_some() {
+            pipeline = pipeline.with_latency(config.latency_profile());
+        }
+        if let Some(name) = config.latency.model.as_deref() {
+            match shared.latency_models.get(name) {
+                Some(_) if config.simulation.disable_delays => {}
+                Some(model) => pipeline = pipeline.with_latency_model(model),
+                None => tracing::warn!("Unknown latency model {:?}, using profiles", name),
+            }
+        }
+        if let Some(shedder) = config.load_shedder() {
+            pipeline = pipeline.with_load_shedding(shedder);
+        }
+        if let Some(replay) = &shared.replay {
+            pipeline = pipeline.with_learned_profiles(replay.clone());
+        }
+
+        let regions = config
+            .regions
+            .iter()
+            .filter_map(|(name, region)| {
+                let region_config = config.for_region(name)?;
+                let stats = Arc::new(Stats::with_rps_window(config.server.rps_window_secs));
+                Some(Region {
+                    name: name.clone(),
+                    mount_path: region.mount_path(name),
+                    state: Arc::new(AppState::for_listener(
+                        region_config,
+                        stats,
+                        shared.clone(),
+                        Some(name.clone()),
+                    )),
+                })
+            })
+            .collect();
+        let mirror = Mirror::from_config(&config).map(Arc::new);
+        let proxy = Proxy::from_config(&config).map(Arc::new);
+        let mcp = config.mcp_catalogs();
+        let error_rules = ErrorRules::new(&config.errors)
+            .inspect_err(|e| tracing::warn!("Not applying error rules: {}", e))
+            .ok()
+            .filter(|rules| !rules.is_empty())
+            .map(Arc::new);
+        let rate_limiter = config.rate_limiter().map(Arc::new);
+        let auxiliary = Auxiliary::from_config(&config).map(Arc::new);
+        let pool = config.pool.enabled.then(|| {
+            let pool = ResponsePool::generate(&pipeline, config.pool.size);
+            tracing::info!("Pre-generated {} pooled responses", pool.len());
+            Arc::new(pool)
+        });
+
         Self {
             config,
             stats,
+            pipeline,
             script: None,
+            regions,
+            drills: shared.drills,
+            forced_errors: shared.forced_errors,
+            mirror,
+            proxy,
+            recorder: shared.recorder,
+            files: shared.files,
+            responses: shared.responses,
+            completions: shared.completions,
+            usage: shared.usage,
+            keys: shared.keys,
+            stream_logs: shared.stream_logs,
+            tee: shared.tee,
+            mcp,
+            deprecations: shared.deprecations,
+            decisions: shared.decisions,
+            error_rules,
+            rate_limiter,
+            auxiliary,
+            pool,
         }
     }
 
@@ -27,4 +298,13 @@ impl AppState {
         self.script = Some(script);
         self
     }
+
+    /// Start a request on the pipeline, noting a script as what answers it
+    pub fn begin(&self, model: &str, streaming: bool, endpoint: EndpointType) -> PipelineRequest {
+        let req = self.pipeline.begin(model, streaming, endpoint);
+        if self.script.is_some() {
+            req.set_generator("script");
+        }
+        req
+    }
 }
@@ -0,0 +1,304 @@
+// Tee Module
+// Router middleware writing the exact bytes of selected SSE responses to
+// `[stream.tee] dir`, with the boundaries they were flushed at, as fixtures
+// for byte-level regression tests of client stream parsers.
+//
+// Decision: the body is captured as it is handed to hyper, after every layer
+// that shapes it, so each frame is one write and its boundaries are exactly
+// where the server flushed: `[stream] buffer_bytes` batching, split UTF-8
+// characters and malformed frames included. A capture is `<name>.sse`, the
+// bytes alone so a parser can read it like the wire, and `<name>.json` with
+// each frame's offset, size and time. Streams are held in memory until they
+// end or the client leaves, then written by one thread, so no request waits
+// on the disk; files are written whole and renamed into place, so a watcher
+// never reads half a capture. Streams are picked by the client's
+// `x-request-id` (named after it, the latest capture winning) or sampled at
+// random (named after when they began).
+
+use super::config::TeeConfig;
+use super::state::AppState;
+use crate::clock;
+use crate::glob::glob_match;
+use axum::{
+    body::{Body, Bytes, HttpBody},
+    extract::{Request, State},
+    http::header,
+    middleware::Next,
+    response::Response,
+};
+use http_body::{Frame, SizeHint};
+use rand::RngExt;
+use serde_json::json;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{channel, Sender};
+use std::sync::Arc;
+use std::task::{ready, Context, Poll};
+use std::time::{Duration, Instant};
+
+/// Request header naming a stream for `[stream.tee] request_ids`
+const REQUEST_ID_HEADER: &str = "x-request-id";
+/// Longest file name taken from a request id
+const MAX_NAME_CHARS: usize = 128;
+
+/// Picks streams to capture and writes them on a thread of its own
+pub struct StreamTee {
+    sample_rate: f64,
+    request_ids: Vec<String>,
+    sender: Sender<Capture>,
+    /// Captures named so far, keeping sampled names unique
+    captured: AtomicU64,
+}
+
+/// One stream's bytes as they were sent
+struct Capture {
+    name: String,
+    path: String,
+    request_id: Option<String>,
+    status: u16,
+    /// Each body frame with when it was sent, from the response's start
+    frames: Vec<(Duration, Bytes)>,
+    /// Whether the body ended, rather than the client leaving mid-stream
+    complete: bool,
+}
+
+impl StreamTee {
+    /// A tee writing to `config.dir`; `None` when it is unset or the
+    /// directory cannot be created
+    pub fn new(config: &TeeConfig) -> Option<Self> {
+        let dir = PathBuf::from(config.dir.as_ref()?);
+        if let Err(e) = std::fs::create_dir_all(&dir) {
+            tracing::warn!("Not capturing streams to {}: {}", dir.display(), e);
+            return None;
+        }
+        let (sender, receiver) = channel::<Capture>();
+        let spawned = std::thread::Builder::new()
+            .name("stream-tee".to_string())
+            .spawn(move || {
+                for capture in receiver {
+                    if let Err(e) = capture.write(&dir) {
+                        tracing::warn!("Not saving stream capture {}: {}", capture.name, e);
+                    }
+                }
+            });
+        if let Err(e) = spawned {
+            tracing::warn!("Not capturing streams: {}", e);
+            return None;
+        }
+        Some(Self {
+            sample_rate: config.sample_rate,
+            request_ids: config.request_ids.clone(),
+            sender,
+            captured: AtomicU64::new(0),
+        })
+    }
+
+    /// File name of the capture of a stream with `request_id`, or `None` if
+    /// it is not captured
+    fn select(&self, request_id: Option<&str>) -> Option<String> {
+        if let Some(id) = request_id {
+            if self.request_ids.iter().any(|glob| glob_match(glob, id)) {
+                return Some(file_name(id));
+            }
+        }
+        if self.sample_rate <= 0.0 || !rand::rng().random_bool(self.sample_rate.min(1.0)) {
+            return None;
+        }
+        let n = self.captured.fetch_add(1, Ordering::Relaxed) + 1;
+        Some(format!("stream-{}-{}", clock::unix_seconds(), n))
+    }
+}
+
+/// `id` with every character a file name could trip on replaced by `_`
+fn file_name(id: &str) -> String {
+    let name: String = id
+        .chars()
+        .take(MAX_NAME_CHARS)
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' => c,
+            _ => '_',
+        })
+        .collect();
+    if name.is_empty() {
+        "_".to_string()
+    } else {
+        name
+    }
+}
+
+impl Capture {
+    /// Write `<name>.sse` and `<name>.json` into `dir`
+    fn write(&self, dir: &Path) -> std::io::Result<()> {
+        let mut bytes = Vec::new();
+        let mut frames = Vec::new();
+        for (at, frame) in &self.frames {
+            frames.push(json!({
+                "offset": bytes.len(),
+                "bytes": frame.len(),
+                "at_ms": at.as_secs_f64() * 1000.0,
+            }));
+            bytes.extend_from_slice(frame);
+        }
+        let meta = json!({
+            "request_id": self.request_id,
+            "path": self.path,
+            "status": self.status,
+            "complete": self.complete,
+            "bytes": bytes.len(),
+            "frames": frames,
+        });
+        let meta = serde_json::to_vec_pretty(&meta).map_err(std::io::Error::other)?;
+        write_whole(&dir.join(format!("{}.sse", self.name)), &bytes)?;
+        write_whole(&dir.join(format!("{}.json", self.name)), &meta)
+    }
+}
+
+/// Write `contents`, replacing the file in one step
+fn write_whole(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    std::fs::write(&tmp, contents)?;
+    std::fs::rename(&tmp, path)
+}
+
+/// Middleware: capture the SSE responses `[stream.tee]` selects
+pub async fn tee_streams(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(tee) = state.tee.clone() else {
+        return next.run(request).await;
+    };
+    let path = request.uri().path().to_string();
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    let response = next.run(request).await;
+    let streaming = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("text/event-stream"));
+    if !streaming {
+        return response;
+    }
+    let Some(name) = tee.select(request_id.as_deref()) else {
+        return response;
+    };
+    let status = response.status().as_u16();
+    response.map(|body| {
+        Body::new(TeeBody {
+            inner: body,
+            started: clock::now(),
+            capture: Some(Capture {
+                name,
+                path,
+                request_id,
+                status,
+                frames: Vec::new(),
+                complete: false,
+            }),
+            sender: tee.sender.clone(),
+        })
+    })
+}
+
+/// A response body whose data frames are also kept for its capture, which
+/// is handed to the writer once the body ends or is dropped
+struct TeeBody {
+    inner: Body,
+    started: Instant,
+    capture: Option<Capture>,
+    sender: Sender<Capture>,
+}
+
+impl TeeBody {
+    fn finish(&mut self, complete: bool) {
+        if let Some(mut capture) = self.capture.take() {
+            capture.complete = complete;
+            let _ = self.sender.send(capture);
+        }
+    }
+}
+
+impl HttpBody for TeeBody {
+    type Data = Bytes;
+    type Error = axum::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, axum::Error>>> {
+        let this = self.get_mut();
+        let frame = ready!(Pin::new(&mut this.inner).poll_frame(cx));
+        match &frame {
+            Some(Ok(frame)) => {
+                if let (Some(capture), Some(data)) = (this.capture.as_mut(), frame.data_ref()) {
+                    capture
+                        .frames
+                        .push((clock::elapsed(this.started), data.clone()));
+                }
+            }
+            Some(Err(_)) => this.finish(false),
+            None => this.finish(true),
+        }
+        Poll::Ready(frame)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+impl Drop for TeeBody {
+    fn drop(&mut self) {
+        self.finish(false);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tee(sample_rate: f64, request_ids: &[&str]) -> StreamTee {
+        let (sender, _) = channel();
+        StreamTee {
+            sample_rate,
+            request_ids: request_ids.iter().map(|id| id.to_string()).collect(),
+            sender,
+            captured: AtomicU64::new(0),
+        }
+    }
+
+    #[test]
+    fn test_select() {
+        let tee = tee(0.0, &["fixture-*"]);
+        assert_eq!(
+            tee.select(Some("fixture-tool-calls")).as_deref(),
+            Some("fixture-tool-calls")
+        );
+        assert_eq!(tee.select(Some("other")), None);
+        assert_eq!(tee.select(None), None);
+
+        let tee = self::tee(1.0, &[]);
+        let first = tee.select(None).unwrap();
+        assert!(first.starts_with("stream-"));
+        assert_ne!(tee.select(Some("other")).unwrap(), first);
+    }
+
+    #[test]
+    fn test_file_name() {
+        assert_eq!(file_name("req_abc-123"), "req_abc-123");
+        assert_eq!(file_name("../../etc/passwd"), "______etc_passwd");
+        assert_eq!(file_name(""), "_");
+        assert_eq!(file_name(&"x".repeat(500)).len(), MAX_NAME_CHARS);
+    }
+}
@@ -0,0 +1,253 @@
+// Traffic Module
+// `[traffic]`: synthetic requests the server sends itself, at a rate that
+// follows a daily curve, so dashboards, the TUI and alerting pipelines can be
+// exercised without an external load tool.
+//
+// Decision: requests go over TCP to the server's own listener with the
+// `[upstreams]` client rather than into the router directly, so they pass
+// through every middleware, show up in stats, request logs and per-key
+// budgets exactly as outside traffic does, and hold real connections open
+// while they stream. Arrivals are Poisson at the rate of the moment: the
+// generator waits an exponential gap, and a gap longer than a second is cut
+// short without sending anything, which (arrivals being memoryless) keeps
+// the rate exact while letting it follow the curve from one second to the
+// next.
+
+use super::config::{TrafficConfig, UpstreamConfig};
+use super::proxy::endpoint_type;
+use super::upstream::Upstream;
+use crate::stats::EndpointType;
+use axum::http::{header, HeaderMap, HeaderValue};
+use rand::RngExt;
+use serde_json::{json, Value};
+use std::f64::consts::PI;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+const DAY_SECS: f64 = 86_400.0;
+/// Longest wait before the rate is looked at again
+const MAX_WAIT: Duration = Duration::from_secs(1);
+/// How long a generated request may take, streaming included
+const REQUEST_TIMEOUT_SECS: u64 = 300;
+
+const PROMPTS: [&str; 6] = [
+    "Summarize the plot of a heist movie in three sentences.",
+    "Write a haiku about distributed systems.",
+    "Explain the difference between TCP and UDP to a new engineer.",
+    "Draft a polite reply declining a meeting invitation.",
+    "List five tips for writing readable Rust code.",
+    "What should I pack for a weekend hiking trip?",
+];
+
+/// Check the settings the TOML types cannot express
+pub(super) fn validate(config: &TrafficConfig) -> Result<(), String> {
+    if config.trough_rps < 0.0 || config.peak_rps < config.trough_rps {
+        return Err("peak_rps must be at least trough_rps, and both at least 0".to_string());
+    }
+    parse_time_of_day(&config.peak_at)
+        .ok_or_else(|| format!("peak_at '{}' is not a time of day (HH:MM)", config.peak_at))?;
+    for (time, rps) in &config.schedule {
+        parse_time_of_day(time)
+            .ok_or_else(|| format!("schedule time '{}' is not a time of day (HH:MM)", time))?;
+        if *rps < 0.0 {
+            return Err(format!("schedule rate at {} must be at least 0", time));
+        }
+    }
+    if config.day_secs == 0 {
+        return Err("day_secs must be at least 1".to_string());
+    }
+    if let Some(path) = config.paths.iter().find(|p| endpoint_type(p).is_none()) {
+        return Err(format!("'{}' is not a generation endpoint", path));
+    }
+    if config.paths.is_empty() || config.models.is_empty() {
+        return Err("paths and models must not be empty".to_string());
+    }
+    if !(0.0..=1.0).contains(&config.stream_share) {
+        return Err("stream_share must be between 0.0 and 1.0".to_string());
+    }
+    Ok(())
+}
+
+/// Seconds since midnight of `HH:MM`
+fn parse_time_of_day(text: &str) -> Option<f64> {
+    let (hours, minutes) = text.split_once(':')?;
+    let (hours, minutes): (u32, u32) = (hours.parse().ok()?, minutes.parse().ok()?);
+    (hours < 24 && minutes < 60).then(|| f64::from(hours * 3600 + minutes * 60))
+}
+
+/// Requests per second the curve asks for `secs` after midnight
+fn rate_at(config: &TrafficConfig, secs: f64) -> f64 {
+    if config.schedule.is_empty() {
+        let peak = parse_time_of_day(&config.peak_at).unwrap_or(0.0);
+        let phase = 2.0 * PI * (secs - peak) / DAY_SECS;
+        let swing = (1.0 + phase.cos()) / 2.0;
+        return config.trough_rps + (config.peak_rps - config.trough_rps) * swing;
+    }
+    let mut points: Vec<(f64, f64)> = config
+        .schedule
+        .iter()
+        .filter_map(|(time, rps)| Some((parse_time_of_day(time)?, *rps)))
+        .collect();
+    points.sort_by(|a, b| a.0.total_cmp(&b.0));
+    // The last point of one day runs into the first of the next
+    let (first, last) = (points[0], points[points.len() - 1]);
+    let before = points.iter().rev().find(|p| p.0 <= secs);
+    let after = points.iter().find(|p| p.0 > secs);
+    let (from, to) = match (before, after) {
+        (Some(from), Some(to)) => (*from, *to),
+        (Some(from), None) => (*from, (first.0 + DAY_SECS, first.1)),
+        (None, Some(to)) => ((last.0 - DAY_SECS, last.1), *to),
+        (None, None) => unreachable!("schedule has at least one point"),
+    };
+    from.1 + (to.1 - from.1) * (secs - from.0) / (to.0 - from.0)
+}
+
+/// Seconds into the simulated day, which runs `86400 / day_secs` times
+/// faster than the real one
+fn time_of_day(config: &TrafficConfig) -> f64 {
+    let now = crate::clock::system_now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs_f64();
+    (now * DAY_SECS / config.day_secs as f64) % DAY_SECS
+}
+
+/// Send requests to the server at `addr` until the process exits
+pub async fn generate(config: TrafficConfig, addr: SocketAddr) {
+    let host = if addr.ip().is_unspecified() {
+        "127.0.0.1".to_string()
+    } else {
+        addr.ip().to_string()
+    };
+    let base_url = format!("http://{}:{}", host, addr.port());
+    loop {
+        let rate = rate_at(&config, time_of_day(&config));
+        let gap = if rate > 0.0 {
+            let uniform: f64 = rand::rng().random();
+            Duration::from_secs_f64(-(1.0 - uniform).ln() / rate)
+        } else {
+            Duration::MAX
+        };
+        if gap > MAX_WAIT {
+            tokio::time::sleep(MAX_WAIT).await;
+            continue;
+        }
+        tokio::time::sleep(gap).await;
+        tokio::spawn(send_one(config.clone(), base_url.clone()));
+    }
+}
+
+async fn send_one(config: TrafficConfig, base_url: String) {
+    let (path, body) = pick_request(&config);
+    let provider = path.trim_start_matches('/').split('/').next().unwrap_or("");
+    let upstream = Upstream::new(
+        provider,
+        &UpstreamConfig {
+            base_url,
+            api_key: Some(config.api_key.clone()),
+            timeout_secs: REQUEST_TIMEOUT_SECS,
+        },
+    );
+    let Ok(upstream) = upstream else {
+        return;
+    };
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/json"),
+    );
+    if let Err(e) = upstream
+        .send("POST", path, &headers, body.to_string().as_bytes())
+        .await
+    {
+        tracing::debug!("Synthetic request to {} failed: {}", path, e);
+    }
+}
+
+/// A path and a body for it, picked at random
+fn pick_request(config: &TrafficConfig) -> (&str, Value) {
+    let mut rng = rand::rng();
+    let path = &config.paths[rng.random_range(0..config.paths.len())];
+    let model = &config.models[rng.random_range(0..config.models.len())];
+    let stream = rng.random_bool(config.stream_share);
+    let prompt = PROMPTS[rng.random_range(0..PROMPTS.len())];
+    // Validation lets only generation endpoints through
+    let endpoint = endpoint_type(path).unwrap_or(EndpointType::ChatCompletions);
+    (path, request_body(endpoint, model, prompt, stream))
+}
+
+fn request_body(endpoint: EndpointType, model: &str, prompt: &str, stream: bool) -> Value {
+    match endpoint {
+        EndpointType::Responses | EndpointType::WebSocketResponses => json!({
+            "model": model,
+            "input": prompt,
+            "stream": stream
+        }),
+        EndpointType::Messages => json!({
+            "model": model,
+            "max_tokens": 1024,
+            "messages": [{"role": "user", "content": prompt}],
+            "stream": stream
+        }),
+        EndpointType::Images => json!({
+            "model": "gpt-image-1",
+            "prompt": prompt
+        }),
+        _ => json!({
+            "model": model,
+            "messages": [{"role": "user", "content": prompt}],
+            "stream": stream
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sine_curve() {
+        let config = TrafficConfig::default();
+        let peak = parse_time_of_day("14:00").unwrap();
+        assert!((rate_at(&config, peak) - 5.0).abs() < 1e-9);
+        assert!((rate_at(&config, peak - DAY_SECS / 2.0) - 0.5).abs() < 1e-9);
+        // Halfway between, either side
+        let middle = rate_at(&config, peak + DAY_SECS / 4.0);
+        assert!((middle - 2.75).abs() < 1e-9);
+        assert!((rate_at(&config, peak - DAY_SECS / 4.0) - middle).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_schedule_wraps_at_midnight() {
+        let config = TrafficConfig {
+            schedule: [("06:00", 0.0), ("12:00", 6.0), ("18:00", 0.0)]
+                .into_iter()
+                .map(|(time, rps)| (time.to_string(), rps))
+                .collect(),
+            ..TrafficConfig::default()
+        };
+        assert_eq!(rate_at(&config, 9.0 * 3600.0), 3.0);
+        assert_eq!(rate_at(&config, 12.0 * 3600.0), 6.0);
+        // 18:00 to 06:00 the next day stays at zero
+        assert_eq!(rate_at(&config, 23.0 * 3600.0), 0.0);
+        assert_eq!(rate_at(&config, 2.0 * 3600.0), 0.0);
+    }
+
+    #[test]
+    fn test_parse_time_of_day() {
+        assert_eq!(parse_time_of_day("00:00"), Some(0.0));
+        assert_eq!(parse_time_of_day("23:59"), Some(86_340.0));
+        assert_eq!(parse_time_of_day("24:00"), None);
+        assert_eq!(parse_time_of_day("noon"), None);
+    }
+
+    #[test]
+    fn test_request_bodies() {
+        let body = request_body(EndpointType::Messages, "claude-sonnet-4", "Hi", true);
+        assert_eq!(body["max_tokens"], 1024);
+        assert_eq!(body["stream"], true);
+        let body = request_body(EndpointType::Images, "gpt-5", "Hi", true);
+        assert_eq!(body["model"], "gpt-image-1");
+        assert!(body.get("stream").is_none());
+    }
+}
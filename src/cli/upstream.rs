@@ -0,0 +1,480 @@
+// Upstream Client Module
+// An HTTP client for forwarding requests to a real provider API, over HTTPS
+// with the `tls` feature.
+//
+// Decision: hyper-util's pooled client rather than one of our own, so
+// keep-alive, chunked bodies, HTTP/2 and 1xx interim responses are handled
+// the way the rest of the hyper stack handles them. TLS (rustls with the
+// webpki roots) is behind the `tls` feature, on by default, so library users
+// who never forward anything keep a small dependency tree; without it an
+// `https://` base URL is refused when the config is checked. A response read
+// whole (mirrored, verified) is capped at `MAX_RESPONSE_BYTES`, as it is held
+// in memory; a relayed stream is passed on as it arrives and is not.
+
+use super::config::{Config, UpstreamConfig};
+use crate::learned::ChunkTiming;
+use axum::body::Bytes;
+use axum::http::{header, HeaderMap, HeaderName, Method, Request};
+use futures_util::{Stream, StreamExt};
+use http_body_util::{BodyExt, Full};
+use hyper::body::Incoming;
+use hyper_util::client::legacy::connect::HttpConnector;
+use hyper_util::rt::TokioExecutor;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Largest upstream response body read into memory
+const MAX_RESPONSE_BYTES: usize = 64 * 1024 * 1024;
+
+#[cfg(feature = "tls")]
+type Connector = hyper_rustls::HttpsConnector<HttpConnector>;
+#[cfg(not(feature = "tls"))]
+type Connector = HttpConnector;
+type Client = hyper_util::client::legacy::Client<Connector, Full<Bytes>>;
+
+/// Upstream failures, reported in stats rather than to clients
+#[derive(Debug, thiserror::Error, PartialEq)]
+pub enum UpstreamError {
+    #[error("invalid upstream URL '{0}': expected http(s)://host[:port][/path]")]
+    InvalidUrl(String),
+    #[error("upstream URL '{0}' needs TLS, which this build leaves out (the `tls` feature)")]
+    TlsUnavailable(String),
+    #[error("connection to {0} failed: {1}")]
+    Connect(String, String),
+    #[error("upstream I/O error: {0}")]
+    Io(String),
+    #[error("upstream did not answer within {0:?}")]
+    Timeout(Duration),
+    #[error("upstream response is larger than {0} bytes")]
+    TooLarge(usize),
+}
+
+/// A buffered upstream response
+#[derive(Debug, Clone)]
+pub struct UpstreamResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+    /// When the body chunks arrived, for learning latency
+    pub timing: ChunkTiming,
+}
+
+impl UpstreamResponse {
+    pub fn is_event_stream(&self) -> bool {
+        is_event_stream(&self.headers)
+    }
+}
+
+/// An upstream response whose body is still being received
+pub struct UpstreamStream {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    body: Incoming,
+}
+
+impl UpstreamStream {
+    pub fn is_event_stream(&self) -> bool {
+        is_event_stream(&self.headers)
+    }
+
+    /// The decoded body, chunk by chunk as it arrives
+    pub fn into_body(self) -> impl Stream<Item = Result<Bytes, UpstreamError>> + Send {
+        self.body
+            .into_data_stream()
+            .map(|chunk| chunk.map_err(|e| UpstreamError::Io(describe(&e))))
+    }
+}
+
+/// `base_url` of an `[upstreams]` entry
+#[derive(Debug, Clone, PartialEq)]
+pub struct BaseUrl {
+    pub https: bool,
+    pub host: String,
+    pub port: u16,
+    /// Prefix of every request path, without a trailing slash
+    pub path: String,
+}
+
+/// One provider's real API
+#[derive(Debug, Clone)]
+pub struct Upstream {
+    base: BaseUrl,
+    auth: Option<(HeaderName, String)>,
+    timeout: Duration,
+    max_response_bytes: usize,
+    client: Client,
+}
+
+/// Headers that describe the client's connection rather than the request
+const HOP_BY_HOP: [&str; 8] = [
+    "host",
+    "connection",
+    "content-length",
+    "transfer-encoding",
+    "accept-encoding",
+    "keep-alive",
+    "upgrade",
+    "te",
+];
+
+impl Upstream {
+    /// Upstream for `provider` (a route prefix such as `openai`). A
+    /// configured key replaces the client's own credentials, as `x-api-key`
+    /// for Anthropic and a bearer token otherwise.
+    pub fn new(provider: &str, config: &UpstreamConfig) -> Result<Self, UpstreamError> {
+        let base = parse_base_url(&config.base_url)?;
+        let auth = config.api_key.as_ref().map(|key| {
+            if provider == "anthropic" {
+                (HeaderName::from_static("x-api-key"), key.clone())
+            } else {
+                (header::AUTHORIZATION, format!("Bearer {}", key))
+            }
+        });
+        Ok(Self {
+            base,
+            auth,
+            timeout: Duration::from_secs(config.timeout_secs),
+            max_response_bytes: MAX_RESPONSE_BYTES,
+            client: hyper_util::client::legacy::Client::builder(TokioExecutor::new())
+                .build(connector()),
+        })
+    }
+
+    /// `host:port` requests are sent to
+    pub fn authority(&self) -> String {
+        format!("{}:{}", self.base.host, self.base.port)
+    }
+
+    /// Send a request and read the whole response. `path` is appended to the
+    /// base URL's path.
+    pub async fn send(
+        &self,
+        method: &str,
+        path: &str,
+        headers: &HeaderMap,
+        body: &[u8],
+    ) -> Result<UpstreamResponse, UpstreamError> {
+        let request = self.request(method, path, headers, body)?;
+        let mut timing = ChunkTiming::new(Instant::now());
+        let exchange = async {
+            let response = self.exchange(request).await?;
+            let (status, headers) = (response.status, response.headers.clone());
+            let mut chunks = std::pin::pin!(response.into_body());
+            let mut body = Vec::new();
+            while let Some(chunk) = chunks.next().await {
+                let chunk = chunk?;
+                if body.len() + chunk.len() > self.max_response_bytes {
+                    return Err(UpstreamError::TooLarge(self.max_response_bytes));
+                }
+                body.extend_from_slice(&chunk);
+                timing.chunk();
+            }
+            Ok(UpstreamResponse {
+                status,
+                headers,
+                body,
+                timing,
+            })
+        };
+        tokio::time::timeout(self.timeout, exchange)
+            .await
+            .map_err(|_| UpstreamError::Timeout(self.timeout))?
+    }
+
+    /// Send a request and return once the response headers are in; the body
+    /// is read as the returned stream is polled. The timeout covers the wait
+    /// for the headers only, so long streams are never cut off.
+    pub async fn open(
+        &self,
+        method: &str,
+        path: &str,
+        headers: &HeaderMap,
+        body: &[u8],
+    ) -> Result<UpstreamStream, UpstreamError> {
+        let request = self.request(method, path, headers, body)?;
+        tokio::time::timeout(self.timeout, self.exchange(request))
+            .await
+            .map_err(|_| UpstreamError::Timeout(self.timeout))?
+    }
+
+    async fn exchange(
+        &self,
+        request: Request<Full<Bytes>>,
+    ) -> Result<UpstreamStream, UpstreamError> {
+        let response = self.client.request(request).await.map_err(|e| {
+            if e.is_connect() {
+                UpstreamError::Connect(self.authority(), describe(&e))
+            } else {
+                UpstreamError::Io(describe(&e))
+            }
+        })?;
+        let (parts, body) = response.into_parts();
+        let headers = parts
+            .headers
+            .iter()
+            .map(|(name, value)| {
+                (
+                    name.to_string(),
+                    String::from_utf8_lossy(value.as_bytes()).into_owned(),
+                )
+            })
+            .collect();
+        Ok(UpstreamStream {
+            status: parts.status.as_u16(),
+            headers,
+            body,
+        })
+    }
+
+    /// The request to send upstream: the client's headers without its
+    /// connection's and, with a configured key, without its credentials
+    fn request(
+        &self,
+        method: &str,
+        path: &str,
+        headers: &HeaderMap,
+        body: &[u8],
+    ) -> Result<Request<Full<Bytes>>, UpstreamError> {
+        let scheme = if self.base.https { "https" } else { "http" };
+        let uri = format!(
+            "{}://{}{}{}",
+            scheme,
+            self.authority(),
+            self.base.path,
+            path
+        );
+        let invalid = || UpstreamError::InvalidUrl(uri.clone());
+        let mut request = Request::builder()
+            .method(Method::from_bytes(method.as_bytes()).map_err(|_| invalid())?)
+            .uri(&uri);
+        for (name, value) in headers {
+            let replaced = self.auth.is_some()
+                && (name == header::AUTHORIZATION || name.as_str() == "x-api-key");
+            if !replaced && !HOP_BY_HOP.contains(&name.as_str()) {
+                request = request.header(name, value);
+            }
+        }
+        if let Some((name, value)) = &self.auth {
+            request = request.header(name, value);
+        }
+        request
+            .body(Full::new(Bytes::copy_from_slice(body)))
+            .map_err(|_| invalid())
+    }
+}
+
+/// Connector for `http://` and, with the `tls` feature, `https://` URLs
+#[cfg(feature = "tls")]
+fn connector() -> Connector {
+    let mut http = HttpConnector::new();
+    http.enforce_http(false);
+    hyper_rustls::HttpsConnectorBuilder::new()
+        .with_provider_and_webpki_roots(rustls::crypto::ring::default_provider())
+        .expect("ring supports the default TLS versions")
+        .https_or_http()
+        .enable_http1()
+        .enable_http2()
+        .wrap_connector(http)
+}
+
+/// Connector for `http://` URLs
+#[cfg(not(feature = "tls"))]
+fn connector() -> Connector {
+    HttpConnector::new()
+}
+
+/// An error with its causes, which hyper keeps out of its own message
+fn describe(error: &dyn std::error::Error) -> String {
+    let mut text = error.to_string();
+    let mut source = error.source();
+    while let Some(cause) = source {
+        text.push_str(": ");
+        text.push_str(&cause.to_string());
+        source = cause.source();
+    }
+    text
+}
+
+/// Every valid `[upstreams]` entry, keyed by provider route prefix
+pub fn from_config(config: &Config) -> BTreeMap<String, Arc<Upstream>> {
+    config
+        .upstreams
+        .iter()
+        .filter_map(
+            |(provider, upstream)| match Upstream::new(provider, upstream) {
+                Ok(u) => Some((provider.clone(), Arc::new(u))),
+                Err(e) => {
+                    tracing::warn!("Ignoring [upstreams.{}]: {}", provider, e);
+                    None
+                }
+            },
+        )
+        .collect()
+}
+
+/// Upstream for a request path and the path to send it to: the provider
+/// prefix is dropped, `/openai/v1/...` becomes `/v1/...`
+pub fn route<'a>(
+    upstreams: &BTreeMap<String, Arc<Upstream>>,
+    path: &'a str,
+) -> Option<(Arc<Upstream>, &'a str)> {
+    let rest = path.strip_prefix('/')?;
+    let (provider, rest) = rest.split_at(rest.find('/')?);
+    Some((upstreams.get(provider)?.clone(), rest))
+}
+
+/// Split `http(s)://host[:port][/path]` into its parts; the path keeps no
+/// trailing slash
+pub fn parse_base_url(url: &str) -> Result<BaseUrl, UpstreamError> {
+    let invalid = || UpstreamError::InvalidUrl(url.to_string());
+    let (https, rest) = match url.strip_prefix("https://") {
+        Some(rest) => (true, rest),
+        None => (false, url.strip_prefix("http://").ok_or_else(invalid)?),
+    };
+    if https && cfg!(not(feature = "tls")) {
+        return Err(UpstreamError::TlsUnavailable(url.to_string()));
+    }
+    let (authority, path) = match rest.find('/') {
+        Some(i) => (&rest[..i], rest[i..].trim_end_matches('/')),
+        None => (rest, ""),
+    };
+    let (host, port) = match authority.rsplit_once(':') {
+        Some((host, port)) => (host, port.parse().map_err(|_| invalid())?),
+        None => (authority, if https { 443 } else { 80 }),
+    };
+    if host.is_empty() {
+        return Err(invalid());
+    }
+    Ok(BaseUrl {
+        https,
+        host: host.to_string(),
+        port,
+        path: path.to_string(),
+    })
+}
+
+fn is_event_stream(headers: &[(String, String)]) -> bool {
+    find_header(headers, "content-type").is_some_and(|ct| ct.starts_with("text/event-stream"))
+}
+
+fn find_header<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(n, _)| n.eq_ignore_ascii_case(name))
+        .map(|(_, v)| v.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    fn config(base_url: &str) -> UpstreamConfig {
+        UpstreamConfig {
+            base_url: base_url.to_string(),
+            api_key: Some("sk-real".to_string()),
+            timeout_secs: 5,
+        }
+    }
+
+    /// A server on a local port answering one connection with `response`
+    async fn answer_once(response: &'static [u8]) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = [0u8; 4096];
+            let _ = socket.read(&mut buf).await;
+            socket.write_all(response).await.unwrap();
+        });
+        format!("http://{}", addr)
+    }
+
+    #[test]
+    fn test_parse_base_url() {
+        let base = parse_base_url("http://gateway.internal:8443/openai/").unwrap();
+        assert_eq!(
+            (
+                base.https,
+                base.host.as_str(),
+                base.port,
+                base.path.as_str()
+            ),
+            (false, "gateway.internal", 8443, "/openai")
+        );
+        let base = parse_base_url("http://localhost").unwrap();
+        assert_eq!((base.port, base.path.as_str()), (80, ""));
+        assert!(parse_base_url("ftp://host").is_err());
+        assert!(parse_base_url("http://host:port").is_err());
+        assert!(parse_base_url("http:///v1").is_err());
+    }
+
+    #[cfg(feature = "tls")]
+    #[test]
+    fn test_parse_https_base_url() {
+        let base = parse_base_url("https://api.openai.com").unwrap();
+        assert_eq!(
+            (base.https, base.host.as_str(), base.port),
+            (true, "api.openai.com", 443)
+        );
+    }
+
+    #[test]
+    fn test_request_replaces_credentials() {
+        let upstream = Upstream::new("openai", &config("http://gateway:9000/proxy")).unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert(header::AUTHORIZATION, "Bearer sk-sim".parse().unwrap());
+        headers.insert(header::HOST, "localhost:8080".parse().unwrap());
+        headers.insert("openai-beta", "assistants=v2".parse().unwrap());
+        let request = upstream
+            .request("POST", "/v1/chat/completions", &headers, b"{}")
+            .unwrap();
+
+        assert_eq!(request.method(), Method::POST);
+        assert_eq!(
+            request.uri().to_string(),
+            "http://gateway:9000/proxy/v1/chat/completions"
+        );
+        assert_eq!(request.headers()[header::AUTHORIZATION], "Bearer sk-real");
+        assert_eq!(request.headers()["openai-beta"], "assistants=v2");
+        assert!(request.headers().get(header::HOST).is_none());
+
+        let anthropic = Upstream::new("anthropic", &config("http://gateway:9000")).unwrap();
+        let request = anthropic
+            .request("POST", "/v1/messages", &HeaderMap::new(), b"")
+            .unwrap();
+        assert_eq!(request.headers()["x-api-key"], "sk-real");
+    }
+
+    #[tokio::test]
+    async fn test_interim_responses_are_skipped() {
+        let url = answer_once(
+            b"HTTP/1.1 100 Continue\r\n\r\n\
+              HTTP/1.1 200 OK\r\ncontent-type: text/event-stream\r\n\
+              transfer-encoding: chunked\r\n\r\n\
+              6\r\ndata: \r\n4\r\n{}\n\n\r\n0\r\n\r\n",
+        )
+        .await;
+        let upstream = Upstream::new("openai", &config(&url)).unwrap();
+        let response = upstream
+            .send("POST", "/v1/chat/completions", &HeaderMap::new(), b"{}")
+            .await
+            .unwrap();
+        assert_eq!(response.status, 200);
+        assert!(response.is_event_stream());
+        assert_eq!(response.body, b"data: {}\n\n");
+    }
+
+    #[tokio::test]
+    async fn test_response_size_is_capped() {
+        let url =
+            answer_once(b"HTTP/1.1 200 OK\r\ncontent-length: 16\r\n\r\n0123456789abcdef").await;
+        let mut upstream = Upstream::new("openai", &config(&url)).unwrap();
+        upstream.max_response_bytes = 8;
+        let result = upstream
+            .send("GET", "/v1/models", &HeaderMap::new(), b"")
+            .await;
+        assert_eq!(result.unwrap_err(), UpstreamError::TooLarge(8));
+    }
+}
@@ -0,0 +1,529 @@
+// Usage Module
+// `[usage]`: the simulator's own traffic reported through the shapes of
+// OpenAI's organization usage and costs APIs
+// (`/openai/v1/organization/usage/completions`,
+// `/openai/v1/organization/costs`), so billing reconciliation jobs have a
+// test double whose figures match the requests they sent.
+//
+// Decision: usage is taken from the decision log, like the request log, so
+// every way a request can complete (streamed, pooled, scripted, over gRPC or
+// WebSocket) is counted by the one place that already tracks it. A thread
+// drains its subscription into per-minute totals by model, so memory grows
+// with the minutes and models served rather than with the requests, and
+// buckets of any width can be cut from them however `start_time` is
+// aligned. Costs are priced when asked for, with the same prices as the
+// cost header. The simulator has no API keys or users of its own: grouping
+// by them is accepted and reports them as null, and all usage belongs to
+// the one `project_id`.
+
+use super::completions_store::percent_decode;
+use super::config::{Config, UsageConfig};
+use crate::decisions::{Decision, DecisionLog, Outcome};
+use crate::stats::EndpointType;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::UNIX_EPOCH;
+
+const MINUTE_SECS: i64 = 60;
+
+/// Token totals of one model in one minute
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct Totals {
+    input_tokens: u64,
+    output_tokens: u64,
+    requests: u64,
+}
+
+impl Totals {
+    fn add(&mut self, other: &Totals) {
+        self.input_tokens += other.input_tokens;
+        self.output_tokens += other.output_tokens;
+        self.requests += other.requests;
+    }
+}
+
+/// Completed requests' tokens by minute and model
+pub struct UsageLedger {
+    project_id: String,
+    /// Start of the minute (Unix seconds), then model
+    minutes: Mutex<BTreeMap<i64, HashMap<String, Totals>>>,
+}
+
+impl UsageLedger {
+    pub fn new(config: &UsageConfig) -> Self {
+        Self {
+            project_id: config.project_id.clone(),
+            minutes: Mutex::default(),
+        }
+    }
+
+    /// A ledger counting every request `decisions` publishes from now on
+    pub fn subscribe(config: &UsageConfig, decisions: &DecisionLog) -> Arc<Self> {
+        let ledger = Arc::new(Self::new(config));
+        let receiver = decisions.subscribe();
+        let counting = ledger.clone();
+        let spawned = std::thread::Builder::new()
+            .name("usage-ledger".to_string())
+            .spawn(move || {
+                for decision in receiver {
+                    counting.add(&decision);
+                }
+            });
+        if let Err(e) = spawned {
+            tracing::warn!("Not recording usage: {}", e);
+        }
+        ledger
+    }
+
+    /// Count `decision` if it completed a text generation
+    pub fn add(&self, decision: &Decision) {
+        let Outcome::Completed {
+            input_tokens,
+            output_tokens,
+        } = decision.outcome
+        else {
+            return;
+        };
+        if decision.endpoint == EndpointType::Images {
+            return;
+        }
+        let secs = decision
+            .started_at
+            .duration_since(UNIX_EPOCH)
+            .map(|since| since.as_secs() as i64)
+            .unwrap_or_default();
+        let totals = Totals {
+            input_tokens: input_tokens as u64,
+            output_tokens: output_tokens as u64,
+            requests: 1,
+        };
+        self.minutes
+            .lock()
+            .unwrap()
+            .entry(secs - secs.rem_euclid(MINUTE_SECS))
+            .or_default()
+            .entry(decision.model.clone())
+            .or_default()
+            .add(&totals);
+    }
+
+    /// Totals by model between `start` and `end` (Unix seconds, end
+    /// excluded)
+    fn totals(&self, start: i64, end: i64) -> BTreeMap<String, Totals> {
+        let minutes = self.minutes.lock().unwrap();
+        let mut totals: BTreeMap<String, Totals> = BTreeMap::new();
+        for models in minutes.range(start..end).map(|(_, models)| models) {
+            for (model, minute) in models {
+                totals.entry(model.clone()).or_default().add(minute);
+            }
+        }
+        totals
+    }
+
+    /// A page of `GET /openai/v1/organization/usage/completions`
+    pub fn completions(&self, query: &UsageQuery) -> Value {
+        page(query, |start, end| {
+            let mut groups: BTreeMap<Vec<Option<String>>, Totals> = BTreeMap::new();
+            for (model, totals) in self.totals(start, end) {
+                if !query.includes_model(&model) || !query.includes_project(&self.project_id) {
+                    continue;
+                }
+                let key = vec![
+                    query.grouped("model").then(|| model.clone()),
+                    query.grouped("project_id").then(|| self.project_id.clone()),
+                ];
+                groups.entry(key).or_default().add(&totals);
+            }
+            groups
+                .into_iter()
+                .map(|(key, totals)| {
+                    json!({
+                        "object": "organization.usage.completions.result",
+                        "input_tokens": totals.input_tokens,
+                        "output_tokens": totals.output_tokens,
+                        "input_cached_tokens": 0,
+                        "input_audio_tokens": 0,
+                        "output_audio_tokens": 0,
+                        "num_model_requests": totals.requests,
+                        "project_id": key[1],
+                        "user_id": null,
+                        "api_key_id": null,
+                        "model": key[0],
+                        "batch": query.grouped("batch").then_some(false),
+                    })
+                })
+                .collect()
+        })
+    }
+
+    /// A page of `GET /openai/v1/organization/costs`, priced by `config`
+    pub fn costs(&self, query: &UsageQuery, config: &Config) -> Value {
+        page(query, |start, end| {
+            let mut groups: BTreeMap<Vec<Option<String>>, f64> = BTreeMap::new();
+            for (model, totals) in self.totals(start, end) {
+                if !query.includes_project(&self.project_id) {
+                    continue;
+                }
+                let Some(price) = config.model_price(&model) else {
+                    continue;
+                };
+                let project = query.grouped("project_id").then(|| self.project_id.clone());
+                let items = [
+                    ("input", totals.input_tokens as f64 * price.input_per_mtok),
+                    (
+                        "output",
+                        totals.output_tokens as f64 * price.output_per_mtok,
+                    ),
+                ];
+                for (item, cost) in items {
+                    let line_item = query
+                        .grouped("line_item")
+                        .then(|| format!("{}, {}", model, item));
+                    *groups.entry(vec![line_item, project.clone()]).or_default() +=
+                        cost / 1_000_000.0;
+                }
+            }
+            groups
+                .into_iter()
+                .map(|(key, usd)| {
+                    json!({
+                        "object": "organization.costs.result",
+                        "amount": {"value": usd, "currency": "usd"},
+                        "line_item": key[0],
+                        "project_id": key[1],
+                    })
+                })
+                .collect()
+        })
+    }
+}
+
+/// Width of the buckets usage is reported in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BucketWidth {
+    Minute,
+    Hour,
+    Day,
+}
+
+impl BucketWidth {
+    fn parse(text: &str) -> Option<Self> {
+        match text {
+            "1m" => Some(Self::Minute),
+            "1h" => Some(Self::Hour),
+            "1d" => Some(Self::Day),
+            _ => None,
+        }
+    }
+
+    fn secs(self) -> i64 {
+        match self {
+            Self::Minute => 60,
+            Self::Hour => 3_600,
+            Self::Day => 86_400,
+        }
+    }
+
+    /// Buckets on a page when `limit` is not given, and the most allowed
+    fn limits(self) -> (usize, usize) {
+        match self {
+            Self::Minute => (60, 1_440),
+            Self::Hour => (24, 168),
+            Self::Day => (7, 31),
+        }
+    }
+}
+
+/// Which endpoint a query is for, as their parameters differ
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UsageEndpoint {
+    Completions,
+    Costs,
+}
+
+/// Parameters of a usage or costs request
+#[derive(Debug, Clone, PartialEq)]
+pub struct UsageQuery {
+    /// Start of the first bucket (Unix seconds)
+    pub start_time: i64,
+    /// End of the last bucket, excluded; now when not given
+    pub end_time: i64,
+    pub bucket_width: BucketWidth,
+    pub group_by: Vec<String>,
+    /// Only these models, when not empty
+    pub models: Vec<String>,
+    /// Only these projects, when not empty
+    pub project_ids: Vec<String>,
+    pub limit: usize,
+    /// Start of the bucket to page from, from a `next_page` cursor
+    pub page: Option<i64>,
+}
+
+impl UsageQuery {
+    /// Parameters from a query string; arrays may be repeated, bracketed
+    /// (`models[]=`) or comma-separated
+    pub fn from_query(query: Option<&str>, endpoint: UsageEndpoint) -> Result<Self, String> {
+        let mut params: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        let pairs = query
+            .into_iter()
+            .flat_map(|q| q.split('&'))
+            .filter_map(|pair| pair.split_once('='));
+        for (name, value) in pairs {
+            let name = percent_decode(name);
+            let values = params
+                .entry(name.trim_end_matches("[]").to_string())
+                .or_default();
+            values.extend(
+                percent_decode(value)
+                    .split(',')
+                    .filter(|v| !v.is_empty())
+                    .map(str::to_string),
+            );
+        }
+        let single = |name: &str| params.get(name).and_then(|values| values.first());
+        let integer = |name: &str| -> Result<Option<i64>, String> {
+            single(name)
+                .map(|value| {
+                    value
+                        .parse()
+                        .map_err(|_| format!("Invalid '{}': expected an integer", name))
+                })
+                .transpose()
+        };
+
+        let start_time =
+            integer("start_time")?.ok_or("Missing required parameter: 'start_time'")?;
+        let end_time = integer("end_time")?.unwrap_or_else(now_secs);
+        let bucket_width = match single("bucket_width") {
+            Some(text) => BucketWidth::parse(text).ok_or_else(|| {
+                format!("Invalid 'bucket_width': '{}' (expected 1m, 1h or 1d)", text)
+            })?,
+            None => BucketWidth::Day,
+        };
+        if endpoint == UsageEndpoint::Costs && bucket_width != BucketWidth::Day {
+            return Err("Invalid 'bucket_width': costs are only reported in 1d buckets".into());
+        }
+        let groupable: &[&str] = match endpoint {
+            UsageEndpoint::Completions => {
+                &["model", "project_id", "user_id", "api_key_id", "batch"]
+            }
+            UsageEndpoint::Costs => &["project_id", "line_item"],
+        };
+        let group_by = params.get("group_by").cloned().unwrap_or_default();
+        if let Some(field) = group_by.iter().find(|f| !groupable.contains(&f.as_str())) {
+            return Err(format!(
+                "Invalid 'group_by': '{}' (expected one of {})",
+                field,
+                groupable.join(", ")
+            ));
+        }
+        let (default_limit, max_limit) = match endpoint {
+            UsageEndpoint::Completions => bucket_width.limits(),
+            UsageEndpoint::Costs => (7, 180),
+        };
+        let limit = match integer("limit")? {
+            Some(limit) if limit < 1 || limit as usize > max_limit => {
+                return Err(format!(
+                    "Invalid 'limit': {} (expected 1 to {})",
+                    limit, max_limit
+                ))
+            }
+            Some(limit) => limit as usize,
+            None => default_limit,
+        };
+        let page = single("page")
+            .map(|cursor| {
+                cursor
+                    .strip_prefix("page_")
+                    .and_then(|secs| secs.parse().ok())
+                    .ok_or_else(|| format!("Invalid 'page': '{}'", cursor))
+            })
+            .transpose()?;
+        Ok(Self {
+            start_time,
+            end_time,
+            bucket_width,
+            group_by,
+            models: params.get("models").cloned().unwrap_or_default(),
+            project_ids: params.get("project_ids").cloned().unwrap_or_default(),
+            limit,
+            page,
+        })
+    }
+
+    fn grouped(&self, field: &str) -> bool {
+        self.group_by.iter().any(|f| f == field)
+    }
+
+    fn includes_model(&self, model: &str) -> bool {
+        self.models.is_empty() || self.models.iter().any(|m| m == model)
+    }
+
+    fn includes_project(&self, project: &str) -> bool {
+        self.project_ids.is_empty() || self.project_ids.iter().any(|p| p == project)
+    }
+}
+
+/// The page of buckets `query` selects, each holding the results
+/// `results(start, end)` gives for it
+fn page(query: &UsageQuery, results: impl Fn(i64, i64) -> Vec<Value>) -> Value {
+    let width = query.bucket_width.secs();
+    let mut start = query.page.unwrap_or(query.start_time);
+    let mut buckets = Vec::new();
+    while start < query.end_time && buckets.len() < query.limit {
+        let end = start + width;
+        buckets.push(json!({
+            "object": "bucket",
+            "start_time": start,
+            "end_time": end,
+            "results": results(start, end.min(query.end_time)),
+        }));
+        start = end;
+    }
+    let has_more = start < query.end_time;
+    json!({
+        "object": "page",
+        "data": buckets,
+        "has_more": has_more,
+        "next_page": has_more.then(|| format!("page_{}", start)),
+    })
+}
+
+fn now_secs() -> i64 {
+    crate::clock::system_now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since| since.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::latency::LatencyProfile;
+    use std::time::{Duration, SystemTime};
+
+    const DAY: i64 = 86_400;
+
+    fn completed(model: &str, at: i64, input_tokens: u32, output_tokens: u32) -> Decision {
+        Decision {
+            request: 1,
+            model: model.to_string(),
+            endpoint: EndpointType::ChatCompletions,
+            streaming: false,
+            latency: LatencyProfile::instant(),
+            delays: Vec::new(),
+            generator: "lorem".to_string(),
+            error: None,
+            outcome: Outcome::Completed {
+                input_tokens,
+                output_tokens,
+            },
+            prompt: None,
+            user: None,
+            started_at: SystemTime::UNIX_EPOCH + Duration::from_secs(at as u64),
+            elapsed: Duration::ZERO,
+        }
+    }
+
+    fn query(text: &str, endpoint: UsageEndpoint) -> UsageQuery {
+        UsageQuery::from_query(Some(text), endpoint).unwrap()
+    }
+
+    #[test]
+    fn test_daily_buckets_by_model() {
+        let ledger = UsageLedger::new(&UsageConfig::default());
+        ledger.add(&completed("gpt-5", 100, 10, 20));
+        ledger.add(&completed("gpt-5", DAY + 100, 1, 2));
+        ledger.add(&completed("gpt-4o", DAY + 200, 3, 4));
+        let mut failed = completed("gpt-5", 100, 1_000, 1_000);
+        failed.outcome = Outcome::Failed { status: 500 };
+        ledger.add(&failed);
+
+        let page = ledger.completions(&query(
+            &format!("start_time=0&end_time={}&group_by[]=model", 3 * DAY),
+            UsageEndpoint::Completions,
+        ));
+        let buckets = page["data"].as_array().unwrap();
+        assert_eq!(buckets.len(), 3);
+        assert_eq!(buckets[0]["results"][0]["input_tokens"], 10);
+        assert_eq!(buckets[0]["results"][0]["num_model_requests"], 1);
+        assert_eq!(buckets[1]["results"].as_array().unwrap().len(), 2);
+        assert_eq!(buckets[1]["results"][1]["model"], "gpt-5");
+        assert_eq!(buckets[2]["results"], json!([]));
+        assert_eq!(page["has_more"], false);
+
+        // Ungrouped, one result a bucket with the model left out
+        let page = ledger.completions(&query(
+            &format!("start_time={}&end_time={}", DAY, 2 * DAY),
+            UsageEndpoint::Completions,
+        ));
+        let result = &page["data"][0]["results"][0];
+        assert_eq!(result["output_tokens"], 6);
+        assert!(result["model"].is_null());
+    }
+
+    #[test]
+    fn test_pages() {
+        let ledger = UsageLedger::new(&UsageConfig::default());
+        let text = format!("start_time=0&end_time={}&limit=2", 3 * DAY);
+        let page = ledger.completions(&query(&text, UsageEndpoint::Completions));
+        assert_eq!(page["has_more"], true);
+        let cursor = page["next_page"].as_str().unwrap();
+
+        let next = format!("{}&page={}", text, cursor);
+        let page = ledger.completions(&query(&next, UsageEndpoint::Completions));
+        assert_eq!(page["data"].as_array().unwrap().len(), 1);
+        assert_eq!(page["data"][0]["start_time"], 2 * DAY);
+        assert_eq!(page["has_more"], false);
+        assert!(page["next_page"].is_null());
+    }
+
+    #[test]
+    fn test_costs_by_line_item() {
+        let ledger = UsageLedger::new(&UsageConfig::default());
+        ledger.add(&completed("gpt-5", 100, 1_000_000, 100_000));
+        ledger.add(&completed("my-finetune", 100, 1_000_000, 100_000));
+        let config = Config::default();
+
+        let page = ledger.costs(
+            &query("start_time=0&end_time=86400", UsageEndpoint::Costs),
+            &config,
+        );
+        let amount = &page["data"][0]["results"][0]["amount"];
+        assert!((amount["value"].as_f64().unwrap() - 2.25).abs() < 1e-9);
+        assert_eq!(amount["currency"], "usd");
+
+        let page = ledger.costs(
+            &query(
+                "start_time=0&end_time=86400&group_by=line_item,project_id",
+                UsageEndpoint::Costs,
+            ),
+            &config,
+        );
+        let results = page["data"][0]["results"].as_array().unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0]["line_item"], "gpt-5, input");
+        assert_eq!(results[0]["project_id"], "proj_llmsim");
+    }
+
+    #[test]
+    fn test_query_errors() {
+        let parse = |text: &str, endpoint| UsageQuery::from_query(Some(text), endpoint);
+        assert!(parse("end_time=10", UsageEndpoint::Completions).is_err());
+        assert!(parse("start_time=0&bucket_width=1w", UsageEndpoint::Completions).is_err());
+        assert!(parse("start_time=0&bucket_width=1h", UsageEndpoint::Costs).is_err());
+        assert!(parse(
+            "start_time=0&group_by=line_item",
+            UsageEndpoint::Completions
+        )
+        .is_err());
+        assert!(parse("start_time=0&limit=32", UsageEndpoint::Completions).is_err());
+        assert!(parse("start_time=0&limit=32", UsageEndpoint::Costs).is_ok());
+        let query = query(
+            "start_time=0&models%5B%5D=gpt-5&models%5B%5D=o3",
+            UsageEndpoint::Completions,
+        );
+        assert_eq!(query.models, ["gpt-5", "o3"]);
+    }
+}
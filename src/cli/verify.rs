@@ -0,0 +1,484 @@
+// Verify Module
+// `llmsim verify`: a battery of canned requests (model listing, chat,
+// streaming, tools, JSON mode, Responses streaming, errors) sent to an
+// OpenAI-compatible endpoint, each reported as passing or with every
+// deviation found.
+//
+// Decision: checks assert what SDK clients rely on rather than llmsim's exact
+// output, so the same battery passes against llmsim and against the real API,
+// and a failure against either means the two have drifted apart. Streams go
+// through the conformance checks; the bundled closed schemas are opt-in
+// because providers add fields over time. Requests use the upstream client,
+// so a provider's HTTPS API is checked directly, like an `[upstreams]` entry.
+
+use super::config::UpstreamConfig;
+use super::upstream::{Upstream, UpstreamError, UpstreamResponse};
+use crate::conformance::{parse_sse, ConformanceChecker};
+use crate::schema::PayloadSchema;
+use axum::http::{header, HeaderMap, HeaderValue};
+use serde_json::{json, Value};
+use std::fmt::Write;
+
+/// Model id no endpoint serves, for the error check
+const MISSING_MODEL: &str = "llmsim-verify-missing-model";
+
+/// Where and how to run the battery
+#[derive(Debug, Clone)]
+pub struct VerifyOptions {
+    /// `http(s)://host[:port][/path]`; request paths such as
+    /// `/v1/chat/completions` are appended, so for llmsim this ends in
+    /// `/openai`
+    pub target: String,
+    /// Model the generation requests ask for
+    pub model: String,
+    /// Sent as a bearer token when set
+    pub api_key: Option<String>,
+    pub timeout_secs: u64,
+    /// Also validate every streamed payload against the bundled schemas
+    pub strict_schema: bool,
+}
+
+impl VerifyOptions {
+    pub fn new(target: impl Into<String>) -> Self {
+        Self {
+            target: target.into(),
+            model: "gpt-5".to_string(),
+            api_key: None,
+            timeout_secs: 60,
+            strict_schema: false,
+        }
+    }
+}
+
+/// The outcome of one check
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckResult {
+    pub name: &'static str,
+    /// Every deviation found; empty when the check passed
+    pub failures: Vec<String>,
+}
+
+impl CheckResult {
+    pub fn passed(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+/// Run every check against `options.target`. Only an unusable target URL is
+/// an error; connection failures are reported by the checks they break.
+pub async fn verify(options: &VerifyOptions) -> Result<Vec<CheckResult>, UpstreamError> {
+    let client = Upstream::new(
+        "openai",
+        &UpstreamConfig {
+            base_url: options.target.clone(),
+            api_key: options.api_key.clone(),
+            timeout_secs: options.timeout_secs,
+        },
+    )?;
+    let verifier = Verifier {
+        client,
+        model: options.model.clone(),
+        strict_schema: options.strict_schema,
+    };
+    Ok(vec![
+        CheckResult {
+            name: "models",
+            failures: verifier.models().await,
+        },
+        CheckResult {
+            name: "chat",
+            failures: verifier.chat().await,
+        },
+        CheckResult {
+            name: "chat_stream",
+            failures: verifier.chat_stream().await,
+        },
+        CheckResult {
+            name: "tools",
+            failures: verifier.tools().await,
+        },
+        CheckResult {
+            name: "json_mode",
+            failures: verifier.json_mode().await,
+        },
+        CheckResult {
+            name: "responses_stream",
+            failures: verifier.responses_stream().await,
+        },
+        CheckResult {
+            name: "error",
+            failures: verifier.error().await,
+        },
+    ])
+}
+
+/// One line per check, failures indented under it, and a closing count
+pub fn verify_report(results: &[CheckResult]) -> String {
+    let mut out = String::new();
+    for result in results {
+        let verdict = if result.passed() { "ok" } else { "FAIL" };
+        let _ = writeln!(out, "{:<18}{}", result.name, verdict);
+        for failure in &result.failures {
+            let _ = writeln!(out, "    {}", failure);
+        }
+    }
+    let passed = results.iter().filter(|r| r.passed()).count();
+    let _ = writeln!(out, "\n{}/{} checks passed", passed, results.len());
+    out
+}
+
+struct Verifier {
+    client: Upstream,
+    model: String,
+    strict_schema: bool,
+}
+
+impl Verifier {
+    async fn send(&self, method: &str, path: &str, body: Option<Value>) -> Result<Reply, String> {
+        let mut headers = HeaderMap::new();
+        let body = match body {
+            Some(body) => {
+                headers.insert(
+                    header::CONTENT_TYPE,
+                    HeaderValue::from_static("application/json"),
+                );
+                body.to_string().into_bytes()
+            }
+            None => Vec::new(),
+        };
+        self.client
+            .send(method, path, &headers, &body)
+            .await
+            .map(Reply)
+            .map_err(|e| format!("{} {}: {}", method, path, e))
+    }
+
+    async fn post(&self, path: &str, body: Value) -> Result<Reply, String> {
+        self.send("POST", path, Some(body)).await
+    }
+
+    /// `GET /v1/models` lists models
+    async fn models(&self) -> Vec<String> {
+        let json = match self
+            .send("GET", "/v1/models", None)
+            .await
+            .and_then(|reply| reply.json(200))
+        {
+            Ok(json) => json,
+            Err(failure) => return vec![failure],
+        };
+        let mut failures = Vec::new();
+        expect_eq(&mut failures, &json, "object", "list");
+        if !json["data"].is_array() {
+            failures.push("`data` is not an array".to_string());
+        }
+        failures
+    }
+
+    /// A plain completion with coherent usage
+    async fn chat(&self) -> Vec<String> {
+        let body = json!({
+            "model": self.model,
+            "messages": [{"role": "user", "content": "Say hello."}]
+        });
+        let json = match self
+            .post("/v1/chat/completions", body)
+            .await
+            .and_then(|reply| reply.json(200))
+        {
+            Ok(json) => json,
+            Err(failure) => return vec![failure],
+        };
+        let mut failures = Vec::new();
+        expect_eq(&mut failures, &json, "object", "chat.completion");
+        if json["id"].as_str().is_none_or(str::is_empty) {
+            failures.push("`id` is missing".to_string());
+        }
+        let choice = &json["choices"][0];
+        expect_eq(&mut failures, &choice["message"], "role", "assistant");
+        if choice["message"]["content"]
+            .as_str()
+            .is_none_or(str::is_empty)
+        {
+            failures.push("message `content` is not a non-empty string".to_string());
+        }
+        if !matches!(choice["finish_reason"].as_str(), Some("stop" | "length")) {
+            failures.push(format!(
+                "`finish_reason` is {}, expected \"stop\" or \"length\"",
+                choice["finish_reason"]
+            ));
+        }
+        check_usage(&mut failures, &json["usage"]);
+        failures
+    }
+
+    /// A stream with a usage chunk, passing the conformance checks
+    async fn chat_stream(&self) -> Vec<String> {
+        let body = json!({
+            "model": self.model,
+            "messages": [{"role": "user", "content": "Count to five."}],
+            "stream": true,
+            "stream_options": {"include_usage": true}
+        });
+        let text = match self
+            .post("/v1/chat/completions", body)
+            .await
+            .and_then(|reply| reply.stream())
+        {
+            Ok(text) => text,
+            Err(failure) => return vec![failure],
+        };
+        let mut failures: Vec<String> = ConformanceChecker::new()
+            .check_chat_stream(&text)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        let has_usage = parse_sse(&text).iter().any(|event| {
+            serde_json::from_str::<Value>(&event.data).is_ok_and(|chunk| chunk["usage"].is_object())
+        });
+        if !has_usage {
+            failures.push("no usage chunk despite `include_usage`".to_string());
+        }
+        self.check_schema(&mut failures, PayloadSchema::ChatCompletionChunk, &text);
+        failures
+    }
+
+    /// `tool_choice: "required"` is answered with a call to the offered tool
+    async fn tools(&self) -> Vec<String> {
+        let body = json!({
+            "model": self.model,
+            "messages": [{"role": "user", "content": "What is the weather in Paris?"}],
+            "tools": [{
+                "type": "function",
+                "function": {
+                    "name": "get_weather",
+                    "description": "Current weather for a city",
+                    "parameters": {
+                        "type": "object",
+                        "properties": {"city": {"type": "string"}},
+                        "required": ["city"]
+                    }
+                }
+            }],
+            "tool_choice": "required",
+            "parallel_tool_calls": false
+        });
+        let json = match self
+            .post("/v1/chat/completions", body)
+            .await
+            .and_then(|reply| reply.json(200))
+        {
+            Ok(json) => json,
+            Err(failure) => return vec![failure],
+        };
+        let mut failures = Vec::new();
+        let choice = &json["choices"][0];
+        expect_eq(&mut failures, choice, "finish_reason", "tool_calls");
+        let Some(calls) = choice["message"]["tool_calls"]
+            .as_array()
+            .filter(|calls| !calls.is_empty())
+        else {
+            failures.push("message has no `tool_calls`".to_string());
+            return failures;
+        };
+        for call in calls {
+            if call["id"].as_str().is_none_or(str::is_empty) {
+                failures.push("tool call `id` is missing".to_string());
+            }
+            expect_eq(&mut failures, call, "type", "function");
+            expect_eq(&mut failures, &call["function"], "name", "get_weather");
+            let arguments = call["function"]["arguments"].as_str();
+            if arguments.is_none_or(|a| serde_json::from_str::<Value>(a).is_err()) {
+                failures.push("tool call `arguments` is not a JSON string".to_string());
+            }
+        }
+        failures
+    }
+
+    /// `response_format: json_object` is answered with a JSON object
+    async fn json_mode(&self) -> Vec<String> {
+        let body = json!({
+            "model": self.model,
+            "messages": [
+                {"role": "system", "content": "Answer in JSON."},
+                {"role": "user", "content": "Give me a greeting."}
+            ],
+            "response_format": {"type": "json_object"}
+        });
+        let json = match self
+            .post("/v1/chat/completions", body)
+            .await
+            .and_then(|reply| reply.json(200))
+        {
+            Ok(json) => json,
+            Err(failure) => return vec![failure],
+        };
+        let content = json["choices"][0]["message"]["content"]
+            .as_str()
+            .unwrap_or_default();
+        match serde_json::from_str::<Value>(content) {
+            Ok(value) if value.is_object() => Vec::new(),
+            _ => vec![format!("content is not a JSON object: {:?}", content)],
+        }
+    }
+
+    /// A Responses stream passing the conformance checks
+    async fn responses_stream(&self) -> Vec<String> {
+        let body = json!({
+            "model": self.model,
+            "input": "Say hello.",
+            "stream": true
+        });
+        let text = match self
+            .post("/v1/responses", body)
+            .await
+            .and_then(|reply| reply.stream())
+        {
+            Ok(text) => text,
+            Err(failure) => return vec![failure],
+        };
+        let mut failures: Vec<String> = ConformanceChecker::new()
+            .check_responses_stream(&text)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        self.check_schema(&mut failures, PayloadSchema::ResponsesStreamEvent, &text);
+        failures
+    }
+
+    /// An unknown model is a 404 in the provider's error format
+    async fn error(&self) -> Vec<String> {
+        let path = format!("/v1/models/{}", MISSING_MODEL);
+        let json = match self
+            .send("GET", &path, None)
+            .await
+            .and_then(|reply| reply.json(404))
+        {
+            Ok(json) => json,
+            Err(failure) => return vec![failure],
+        };
+        let mut failures = Vec::new();
+        for field in ["message", "type"] {
+            if !json["error"][field].is_string() {
+                failures.push(format!("`error.{}` is not a string", field));
+            }
+        }
+        failures
+    }
+
+    fn check_schema(&self, failures: &mut Vec<String>, schema: PayloadSchema, text: &str) {
+        if !self.strict_schema {
+            return;
+        }
+        for (i, event) in parse_sse(text).iter().enumerate() {
+            if event.data == "[DONE]" {
+                continue;
+            }
+            let violation = serde_json::from_str::<Value>(&event.data)
+                .map_err(|e| format!("payload is not JSON: {}", e))
+                .and_then(|payload| schema.validate(&payload).map_err(|v| v.to_string()));
+            if let Err(violation) = violation {
+                failures.push(format!("event {} [schema]: {}", i, violation));
+            }
+        }
+    }
+}
+
+/// A response with the checks every request shares
+struct Reply(UpstreamResponse);
+
+impl Reply {
+    fn expect_status(&self, status: u16) -> Result<(), String> {
+        if self.0.status == status {
+            return Ok(());
+        }
+        let body = String::from_utf8_lossy(&self.0.body);
+        let body: String = body.chars().take(200).collect();
+        Err(format!(
+            "status {}, expected {}: {}",
+            self.0.status, status, body
+        ))
+    }
+
+    /// The body as JSON, if the status is `status`
+    fn json(&self, status: u16) -> Result<Value, String> {
+        self.expect_status(status)?;
+        serde_json::from_slice(&self.0.body).map_err(|e| format!("body is not JSON: {}", e))
+    }
+
+    /// The body of a successful event stream
+    fn stream(&self) -> Result<String, String> {
+        self.expect_status(200)?;
+        if !self.0.is_event_stream() {
+            return Err("content type is not text/event-stream".to_string());
+        }
+        String::from_utf8(self.0.body.clone()).map_err(|e| format!("body is not UTF-8: {}", e))
+    }
+}
+
+fn expect_eq(failures: &mut Vec<String>, object: &Value, field: &str, expected: &str) {
+    if object[field] != expected {
+        failures.push(format!(
+            "`{}` is {}, expected {:?}",
+            field, object[field], expected
+        ));
+    }
+}
+
+fn check_usage(failures: &mut Vec<String>, usage: &Value) {
+    let (Some(prompt), Some(completion), Some(total)) = (
+        usage["prompt_tokens"].as_u64(),
+        usage["completion_tokens"].as_u64(),
+        usage["total_tokens"].as_u64(),
+    ) else {
+        failures.push("`usage` lacks token counts".to_string());
+        return;
+    };
+    if prompt == 0 {
+        failures.push("`usage.prompt_tokens` is zero".to_string());
+    }
+    if prompt + completion != total {
+        failures.push(format!(
+            "usage does not add up: {} + {} != {}",
+            prompt, completion, total
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_lists_failures() {
+        let results = [
+            CheckResult {
+                name: "models",
+                failures: Vec::new(),
+            },
+            CheckResult {
+                name: "chat",
+                failures: vec!["`object` is null, expected \"chat.completion\"".to_string()],
+            },
+        ];
+        let report = verify_report(&results);
+        assert!(report.contains("models            ok\n"));
+        assert!(report.contains("chat              FAIL\n    `object` is null"));
+        assert!(report.ends_with("1/2 checks passed\n"));
+    }
+
+    #[test]
+    fn test_usage_must_add_up() {
+        let mut failures = Vec::new();
+        check_usage(
+            &mut failures,
+            &json!({"prompt_tokens": 3, "completion_tokens": 4, "total_tokens": 7}),
+        );
+        assert!(failures.is_empty());
+        check_usage(
+            &mut failures,
+            &json!({"prompt_tokens": 3, "completion_tokens": 4, "total_tokens": 8}),
+        );
+        assert_eq!(failures.len(), 1);
+    }
+}
@@ -0,0 +1,249 @@
+// vLLM Compatibility HTTP Handlers
+// Implements the `/vllm/...` routes that stand in for a self-hosted vLLM
+// OpenAI-compatible server: chat completions, a single served model, and a
+// Prometheus `/metrics` endpoint using vLLM metric names.
+//
+// Decision: vLLM lives under its own provider prefix (`/vllm/v1/...`,
+// `/vllm/metrics`) instead of a global mode switch, so one llmsim instance can
+// impersonate OpenAI and vLLM side by side and the `/openai` wire format never
+// changes. Generation itself is shared with the OpenAI chat handler.
+
+use super::api_version::ApiVersion;
+use super::handlers;
+use super::state::AppState;
+use crate::openai::{get_model_profile, ChatCompletionRequest, StreamOptions};
+use crate::overrides::RequestOverrides;
+use crate::stats::StatsSnapshot;
+use axum::{
+    extract::{Path, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+use std::fmt::Write;
+use std::sync::Arc;
+
+/// vLLM model card (`/v1/models` entry)
+#[derive(Debug, Serialize)]
+struct VllmModelCard {
+    id: String,
+    object: &'static str,
+    created: i64,
+    owned_by: &'static str,
+    root: String,
+    parent: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_model_len: Option<u32>,
+}
+
+impl VllmModelCard {
+    fn new(id: String) -> Self {
+        Self {
+            max_model_len: get_model_profile(&id).map(|p| p.context_window),
+            root: id.clone(),
+            id,
+            object: "model",
+            created: crate::ids::unix_timestamp(),
+            owned_by: "vllm",
+            parent: None,
+        }
+    }
+}
+
+/// vLLM error envelope: `{"object": "error", "message", "type", "param", "code"}`
+fn vllm_error(status: StatusCode, error_type: &str, message: String) -> Response {
+    let body = serde_json::json!({
+        "object": "error",
+        "message": message,
+        "type": error_type,
+        "param": null,
+        "code": status.as_u16(),
+    });
+    (status, Json(body)).into_response()
+}
+
+fn model_not_found(model: &str) -> Response {
+    vllm_error(
+        StatusCode::NOT_FOUND,
+        "NotFoundError",
+        format!("The model `{}` does not exist.", model),
+    )
+}
+
+/// POST /vllm/v1/chat/completions
+pub async fn chat_completions(
+    State(state): State<Arc<AppState>>,
+    version: ApiVersion,
+    overrides: RequestOverrides,
+    Json(mut request): Json<ChatCompletionRequest>,
+) -> Result<Response, handlers::AppError> {
+    // vLLM only answers for the model it was started with.
+    if request.model != state.config.vllm_served_model() {
+        return Ok(model_not_found(&request.model));
+    }
+
+    if state.config.vllm.continuous_usage_stats {
+        request
+            .stream_options
+            .get_or_insert_with(StreamOptions::default)
+            .continuous_usage_stats = Some(true);
+    }
+
+    handlers::chat_completions(State(state), version, overrides, Json(request)).await
+}
+
+/// GET /vllm/v1/models
+pub async fn list_models(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    Json(serde_json::json!({
+        "object": "list",
+        "data": [VllmModelCard::new(state.config.vllm_served_model())],
+    }))
+}
+
+/// GET /vllm/v1/models/:model_id
+pub async fn get_model(
+    State(state): State<Arc<AppState>>,
+    Path(model_id): Path<String>,
+) -> Response {
+    if model_id == state.config.vllm_served_model() {
+        Json(VllmModelCard::new(model_id)).into_response()
+    } else {
+        model_not_found(&model_id)
+    }
+}
+
+/// GET /vllm/metrics - Prometheus text exposition with vLLM metric names
+pub async fn metrics(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let body = render_metrics(
+        &state.stats.snapshot(),
+        &state.config.vllm_served_model(),
+        state.config.vllm.max_num_seqs,
+    );
+    (
+        [(
+            header::CONTENT_TYPE,
+            "text/plain; version=0.0.4; charset=utf-8",
+        )],
+        body,
+    )
+}
+
+/// Render a stats snapshot as vLLM-named Prometheus metrics.
+///
+/// In-flight requests are split as vLLM's continuous batching scheduler would:
+/// up to `max_num_seqs` run concurrently and the rest wait in the queue. KV
+/// cache usage tracks the share of the batch that is occupied.
+fn render_metrics(snapshot: &StatsSnapshot, model_name: &str, max_num_seqs: u64) -> String {
+    let label = format!("model_name=\"{}\"", model_name.replace('"', "\\\""));
+    let running = snapshot.active_requests.min(max_num_seqs);
+    let waiting = snapshot.active_requests - running;
+    let cache_usage = if max_num_seqs == 0 {
+        0.0
+    } else {
+        running as f64 / max_num_seqs as f64
+    };
+
+    let metrics: [(&str, &str, &str, String); 8] = [
+        (
+            "vllm:num_requests_running",
+            "gauge",
+            "Number of requests currently running on GPU.",
+            running.to_string(),
+        ),
+        (
+            "vllm:num_requests_waiting",
+            "gauge",
+            "Number of requests waiting to be processed.",
+            waiting.to_string(),
+        ),
+        (
+            "vllm:gpu_cache_usage_perc",
+            "gauge",
+            "GPU KV-cache usage. 1 means 100 percent usage.",
+            cache_usage.to_string(),
+        ),
+        (
+            "vllm:prompt_tokens_total",
+            "counter",
+            "Number of prefill tokens processed.",
+            snapshot.prompt_tokens.to_string(),
+        ),
+        (
+            "vllm:generation_tokens_total",
+            "counter",
+            "Number of generation tokens processed.",
+            snapshot.completion_tokens.to_string(),
+        ),
+        (
+            "vllm:request_success_total",
+            "counter",
+            "Count of successfully processed requests.",
+            snapshot.completed_requests.to_string(),
+        ),
+        (
+            "vllm:e2e_request_latency_seconds_sum",
+            "summary",
+            "End to end request latency in seconds.",
+            (snapshot.total_latency_ms / 1000.0).to_string(),
+        ),
+        (
+            "vllm:e2e_request_latency_seconds_count",
+            "summary",
+            "End to end request latency in seconds.",
+            snapshot.completed_requests.to_string(),
+        ),
+    ];
+
+    let mut out = String::new();
+    for (name, kind, help, value) in metrics {
+        // The summary's _sum/_count share one HELP/TYPE header.
+        let family = name
+            .strip_suffix("_sum")
+            .or_else(|| name.strip_suffix("_count"))
+            .unwrap_or(name);
+        if kind != "summary" || name.ends_with("_sum") {
+            let _ = writeln!(out, "# HELP {} {}", family, help);
+            let _ = writeln!(out, "# TYPE {} {}", family, kind);
+        }
+        let _ = writeln!(out, "{}{{{}}} {}", name, label, value);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::Stats;
+    use crate::EndpointType;
+    use std::time::Duration;
+
+    #[test]
+    fn test_render_metrics_splits_running_and_waiting() {
+        let stats = Stats::new();
+        for _ in 0..5 {
+            stats.record_request_start("m", true, EndpointType::ChatCompletions);
+        }
+        let text = render_metrics(&stats.snapshot(), "m", 4);
+
+        assert!(text.contains("vllm:num_requests_running{model_name=\"m\"} 4\n"));
+        assert!(text.contains("vllm:num_requests_waiting{model_name=\"m\"} 1\n"));
+        assert!(text.contains("vllm:gpu_cache_usage_perc{model_name=\"m\"} 1\n"));
+    }
+
+    #[test]
+    fn test_render_metrics_counters() {
+        let stats = Stats::new();
+        stats.record_request_start("m", false, EndpointType::ChatCompletions);
+        stats.record_request_end(Duration::from_millis(1500), 12, 30);
+        let text = render_metrics(&stats.snapshot(), "m", 256);
+
+        assert!(text.contains("# TYPE vllm:prompt_tokens_total counter\n"));
+        assert!(text.contains("vllm:prompt_tokens_total{model_name=\"m\"} 12\n"));
+        assert!(text.contains("vllm:generation_tokens_total{model_name=\"m\"} 30\n"));
+        assert!(text.contains("vllm:request_success_total{model_name=\"m\"} 1\n"));
+        assert!(text.contains("# TYPE vllm:e2e_request_latency_seconds summary\n"));
+        assert!(text.contains("vllm:e2e_request_latency_seconds_sum{model_name=\"m\"} 1.5\n"));
+        assert_eq!(text.matches("# TYPE vllm:e2e_request_latency").count(), 1);
+    }
+}
@@ -2,14 +2,19 @@
 // Implements WebSocket transport for the OpenAI Responses API.
 // Reference: https://platform.openai.com/docs/guides/websocket-mode
 
-use super::handlers::{generate_responses_result, ResponseGenerationParams};
+use super::handlers::{
+    carry_context, check_reasoning_items, check_responses_capabilities, extract_input_text,
+    generate_responses_result, includes_encrypted_reasoning, ResponseGenerationParams,
+};
+use super::responses_store::conversation_tokens;
 use super::state::AppState;
 use crate::openai::websocket::{ClientEvent, ServerEvent};
-use crate::openai::ResponsesResponse;
-use crate::{EndpointType, ErrorInjector, ResponsesTokenStreamBuilder};
+use crate::openai::{ResponsesResponse, StreamOptions};
+use crate::schema::{validate_stream, PayloadSchema};
+use crate::{EndpointType, ResponsesTokenStreamBuilder};
 use axum::extract::ws::{Message, WebSocket};
 use axum::extract::{State, WebSocketUpgrade};
-use axum::http::StatusCode;
+use axum::http::{HeaderMap, StatusCode};
 use axum::response::{IntoResponse, Response};
 use futures_util::StreamExt;
 use std::sync::Arc;
@@ -19,7 +24,11 @@ use std::time::{Duration, Instant};
 const MAX_CONNECTION_DURATION: Duration = Duration::from_secs(60 * 60);
 
 /// GET /openai/v1/responses (WebSocket upgrade)
-pub async fn ws_responses(ws: WebSocketUpgrade, State(state): State<Arc<AppState>>) -> Response {
+pub async fn ws_responses(
+    ws: WebSocketUpgrade,
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Response {
     let max_connections = state.config.server.max_websocket_connections;
 
     if !state.stats.try_reserve_ws_connection(max_connections) {
@@ -47,11 +56,16 @@ pub async fn ws_responses(ws: WebSocketUpgrade, State(state): State<Arc<AppState
             tracing::warn!("WebSocket upgrade failed: {}", error);
         }
     })
-    .on_upgrade(move |socket| handle_ws_connection(socket, state))
+    .on_upgrade(move |socket| {
+        // The connection runs in a task of its own, outside the request's
+        // scope
+        let stamps = state.pipeline.stamps().clone();
+        stamps.scope(Box::pin(handle_ws_connection(socket, state, headers)))
+    })
 }
 
 /// Handle a single WebSocket connection lifecycle.
-async fn handle_ws_connection(mut socket: WebSocket, state: Arc<AppState>) {
+async fn handle_ws_connection(mut socket: WebSocket, state: Arc<AppState>, headers: HeaderMap) {
     let connection_start = Instant::now();
 
     tracing::info!("WebSocket connection established");
@@ -138,7 +152,8 @@ async fn handle_ws_connection(mut socket: WebSocket, state: Arc<AppState>) {
                             let warmup_response = ResponsesResponse::warmup(body.model.clone());
                             let event = serde_json::json!({
                                 "type": "response.created",
-                                "response": warmup_response
+                                "response": warmup_response,
+                                "sequence_number": 0
                             });
                             let _ = socket
                                 .send(Message::Text(serde_json::to_string(&event).unwrap().into()))
@@ -146,26 +161,21 @@ async fn handle_ws_connection(mut socket: WebSocket, state: Arc<AppState>) {
                             continue;
                         }
 
-                        let request_start = Instant::now();
-
                         tracing::info!(
                             model = %body.model,
                             "WebSocket response.create"
                         );
 
-                        // Record request start
-                        state.stats.record_request_start(
+                        let req = state.begin(
                             &body.model,
                             true, // WS is always streaming
                             EndpointType::WebSocketResponses,
                         );
+                        state.stats.record_request_messages(body.input.item_count());
+                        req.set_prompt(&extract_input_text(&body.input, &body.instructions));
 
                         // Check for error injection
-                        let error_injector = ErrorInjector::new(state.config.error_config());
-                        if let Some(error) = error_injector.maybe_inject() {
-                            tracing::warn!("Injecting error on WebSocket: {:?}", error);
-                            state.stats.record_error(error.status_code());
-
+                        if let Some(error) = req.inject_error() {
                             let err_resp = error.to_error_response();
                             let error_event = ServerEvent::from_error(
                                 &err_resp.error.error_type,
@@ -180,8 +190,31 @@ async fn handle_ws_connection(mut socket: WebSocket, state: Arc<AppState>) {
                             continue;
                         }
 
+                        // Reasoning passed back by id may be in the
+                        // connection's cache; only encrypted content is checked
+                        let checked = check_responses_capabilities(
+                            &state,
+                            &body.model,
+                            &body.input,
+                            body.tools.as_deref(),
+                        )
+                        .map_err(|error| (StatusCode::BAD_REQUEST, error))
+                        .and_then(|()| check_reasoning_items(&body.input, true));
+                        if let Err((status, error)) = checked {
+                            req.fail(status.as_u16());
+                            let code = error.code.as_deref().unwrap_or(&error.error_type);
+                            let error_event = ServerEvent::from_error(code, &error.message);
+                            let _ = socket
+                                .send(Message::Text(
+                                    serde_json::to_string(&error_event).unwrap().into(),
+                                ))
+                                .await;
+                            cached_response = None;
+                            continue;
+                        }
+
                         // Generate response using shared logic
-                        let result = generate_responses_result(
+                        let mut result = generate_responses_result(
                             &state,
                             &ResponseGenerationParams {
                                 model: &body.model,
@@ -191,28 +224,67 @@ async fn handle_ws_connection(mut socket: WebSocket, state: Arc<AppState>) {
                                 top_p: body.top_p,
                                 max_output_tokens: body.max_output_tokens,
                                 reasoning: &body.reasoning,
+                                tools: body.tools.as_deref(),
+                                tool_choice: body.tool_choice.as_ref(),
+                                include: body.include.as_deref(),
+                                headers: &headers,
                             },
                         );
 
+                        // A chained response continues the cached conversation
+                        let context = body
+                            .previous_response_id
+                            .as_ref()
+                            .and(cached_response.as_ref())
+                            .map_or(0, conversation_tokens);
+                        if let Err(error) = carry_context(
+                            &state,
+                            &body.model,
+                            context,
+                            body.truncation,
+                            &mut result.usage,
+                        ) {
+                            req.fail(400);
+                            let code = error.code.as_deref().unwrap_or(&error.error_type);
+                            let error_event = ServerEvent::from_error(code, &error.message);
+                            let _ = socket
+                                .send(Message::Text(
+                                    serde_json::to_string(&error_event).unwrap().into(),
+                                ))
+                                .await;
+                            cached_response = None;
+                            continue;
+                        }
+
                         // Build the streaming response
-                        let stats = state.stats.clone();
                         let input_tok = result.usage.input_tokens;
                         let output_tok = result.usage.output_tokens;
 
                         let mut builder =
                             ResponsesTokenStreamBuilder::new(&body.model, result.content)
-                                .latency(result.latency)
+                                .latency(req.request_latency(input_tok))
+                                .sleeper(req.sleeper().clone())
+                                .watchdog(req.stream_watchdog())
                                 .usage(result.usage)
-                                .on_complete(move || {
-                                    stats.record_request_end(
-                                        request_start.elapsed(),
-                                        input_tok,
-                                        output_tok,
-                                    );
-                                });
+                                .truncated(result.truncated)
+                                .refusal(result.refused)
+                                .annotations(result.annotations)
+                                .mcp_items(result.mcp_items)
+                                .code_interpreter_call(result.code_interpreter_call)
+                                .obfuscation(
+                                    body.stream_options
+                                        .as_ref()
+                                        .is_none_or(StreamOptions::obfuscation_enabled),
+                                )
+                                .usage_updates(state.config.stream.usage_updates_every)
+                                .on_complete(req.finish_callback(input_tok, output_tok));
 
                         if result.reasoning_tokens > 0 {
-                            builder = builder.reasoning(result.reasoning_summary);
+                            builder = builder
+                                .reasoning(result.reasoning_summary)
+                                .encrypted_reasoning(includes_encrypted_reasoning(
+                                    body.include.as_deref(),
+                                ));
                         }
 
                         let stream = builder.build();
@@ -220,7 +292,13 @@ async fn handle_ws_connection(mut socket: WebSocket, state: Arc<AppState>) {
                         // Stream events over WebSocket as JSON text frames.
                         // The existing stream produces SSE-formatted strings;
                         // we extract the JSON payload from each SSE chunk.
-                        let mut sse_stream = stream.into_stream();
+                        let events = stream.into_stream();
+                        let events = if state.config.stream.strict_schema {
+                            validate_stream(events, PayloadSchema::ResponsesStreamEvent).boxed()
+                        } else {
+                            events
+                        };
+                        let mut sse_stream = req.guard_stream(events);
                         let mut last_completed_response: Option<ResponsesResponse> = None;
 
                         while let Some(sse_chunk) = sse_stream.next().await {
@@ -0,0 +1,229 @@
+// Clock Module
+// The one place the simulator reads the time: monotonic instants for stats,
+// load shedding, drills and request durations, and the Unix time stamped on
+// responses.
+//
+// Decision: instants come from `tokio::time::Instant`, which follows
+// `tokio::time::pause`/`advance` when tokio's `test-util` feature is on and is
+// the plain system clock otherwise. A test that pauses time can then
+// fast-forward hours of simulated traffic (latency, RPS windows, drill and
+// sunset expiry, `created` timestamps) in milliseconds. Unix time is the wall
+// clock read once and carried forward on that monotonic clock, so it moves
+// with paused time too; a long-running server does not follow later wall
+// clock adjustments, which is harmless for response timestamps.
+//
+// Decision: wasm32 has neither clock to read without the browser's help
+// (`Instant::now` and `SystemTime::now` panic there) and no tokio timer, so
+// streams wait on `VirtualSleeper`, which only adds each delay to a virtual
+// clock. There the simulator's clock is that virtual time: `Instant` counts
+// the delays slept since start, and Unix time is the start (the epoch unless
+// the embedder passes `Date.now()` to `set_start_time`) plus the same.
+//
+// Timestamps stamped on responses can be skewed from that clock, by a fixed
+// offset and a random jitter per timestamp, to play a provider whose clock
+// drifts. Like id formats, the skew is part of a pipeline's `Stamps`; the
+// simulator's own bookkeeping (deprecation sunsets, rate limit resets,
+// fingerprint rotation) keeps the true time.
+
+use rand::RngExt;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// A point in simulated time
+#[cfg(not(target_arch = "wasm32"))]
+pub use std::time::Instant;
+#[cfg(target_arch = "wasm32")]
+pub(crate) use virtual_time::advance;
+#[cfg(target_arch = "wasm32")]
+pub use virtual_time::set_start_time;
+#[cfg(target_arch = "wasm32")]
+pub use virtual_time::Instant;
+
+/// Wall clock and monotonic clock read together at first use
+#[cfg(not(target_arch = "wasm32"))]
+static ANCHOR: std::sync::OnceLock<(SystemTime, Instant)> = std::sync::OnceLock::new();
+
+/// Current instant, on tokio's (possibly paused) clock, or the virtual one
+/// on wasm32
+pub fn now() -> Instant {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        tokio::time::Instant::now().into_std()
+    }
+    #[cfg(target_arch = "wasm32")]
+    {
+        virtual_time::now()
+    }
+}
+
+/// Time since `earlier`, zero if it is in the future
+pub fn elapsed(earlier: Instant) -> Duration {
+    now().saturating_duration_since(earlier)
+}
+
+/// Current wall clock time, advancing with `now()`
+pub fn system_now() -> SystemTime {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let (wall, at) = ANCHOR.get_or_init(|| (SystemTime::now(), now()));
+        *wall + elapsed(*at)
+    }
+    #[cfg(target_arch = "wasm32")]
+    {
+        virtual_time::system_now()
+    }
+}
+
+/// Seconds since the Unix epoch, advancing with `now()`
+pub fn unix_seconds() -> u64 {
+    system_now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// How a simulated provider's clock strays from the true time
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ClockSkew {
+    /// Seconds added to every response timestamp
+    pub offset_secs: i64,
+    /// Most seconds a response timestamp strays either side of the offset
+    pub jitter_secs: u64,
+}
+
+impl ClockSkew {
+    pub fn new(offset_secs: i64, jitter_secs: u64) -> Self {
+        Self {
+            offset_secs,
+            jitter_secs,
+        }
+    }
+
+    /// Unix seconds to stamp on a response (`created`, `created_at`): the
+    /// current time as the simulated provider's clock reads it
+    pub fn response_seconds(&self) -> i64 {
+        let jitter = self.jitter_secs.min(i64::MAX as u64) as i64;
+        let jitter = if jitter > 0 {
+            rand::rng().random_range(-jitter..=jitter)
+        } else {
+            0
+        };
+        (unix_seconds() as i64)
+            .saturating_add(self.offset_secs)
+            .saturating_add(jitter)
+            .max(0)
+    }
+}
+
+/// The virtual clock `VirtualSleeper` advances on wasm32
+#[cfg(target_arch = "wasm32")]
+mod virtual_time {
+    use std::ops::{Add, AddAssign, Sub, SubAssign};
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    /// Delays slept since start, in nanoseconds
+    static ELAPSED_NANOS: AtomicU64 = AtomicU64::new(0);
+    /// Unix time of the start, in milliseconds
+    static START_MILLIS: AtomicU64 = AtomicU64::new(0);
+
+    /// A point in virtual time: the delays slept before it
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+    pub struct Instant(Duration);
+
+    impl Instant {
+        pub fn duration_since(&self, earlier: Instant) -> Duration {
+            self.saturating_duration_since(earlier)
+        }
+
+        pub fn checked_duration_since(&self, earlier: Instant) -> Option<Duration> {
+            self.0.checked_sub(earlier.0)
+        }
+
+        pub fn saturating_duration_since(&self, earlier: Instant) -> Duration {
+            self.0.saturating_sub(earlier.0)
+        }
+
+        pub fn checked_add(&self, duration: Duration) -> Option<Instant> {
+            self.0.checked_add(duration).map(Instant)
+        }
+
+        pub fn checked_sub(&self, duration: Duration) -> Option<Instant> {
+            self.0.checked_sub(duration).map(Instant)
+        }
+
+        pub fn elapsed(&self) -> Duration {
+            now().saturating_duration_since(*self)
+        }
+    }
+
+    impl Add<Duration> for Instant {
+        type Output = Instant;
+
+        fn add(self, duration: Duration) -> Instant {
+            Instant(self.0 + duration)
+        }
+    }
+
+    impl AddAssign<Duration> for Instant {
+        fn add_assign(&mut self, duration: Duration) {
+            self.0 += duration;
+        }
+    }
+
+    impl Sub<Duration> for Instant {
+        type Output = Instant;
+
+        fn sub(self, duration: Duration) -> Instant {
+            Instant(self.0.saturating_sub(duration))
+        }
+    }
+
+    impl SubAssign<Duration> for Instant {
+        fn sub_assign(&mut self, duration: Duration) {
+            self.0 = self.0.saturating_sub(duration);
+        }
+    }
+
+    impl Sub<Instant> for Instant {
+        type Output = Duration;
+
+        fn sub(self, earlier: Instant) -> Duration {
+            self.saturating_duration_since(earlier)
+        }
+    }
+
+    pub fn now() -> Instant {
+        Instant(Duration::from_nanos(ELAPSED_NANOS.load(Ordering::Relaxed)))
+    }
+
+    /// Move virtual time on by `by`
+    pub fn advance(by: Duration) {
+        let nanos = u64::try_from(by.as_nanos()).unwrap_or(u64::MAX);
+        ELAPSED_NANOS.fetch_add(nanos, Ordering::Relaxed);
+    }
+
+    /// Have Unix time start at `unix_millis` (`Date.now()`) rather than the
+    /// epoch
+    pub fn set_start_time(unix_millis: u64) {
+        START_MILLIS.store(unix_millis, Ordering::Relaxed);
+    }
+
+    pub fn system_now() -> SystemTime {
+        let start = Duration::from_millis(START_MILLIS.load(Ordering::Relaxed));
+        UNIX_EPOCH + start + now().0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_follows_paused_time() {
+        let started = now();
+        let unix = unix_seconds();
+        tokio::time::advance(Duration::from_secs(3 * 3600)).await;
+        assert_eq!(elapsed(started), Duration::from_secs(3 * 3600));
+        assert!(unix_seconds() >= unix + 3 * 3600);
+    }
+}
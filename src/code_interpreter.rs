@@ -0,0 +1,133 @@
+// Code Interpreter Simulation
+// Decides when a Responses request is answered with a `code_interpreter_call`
+// and makes up the code the model "wrote" and the logs it "printed".
+//
+// Decision: nothing is executed. The snippet counts the words and characters
+// of the prompt and its logs carry those counts, computed here, so the code
+// and its output agree and stay deterministic for a given input. The call is
+// made whenever the tool is offered and `tool_choice` is not "none": clients
+// that offer the tool should always see the call shape.
+
+use crate::ids::{new_id, IdKind};
+use crate::openai::{
+    CodeInterpreterOutput, ItemStatus, OutputItem, ResponsesTool, ResponsesToolChoice,
+};
+
+/// Longest prompt excerpt quoted in the generated code
+const MAX_QUOTED_CHARS: usize = 60;
+
+/// Whether a request offering `tools` is answered with a code interpreter call
+pub fn is_called(
+    tools: Option<&[ResponsesTool]>,
+    tool_choice: Option<&ResponsesToolChoice>,
+) -> bool {
+    let offered = tools.is_some_and(|tools| {
+        tools
+            .iter()
+            .any(|tool| matches!(tool, ResponsesTool::CodeInterpreter { .. }))
+    });
+    let declined =
+        matches!(tool_choice, Some(ResponsesToolChoice::String(choice)) if choice == "none");
+    offered && !declined
+}
+
+/// Simulated code and the logs it prints for `prompt`
+pub struct CodeRun {
+    pub code: String,
+    pub logs: String,
+}
+
+impl CodeRun {
+    pub fn for_prompt(prompt: &str) -> Self {
+        let excerpt: String = prompt.chars().take(MAX_QUOTED_CHARS).collect();
+        let words = excerpt.split_whitespace().count();
+        let chars = excerpt.chars().count();
+        let code = format!(
+            "text = {}\nwords = text.split()\nprint(f\"{{len(words)}} words, {{len(text)}} characters\")\n",
+            python_string(&excerpt)
+        );
+        Self {
+            code,
+            logs: format!("{} words, {} characters\n", words, chars),
+        }
+    }
+
+    /// Completed call item; `outputs` only when the request asks for them
+    /// with `include: ["code_interpreter_call.outputs"]`
+    pub fn into_item(self, image_url: Option<String>, include_outputs: bool) -> OutputItem {
+        let outputs = include_outputs.then(|| {
+            let mut outputs = vec![CodeInterpreterOutput::Logs { logs: self.logs }];
+            outputs.extend(image_url.map(|url| CodeInterpreterOutput::Image { url }));
+            outputs
+        });
+        OutputItem::CodeInterpreterCall {
+            id: new_id(IdKind::CodeInterpreterCall),
+            status: ItemStatus::Completed,
+            code: Some(self.code),
+            container_id: new_id(IdKind::Container),
+            outputs,
+        }
+    }
+}
+
+/// `text` as a double-quoted Python string literal
+fn python_string(text: &str) -> String {
+    let mut literal = String::with_capacity(text.len() + 2);
+    literal.push('"');
+    for ch in text.chars() {
+        match ch {
+            '"' => literal.push_str("\\\""),
+            '\\' => literal.push_str("\\\\"),
+            '\n' => literal.push_str("\\n"),
+            '\r' => literal.push_str("\\r"),
+            '\t' => literal.push_str("\\t"),
+            ch => literal.push(ch),
+        }
+    }
+    literal.push('"');
+    literal
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_called() {
+        let tools = [ResponsesTool::CodeInterpreter {}];
+        let none = ResponsesToolChoice::String("none".to_string());
+        let auto = ResponsesToolChoice::String("auto".to_string());
+        assert!(is_called(Some(&tools), None));
+        assert!(is_called(Some(&tools), Some(&auto)));
+        assert!(!is_called(Some(&tools), Some(&none)));
+        assert!(!is_called(Some(&[ResponsesTool::WebSearch {}]), None));
+        assert!(!is_called(None, None));
+    }
+
+    #[test]
+    fn test_code_and_logs_agree() {
+        let run = CodeRun::for_prompt("Say \"hi\"\nto me");
+        assert!(run.code.starts_with("text = \"Say \\\"hi\\\"\\nto me\"\n"));
+        assert_eq!(run.logs, "4 words, 14 characters\n");
+    }
+
+    #[test]
+    fn test_outputs_need_include() {
+        let item = CodeRun::for_prompt("hello").into_item(None, false);
+        let json = serde_json::to_value(&item).unwrap();
+        assert_eq!(json["type"], "code_interpreter_call");
+        assert!(json["outputs"].is_null());
+        assert!(json["container_id"].as_str().unwrap().starts_with("cntr_"));
+
+        let item = CodeRun::for_prompt("hello").into_item(Some("http://x/f".into()), true);
+        let json = serde_json::to_value(&item).unwrap();
+        assert_eq!(
+            json["outputs"][0],
+            serde_json::json!({"type": "logs", "logs": "1 words, 5 characters\n"})
+        );
+        assert_eq!(
+            json["outputs"][1],
+            serde_json::json!({"type": "image", "url": "http://x/f"})
+        );
+    }
+}
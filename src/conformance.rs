@@ -0,0 +1,1035 @@
+// Conformance Module
+// Reusable assertions over streamed Chat Completions and Responses output:
+// event ordering, id stability across chunks, and usage consistency with
+// the streamed deltas. They run against the raw SSE body from any
+// OpenAI-compatible endpoint, with llmsim as the reference implementation.
+//
+// Decision: checks take the SSE body text a client received, not llmsim's
+// own types, so they apply unchanged to a gateway, a proxy or the real API
+// and need no HTTP client here. They collect every failure instead of
+// stopping at the first, so one run against a gateway lists all of its
+// deviations. Usage is compared with the delta text only when the caller
+// supplies a token counter, since token counts depend on the tokenizer.
+
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+/// One SSE event: its `event:` name, if any, and its joined `data:` lines
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SseEvent {
+    pub event: Option<String>,
+    pub data: String,
+}
+
+/// Split an SSE body into events. Comments and blank events are skipped.
+pub fn parse_sse(body: &str) -> Vec<SseEvent> {
+    let mut events = Vec::new();
+    for block in body.replace("\r\n", "\n").split("\n\n") {
+        let mut event = None;
+        let mut data: Vec<&str> = Vec::new();
+        for line in block.lines() {
+            if let Some(name) = line.strip_prefix("event:") {
+                event = Some(name.trim_start().to_string());
+            } else if let Some(d) = line.strip_prefix("data:") {
+                data.push(d.strip_prefix(' ').unwrap_or(d));
+            }
+        }
+        if event.is_some() || !data.is_empty() {
+            events.push(SseEvent {
+                event,
+                data: data.join("\n"),
+            });
+        }
+    }
+    events
+}
+
+/// The property a failed check belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Rule {
+    /// SSE framing and payload shape (JSON, `[DONE]`, event names)
+    Framing,
+    /// Events arrive in the order the protocol defines
+    Ordering,
+    /// Ids and other per-stream values do not change between chunks
+    IdStability,
+    /// `*.done` text and final output match the concatenated deltas
+    Content,
+    /// Reported usage adds up and matches the streamed text
+    Usage,
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Rule::Framing => "framing",
+            Rule::Ordering => "ordering",
+            Rule::IdStability => "id-stability",
+            Rule::Content => "content",
+            Rule::Usage => "usage",
+        })
+    }
+}
+
+/// A single failed check
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConformanceFailure {
+    /// Index of the offending event in the stream (the last event for
+    /// end-of-stream checks)
+    pub event: usize,
+    pub rule: Rule,
+    pub message: String,
+}
+
+impl fmt::Display for ConformanceFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "event {} [{}]: {}", self.event, self.rule, self.message)
+    }
+}
+
+type CountTokens = Arc<dyn Fn(&str) -> usize + Send + Sync>;
+
+/// Runs the conformance checks over a stream body
+///
+/// ```
+/// use llmsim::conformance::ConformanceChecker;
+///
+/// let body = "data: {\"id\":\"c1\",\"object\":\"chat.completion.chunk\",\"created\":1,\"model\":\"m\",\"choices\":[{\"index\":0,\"delta\":{\"role\":\"assistant\",\"content\":\"Hi\"},\"finish_reason\":\"stop\"}]}\n\ndata: [DONE]\n\n";
+/// assert!(ConformanceChecker::new().check_chat_stream(body).is_empty());
+/// ```
+#[derive(Clone, Default)]
+pub struct ConformanceChecker {
+    count_tokens: Option<CountTokens>,
+    token_tolerance: usize,
+}
+
+impl fmt::Debug for ConformanceChecker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConformanceChecker")
+            .field("count_tokens", &self.count_tokens.is_some())
+            .field("token_tolerance", &self.token_tolerance)
+            .finish()
+    }
+}
+
+impl ConformanceChecker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Compare reported output tokens with this count of the streamed text
+    pub fn with_token_counter(
+        mut self,
+        count_tokens: impl Fn(&str) -> usize + Send + Sync + 'static,
+    ) -> Self {
+        self.count_tokens = Some(Arc::new(count_tokens));
+        self
+    }
+
+    /// Count streamed text with a model's tiktoken encoding
+    #[cfg(feature = "tokens")]
+    pub fn with_tokenizer(self, model: &str) -> Result<Self, crate::tokens::TokenError> {
+        let counter = crate::tokens::TokenCounter::new(model)?;
+        Ok(self.with_token_counter(move |text| counter.count(text)))
+    }
+
+    /// How far reported output tokens may be from the counted text
+    /// (default 0, i.e. exact)
+    pub fn with_token_tolerance(mut self, tokens: usize) -> Self {
+        self.token_tolerance = tokens;
+        self
+    }
+
+    /// Check a `chat.completion.chunk` stream ending in `data: [DONE]`
+    pub fn check_chat_stream(&self, body: &str) -> Vec<ConformanceFailure> {
+        let mut check = ChatCheck::default();
+        for (i, event) in parse_sse(body).iter().enumerate() {
+            check.event(i, event);
+        }
+        check.finish(self)
+    }
+
+    /// Check a Responses API event stream
+    pub fn check_responses_stream(&self, body: &str) -> Vec<ConformanceFailure> {
+        let mut check = ResponsesCheck::default();
+        for (i, event) in parse_sse(body).iter().enumerate() {
+            check.event(i, event);
+        }
+        check.finish(self)
+    }
+
+    /// Whether `reported` tokens match `text` within the tolerance; true
+    /// when no counter is configured
+    fn tokens_match(&self, text: &str, reported: &[u64]) -> Result<(), usize> {
+        let Some(count) = &self.count_tokens else {
+            return Ok(());
+        };
+        let counted = count(text);
+        let tolerance = self.token_tolerance as u64;
+        if reported
+            .iter()
+            .any(|&r| r.abs_diff(counted as u64) <= tolerance)
+        {
+            Ok(())
+        } else {
+            Err(counted)
+        }
+    }
+}
+
+/// Panic listing every failure if `body` is not a conforming chat stream
+pub fn assert_chat_stream(body: &str) {
+    assert_clean(ConformanceChecker::new().check_chat_stream(body), body);
+}
+
+/// Panic listing every failure if `body` is not a conforming Responses stream
+pub fn assert_responses_stream(body: &str) {
+    assert_clean(ConformanceChecker::new().check_responses_stream(body), body);
+}
+
+fn assert_clean(failures: Vec<ConformanceFailure>, body: &str) {
+    if !failures.is_empty() {
+        let list: Vec<String> = failures.iter().map(|f| format!("  {}", f)).collect();
+        panic!(
+            "stream does not conform:\n{}\n--- stream ---\n{}",
+            list.join("\n"),
+            body
+        );
+    }
+}
+
+#[derive(Default)]
+struct Failures(Vec<ConformanceFailure>);
+
+impl Failures {
+    fn push(&mut self, event: usize, rule: Rule, message: impl Into<String>) {
+        self.0.push(ConformanceFailure {
+            event,
+            rule,
+            message: message.into(),
+        });
+    }
+}
+
+/// Compare a per-stream value against the first one seen
+fn stable(
+    failures: &mut Failures,
+    i: usize,
+    first: &mut Option<Value>,
+    value: Option<&Value>,
+    what: &str,
+) {
+    let value = value.cloned().unwrap_or(Value::Null);
+    match first {
+        None => *first = Some(value),
+        Some(expected) if *expected != value => failures.push(
+            i,
+            Rule::IdStability,
+            format!("{} changed from {} to {}", what, expected, value),
+        ),
+        Some(_) => {}
+    }
+}
+
+fn as_u64(value: &Value, key: &str) -> Option<u64> {
+    value.get(key).and_then(Value::as_u64)
+}
+
+// ============================================================================
+// Chat Completions
+// ============================================================================
+
+#[derive(Default)]
+struct ChatChoice {
+    started: bool,
+    finish_reason: Option<String>,
+    content: String,
+    tool_calls: Vec<ChatToolCall>,
+}
+
+#[derive(Default)]
+struct ChatToolCall {
+    id: Option<String>,
+    name: Option<String>,
+    arguments: String,
+}
+
+#[derive(Default)]
+struct ChatCheck {
+    failures: Failures,
+    last: usize,
+    done: bool,
+    id: Option<Value>,
+    created: Option<Value>,
+    model: Option<Value>,
+    fingerprint: Option<Value>,
+    choices: HashMap<u64, ChatChoice>,
+    usage: Option<(usize, Value)>,
+    usage_only_at: Option<usize>,
+}
+
+impl ChatCheck {
+    fn event(&mut self, i: usize, event: &SseEvent) {
+        self.last = i;
+        if self.done {
+            self.failures
+                .push(i, Rule::Framing, "event after `data: [DONE]`");
+            return;
+        }
+        if event.data == "[DONE]" {
+            self.done = true;
+            return;
+        }
+        let chunk: Value = match serde_json::from_str(&event.data) {
+            Ok(chunk) => chunk,
+            Err(e) => {
+                self.failures
+                    .push(i, Rule::Framing, format!("payload is not JSON: {}", e));
+                return;
+            }
+        };
+        if event.event.as_deref() == Some("error") || chunk.get("error").is_some() {
+            self.failures.push(
+                i,
+                Rule::Framing,
+                format!("stream reported an error: {}", chunk["error"]),
+            );
+            return;
+        }
+        if chunk["object"] != "chat.completion.chunk" {
+            self.failures.push(
+                i,
+                Rule::Framing,
+                format!(
+                    "object is {}, not \"chat.completion.chunk\"",
+                    chunk["object"]
+                ),
+            );
+        }
+
+        let f = &mut self.failures;
+        stable(f, i, &mut self.id, chunk.get("id"), "id");
+        stable(f, i, &mut self.created, chunk.get("created"), "created");
+        stable(f, i, &mut self.model, chunk.get("model"), "model");
+        stable(
+            f,
+            i,
+            &mut self.fingerprint,
+            chunk.get("system_fingerprint"),
+            "system_fingerprint",
+        );
+
+        if let Some(at) = self.usage_only_at {
+            if self.choices_in(&chunk) > 0 {
+                self.failures.push(
+                    i,
+                    Rule::Ordering,
+                    format!("choice delta after the usage-only chunk at event {}", at),
+                );
+            }
+        }
+        if let Some(usage) = chunk.get("usage").filter(|u| !u.is_null()) {
+            self.usage_chunk(i, usage, self.choices_in(&chunk) == 0);
+        }
+
+        for choice in chunk["choices"].as_array().into_iter().flatten() {
+            self.choice(i, choice);
+        }
+    }
+
+    fn choices_in(&self, chunk: &Value) -> usize {
+        chunk["choices"].as_array().map_or(0, Vec::len)
+    }
+
+    fn usage_chunk(&mut self, i: usize, usage: &Value, usage_only: bool) {
+        let prompt = as_u64(usage, "prompt_tokens").unwrap_or(0);
+        let completion = as_u64(usage, "completion_tokens").unwrap_or(0);
+        let total = as_u64(usage, "total_tokens").unwrap_or(0);
+        if prompt + completion != total {
+            self.failures.push(
+                i,
+                Rule::Usage,
+                format!(
+                    "total_tokens {} != prompt_tokens {} + completion_tokens {}",
+                    total, prompt, completion
+                ),
+            );
+        }
+        if let Some((_, previous)) = &self.usage {
+            let before = as_u64(previous, "completion_tokens").unwrap_or(0);
+            if completion < before {
+                self.failures.push(
+                    i,
+                    Rule::Usage,
+                    format!(
+                        "completion_tokens went down from {} to {}",
+                        before, completion
+                    ),
+                );
+            }
+        }
+        if usage_only {
+            self.usage_only_at = Some(i);
+        }
+        self.usage = Some((i, usage.clone()));
+    }
+
+    fn choice(&mut self, i: usize, choice: &Value) {
+        let Some(index) = as_u64(choice, "index") else {
+            self.failures
+                .push(i, Rule::Framing, "choice without an index");
+            return;
+        };
+        let state = self.choices.entry(index).or_default();
+        let delta = &choice["delta"];
+        let content = delta["content"].as_str().unwrap_or_default();
+        let tool_calls = delta["tool_calls"].as_array();
+
+        if !state.started {
+            state.started = true;
+            if delta["role"] != "assistant" {
+                self.failures.push(
+                    i,
+                    Rule::Ordering,
+                    format!("first delta of choice {} has no `role: assistant`", index),
+                );
+            }
+        }
+        if let Some(reason) = &state.finish_reason {
+            if !content.is_empty() || tool_calls.is_some_and(|c| !c.is_empty()) {
+                self.failures.push(
+                    i,
+                    Rule::Ordering,
+                    format!(
+                        "choice {} got a delta after finish_reason {:?}",
+                        index, reason
+                    ),
+                );
+            }
+        }
+        state.content.push_str(content);
+
+        for call in tool_calls.into_iter().flatten() {
+            let Some(call_index) = as_u64(call, "index") else {
+                self.failures
+                    .push(i, Rule::Framing, "tool call delta without an index");
+                continue;
+            };
+            let call_index = call_index as usize;
+            if call_index > state.tool_calls.len() {
+                self.failures.push(
+                    i,
+                    Rule::Ordering,
+                    format!(
+                        "tool call index {} skips index {}",
+                        call_index,
+                        state.tool_calls.len()
+                    ),
+                );
+                continue;
+            }
+            let id = call["id"].as_str();
+            let name = call["function"]["name"].as_str();
+            if call_index == state.tool_calls.len() {
+                if id.is_none() || name.is_none() {
+                    self.failures.push(
+                        i,
+                        Rule::Ordering,
+                        format!(
+                            "first delta of tool call {} lacks its id or function name",
+                            call_index
+                        ),
+                    );
+                }
+                state.tool_calls.push(ChatToolCall::default());
+            }
+            let tool = &mut state.tool_calls[call_index];
+            for (what, seen, new) in [
+                ("id", &mut tool.id, id),
+                ("function name", &mut tool.name, name),
+            ] {
+                match (seen.as_deref(), new) {
+                    (Some(before), Some(after)) if before != after => self.failures.push(
+                        i,
+                        Rule::IdStability,
+                        format!(
+                            "tool call {} {} changed from {:?} to {:?}",
+                            call_index, what, before, after
+                        ),
+                    ),
+                    (None, Some(after)) => *seen = Some(after.to_string()),
+                    _ => {}
+                }
+            }
+            if let Some(args) = call["function"]["arguments"].as_str() {
+                tool.arguments.push_str(args);
+            }
+        }
+
+        if let Some(reason) = choice["finish_reason"].as_str() {
+            if let Some(before) = &state.finish_reason {
+                self.failures.push(
+                    i,
+                    Rule::Ordering,
+                    format!(
+                        "choice {} finished twice ({:?}, then {:?})",
+                        index, before, reason
+                    ),
+                );
+            }
+            state.finish_reason = Some(reason.to_string());
+        }
+    }
+
+    fn finish(mut self, checker: &ConformanceChecker) -> Vec<ConformanceFailure> {
+        let last = self.last;
+        if !self.done {
+            self.failures.push(
+                last,
+                Rule::Framing,
+                "stream did not end with `data: [DONE]`",
+            );
+        }
+        if self.choices.is_empty() {
+            self.failures
+                .push(last, Rule::Ordering, "stream carried no choice deltas");
+        }
+
+        let mut indexes: Vec<_> = self.choices.keys().copied().collect();
+        indexes.sort_unstable();
+        let mut text = String::new();
+        let mut has_tool_calls = false;
+        for index in indexes {
+            let choice = &self.choices[&index];
+            match choice.finish_reason.as_deref() {
+                None => self.failures.push(
+                    last,
+                    Rule::Ordering,
+                    format!("choice {} never got a finish_reason", index),
+                ),
+                Some("tool_calls") if choice.tool_calls.is_empty() => self.failures.push(
+                    last,
+                    Rule::Content,
+                    format!(
+                        "choice {} finished with tool_calls but streamed none",
+                        index
+                    ),
+                ),
+                _ => {}
+            }
+            for (n, call) in choice.tool_calls.iter().enumerate() {
+                if let Err(e) = serde_json::from_str::<Value>(&call.arguments) {
+                    self.failures.push(
+                        last,
+                        Rule::Content,
+                        format!(
+                            "tool call {} of choice {} has arguments that are not JSON: {}",
+                            n, index, e
+                        ),
+                    );
+                }
+            }
+            has_tool_calls |= !choice.tool_calls.is_empty();
+            text.push_str(&choice.content);
+        }
+
+        // Hidden reasoning and tool-call framing are billed but not visible
+        // in text deltas, so only plain text streams are counted.
+        if let Some((at, usage)) = &self.usage {
+            let completion = as_u64(usage, "completion_tokens").unwrap_or(0);
+            let reasoning = usage["completion_tokens_details"]["reasoning_tokens"]
+                .as_u64()
+                .unwrap_or(0);
+            if !has_tool_calls && self.choices.len() == 1 {
+                if let Err(counted) =
+                    checker.tokens_match(&text, &[completion.saturating_sub(reasoning)])
+                {
+                    self.failures.push(
+                        *at,
+                        Rule::Usage,
+                        format!(
+                            "completion_tokens {} (excluding {} reasoning) but the deltas are {} tokens",
+                            completion, reasoning, counted
+                        ),
+                    );
+                }
+            }
+        }
+        self.failures.0
+    }
+}
+
+// ============================================================================
+// Responses
+// ============================================================================
+
+const TERMINAL: [&str; 3] = [
+    "response.completed",
+    "response.incomplete",
+    "response.failed",
+];
+
+struct ResponsesItem {
+    id: Value,
+    done: bool,
+    /// Concatenated deltas by (event family, part index)
+    deltas: HashMap<(&'static str, u64), String>,
+}
+
+#[derive(Default)]
+struct ResponsesCheck {
+    failures: Failures,
+    last: usize,
+    count: usize,
+    sequence: Option<u64>,
+    response_id: Option<Value>,
+    created_at: Option<Value>,
+    terminal: Option<(usize, Value)>,
+    items: Vec<ResponsesItem>,
+}
+
+impl ResponsesCheck {
+    fn event(&mut self, i: usize, sse: &SseEvent) {
+        self.last = i;
+        if sse.data == "[DONE]" {
+            return;
+        }
+        let event: Value = match serde_json::from_str(&sse.data) {
+            Ok(event) => event,
+            Err(e) => {
+                self.failures
+                    .push(i, Rule::Framing, format!("payload is not JSON: {}", e));
+                return;
+            }
+        };
+        let Some(ty) = event["type"].as_str() else {
+            self.failures
+                .push(i, Rule::Framing, "event payload has no `type`");
+            return;
+        };
+        if let Some(name) = &sse.event {
+            if name != ty {
+                self.failures.push(
+                    i,
+                    Rule::Framing,
+                    format!("SSE event name {:?} does not match type {:?}", name, ty),
+                );
+            }
+        }
+        if ty == "error" {
+            self.failures.push(
+                i,
+                Rule::Framing,
+                format!("stream reported an error: {}", event),
+            );
+        }
+
+        match as_u64(&event, "sequence_number") {
+            None => self
+                .failures
+                .push(i, Rule::Ordering, format!("{} has no sequence_number", ty)),
+            Some(n) => {
+                let expected = self.sequence.map_or(0, |s| s + 1);
+                if n != expected {
+                    self.failures.push(
+                        i,
+                        Rule::Ordering,
+                        format!("sequence_number {} where {} was expected", n, expected),
+                    );
+                }
+                self.sequence = Some(n);
+            }
+        }
+
+        if let Some((at, _)) = &self.terminal {
+            self.failures.push(
+                i,
+                Rule::Ordering,
+                format!("{} after the terminal event at {}", ty, at),
+            );
+        }
+        let position = self.count;
+        self.count += 1;
+        match (position, ty) {
+            (0, "response.created") => {}
+            (0, _) => self.failures.push(
+                i,
+                Rule::Ordering,
+                format!("stream starts with {}, not response.created", ty),
+            ),
+            (_, "response.created") => {
+                self.failures
+                    .push(i, Rule::Ordering, "response.created sent twice")
+            }
+            _ => {}
+        }
+
+        if let Some(response) = event.get("response") {
+            let f = &mut self.failures;
+            stable(
+                f,
+                i,
+                &mut self.response_id,
+                response.get("id"),
+                "response.id",
+            );
+            stable(
+                f,
+                i,
+                &mut self.created_at,
+                response.get("created_at"),
+                "response.created_at",
+            );
+            if TERMINAL.contains(&ty) {
+                self.terminal = Some((i, response.clone()));
+            }
+        }
+
+        match ty {
+            "response.output_item.added" => self.item_added(i, &event),
+            "response.output_item.done" => self.item_done(i, &event),
+            _ => {
+                if event.get("item_id").is_some() {
+                    self.item_event(i, ty, &event);
+                }
+            }
+        }
+    }
+
+    fn item_added(&mut self, i: usize, event: &Value) {
+        let index = as_u64(event, "output_index");
+        if index != Some(self.items.len() as u64) {
+            self.failures.push(
+                i,
+                Rule::Ordering,
+                format!(
+                    "output item added at output_index {:?}, expected {}",
+                    index,
+                    self.items.len()
+                ),
+            );
+        }
+        if let Some(open) = self.items.iter().position(|item| !item.done) {
+            self.failures.push(
+                i,
+                Rule::Ordering,
+                format!("output item added while item {} is still open", open),
+            );
+        }
+        self.items.push(ResponsesItem {
+            id: event["item"]["id"].clone(),
+            done: false,
+            deltas: HashMap::new(),
+        });
+    }
+
+    /// The open item an event refers to, checking its id and index agree
+    fn open_item(&mut self, i: usize, ty: &str, index: Option<u64>, id: &Value) -> Option<usize> {
+        let Some(n) = index.map(|n| n as usize).filter(|&n| n < self.items.len()) else {
+            self.failures.push(
+                i,
+                Rule::Ordering,
+                format!("{} for output_index {:?} that was never added", ty, index),
+            );
+            return None;
+        };
+        let item = &self.items[n];
+        if item.id != *id {
+            self.failures.push(
+                i,
+                Rule::IdStability,
+                format!(
+                    "{} refers to item {} at output_index {}, which was added as {}",
+                    ty, id, n, item.id
+                ),
+            );
+        }
+        if item.done {
+            self.failures.push(
+                i,
+                Rule::Ordering,
+                format!("{} after output item {} was done", ty, n),
+            );
+        }
+        Some(n)
+    }
+
+    fn item_done(&mut self, i: usize, event: &Value) {
+        let index = as_u64(event, "output_index");
+        if let Some(n) = self.open_item(i, "response.output_item.done", index, &event["item"]["id"])
+        {
+            self.items[n].done = true;
+            self.compare_item(i, n, &event["item"]);
+        }
+    }
+
+    fn item_event(&mut self, i: usize, ty: &str, event: &Value) {
+        let index = as_u64(event, "output_index");
+        let Some(n) = self.open_item(i, ty, index, &event["item_id"]) else {
+            return;
+        };
+        let (family, part) = match ty.strip_prefix("response.") {
+            Some(rest) if rest.starts_with("output_text.") => {
+                ("output_text", as_u64(event, "content_index").unwrap_or(0))
+            }
+            Some(rest) if rest.starts_with("reasoning_summary_text.") => (
+                "reasoning_summary_text",
+                as_u64(event, "summary_index").unwrap_or(0),
+            ),
+            Some(rest) if rest.starts_with("function_call_arguments.") => {
+                ("function_call_arguments", 0)
+            }
+            _ => return,
+        };
+        let text = self.items[n].deltas.entry((family, part)).or_default();
+        if ty.ends_with(".delta") {
+            text.push_str(event["delta"].as_str().unwrap_or_default());
+        } else if ty.ends_with(".done") {
+            let key = if family == "function_call_arguments" {
+                "arguments"
+            } else {
+                "text"
+            };
+            let done = event[key].as_str().unwrap_or_default();
+            if done != text {
+                self.failures.push(
+                    i,
+                    Rule::Content,
+                    format!("{} {:?} does not match its deltas {:?}", ty, done, text),
+                );
+            }
+        }
+    }
+
+    /// A finished item's parts against the deltas streamed for it
+    fn compare_item(&mut self, i: usize, n: usize, item: &Value) {
+        let parts: Vec<((&'static str, u64), &str)> = match item["type"].as_str() {
+            Some("message") => item["content"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .enumerate()
+                .filter_map(|(k, part)| Some((("output_text", k as u64), part["text"].as_str()?)))
+                .collect(),
+            Some("reasoning") => item["summary"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .enumerate()
+                .filter_map(|(k, part)| {
+                    Some((("reasoning_summary_text", k as u64), part["text"].as_str()?))
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+        for (key, text) in parts {
+            if let Some(streamed) = self.items[n].deltas.get(&key) {
+                if streamed != text {
+                    self.failures.push(
+                        i,
+                        Rule::Content,
+                        format!(
+                            "output item {} {} {} is {:?} but its deltas are {:?}",
+                            n, key.0, key.1, text, streamed
+                        ),
+                    );
+                }
+            }
+        }
+    }
+
+    fn finish(mut self, checker: &ConformanceChecker) -> Vec<ConformanceFailure> {
+        let last = self.last;
+        let Some((at, response)) = self.terminal.take() else {
+            self.failures.push(
+                last,
+                Rule::Ordering,
+                "stream ended without response.completed, .incomplete or .failed",
+            );
+            return self.failures.0;
+        };
+        for (n, item) in self.items.iter().enumerate() {
+            if !item.done {
+                self.failures.push(
+                    at,
+                    Rule::Ordering,
+                    format!("output item {} was never done", n),
+                );
+            }
+        }
+
+        let output = response["output"].as_array().map_or(&[][..], Vec::as_slice);
+        if output.len() != self.items.len() {
+            self.failures.push(
+                at,
+                Rule::Content,
+                format!(
+                    "final response has {} output items but {} were streamed",
+                    output.len(),
+                    self.items.len()
+                ),
+            );
+        }
+        for (n, (final_item, streamed)) in output.iter().zip(&self.items).enumerate() {
+            if final_item["id"] != streamed.id {
+                self.failures.push(
+                    at,
+                    Rule::IdStability,
+                    format!(
+                        "final output item {} is {} but was streamed as {}",
+                        n, final_item["id"], streamed.id
+                    ),
+                );
+            }
+        }
+        for (n, final_item) in output.iter().enumerate().take(self.items.len()) {
+            self.compare_item(at, n, final_item);
+        }
+
+        let Some(usage) = response.get("usage").filter(|u| !u.is_null()) else {
+            return self.failures.0;
+        };
+        let input = as_u64(usage, "input_tokens").unwrap_or(0);
+        let output_tokens = as_u64(usage, "output_tokens").unwrap_or(0);
+        let total = as_u64(usage, "total_tokens").unwrap_or(0);
+        let reasoning = usage["output_tokens_details"]["reasoning_tokens"]
+            .as_u64()
+            .unwrap_or(0);
+        // The OpenAI API counts reasoning inside output_tokens; llmsim
+        // reports it alongside. Either accounting is accepted.
+        if total != input + output_tokens && total != input + output_tokens + reasoning {
+            self.failures.push(
+                at,
+                Rule::Usage,
+                format!(
+                    "total_tokens {} != input_tokens {} + output_tokens {} (reasoning {})",
+                    total, input, output_tokens, reasoning
+                ),
+            );
+        }
+
+        let text_only = output
+            .iter()
+            .all(|item| matches!(item["type"].as_str(), Some("message" | "reasoning")));
+        let text: String = self
+            .items
+            .iter()
+            .filter_map(|item| item.deltas.get(&("output_text", 0)))
+            .map(String::as_str)
+            .collect();
+        if text_only {
+            let visible = [output_tokens, output_tokens.saturating_sub(reasoning)];
+            if let Err(counted) = checker.tokens_match(&text, &visible) {
+                self.failures.push(
+                    at,
+                    Rule::Usage,
+                    format!(
+                        "output_tokens {} (reasoning {}) but the text deltas are {} tokens",
+                        output_tokens, reasoning, counted
+                    ),
+                );
+            }
+        }
+        self.failures.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(id: &str, delta: Value, finish: Option<&str>) -> String {
+        let body = serde_json::json!({
+            "id": id,
+            "object": "chat.completion.chunk",
+            "created": 1,
+            "model": "gpt-5",
+            "choices": [{"index": 0, "delta": delta, "finish_reason": finish}],
+        });
+        format!("data: {}\n\n", body)
+    }
+
+    fn rules(failures: &[ConformanceFailure]) -> Vec<Rule> {
+        failures.iter().map(|f| f.rule).collect()
+    }
+
+    #[test]
+    fn test_parse_sse() {
+        let events = parse_sse(": ping\n\nevent: a\ndata: {\"x\":1}\n\ndata: [DONE]\n\n");
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].event.as_deref(), Some("a"));
+        assert_eq!(events[0].data, "{\"x\":1}");
+        assert_eq!(events[1].data, "[DONE]");
+    }
+
+    #[test]
+    fn test_chat_stream_passes() {
+        let body = [
+            chunk("c1", serde_json::json!({"role": "assistant"}), None),
+            chunk("c1", serde_json::json!({"content": "Hi there"}), None),
+            chunk("c1", serde_json::json!({}), Some("stop")),
+            "data: [DONE]\n\n".to_string(),
+        ]
+        .concat();
+        let checker = ConformanceChecker::new().with_token_counter(|t| t.split(' ').count());
+        assert_eq!(checker.check_chat_stream(&body), vec![]);
+    }
+
+    #[test]
+    fn test_chat_id_change_and_missing_done() {
+        let body = [
+            chunk("c1", serde_json::json!({"role": "assistant"}), None),
+            chunk("c2", serde_json::json!({"content": "Hi"}), Some("stop")),
+        ]
+        .concat();
+        let failures = ConformanceChecker::new().check_chat_stream(&body);
+        assert_eq!(rules(&failures), vec![Rule::IdStability, Rule::Framing]);
+        assert_eq!(failures[0].event, 1);
+    }
+
+    #[test]
+    fn test_chat_delta_after_finish() {
+        let body = [
+            chunk("c1", serde_json::json!({"role": "assistant"}), Some("stop")),
+            chunk("c1", serde_json::json!({"content": "late"}), None),
+            "data: [DONE]\n\n".to_string(),
+        ]
+        .concat();
+        let failures = ConformanceChecker::new().check_chat_stream(&body);
+        assert_eq!(rules(&failures), vec![Rule::Ordering]);
+    }
+
+    #[test]
+    fn test_chat_usage_mismatch() {
+        let usage = serde_json::json!({
+            "id": "c1", "object": "chat.completion.chunk", "created": 1, "model": "gpt-5",
+            "choices": [],
+            "usage": {"prompt_tokens": 3, "completion_tokens": 5, "total_tokens": 8},
+        });
+        let body = [
+            chunk(
+                "c1",
+                serde_json::json!({"role": "assistant", "content": "a b"}),
+                Some("stop"),
+            ),
+            format!("data: {}\n\n", usage),
+            "data: [DONE]\n\n".to_string(),
+        ]
+        .concat();
+        let checker = ConformanceChecker::new().with_token_counter(|t| t.split(' ').count());
+        assert_eq!(rules(&checker.check_chat_stream(&body)), vec![Rule::Usage]);
+        let lenient = checker.with_token_tolerance(3);
+        assert!(lenient.check_chat_stream(&body).is_empty());
+    }
+
+    #[test]
+    fn test_responses_sequence_gap_and_missing_terminal() {
+        let body = "event: response.created\ndata: {\"type\":\"response.created\",\"sequence_number\":0,\"response\":{\"id\":\"r1\"}}\n\n\
+                    event: response.in_progress\ndata: {\"type\":\"response.in_progress\",\"sequence_number\":2,\"response\":{\"id\":\"r1\"}}\n\n";
+        let failures = ConformanceChecker::new().check_responses_stream(body);
+        assert_eq!(rules(&failures), vec![Rule::Ordering, Rule::Ordering]);
+        assert!(failures[0].message.contains("sequence_number 2"));
+    }
+}
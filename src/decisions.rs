@@ -0,0 +1,312 @@
+// Decision Log Module
+// A machine-readable record of what the simulator decided for each request:
+// the latency profile and the delays it slept, the generator, any error it
+// injected and why, and how the request ended.
+//
+// Decision: one `Decision` per request, sent to every subscriber when the
+// request's last handle is dropped. Streams finish after their handler
+// returns, and a client can hang up mid-stream, so "the request is over"
+// is only known once nothing holds it any more; a request that never
+// reported completion or failure is `Abandoned`. Subscribers are plain
+// `std::sync::mpsc` channels so the core needs no tokio sync primitives, and
+// nothing is recorded while nobody is subscribed.
+//
+// Decision: a log can also keep its most recent decisions (`with_history`)
+// for whoever asks later, such as the server's `/llmsim/requests`. The ring
+// is filled where decisions are published, on the request's own task, so it
+// needs no reader thread and holds no more than its capacity however long
+// nobody looks.
+
+use crate::clock::{self, Instant};
+use crate::errors::SimulatedError;
+use crate::latency::LatencyProfile;
+use crate::sleep::{SleepFuture, Sleeper};
+use crate::stats::EndpointType;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+/// Characters of a request's prompt kept in its decision, from the end,
+/// where the latest turn is
+pub const PROMPT_EXCERPT_CHARS: usize = 200;
+
+/// Why the simulator failed a request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCause {
+    /// A running drill took the listener down or injected errors
+    Drill,
+    /// Shed by the configured capacity limit
+    LoadShedding,
+    /// Drawn by the configured error rates
+    Injected,
+    /// The model is retired on the deprecation schedule
+    Deprecation,
+    /// The request matched a content-keyed error rule
+    Rule,
+    /// The API key spent its `[rate_limits]` budget
+    RateLimited,
+    /// The request's `llmsim` extension asked for the error
+    Requested,
+    /// The request carried no live API key from `[keys]`
+    Unauthorized,
+}
+
+/// How a request ended
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum Outcome {
+    Completed {
+        input_tokens: u32,
+        output_tokens: u32,
+    },
+    /// Answered with this HTTP status instead of a completion
+    Failed { status: u16 },
+    /// Dropped before completing, e.g. the client left mid-stream
+    #[default]
+    Abandoned,
+}
+
+/// Everything the simulator decided for one request
+#[derive(Debug, Clone, PartialEq)]
+pub struct Decision {
+    /// Sequence number of the request in this log, from 1
+    pub request: u64,
+    pub model: String,
+    pub endpoint: EndpointType,
+    pub streaming: bool,
+    /// Latency profile the request was served with, after scaling. A custom
+    /// `LatencyModel` samples `delays` without it.
+    pub latency: LatencyProfile,
+    /// Every delay slept for the request, in order: time to first token
+    /// first, then the gaps between chunks
+    pub delays: Vec<Duration>,
+    /// Name of the generator that wrote the response
+    pub generator: String,
+    /// Error the simulator answered with instead of a response
+    pub error: Option<(ErrorCause, SimulatedError)>,
+    pub outcome: Outcome,
+    /// The last `PROMPT_EXCERPT_CHARS` characters of the prompt, when the
+    /// handler noted it
+    pub prompt: Option<String>,
+    /// End user the client named in the request (`user`, `metadata.user_id`)
+    pub user: Option<String>,
+    /// When the request began
+    pub started_at: SystemTime,
+    /// Wall time from the request beginning until it was over
+    pub elapsed: Duration,
+}
+
+/// Subscribers to the decisions of a pipeline's requests
+#[derive(Debug, Default)]
+pub struct DecisionLog {
+    subscribers: Mutex<Vec<Sender<Decision>>>,
+    next_request: AtomicU64,
+    /// Most recent decisions, oldest first, up to `history`
+    recent: Mutex<VecDeque<Decision>>,
+    history: usize,
+}
+
+impl DecisionLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A log that also keeps the last `history` decisions for `recent`
+    pub fn with_history(history: usize) -> Self {
+        Self {
+            history,
+            ..Self::default()
+        }
+    }
+
+    /// Up to `limit` of the kept decisions, newest first
+    pub fn recent(&self, limit: usize) -> Vec<Decision> {
+        self.recent
+            .lock()
+            .map(|recent| recent.iter().rev().take(limit).cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Receive the decision of every request that begins from now on
+    pub fn subscribe(&self) -> Receiver<Decision> {
+        let (tx, rx) = channel();
+        if let Ok(mut subscribers) = self.subscribers.lock() {
+            subscribers.push(tx);
+        }
+        rx
+    }
+
+    /// Whether anyone is listening; decisions are only recorded if so
+    pub fn has_subscribers(&self) -> bool {
+        self.subscribers
+            .lock()
+            .map(|subscribers| !subscribers.is_empty())
+            .unwrap_or(false)
+    }
+
+    /// Whether decisions are recorded: someone is subscribed or the log
+    /// keeps a history
+    pub fn is_recording(&self) -> bool {
+        self.history > 0 || self.has_subscribers()
+    }
+
+    /// Send `decision` to every subscriber, forgetting those that hung up,
+    /// and keep it in the history
+    pub fn publish(&self, decision: Decision) {
+        if self.history > 0 {
+            if let Ok(mut recent) = self.recent.lock() {
+                if recent.len() == self.history {
+                    recent.pop_front();
+                }
+                recent.push_back(decision.clone());
+            }
+        }
+        if let Ok(mut subscribers) = self.subscribers.lock() {
+            subscribers.retain(|tx| tx.send(decision.clone()).is_ok());
+        }
+    }
+
+    /// Start recording a request
+    pub(crate) fn record(
+        self: &Arc<Self>,
+        model: &str,
+        endpoint: EndpointType,
+        streaming: bool,
+        latency: LatencyProfile,
+        generator: &str,
+    ) -> Arc<Recording> {
+        let decision = Decision {
+            request: self.next_request.fetch_add(1, Ordering::Relaxed) + 1,
+            model: model.to_string(),
+            endpoint,
+            streaming,
+            latency,
+            delays: Vec::new(),
+            generator: generator.to_string(),
+            error: None,
+            outcome: Outcome::default(),
+            prompt: None,
+            user: None,
+            started_at: clock::system_now(),
+            elapsed: Duration::ZERO,
+        };
+        Arc::new(Recording {
+            log: self.clone(),
+            started: clock::now(),
+            decision: Mutex::new(decision),
+        })
+    }
+}
+
+/// The decision of an in-flight request, published when dropped
+#[derive(Debug)]
+pub(crate) struct Recording {
+    log: Arc<DecisionLog>,
+    started: Instant,
+    decision: Mutex<Decision>,
+}
+
+impl Recording {
+    pub(crate) fn update(&self, f: impl FnOnce(&mut Decision)) {
+        if let Ok(mut decision) = self.decision.lock() {
+            f(&mut decision);
+        }
+    }
+}
+
+impl Drop for Recording {
+    fn drop(&mut self) {
+        if let Ok(decision) = self.decision.get_mut() {
+            decision.elapsed = clock::elapsed(self.started);
+            self.log.publish(decision.clone());
+        }
+    }
+}
+
+/// The end of `prompt`, at most `PROMPT_EXCERPT_CHARS` characters
+pub(crate) fn prompt_excerpt(prompt: &str) -> String {
+    let skip = prompt.chars().count().saturating_sub(PROMPT_EXCERPT_CHARS);
+    prompt.chars().skip(skip).collect()
+}
+
+/// Sleeps with `inner`, noting each delay in the request's decision
+pub(crate) struct RecordingSleeper {
+    pub(crate) inner: Arc<dyn Sleeper>,
+    pub(crate) recording: Arc<Recording>,
+}
+
+impl Sleeper for RecordingSleeper {
+    fn sleep(&self, duration: Duration) -> SleepFuture {
+        self.recording
+            .update(|decision| decision.delays.push(duration));
+        self.inner.sleep(duration)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_published_when_last_handle_drops() {
+        let log = Arc::new(DecisionLog::new());
+        let rx = log.subscribe();
+        assert!(log.has_subscribers());
+
+        let recording = log.record(
+            "gpt-5",
+            EndpointType::ChatCompletions,
+            true,
+            LatencyProfile::instant(),
+            "lorem",
+        );
+        let held = recording.clone();
+        recording.update(|d| d.delays.push(Duration::from_millis(5)));
+        drop(recording);
+        assert!(rx.try_recv().is_err());
+
+        drop(held);
+        let decision = rx.try_recv().unwrap();
+        assert_eq!(decision.request, 1);
+        assert_eq!(decision.delays, [Duration::from_millis(5)]);
+        assert_eq!(decision.outcome, Outcome::Abandoned);
+        assert_eq!(decision.prompt, None);
+
+        drop(rx);
+        log.publish(decision);
+        assert!(!log.has_subscribers());
+    }
+
+    #[test]
+    fn test_history_keeps_the_latest() {
+        let log = Arc::new(DecisionLog::with_history(2));
+        assert!(log.is_recording());
+        assert!(!DecisionLog::new().is_recording());
+        for model in ["a", "b", "c"] {
+            log.record(
+                model,
+                EndpointType::Messages,
+                false,
+                LatencyProfile::instant(),
+                "lorem",
+            );
+        }
+        let models: Vec<String> = log.recent(10).into_iter().map(|d| d.model).collect();
+        assert_eq!(models, ["c", "b"]);
+        assert_eq!(log.recent(1)[0].request, 3);
+    }
+
+    #[test]
+    fn test_prompt_excerpt_keeps_the_end() {
+        assert_eq!(prompt_excerpt("short"), "short");
+        let long = format!(
+            "{}é{}",
+            "a".repeat(300),
+            "b".repeat(PROMPT_EXCERPT_CHARS - 1)
+        );
+        let excerpt = prompt_excerpt(&long);
+        assert_eq!(excerpt.chars().count(), PROMPT_EXCERPT_CHARS);
+        assert!(excerpt.starts_with('é'));
+    }
+}
@@ -0,0 +1,412 @@
+// Model Deprecation Module
+// Scheduled model retirement: after a sunset date or a number of requests a
+// model stops being served, and for a warning period before that its
+// responses carry `Deprecation` and `Sunset` headers.
+//
+// Decision: a schedule retires at a date, after a number of requests, or at
+// whichever comes first, since those are the two ways a retirement is
+// rehearsed (against the calendar, or "after the next N calls"). Dates are plain UTC days or RFC 3339 timestamps, parsed
+// here rather than pulling in a date crate for two formats. A count-based
+// schedule has no date up front, so its `Sunset` is the moment the count
+// ran out and its `Deprecation` the moment the warning began.
+
+use crate::clock;
+use crate::errors::SimulatedError;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+
+const SECONDS_PER_DAY: i64 = 86_400;
+
+/// When and how a model is retired
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeprecationSchedule {
+    /// Retirement date: `2026-12-01` (midnight UTC) or an RFC 3339 timestamp
+    #[serde(default)]
+    pub sunset: Option<String>,
+    /// Retire after serving this many requests instead of at a date
+    #[serde(default)]
+    pub after_requests: Option<u64>,
+    /// Days before `sunset` that responses carry the headers
+    #[serde(default = "default_warning_days")]
+    pub warning_days: f64,
+    /// Requests before `after_requests` runs out that carry the headers;
+    /// all of them if unset
+    #[serde(default)]
+    pub warning_requests: Option<u64>,
+    /// Error a retired model answers with
+    #[serde(default)]
+    pub error: RetirementError,
+}
+
+fn default_warning_days() -> f64 {
+    30.0
+}
+
+/// Error returned for a retired model
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RetirementError {
+    /// 404 `model_not_found`, as if the model never existed
+    #[default]
+    ModelNotFound,
+    /// 410 `model_deprecated`, naming the sunset date
+    Deprecated,
+}
+
+/// Where a model is in its retirement
+#[derive(Debug, Clone, PartialEq)]
+pub enum DeprecationStatus {
+    /// Still served, with headers announcing the retirement
+    Warning {
+        /// Unix time the model was deprecated
+        deprecated_at: i64,
+        /// Unix time it retires, when known
+        sunset_at: Option<i64>,
+    },
+    /// No longer served
+    Retired {
+        sunset_at: i64,
+        error: RetirementError,
+    },
+}
+
+impl DeprecationStatus {
+    /// `Deprecation` (RFC 9745) and `Sunset` (RFC 8594) response headers
+    pub fn headers(&self) -> Vec<(&'static str, String)> {
+        match self {
+            DeprecationStatus::Warning {
+                deprecated_at,
+                sunset_at,
+            } => {
+                let mut headers = vec![("deprecation", format!("@{}", deprecated_at))];
+                headers.extend(sunset_at.map(|at| ("sunset", http_date(at))));
+                headers
+            }
+            DeprecationStatus::Retired { sunset_at, .. } => {
+                vec![("sunset", http_date(*sunset_at))]
+            }
+        }
+    }
+
+    /// Error a request for `model` gets, once retired
+    pub fn error(&self, model: &str) -> Option<SimulatedError> {
+        match self {
+            DeprecationStatus::Warning { .. } => None,
+            DeprecationStatus::Retired {
+                error: RetirementError::ModelNotFound,
+                ..
+            } => Some(SimulatedError::ModelNotFound {
+                model: model.to_string(),
+            }),
+            DeprecationStatus::Retired {
+                sunset_at,
+                error: RetirementError::Deprecated,
+            } => Some(SimulatedError::ModelDeprecated {
+                model: model.to_string(),
+                sunset: http_date(*sunset_at),
+            }),
+        }
+    }
+}
+
+/// A schedule with its request count and the moments it passed its marks
+#[derive(Debug)]
+struct Tracked {
+    schedule: DeprecationSchedule,
+    sunset_at: Option<i64>,
+    requests: AtomicU64,
+    /// Unix time the count-based warning began / ran out; 0 until then
+    warned_at: AtomicI64,
+    retired_at: AtomicI64,
+}
+
+/// Deprecation schedules keyed by model
+#[derive(Debug, Default)]
+pub struct Deprecations {
+    models: BTreeMap<String, Tracked>,
+}
+
+impl Deprecations {
+    /// Track `schedules`; a `sunset` that does not parse is an error
+    pub fn new(schedules: &BTreeMap<String, DeprecationSchedule>) -> Result<Self, String> {
+        let models = schedules
+            .iter()
+            .map(|(model, schedule)| {
+                let sunset_at = schedule
+                    .sunset
+                    .as_deref()
+                    .map(|date| {
+                        parse_date(date).ok_or_else(|| {
+                            format!("[deprecations.\"{}\"] invalid sunset {:?}", model, date)
+                        })
+                    })
+                    .transpose()?;
+                let tracked = Tracked {
+                    schedule: schedule.clone(),
+                    sunset_at,
+                    requests: AtomicU64::new(0),
+                    warned_at: AtomicI64::new(0),
+                    retired_at: AtomicI64::new(0),
+                };
+                Ok((model.clone(), tracked))
+            })
+            .collect::<Result<_, String>>()?;
+        Ok(Self { models })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.models.is_empty()
+    }
+
+    /// Count a request for `model` and say where it is in its retirement;
+    /// `None` for models without a schedule or before the warning period
+    pub fn check(&self, model: &str) -> Option<DeprecationStatus> {
+        self.check_at(model, clock::unix_seconds() as i64)
+    }
+
+    fn check_at(&self, model: &str, now: i64) -> Option<DeprecationStatus> {
+        let tracked = self.models.get(model)?;
+        let schedule = &tracked.schedule;
+        let served = tracked.requests.fetch_add(1, Ordering::Relaxed);
+
+        if let Some(sunset_at) = tracked.sunset_at {
+            if now >= sunset_at {
+                return Some(DeprecationStatus::Retired {
+                    sunset_at,
+                    error: schedule.error,
+                });
+            }
+            let warning = (schedule.warning_days * SECONDS_PER_DAY as f64) as i64;
+            let deprecated_at = sunset_at - warning;
+            // With no request limit as well, the date alone decides
+            if schedule.after_requests.is_none() {
+                return (now >= deprecated_at).then_some(DeprecationStatus::Warning {
+                    deprecated_at,
+                    sunset_at: Some(sunset_at),
+                });
+            }
+        }
+
+        let limit = schedule.after_requests?;
+        if served >= limit {
+            let sunset_at = first_time(&tracked.retired_at, now);
+            return Some(DeprecationStatus::Retired {
+                sunset_at,
+                error: schedule.error,
+            });
+        }
+        let warn_from = schedule
+            .warning_requests
+            .map_or(0, |warning| limit.saturating_sub(warning));
+        (served >= warn_from).then(|| DeprecationStatus::Warning {
+            deprecated_at: first_time(&tracked.warned_at, now),
+            sunset_at: tracked.sunset_at,
+        })
+    }
+}
+
+/// The time stored in `slot`, storing `now` if none was yet
+fn first_time(slot: &AtomicI64, now: i64) -> i64 {
+    match slot.compare_exchange(0, now, Ordering::Relaxed, Ordering::Relaxed) {
+        Ok(_) => now,
+        Err(earlier) => earlier,
+    }
+}
+
+/// Unix time of `2026-12-01` (midnight UTC) or `2026-12-01T12:30:00Z`
+/// (an offset such as `+02:00` is applied; fractional seconds are ignored)
+pub fn parse_date(text: &str) -> Option<i64> {
+    let number = |s: &str| s.parse::<i64>().ok();
+    let (date, time) = match text.split_once(['T', ' ']) {
+        Some((date, time)) => (date, Some(time)),
+        None => (text, None),
+    };
+    let mut parts = date.splitn(3, '-');
+    let (year, month, day) = (
+        number(parts.next()?)?,
+        number(parts.next()?)?,
+        number(parts.next()?)?,
+    );
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    let mut seconds = days_from_civil(year, month, day) * SECONDS_PER_DAY;
+
+    if let Some(time) = time {
+        let (clock, offset) = if let Some(clock) = time.strip_suffix(['Z', 'z']) {
+            (clock, 0)
+        } else if let Some(at) = time.rfind(['+', '-']) {
+            let (clock, offset) = time.split_at(at);
+            let (hours, minutes) = offset[1..].split_once(':')?;
+            let seconds = number(hours)? * 3600 + number(minutes)? * 60;
+            (
+                clock,
+                if offset.starts_with('-') {
+                    -seconds
+                } else {
+                    seconds
+                },
+            )
+        } else {
+            (time, 0)
+        };
+        let clock = clock.split('.').next()?;
+        let mut fields = clock.split(':');
+        let hours = number(fields.next()?)?;
+        let minutes = number(fields.next()?)?;
+        let secs = fields.next().map_or(Some(0), number)?;
+        if hours > 23 || minutes > 59 || secs > 60 {
+            return None;
+        }
+        seconds += hours * 3600 + minutes * 60 + secs - offset;
+    }
+    Some(seconds)
+}
+
+/// `at` as an HTTP-date, e.g. `Tue, 01 Dec 2026 00:00:00 GMT`
+pub fn http_date(at: i64) -> String {
+    const WEEKDAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+    let days = at.div_euclid(SECONDS_PER_DAY);
+    let secs = at.rem_euclid(SECONDS_PER_DAY);
+    let (year, month, day) = civil_from_days(days);
+    format!(
+        "{}, {:02} {} {} {:02}:{:02}:{:02} GMT",
+        WEEKDAYS[days.rem_euclid(7) as usize],
+        day,
+        MONTHS[month as usize - 1],
+        year,
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60
+    )
+}
+
+/// `at` as an RFC 3339 timestamp in UTC, e.g. `2026-12-01T00:00:00Z`
+pub fn rfc3339(at: i64) -> String {
+    let secs = at.rem_euclid(SECONDS_PER_DAY);
+    let (year, month, day) = civil_from_days(at.div_euclid(SECONDS_PER_DAY));
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60
+    )
+}
+
+/// Days since 1970-01-01 of a proleptic Gregorian date
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Inverse of `days_from_civil`
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schedule(toml: &str) -> Deprecations {
+        let schedules: BTreeMap<String, DeprecationSchedule> = toml::from_str(toml).unwrap();
+        Deprecations::new(&schedules).unwrap()
+    }
+
+    #[test]
+    fn test_dates() {
+        assert_eq!(parse_date("1970-01-01"), Some(0));
+        assert_eq!(parse_date("2026-12-01"), Some(1_796_083_200));
+        assert_eq!(parse_date("2026-12-01T01:00:00Z"), Some(1_796_086_800));
+        assert_eq!(parse_date("2026-12-01T03:00:00+02:00"), Some(1_796_086_800));
+        assert_eq!(parse_date("2026-13-01"), None);
+        assert_eq!(parse_date("soon"), None);
+        assert_eq!(http_date(1_796_083_200), "Tue, 01 Dec 2026 00:00:00 GMT");
+        assert_eq!(http_date(951_782_400), "Tue, 29 Feb 2000 00:00:00 GMT");
+        assert_eq!(rfc3339(1_796_086_805), "2026-12-01T01:00:05Z");
+    }
+
+    #[test]
+    fn test_date_schedule() {
+        let deprecations = schedule(
+            r#"
+["gpt-4"]
+sunset = "2026-12-01"
+warning_days = 10
+"#,
+        );
+        let sunset = 1_796_083_200;
+        assert_eq!(deprecations.check_at("gpt-4", sunset - 11 * 86_400), None);
+        let warning = deprecations
+            .check_at("gpt-4", sunset - 86_400)
+            .unwrap()
+            .headers();
+        assert_eq!(
+            warning,
+            [
+                ("deprecation", format!("@{}", sunset - 10 * 86_400)),
+                ("sunset", "Tue, 01 Dec 2026 00:00:00 GMT".to_string())
+            ]
+        );
+        let retired = deprecations.check_at("gpt-4", sunset).unwrap();
+        assert!(matches!(
+            retired.error("gpt-4"),
+            Some(SimulatedError::ModelNotFound { .. })
+        ));
+        assert_eq!(deprecations.check_at("gpt-5", sunset), None);
+    }
+
+    #[test]
+    fn test_count_schedule() {
+        let deprecations = schedule(
+            r#"
+["gpt-4"]
+after_requests = 3
+warning_requests = 1
+error = "deprecated"
+"#,
+        );
+        assert_eq!(deprecations.check_at("gpt-4", 100), None);
+        assert_eq!(deprecations.check_at("gpt-4", 100), None);
+        assert_eq!(
+            deprecations.check_at("gpt-4", 200),
+            Some(DeprecationStatus::Warning {
+                deprecated_at: 200,
+                sunset_at: None
+            })
+        );
+        let retired = deprecations.check_at("gpt-4", 300).unwrap();
+        assert_eq!(retired.headers(), [("sunset", http_date(300))]);
+        assert_eq!(
+            retired.error("gpt-4").unwrap().status_code(),
+            410,
+            "deprecated models are gone"
+        );
+        // The sunset stays at the moment the count ran out
+        assert_eq!(
+            deprecations.check_at("gpt-4", 400).unwrap().headers(),
+            [("sunset", http_date(300))]
+        );
+    }
+}
@@ -0,0 +1,440 @@
+// Drills Module
+// Failover drills: named failure scenarios that run for a fixed time, e.g.
+// "region eu-west hard down for 120s" or "latency 5x everywhere for 60s",
+// started on demand instead of by editing config and restarting.
+//
+// Decision: drills live on one board shared by the main listener and every
+// region, and the pipeline reads their combined effect when a request
+// begins. Expired drills are not swept by a timer; they simply stop matching
+// once their end time passes and are dropped the next time the board is
+// read, so an idle server costs nothing. A storm's rate is likewise not
+// stepped by a timer but read off its curve from the time since it started.
+
+use crate::clock::{self, Instant};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::RwLock;
+use std::time::Duration;
+
+/// What a drill does to the requests it applies to
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum DrillKind {
+    /// Hard down: every request fails with 503
+    Outage,
+    /// Every latency profile is multiplied by `factor`
+    Latency { factor: f64 },
+    /// `rate` (0.0-1.0) of requests fail with 500
+    Errors { rate: f64 },
+    /// A 429 storm: the share of requests rate limited eases up to
+    /// `peak_rate` over `ramp_secs`, holds for `hold_secs`, then eases back
+    /// down over the rest of the drill
+    Storm {
+        #[serde(default = "default_peak_rate")]
+        peak_rate: f64,
+        ramp_secs: u64,
+        hold_secs: u64,
+        /// `Retry-After` of the storm's 429s
+        #[serde(default = "default_storm_retry_after")]
+        retry_after_secs: u32,
+    },
+}
+
+fn default_peak_rate() -> f64 {
+    0.98
+}
+
+fn default_storm_retry_after() -> u32 {
+    1
+}
+
+/// Where a storm is on its curve
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StormPhase {
+    Ramp,
+    Hold,
+    Recover,
+}
+
+/// A storm's phase and its current share of rate limited requests
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct StormProgress {
+    pub phase: StormPhase,
+    pub rate_limit_rate: f64,
+}
+
+impl DrillSpec {
+    /// Progress of a storm `elapsed` after it started; `None` for other kinds
+    pub fn storm_progress(&self, elapsed: Duration) -> Option<StormProgress> {
+        let DrillKind::Storm {
+            peak_rate,
+            ramp_secs,
+            hold_secs,
+            ..
+        } = self.kind
+        else {
+            return None;
+        };
+        // Eases in and out, so the rate changes slowest at the ends of a
+        // phase, like an incident building up and draining
+        let smoothstep = |x: f64| {
+            let x = x.clamp(0.0, 1.0);
+            x * x * (3.0 - 2.0 * x)
+        };
+        let t = elapsed.as_secs_f64();
+        let (ramp, hold) = (ramp_secs as f64, hold_secs as f64);
+        let recover = (self.duration_secs as f64 - ramp - hold).max(0.0);
+        let (phase, share) = if t < ramp {
+            (StormPhase::Ramp, smoothstep(t / ramp))
+        } else if t < ramp + hold || recover == 0.0 {
+            (StormPhase::Hold, 1.0)
+        } else {
+            (
+                StormPhase::Recover,
+                1.0 - smoothstep((t - ramp - hold) / recover),
+            )
+        };
+        Some(StormProgress {
+            phase,
+            rate_limit_rate: peak_rate * share,
+        })
+    }
+}
+
+/// A drill to start
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DrillSpec {
+    #[serde(flatten)]
+    pub kind: DrillKind,
+    /// Region the drill targets; every listener when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
+    pub duration_secs: u64,
+}
+
+/// A running drill, as reported by the API and in stats
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DrillStatus {
+    pub id: String,
+    #[serde(flatten)]
+    pub spec: DrillSpec,
+    pub remaining_secs: u64,
+    /// Where a storm is on its curve, so polling stats shows the timeline
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub storm: Option<StormProgress>,
+}
+
+/// Combined effect of the drills active for one listener
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DrillEffects {
+    pub down: bool,
+    pub latency_factor: f64,
+    pub error_rate: f64,
+    /// Share of requests failing with 429, from storms
+    pub rate_limit_rate: f64,
+    pub retry_after_seconds: u32,
+}
+
+impl DrillEffects {
+    /// No active drill
+    pub const NONE: Self = Self {
+        down: false,
+        latency_factor: 1.0,
+        error_rate: 0.0,
+        rate_limit_rate: 0.0,
+        retry_after_seconds: 0,
+    };
+}
+
+/// Why a drill could not be started
+#[derive(Debug, thiserror::Error, PartialEq)]
+pub enum DrillError {
+    #[error("unknown region '{0}'")]
+    UnknownRegion(String),
+    #[error("duration_secs must be greater than 0")]
+    NoDuration,
+    #[error("latency factor must be a positive number")]
+    InvalidFactor,
+    #[error("error rate must be between 0.0 and 1.0")]
+    InvalidRate,
+    #[error("ramp_secs and hold_secs must fit in duration_secs")]
+    StormTooLong,
+}
+
+struct ActiveDrill {
+    id: u64,
+    spec: DrillSpec,
+    started: Instant,
+    ends: Instant,
+}
+
+impl ActiveDrill {
+    fn status(&self, now: Instant) -> DrillStatus {
+        DrillStatus {
+            id: drill_id(self.id),
+            spec: self.spec.clone(),
+            remaining_secs: (self.ends - now).as_secs_f64().ceil() as u64,
+            storm: self.spec.storm_progress(now - self.started),
+        }
+    }
+}
+
+/// Board of running drills
+pub struct Drills {
+    regions: Vec<String>,
+    active: RwLock<Vec<ActiveDrill>>,
+    next_id: AtomicU64,
+}
+
+impl Drills {
+    /// Board for a server with the given named regions
+    pub fn new(regions: Vec<String>) -> Self {
+        Self {
+            regions,
+            active: RwLock::new(Vec::new()),
+            next_id: AtomicU64::new(1),
+        }
+    }
+
+    /// Start a drill now
+    pub fn start(&self, spec: DrillSpec) -> Result<DrillStatus, DrillError> {
+        if let Some(region) = spec.region.as_ref().filter(|r| !self.regions.contains(r)) {
+            return Err(DrillError::UnknownRegion(region.clone()));
+        }
+        if spec.duration_secs == 0 {
+            return Err(DrillError::NoDuration);
+        }
+        match spec.kind {
+            DrillKind::Latency { factor } if !(factor.is_finite() && factor > 0.0) => {
+                return Err(DrillError::InvalidFactor)
+            }
+            DrillKind::Errors { rate } if !(0.0..=1.0).contains(&rate) => {
+                return Err(DrillError::InvalidRate)
+            }
+            DrillKind::Storm { peak_rate, .. } if !(0.0..=1.0).contains(&peak_rate) => {
+                return Err(DrillError::InvalidRate)
+            }
+            DrillKind::Storm {
+                ramp_secs,
+                hold_secs,
+                ..
+            } if ramp_secs.saturating_add(hold_secs) > spec.duration_secs => {
+                return Err(DrillError::StormTooLong)
+            }
+            _ => {}
+        }
+
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let started = clock::now();
+        let drill = ActiveDrill {
+            id,
+            ends: started + Duration::from_secs(spec.duration_secs),
+            spec,
+            started,
+        };
+        let status = drill.status(started);
+        if let Ok(mut active) = self.active.write() {
+            active.retain(|d| d.ends > started);
+            active.push(drill);
+        }
+        Ok(status)
+    }
+
+    /// Stop a drill early; false if it is unknown or already over
+    pub fn cancel(&self, id: &str) -> bool {
+        let Ok(mut active) = self.active.write() else {
+            return false;
+        };
+        let now = clock::now();
+        active.retain(|d| d.ends > now);
+        let before = active.len();
+        active.retain(|d| drill_id(d.id) != id);
+        active.len() < before
+    }
+
+    /// Drills still running, oldest first
+    pub fn active(&self) -> Vec<DrillStatus> {
+        let now = clock::now();
+        let Ok(active) = self.active.read() else {
+            return Vec::new();
+        };
+        active
+            .iter()
+            .filter(|d| d.ends > now)
+            .map(|d| d.status(now))
+            .collect()
+    }
+
+    /// Effect of the running drills on a listener: `None` for the main
+    /// one, or a region name
+    pub fn effects(&self, region: Option<&str>) -> DrillEffects {
+        let now = clock::now();
+        let Ok(active) = self.active.read() else {
+            return DrillEffects::NONE;
+        };
+        let mut effects = DrillEffects::NONE;
+        for drill in active.iter().filter(|d| d.ends > now) {
+            if drill.spec.region.is_some() && drill.spec.region.as_deref() != region {
+                continue;
+            }
+            match drill.spec.kind {
+                DrillKind::Outage => effects.down = true,
+                DrillKind::Latency { factor } => effects.latency_factor *= factor,
+                DrillKind::Errors { rate } => effects.error_rate = effects.error_rate.max(rate),
+                DrillKind::Storm {
+                    retry_after_secs, ..
+                } => {
+                    let rate = drill
+                        .spec
+                        .storm_progress(now - drill.started)
+                        .map_or(0.0, |storm| storm.rate_limit_rate);
+                    if rate > effects.rate_limit_rate {
+                        effects.rate_limit_rate = rate;
+                        effects.retry_after_seconds = retry_after_secs;
+                    }
+                }
+            }
+        }
+        effects
+    }
+}
+
+impl Default for Drills {
+    fn default() -> Self {
+        Self::new(Vec::new())
+    }
+}
+
+fn drill_id(n: u64) -> String {
+    format!("drill_{}", n)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec(kind: DrillKind, region: Option<&str>) -> DrillSpec {
+        DrillSpec {
+            kind,
+            region: region.map(str::to_string),
+            duration_secs: 60,
+        }
+    }
+
+    #[test]
+    fn test_effects_by_region() {
+        let drills = Drills::new(vec!["eu".to_string(), "us".to_string()]);
+        drills.start(spec(DrillKind::Outage, Some("eu"))).unwrap();
+        drills
+            .start(spec(DrillKind::Latency { factor: 5.0 }, None))
+            .unwrap();
+
+        let eu = drills.effects(Some("eu"));
+        assert!(eu.down);
+        assert_eq!(eu.latency_factor, 5.0);
+        let us = drills.effects(Some("us"));
+        assert!(!us.down);
+        assert_eq!(us.latency_factor, 5.0);
+        assert!(!drills.effects(None).down);
+    }
+
+    #[test]
+    fn test_start_validates() {
+        let drills = Drills::new(vec!["eu".to_string()]);
+        assert_eq!(
+            drills.start(spec(DrillKind::Outage, Some("mars"))),
+            Err(DrillError::UnknownRegion("mars".to_string()))
+        );
+        assert_eq!(
+            drills.start(spec(DrillKind::Latency { factor: 0.0 }, None)),
+            Err(DrillError::InvalidFactor)
+        );
+        assert_eq!(
+            drills.start(spec(DrillKind::Errors { rate: 1.5 }, None)),
+            Err(DrillError::InvalidRate)
+        );
+        let mut zero = spec(DrillKind::Outage, None);
+        zero.duration_secs = 0;
+        assert_eq!(drills.start(zero), Err(DrillError::NoDuration));
+        assert!(drills.active().is_empty());
+    }
+
+    #[test]
+    fn test_cancel() {
+        let drills = Drills::default();
+        let a = drills.start(spec(DrillKind::Outage, None)).unwrap();
+        let b = drills
+            .start(spec(DrillKind::Errors { rate: 0.5 }, None))
+            .unwrap();
+        assert_eq!(a.id, "drill_1");
+        assert_eq!(drills.active().len(), 2);
+
+        assert!(drills.cancel(&a.id));
+        assert!(!drills.cancel(&a.id));
+        let active = drills.active();
+        assert_eq!(active.len(), 1);
+        assert_eq!(active[0].id, b.id);
+        assert_eq!(active[0].remaining_secs, 60);
+        assert_eq!(
+            drills.effects(None),
+            DrillEffects {
+                error_rate: 0.5,
+                ..DrillEffects::NONE
+            }
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_storm_curve() {
+        let drills = Drills::default();
+        let storm = DrillKind::Storm {
+            peak_rate: 0.9,
+            ramp_secs: 10,
+            hold_secs: 20,
+            retry_after_secs: 3,
+        };
+        drills.start(spec(storm, None)).unwrap();
+        let mut rates = Vec::new();
+        for _ in 0..6 {
+            let effects = drills.effects(None);
+            rates.push((effects.rate_limit_rate * 1000.0).round() / 1000.0);
+            tokio::time::advance(Duration::from_secs(5)).await;
+        }
+        // Ramp (0s, 5s), hold (10s-25s), then recovery over the last 30s
+        assert_eq!(rates, [0.0, 0.45, 0.9, 0.9, 0.9, 0.9]);
+        tokio::time::advance(Duration::from_secs(15)).await;
+        let active = drills.active();
+        let storm = active[0].storm.unwrap();
+        assert_eq!(storm.phase, StormPhase::Recover);
+        assert!((storm.rate_limit_rate - 0.45).abs() < 1e-9);
+        assert_eq!(drills.effects(None).retry_after_seconds, 3);
+        tokio::time::advance(Duration::from_secs(15)).await;
+        assert_eq!(drills.effects(None), DrillEffects::NONE);
+    }
+
+    #[test]
+    fn test_storm_must_fit() {
+        let drills = Drills::default();
+        let storm = DrillKind::Storm {
+            peak_rate: 0.98,
+            ramp_secs: 40,
+            hold_secs: 30,
+            retry_after_secs: 1,
+        };
+        assert_eq!(
+            drills.start(spec(storm, None)),
+            Err(DrillError::StormTooLong)
+        );
+    }
+
+    #[test]
+    fn test_spec_json() {
+        let spec: DrillSpec = serde_json::from_str(
+            r#"{"kind": "latency", "factor": 5, "region": "eu", "duration_secs": 60}"#,
+        )
+        .unwrap();
+        assert_eq!(spec.kind, DrillKind::Latency { factor: 5.0 });
+        assert_eq!(spec.region.as_deref(), Some("eu"));
+    }
+}
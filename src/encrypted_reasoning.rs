@@ -0,0 +1,137 @@
+// Encrypted Reasoning
+// Opaque `encrypted_content` for reasoning items, returned with
+// `include: ["reasoning.encrypted_content"]` and accepted back as input, so
+// stateless (`store: false`) reasoning workflows can be exercised offline.
+//
+// Decision: blobs are shaped like the API's, which are Fernet tokens: a 0x80
+// version byte, a big-endian timestamp, a 16-byte IV, ciphertext in 16-byte
+// blocks and a 32-byte MAC, all URL-safe base64, so they start `gAAAAA` as
+// the real ones do. Nothing is encrypted: the "ciphertext" is random, sized
+// by the reasoning tokens behind it, and the MAC a fixed-key checksum over
+// the rest. Checking the MAC is enough to tell a blob this simulator minted
+// from a truncated, edited or made-up one, which is what a client that
+// mangles the round trip needs to hear about; any llmsim process accepts
+// any other's blobs.
+
+use rand::RngExt;
+
+const VERSION: u8 = 0x80;
+const HEADER: usize = 1 + 8 + 16;
+const BLOCK: usize = 16;
+const MAC: usize = 32;
+/// Ciphertext bytes per reasoning token, before rounding up to a block
+const BYTES_PER_TOKEN: usize = 4;
+/// Most ciphertext blocks in a blob, however long the reasoning
+const MAX_BLOCKS: usize = 256;
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+/// A new blob standing for `reasoning_tokens` tokens of reasoning
+pub fn encrypt(reasoning_tokens: u32) -> String {
+    let blocks = (reasoning_tokens as usize * BYTES_PER_TOKEN)
+        .div_ceil(BLOCK)
+        .clamp(1, MAX_BLOCKS);
+    let mut token = Vec::with_capacity(HEADER + blocks * BLOCK + MAC);
+    token.push(VERSION);
+    token.extend_from_slice(&crate::clock::unix_seconds().to_be_bytes());
+    let mut rng = rand::rng();
+    token.extend((0..16 + blocks * BLOCK).map(|_| rng.random::<u8>()));
+    let mac = mac(&token);
+    token.extend_from_slice(&mac);
+    encode(&token)
+}
+
+/// Whether `blob` is one `encrypt` minted, whole and unchanged
+pub fn verify(blob: &str) -> bool {
+    let Some(token) = decode(blob) else {
+        return false;
+    };
+    let ciphertext = token.len().saturating_sub(HEADER + MAC);
+    if token.first() != Some(&VERSION) || ciphertext == 0 || ciphertext % BLOCK != 0 {
+        return false;
+    }
+    let (signed, mac_bytes) = token.split_at(token.len() - MAC);
+    mac(signed) == mac_bytes
+}
+
+/// 32 bytes of FNV-1a over `data`, one 8-byte lane per seed
+fn mac(data: &[u8]) -> [u8; MAC] {
+    const SEEDS: [u64; 4] = [
+        0xcbf2_9ce4_8422_2325,
+        0x6c6c_6d73_696d_2d31,
+        0x7265_6173_6f6e_696e,
+        0x656e_6372_7970_7465,
+    ];
+    let mut out = [0; MAC];
+    for (lane, seed) in out.chunks_mut(8).zip(SEEDS) {
+        let hash = data.iter().fold(seed, |hash, byte| {
+            (hash ^ u64::from(*byte)).wrapping_mul(0x0000_0100_0000_01b3)
+        });
+        lane.copy_from_slice(&hash.to_be_bytes());
+    }
+    out
+}
+
+/// URL-safe base64 with padding, as Fernet writes it
+fn encode(data: &[u8]) -> String {
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, b)| n | u32::from(*b) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+fn decode(text: &str) -> Option<Vec<u8>> {
+    let text = text.trim_end_matches('=');
+    let mut out = Vec::with_capacity(text.len() * 3 / 4);
+    let (mut bits, mut n) = (0u32, 0u32);
+    for c in text.bytes() {
+        let value = BASE64.iter().position(|b| *b == c)? as u32;
+        n = n << 6 | value;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((n >> bits) as u8);
+            n &= (1 << bits) - 1;
+        }
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let blob = encrypt(300);
+        assert!(blob.starts_with("gAAAAA"));
+        assert!(verify(&blob));
+        // Longer reasoning, longer blob
+        assert!(encrypt(3000).len() > blob.len());
+
+        // Truncated, edited or made up
+        assert!(!verify(&blob[..blob.len() - 4]));
+        let mut edited = blob.clone().into_bytes();
+        edited[40] = if edited[40] == b'A' { b'B' } else { b'A' };
+        assert!(!verify(&String::from_utf8(edited).unwrap()));
+        assert!(!verify("gAAAAABnot-a-real-token"));
+        assert!(!verify("not base64!"));
+    }
+
+    #[test]
+    fn test_base64() {
+        assert_eq!(encode(b"fo"), "Zm8=");
+        assert_eq!(encode(&[0xfb, 0xff]), "-_8=");
+        assert_eq!(decode("-_8=").unwrap(), vec![0xfb, 0xff]);
+    }
+}
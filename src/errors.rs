@@ -117,12 +117,20 @@ pub enum SimulatedError {
     ServerError,
     /// Service unavailable (HTTP 503)
     ServiceUnavailable,
+    /// Shed by the capacity limit (HTTP 503)
+    Overloaded { retry_after_seconds: u32 },
     /// Request timeout
     Timeout { after: Duration },
+    /// A held request finally answered with 408 Request Timeout
+    RequestTimeout,
     /// Invalid request (HTTP 400)
     InvalidRequest { message: String },
     /// Authentication error (HTTP 401)
     AuthenticationError,
+    /// Retired model reported as unknown (HTTP 404)
+    ModelNotFound { model: String },
+    /// Retired model reported as deprecated since `sunset` (HTTP 410)
+    ModelDeprecated { model: String, sunset: String },
 }
 
 impl SimulatedError {
@@ -132,9 +140,13 @@ impl SimulatedError {
             SimulatedError::RateLimit { .. } => 429,
             SimulatedError::ServerError => 500,
             SimulatedError::ServiceUnavailable => 503,
+            SimulatedError::Overloaded { .. } => 503,
             SimulatedError::Timeout { .. } => 504,
+            SimulatedError::RequestTimeout => 408,
             SimulatedError::InvalidRequest { .. } => 400,
             SimulatedError::AuthenticationError => 401,
+            SimulatedError::ModelNotFound { .. } => 404,
+            SimulatedError::ModelDeprecated { .. } => 410,
         }
     }
 
@@ -146,15 +158,35 @@ impl SimulatedError {
             SimulatedError::ServiceUnavailable => {
                 ErrorResponse::new("Service temporarily unavailable", "service_unavailable")
             }
+            SimulatedError::Overloaded { .. } => ErrorResponse::new(
+                "The server is currently overloaded with other requests. Please retry your request.",
+                "service_unavailable",
+            ),
             SimulatedError::Timeout { .. } => {
                 ErrorResponse::new("Request timed out", "timeout_error")
             }
+            SimulatedError::RequestTimeout => ErrorResponse::new(
+                "The server timed out waiting to respond to the request",
+                "request_timeout",
+            ),
             SimulatedError::InvalidRequest { message } => {
                 ErrorResponse::invalid_request(message.clone())
             }
             SimulatedError::AuthenticationError => {
                 ErrorResponse::new("Invalid API key provided", "authentication_error")
             }
+            SimulatedError::ModelNotFound { model } => ErrorResponse::invalid_request(format!(
+                "The model `{}` does not exist or you do not have access to it.",
+                model
+            ))
+            .with_code("model_not_found"),
+            SimulatedError::ModelDeprecated { model, sunset } => {
+                ErrorResponse::invalid_request(format!(
+                    "The model `{}` has been deprecated and is no longer available since {}.",
+                    model, sunset
+                ))
+                .with_code("model_deprecated")
+            }
         }
     }
 
@@ -165,6 +197,9 @@ impl SimulatedError {
                 retry_after_seconds,
             } => Some(*retry_after_seconds),
             SimulatedError::ServiceUnavailable => Some(60),
+            SimulatedError::Overloaded {
+                retry_after_seconds,
+            } => Some(*retry_after_seconds),
             _ => None,
         }
     }
@@ -320,6 +355,7 @@ mod tests {
             400
         );
         assert_eq!(SimulatedError::AuthenticationError.status_code(), 401);
+        assert_eq!(SimulatedError::RequestTimeout.status_code(), 408);
     }
 
     #[test]
@@ -0,0 +1,113 @@
+// System Fingerprint Module
+// Chooses the `system_fingerprint` reported on chat completions.
+//
+// Decision: rotation is derived from wall-clock time (`unix_seconds /
+// rotate_every`) rather than from server uptime, so every llmsim instance
+// with the same config reports the same fingerprint at the same moment and a
+// test can compute which value to expect. The fingerprint is picked once when
+// a request begins, so every chunk of a stream carries the same value even
+// when a rotation boundary passes mid-stream.
+
+use crate::clock;
+use crate::openai::DEFAULT_SYSTEM_FINGERPRINT;
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Fingerprints reported per model, optionally rotating over time
+#[derive(Debug, Clone)]
+pub struct SystemFingerprints {
+    /// Values to cycle through; the first is used when not rotating
+    pub values: Vec<String>,
+    /// How long each value is reported before moving to the next
+    pub rotate_every: Option<Duration>,
+    /// Fixed fingerprint for specific models, bypassing rotation
+    pub per_model: HashMap<String, String>,
+}
+
+impl Default for SystemFingerprints {
+    fn default() -> Self {
+        Self::fixed(DEFAULT_SYSTEM_FINGERPRINT)
+    }
+}
+
+impl SystemFingerprints {
+    /// Always report `value`
+    pub fn fixed(value: impl Into<String>) -> Self {
+        Self {
+            values: vec![value.into()],
+            rotate_every: None,
+            per_model: HashMap::new(),
+        }
+    }
+
+    /// Cycle through `values`, moving to the next one every `every`
+    pub fn rotating(values: Vec<String>, every: Duration) -> Self {
+        Self {
+            values,
+            rotate_every: Some(every),
+            per_model: HashMap::new(),
+        }
+    }
+
+    /// Report `value` for `model` regardless of rotation
+    pub fn with_model(mut self, model: impl Into<String>, value: impl Into<String>) -> Self {
+        self.per_model.insert(model.into(), value.into());
+        self
+    }
+
+    /// Fingerprint to report for `model` now
+    pub fn for_model(&self, model: &str) -> String {
+        self.for_model_at(model, clock::unix_seconds())
+    }
+
+    /// Fingerprint to report for `model` at `unix_seconds`
+    pub fn for_model_at(&self, model: &str, unix_seconds: u64) -> String {
+        if let Some(value) = self.per_model.get(model) {
+            return value.clone();
+        }
+        if self.values.is_empty() {
+            return DEFAULT_SYSTEM_FINGERPRINT.to_string();
+        }
+        let slot = match self.rotate_every.map(|d| d.as_secs()) {
+            Some(every) if every > 0 => (unix_seconds / every) as usize % self.values.len(),
+            _ => 0,
+        };
+        self.values[slot].clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_is_constant() {
+        let fingerprints = SystemFingerprints::default();
+        assert_eq!(fingerprints.for_model_at("gpt-5", 0), "fp_llmsim");
+        assert_eq!(fingerprints.for_model_at("gpt-5", 1_000_000), "fp_llmsim");
+    }
+
+    #[test]
+    fn test_rotation_follows_schedule() {
+        let fingerprints = SystemFingerprints::rotating(
+            vec!["fp_a".into(), "fp_b".into(), "fp_c".into()],
+            Duration::from_secs(60),
+        );
+        assert_eq!(fingerprints.for_model_at("gpt-5", 0), "fp_a");
+        assert_eq!(fingerprints.for_model_at("gpt-5", 59), "fp_a");
+        assert_eq!(fingerprints.for_model_at("gpt-5", 60), "fp_b");
+        assert_eq!(fingerprints.for_model_at("gpt-5", 150), "fp_c");
+        assert_eq!(fingerprints.for_model_at("gpt-5", 180), "fp_a");
+    }
+
+    #[test]
+    fn test_per_model_override() {
+        let fingerprints = SystemFingerprints::rotating(
+            vec!["fp_a".into(), "fp_b".into()],
+            Duration::from_secs(1),
+        )
+        .with_model("gpt-4o", "fp_4o");
+        assert_eq!(fingerprints.for_model_at("gpt-4o", 1), "fp_4o");
+        assert_eq!(fingerprints.for_model_at("gpt-5", 1), "fp_b");
+    }
+}
@@ -1,17 +1,124 @@
 // Response Generator Module
 // Provides different strategies for generating simulated LLM responses.
+//
+// Decision: generators can produce a response incrementally as stream pieces
+// (words and the whitespace between them) via `generate_pieces`. Synthetic
+// generators do this lazily, so a streamed 100k-token response never exists
+// as one String and generation stops when the consumer stops pulling. The
+// default implementation splits `generate`, so simple generators only need
+// to provide the full text.
+//
+// Decision: synthetic generators draw from `request_rng`, which is seeded
+// from the request's `seed` and content when a seed is given. Repeating a
+// seeded request reproduces its response, the way OpenAI's `seed` promises
+// best-effort reproducibility; unseeded requests stay random.
 
 use crate::openai::ChatCompletionRequest;
 use rand::prelude::IndexedRandom;
-use rand::RngExt;
+use rand::rngs::StdRng;
+use rand::{RngExt, SeedableRng};
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+/// Lazily produced response pieces, in stream order
+pub type TextPieces = Box<dyn Iterator<Item = String> + Send>;
 
 /// Trait for generating simulated responses
 pub trait ResponseGenerator: Send + Sync {
     /// Generate a response for the given request
     fn generate(&self, request: &ChatCompletionRequest) -> String;
 
+    /// Generate the response incrementally. Concatenating the pieces yields
+    /// a complete response.
+    fn generate_pieces(&self, request: &ChatCompletionRequest) -> TextPieces {
+        Box::new(split_pieces(&self.generate(request)).into_iter())
+    }
+
     /// Get a name for this generator (for logging/debugging)
     fn name(&self) -> &str;
+
+    /// The same generator aiming at `target_tokens` instead, for generators
+    /// whose length is configurable (used to replay learned lengths)
+    fn with_target_tokens(&self, _target_tokens: usize) -> Option<Box<dyn ResponseGenerator>> {
+        None
+    }
+
+    /// The length this generator aims at, for generators whose length is
+    /// configurable
+    fn target_tokens(&self) -> Option<usize> {
+        None
+    }
+}
+
+/// RNG for answering `request`. With a `seed`, it is derived from the seed,
+/// model and messages, so the same seeded request always draws the same
+/// values; without one it is freshly seeded.
+pub fn request_rng(request: &ChatCompletionRequest) -> StdRng {
+    let Some(seed) = request.seed else {
+        return rand::make_rng();
+    };
+    let mut hasher = DefaultHasher::new();
+    seed.hash(&mut hasher);
+    request.model.hash(&mut hasher);
+    serde_json::to_string(&request.messages)
+        .unwrap_or_default()
+        .hash(&mut hasher);
+    StdRng::seed_from_u64(hasher.finish())
+}
+
+/// Split text into stream pieces: runs of non-whitespace, with each
+/// whitespace character as its own piece
+pub fn split_pieces(text: &str) -> Vec<String> {
+    let mut pieces = Vec::new();
+    let mut current_word = String::new();
+
+    for ch in text.chars() {
+        if ch.is_whitespace() {
+            if !current_word.is_empty() {
+                pieces.push(std::mem::take(&mut current_word));
+            }
+            pieces.push(ch.to_string());
+        } else {
+            current_word.push(ch);
+        }
+    }
+
+    if !current_word.is_empty() {
+        pieces.push(current_word);
+    }
+
+    pieces
+}
+
+/// `count` words produced on demand by `next_word(index)`, separated by
+/// single-space pieces
+fn word_pieces<F>(count: usize, mut next_word: F) -> TextPieces
+where
+    F: FnMut(usize) -> String + Send + 'static,
+{
+    let mut index = 0;
+    let mut pending_space = false;
+    Box::new(std::iter::from_fn(move || {
+        if index >= count {
+            return None;
+        }
+        if pending_space {
+            pending_space = false;
+            return Some(" ".to_string());
+        }
+        let word = next_word(index);
+        index += 1;
+        pending_space = true;
+        Some(word)
+    }))
+}
+
+/// Capitalize the first letter of a word
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_ascii_uppercase().to_string() + chars.as_str(),
+        None => String::new(),
+    }
 }
 
 /// Generates lorem ipsum text
@@ -89,33 +196,20 @@ impl LoremGenerator {
         Self { target_tokens }
     }
 
-    fn generate_text(&self, word_count: usize) -> String {
-        let mut rng = rand::rng();
-        let words: Vec<&str> = (0..word_count)
-            .map(|_| *Self::LOREM_WORDS.choose(&mut rng).unwrap())
-            .collect();
-
-        let mut result = String::new();
-        for (i, word) in words.iter().enumerate() {
-            if i == 0 {
-                // Capitalize first letter
-                let mut chars = word.chars();
-                if let Some(first) = chars.next() {
-                    result.push(first.to_ascii_uppercase());
-                    result.extend(chars);
-                }
+    fn text_pieces(word_count: usize, mut rng: StdRng) -> TextPieces {
+        word_pieces(word_count, move |i| {
+            let word = *Self::LOREM_WORDS.choose(&mut rng).unwrap();
+            let mut piece = if i == 0 {
+                capitalize(word)
             } else {
-                result.push(' ');
-                result.push_str(word);
+                word.to_string()
+            };
+            // Add punctuation periodically, and always at the end
+            if (i + 1) % 10 == 0 || i + 1 == word_count {
+                piece.push('.');
             }
-
-            // Add punctuation periodically
-            if (i + 1) % 10 == 0 && i < words.len() - 1 {
-                result.push('.');
-            }
-        }
-        result.push('.');
-        result
+            piece
+        })
     }
 }
 
@@ -126,23 +220,61 @@ impl Default for LoremGenerator {
 }
 
 impl ResponseGenerator for LoremGenerator {
-    fn generate(&self, _request: &ChatCompletionRequest) -> String {
+    fn generate(&self, request: &ChatCompletionRequest) -> String {
+        self.generate_pieces(request).collect()
+    }
+
+    fn generate_pieces(&self, request: &ChatCompletionRequest) -> TextPieces {
         // Rough estimate: 1 token ≈ 0.75 words for English text
         let word_count = (self.target_tokens as f64 * 0.75) as usize;
-        self.generate_text(word_count.max(1))
+        Self::text_pieces(word_count.max(1), request_rng(request))
     }
 
     fn name(&self) -> &str {
         "lorem"
     }
+
+    fn with_target_tokens(&self, target_tokens: usize) -> Option<Box<dyn ResponseGenerator>> {
+        Some(Box::new(LoremGenerator::new(target_tokens)))
+    }
+
+    fn target_tokens(&self) -> Option<usize> {
+        Some(self.target_tokens)
+    }
 }
 
-/// Echoes back the last user message
-pub struct EchoGenerator;
+/// What `EchoGenerator` echoes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EchoMode {
+    /// The last user message, after `Echo: `
+    #[default]
+    LastMessage,
+    /// A JSON summary of the whole conversation (`echo:context`): message
+    /// count, roles, prompt tokens and a hash of the messages
+    Context,
+}
+
+/// Echoes back the last user message, or a summary of the conversation
+pub struct EchoGenerator {
+    mode: EchoMode,
+}
 
 impl EchoGenerator {
     pub fn new() -> Self {
-        Self
+        Self {
+            mode: EchoMode::LastMessage,
+        }
+    }
+
+    /// Echo a JSON summary of the conversation instead of its last message
+    pub fn context() -> Self {
+        Self {
+            mode: EchoMode::Context,
+        }
+    }
+
+    pub fn mode(&self) -> EchoMode {
+        self.mode
     }
 }
 
@@ -152,21 +284,83 @@ impl Default for EchoGenerator {
     }
 }
 
+/// Text of the last user message that has content
+fn last_user_text(request: &ChatCompletionRequest) -> Option<String> {
+    request
+        .messages
+        .iter()
+        .rev()
+        .filter(|message| matches!(message.role, crate::openai::Role::User))
+        .find_map(|message| Some(message.content.as_ref()?.text()))
+}
+
+/// The conversation as a client sent it, for asserting on prompt assembly.
+/// `prompt_tokens` is counted like `usage.prompt_tokens` with the default
+/// tokenizer; `hash` is FNV-1a over the messages as compact JSON, so equal
+/// conversations have equal hashes across runs and builds.
+fn context_summary(request: &ChatCompletionRequest) -> String {
+    let roles: Vec<&str> = request.messages.iter().map(|m| m.role.as_str()).collect();
+    let prompt_tokens: usize = request
+        .messages
+        .iter()
+        .map(|message| {
+            let content = message.content.as_ref().map_or(0, |content| {
+                crate::pipeline::count_text_tokens(&content.text()) + image_tokens(content)
+            });
+            content + 4
+        })
+        .sum::<usize>()
+        + 3;
+    let messages = serde_json::to_vec(&request.messages).unwrap_or_default();
+    serde_json::json!({
+        "message_count": request.messages.len(),
+        "roles": roles,
+        "prompt_tokens": prompt_tokens,
+        "hash": format!("{:016x}", fnv1a(&messages)),
+    })
+    .to_string()
+}
+
+/// Estimated tokens of a message's image parts, when token counting is built in
+fn image_tokens(content: &crate::openai::ChatMessageContent) -> usize {
+    #[cfg(feature = "tokens")]
+    {
+        content
+            .images()
+            .iter()
+            .map(|image| crate::estimate_image_tokens(image.detail.as_deref()))
+            .sum()
+    }
+    #[cfg(not(feature = "tokens"))]
+    {
+        let _ = content;
+        0
+    }
+}
+
+/// 64-bit FNV-1a; unlike `DefaultHasher`, stable across Rust releases
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
 impl ResponseGenerator for EchoGenerator {
     fn generate(&self, request: &ChatCompletionRequest) -> String {
-        // Find the last user message
-        for message in request.messages.iter().rev() {
-            if matches!(message.role, crate::openai::Role::User) {
-                if let Some(content) = &message.content {
-                    return format!("Echo: {}", content.text());
-                }
-            }
+        if self.mode == EchoMode::Context {
+            return context_summary(request);
+        }
+        match last_user_text(request) {
+            Some(text) => format!("Echo: {}", text),
+            None => "Echo: (no user message found)".to_string(),
         }
-        "Echo: (no user message found)".to_string()
     }
 
     fn name(&self) -> &str {
-        "echo"
+        match self.mode {
+            EchoMode::LastMessage => "echo",
+            EchoMode::Context => "echo:context",
+        }
     }
 }
 
@@ -223,42 +417,41 @@ impl Default for RandomWordGenerator {
 }
 
 impl ResponseGenerator for RandomWordGenerator {
-    fn generate(&self, _request: &ChatCompletionRequest) -> String {
-        let mut rng = rand::rng();
+    fn generate(&self, request: &ChatCompletionRequest) -> String {
+        self.generate_pieces(request).collect()
+    }
+
+    fn generate_pieces(&self, request: &ChatCompletionRequest) -> TextPieces {
         // Approximate: 1 token ≈ 0.75 words
-        let word_count = (self.target_tokens as f64 * 0.75) as usize;
+        let word_count = ((self.target_tokens as f64 * 0.75) as usize).max(1);
+        let mut rng = request_rng(request);
 
-        let words: Vec<&str> = (0..word_count.max(1))
-            .map(|_| *Self::COMMON_WORDS.choose(&mut rng).unwrap())
-            .collect();
-
-        let mut result = String::new();
-        for (i, word) in words.iter().enumerate() {
-            if i == 0 {
-                // Capitalize first letter
-                let mut chars = word.chars();
-                if let Some(first) = chars.next() {
-                    result.push(first.to_ascii_uppercase());
-                    result.extend(chars);
-                }
+        word_pieces(word_count, move |i| {
+            let word = *Self::COMMON_WORDS.choose(&mut rng).unwrap();
+            let mut piece = if i == 0 {
+                capitalize(word)
             } else {
-                result.push(' ');
-                result.push_str(word);
+                word.to_string()
+            };
+            // Add punctuation at irregular intervals, and always at the end
+            if (i + 1) % rng.random_range(8..15) == 0 || i + 1 == word_count {
+                piece.push('.');
             }
-
-            // Add punctuation
-            if (i + 1) % rng.random_range(8..15) == 0 && i < words.len() - 1 {
-                result.push('.');
-                // Next word should be capitalized (handled in next iteration if we check)
-            }
-        }
-        result.push('.');
-        result
+            piece
+        })
     }
 
     fn name(&self) -> &str {
         "random_word"
     }
+
+    fn with_target_tokens(&self, target_tokens: usize) -> Option<Box<dyn ResponseGenerator>> {
+        Some(Box::new(RandomWordGenerator::new(target_tokens)))
+    }
+
+    fn target_tokens(&self) -> Option<usize> {
+        Some(self.target_tokens)
+    }
 }
 
 /// Generates numbered sequence responses (useful for testing streaming)
@@ -279,17 +472,200 @@ impl Default for SequenceGenerator {
 }
 
 impl ResponseGenerator for SequenceGenerator {
-    fn generate(&self, _request: &ChatCompletionRequest) -> String {
-        let count = self.target_tokens.max(1);
-        (1..=count)
-            .map(|i| i.to_string())
-            .collect::<Vec<_>>()
-            .join(" ")
+    fn generate(&self, request: &ChatCompletionRequest) -> String {
+        self.generate_pieces(request).collect()
+    }
+
+    fn generate_pieces(&self, _request: &ChatCompletionRequest) -> TextPieces {
+        word_pieces(self.target_tokens.max(1), |i| (i + 1).to_string())
     }
 
     fn name(&self) -> &str {
         "sequence"
     }
+
+    fn with_target_tokens(&self, target_tokens: usize) -> Option<Box<dyn ResponseGenerator>> {
+        Some(Box::new(SequenceGenerator::new(target_tokens)))
+    }
+
+    fn target_tokens(&self) -> Option<usize> {
+        Some(self.target_tokens)
+    }
+}
+
+/// Answers prompts that mention a date past the model's knowledge cutoff
+/// with the disclaimer a real model gives ("I don't have information past
+/// ..."), and anything else with lorem ipsum. The cutoff comes from the
+/// model registries; models without one always get lorem ipsum.
+pub struct CutoffGenerator {
+    fallback: LoremGenerator,
+}
+
+/// Disclaimers, filled in with the cutoff and the date asked about
+const CUTOFF_REPLIES: &[&str] = &[
+    "I don't have information past {cutoff}, so I can't tell you what happened in {date}. For anything more recent, please check a current source.",
+    "My knowledge only goes up to {cutoff}. I don't have any information about {date}, so I can't answer that reliably; an up-to-date source will be more accurate.",
+    "I'm sorry, but my training data only extends to {cutoff}, and I have no information about events in {date}. You may want to consult a recent news source or official website.",
+];
+
+const MONTHS: [&str; 12] = [
+    "january",
+    "february",
+    "march",
+    "april",
+    "may",
+    "june",
+    "july",
+    "august",
+    "september",
+    "october",
+    "november",
+    "december",
+];
+
+/// A year, with the month when one was given
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct MentionedDate {
+    year: u32,
+    month: Option<u32>,
+}
+
+impl MentionedDate {
+    /// Whether the date falls after the month `cutoff` is in. A bare year
+    /// is only past the cutoff once the whole year is.
+    fn is_after(&self, cutoff: MentionedDate) -> bool {
+        match (self.month, cutoff.month) {
+            (Some(month), Some(cutoff_month)) => (self.year, month) > (cutoff.year, cutoff_month),
+            _ => self.year > cutoff.year,
+        }
+    }
+
+    /// "March 2026", or "2026" for a bare year
+    fn describe(&self) -> String {
+        match self.month {
+            Some(month) => format!("{} {}", capitalize(MONTHS[month as usize - 1]), self.year),
+            None => self.year.to_string(),
+        }
+    }
+}
+
+/// Month number (1-12) of a month name or its three-letter abbreviation
+fn month_number(word: &str) -> Option<u32> {
+    let word = word.to_lowercase();
+    MONTHS
+        .iter()
+        .position(|month| *month == word || (word.len() == 3 && month.starts_with(&word)))
+        .map(|i| i as u32 + 1)
+}
+
+/// Year of a four-digit word between 1900 and 2199
+fn year_number(word: &str) -> Option<u32> {
+    (word.len() == 4 && word.bytes().all(|b| b.is_ascii_digit()))
+        .then(|| word.parse().ok())
+        .flatten()
+        .filter(|year| (1900..2200).contains(year))
+}
+
+/// An ISO date or month: `2026-03-14` or `2026-03`
+fn iso_date(word: &str) -> Option<MentionedDate> {
+    let mut parts = word.split('-');
+    let year = year_number(parts.next()?)?;
+    let month = parts.next()?;
+    let month: u32 = (month.len() == 2).then(|| month.parse().ok()).flatten()?;
+    (1..=12).contains(&month).then_some(MentionedDate {
+        year,
+        month: Some(month),
+    })
+}
+
+/// Dates in free text: ISO dates, "March 2026", "March 5, 2026", "Mar 2026"
+/// and bare years
+fn mentioned_dates(text: &str) -> Vec<MentionedDate> {
+    let words: Vec<&str> = text
+        .split(|c: char| !c.is_alphanumeric() && c != '-')
+        .filter(|word| !word.is_empty())
+        .collect();
+    let mut dates = Vec::new();
+    for (i, word) in words.iter().enumerate() {
+        if let Some(date) = iso_date(word) {
+            dates.push(date);
+        } else if let Some(year) = year_number(word) {
+            // "March 2026" or "March 5, 2026"
+            let before = |back: usize| i.checked_sub(back).map(|j| words[j]);
+            let day = |word: &str| word.len() <= 2 && word.bytes().all(|b| b.is_ascii_digit());
+            let month = before(1).and_then(month_number).or_else(|| {
+                before(1)
+                    .filter(|w| day(w))
+                    .and(before(2))
+                    .and_then(month_number)
+            });
+            dates.push(MentionedDate { year, month });
+        }
+    }
+    dates
+}
+
+/// Knowledge cutoff of a model in either registry
+fn knowledge_cutoff(model: &str) -> Option<MentionedDate> {
+    let profile = crate::openai::get_model_profile(model)
+        .or_else(|| crate::anthropic::get_anthropic_model_profile(model))?;
+    iso_date(profile.knowledge_cutoff.as_deref()?.get(..7)?)
+}
+
+impl CutoffGenerator {
+    pub fn new(target_tokens: usize) -> Self {
+        Self {
+            fallback: LoremGenerator::new(target_tokens),
+        }
+    }
+
+    /// The disclaimer for `request`, if it asks about a date past the
+    /// model's cutoff
+    fn disclaimer(&self, request: &ChatCompletionRequest) -> Option<String> {
+        let cutoff = knowledge_cutoff(&request.model)?;
+        let latest = mentioned_dates(&last_user_text(request)?)
+            .into_iter()
+            .filter(|date| date.is_after(cutoff))
+            .max()?;
+        let reply = CUTOFF_REPLIES.choose(&mut request_rng(request))?;
+        Some(
+            reply
+                .replace("{cutoff}", &cutoff.describe())
+                .replace("{date}", &latest.describe()),
+        )
+    }
+}
+
+impl Default for CutoffGenerator {
+    fn default() -> Self {
+        Self::new(100)
+    }
+}
+
+impl ResponseGenerator for CutoffGenerator {
+    fn generate(&self, request: &ChatCompletionRequest) -> String {
+        self.disclaimer(request)
+            .unwrap_or_else(|| self.fallback.generate(request))
+    }
+
+    fn generate_pieces(&self, request: &ChatCompletionRequest) -> TextPieces {
+        match self.disclaimer(request) {
+            Some(text) => Box::new(split_pieces(&text).into_iter()),
+            None => self.fallback.generate_pieces(request),
+        }
+    }
+
+    fn name(&self) -> &str {
+        "cutoff"
+    }
+
+    fn with_target_tokens(&self, target_tokens: usize) -> Option<Box<dyn ResponseGenerator>> {
+        Some(Box::new(CutoffGenerator::new(target_tokens)))
+    }
+
+    fn target_tokens(&self) -> Option<usize> {
+        Some(self.fallback.target_tokens)
+    }
 }
 
 /// Factory for creating generators from config
@@ -297,8 +673,10 @@ pub fn create_generator(name: &str, target_tokens: usize) -> Box<dyn ResponseGen
     match name.to_lowercase().as_str() {
         "lorem" => Box::new(LoremGenerator::new(target_tokens)),
         "echo" => Box::new(EchoGenerator::new()),
+        "echo:context" => Box::new(EchoGenerator::context()),
         "random" | "random_word" => Box::new(RandomWordGenerator::new(target_tokens)),
         "sequence" => Box::new(SequenceGenerator::new(target_tokens)),
+        "cutoff" => Box::new(CutoffGenerator::new(target_tokens)),
         fixed if fixed.starts_with("fixed:") => Box::new(FixedGenerator::new(&fixed[6..])),
         _ => Box::new(LoremGenerator::new(target_tokens)),
     }
@@ -329,8 +707,14 @@ mod tests {
             user: None,
             tools: None,
             tool_choice: None,
+            parallel_tool_calls: None,
             response_format: None,
             seed: None,
+            stream_options: None,
+            functions: None,
+            function_call: None,
+            store: None,
+            metadata: None,
         }
     }
 
@@ -349,6 +733,26 @@ mod tests {
         assert!(response.contains("Hello, how are you?"));
     }
 
+    #[test]
+    fn test_echo_context_generator() {
+        let gen = EchoGenerator::context();
+        let summary: serde_json::Value =
+            serde_json::from_str(&gen.generate(&sample_request())).unwrap();
+        assert_eq!(summary["message_count"], 2);
+        assert_eq!(summary["roles"], serde_json::json!(["system", "user"]));
+        assert!(summary["prompt_tokens"].as_u64().unwrap() > 11);
+        assert_eq!(summary["hash"].as_str().unwrap().len(), 16);
+
+        // The hash tells conversations apart and repeats for the same one
+        let mut other = sample_request();
+        other.messages[1] = Message::user("Hello, how are you!");
+        let again: serde_json::Value =
+            serde_json::from_str(&gen.generate(&sample_request())).unwrap();
+        let changed: serde_json::Value = serde_json::from_str(&gen.generate(&other)).unwrap();
+        assert_eq!(summary["hash"], again["hash"]);
+        assert_ne!(summary["hash"], changed["hash"]);
+    }
+
     #[test]
     fn test_fixed_generator() {
         let gen = FixedGenerator::new("This is a fixed response.");
@@ -371,6 +775,110 @@ mod tests {
         assert!(response.contains("10"));
     }
 
+    #[test]
+    fn test_seed_makes_output_reproducible() {
+        let mut request = sample_request();
+        request.seed = Some(42);
+        let lorem = LoremGenerator::new(50);
+        let random = RandomWordGenerator::new(50);
+        assert_eq!(lorem.generate(&request), lorem.generate(&request));
+        assert_eq!(random.generate(&request), random.generate(&request));
+
+        let mut other_seed = request.clone();
+        other_seed.seed = Some(43);
+        assert_ne!(lorem.generate(&request), lorem.generate(&other_seed));
+
+        let mut other_prompt = request.clone();
+        other_prompt
+            .messages
+            .push(Message::user("And another thing"));
+        assert_ne!(lorem.generate(&request), lorem.generate(&other_prompt));
+    }
+
+    #[test]
+    fn test_split_pieces() {
+        assert_eq!(
+            split_pieces("Hello  world\n"),
+            vec!["Hello", " ", " ", "world", "\n"]
+        );
+        assert!(split_pieces("").is_empty());
+    }
+
+    #[test]
+    fn test_pieces_match_generated_text() {
+        let gen = SequenceGenerator::new(5);
+        let pieces: Vec<String> = gen.generate_pieces(&sample_request()).collect();
+        assert_eq!(pieces, vec!["1", " ", "2", " ", "3", " ", "4", " ", "5"]);
+        assert_eq!(pieces.concat(), gen.generate(&sample_request()));
+
+        // Default implementation splits the full text
+        let fixed = FixedGenerator::new("a b");
+        let pieces: Vec<String> = fixed.generate_pieces(&sample_request()).collect();
+        assert_eq!(pieces, vec!["a", " ", "b"]);
+    }
+
+    #[test]
+    fn test_lorem_pieces_are_lazy() {
+        // A huge target must not be materialized up front
+        let gen = LoremGenerator::new(usize::MAX / 2);
+        let first: Vec<String> = gen.generate_pieces(&sample_request()).take(3).collect();
+        assert_eq!(first.len(), 3);
+        assert!(first[0].chars().next().unwrap().is_uppercase());
+        assert_eq!(first[1], " ");
+    }
+
+    fn asking(model: &str, question: &str) -> ChatCompletionRequest {
+        ChatCompletionRequest {
+            model: model.to_string(),
+            messages: vec![Message::user(question)],
+            seed: Some(7),
+            ..sample_request()
+        }
+    }
+
+    #[test]
+    fn test_mentioned_dates() {
+        let date = |year, month| MentionedDate { year, month };
+        assert_eq!(
+            mentioned_dates("On 2026-03-14, and in Mar 2025, or March 5, 2027 - not 12345 or 1800"),
+            vec![
+                date(2026, Some(3)),
+                date(2025, Some(3)),
+                date(2027, Some(3))
+            ]
+        );
+        assert_eq!(mentioned_dates("the 2030 election"), vec![date(2030, None)]);
+        assert!(date(2024, None).is_after(date(2023, Some(12))));
+        assert!(!date(2024, None).is_after(date(2024, Some(9))));
+        assert!(date(2024, Some(10)).is_after(date(2024, Some(9))));
+    }
+
+    #[test]
+    fn test_cutoff_generator() {
+        // gpt-5's knowledge cutoff is September 2024
+        let gen = CutoffGenerator::new(20);
+        let request = asking("gpt-5", "Who won the election in November 2024?");
+        let reply = gen.generate(&request);
+        assert!(reply.contains("September 2024"), "{}", reply);
+        assert!(reply.contains("November 2024"), "{}", reply);
+        let streamed: String = gen.generate_pieces(&request).collect();
+        assert_eq!(streamed, reply);
+
+        // Dates within the cutoff, no dates and unknown models get lorem
+        for request in [
+            asking("gpt-5", "What happened in March 2024?"),
+            asking("gpt-5", "What is the capital of France?"),
+            asking("acme-1", "What happens in 2031?"),
+        ] {
+            let reply = gen.generate(&request);
+            assert!(!reply.contains("information"), "{}", reply);
+        }
+
+        // Claude ids resolve through the Anthropic registry
+        let reply = gen.generate(&asking("claude-opus-4-7", "Any news from 2031?"));
+        assert!(reply.contains("2031"), "{}", reply);
+    }
+
     #[test]
     fn test_create_generator() {
         let lorem = create_generator("lorem", 100);
@@ -386,9 +894,11 @@ mod tests {
     #[test]
     fn test_generator_names() {
         assert_eq!(LoremGenerator::default().name(), "lorem");
-        assert_eq!(EchoGenerator.name(), "echo");
+        assert_eq!(EchoGenerator::new().name(), "echo");
+        assert_eq!(EchoGenerator::context().name(), "echo:context");
         assert_eq!(FixedGenerator::new("test").name(), "fixed");
         assert_eq!(RandomWordGenerator::default().name(), "random_word");
         assert_eq!(SequenceGenerator::default().name(), "sequence");
+        assert_eq!(CutoffGenerator::default().name(), "cutoff");
     }
 }
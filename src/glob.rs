@@ -0,0 +1,50 @@
+// Glob Module
+// Shell-style patterns naming models and paths in the configuration:
+// `gpt-4*`, `claude-*-4-?`.
+//
+// Decision: only `*` and `?`, matched with one backtracking point, since
+// patterns are short and checked per request; no character classes, so a
+// model name with brackets is matched literally.
+
+/// `*` matches any run of characters, `?` exactly one
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // Position of the last `*` and the text position it was tried at
+    let mut backtrack = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if let Some((star, tried)) = backtrack {
+            p = star + 1;
+            t = tried + 1;
+            backtrack = Some((star, tried + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("gpt-4*", "gpt-4o-mini"));
+        assert!(glob_match("gpt-4*", "gpt-4"));
+        assert!(!glob_match("gpt-4*", "gpt-5"));
+        assert!(glob_match("claude-*-4-?", "claude-sonnet-4-6"));
+        assert!(glob_match("*mini", "gpt-4o-mini"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("a*b*c", "axxbyyc"));
+        assert!(!glob_match("a*b*c", "axxbyy"));
+        assert!(!glob_match("gpt-5", "gpt-5-mini"));
+    }
+}
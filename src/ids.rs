@@ -1,29 +1,295 @@
+// Ids Module
+// Ids of completions, responses, items, messages and tool calls, shaped like
+// the real APIs' (`chatcmpl-` and 29 base62 characters, `resp_` and 48 hex
+// digits, ...) so client code that checks an id's shape accepts llmsim's.
+//
+// Decision: every kind of id has one format (prefix, alphabet, length), the
+// real API's unless `[ids.<kind>]` replaces part of it. Ids are minted deep in
+// the library, in response builders and stream constructors no configuration
+// reaches, so they are minted in the formats of the current `Stamps`, which
+// the server scopes each request in.
+
 use rand::RngExt;
-use std::time::{SystemTime, UNIX_EPOCH};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+const HEX: &str = "0123456789abcdef";
+const BASE62: &str = "0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
 
+/// Time stamped on responses, skewed as the current `Stamps` say
 pub(crate) fn unix_timestamp() -> i64 {
-    SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .map(|duration| duration.as_secs() as i64)
-        .unwrap_or_default()
+    crate::stamps::with_current(|stamps| stamps.skew().response_seconds())
+}
+
+/// A new id of `kind`, in the current `Stamps`' format
+pub(crate) fn new_id(kind: IdKind) -> String {
+    crate::stamps::with_current(|stamps| stamps.ids().generate(kind))
+}
+
+/// What an id names, as keyed in `[ids]`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IdKind {
+    /// Chat completions and their chunks, `chatcmpl-`
+    ChatCompletion,
+    /// Chat completion tool calls, `call_`
+    ToolCall,
+    /// Responses API responses, `resp_`
+    Response,
+    /// Responses API message items, `msg_`
+    Message,
+    /// Responses API function calls, `fc_`
+    FunctionCall,
+    /// Function call outputs given as input, `fco_`
+    FunctionCallOutput,
+    /// Reasoning items, `rs_`
+    Reasoning,
+    /// MCP tool listings, `mcpl_`
+    McpListTools,
+    /// MCP approval requests, `mcpr_`
+    McpApprovalRequest,
+    /// MCP approval responses given as input, `mcpa_`
+    McpApprovalResponse,
+    /// MCP tool calls, `mcp_`
+    McpCall,
+    /// Code interpreter calls, `ci_`
+    CodeInterpreterCall,
+    /// Code interpreter containers, `cntr_`
+    Container,
+    /// Other input items, `item_`
+    InputItem,
+    /// Anthropic messages, `msg_01`
+    AnthropicMessage,
+    /// Anthropic tool uses, `toolu_01`
+    ToolUse,
+    /// gRPC generations, `gen-`
+    Generation,
+}
+
+impl IdKind {
+    /// Every kind of id
+    pub const ALL: [IdKind; 17] = [
+        IdKind::ChatCompletion,
+        IdKind::ToolCall,
+        IdKind::Response,
+        IdKind::Message,
+        IdKind::FunctionCall,
+        IdKind::FunctionCallOutput,
+        IdKind::Reasoning,
+        IdKind::McpListTools,
+        IdKind::McpApprovalRequest,
+        IdKind::McpApprovalResponse,
+        IdKind::McpCall,
+        IdKind::CodeInterpreterCall,
+        IdKind::Container,
+        IdKind::InputItem,
+        IdKind::AnthropicMessage,
+        IdKind::ToolUse,
+        IdKind::Generation,
+    ];
+
+    /// The real API's prefix, alphabet and length
+    fn real_format(self) -> (&'static str, &'static str, usize) {
+        match self {
+            IdKind::ChatCompletion => ("chatcmpl-", BASE62, 29),
+            IdKind::ToolCall => ("call_", BASE62, 24),
+            IdKind::Response => ("resp_", HEX, 48),
+            IdKind::Message => ("msg_", HEX, 48),
+            IdKind::FunctionCall => ("fc_", HEX, 48),
+            IdKind::FunctionCallOutput => ("fco_", HEX, 48),
+            IdKind::Reasoning => ("rs_", HEX, 48),
+            IdKind::McpListTools => ("mcpl_", HEX, 48),
+            IdKind::McpApprovalRequest => ("mcpr_", HEX, 48),
+            IdKind::McpApprovalResponse => ("mcpa_", HEX, 48),
+            IdKind::McpCall => ("mcp_", HEX, 48),
+            IdKind::CodeInterpreterCall => ("ci_", HEX, 48),
+            IdKind::Container => ("cntr_", HEX, 48),
+            IdKind::InputItem => ("item_", HEX, 48),
+            IdKind::AnthropicMessage => ("msg_01", BASE62, 22),
+            IdKind::ToolUse => ("toolu_01", BASE62, 22),
+            IdKind::Generation => ("gen-", BASE62, 24),
+        }
+    }
+}
+
+/// `[ids.<kind>]`: the parts of an id format to change; the rest stay the
+/// real API's
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct IdFormat {
+    /// Text every id starts with
+    pub prefix: Option<String>,
+    /// `hex`, `base62`, `uuid` (a dashed UUID, ignoring `length`), or the
+    /// characters to draw from
+    pub alphabet: Option<String>,
+    /// Characters after the prefix
+    pub length: Option<usize>,
 }
 
-pub(crate) fn prefixed_id(prefix: &str) -> String {
-    format!("{}{}", prefix, random_uuid_like())
+#[derive(Debug, Clone)]
+enum Alphabet {
+    Chars(Vec<char>),
+    Uuid,
 }
 
-pub(crate) fn prefixed_compact_id(prefix: &str) -> String {
-    format!("{}{:032x}", prefix, rand::rng().random::<u128>())
+#[derive(Debug, Clone)]
+struct Format {
+    prefix: String,
+    alphabet: Alphabet,
+    length: usize,
 }
 
-fn random_uuid_like() -> String {
-    let hex = format!("{:032x}", rand::rng().random::<u128>());
-    format!(
-        "{}-{}-{}-{}-{}",
-        &hex[0..8],
-        &hex[8..12],
-        &hex[12..16],
-        &hex[16..20],
-        &hex[20..32]
-    )
+/// The format of every kind of id
+#[derive(Debug, Clone)]
+pub struct IdFormats {
+    formats: BTreeMap<IdKind, Format>,
+}
+
+impl Default for IdFormats {
+    fn default() -> Self {
+        let formats = IdKind::ALL
+            .into_iter()
+            .map(|kind| {
+                let (prefix, alphabet, length) = kind.real_format();
+                let format = Format {
+                    prefix: prefix.to_string(),
+                    alphabet: Alphabet::Chars(alphabet.chars().collect()),
+                    length,
+                };
+                (kind, format)
+            })
+            .collect();
+        Self { formats }
+    }
+}
+
+impl IdFormats {
+    /// The real APIs' formats with `overrides` applied
+    pub fn new(overrides: &BTreeMap<IdKind, IdFormat>) -> Result<Self, String> {
+        let mut formats = Self::default();
+        for (kind, spec) in overrides {
+            let section = serde_json::to_value(kind).unwrap_or_default();
+            let section = format!("[ids.{}]", section.as_str().unwrap_or_default());
+            let format = formats
+                .formats
+                .get_mut(kind)
+                .expect("every kind has a format");
+            if let Some(prefix) = &spec.prefix {
+                format.prefix = prefix.clone();
+            }
+            if let Some(alphabet) = &spec.alphabet {
+                format.alphabet = match alphabet.as_str() {
+                    "hex" => Alphabet::Chars(HEX.chars().collect()),
+                    "base62" => Alphabet::Chars(BASE62.chars().collect()),
+                    "uuid" => Alphabet::Uuid,
+                    chars => {
+                        let mut unique: Vec<char> = chars.chars().collect();
+                        unique.sort_unstable();
+                        unique.dedup();
+                        if unique.len() < 2 {
+                            return Err(format!(
+                                "{} alphabet needs at least two different characters",
+                                section
+                            ));
+                        }
+                        Alphabet::Chars(chars.chars().collect())
+                    }
+                };
+            }
+            if let Some(length) = spec.length {
+                if length == 0 {
+                    return Err(format!("{} length must be at least 1", section));
+                }
+                format.length = length;
+            }
+        }
+        Ok(formats)
+    }
+
+    /// A new id of `kind`
+    pub fn generate(&self, kind: IdKind) -> String {
+        let format = &self.formats[&kind];
+        let mut rng = rand::rng();
+        let mut id = format.prefix.clone();
+        match &format.alphabet {
+            Alphabet::Chars(chars) => {
+                id.extend((0..format.length).map(|_| chars[rng.random_range(0..chars.len())]));
+            }
+            Alphabet::Uuid => {
+                let hex = format!("{:032x}", rng.random::<u128>());
+                id.push_str(&format!(
+                    "{}-{}-{}-{}-{}",
+                    &hex[0..8],
+                    &hex[8..12],
+                    &hex[12..16],
+                    &hex[16..20],
+                    &hex[20..32]
+                ));
+            }
+        }
+        id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_real_formats() {
+        let formats = IdFormats::default();
+        let id = formats.generate(IdKind::ChatCompletion);
+        let rest = id.strip_prefix("chatcmpl-").unwrap();
+        assert_eq!(rest.len(), 29);
+        assert!(rest.chars().all(|c| c.is_ascii_alphanumeric()));
+
+        let id = formats.generate(IdKind::Response);
+        let rest = id.strip_prefix("resp_").unwrap();
+        assert_eq!(rest.len(), 48);
+        assert!(rest
+            .chars()
+            .all(|c| c.is_ascii_hexdigit() && !c.is_ascii_uppercase()));
+    }
+
+    #[test]
+    fn test_overrides() {
+        let overrides = BTreeMap::from([
+            (
+                IdKind::Response,
+                IdFormat {
+                    prefix: Some("r-".to_string()),
+                    alphabet: Some("ab".to_string()),
+                    length: Some(6),
+                },
+            ),
+            (
+                IdKind::ChatCompletion,
+                IdFormat {
+                    alphabet: Some("uuid".to_string()),
+                    ..IdFormat::default()
+                },
+            ),
+        ]);
+        let formats = IdFormats::new(&overrides).unwrap();
+        let id = formats.generate(IdKind::Response);
+        assert_eq!(id.len(), 8);
+        assert!(id[2..].chars().all(|c| c == 'a' || c == 'b'));
+        let id = formats.generate(IdKind::ChatCompletion);
+        assert_eq!(id.len(), "chatcmpl-".len() + 36);
+        assert_eq!(id.matches('-').count(), 5);
+
+        let bad = |spec| IdFormats::new(&BTreeMap::from([(IdKind::ToolUse, spec)])).unwrap_err();
+        assert_eq!(
+            bad(IdFormat {
+                length: Some(0),
+                ..IdFormat::default()
+            }),
+            "[ids.tool_use] length must be at least 1"
+        );
+        assert!(bad(IdFormat {
+            alphabet: Some("xx".to_string()),
+            ..IdFormat::default()
+        })
+        .contains("two different characters"));
+    }
 }
@@ -11,11 +11,12 @@ use crate::openai::images::{
     completed_image_sse, image_total_duration, partial_image_sse, CompletedImageEvent, ImagesUsage,
     PartialImageEvent, ResolvedImageParams,
 };
+use crate::sleep::{default_sleeper, Sleeper};
 use async_stream::stream;
 use futures_core::Stream;
 use std::pin::Pin;
+use std::sync::Arc;
 use std::time::Duration;
-use tokio::time::sleep;
 
 type OnCompleteCallback = Box<dyn FnOnce() + Send>;
 
@@ -48,6 +49,7 @@ pub struct ImageStream {
     params: ResolvedImageParams,
     latency: LatencyProfile,
     usage: ImagesUsage,
+    sleeper: Arc<dyn Sleeper>,
     on_complete: Option<OnCompleteCallback>,
 }
 
@@ -65,10 +67,17 @@ impl ImageStream {
             params,
             latency,
             usage,
+            sleeper: default_sleeper(),
             on_complete: None,
         }
     }
 
+    /// Wait out delays with this sleeper (default: the tokio timer)
+    pub fn with_sleeper(mut self, sleeper: Arc<dyn Sleeper>) -> Self {
+        self.sleeper = sleeper;
+        self
+    }
+
     pub fn with_on_complete<F>(mut self, callback: F) -> Self
     where
         F: FnOnce() + Send + 'static,
@@ -84,6 +93,7 @@ impl ImageStream {
             params,
             latency,
             usage,
+            sleeper,
             on_complete,
         } = self;
 
@@ -110,7 +120,7 @@ impl ImageStream {
             // Partial frames get progressively finer (smaller block size).
             for i in 0..partials {
                 if !per_frame.is_zero() {
-                    sleep(per_frame).await;
+                    sleeper.sleep(per_frame).await;
                 }
                 let blockiness = 1u32 << (partials - i); // e.g. 8,4,2 for p=3
                 let png = render_png(&PlaceholderSpec {
@@ -136,7 +146,7 @@ impl ImageStream {
 
             // Final, crisp frame.
             if !per_frame.is_zero() {
-                sleep(per_frame).await;
+                sleeper.sleep(per_frame).await;
             }
             let png = render_png(&PlaceholderSpec {
                 width: params.width,
@@ -1,11 +1,22 @@
 // Latency Profiles Module
 // Defines latency profiles for simulating realistic LLM response times.
+//
+// Decision: streams sample through the `LatencyModel` trait, with the
+// profiles as its built-in implementation, so measured models (e.g. Gaussian
+// processes over prompt size and load) can be plugged in without touching the
+// engines. A model sees the request's context, not just its name; streams
+// hold it bound to that context as a `RequestLatency`, which advances the
+// token position as they go. Models are named in a `LatencyModels` registry
+// and picked with `[latency] model`.
 
 use rand::RngExt;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
 use std::time::Duration;
 
 /// Latency profile for simulating LLM response timing
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct LatencyProfile {
     /// Mean time to first token in milliseconds
     pub ttft_mean_ms: u64,
@@ -175,8 +186,46 @@ impl LatencyProfile {
         }
     }
 
-    /// Get a profile based on model name
+    /// A profile by name, as `[latency] profile` takes it ("gpt-5-mini",
+    /// "claude-haiku", "instant", ...)
+    pub fn named(name: &str) -> Option<Self> {
+        let profile = match name.to_lowercase().as_str() {
+            // GPT-5 family
+            "gpt5" | "gpt-5" => Self::gpt5(),
+            "gpt5-mini" | "gpt-5-mini" => Self::gpt5_mini(),
+            // O-series reasoning models (o3, o4)
+            "o3" | "o4" | "o-series" => Self::o_series(),
+            // GPT-4 family
+            "gpt4" | "gpt-4" => Self::gpt4(),
+            "gpt4o" | "gpt-4o" => Self::gpt4o(),
+            // Claude family
+            "claude-opus" | "opus" => Self::claude_opus(),
+            "claude-sonnet" | "sonnet" => Self::claude_sonnet(),
+            "claude-haiku" | "haiku" => Self::claude_haiku(),
+            // Gemini
+            "gemini" | "gemini-pro" => Self::gemini_pro(),
+            "gemini-flash" => Self::gemini_flash(),
+            // DeepSeek
+            "deepseek" | "deepseek-chat" => Self::deepseek(),
+            "deepseek-reasoner" => Self::deepseek_reasoner(),
+            // Special profiles
+            "instant" => Self::instant(),
+            "fast" => Self::fast(),
+            _ => return None,
+        };
+        Some(profile)
+    }
+
+    /// Get a profile for a model: the one its model registry entry pins, or
+    /// else one guessed from the name
     pub fn from_model(model: &str) -> Self {
+        crate::openai::get_model_profile(model)
+            .and_then(|profile| profile.latency.as_ref()?.profile())
+            .unwrap_or_else(|| Self::from_model_name(model))
+    }
+
+    /// Guess a profile from the model name alone
+    fn from_model_name(model: &str) -> Self {
         let model_lower = model.to_lowercase();
 
         // GPT-5 family (check specific variants first)
@@ -218,6 +267,18 @@ impl LatencyProfile {
         }
     }
 
+    /// The same profile with every mean and deviation multiplied by `factor`
+    /// (0.1 runs ten times faster), rounded to whole milliseconds
+    pub fn scaled(&self, factor: f64) -> Self {
+        let scale = |ms: u64| (ms as f64 * factor.max(0.0)).round() as u64;
+        Self {
+            ttft_mean_ms: scale(self.ttft_mean_ms),
+            ttft_stddev_ms: scale(self.ttft_stddev_ms),
+            tbt_mean_ms: scale(self.tbt_mean_ms),
+            tbt_stddev_ms: scale(self.tbt_stddev_ms),
+        }
+    }
+
     /// Sample time to first token using normal distribution
     pub fn sample_ttft(&self) -> Duration {
         if self.ttft_mean_ms == 0 {
@@ -248,6 +309,84 @@ impl LatencyProfile {
         let factor = rng.random_range(0.5..1.5);
         Duration::from_millis((base_ms as f64 * factor) as u64)
     }
+
+    /// Sample the total duration of a stream of `tokens` content chunks:
+    /// one TTFT followed by a TBT before every chunk, as `TokenStream` waits
+    pub fn sample_stream_duration(&self, tokens: usize) -> Duration {
+        self.sample_ttft() + (0..tokens).map(|_| self.sample_tbt()).sum::<Duration>()
+    }
+}
+
+/// Latency pinned to a model, in the model registry or `[models.latency]`:
+/// a profile name, or explicit timing
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ModelLatency {
+    /// A profile `LatencyProfile::named` knows
+    Named(String),
+    /// Explicit means, with deviations defaulting to zero
+    Custom {
+        ttft_mean_ms: u64,
+        #[serde(default)]
+        ttft_stddev_ms: u64,
+        tbt_mean_ms: u64,
+        #[serde(default)]
+        tbt_stddev_ms: u64,
+    },
+}
+
+impl ModelLatency {
+    /// The profile to use; None for a name no profile has
+    pub fn profile(&self) -> Option<LatencyProfile> {
+        match self {
+            Self::Named(name) => LatencyProfile::named(name),
+            Self::Custom {
+                ttft_mean_ms,
+                ttft_stddev_ms,
+                tbt_mean_ms,
+                tbt_stddev_ms,
+            } => Some(LatencyProfile::new(
+                *ttft_mean_ms,
+                *ttft_stddev_ms,
+                *tbt_mean_ms,
+                *tbt_stddev_ms,
+            )),
+        }
+    }
+}
+
+/// Distribution summary of latency samples
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LatencySummary {
+    pub count: usize,
+    pub min: Duration,
+    pub mean: Duration,
+    pub p50: Duration,
+    pub p90: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+}
+
+impl LatencySummary {
+    /// Summarize samples (nearest-rank percentiles). None when empty.
+    pub fn from_samples(samples: &[Duration]) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+        let mut sorted = samples.to_vec();
+        sorted.sort_unstable();
+        let rank =
+            |p: f64| sorted[((p * sorted.len() as f64).ceil() as usize).clamp(1, sorted.len()) - 1];
+        Some(Self {
+            count: sorted.len(),
+            min: sorted[0],
+            mean: sorted.iter().sum::<Duration>() / sorted.len() as u32,
+            p50: rank(0.50),
+            p90: rank(0.90),
+            p99: rank(0.99),
+            max: sorted[sorted.len() - 1],
+        })
+    }
 }
 
 fn sample_normal_ms(mean_ms: u64, stddev_ms: u64, rng: &mut impl rand::Rng) -> u64 {
@@ -268,6 +407,109 @@ impl Default for LatencyProfile {
     }
 }
 
+/// What a latency model knows about the request it is timing
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LatencyContext {
+    pub model: String,
+    pub prompt_tokens: u32,
+    /// Index of the chunk about to be sent, from 0 for the first token
+    pub position: u32,
+    /// Requests in flight when this one began, itself included
+    pub concurrency: u64,
+}
+
+/// Samples the delays of a simulated response
+pub trait LatencyModel: Send + Sync {
+    /// Wait before the first token
+    fn sample_ttft(&self, context: &LatencyContext) -> Duration;
+    /// Wait before the token at `context.position`
+    fn sample_tbt(&self, context: &LatencyContext) -> Duration;
+}
+
+/// Profiles ignore the context: the same distributions for every request
+impl LatencyModel for LatencyProfile {
+    fn sample_ttft(&self, _context: &LatencyContext) -> Duration {
+        LatencyProfile::sample_ttft(self)
+    }
+
+    fn sample_tbt(&self, _context: &LatencyContext) -> Duration {
+        LatencyProfile::sample_tbt(self)
+    }
+}
+
+/// Another model's delays multiplied by `factor`
+pub struct ScaledLatency {
+    pub inner: Arc<dyn LatencyModel>,
+    pub factor: f64,
+}
+
+impl LatencyModel for ScaledLatency {
+    fn sample_ttft(&self, context: &LatencyContext) -> Duration {
+        self.inner.sample_ttft(context).mul_f64(self.factor)
+    }
+
+    fn sample_tbt(&self, context: &LatencyContext) -> Duration {
+        self.inner.sample_tbt(context).mul_f64(self.factor)
+    }
+}
+
+/// A latency model bound to the request it times
+#[derive(Clone)]
+pub struct RequestLatency {
+    model: Arc<dyn LatencyModel>,
+    context: LatencyContext,
+}
+
+impl RequestLatency {
+    pub fn new(model: Arc<dyn LatencyModel>, context: LatencyContext) -> Self {
+        Self { model, context }
+    }
+
+    pub fn context(&self) -> &LatencyContext {
+        &self.context
+    }
+
+    /// Wait before the first token (or before an event that starts a new
+    /// round of work, such as a tool call)
+    pub fn ttft(&self) -> Duration {
+        self.model.sample_ttft(&self.context)
+    }
+
+    /// Wait before the next token
+    pub fn tbt(&mut self) -> Duration {
+        self.context.position += 1;
+        self.model.sample_tbt(&self.context)
+    }
+}
+
+impl From<LatencyProfile> for RequestLatency {
+    fn from(profile: LatencyProfile) -> Self {
+        Self::new(Arc::new(profile), LatencyContext::default())
+    }
+}
+
+/// Latency models by name, for `[latency] model`
+#[derive(Clone, Default)]
+pub struct LatencyModels {
+    models: HashMap<String, Arc<dyn LatencyModel>>,
+}
+
+impl LatencyModels {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Make `model` selectable as `name`
+    pub fn with(mut self, name: impl Into<String>, model: Arc<dyn LatencyModel>) -> Self {
+        self.models.insert(name.into(), model);
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<dyn LatencyModel>> {
+        self.models.get(name).cloned()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -286,6 +528,21 @@ mod tests {
         assert_eq!(instant.tbt_mean_ms, 0);
     }
 
+    #[test]
+    fn test_scaled_profile() {
+        let fast = LatencyProfile::new(800, 200, 50, 15).scaled(0.1);
+        assert_eq!(fast.ttft_mean_ms, 80);
+        assert_eq!(fast.ttft_stddev_ms, 20);
+        assert_eq!(fast.tbt_mean_ms, 5);
+        assert_eq!(fast.tbt_stddev_ms, 2);
+
+        let slow = LatencyProfile::gpt4().scaled(2.0);
+        assert_eq!(slow.ttft_mean_ms, 1600);
+
+        let none = LatencyProfile::gpt4().scaled(-1.0);
+        assert_eq!(none.sample_ttft(), Duration::ZERO);
+    }
+
     #[test]
     fn test_gpt5_family() {
         let gpt5 = LatencyProfile::gpt5();
@@ -326,6 +583,34 @@ mod tests {
         assert_eq!(tbt, Duration::ZERO);
     }
 
+    #[test]
+    fn test_sample_stream_duration() {
+        let profile = LatencyProfile::new(100, 0, 10, 0);
+        assert_eq!(
+            profile.sample_stream_duration(5),
+            Duration::from_millis(150)
+        );
+        assert_eq!(
+            profile.sample_stream_duration(0),
+            Duration::from_millis(100)
+        );
+    }
+
+    #[test]
+    fn test_latency_summary() {
+        let samples: Vec<Duration> = (1..=100).map(Duration::from_millis).collect();
+        let summary = LatencySummary::from_samples(&samples).unwrap();
+        assert_eq!(summary.count, 100);
+        assert_eq!(summary.min, Duration::from_millis(1));
+        assert_eq!(summary.p50, Duration::from_millis(50));
+        assert_eq!(summary.p90, Duration::from_millis(90));
+        assert_eq!(summary.p99, Duration::from_millis(99));
+        assert_eq!(summary.max, Duration::from_millis(100));
+        assert_eq!(summary.mean, Duration::from_micros(50_500));
+
+        assert!(LatencySummary::from_samples(&[]).is_none());
+    }
+
     #[test]
     fn test_from_model() {
         // GPT-5 family
@@ -367,6 +652,71 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_from_model_consults_the_registry() {
+        // Names the heuristics read as full-size models
+        assert_eq!(
+            LatencyProfile::from_model("gpt-5.4-nano"),
+            LatencyProfile::gpt5_mini()
+        );
+        assert_eq!(
+            LatencyProfile::from_model("gpt-4.1-mini"),
+            LatencyProfile::gpt4o()
+        );
+        let spark = LatencyProfile::from_model("gpt-5.3-codex-spark");
+        assert!(spark.tbt_mean_ms < LatencyProfile::gpt5_mini().tbt_mean_ms);
+        // Unpinned registry entries and unknown models still go by name
+        assert_eq!(LatencyProfile::from_model("o3"), LatencyProfile::o_series());
+        assert_eq!(
+            LatencyProfile::from_model("acme-haiku-v2"),
+            LatencyProfile::claude_haiku()
+        );
+    }
+
+    #[test]
+    fn test_model_latency() {
+        let named: ModelLatency = serde_json::from_str("\"Haiku\"").unwrap();
+        assert_eq!(named.profile(), Some(LatencyProfile::claude_haiku()));
+        let custom: ModelLatency =
+            serde_json::from_str(r#"{"ttft_mean_ms": 900, "tbt_mean_ms": 12}"#).unwrap();
+        assert_eq!(custom.profile(), Some(LatencyProfile::new(900, 0, 12, 0)));
+        assert_eq!(ModelLatency::Named("warp".to_string()).profile(), None);
+    }
+
+    /// Waits `position` ms before each token, to show what it was given
+    struct ByPosition;
+
+    impl LatencyModel for ByPosition {
+        fn sample_ttft(&self, context: &LatencyContext) -> Duration {
+            Duration::from_millis(context.prompt_tokens as u64)
+        }
+
+        fn sample_tbt(&self, context: &LatencyContext) -> Duration {
+            Duration::from_millis(context.position as u64)
+        }
+    }
+
+    #[test]
+    fn test_request_latency_advances_position() {
+        let context = LatencyContext {
+            prompt_tokens: 40,
+            ..LatencyContext::default()
+        };
+        let mut latency = RequestLatency::new(Arc::new(ByPosition), context);
+        assert_eq!(latency.ttft(), Duration::from_millis(40));
+        assert_eq!(latency.tbt(), Duration::from_millis(1));
+        assert_eq!(latency.tbt(), Duration::from_millis(2));
+
+        let scaled = ScaledLatency {
+            inner: Arc::new(ByPosition),
+            factor: 0.5,
+        };
+        assert_eq!(
+            scaled.sample_ttft(latency.context()),
+            Duration::from_millis(20)
+        );
+    }
+
     #[test]
     fn test_custom_profile() {
         let custom = LatencyProfile::new(100, 10, 5, 1);
@@ -0,0 +1,390 @@
+// Learned Profiles Module
+// Per-model latency and response-length distributions measured from real
+// upstream traffic, saved as JSON and replayed by the simulator.
+//
+// Decision: each distribution is kept as a running mean and standard
+// deviation (Welford) instead of raw samples. The file stays a few lines per
+// model however long recording runs, a restarted recorder keeps
+// accumulating into it, and the numbers map straight onto the normal
+// distributions `LatencyProfile` already samples from. Latency is learned
+// from streamed responses only: TTFT is the wait for the first body chunk
+// and TBT the rest of the stream spread over its completion tokens.
+// Non-streamed responses still teach response length.
+
+use crate::latency::LatencyProfile;
+use rand::RngExt;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Models tracked at most, so arbitrary model names cannot grow the file
+/// without bound
+const MAX_MODELS: usize = 256;
+
+/// Running mean and standard deviation of a measured quantity
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(from = "DistributionFile", into = "DistributionFile")]
+pub struct Distribution {
+    count: u64,
+    mean: f64,
+    /// Sum of squared differences from the mean
+    m2: f64,
+}
+
+/// How a distribution is written: the standard deviation instead of `m2`
+#[derive(Serialize, Deserialize)]
+struct DistributionFile {
+    count: u64,
+    mean: f64,
+    stddev: f64,
+}
+
+impl From<DistributionFile> for Distribution {
+    fn from(file: DistributionFile) -> Self {
+        Self {
+            count: file.count,
+            mean: file.mean,
+            m2: file.stddev * file.stddev * file.count as f64,
+        }
+    }
+}
+
+impl From<Distribution> for DistributionFile {
+    fn from(d: Distribution) -> Self {
+        Self {
+            count: d.count,
+            mean: d.mean,
+            stddev: d.stddev(),
+        }
+    }
+}
+
+impl Distribution {
+    pub fn add(&mut self, value: f64) {
+        self.count += 1;
+        let delta = value - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (value - self.mean);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn mean(&self) -> f64 {
+        self.mean
+    }
+
+    /// Population standard deviation; 0 until two values are in
+    pub fn stddev(&self) -> f64 {
+        if self.count < 2 {
+            0.0
+        } else {
+            (self.m2 / self.count as f64).sqrt()
+        }
+    }
+
+    /// A normally distributed value with this mean and deviation, not below
+    /// zero
+    pub fn sample(&self, rng: &mut impl rand::Rng) -> f64 {
+        let u1: f64 = rng.random_range(f64::MIN_POSITIVE..1.0);
+        let u2: f64 = rng.random_range(0.0..1.0);
+        let z = (-2.0 * u1.ln()).sqrt() * (std::f64::consts::TAU * u2).cos();
+        (self.mean + self.stddev() * z).max(0.0)
+    }
+}
+
+/// What was learned about one model
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ModelProfile {
+    #[serde(default)]
+    pub ttft_ms: Distribution,
+    #[serde(default)]
+    pub tbt_ms: Distribution,
+    #[serde(default)]
+    pub completion_tokens: Distribution,
+}
+
+impl ModelProfile {
+    /// Latency profile replaying the learned timing, once a streamed
+    /// response has been seen
+    pub fn latency(&self) -> Option<LatencyProfile> {
+        if self.ttft_ms.count() == 0 {
+            return None;
+        }
+        let ms = |v: f64| v.round() as u64;
+        Some(LatencyProfile::new(
+            ms(self.ttft_ms.mean()),
+            ms(self.ttft_ms.stddev()),
+            ms(self.tbt_ms.mean()),
+            ms(self.tbt_ms.stddev()),
+        ))
+    }
+
+    /// A response length (at least one token) drawn from the learned
+    /// distribution
+    pub fn sample_completion_tokens(&self, rng: &mut impl rand::Rng) -> Option<usize> {
+        if self.completion_tokens.count() == 0 {
+            return None;
+        }
+        Some((self.completion_tokens.sample(rng).round() as usize).max(1))
+    }
+}
+
+/// One response observed from a real API
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Observation {
+    pub ttft: Option<Duration>,
+    pub tbt: Option<Duration>,
+    pub completion_tokens: Option<u64>,
+}
+
+/// Arrival times of a response's body chunks
+#[derive(Debug, Clone, Copy)]
+pub struct ChunkTiming {
+    started: Instant,
+    first: Option<Instant>,
+    last: Option<Instant>,
+}
+
+impl ChunkTiming {
+    /// Timing for a request sent at `started`
+    pub fn new(started: Instant) -> Self {
+        Self {
+            started,
+            first: None,
+            last: None,
+        }
+    }
+
+    /// A body chunk arrived now
+    pub fn chunk(&mut self) {
+        let now = Instant::now();
+        self.first.get_or_insert(now);
+        self.last = Some(now);
+    }
+
+    /// What the response teaches. Timing only counts for streams, where the
+    /// first chunk stands for the first token.
+    pub fn observation(&self, streamed: bool, completion_tokens: Option<u64>) -> Observation {
+        let (ttft, tbt) = match (streamed, self.first, self.last) {
+            (true, Some(first), Some(last)) => {
+                let tbt = completion_tokens
+                    .filter(|tokens| *tokens > 1)
+                    .map(|tokens| (last - first) / (tokens - 1) as u32);
+                (Some(first - self.started), tbt)
+            }
+            _ => (None, None),
+        };
+        Observation {
+            ttft,
+            tbt,
+            completion_tokens,
+        }
+    }
+}
+
+/// Learned profiles keyed by model name, as saved to disk
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LearnedProfiles {
+    #[serde(default)]
+    pub models: BTreeMap<String, ModelProfile>,
+}
+
+impl LearnedProfiles {
+    /// Read a saved profile file
+    pub fn load(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let text = std::fs::read_to_string(path)?;
+        serde_json::from_str(&text)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+    }
+
+    /// Write the profiles, replacing the file in one step so a reader never
+    /// sees half of it
+    pub fn save(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let path = path.as_ref();
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        let json = serde_json::to_string_pretty(self).map_err(std::io::Error::other)?;
+        std::fs::write(&tmp, json)?;
+        std::fs::rename(&tmp, path)
+    }
+
+    pub fn get(&self, model: &str) -> Option<&ModelProfile> {
+        self.models.get(model)
+    }
+
+    /// Fold one observation into `model`'s profile
+    pub fn record(&mut self, model: &str, observation: &Observation) {
+        if model.is_empty() || (!self.models.contains_key(model) && self.models.len() >= MAX_MODELS)
+        {
+            return;
+        }
+        let profile = self.models.entry(model.to_string()).or_default();
+        let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+        if let Some(ttft) = observation.ttft {
+            profile.ttft_ms.add(ms(ttft));
+        }
+        if let Some(tbt) = observation.tbt {
+            profile.tbt_ms.add(ms(tbt));
+        }
+        if let Some(tokens) = observation.completion_tokens {
+            profile.completion_tokens.add(tokens as f64);
+        }
+    }
+}
+
+/// Learns profiles from observations and saves them periodically
+pub struct Recorder {
+    path: PathBuf,
+    save_interval: Duration,
+    state: Mutex<(LearnedProfiles, Option<Instant>)>,
+}
+
+impl Recorder {
+    /// Recorder saving to `path` at most every `save_interval`. An existing
+    /// file is extended rather than replaced.
+    pub fn new(path: impl Into<PathBuf>, save_interval: Duration) -> Self {
+        let path = path.into();
+        let profiles = LearnedProfiles::load(&path).unwrap_or_default();
+        Self {
+            path,
+            save_interval,
+            state: Mutex::new((profiles, None)),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Record an observation, saving when the interval has passed since the
+    /// last save
+    pub fn record(&self, model: &str, observation: &Observation) {
+        let Ok(mut state) = self.state.lock() else {
+            return;
+        };
+        let (profiles, last_save) = &mut *state;
+        profiles.record(model, observation);
+        if last_save.is_none_or(|at| at.elapsed() >= self.save_interval) {
+            if let Err(e) = profiles.save(&self.path) {
+                tracing::warn!("Saving learned profiles to {:?} failed: {}", self.path, e);
+            }
+            *last_save = Some(Instant::now());
+        }
+    }
+
+    /// Profiles learned so far
+    pub fn profiles(&self) -> LearnedProfiles {
+        self.state
+            .lock()
+            .map(|state| state.0.clone())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_distribution() {
+        let mut d = Distribution::default();
+        for v in [2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0] {
+            d.add(v);
+        }
+        assert_eq!(d.count(), 8);
+        assert_eq!(d.mean(), 5.0);
+        assert_eq!(d.stddev(), 2.0);
+
+        // Written with its standard deviation, read back to the same state
+        let json = serde_json::to_value(d).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({"count": 8, "mean": 5.0, "stddev": 2.0})
+        );
+        let back: Distribution = serde_json::from_value(json).unwrap();
+        assert_eq!(back, d);
+    }
+
+    #[test]
+    fn test_chunk_timing() {
+        let started = Instant::now() - Duration::from_millis(500);
+        let mut timing = ChunkTiming::new(started);
+        timing.first = Some(started + Duration::from_millis(300));
+        timing.last = Some(started + Duration::from_millis(400));
+
+        let streamed = timing.observation(true, Some(11));
+        assert_eq!(streamed.ttft, Some(Duration::from_millis(300)));
+        assert_eq!(streamed.tbt, Some(Duration::from_millis(10)));
+        assert_eq!(streamed.completion_tokens, Some(11));
+
+        let buffered = timing.observation(false, Some(11));
+        assert_eq!(buffered.ttft, None);
+        assert_eq!(buffered.tbt, None);
+        assert_eq!(buffered.completion_tokens, Some(11));
+    }
+
+    #[test]
+    fn test_learned_profile_replay() {
+        let mut profiles = LearnedProfiles::default();
+        for (ttft, tokens) in [(400, 90), (600, 110)] {
+            profiles.record(
+                "gpt-4o",
+                &Observation {
+                    ttft: Some(Duration::from_millis(ttft)),
+                    tbt: Some(Duration::from_millis(20)),
+                    completion_tokens: Some(tokens),
+                },
+            );
+        }
+        profiles.record(
+            "gpt-5",
+            &Observation {
+                completion_tokens: Some(50),
+                ..Default::default()
+            },
+        );
+
+        let gpt4o = profiles.get("gpt-4o").unwrap();
+        let latency = gpt4o.latency().unwrap();
+        assert_eq!(latency.ttft_mean_ms, 500);
+        assert_eq!(latency.ttft_stddev_ms, 100);
+        assert_eq!(latency.tbt_mean_ms, 20);
+        assert_eq!(latency.tbt_stddev_ms, 0);
+        let tokens = gpt4o.sample_completion_tokens(&mut rand::rng()).unwrap();
+        assert!(tokens >= 1);
+
+        // Length without latency when only buffered responses were seen
+        let gpt5 = profiles.get("gpt-5").unwrap();
+        assert!(gpt5.latency().is_none());
+        assert_eq!(gpt5.sample_completion_tokens(&mut rand::rng()), Some(50));
+    }
+
+    #[test]
+    fn test_recorder_saves_and_resumes() {
+        let path = std::env::temp_dir().join(format!("llmsim-learned-{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let observation = Observation {
+            completion_tokens: Some(10),
+            ..Default::default()
+        };
+
+        let recorder = Recorder::new(&path, Duration::ZERO);
+        recorder.record("gpt-4o", &observation);
+        let saved = LearnedProfiles::load(&path).unwrap();
+        assert_eq!(saved.models["gpt-4o"].completion_tokens.count(), 1);
+
+        let resumed = Recorder::new(&path, Duration::ZERO);
+        resumed.record("gpt-4o", &observation);
+        assert_eq!(
+            resumed.profiles().models["gpt-4o"]
+                .completion_tokens
+                .count(),
+            2
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+}
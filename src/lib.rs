@@ -42,19 +42,45 @@
 
 // Core library modules
 pub mod anthropic;
+pub mod capacity;
+pub mod citations;
+pub mod clock;
+pub mod code_interpreter;
+pub mod conformance;
+pub mod decisions;
+pub mod deprecation;
+pub mod drill;
+pub mod encrypted_reasoning;
 pub mod errors;
+pub mod fingerprint;
 pub mod generator;
+pub mod glob;
 mod ids;
 pub mod image_stream;
 pub mod imagegen;
 pub mod latency;
+pub mod learned;
+pub mod mcp;
 pub mod openai;
 pub mod openresponses;
+pub mod overrides;
+pub mod pacing;
+pub mod pipeline;
+pub mod pool;
+pub mod pricing;
+pub mod ratelimit;
 pub mod responses_stream;
+pub mod schema;
+pub mod schema_faker;
 pub mod script;
 pub mod script_stream;
+pub mod sleep;
+mod stamps;
 pub mod stats;
+pub mod stop;
 pub mod stream;
+pub mod tool_calls;
+pub mod watchdog;
 
 // Token counting via tiktoken-rs (enabled by the `tokens` feature)
 #[cfg(feature = "tokens")]
@@ -69,19 +95,41 @@ pub mod cli;
 pub mod tui;
 
 // Re-export commonly used types
+pub use capacity::LoadShedder;
+pub use conformance::{ConformanceChecker, ConformanceFailure};
+pub use decisions::{Decision, DecisionLog, ErrorCause, Outcome};
+pub use deprecation::{DeprecationSchedule, DeprecationStatus, Deprecations};
+pub use drill::{DrillKind, DrillSpec, DrillStatus, Drills, StormPhase, StormProgress};
 pub use errors::{ErrorConfig, ErrorInjector, SimulatedError};
+pub use fingerprint::SystemFingerprints;
 pub use generator::{
-    create_generator, EchoGenerator, FixedGenerator, LoremGenerator, RandomWordGenerator,
-    ResponseGenerator, SequenceGenerator,
+    create_generator, CutoffGenerator, EchoGenerator, EchoMode, FixedGenerator, LoremGenerator,
+    RandomWordGenerator, ResponseGenerator, SequenceGenerator, TextPieces,
 };
-pub use latency::LatencyProfile;
+pub use ids::{IdFormat, IdFormats, IdKind};
+pub use latency::{
+    LatencyContext, LatencyModel, LatencyModels, LatencyProfile, ModelLatency, RequestLatency,
+};
+pub use learned::{LearnedProfiles, ModelProfile, Recorder};
+pub use overrides::{ForcedError, RequestOverrides};
+pub use pipeline::{FormatAdapter, PipelineRequest, RequestPipeline, TextTokenizer};
+pub use pricing::ModelPrice;
+pub use ratelimit::{BudgetStatus, RateLimitStatus, RateLimiter, RetryAfterMode, RetryAfterPolicy};
 pub use responses_stream::{ResponsesTokenStream, ResponsesTokenStreamBuilder};
+pub use schema::{PayloadSchema, SchemaViolation};
 pub use script::{
     OnExhausted, Script, ScriptError, ScriptSpec, ScriptedResponse, SimError, SimToolCall, SimTurn,
 };
-pub use stats::{new_shared_stats, EndpointType, SharedStats, Stats, StatsSnapshot};
-pub use stream::{TokenStream, TokenStreamBuilder};
+pub use sleep::{Sleeper, VirtualSleeper};
+pub use stamps::{Scoped, Stamps};
+pub use stats::{
+    new_shared_stats, EndpointType, SharedStats, SizeBucket, SizeSnapshot, Stats, StatsSnapshot,
+};
+pub use stream::{ChunkEncoder, TokenStream, TokenStreamBuilder};
 #[cfg(feature = "tokens")]
 pub use tokens::{
-    count_tokens, count_tokens_default, estimate_image_tokens, TokenCounter, TokenError,
+    count_chat_tokens, count_tokens, count_tokens_default, estimate_image_tokens, Encoding,
+    EncodingMap, TokenCounter, TokenError, Tokenizer,
 };
+pub use tool_calls::{ToolArguments, ToolCallConfig};
+pub use watchdog::StreamWatchdog;
@@ -2,17 +2,41 @@
 //!
 //! Usage:
 //!   llmsim serve [OPTIONS]    Start the HTTP server
+//!   llmsim presets [NAME]     List bundled presets, or print one
+//!   llmsim config validate    Check a config file for typos and bad values
+//!   llmsim config defaults    Print the effective configuration
+//!   llmsim tokens count       Count tokens in a prompt
+//!   llmsim latency-test       Preview a latency profile's distribution
+//!   llmsim verify --target    Check an endpoint against canned requests
 //!
 //! Examples:
 //!   llmsim serve --port 8080
 //!   llmsim serve --config config.toml
+//!   llmsim serve --preset flaky-provider
 //!   llmsim serve --generator echo --target-tokens 50
 //!   llmsim serve --tui              # Start with real-time stats dashboard
+//!   llmsim serve --tui --tui-history run.csv --resume
+//!   llmsim serve --workers 4        # Four processes on one port
+//!   llmsim tokens count --model gpt-4o --file prompt.txt
+//!   llmsim tokens count --chat < messages.json
+//!   llmsim latency-test --profile claude-sonnet --tokens 500
+//!   llmsim verify --target http://localhost:8080/openai
 
 use clap::{Parser, Subcommand};
-use llmsim::cli::{Config, ConfigError};
+use llmsim::cli::{
+    build_runtime, check_config, config_toml, find_preset, verify, verify_report, CheckResult,
+    Config, ConfigError, ConfigIssue, RuntimeConfig, VerifyOptions, PRESETS,
+};
+use llmsim::latency::{LatencyProfile, LatencySummary};
+use llmsim::openai::Message;
 #[cfg(feature = "tui")]
 use llmsim::tui::{run_dashboard, DashboardConfig};
+use llmsim::EncodingMap;
+use serde::Deserialize;
+use std::fmt::Write;
+use std::io::Read;
+use std::path::Path;
+use std::time::Duration;
 
 #[derive(Parser)]
 #[command(name = "llmsim")]
@@ -27,9 +51,15 @@ enum Commands {
     /// Start the LLMSim HTTP server
     Serve {
         /// Configuration file path (TOML)
+        ///
+        /// With `--preset`, its settings are layered over the preset's.
         #[arg(short, long)]
         config: Option<String>,
 
+        /// Start from a bundled configuration (see `llmsim presets`)
+        #[arg(long)]
+        preset: Option<String>,
+
         /// Port to listen on
         #[arg(short, long)]
         port: Option<u16>,
@@ -38,7 +68,7 @@ enum Commands {
         #[arg(long, env = "LLMSIM_HOST")]
         host: Option<String>,
 
-        /// Response generator (lorem, echo, random, fixed:text)
+        /// Response generator (lorem, echo, random, sequence, cutoff, fixed:text)
         ///
         /// Overrides the config file when set; otherwise the config value
         /// (or the "lorem" default) is used.
@@ -52,27 +82,353 @@ enum Commands {
         #[arg(long)]
         target_tokens: Option<usize>,
 
+        /// Validate every streamed chat chunk and Responses event against
+        /// the bundled JSON Schemas, ending the stream with an error on drift
+        ///
+        /// Same as `strict_schema = true` under `[stream]` in the config.
+        #[arg(long)]
+        strict_schema: bool,
+
+        /// Disable every simulated delay (time to first token, inter-token
+        /// gaps, image generation time) for fast functional test runs
+        ///
+        /// Same as `disable_delays = true` under `[simulation]` in the config.
+        #[arg(long)]
+        no_latency: bool,
+
         /// Show real-time stats dashboard (TUI)
         ///
         /// Requires building with `--features tui`.
         #[arg(long)]
         tui: bool,
+
+        /// File the dashboard's RPS and token-rate history is saved to on
+        /// exit: CSV for a `.csv` path, JSON otherwise
+        #[arg(long, requires = "tui")]
+        tui_history: Option<std::path::PathBuf>,
+
+        /// Start the dashboard's charts from the `--tui-history` file
+        #[arg(long, requires = "tui_history")]
+        resume: bool,
+
+        /// Threads of the runtime running requests (default: one per core)
+        ///
+        /// Same as `worker_threads` under `[runtime]` in the config.
+        #[arg(long, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+        worker_threads: Option<usize>,
+
+        /// Most threads kept for blocking work (default: 512)
+        ///
+        /// Same as `max_blocking_threads` under `[runtime]` in the config.
+        #[arg(long, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+        max_blocking_threads: Option<usize>,
+
+        /// Run this many server processes sharing the port (Unix only)
+        ///
+        /// Each worker is a copy of this command binding the port with
+        /// SO_REUSEPORT; their combined stats are at /llmsim/cluster/stats.
+        /// Pair with --worker-threads so the workers do not oversubscribe
+        /// the cores.
+        #[arg(long, conflicts_with = "tui", value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+        workers: Option<usize>,
+    },
+
+    /// List the bundled presets, or print one's configuration
+    Presets {
+        /// Preset to print as TOML
+        name: Option<String>,
+    },
+
+    /// Check a config file, or print the configuration with its defaults
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+
+    /// Token utilities
+    Tokens {
+        #[command(subcommand)]
+        command: TokensCommand,
+    },
+
+    /// Sample a latency profile and print its distribution
+    LatencyTest {
+        /// Configuration file whose `[latency]` section is sampled
+        #[arg(short, long)]
+        config: Option<String>,
+
+        /// Named latency profile (e.g. gpt-5, claude-sonnet, fast)
+        #[arg(long)]
+        profile: Option<String>,
+
+        /// Custom profile: mean time to first token (ms)
+        #[arg(long)]
+        ttft_mean_ms: Option<u64>,
+
+        /// Custom profile: time to first token standard deviation (ms)
+        #[arg(long)]
+        ttft_stddev_ms: Option<u64>,
+
+        /// Custom profile: mean time between tokens (ms)
+        #[arg(long)]
+        tbt_mean_ms: Option<u64>,
+
+        /// Custom profile: time between tokens standard deviation (ms)
+        #[arg(long)]
+        tbt_stddev_ms: Option<u64>,
+
+        /// Number of samples to draw
+        #[arg(short = 'n', long, default_value_t = 1000)]
+        samples: usize,
+
+        /// Also sample the duration of a whole stream of this many tokens
+        #[arg(long)]
+        tokens: Option<usize>,
+    },
+
+    /// Send canned requests (streaming, tools, JSON mode, errors) to an
+    /// OpenAI-compatible endpoint and report where it deviates
+    ///
+    /// Exits with status 1 if any check fails.
+    Verify {
+        /// Base URL the `/v1/...` paths are appended to, e.g.
+        /// http://localhost:8080/openai or https://api.openai.com
+        #[arg(long)]
+        target: String,
+
+        /// Model the generation requests ask for
+        #[arg(short, long, default_value = "gpt-5")]
+        model: String,
+
+        /// Bearer token for the target
+        #[arg(long, env = "OPENAI_API_KEY", hide_env_values = true)]
+        api_key: Option<String>,
+
+        /// Seconds to wait for each response
+        #[arg(long, default_value_t = 60)]
+        timeout_secs: u64,
+
+        /// Also validate every streamed payload against the bundled JSON
+        /// Schemas
+        #[arg(long)]
+        strict_schema: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConfigCommand {
+    /// Report syntax errors, unknown keys and invalid values with their
+    /// line and column; exits with status 1 if there are any
+    Validate {
+        /// Configuration file path (TOML)
+        file: String,
+    },
+
+    /// Print the effective configuration, every default filled in
+    Defaults {
+        /// Configuration file to resolve instead of the built-in defaults
+        #[arg(short, long)]
+        config: Option<String>,
+
+        /// Bundled preset to start from (see `llmsim presets`)
+        #[arg(long)]
+        preset: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum TokensCommand {
+    /// Count tokens in text read from a file or stdin
+    Count {
+        /// Model whose tokenizer to use
+        #[arg(short, long, default_value = "gpt-5")]
+        model: String,
+
+        /// Input file; reads stdin when omitted
+        #[arg(short, long)]
+        file: Option<String>,
+
+        /// Configuration file whose `[tokens]` model mappings and fallback
+        /// encoding are used
+        #[arg(short, long)]
+        config: Option<String>,
+
+        /// Treat the input as chat messages (a JSON array of messages or a
+        /// request body with `messages`) and include per-message overhead
+        #[arg(long)]
+        chat: bool,
     },
 }
 
+/// Chat input for `tokens count --chat`
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ChatInput {
+    Messages(Vec<Message>),
+    Request { messages: Vec<Message> },
+}
+
+/// Sample a profile and render percentile summaries plus a histogram of the
+/// longest measured span (the stream duration when `tokens` is set, else TTFT)
+fn latency_report(profile: &LatencyProfile, samples: usize, tokens: Option<usize>) -> String {
+    let ttft: Vec<Duration> = (0..samples).map(|_| profile.sample_ttft()).collect();
+    let tbt: Vec<Duration> = (0..samples).map(|_| profile.sample_tbt()).collect();
+    let stream: Option<Vec<Duration>> = tokens.map(|n| {
+        (0..samples)
+            .map(|_| profile.sample_stream_duration(n))
+            .collect()
+    });
+
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "Latency profile: ttft {}ms ± {}ms, tbt {}ms ± {}ms ({} samples)\n",
+        profile.ttft_mean_ms,
+        profile.ttft_stddev_ms,
+        profile.tbt_mean_ms,
+        profile.tbt_stddev_ms,
+        samples
+    );
+    let _ = writeln!(
+        out,
+        "{:<14}{:>10}{:>10}{:>10}{:>10}{:>10}{:>10}",
+        "", "min", "mean", "p50", "p90", "p99", "max"
+    );
+    let mut rows = vec![("ttft".to_string(), &ttft), ("tbt".to_string(), &tbt)];
+    if let (Some(n), Some(stream)) = (tokens, &stream) {
+        rows.push((format!("stream ({})", n), stream));
+    }
+    for (label, values) in rows {
+        if let Some(s) = LatencySummary::from_samples(values) {
+            let _ = writeln!(
+                out,
+                "{:<14}{:>10}{:>10}{:>10}{:>10}{:>10}{:>10}",
+                label,
+                format_ms(s.min),
+                format_ms(s.mean),
+                format_ms(s.p50),
+                format_ms(s.p90),
+                format_ms(s.p99),
+                format_ms(s.max)
+            );
+        }
+    }
+
+    let (title, values) = match &stream {
+        Some(stream) => ("stream duration", stream),
+        None => ("ttft", &ttft),
+    };
+    let _ = writeln!(out, "\n{} histogram", title);
+    for line in histogram(values, 10, 40) {
+        let _ = writeln!(out, "{}", line);
+    }
+    out
+}
+
+fn format_ms(d: Duration) -> String {
+    format!("{:.0}ms", d.as_secs_f64() * 1000.0)
+}
+
+/// Equal-width buckets between the smallest and largest sample, one line each
+fn histogram(samples: &[Duration], buckets: usize, width: usize) -> Vec<String> {
+    let ms: Vec<f64> = samples.iter().map(|d| d.as_secs_f64() * 1000.0).collect();
+    let (Some(min), Some(max)) = (
+        ms.iter().copied().reduce(f64::min),
+        ms.iter().copied().reduce(f64::max),
+    ) else {
+        return Vec::new();
+    };
+    let step = ((max - min) / buckets as f64).max(1.0);
+    let buckets = (((max - min) / step) as usize + 1).min(buckets);
+
+    let mut counts = vec![0usize; buckets];
+    for v in &ms {
+        counts[(((v - min) / step) as usize).min(buckets - 1)] += 1;
+    }
+    let peak = counts.iter().copied().max().unwrap_or(1).max(1);
+    counts
+        .iter()
+        .enumerate()
+        .map(|(i, &count)| {
+            let lo = min + step * i as f64;
+            format!(
+                "{:>8.0}-{:<8} | {:<width$} {}",
+                lo,
+                format!("{:.0}ms", lo + step),
+                "#".repeat(count * width / peak),
+                count,
+                width = width
+            )
+        })
+        .collect()
+}
+
+fn count_input_tokens(
+    encodings: &EncodingMap,
+    input: &str,
+    model: &str,
+    chat: bool,
+) -> Result<usize, Box<dyn std::error::Error>> {
+    if !chat {
+        return Ok(encodings.count_tokens(input, model)?);
+    }
+    let messages = match serde_json::from_str(input)
+        .map_err(|e| format!("invalid chat messages JSON: {}", e))?
+    {
+        ChatInput::Messages(messages) | ChatInput::Request { messages } => messages,
+    };
+    Ok(encodings.count_chat_tokens(&messages, model)?)
+}
+
+/// The configuration of a `--preset`, with a `--config` file layered on top,
+/// or of either alone
+fn load_config(config_file: Option<String>, preset: Option<String>) -> Result<Config, ConfigError> {
+    let Some(name) = preset else {
+        return match config_file {
+            Some(path) => Config::from_file(&path),
+            None => Ok(Config::default()),
+        };
+    };
+    let preset = find_preset(&name).ok_or_else(|| {
+        ConfigError::Validation(format!(
+            "unknown preset {:?}; `llmsim presets` lists them",
+            name
+        ))
+    })?;
+    match config_file {
+        Some(path) => preset.config_with_file(path),
+        None => preset.config(None),
+    }
+}
+
+/// `line:column: message`, compiler style, or ` message` for an issue with
+/// no position in the file
+fn issue_location(issue: &ConfigIssue) -> String {
+    match issue.position {
+        Some((line, column)) => format!("{}:{}: {}", line, column, issue.message),
+        None => format!(" {}", issue.message),
+    }
+}
+
+/// One line per bundled preset
+fn presets_report() -> String {
+    let width = PRESETS.iter().map(|p| p.name.len()).max().unwrap_or(0);
+    let mut report = String::new();
+    for preset in PRESETS {
+        let _ = writeln!(report, "{:width$}  {}", preset.name, preset.description);
+    }
+    report
+}
+
 fn build_config(
-    config_file: Option<String>,
+    mut config: Config,
     port: Option<u16>,
     host: Option<String>,
     generator: Option<String>,
     target_tokens: Option<usize>,
+    strict_schema: bool,
+    no_latency: bool,
 ) -> Result<Config, ConfigError> {
-    let mut config = if let Some(path) = config_file {
-        Config::from_file(&path)?
-    } else {
-        Config::default()
-    };
-
     // Override with CLI arguments only when explicitly provided, so values from
     // the config file are respected (previously the CLI defaults silently
     // clobbered port/generator/target_tokens from --config; see the host fix
@@ -89,67 +445,285 @@ fn build_config(
     if let Some(target_tokens) = target_tokens {
         config.response.target_tokens = target_tokens;
     }
+    if strict_schema {
+        config.stream.strict_schema = true;
+    }
+    if no_latency {
+        config.simulation.disable_delays = true;
+    }
 
     Ok(config)
 }
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
+fn main() -> Result<(), Box<dyn std::error::Error>> {
     let cli = Cli::parse();
 
     match cli.command {
         Commands::Serve {
             config,
+            preset,
             port,
             host,
             generator,
             target_tokens,
+            strict_schema,
+            no_latency,
             tui,
+            tui_history,
+            resume,
+            worker_threads,
+            max_blocking_threads,
+            workers,
         } => {
-            let config = build_config(config, port, host.clone(), generator, target_tokens)?;
-
-            if tui {
-                #[cfg(not(feature = "tui"))]
-                {
-                    return Err(
-                        "the --tui flag requires building llmsim with --features tui".into(),
-                    );
+            let mut config = build_config(
+                load_config(config, preset)?,
+                port,
+                host,
+                generator,
+                target_tokens,
+                strict_schema,
+                no_latency,
+            )?;
+            if worker_threads.is_some() {
+                config.runtime.worker_threads = worker_threads;
+            }
+            if max_blocking_threads.is_some() {
+                config.runtime.max_blocking_threads = max_blocking_threads;
+            }
+            if let Some(workers) = workers {
+                match std::env::var(WORKER_ENV) {
+                    Ok(index) => join_workers(&mut config, &index),
+                    Err(_) => return run_workers(workers),
                 }
+            }
+            // The server's runtime is sized by its config; other commands
+            // run on tokio's defaults
+            build_runtime(&config.runtime)?.block_on(serve(config, tui, tui_history, resume))
+        }
+        command => build_runtime(&RuntimeConfig::default())?.block_on(run(command)),
+    }
+}
+
+/// Set on the processes `serve --workers` starts, to each one's index
+const WORKER_ENV: &str = "LLMSIM_WORKER";
+
+/// Configure a process started by `serve --workers` as the worker at
+/// `index`: on the shared port, publishing stats under its own name
+fn join_workers(config: &mut Config, index: &str) {
+    let cluster = &mut config.cluster;
+    cluster.reuse_port = true;
+    cluster.node = Some(format!("worker-{}", index));
+    if cluster.stats_dir.is_none() {
+        let dir = std::env::temp_dir().join(format!("llmsim-workers-{}", config.server.port));
+        cluster.stats_dir = Some(dir.display().to_string());
+    }
+}
+
+/// Start `workers` copies of this command as workers and wait for them. A
+/// worker exiting, or this process being stopped, stops them all.
+fn run_workers(workers: usize) -> Result<(), Box<dyn std::error::Error>> {
+    if !cfg!(unix) {
+        return Err("--workers needs a Unix platform (SO_REUSEPORT)".into());
+    }
+    let exe = std::env::current_exe()?;
+    let mut children = (0..workers)
+        .map(|index| {
+            std::process::Command::new(&exe)
+                .args(std::env::args_os().skip(1))
+                .env(WORKER_ENV, index.to_string())
+                .spawn()
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    eprintln!("Started {} workers", workers);
 
-                #[cfg(feature = "tui")]
-                {
-                    // Run server and TUI concurrently. Use the resolved
-                    // config port so the dashboard targets the same port the
-                    // server binds (config.toml value when --port is absent).
-                    let stats = llmsim::new_shared_stats();
-                    let server_url = format!("http://127.0.0.1:{}", config.server.port);
-
-                    let dashboard_config = DashboardConfig {
-                        server_url,
-                        refresh_ms: 200,
-                    };
-
-                    // Run both concurrently - TUI exit will shut down the app
-                    tokio::select! {
-                        result = llmsim::cli::run_server_with_stats(config, stats) => {
-                            result?;
-                        }
-                        result = run_dashboard(dashboard_config) => {
-                            result?;
-                        }
+    let failed = build_runtime(&RuntimeConfig::default())?.block_on(async {
+        let mut poll = tokio::time::interval(Duration::from_millis(200));
+        let exited = async {
+            loop {
+                poll.tick().await;
+                for child in &mut children {
+                    if let Ok(Some(status)) = child.try_wait() {
+                        return !status.success();
                     }
                 }
-            } else {
-                // Initialize tracing for server-only mode
-                tracing_subscriber::fmt()
-                    .with_env_filter(
-                        tracing_subscriber::EnvFilter::from_default_env()
-                            .add_directive("llmsim=info".parse().unwrap())
-                            .add_directive("tower_http=debug".parse().unwrap()),
-                    )
-                    .init();
-
-                llmsim::cli::run_server(config).await?;
+            }
+        };
+        tokio::select! {
+            failed = exited => failed,
+            _ = llmsim::cli::shutdown_signal() => false,
+        }
+    });
+
+    // Workers that got the same Ctrl+C are given time to shut down cleanly
+    let deadline = std::time::Instant::now() + Duration::from_secs(5);
+    for child in &mut children {
+        while matches!(child.try_wait(), Ok(None)) && std::time::Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+    if failed {
+        return Err("a worker exited with an error".into());
+    }
+    Ok(())
+}
+
+async fn serve(
+    config: Config,
+    tui: bool,
+    tui_history: Option<std::path::PathBuf>,
+    resume: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if tui {
+        #[cfg(not(feature = "tui"))]
+        {
+            let _ = (config, tui_history, resume);
+            return Err("the --tui flag requires building llmsim with --features tui".into());
+        }
+
+        #[cfg(feature = "tui")]
+        {
+            // Run server and TUI concurrently. Use the resolved
+            // config port so the dashboard targets the same port the
+            // server binds (config.toml value when --port is absent).
+            let stats = std::sync::Arc::new(llmsim::Stats::with_rps_window(
+                config.server.rps_window_secs,
+            ));
+            let server_url = format!("http://127.0.0.1:{}", config.server.port);
+
+            let dashboard_config = DashboardConfig {
+                server_url,
+                refresh_ms: 200,
+                history_path: tui_history,
+                resume,
+                alerts: config.tui.alerts.clone(),
+            };
+
+            // Run both concurrently - TUI exit will shut down the app
+            tokio::select! {
+                result = llmsim::cli::run_server_with_stats(config, stats) => {
+                    result?;
+                }
+                result = run_dashboard(dashboard_config) => {
+                    result?;
+                }
+            }
+        }
+    } else {
+        // Initialize tracing for server-only mode
+        tracing_subscriber::fmt()
+            .with_env_filter(
+                tracing_subscriber::EnvFilter::from_default_env()
+                    .add_directive("llmsim=info".parse().unwrap())
+                    .add_directive("tower_http=debug".parse().unwrap()),
+            )
+            .init();
+
+        llmsim::cli::run_server(config).await?;
+    }
+    Ok(())
+}
+
+/// Every command but `serve`
+async fn run(command: Commands) -> Result<(), Box<dyn std::error::Error>> {
+    match command {
+        Commands::Serve { .. } => unreachable!("serve runs on a runtime of its own"),
+        Commands::Presets { name: None } => print!("{}", presets_report()),
+        Commands::Presets { name: Some(name) } => {
+            let preset = find_preset(&name)
+                .ok_or_else(|| format!("unknown preset {:?}; `llmsim presets` lists them", name))?;
+            print!("{}", preset.toml);
+        }
+        Commands::Config {
+            command: ConfigCommand::Validate { file },
+        } => {
+            let source = std::fs::read_to_string(&file)
+                .map_err(|e| format!("failed to read {}: {}", file, e))?;
+            let dir = Path::new(&file).parent().unwrap_or(Path::new("."));
+            match check_config(&source, dir) {
+                Ok(_) => println!("{}: ok", file),
+                Err(issues) => {
+                    for issue in &issues {
+                        eprintln!("{}:{}", file, issue_location(issue));
+                    }
+                    std::process::exit(1);
+                }
+            }
+        }
+        Commands::Config {
+            command: ConfigCommand::Defaults { config, preset },
+        } => print!("{}", config_toml(&load_config(config, preset)?)?),
+        Commands::Tokens {
+            command:
+                TokensCommand::Count {
+                    model,
+                    file,
+                    config,
+                    chat,
+                },
+        } => {
+            let encodings = match config {
+                Some(path) => Config::from_file(&path)?.encoding_map(),
+                None => EncodingMap::default(),
+            };
+            let input = match file {
+                Some(path) => std::fs::read_to_string(&path)
+                    .map_err(|e| format!("failed to read {}: {}", path, e))?,
+                None => {
+                    let mut input = String::new();
+                    std::io::stdin().read_to_string(&mut input)?;
+                    input
+                }
+            };
+            println!("{}", count_input_tokens(&encodings, &input, &model, chat)?);
+        }
+        Commands::LatencyTest {
+            config,
+            profile,
+            ttft_mean_ms,
+            ttft_stddev_ms,
+            tbt_mean_ms,
+            tbt_stddev_ms,
+            samples,
+            tokens,
+        } => {
+            let mut config = build_config(
+                load_config(config, None)?,
+                None,
+                None,
+                None,
+                None,
+                false,
+                false,
+            )?;
+            let latency = &mut config.latency;
+            latency.profile = profile.or(latency.profile.take());
+            latency.ttft_mean_ms = ttft_mean_ms.or(latency.ttft_mean_ms);
+            latency.ttft_stddev_ms = ttft_stddev_ms.or(latency.ttft_stddev_ms);
+            latency.tbt_mean_ms = tbt_mean_ms.or(latency.tbt_mean_ms);
+            latency.tbt_stddev_ms = tbt_stddev_ms.or(latency.tbt_stddev_ms);
+            print!(
+                "{}",
+                latency_report(&config.latency_profile(), samples, tokens)
+            );
+        }
+        Commands::Verify {
+            target,
+            model,
+            api_key,
+            timeout_secs,
+            strict_schema,
+        } => {
+            let mut options = VerifyOptions::new(target);
+            options.model = model;
+            options.api_key = api_key;
+            options.timeout_secs = timeout_secs;
+            options.strict_schema = strict_schema;
+            let results = verify(&options).await?;
+            print!("{}", verify_report(&results));
+            if !results.iter().all(CheckResult::passed) {
+                std::process::exit(1);
             }
         }
     }
@@ -183,7 +757,16 @@ mod tests {
             "preserve",
         );
 
-        let config = build_config(Some(path.clone()), None, None, None, None).unwrap();
+        let config = build_config(
+            load_config(Some(path.clone()), None).unwrap(),
+            None,
+            None,
+            None,
+            None,
+            false,
+            false,
+        )
+        .unwrap();
         assert_eq!(config.server.port, 9123);
         assert_eq!(config.server.host, "127.0.0.1");
         assert_eq!(config.response.generator, "echo");
@@ -200,11 +783,13 @@ mod tests {
         );
 
         let config = build_config(
-            Some(path.clone()),
+            load_config(Some(path.clone()), None).unwrap(),
             Some(9555),
             None,
             Some("lorem".to_string()),
             Some(50),
+            false,
+            false,
         )
         .unwrap();
         assert_eq!(config.server.port, 9555);
@@ -214,11 +799,72 @@ mod tests {
         let _ = std::fs::remove_file(path);
     }
 
+    #[test]
+    fn count_input_tokens_text_and_chat() {
+        let map = EncodingMap::default();
+        let text = count_input_tokens(&map, "Hello, world!", "gpt-4o", false).unwrap();
+        assert_eq!(
+            text,
+            llmsim::count_tokens("Hello, world!", "gpt-4o").unwrap()
+        );
+
+        let array = r#"[{"role": "user", "content": "Hello, world!"}]"#;
+        let body =
+            r#"{"model": "gpt-4o", "messages": [{"role": "user", "content": "Hello, world!"}]}"#;
+        let chat = count_input_tokens(&map, array, "gpt-4o", true).unwrap();
+        assert_eq!(
+            chat,
+            count_input_tokens(&map, body, "gpt-4o", true).unwrap()
+        );
+        assert!(chat > text);
+
+        assert!(count_input_tokens(&map, "not json", "gpt-4o", true).is_err());
+    }
+
+    #[test]
+    fn latency_report_summarizes_fixed_profile() {
+        let profile = LatencyProfile::new(100, 0, 10, 0);
+        let report = latency_report(&profile, 20, Some(50));
+        assert!(report.contains("(20 samples)"));
+        assert!(report.contains("stream (50)"));
+        // 100ms + 50 * 10ms, every sample identical
+        assert!(report.contains("stream duration histogram"));
+        assert!(report.contains("600ms"));
+        assert!(report.trim_end().ends_with(" 20"));
+    }
+
+    #[test]
+    fn histogram_counts_every_sample() {
+        let samples: Vec<Duration> = (0..100).map(Duration::from_millis).collect();
+        let lines = histogram(&samples, 10, 20);
+        assert_eq!(lines.len(), 10);
+        let total: usize = lines
+            .iter()
+            .map(|l| l.rsplit(' ').next().unwrap().parse::<usize>().unwrap())
+            .sum();
+        assert_eq!(total, 100);
+        assert!(histogram(&[], 10, 20).is_empty());
+    }
+
     #[test]
     fn no_config_file_uses_defaults() {
-        let config = build_config(None, None, None, None, None).unwrap();
+        let config = build_config(Config::default(), None, None, None, None, false, false).unwrap();
         assert_eq!(config.server.port, 8080);
         assert_eq!(config.response.generator, "lorem");
         assert_eq!(config.response.target_tokens, 100);
     }
+
+    #[test]
+    fn preset_under_config_file() {
+        let path = write_temp_config("[response]\ntarget_tokens = 7\n", "preset");
+
+        let config = load_config(Some(path.clone()), Some("slow-reasoning".to_string())).unwrap();
+        assert_eq!(config.response.target_tokens, 7);
+        assert_eq!(config.latency.ttft_mean_ms, Some(8000));
+
+        let err = load_config(None, Some("fast-and-loose".to_string())).unwrap_err();
+        assert!(err.to_string().contains("llmsim presets"));
+
+        let _ = std::fs::remove_file(path);
+    }
 }
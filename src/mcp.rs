@@ -0,0 +1,390 @@
+// MCP Server Tool Simulation
+// Decides which MCP items a Responses request offering `mcp` tools gets:
+// the server's tool listing, an approval request, or a call with its result.
+//
+// Decision: no MCP server is contacted. Each `server_label` lists a fake
+// catalog (configured under `[mcp.servers]`, or a small built-in one), and
+// the model calls the first tool the request allows with the prompt as its
+// argument. The approval round trip follows the conversation: a call that
+// needs approval is asked for, and made once the input carries an approving
+// `mcp_approval_response` for it. A server is listed once per conversation,
+// so input that already has its `mcp_list_tools` item skips the listing.
+
+use crate::ids::{new_id, IdKind};
+use crate::openai::{
+    InputItem, ItemStatus, McpListedTool, OutputItem, ResponsesInput, ResponsesTool,
+    ResponsesToolChoice,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Longest prompt excerpt passed as a tool argument
+const MAX_ARGUMENT_CHARS: usize = 60;
+
+/// A tool in a fake MCP server's catalog
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct McpTool {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    /// JSON Schema of the arguments; the first string property gets the prompt
+    #[serde(default = "default_input_schema")]
+    pub input_schema: serde_json::Value,
+    /// Result text of every call; made up from the arguments if unset
+    #[serde(default)]
+    pub output: Option<String>,
+}
+
+fn default_input_schema() -> serde_json::Value {
+    serde_json::json!({"type": "object", "properties": {}})
+}
+
+impl McpTool {
+    fn new(name: &str, description: &str, argument: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            description: Some(description.to_string()),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "properties": {argument: {"type": "string"}},
+                "required": [argument]
+            }),
+            output: None,
+        }
+    }
+
+    /// Arguments for a call about `prompt`, as a JSON string
+    fn arguments_for(&self, prompt: &str) -> String {
+        let excerpt: String = prompt.chars().take(MAX_ARGUMENT_CHARS).collect();
+        let argument = self.input_schema["properties"]
+            .as_object()
+            .and_then(|properties| {
+                properties
+                    .iter()
+                    .find(|(_, schema)| schema["type"] == "string")
+                    .map(|(name, _)| name.clone())
+            });
+        let mut arguments = serde_json::Map::new();
+        if let Some(argument) = argument {
+            arguments.insert(argument, excerpt.into());
+        }
+        serde_json::Value::Object(arguments).to_string()
+    }
+
+    fn output_for(&self, arguments: &str) -> String {
+        self.output
+            .clone()
+            .unwrap_or_else(|| format!("{} returned results for {}", self.name, arguments))
+    }
+
+    fn listed(&self) -> McpListedTool {
+        McpListedTool {
+            name: self.name.clone(),
+            description: self.description.clone(),
+            input_schema: self.input_schema.clone(),
+            annotations: None,
+        }
+    }
+}
+
+/// Fake tool catalogs keyed by `server_label`
+#[derive(Debug, Clone, Default)]
+pub struct McpCatalogs {
+    servers: BTreeMap<String, Vec<McpTool>>,
+    default_tools: Vec<McpTool>,
+}
+
+impl McpCatalogs {
+    /// Catalogs for the given servers; others list the built-in tools
+    pub fn new(servers: BTreeMap<String, Vec<McpTool>>) -> Self {
+        Self {
+            servers,
+            default_tools: vec![
+                McpTool::new("search", "Search the server's documents", "query"),
+                McpTool::new("fetch", "Fetch a document by id", "id"),
+            ],
+        }
+    }
+
+    /// Tools `server_label` lists
+    pub fn tools(&self, server_label: &str) -> &[McpTool] {
+        self.servers
+            .get(server_label)
+            .unwrap_or(&self.default_tools)
+    }
+
+    /// MCP items answering a request, in output order
+    pub fn items_for(
+        &self,
+        tools: Option<&[ResponsesTool]>,
+        tool_choice: Option<&ResponsesToolChoice>,
+        input: &ResponsesInput,
+        prompt: &str,
+    ) -> Vec<OutputItem> {
+        let servers: Vec<McpServer<'_>> = tools
+            .unwrap_or_default()
+            .iter()
+            .filter_map(McpServer::from_tool)
+            .collect();
+        if servers.is_empty() {
+            return Vec::new();
+        }
+        let history = match input {
+            ResponsesInput::Items(items) => items.as_slice(),
+            ResponsesInput::Text(_) => &[],
+        };
+
+        let mut items: Vec<OutputItem> = servers
+            .iter()
+            .filter(|server| !server.listed_in(history))
+            .map(|server| OutputItem::McpListTools {
+                id: new_id(IdKind::McpListTools),
+                server_label: server.label.to_string(),
+                tools: self
+                    .tools(server.label)
+                    .iter()
+                    .map(McpTool::listed)
+                    .collect(),
+                error: None,
+            })
+            .collect();
+
+        let declined =
+            matches!(tool_choice, Some(ResponsesToolChoice::String(choice)) if choice == "none");
+        if declined {
+            return items;
+        }
+
+        // Answers to earlier approval requests: approved calls are made now
+        let answered: Vec<(&str, bool)> = history
+            .iter()
+            .filter_map(|item| match item {
+                InputItem::McpApprovalResponse {
+                    approval_request_id,
+                    approve,
+                    ..
+                } => Some((approval_request_id.as_str(), *approve)),
+                _ => None,
+            })
+            .collect();
+        if !answered.is_empty() {
+            for item in history {
+                let InputItem::McpApprovalRequest {
+                    id,
+                    server_label,
+                    name,
+                    arguments,
+                } = item
+                else {
+                    continue;
+                };
+                if !answered.contains(&(id.as_str(), true)) {
+                    continue;
+                }
+                let output = self
+                    .tools(server_label)
+                    .iter()
+                    .find(|tool| &tool.name == name)
+                    .map(|tool| tool.output_for(arguments));
+                items.push(call_item(server_label, name, arguments, output, Some(id)));
+            }
+            return items;
+        }
+
+        // A new call to the first tool any server allows
+        let choice = servers.iter().find_map(|server| {
+            let tool = self
+                .tools(server.label)
+                .iter()
+                .find(|tool| server.allows(&tool.name))?;
+            Some((server, tool))
+        });
+        if let Some((server, tool)) = choice {
+            let arguments = tool.arguments_for(prompt);
+            if server.needs_approval(&tool.name) {
+                items.push(OutputItem::McpApprovalRequest {
+                    id: new_id(IdKind::McpApprovalRequest),
+                    server_label: server.label.to_string(),
+                    name: tool.name.clone(),
+                    arguments,
+                });
+            } else {
+                let output = tool.output_for(&arguments);
+                items.push(call_item(
+                    server.label,
+                    &tool.name,
+                    &arguments,
+                    Some(output),
+                    None,
+                ));
+            }
+        }
+        items
+    }
+}
+
+/// Completed `mcp_call`; a tool missing from the catalog fails
+fn call_item(
+    server_label: &str,
+    name: &str,
+    arguments: &str,
+    output: Option<String>,
+    approval_request_id: Option<&str>,
+) -> OutputItem {
+    let error = output
+        .is_none()
+        .then(|| format!("Tool '{}' not found on server '{}'", name, server_label));
+    OutputItem::McpCall {
+        id: new_id(IdKind::McpCall),
+        server_label: server_label.to_string(),
+        name: name.to_string(),
+        arguments: arguments.to_string(),
+        status: if error.is_some() {
+            ItemStatus::Failed
+        } else {
+            ItemStatus::Completed
+        },
+        output,
+        error,
+        approval_request_id: approval_request_id.map(str::to_string),
+    }
+}
+
+/// An `mcp` tool offered by the request
+struct McpServer<'a> {
+    label: &'a str,
+    require_approval: Option<&'a crate::openai::McpRequireApproval>,
+    allowed_tools: Option<&'a [String]>,
+}
+
+impl<'a> McpServer<'a> {
+    fn from_tool(tool: &'a ResponsesTool) -> Option<Self> {
+        match tool {
+            ResponsesTool::Mcp {
+                server_label,
+                require_approval,
+                allowed_tools,
+                ..
+            } => Some(Self {
+                label: server_label,
+                require_approval: require_approval.as_ref(),
+                allowed_tools: allowed_tools.as_deref(),
+            }),
+            _ => None,
+        }
+    }
+
+    fn listed_in(&self, history: &[InputItem]) -> bool {
+        history.iter().any(|item| {
+            matches!(item, InputItem::McpListTools { server_label } if server_label == self.label)
+        })
+    }
+
+    fn allows(&self, tool: &str) -> bool {
+        self.allowed_tools
+            .is_none_or(|allowed| allowed.iter().any(|name| name == tool))
+    }
+
+    /// Approval is required unless the request says otherwise
+    fn needs_approval(&self, tool: &str) -> bool {
+        self.require_approval
+            .is_none_or(|require| require.applies_to(tool))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::openai::McpRequireApproval;
+
+    fn mcp_tool(require_approval: Option<&str>) -> ResponsesTool {
+        ResponsesTool::Mcp {
+            server_label: "docs".to_string(),
+            server_url: "https://mcp.example.com".to_string(),
+            headers: None,
+            require_approval: require_approval.map(|mode| McpRequireApproval::Mode(mode.into())),
+            allowed_tools: None,
+        }
+    }
+
+    fn types(items: &[OutputItem]) -> Vec<String> {
+        items
+            .iter()
+            .map(|item| {
+                serde_json::to_value(item).unwrap()["type"]
+                    .as_str()
+                    .unwrap()
+                    .to_string()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_approval_requested_by_default() {
+        let catalogs = McpCatalogs::new(BTreeMap::new());
+        let tools = [mcp_tool(None)];
+        let input = ResponsesInput::Text("find the guide".to_string());
+        let items = catalogs.items_for(Some(&tools), None, &input, "find the guide");
+        assert_eq!(types(&items), ["mcp_list_tools", "mcp_approval_request"]);
+        let OutputItem::McpApprovalRequest {
+            name, arguments, ..
+        } = &items[1]
+        else {
+            unreachable!()
+        };
+        assert_eq!(name, "search");
+        assert_eq!(arguments, r#"{"query":"find the guide"}"#);
+    }
+
+    #[test]
+    fn test_approved_request_is_called() {
+        let catalogs = McpCatalogs::new(BTreeMap::new());
+        let tools = [mcp_tool(None)];
+        let input: ResponsesInput = serde_json::from_value(serde_json::json!([
+            {"role": "user", "content": "find the guide"},
+            {"type": "mcp_list_tools", "id": "mcpl_1", "server_label": "docs", "tools": []},
+            {"type": "mcp_approval_request", "id": "mcpr_1", "server_label": "docs",
+             "name": "search", "arguments": "{\"query\":\"guide\"}"},
+            {"type": "mcp_approval_response", "approval_request_id": "mcpr_1", "approve": true}
+        ]))
+        .unwrap();
+        let items = catalogs.items_for(Some(&tools), None, &input, "find the guide");
+        assert_eq!(types(&items), ["mcp_call"]);
+        let OutputItem::McpCall {
+            approval_request_id,
+            output,
+            ..
+        } = &items[0]
+        else {
+            unreachable!()
+        };
+        assert_eq!(approval_request_id.as_deref(), Some("mcpr_1"));
+        assert!(output.as_deref().unwrap().contains("guide"));
+    }
+
+    #[test]
+    fn test_never_approval_calls_configured_tool() {
+        let mut servers = BTreeMap::new();
+        servers.insert(
+            "docs".to_string(),
+            vec![McpTool {
+                output: Some("42".to_string()),
+                ..McpTool::new("answer", "The answer", "question")
+            }],
+        );
+        let catalogs = McpCatalogs::new(servers);
+        let tools = [mcp_tool(Some("never"))];
+        let input = ResponsesInput::Text("why".to_string());
+        let items = catalogs.items_for(Some(&tools), None, &input, "why");
+        assert_eq!(types(&items), ["mcp_list_tools", "mcp_call"]);
+        let OutputItem::McpCall { output, status, .. } = &items[1] else {
+            unreachable!()
+        };
+        assert_eq!(output.as_deref(), Some("42"));
+        assert!(matches!(status, ItemStatus::Completed));
+
+        let none = ResponsesToolChoice::String("none".to_string());
+        let items = catalogs.items_for(Some(&tools), Some(&none), &input, "why");
+        assert_eq!(types(&items), ["mcp_list_tools"]);
+    }
+}
@@ -30,7 +30,8 @@ pub struct ImageGenerationRequest {
     pub size: Option<String>,
     /// Quality: "low" | "medium" | "high" | "auto".
     pub quality: Option<String>,
-    /// DALL·E response format ("url" | "b64_json"). gpt-image always returns b64.
+    /// DALL·E response format ("url" | "b64_json"). `url` links to a copy
+    /// hosted under `/llmsim/files`; streams always carry b64.
     pub response_format: Option<String>,
     /// Output encoding for gpt-image models ("png" | "jpeg" | "webp").
     pub output_format: Option<String>,
@@ -4,6 +4,7 @@
 // for use in simulating LLM API behavior.
 
 use crate::ids::unix_timestamp;
+use crate::latency::ModelLatency;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::LazyLock;
@@ -45,6 +46,10 @@ pub struct ModelProfile {
     /// Knowledge cutoff date (YYYY-MM-DD format)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub knowledge_cutoff: Option<String>,
+    /// Latency to simulate, where the name alone would suggest the wrong
+    /// profile (see `LatencyProfile::from_model`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latency: Option<ModelLatency>,
 }
 
 impl ModelProfile {
@@ -65,6 +70,7 @@ impl ModelProfile {
             created: unix_timestamp(),
             capabilities: ModelCapabilities::default(),
             knowledge_cutoff: None,
+            latency: None,
         }
     }
 
@@ -85,6 +91,12 @@ impl ModelProfile {
         self.knowledge_cutoff = Some(cutoff.into());
         self
     }
+
+    /// Builder method to pin the simulated latency
+    pub fn with_latency(mut self, latency: ModelLatency) -> Self {
+        self.latency = Some(latency);
+        self
+    }
 }
 
 /// Standard capabilities for GPT-5 series models
@@ -198,6 +210,11 @@ fn deepseek_reasoning_capabilities() -> ModelCapabilities {
     }
 }
 
+/// Latency of the small GPT-5 variants whose names lack `gpt-5-mini`
+fn gpt5_mini_latency() -> ModelLatency {
+    ModelLatency::Named("gpt-5-mini".to_string())
+}
+
 /// Build the static model registry with profiles from models.dev
 fn build_model_registry() -> HashMap<String, ModelProfile> {
     let mut registry = HashMap::new();
@@ -220,7 +237,8 @@ fn build_model_registry() -> HashMap<String, ModelProfile> {
         ModelProfile::new("gpt-5-nano", "GPT-5 Nano", "openai", 400_000, 128_000)
             .with_created(1754524800)
             .with_capabilities(gpt5_capabilities())
-            .with_knowledge_cutoff("2024-05-30"),
+            .with_knowledge_cutoff("2024-05-30")
+            .with_latency(gpt5_mini_latency()),
         ModelProfile::new("gpt-5-codex", "GPT-5 Codex", "openai", 400_000, 128_000)
             .with_created(1754524800)
             .with_capabilities(gpt5_capabilities())
@@ -243,7 +261,8 @@ fn build_model_registry() -> HashMap<String, ModelProfile> {
         )
         .with_created(1762387200)
         .with_capabilities(gpt5_capabilities())
-        .with_knowledge_cutoff("2025-03-31"),
+        .with_knowledge_cutoff("2025-03-31")
+        .with_latency(gpt5_mini_latency()),
         ModelProfile::new(
             "gpt-5.1-codex-max",
             "GPT-5.1 Codex Max",
@@ -281,7 +300,13 @@ fn build_model_registry() -> HashMap<String, ModelProfile> {
         )
         .with_created(1770249600) // 2026-02-05
         .with_capabilities(gpt5_capabilities())
-        .with_knowledge_cutoff("2025-08-31"),
+        .with_knowledge_cutoff("2025-08-31")
+        .with_latency(ModelLatency::Custom {
+            ttft_mean_ms: 250,
+            ttft_stddev_ms: 60,
+            tbt_mean_ms: 2,
+            tbt_stddev_ms: 1,
+        }),
         ModelProfile::new(
             "gpt-5.3-chat-latest",
             "GPT-5.3 Chat (latest)",
@@ -304,11 +329,13 @@ fn build_model_registry() -> HashMap<String, ModelProfile> {
         ModelProfile::new("gpt-5.4-mini", "GPT-5.4 Mini", "openai", 400_000, 128_000)
             .with_created(1773705600) // 2026-03-17
             .with_capabilities(gpt5_capabilities())
-            .with_knowledge_cutoff("2025-08-31"),
+            .with_knowledge_cutoff("2025-08-31")
+            .with_latency(gpt5_mini_latency()),
         ModelProfile::new("gpt-5.4-nano", "GPT-5.4 Nano", "openai", 400_000, 128_000)
             .with_created(1773705600) // 2026-03-17
             .with_capabilities(gpt5_capabilities())
-            .with_knowledge_cutoff("2025-08-31"),
+            .with_knowledge_cutoff("2025-08-31")
+            .with_latency(gpt5_mini_latency()),
         // GPT-5.5
         ModelProfile::new("gpt-5.5", "GPT-5.5", "openai", 1_050_000, 128_000)
             .with_created(1776902400) // 2026-04-23
@@ -374,15 +401,18 @@ fn build_model_registry() -> HashMap<String, ModelProfile> {
         ModelProfile::new("gpt-4.1", "GPT-4.1", "openai", 1_047_576, 32_768)
             .with_created(1744675200) // 2025-04-14
             .with_capabilities(gpt4o_capabilities())
-            .with_knowledge_cutoff("2024-06-01"),
+            .with_knowledge_cutoff("2024-06-01")
+            .with_latency(ModelLatency::Named("gpt-4o".to_string())),
         ModelProfile::new("gpt-4.1-mini", "GPT-4.1 Mini", "openai", 1_047_576, 32_768)
             .with_created(1744675200) // 2025-04-14
             .with_capabilities(gpt4o_capabilities())
-            .with_knowledge_cutoff("2024-06-01"),
+            .with_knowledge_cutoff("2024-06-01")
+            .with_latency(ModelLatency::Named("gpt-4o".to_string())),
         ModelProfile::new("gpt-4.1-nano", "GPT-4.1 Nano", "openai", 1_047_576, 32_768)
             .with_created(1744675200) // 2025-04-14
             .with_capabilities(gpt4o_capabilities())
-            .with_knowledge_cutoff("2024-06-01"),
+            .with_knowledge_cutoff("2024-06-01")
+            .with_latency(ModelLatency::Named("gpt-4o".to_string())),
     ];
 
     // Claude family (for completeness when using simulator)
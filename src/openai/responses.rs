@@ -2,7 +2,8 @@
 // These types are designed to be compatible with the OpenAI Responses API.
 // Reference: https://platform.openai.com/docs/api-reference/responses
 
-use crate::ids::{prefixed_id, unix_timestamp};
+use super::StreamOptions;
+use crate::ids::{new_id, unix_timestamp, IdKind};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
@@ -16,6 +17,55 @@ pub enum ResponsesInput {
     Items(Vec<InputItem>),
 }
 
+impl ResponsesInput {
+    /// Input items sent; a plain text input is one
+    pub fn item_count(&self) -> usize {
+        match self {
+            ResponsesInput::Text(_) => 1,
+            ResponsesInput::Items(items) => items.len(),
+        }
+    }
+
+    /// Whether any input message has an `input_image` part
+    pub fn has_images(&self) -> bool {
+        let ResponsesInput::Items(items) = self else {
+            return false;
+        };
+        items.iter().any(|item| {
+            matches!(item, InputItem::Message { content: MessageContent::Parts(parts), .. }
+                if parts.iter().any(|p| matches!(p, ContentPart::InputImage { .. })))
+        })
+    }
+
+    /// `(id, encrypted_content)` of the `reasoning` items in the input
+    pub fn reasoning_items(&self) -> impl Iterator<Item = (&str, Option<&str>)> {
+        let items = match self {
+            ResponsesInput::Items(items) => items.as_slice(),
+            ResponsesInput::Text(_) => &[],
+        };
+        items.iter().filter_map(|item| match item {
+            InputItem::Reasoning {
+                id,
+                encrypted_content,
+                ..
+            } => Some((id.as_str(), encrypted_content.as_deref())),
+            _ => None,
+        })
+    }
+
+    /// `call_id`s of the `function_call_output` items in the input
+    pub fn tool_result_ids(&self) -> impl Iterator<Item = &str> {
+        let items = match self {
+            ResponsesInput::Items(items) => items.as_slice(),
+            ResponsesInput::Text(_) => &[],
+        };
+        items.iter().filter_map(|item| match item {
+            InputItem::FunctionCallOutput { call_id, .. } => Some(call_id.as_str()),
+            _ => None,
+        })
+    }
+}
+
 /// An input item in the Responses API.
 /// Accepts both tagged (`{"type": "message", ...}`) and shorthand
 /// (`{"role": "user", "content": "..."}`) formats for compatibility with
@@ -30,6 +80,112 @@ pub enum InputItem {
     },
     /// A function call result (tool output)
     FunctionCallOutput { call_id: String, output: String },
+    /// Tools an MCP server listed earlier in the conversation
+    McpListTools { server_label: String },
+    /// An MCP call the model asked to make earlier
+    McpApprovalRequest {
+        id: String,
+        server_label: String,
+        name: String,
+        arguments: String,
+    },
+    /// The user's answer to an `mcp_approval_request`
+    McpApprovalResponse {
+        approval_request_id: String,
+        approve: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        reason: Option<String>,
+    },
+    /// An MCP call made earlier in the conversation
+    McpCall {
+        id: String,
+        server_label: String,
+        name: String,
+    },
+    /// Reasoning from an earlier response, passed back with its
+    /// `encrypted_content` when nothing is stored
+    Reasoning {
+        id: String,
+        summary: Vec<ReasoningSummary>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        encrypted_content: Option<String>,
+    },
+}
+
+/// A reasoning item as clients send it back
+#[derive(Deserialize)]
+struct ReasoningInputItem {
+    id: String,
+    #[serde(default)]
+    summary: Vec<ReasoningSummary>,
+    #[serde(default)]
+    encrypted_content: Option<String>,
+}
+
+/// The MCP items clients send back as input, which deserialize as they
+/// serialize
+#[derive(Deserialize)]
+#[serde(tag = "type")]
+enum McpInputItem {
+    #[serde(rename = "mcp_list_tools")]
+    ListTools { server_label: String },
+    #[serde(rename = "mcp_approval_request")]
+    ApprovalRequest {
+        id: String,
+        server_label: String,
+        name: String,
+        arguments: String,
+    },
+    #[serde(rename = "mcp_approval_response")]
+    ApprovalResponse {
+        approval_request_id: String,
+        approve: bool,
+        #[serde(default)]
+        reason: Option<String>,
+    },
+    #[serde(rename = "mcp_call")]
+    Call {
+        id: String,
+        server_label: String,
+        name: String,
+    },
+}
+
+impl From<McpInputItem> for InputItem {
+    fn from(item: McpInputItem) -> Self {
+        match item {
+            McpInputItem::ListTools { server_label } => InputItem::McpListTools { server_label },
+            McpInputItem::ApprovalRequest {
+                id,
+                server_label,
+                name,
+                arguments,
+            } => InputItem::McpApprovalRequest {
+                id,
+                server_label,
+                name,
+                arguments,
+            },
+            McpInputItem::ApprovalResponse {
+                approval_request_id,
+                approve,
+                reason,
+            } => InputItem::McpApprovalResponse {
+                approval_request_id,
+                approve,
+                reason,
+            },
+            McpInputItem::Call {
+                id,
+                server_label,
+                name,
+            } => InputItem::McpCall {
+                id,
+                server_label,
+                name,
+            },
+        }
+    }
 }
 
 impl<'de> serde::Deserialize<'de> for InputItem {
@@ -72,9 +228,29 @@ impl<'de> serde::Deserialize<'de> for InputItem {
                     .to_string();
                 Ok(InputItem::FunctionCallOutput { call_id, output })
             }
+            Some(
+                "mcp_list_tools" | "mcp_approval_request" | "mcp_approval_response" | "mcp_call",
+            ) => serde_json::from_value::<McpInputItem>(value)
+                .map(InputItem::from)
+                .map_err(serde::de::Error::custom),
+            Some("reasoning") => serde_json::from_value::<ReasoningInputItem>(value)
+                .map(|item| InputItem::Reasoning {
+                    id: item.id,
+                    summary: item.summary,
+                    encrypted_content: item.encrypted_content,
+                })
+                .map_err(serde::de::Error::custom),
             Some(other) => Err(serde::de::Error::unknown_variant(
                 other,
-                &["message", "function_call_output"],
+                &[
+                    "message",
+                    "function_call_output",
+                    "mcp_list_tools",
+                    "mcp_approval_request",
+                    "mcp_approval_response",
+                    "mcp_call",
+                    "reasoning",
+                ],
             )),
             None => Err(serde::de::Error::custom(
                 "missing 'type' or 'role' field in input item",
@@ -174,6 +350,26 @@ pub struct ResponsesRequest {
     /// Include additional data in response (e.g., "reasoning.encrypted_content")
     #[serde(skip_serializing_if = "Option::is_none")]
     pub include: Option<Vec<String>>,
+    /// Keep the response for retrieval and listing (the default)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub store: Option<bool>,
+    /// Streaming options; only `include_obfuscation` applies here
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stream_options: Option<StreamOptions>,
+    /// What happens when the conversation outgrows the context window
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub truncation: Option<Truncation>,
+}
+
+/// Handling of a conversation beyond the model's context window
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Truncation {
+    /// Drop the oldest of the conversation until it fits
+    Auto,
+    /// Fail the request with `context_length_exceeded` (the API default)
+    #[default]
+    Disabled,
 }
 
 /// A tool definition for the Responses API
@@ -196,16 +392,63 @@ pub enum ResponsesTool {
     CodeInterpreter {},
     /// Remote MCP server tool
     Mcp {
+        /// Name the server's items are labelled with
+        #[serde(default)]
+        server_label: String,
         /// MCP server URL
         server_url: String,
         /// Optional headers for authentication
         #[serde(skip_serializing_if = "Option::is_none")]
         headers: Option<std::collections::HashMap<String, String>>,
+        /// Which calls wait for an `mcp_approval_response`; all by default
+        #[serde(skip_serializing_if = "Option::is_none")]
+        require_approval: Option<McpRequireApproval>,
+        /// Tools the model may call, out of all the server lists
+        #[serde(skip_serializing_if = "Option::is_none")]
+        allowed_tools: Option<Vec<String>>,
     },
     /// Image generation tool
     ImageGeneration {},
 }
 
+/// `require_approval` of an MCP tool: `"always"`, `"never"`, or the tools
+/// that skip (`never`) or need (`always`) approval
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum McpRequireApproval {
+    Mode(String),
+    Filter {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        always: Option<McpToolNames>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        never: Option<McpToolNames>,
+    },
+}
+
+impl McpRequireApproval {
+    /// Whether calling `tool` waits for approval
+    pub fn applies_to(&self, tool: &str) -> bool {
+        match self {
+            McpRequireApproval::Mode(mode) => mode != "never",
+            McpRequireApproval::Filter { never, .. } => {
+                !never.as_ref().is_some_and(|never| never.contains(tool))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct McpToolNames {
+    #[serde(default)]
+    pub tool_names: Vec<String>,
+}
+
+impl McpToolNames {
+    fn contains(&self, tool: &str) -> bool {
+        self.tool_names.iter().any(|name| name == tool)
+    }
+}
+
 /// Tool choice option for Responses API
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(untagged)]
@@ -251,15 +494,33 @@ pub struct ResponsesResponse {
     /// Error information
     #[serde(skip_serializing_if = "Option::is_none")]
     pub error: Option<ResponsesError>,
+    /// Why the response stopped early (`status: incomplete` only)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub incomplete_details: Option<IncompleteDetails>,
     /// Metadata
     #[serde(skip_serializing_if = "Option::is_none")]
     pub metadata: Option<HashMap<String, String>>,
 }
 
+/// Reason a response ended with `status: incomplete`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct IncompleteDetails {
+    /// `max_output_tokens` or `content_filter`
+    pub reason: String,
+}
+
+impl IncompleteDetails {
+    pub fn max_output_tokens() -> Self {
+        Self {
+            reason: "max_output_tokens".to_string(),
+        }
+    }
+}
+
 impl ResponsesResponse {
     pub fn new(model: String, content: String, usage: ResponsesUsage) -> Self {
         let output_item = OutputItem::Message {
-            id: prefixed_id("msg_"),
+            id: new_id(IdKind::Message),
             role: OutputRole::Assistant,
             status: ItemStatus::Completed,
             content: vec![OutputContentPart::OutputText {
@@ -269,7 +530,7 @@ impl ResponsesResponse {
         };
 
         Self {
-            id: prefixed_id("resp_"),
+            id: new_id(IdKind::Response),
             object: "response".to_string(),
             created_at: unix_timestamp(),
             model,
@@ -278,6 +539,7 @@ impl ResponsesResponse {
             output_text: Some(content),
             usage: Some(usage),
             error: None,
+            incomplete_details: None,
             metadata: None,
         }
     }
@@ -285,7 +547,7 @@ impl ResponsesResponse {
     /// Create a minimal response for WebSocket warmup (generate=false).
     pub fn warmup(model: String) -> Self {
         Self {
-            id: prefixed_id("resp_"),
+            id: new_id(IdKind::Response),
             object: "response".to_string(),
             created_at: unix_timestamp(),
             model,
@@ -294,6 +556,7 @@ impl ResponsesResponse {
             output_text: None,
             usage: None,
             error: None,
+            incomplete_details: None,
             metadata: None,
         }
     }
@@ -307,7 +570,7 @@ impl ResponsesResponse {
         usage: ResponsesUsage,
     ) -> Self {
         let reasoning_item = OutputItem::Reasoning {
-            id: prefixed_id("rs_"),
+            id: new_id(IdKind::Reasoning),
             status: ItemStatus::Completed,
             summary: summary_text.map(|text| {
                 vec![ReasoningSummary {
@@ -315,10 +578,11 @@ impl ResponsesResponse {
                     text,
                 }]
             }),
+            encrypted_content: None,
         };
 
         let message_item = OutputItem::Message {
-            id: prefixed_id("msg_"),
+            id: new_id(IdKind::Message),
             role: OutputRole::Assistant,
             status: ItemStatus::Completed,
             content: vec![OutputContentPart::OutputText {
@@ -328,7 +592,7 @@ impl ResponsesResponse {
         };
 
         Self {
-            id: prefixed_id("resp_"),
+            id: new_id(IdKind::Response),
             object: "response".to_string(),
             created_at: unix_timestamp(),
             model,
@@ -337,9 +601,87 @@ impl ResponsesResponse {
             output_text: Some(content),
             usage: Some(usage),
             error: None,
+            incomplete_details: None,
             metadata: None,
         }
     }
+
+    /// Give each reasoning item an opaque `encrypted_content` standing for
+    /// `reasoning_tokens` tokens, for `include: ["reasoning.encrypted_content"]`
+    pub fn with_encrypted_reasoning(mut self, reasoning_tokens: u32) -> Self {
+        for item in &mut self.output {
+            if let OutputItem::Reasoning {
+                encrypted_content, ..
+            } = item
+            {
+                *encrypted_content = Some(crate::encrypted_reasoning::encrypt(reasoning_tokens));
+            }
+        }
+        self
+    }
+
+    /// Insert a tool call item (e.g. a code interpreter call) before the
+    /// message, as the model makes calls before answering
+    pub fn with_tool_item(mut self, item: OutputItem) -> Self {
+        let index = self
+            .output
+            .iter()
+            .position(|item| matches!(item, OutputItem::Message { .. }))
+            .unwrap_or(self.output.len());
+        self.output.insert(index, item);
+        self
+    }
+
+    /// Mark the response as cut off by `max_output_tokens`
+    pub fn truncated(mut self) -> Self {
+        self.status = ResponseStatus::Incomplete;
+        self.incomplete_details = Some(IncompleteDetails::max_output_tokens());
+        for item in &mut self.output {
+            if let OutputItem::Message { status, .. } = item {
+                *status = ItemStatus::Incomplete;
+            }
+        }
+        self
+    }
+
+    /// Attach annotations (e.g. citations) to the message's output text
+    pub fn with_annotations(mut self, annotations: Vec<serde_json::Value>) -> Self {
+        if annotations.is_empty() {
+            return self;
+        }
+        for item in &mut self.output {
+            if let OutputItem::Message { content, .. } = item {
+                for part in content.iter_mut() {
+                    if let OutputContentPart::OutputText {
+                        annotations: part_annotations,
+                        ..
+                    } = part
+                    {
+                        *part_annotations = annotations.clone();
+                    }
+                }
+            }
+        }
+        self
+    }
+
+    /// Turn the message text into a `refusal` content part, as the API
+    /// answers requests the model declines
+    pub fn refused(mut self) -> Self {
+        for item in &mut self.output {
+            if let OutputItem::Message { content, .. } = item {
+                for part in content.iter_mut() {
+                    if let OutputContentPart::OutputText { text, .. } = part {
+                        *part = OutputContentPart::Refusal {
+                            refusal: std::mem::take(text),
+                        };
+                    }
+                }
+            }
+        }
+        self.output_text = None;
+        self
+    }
 }
 
 /// An output item in the response
@@ -367,9 +709,65 @@ pub enum OutputItem {
         status: ItemStatus,
         #[serde(skip_serializing_if = "Option::is_none")]
         summary: Option<Vec<ReasoningSummary>>,
+        /// Opaque reasoning to pass back, with
+        /// `include: ["reasoning.encrypted_content"]`
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        encrypted_content: Option<String>,
+    },
+    /// Code run by the code interpreter tool
+    CodeInterpreterCall {
+        id: String,
+        status: ItemStatus,
+        code: Option<String>,
+        container_id: String,
+        /// `null` unless requested with `include`
+        outputs: Option<Vec<CodeInterpreterOutput>>,
+    },
+    /// Tools an MCP server offers
+    McpListTools {
+        id: String,
+        server_label: String,
+        tools: Vec<McpListedTool>,
+        error: Option<String>,
+    },
+    /// An MCP call waiting for the user's approval
+    McpApprovalRequest {
+        id: String,
+        server_label: String,
+        name: String,
+        arguments: String,
+    },
+    /// A call to an MCP server tool
+    McpCall {
+        id: String,
+        server_label: String,
+        name: String,
+        arguments: String,
+        output: Option<String>,
+        error: Option<String>,
+        status: ItemStatus,
+        /// The approved `mcp_approval_request`, if the call needed one
+        approval_request_id: Option<String>,
     },
 }
 
+/// One tool in an `mcp_list_tools` item
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct McpListedTool {
+    pub name: String,
+    pub description: Option<String>,
+    pub input_schema: serde_json::Value,
+    pub annotations: Option<serde_json::Value>,
+}
+
+/// What a code interpreter run produced
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum CodeInterpreterOutput {
+    Logs { logs: String },
+    Image { url: String },
+}
+
 /// Role for output messages
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "lowercase")]
@@ -384,6 +782,7 @@ pub enum ItemStatus {
     Completed,
     InProgress,
     Failed,
+    Incomplete,
 }
 
 /// An output content part
@@ -468,6 +867,34 @@ pub struct ResponsesErrorResponse {
     pub error: ResponsesError,
 }
 
+/// One page of a cursor-paginated list (`GET /v1/responses`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListResponse<T> {
+    /// Object type (always "list")
+    pub object: String,
+    pub data: Vec<T>,
+    /// Id of the first item on the page, the `before` cursor for the
+    /// previous page
+    pub first_id: Option<String>,
+    /// Id of the last item on the page, the `after` cursor for the next page
+    pub last_id: Option<String>,
+    /// More items follow in the direction being paged
+    pub has_more: bool,
+}
+
+impl<T: Clone> ListResponse<&T> {
+    /// The same page owning clones of its items
+    pub fn cloned(self) -> ListResponse<T> {
+        ListResponse {
+            object: self.object,
+            data: self.data.into_iter().cloned().collect(),
+            first_id: self.first_id,
+            last_id: self.last_id,
+            has_more: self.has_more,
+        }
+    }
+}
+
 // ============================================================================
 // Streaming Types
 // ============================================================================
@@ -594,9 +1021,10 @@ impl ResponsesStreamEvent {
         content_index: u32,
         item_id: &str,
         delta: &str,
+        obfuscation: Option<&str>,
         seq: u32,
     ) -> String {
-        let event = serde_json::json!({
+        let mut event = serde_json::json!({
             "type": "response.output_text.delta",
             "output_index": output_index,
             "content_index": content_index,
@@ -605,6 +1033,9 @@ impl ResponsesStreamEvent {
             "logprobs": [],
             "sequence_number": seq
         });
+        if let Some(obfuscation) = obfuscation {
+            event["obfuscation"] = obfuscation.into();
+        }
         format!("event: response.output_text.delta\ndata: {}\n\n", event)
     }
 
@@ -627,6 +1058,192 @@ impl ResponsesStreamEvent {
         format!("event: response.output_text.done\ndata: {}\n\n", event)
     }
 
+    pub fn output_text_annotation_added(
+        output_index: u32,
+        content_index: u32,
+        item_id: &str,
+        annotation_index: u32,
+        annotation: &serde_json::Value,
+        seq: u32,
+    ) -> String {
+        let event = serde_json::json!({
+            "type": "response.output_text.annotation.added",
+            "output_index": output_index,
+            "content_index": content_index,
+            "item_id": item_id,
+            "annotation_index": annotation_index,
+            "annotation": annotation,
+            "sequence_number": seq
+        });
+        format!(
+            "event: response.output_text.annotation.added\ndata: {}\n\n",
+            event
+        )
+    }
+
+    pub fn code_interpreter_call_in_progress(output_index: u32, item_id: &str, seq: u32) -> String {
+        Self::code_interpreter_call_status("in_progress", output_index, item_id, seq)
+    }
+
+    pub fn code_interpreter_call_interpreting(
+        output_index: u32,
+        item_id: &str,
+        seq: u32,
+    ) -> String {
+        Self::code_interpreter_call_status("interpreting", output_index, item_id, seq)
+    }
+
+    pub fn code_interpreter_call_completed(output_index: u32, item_id: &str, seq: u32) -> String {
+        Self::code_interpreter_call_status("completed", output_index, item_id, seq)
+    }
+
+    fn code_interpreter_call_status(
+        status: &str,
+        output_index: u32,
+        item_id: &str,
+        seq: u32,
+    ) -> String {
+        let event_type = format!("response.code_interpreter_call.{}", status);
+        let event = serde_json::json!({
+            "type": event_type,
+            "output_index": output_index,
+            "item_id": item_id,
+            "sequence_number": seq
+        });
+        format!("event: {}\ndata: {}\n\n", event_type, event)
+    }
+
+    pub fn code_interpreter_call_code_delta(
+        output_index: u32,
+        item_id: &str,
+        delta: &str,
+        seq: u32,
+    ) -> String {
+        let event = serde_json::json!({
+            "type": "response.code_interpreter_call_code.delta",
+            "output_index": output_index,
+            "item_id": item_id,
+            "delta": delta,
+            "sequence_number": seq
+        });
+        format!(
+            "event: response.code_interpreter_call_code.delta\ndata: {}\n\n",
+            event
+        )
+    }
+
+    pub fn code_interpreter_call_code_done(
+        output_index: u32,
+        item_id: &str,
+        code: &str,
+        seq: u32,
+    ) -> String {
+        let event = serde_json::json!({
+            "type": "response.code_interpreter_call_code.done",
+            "output_index": output_index,
+            "item_id": item_id,
+            "code": code,
+            "sequence_number": seq
+        });
+        format!(
+            "event: response.code_interpreter_call_code.done\ndata: {}\n\n",
+            event
+        )
+    }
+
+    /// `response.mcp_list_tools.*` or `response.mcp_call.*` transition,
+    /// e.g. `mcp_status("mcp_call", "completed", ...)`
+    pub fn mcp_status(
+        item: &str,
+        status: &str,
+        output_index: u32,
+        item_id: &str,
+        seq: u32,
+    ) -> String {
+        let event_type = format!("response.{}.{}", item, status);
+        let event = serde_json::json!({
+            "type": event_type,
+            "output_index": output_index,
+            "item_id": item_id,
+            "sequence_number": seq
+        });
+        format!("event: {}\ndata: {}\n\n", event_type, event)
+    }
+
+    pub fn mcp_call_arguments_delta(
+        output_index: u32,
+        item_id: &str,
+        delta: &str,
+        seq: u32,
+    ) -> String {
+        let event = serde_json::json!({
+            "type": "response.mcp_call_arguments.delta",
+            "output_index": output_index,
+            "item_id": item_id,
+            "delta": delta,
+            "sequence_number": seq
+        });
+        format!(
+            "event: response.mcp_call_arguments.delta\ndata: {}\n\n",
+            event
+        )
+    }
+
+    pub fn mcp_call_arguments_done(
+        output_index: u32,
+        item_id: &str,
+        arguments: &str,
+        seq: u32,
+    ) -> String {
+        let event = serde_json::json!({
+            "type": "response.mcp_call_arguments.done",
+            "output_index": output_index,
+            "item_id": item_id,
+            "arguments": arguments,
+            "sequence_number": seq
+        });
+        format!(
+            "event: response.mcp_call_arguments.done\ndata: {}\n\n",
+            event
+        )
+    }
+
+    pub fn refusal_delta(
+        output_index: u32,
+        content_index: u32,
+        item_id: &str,
+        delta: &str,
+        seq: u32,
+    ) -> String {
+        let event = serde_json::json!({
+            "type": "response.refusal.delta",
+            "output_index": output_index,
+            "content_index": content_index,
+            "item_id": item_id,
+            "delta": delta,
+            "sequence_number": seq
+        });
+        format!("event: response.refusal.delta\ndata: {}\n\n", event)
+    }
+
+    pub fn refusal_done(
+        output_index: u32,
+        content_index: u32,
+        item_id: &str,
+        refusal: &str,
+        seq: u32,
+    ) -> String {
+        let event = serde_json::json!({
+            "type": "response.refusal.done",
+            "output_index": output_index,
+            "content_index": content_index,
+            "item_id": item_id,
+            "refusal": refusal,
+            "sequence_number": seq
+        });
+        format!("event: response.refusal.done\ndata: {}\n\n", event)
+    }
+
     pub fn content_part_done(
         output_index: u32,
         content_index: u32,
@@ -664,6 +1281,15 @@ impl ResponsesStreamEvent {
         format!("event: response.completed\ndata: {}\n\n", event)
     }
 
+    pub fn response_incomplete(response: ResponsesResponse, seq: u32) -> String {
+        let event = serde_json::json!({
+            "type": "response.incomplete",
+            "response": response,
+            "sequence_number": seq
+        });
+        format!("event: response.incomplete\ndata: {}\n\n", event)
+    }
+
     pub fn reasoning_summary_part_added(
         output_index: u32,
         summary_index: u32,
@@ -690,9 +1316,10 @@ impl ResponsesStreamEvent {
         summary_index: u32,
         item_id: &str,
         delta: &str,
+        obfuscation: Option<&str>,
         seq: u32,
     ) -> String {
-        let event = serde_json::json!({
+        let mut event = serde_json::json!({
             "type": "response.reasoning_summary_text.delta",
             "output_index": output_index,
             "summary_index": summary_index,
@@ -700,6 +1327,9 @@ impl ResponsesStreamEvent {
             "delta": delta,
             "sequence_number": seq
         });
+        if let Some(obfuscation) = obfuscation {
+            event["obfuscation"] = obfuscation.into();
+        }
         format!(
             "event: response.reasoning_summary_text.delta\ndata: {}\n\n",
             event
@@ -871,6 +1501,25 @@ mod tests {
         assert_eq!(response.output_text, Some("Hello!".to_string()));
     }
 
+    #[test]
+    fn test_responses_response_refused() {
+        let usage = ResponsesUsage {
+            input_tokens: 10,
+            output_tokens: 5,
+            total_tokens: 15,
+            output_tokens_details: None,
+        };
+        let response =
+            ResponsesResponse::new("gpt-5".to_string(), "No.".to_string(), usage).refused();
+
+        assert_eq!(response.status, ResponseStatus::Completed);
+        assert!(response.output_text.is_none());
+        let OutputItem::Message { content, .. } = &response.output[0] else {
+            panic!("Expected Message variant");
+        };
+        assert!(matches!(&content[0], OutputContentPart::Refusal { refusal } if refusal == "No."));
+    }
+
     #[test]
     fn test_responses_response_serialization() {
         let usage = ResponsesUsage {
@@ -902,7 +1551,7 @@ mod tests {
 
     #[test]
     fn test_stream_event_creation() {
-        let delta = ResponsesStreamEvent::output_text_delta(0, 0, "msg_123", "Hello", 5);
+        let delta = ResponsesStreamEvent::output_text_delta(0, 0, "msg_123", "Hello", None, 5);
         assert!(delta.contains("event: response.output_text.delta"));
         assert!(delta.contains("\"delta\":\"Hello\""));
         assert!(delta.contains("\"sequence_number\":5"));
@@ -1001,6 +1650,7 @@ mod tests {
                 summary_type: "summary_text".to_string(),
                 text: "Analyzing the problem.".to_string(),
             }]),
+            encrypted_content: None,
         };
 
         let json = serde_json::to_string(&item).unwrap();
@@ -1024,7 +1674,7 @@ mod tests {
         assert!(event.contains("\"sequence_number\":3"));
 
         let delta =
-            ResponsesStreamEvent::reasoning_summary_text_delta(0, 0, "rs_123", "Thinking", 4);
+            ResponsesStreamEvent::reasoning_summary_text_delta(0, 0, "rs_123", "Thinking", None, 4);
         assert!(delta.contains("event: response.reasoning_summary_text.delta"));
         assert!(delta.contains("\"delta\":\"Thinking\""));
         assert!(delta.contains("\"sequence_number\":4"));
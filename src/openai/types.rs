@@ -2,7 +2,7 @@
 // These types are designed to be compatible with the OpenAI Chat Completions API.
 // Reference: https://platform.openai.com/docs/api-reference/chat
 
-use crate::ids::{prefixed_id, unix_timestamp};
+use crate::ids::{new_id, unix_timestamp, IdKind};
 use serde::{Deserialize, Serialize};
 
 /// Role of a message in a conversation
@@ -19,6 +19,20 @@ pub enum Role {
     Function,
 }
 
+impl Role {
+    /// Wire name of the role
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::System => "system",
+            Role::Developer => "developer",
+            Role::User => "user",
+            Role::Assistant => "assistant",
+            Role::Tool => "tool",
+            Role::Function => "function",
+        }
+    }
+}
+
 /// Image reference for a Chat Completions `image_url` content part.
 /// Matches the Chat Completions wire format where `image_url` is an object
 /// (`{"url": "...", "detail": "..."}`), unlike the Responses API where it is a
@@ -110,6 +124,9 @@ pub struct Message {
     pub tool_calls: Option<Vec<ToolCall>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tool_call_id: Option<String>,
+    /// Deprecated single function call, superseded by `tool_calls`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub function_call: Option<FunctionCall>,
 }
 
 impl Message {
@@ -120,6 +137,7 @@ impl Message {
             name: None,
             tool_calls: None,
             tool_call_id: None,
+            function_call: None,
         }
     }
 
@@ -130,6 +148,7 @@ impl Message {
             name: None,
             tool_calls: None,
             tool_call_id: None,
+            function_call: None,
         }
     }
 
@@ -140,6 +159,7 @@ impl Message {
             name: None,
             tool_calls: None,
             tool_call_id: None,
+            function_call: None,
         }
     }
 }
@@ -160,6 +180,36 @@ pub struct FunctionCall {
     pub arguments: String,
 }
 
+/// Wire shape of assistant function calls in chat completions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolCallFormat {
+    /// `tool_calls` array, `finish_reason: "tool_calls"`
+    #[default]
+    ToolCalls,
+    /// Legacy single `function_call`, `finish_reason: "function_call"`.
+    /// Only the first call of a turn can be expressed.
+    FunctionCall,
+}
+
+impl ToolCallFormat {
+    /// `finish_reason` of a turn that ends in function calls
+    pub fn finish_reason(self) -> &'static str {
+        match self {
+            ToolCallFormat::ToolCalls => "tool_calls",
+            ToolCallFormat::FunctionCall => "function_call",
+        }
+    }
+
+    /// How many calls of one turn the format can carry
+    pub fn max_calls(self) -> usize {
+        match self {
+            ToolCallFormat::ToolCalls => usize::MAX,
+            ToolCallFormat::FunctionCall => 1,
+        }
+    }
+}
+
 /// A function definition for function calling
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Function {
@@ -235,9 +285,95 @@ pub struct ChatCompletionRequest {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tool_choice: Option<ToolChoice>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub parallel_tool_calls: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub response_format: Option<ResponseFormat>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub seed: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stream_options: Option<StreamOptions>,
+    /// Deprecated predecessor of `tools`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub functions: Option<Vec<Function>>,
+    /// Deprecated predecessor of `tool_choice`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub function_call: Option<FunctionCallChoice>,
+    /// Keep the completion for retrieval at `/v1/chat/completions/{id}`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub store: Option<bool>,
+    /// Up to 16 key-value pairs kept with a stored completion
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<std::collections::BTreeMap<String, String>>,
+}
+
+/// Legacy `function_call` request option: `"none"`, `"auto"` or
+/// `{"name": "..."}` to force a call
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum FunctionCallChoice {
+    Mode(String),
+    Named { name: String },
+}
+
+/// Streaming options (`stream_options`)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StreamOptions {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub include_usage: Option<bool>,
+    /// vLLM extension: attach running usage to every chunk, not just the last
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub continuous_usage_stats: Option<bool>,
+    /// Pad stream deltas with a random `obfuscation` field (default true)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub include_obfuscation: Option<bool>,
+}
+
+impl StreamOptions {
+    /// Whether delta events carry `obfuscation` padding; on unless disabled
+    pub fn obfuscation_enabled(&self) -> bool {
+        self.include_obfuscation.unwrap_or(true)
+    }
+}
+
+impl ChatCompletionRequest {
+    /// Whether the request uses the deprecated functions API instead of tools
+    pub fn uses_legacy_functions(&self) -> bool {
+        self.tools.is_none() && (self.functions.is_some() || self.function_call.is_some())
+    }
+
+    /// Function the legacy `function_call` option forces, if any
+    pub fn forced_function(&self) -> Option<&str> {
+        match &self.function_call {
+            Some(FunctionCallChoice::Named { name }) => Some(name),
+            _ => None,
+        }
+    }
+
+    /// `tool_call_id`s of the tool messages the request sends back
+    pub fn tool_result_ids(&self) -> impl Iterator<Item = &str> {
+        self.messages
+            .iter()
+            .filter(|m| m.role == Role::Tool)
+            .filter_map(|m| m.tool_call_id.as_deref())
+    }
+
+    /// Whether the client asked for usage on every streamed chunk
+    pub fn wants_continuous_usage(&self) -> bool {
+        self.stream_options
+            .as_ref()
+            .and_then(|o| o.continuous_usage_stats)
+            .unwrap_or(false)
+    }
+
+    /// The non-empty sequences `stop` ends generation at
+    pub fn stop_sequences(&self) -> Vec<&str> {
+        let sequences: Vec<&str> = match &self.stop {
+            Some(StopCondition::Single(stop)) => vec![stop],
+            Some(StopCondition::Multiple(stops)) => stops.iter().map(String::as_str).collect(),
+            None => Vec::new(),
+        };
+        sequences.into_iter().filter(|s| !s.is_empty()).collect()
+    }
 }
 
 /// Stop condition for generation
@@ -267,6 +403,9 @@ pub struct Choice {
 }
 
 /// Chat completion response
+/// `system_fingerprint` reported unless another is configured
+pub const DEFAULT_SYSTEM_FINGERPRINT: &str = "fp_llmsim";
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChatCompletionResponse {
     pub id: String,
@@ -283,7 +422,7 @@ pub struct ChatCompletionResponse {
 impl ChatCompletionResponse {
     pub fn new(model: String, content: String, usage: Usage) -> Self {
         Self {
-            id: prefixed_id("chatcmpl-"),
+            id: new_id(IdKind::ChatCompletion),
             object: "chat.completion".to_string(),
             created: unix_timestamp(),
             model,
@@ -294,7 +433,7 @@ impl ChatCompletionResponse {
                 logprobs: None,
             }],
             usage: Some(usage),
-            system_fingerprint: Some("fp_llmsim".to_string()),
+            system_fingerprint: Some(DEFAULT_SYSTEM_FINGERPRINT.to_string()),
         }
     }
 }
@@ -308,6 +447,8 @@ pub struct ChunkDelta {
     pub content: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tool_calls: Option<Vec<ChunkToolCall>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub function_call: Option<ChunkFunctionCall>,
 }
 
 /// Tool call in streaming chunk
@@ -364,7 +505,7 @@ impl ChatCompletionChunk {
             created,
             model,
             choices: vec![],
-            system_fingerprint: Some("fp_llmsim".to_string()),
+            system_fingerprint: Some(DEFAULT_SYSTEM_FINGERPRINT.to_string()),
             usage: None,
         }
     }
@@ -376,6 +517,7 @@ impl ChatCompletionChunk {
                 role: Some(Role::Assistant),
                 content: None,
                 tool_calls: None,
+                function_call: None,
             },
             finish_reason: None,
             logprobs: None,
@@ -390,6 +532,7 @@ impl ChatCompletionChunk {
                 role: None,
                 content: Some(content),
                 tool_calls: None,
+                function_call: None,
             },
             finish_reason: None,
             logprobs: None,
@@ -475,6 +618,11 @@ impl ErrorResponse {
             },
         }
     }
+
+    pub fn with_code(mut self, code: impl Into<String>) -> Self {
+        self.error.code = Some(code.into());
+        self
+    }
 }
 
 /// Model object returned by /openai/v1/models endpoint
@@ -7,7 +7,9 @@
 //
 // Reference: https://platform.openai.com/docs/guides/websocket-mode
 
-use super::{ReasoningConfig, ResponsesInput, ResponsesTool, ResponsesToolChoice};
+use super::{
+    ReasoningConfig, ResponsesInput, ResponsesTool, ResponsesToolChoice, StreamOptions, Truncation,
+};
 use serde::Deserialize;
 use std::collections::HashMap;
 
@@ -101,6 +103,12 @@ pub struct ResponseCreateBody {
     /// Include additional data
     #[serde(skip_serializing_if = "Option::is_none")]
     pub include: Option<Vec<String>>,
+    /// Streaming options; only `include_obfuscation` applies here
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stream_options: Option<StreamOptions>,
+    /// What happens when the conversation outgrows the context window
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub truncation: Option<Truncation>,
     /// Set to false for warmup/pre-loading (no model output)
     #[serde(default = "default_generate")]
     pub generate: bool,
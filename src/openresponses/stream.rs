@@ -5,12 +5,14 @@
 use super::types::{
     format_sse, OutputContent, OutputItem, Response, ResponseStatus, Role, StreamEvent, Usage,
 };
-use crate::ids::{prefixed_compact_id, unix_timestamp};
-use crate::latency::LatencyProfile;
+use crate::ids::{new_id, unix_timestamp, IdKind};
+use crate::latency::{LatencyProfile, RequestLatency};
+use crate::sleep::{default_sleeper, Sleeper};
+use crate::watchdog::StreamWatchdog;
 use async_stream::stream;
 use futures_core::Stream;
 use std::pin::Pin;
-use tokio::time::sleep;
+use std::sync::Arc;
 
 /// Callback type for stream completion
 type OnCompleteCallback = Box<dyn FnOnce() + Send + 'static>;
@@ -24,33 +26,64 @@ pub struct OpenResponsesTokenStream {
     /// Unix timestamp of creation
     created_at: i64,
     /// Latency profile for timing simulation
-    latency: LatencyProfile,
+    latency: RequestLatency,
     /// The full response text to stream
     content: String,
     /// Token usage (included in final event)
     usage: Option<Usage>,
+    /// End with `response.incomplete` (cut off by max_output_tokens)
+    truncated: bool,
+    /// Waits out the simulated delays
+    sleeper: Arc<dyn Sleeper>,
+    /// Ends the stream early once its time budget is spent
+    watchdog: StreamWatchdog,
     /// Callback to invoke when stream completes
     on_complete: Option<OnCompleteCallback>,
 }
 
 impl OpenResponsesTokenStream {
-    pub fn new(id: String, model: String, content: String, latency: LatencyProfile) -> Self {
+    pub fn new(
+        id: String,
+        model: String,
+        content: String,
+        latency: impl Into<RequestLatency>,
+    ) -> Self {
         Self {
             id,
             model,
             created_at: unix_timestamp(),
-            latency,
+            latency: latency.into(),
             content,
             usage: None,
+            truncated: false,
+            sleeper: default_sleeper(),
+            watchdog: StreamWatchdog::unlimited(),
             on_complete: None,
         }
     }
 
+    /// Wait out delays with this sleeper (default: the tokio timer)
+    pub fn with_sleeper(mut self, sleeper: Arc<dyn Sleeper>) -> Self {
+        self.sleeper = sleeper;
+        self
+    }
+
+    /// End with `response.incomplete` once the watchdog's budget is spent
+    pub fn with_watchdog(mut self, watchdog: StreamWatchdog) -> Self {
+        self.watchdog = watchdog;
+        self
+    }
+
     pub fn with_usage(mut self, usage: Usage) -> Self {
         self.usage = Some(usage);
         self
     }
 
+    pub fn with_truncated(mut self, truncated: bool) -> Self {
+        self.truncated = truncated;
+        self
+    }
+
     pub fn with_on_complete<F>(mut self, callback: F) -> Self
     where
         F: FnOnce() + Send + 'static,
@@ -86,21 +119,25 @@ impl OpenResponsesTokenStream {
     /// Create a streaming response as Server-Sent Events following OpenResponses format
     pub fn into_stream(self) -> Pin<Box<dyn Stream<Item = String> + Send>> {
         let tokens = self.tokenize();
+        let token_count = tokens.len();
         let id = self.id.clone();
         let model = self.model.clone();
         let created_at = self.created_at;
-        let latency = self.latency.clone();
+        let mut latency = self.latency.clone();
         let usage = self.usage.clone();
+        let truncated = self.truncated;
+        let sleeper = self.sleeper;
+        let mut watchdog = self.watchdog;
         let on_complete = self.on_complete;
 
         Box::pin(stream! {
             // Generate IDs for the output items
-            let item_id = prefixed_compact_id("msg_");
+            let item_id = new_id(IdKind::Message);
 
             // Initial delay (time to first token)
-            let ttft = latency.sample_ttft();
-            if !ttft.is_zero() {
-                sleep(ttft).await;
+            let ttft = latency.ttft();
+            if watchdog.allows(ttft) && !ttft.is_zero() {
+                sleeper.sleep(ttft).await;
             }
 
             // 1. response.created event
@@ -115,6 +152,7 @@ impl OpenResponsesTokenStream {
                 usage: None,
                 metadata: None,
                 error: None,
+                incomplete_details: None,
             };
             yield format_sse(&StreamEvent::response_created(created_response));
 
@@ -130,6 +168,7 @@ impl OpenResponsesTokenStream {
                 usage: None,
                 metadata: None,
                 error: None,
+                incomplete_details: None,
             };
             yield format_sse(&StreamEvent::response_in_progress(in_progress_response));
 
@@ -149,19 +188,41 @@ impl OpenResponsesTokenStream {
             };
             yield format_sse(&StreamEvent::content_part_added(0, 0, content_part));
 
-            // 5. Stream the tokens as response.output_text.delta events
+            // 5. Stream the tokens as response.output_text.delta events,
+            //    until the time budget runs out
             let mut full_text = String::new();
+            let mut sent = 0;
             for token in tokens {
                 // Inter-token delay
-                let tbt = latency.sample_tbt();
+                let tbt = latency.tbt();
+                if !watchdog.allows(tbt) {
+                    break;
+                }
                 if !tbt.is_zero() {
-                    sleep(tbt).await;
+                    sleeper.sleep(tbt).await;
                 }
 
                 full_text.push_str(&token);
+                sent += 1;
                 yield format_sse(&StreamEvent::output_text_delta(0, 0, token));
             }
 
+            // A stream out of time ends as if cut off by max_output_tokens,
+            // counting the output it sent
+            let capped = watchdog.tripped();
+            let truncated = truncated || capped;
+            let usage = usage.map(|usage| {
+                if !capped {
+                    return usage;
+                }
+                let output_tokens = (usage.output_tokens as usize * sent / token_count.max(1)) as u32;
+                Usage {
+                    output_tokens,
+                    total_tokens: usage.input_tokens + output_tokens,
+                    ..usage
+                }
+            });
+
             // 6. response.output_text.done event
             yield format_sse(&StreamEvent::output_text_done(0, 0, full_text.clone()));
 
@@ -180,11 +241,11 @@ impl OpenResponsesTokenStream {
                     text: full_text.clone(),
                     annotations: None,
                 }],
-                status: Some("completed".to_string()),
+                status: Some(if truncated { "incomplete" } else { "completed" }.to_string()),
             };
             yield format_sse(&StreamEvent::output_item_done(0, completed_item));
 
-            // 9. response.completed event
+            // 9. response.completed (or response.incomplete) event
             let completed_at = unix_timestamp();
             let completed_response = Response {
                 id: id.clone(),
@@ -205,8 +266,13 @@ impl OpenResponsesTokenStream {
                 usage,
                 metadata: None,
                 error: None,
+                incomplete_details: None,
             };
-            yield format_sse(&StreamEvent::response_completed(completed_response));
+            if truncated {
+                yield format_sse(&StreamEvent::response_incomplete(completed_response.truncated()));
+            } else {
+                yield format_sse(&StreamEvent::response_completed(completed_response));
+            }
 
             // Done marker (same as OpenAI)
             yield "data: [DONE]\n\n".to_string();
@@ -224,8 +290,11 @@ pub struct OpenResponsesStreamBuilder {
     id: Option<String>,
     model: String,
     content: String,
-    latency: LatencyProfile,
+    latency: RequestLatency,
     usage: Option<Usage>,
+    truncated: bool,
+    sleeper: Option<Arc<dyn Sleeper>>,
+    watchdog: StreamWatchdog,
     on_complete: Option<OnCompleteCallback>,
 }
 
@@ -235,8 +304,11 @@ impl OpenResponsesStreamBuilder {
             id: None,
             model: model.into(),
             content: content.into(),
-            latency: LatencyProfile::default(),
+            latency: LatencyProfile::default().into(),
             usage: None,
+            truncated: false,
+            sleeper: None,
+            watchdog: StreamWatchdog::unlimited(),
             on_complete: None,
         }
     }
@@ -246,8 +318,8 @@ impl OpenResponsesStreamBuilder {
         self
     }
 
-    pub fn latency(mut self, latency: LatencyProfile) -> Self {
-        self.latency = latency;
+    pub fn latency(mut self, latency: impl Into<RequestLatency>) -> Self {
+        self.latency = latency.into();
         self
     }
 
@@ -256,6 +328,25 @@ impl OpenResponsesStreamBuilder {
         self
     }
 
+    /// End the stream with `response.incomplete` for output cut off by
+    /// `max_output_tokens`
+    pub fn truncated(mut self, truncated: bool) -> Self {
+        self.truncated = truncated;
+        self
+    }
+
+    /// Wait out delays with this sleeper
+    pub fn sleeper(mut self, sleeper: Arc<dyn Sleeper>) -> Self {
+        self.sleeper = Some(sleeper);
+        self
+    }
+
+    /// End with `response.incomplete` once the watchdog's budget is spent
+    pub fn watchdog(mut self, watchdog: StreamWatchdog) -> Self {
+        self.watchdog = watchdog;
+        self
+    }
+
     pub fn on_complete<F>(mut self, callback: F) -> Self
     where
         F: FnOnce() + Send + 'static,
@@ -265,12 +356,17 @@ impl OpenResponsesStreamBuilder {
     }
 
     pub fn build(self) -> OpenResponsesTokenStream {
-        let id = self.id.unwrap_or_else(|| prefixed_compact_id("resp_"));
+        let id = self.id.unwrap_or_else(|| new_id(IdKind::Response));
 
-        let mut stream = OpenResponsesTokenStream::new(id, self.model, self.content, self.latency);
+        let mut stream = OpenResponsesTokenStream::new(id, self.model, self.content, self.latency)
+            .with_truncated(self.truncated)
+            .with_watchdog(self.watchdog);
         if let Some(usage) = self.usage {
             stream = stream.with_usage(usage);
         }
+        if let Some(sleeper) = self.sleeper {
+            stream = stream.with_sleeper(sleeper);
+        }
         if let Some(on_complete) = self.on_complete {
             stream = stream.with_on_complete(on_complete);
         }
@@ -2,7 +2,7 @@
 // These types are designed to be compatible with the Open Responses API specification.
 // Reference: https://www.openresponses.org/specification
 
-use crate::ids::{prefixed_compact_id, unix_timestamp};
+use crate::ids::{new_id, unix_timestamp, IdKind};
 use serde::{Deserialize, Serialize};
 
 /// Role of a message in a conversation
@@ -86,6 +86,14 @@ pub enum Input {
 }
 
 impl Input {
+    /// Messages sent; a plain text input is one
+    pub fn message_count(&self) -> usize {
+        match self {
+            Input::Text(_) => 1,
+            Input::Messages(messages) => messages.len(),
+        }
+    }
+
     /// Extract text content from input for token counting
     pub fn extract_text(&self) -> String {
         match self {
@@ -345,13 +353,21 @@ pub struct Response {
     pub metadata: Option<std::collections::HashMap<String, String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub error: Option<ErrorInfo>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub incomplete_details: Option<IncompleteDetails>,
+}
+
+/// Reason a response ended with `status: incomplete`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct IncompleteDetails {
+    pub reason: String,
 }
 
 impl Response {
     pub fn new(model: String, content: String, usage: Usage) -> Self {
         let created_at = unix_timestamp();
-        let id = prefixed_compact_id("resp_");
-        let item_id = prefixed_compact_id("msg_");
+        let id = new_id(IdKind::Response);
+        let item_id = new_id(IdKind::Message);
 
         Self {
             id,
@@ -372,8 +388,23 @@ impl Response {
             usage: Some(usage),
             metadata: None,
             error: None,
+            incomplete_details: None,
         }
     }
+
+    /// Mark the response as cut off by `max_output_tokens`
+    pub fn truncated(mut self) -> Self {
+        self.status = ResponseStatus::Incomplete;
+        self.incomplete_details = Some(IncompleteDetails {
+            reason: "max_output_tokens".to_string(),
+        });
+        for item in &mut self.output {
+            if let OutputItem::Message { status, .. } = item {
+                *status = Some("incomplete".to_string());
+            }
+        }
+        self
+    }
 }
 
 /// Error information in response
@@ -466,6 +497,8 @@ pub enum StreamEventType {
     ResponseInProgress,
     #[serde(rename = "response.completed")]
     ResponseCompleted,
+    #[serde(rename = "response.incomplete")]
+    ResponseIncomplete,
     #[serde(rename = "response.failed")]
     ResponseFailed,
 
@@ -664,6 +697,14 @@ impl StreamEvent {
             error: None,
         }
     }
+
+    /// Create a response.incomplete event
+    pub fn response_incomplete(response: Response) -> Self {
+        Self {
+            event_type: StreamEventType::ResponseIncomplete,
+            ..Self::response_completed(response)
+        }
+    }
 }
 
 /// Format a stream event as Server-Sent Event
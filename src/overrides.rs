@@ -0,0 +1,133 @@
+// Request Overrides Module
+// Simulator settings a client chooses for a single request, sent inside the
+// request body as an `llmsim` object, e.g.
+// `{"llmsim": {"force_error": "rate_limit", "ttft_ms": 5000}}`, for SDKs that
+// strip custom headers but pass unknown body fields through.
+//
+// Decision: overrides are applied to a begun `PipelineRequest`, so every
+// protocol honors them the same way: a forced error comes out of
+// `inject_error` like a drawn one, in the endpoint's error format, and the
+// delays replace the request's latency model. Delays are taken literally,
+// unscaled by `time_scale` or drills, since the client asked for that exact
+// number. Unknown fields are rejected so a typo fails loudly instead of
+// silently testing nothing.
+
+use crate::errors::SimulatedError;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Name of the request body field holding the overrides
+pub const OVERRIDES_FIELD: &str = "llmsim";
+
+/// Error a request asks to be answered with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ForcedError {
+    /// 429
+    RateLimit,
+    /// 500
+    ServerError,
+    /// 503
+    ServiceUnavailable,
+    /// 503 from a shed request
+    Overloaded,
+    /// 504
+    Timeout,
+    /// 400
+    InvalidRequest,
+    /// 401
+    Authentication,
+}
+
+impl ForcedError {
+    /// The simulated error; `timeout` is what a 504 reports waiting
+    pub fn error(self, timeout: Duration) -> SimulatedError {
+        match self {
+            Self::RateLimit => SimulatedError::RateLimit {
+                retry_after_seconds: 1,
+            },
+            Self::ServerError => SimulatedError::ServerError,
+            Self::ServiceUnavailable => SimulatedError::ServiceUnavailable,
+            Self::Overloaded => SimulatedError::Overloaded {
+                retry_after_seconds: 1,
+            },
+            Self::Timeout => SimulatedError::Timeout { after: timeout },
+            Self::InvalidRequest => SimulatedError::InvalidRequest {
+                message: "Invalid request forced by the llmsim extension".to_string(),
+            },
+            Self::Authentication => SimulatedError::AuthenticationError,
+        }
+    }
+}
+
+/// The `llmsim` object of a request body
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RequestOverrides {
+    /// Answer with this error instead of a response
+    #[serde(default)]
+    pub force_error: Option<ForcedError>,
+    /// Exact time to first token (ms)
+    #[serde(default)]
+    pub ttft_ms: Option<u64>,
+    /// Exact time between tokens (ms)
+    #[serde(default)]
+    pub tbt_ms: Option<u64>,
+    /// Latency profile of the caller's API key (`LatencyProfile::named`), in
+    /// place of the model's; set by the server, never read from the body
+    #[serde(skip)]
+    pub profile: Option<String>,
+}
+
+impl RequestOverrides {
+    /// Parse a request body's `llmsim` object
+    pub fn from_value(value: serde_json::Value) -> Result<Self, String> {
+        serde_json::from_value(value).map_err(|e| format!("invalid `llmsim` object: {}", e))
+    }
+
+    /// Whether any delay is overridden
+    pub fn has_latency(&self) -> bool {
+        self.ttft_ms.is_some() || self.tbt_ms.is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_overrides() {
+        let overrides =
+            RequestOverrides::from_value(json!({"force_error": "rate_limit", "ttft_ms": 5000}))
+                .unwrap();
+        assert_eq!(overrides.force_error, Some(ForcedError::RateLimit));
+        assert_eq!(overrides.ttft_ms, Some(5000));
+        assert!(overrides.has_latency());
+        assert!(!RequestOverrides::default().has_latency());
+    }
+
+    #[test]
+    fn test_unknown_fields_rejected() {
+        let err = RequestOverrides::from_value(json!({"ttft": 5000})).unwrap_err();
+        assert!(err.contains("ttft"), "{}", err);
+        assert!(RequestOverrides::from_value(json!({"force_error": "teapot"})).is_err());
+    }
+
+    #[test]
+    fn test_forced_error_statuses() {
+        let timeout = Duration::from_secs(30);
+        let statuses: Vec<u16> = [
+            ForcedError::RateLimit,
+            ForcedError::ServerError,
+            ForcedError::ServiceUnavailable,
+            ForcedError::Timeout,
+            ForcedError::InvalidRequest,
+            ForcedError::Authentication,
+        ]
+        .into_iter()
+        .map(|forced| forced.error(timeout).status_code())
+        .collect();
+        assert_eq!(statuses, [429, 500, 503, 504, 400, 401]);
+    }
+}
@@ -0,0 +1,152 @@
+// Pacing Module
+// How closely streams keep to the delays they sample: each streamed
+// request's gaps between chunks are compared with the configured ones, and
+// the differences reported in stats as lag (mean) and jitter (standard
+// deviation), so a benchmark can tell a simulator that paces as configured
+// from one distorted by runtime scheduling under load.
+//
+// Decision: measured in a `Sleeper` wrapper, like the decision log's
+// recording, so every streaming engine is covered without touching them. A
+// gap runs from the previous wake-up to the end of the next sleep, so it
+// takes in the timer's overshoot, the wait for a worker thread and the time
+// the chunk took to be written out in between, all of which a client sees
+// as a late token. The first sleep of a stream (time to first token) has no
+// wake-up before it and counts from when it began. Each stream reports once,
+// when its last handle is dropped, so stats take no lock per chunk.
+
+use crate::clock::{self, Instant};
+use crate::sleep::{SleepFuture, Sleeper};
+use crate::stats::SharedStats;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Emitted minus configured gaps of one stream
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StreamPacing {
+    /// Gaps measured
+    pub gaps: u64,
+    /// Sum of emitted minus configured gap (ms)
+    pub sum_ms: f64,
+    /// Sum of its squares
+    pub sum_sq_ms: f64,
+}
+
+impl StreamPacing {
+    /// Add a gap that took `emitted` where `configured` was asked for
+    pub fn add(&mut self, emitted: Duration, configured: Duration) {
+        let error = (emitted.as_secs_f64() - configured.as_secs_f64()) * 1000.0;
+        self.gaps += 1;
+        self.sum_ms += error;
+        self.sum_sq_ms += error * error;
+    }
+
+    /// Mean of emitted minus configured gap (ms); positive when the stream
+    /// runs behind
+    pub fn lag_ms(&self) -> f64 {
+        if self.gaps == 0 {
+            0.0
+        } else {
+            self.sum_ms / self.gaps as f64
+        }
+    }
+
+    /// Standard deviation of emitted minus configured gap (ms)
+    pub fn jitter_ms(&self) -> f64 {
+        if self.gaps == 0 {
+            return 0.0;
+        }
+        let mean = self.lag_ms();
+        (self.sum_sq_ms / self.gaps as f64 - mean * mean)
+            .max(0.0)
+            .sqrt()
+    }
+}
+
+/// Sleeps with `inner`, timing each gap of one stream and reporting them to
+/// stats when dropped
+pub struct PacingSleeper {
+    inner: Arc<dyn Sleeper>,
+    stats: SharedStats,
+    state: Arc<Mutex<PacingState>>,
+}
+
+#[derive(Debug, Default)]
+struct PacingState {
+    last_wake: Option<Instant>,
+    pacing: StreamPacing,
+}
+
+impl PacingSleeper {
+    pub fn new(inner: Arc<dyn Sleeper>, stats: SharedStats) -> Self {
+        Self {
+            inner,
+            stats,
+            state: Arc::default(),
+        }
+    }
+}
+
+impl Sleeper for PacingSleeper {
+    fn sleep(&self, duration: Duration) -> SleepFuture {
+        let since = self
+            .state
+            .lock()
+            .unwrap()
+            .last_wake
+            .unwrap_or_else(clock::now);
+        let sleep = self.inner.sleep(duration);
+        let state = self.state.clone();
+        Box::pin(async move {
+            sleep.await;
+            let woke = clock::now();
+            let mut state = state.lock().unwrap();
+            state
+                .pacing
+                .add(woke.saturating_duration_since(since), duration);
+            state.last_wake = Some(woke);
+        })
+    }
+}
+
+impl Drop for PacingSleeper {
+    fn drop(&mut self) {
+        let state = self.state.lock().unwrap();
+        if state.pacing.gaps > 0 {
+            self.stats.record_stream_pacing(&state.pacing);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sleep::VirtualSleeper;
+    use crate::stats::new_shared_stats;
+
+    #[test]
+    fn test_lag_and_jitter() {
+        let mut pacing = StreamPacing::default();
+        for emitted in [12, 8, 14, 10] {
+            pacing.add(Duration::from_millis(emitted), Duration::from_millis(10));
+        }
+        assert!((pacing.lag_ms() - 1.0).abs() < 1e-9);
+        assert!((pacing.jitter_ms() - 5.0f64.sqrt()).abs() < 1e-9);
+        assert_eq!(StreamPacing::default().jitter_ms(), 0.0);
+    }
+
+    #[tokio::test]
+    async fn test_reports_once_dropped() {
+        let stats = new_shared_stats();
+        let sleeper = PacingSleeper::new(Arc::new(VirtualSleeper::new()), stats.clone());
+        sleeper.sleep(Duration::from_millis(5)).await;
+        sleeper.sleep(Duration::from_millis(5)).await;
+        assert_eq!(stats.snapshot().pacing.gaps, 0);
+
+        drop(sleeper);
+        let pacing = stats.snapshot().pacing;
+        assert_eq!(pacing.gaps, 2);
+        assert_eq!(pacing.stream_jitter_us.count, 1);
+        // A sleeper that never waits runs ahead of every gap
+        assert!(pacing.mean_lag_ms < 0.0);
+    }
+}
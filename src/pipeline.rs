@@ -0,0 +1,1346 @@
+// Request Pipeline Module
+// Shared request lifecycle for protocol frontends: stats bookkeeping, error
+// injection, latency selection, generation, and token counting.
+//
+// Decision: the pipeline owns everything that is identical across wire
+// formats and nothing that is not. A frontend begins a `PipelineRequest`,
+// asks it for an injected error (rendered through its `FormatAdapter`), the
+// latency profile, and generated text, then builds its own protocol-shaped
+// body and stream. Adding a new frontend means writing a `FormatAdapter` and
+// the body/stream types, not re-deriving the lifecycle.
+
+use crate::anthropic::AnthropicErrorResponse;
+use crate::capacity::LoadShedder;
+use crate::citations;
+use crate::clock::{self, Instant};
+use crate::decisions::{
+    prompt_excerpt, DecisionLog, ErrorCause, Outcome, Recording, RecordingSleeper,
+};
+use crate::drill::{DrillEffects, Drills};
+use crate::errors::{ErrorConfig, ErrorInjector, SimulatedError};
+use crate::fingerprint::SystemFingerprints;
+use crate::generator::{create_generator, request_rng, ResponseGenerator, TextPieces};
+use crate::latency::{LatencyContext, LatencyModel, LatencyProfile, RequestLatency, ScaledLatency};
+use crate::learned::LearnedProfiles;
+use crate::openai::{ChatCompletionRequest, ErrorResponse, ResponsesError, ResponsesErrorResponse};
+use crate::overrides::RequestOverrides;
+use crate::pacing::PacingSleeper;
+use crate::script::SimToolCall;
+use crate::sleep::{default_sleeper, Sleeper};
+use crate::stamps::Stamps;
+use crate::stats::{EndpointType, SharedStats};
+use crate::stop::{cut_at_stop, find_stop};
+use crate::tool_calls::ToolCallConfig;
+use crate::watchdog::StreamWatchdog;
+use futures_core::Stream;
+use rand::prelude::IndexedRandom;
+use rand::RngExt;
+use serde::Serialize;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+/// What a refusing model says instead of answering
+const REFUSAL_MESSAGE: &str = "I'm sorry, but I can't help with that request.";
+
+/// Log-length standard deviation per unit of `temperature * top_p`: at the
+/// default temperature of 1, two thirds of responses land within about 30%
+/// of the target length
+const LENGTH_SPREAD_PER_TEMPERATURE: f64 = 0.3;
+
+/// Gaps a latency model's mean time between tokens is averaged over
+const TBT_SAMPLES: u32 = 32;
+
+/// Protocol-specific rendering of pipeline outcomes
+pub trait FormatAdapter {
+    /// Error envelope serialized for an injected error
+    type ErrorBody: Serialize;
+
+    /// Build the protocol's error body for an injected error
+    fn error_body(error: &SimulatedError) -> Self::ErrorBody;
+}
+
+/// OpenAI Chat Completions (and other `{"error": {...}}` OpenAI endpoints)
+pub struct OpenAIFormat;
+
+impl FormatAdapter for OpenAIFormat {
+    type ErrorBody = ErrorResponse;
+
+    fn error_body(error: &SimulatedError) -> ErrorResponse {
+        error.to_error_response()
+    }
+}
+
+/// OpenAI Responses API
+pub struct ResponsesFormat;
+
+impl FormatAdapter for ResponsesFormat {
+    type ErrorBody = ResponsesErrorResponse;
+
+    fn error_body(error: &SimulatedError) -> ResponsesErrorResponse {
+        let detail = error.to_error_response().error;
+        ResponsesErrorResponse {
+            error: ResponsesError {
+                code: detail.code,
+                ..ResponsesError::new(detail.error_type, detail.message)
+            },
+        }
+    }
+}
+
+/// OpenResponses (https://www.openresponses.org)
+pub struct OpenResponsesFormat;
+
+impl FormatAdapter for OpenResponsesFormat {
+    type ErrorBody = crate::openresponses::ErrorResponse;
+
+    fn error_body(error: &SimulatedError) -> Self::ErrorBody {
+        let detail = error.to_error_response().error;
+        crate::openresponses::ErrorResponse::new(detail.message, detail.error_type)
+    }
+}
+
+/// Anthropic Messages API
+pub struct AnthropicFormat;
+
+impl FormatAdapter for AnthropicFormat {
+    type ErrorBody = AnthropicErrorResponse;
+
+    fn error_body(error: &SimulatedError) -> AnthropicErrorResponse {
+        AnthropicErrorResponse::new(
+            AnthropicErrorResponse::type_for_status(error.status_code()),
+            error.to_error_response().error.message,
+        )
+    }
+}
+
+/// Request lifecycle shared by every protocol frontend
+#[derive(Clone)]
+pub struct RequestPipeline {
+    stats: SharedStats,
+    errors: ErrorConfig,
+    latency: Option<LatencyProfile>,
+    latency_model: Option<Arc<dyn LatencyModel>>,
+    model_latency: Arc<HashMap<String, LatencyProfile>>,
+    generator: Arc<dyn ResponseGenerator>,
+    tool_calls: ToolCallConfig,
+    fingerprints: SystemFingerprints,
+    shedder: Option<Arc<LoadShedder>>,
+    truncation_rate: f64,
+    refusal_rate: f64,
+    citation_rate: f64,
+    tokenizer: Arc<dyn TextTokenizer>,
+    sleeper: Arc<dyn Sleeper>,
+    stamps: Arc<Stamps>,
+    time_scale: f64,
+    drills: Option<Arc<Drills>>,
+    region: Option<String>,
+    learned: Option<Arc<LearnedProfiles>>,
+    decisions: Option<Arc<DecisionLog>>,
+    temperature_effects: bool,
+    stop_hit_rate: f64,
+    max_stream_duration: Option<Duration>,
+    delays_disabled: bool,
+}
+
+impl RequestPipeline {
+    /// Pipeline with no error injection, model-derived latency, and the
+    /// default lorem generator
+    pub fn new(stats: SharedStats) -> Self {
+        Self {
+            stats,
+            errors: ErrorConfig::none(),
+            latency: None,
+            latency_model: None,
+            model_latency: Arc::default(),
+            generator: Arc::from(create_generator("lorem", 100)),
+            tool_calls: ToolCallConfig::none(),
+            fingerprints: SystemFingerprints::default(),
+            shedder: None,
+            truncation_rate: 0.0,
+            refusal_rate: 0.0,
+            citation_rate: 0.0,
+            tokenizer: Arc::new(DefaultTokenizer),
+            sleeper: default_sleeper(),
+            stamps: Arc::default(),
+            time_scale: 1.0,
+            drills: None,
+            region: None,
+            learned: None,
+            decisions: None,
+            temperature_effects: false,
+            stop_hit_rate: 0.0,
+            max_stream_duration: None,
+            delays_disabled: false,
+        }
+    }
+
+    pub fn with_errors(mut self, errors: ErrorConfig) -> Self {
+        self.errors = errors;
+        self
+    }
+
+    /// Use a fixed latency profile for every model instead of
+    /// `LatencyProfile::from_model`
+    pub fn with_latency(mut self, latency: LatencyProfile) -> Self {
+        self.latency = Some(latency);
+        self
+    }
+
+    /// Sample delays from `model` instead of the latency profiles. The
+    /// profile still anchors image generation times and is what decisions
+    /// report.
+    pub fn with_latency_model(mut self, model: Arc<dyn LatencyModel>) -> Self {
+        self.latency_model = Some(model);
+        self
+    }
+
+    /// Use these profiles for the models they are keyed by, ahead of
+    /// learned latency and `LatencyProfile::from_model`
+    pub fn with_model_latency(mut self, profiles: HashMap<String, LatencyProfile>) -> Self {
+        self.model_latency = Arc::new(profiles);
+        self
+    }
+
+    pub fn with_generator(mut self, generator: Arc<dyn ResponseGenerator>) -> Self {
+        self.generator = generator;
+        self
+    }
+
+    pub fn with_tool_calls(mut self, tool_calls: ToolCallConfig) -> Self {
+        self.tool_calls = tool_calls;
+        self
+    }
+
+    pub fn with_fingerprints(mut self, fingerprints: SystemFingerprints) -> Self {
+        self.fingerprints = fingerprints;
+        self
+    }
+
+    /// Shed requests beyond the shedder's rate with 503 + Retry-After
+    pub fn with_load_shedding(mut self, shedder: LoadShedder) -> Self {
+        self.shedder = Some(Arc::new(shedder));
+        self
+    }
+
+    /// Chance (0.0-1.0) that generated Responses output is cut short as if
+    /// it had hit `max_output_tokens`
+    pub fn with_truncation_rate(mut self, rate: f64) -> Self {
+        self.truncation_rate = rate;
+        self
+    }
+
+    /// Chance (0.0-1.0) that a Responses answer is a refusal instead of
+    /// generated text
+    pub fn with_refusal_rate(mut self, rate: f64) -> Self {
+        self.refusal_rate = rate;
+        self
+    }
+
+    /// Chance (0.0-1.0) that each word of a Responses answer is cited by an
+    /// annotation
+    pub fn with_citation_rate(mut self, rate: f64) -> Self {
+        self.citation_rate = rate;
+        self
+    }
+
+    /// Count prompt and generated text with this tokenizer instead of
+    /// `count_text_tokens`
+    pub fn with_tokenizer(mut self, tokenizer: Arc<dyn TextTokenizer>) -> Self {
+        self.tokenizer = tokenizer;
+        self
+    }
+
+    /// Wait out simulated latency with this sleeper instead of the tokio
+    /// timer; handlers hand it to the streams they build
+    pub fn with_sleeper(mut self, sleeper: Arc<dyn Sleeper>) -> Self {
+        self.sleeper = sleeper;
+        self
+    }
+
+    /// Mint ids and timestamps as `stamps` say in the requests scoped in
+    /// `stamps()`
+    pub fn with_stamps(mut self, stamps: Stamps) -> Self {
+        self.stamps = Arc::new(stamps);
+        self
+    }
+
+    /// Answer every request instantly: the instant profile for every model,
+    /// which neither a key's profile nor a request's own delays replace
+    pub fn without_delays(mut self) -> Self {
+        self.latency = Some(LatencyProfile::instant());
+        self.delays_disabled = true;
+        self
+    }
+
+    /// Multiply every latency profile by `scale`, fixed or model-derived, so
+    /// the same profiles can run at a fraction of real time
+    pub fn with_time_scale(mut self, scale: f64) -> Self {
+        self.time_scale = scale;
+        self
+    }
+
+    /// Apply the board's running drills to requests, as the listener for
+    /// `region` (`None` for the main one)
+    pub fn with_drills(mut self, drills: Arc<Drills>, region: Option<String>) -> Self {
+        self.drills = Some(drills);
+        self.region = region;
+        self
+    }
+
+    /// Replay learned per-model latency and response lengths. A fixed
+    /// latency profile still wins; learned latency replaces the
+    /// model-derived one.
+    pub fn with_learned_profiles(mut self, profiles: Arc<LearnedProfiles>) -> Self {
+        self.learned = Some(profiles);
+        self
+    }
+
+    /// Let `temperature` and `top_p` shape generated text: at temperature 0
+    /// an unseeded request is seeded from the model's system fingerprint, so
+    /// it repeats until the fingerprint changes, and response length varies
+    /// more the higher `temperature * top_p` is
+    pub fn with_temperature_effects(mut self, enabled: bool) -> Self {
+        self.temperature_effects = enabled;
+        self
+    }
+
+    /// Chance (0.0-1.0) that a request with `stop` sequences ends at one of
+    /// them, cut partway through its response
+    pub fn with_stop_hit_rate(mut self, rate: f64) -> Self {
+        self.stop_hit_rate = rate;
+        self
+    }
+
+    /// Cut generated streams short once they have waited out `limit` of
+    /// simulated delays in total
+    pub fn with_max_stream_duration(mut self, limit: Option<Duration>) -> Self {
+        self.max_stream_duration = limit;
+        self
+    }
+
+    /// Record each request's decisions for the log's subscribers
+    pub fn with_decision_log(mut self, log: Arc<DecisionLog>) -> Self {
+        self.decisions = Some(log);
+        self
+    }
+
+    pub fn stats(&self) -> &SharedStats {
+        &self.stats
+    }
+
+    pub fn tokenizer(&self) -> &Arc<dyn TextTokenizer> {
+        &self.tokenizer
+    }
+
+    pub fn sleeper(&self) -> &Arc<dyn Sleeper> {
+        &self.sleeper
+    }
+
+    pub fn stamps(&self) -> &Arc<Stamps> {
+        &self.stamps
+    }
+
+    /// Token count of `text` with the configured tokenizer
+    pub fn count_tokens(&self, text: &str) -> usize {
+        self.tokenizer.count(text)
+    }
+
+    /// Latency profile for a model, honoring a configured override and the
+    /// time scale: a fixed profile, else one pinned to the model, else the
+    /// learned one, else `LatencyProfile::from_model`
+    pub fn latency_for(&self, model: &str) -> LatencyProfile {
+        let profile = self
+            .latency
+            .clone()
+            .or_else(|| self.model_latency.get(model).cloned())
+            .or_else(|| self.learned.as_ref()?.get(model)?.latency())
+            .unwrap_or_else(|| LatencyProfile::from_model(model));
+        if self.time_scale == 1.0 {
+            profile
+        } else {
+            profile.scaled(self.time_scale)
+        }
+    }
+
+    /// Shortest mean time between tokens this pipeline streams with, among
+    /// `models` and every model with a pinned or learned profile, as timed by
+    /// the latency model when one is set; `None` without delays
+    pub fn shortest_tbt<'a>(
+        &'a self,
+        models: impl IntoIterator<Item = &'a str>,
+    ) -> Option<Duration> {
+        if self.delays_disabled {
+            return None;
+        }
+        let learned = self
+            .learned
+            .iter()
+            .flat_map(|learned| learned.models.keys());
+        models
+            .into_iter()
+            .chain(self.model_latency.keys().map(String::as_str))
+            .chain(learned.map(String::as_str))
+            .map(|model| self.mean_tbt(model))
+            .filter(|tbt| !tbt.is_zero())
+            .min()
+    }
+
+    /// Mean time between tokens for `model`; a latency model's is averaged
+    /// over the first gaps of a response
+    fn mean_tbt(&self, model: &str) -> Duration {
+        let Some(custom) = &self.latency_model else {
+            return Duration::from_millis(self.latency_for(model).tbt_mean_ms);
+        };
+        let total: Duration = (1..=TBT_SAMPLES)
+            .map(|position| {
+                custom.sample_tbt(&LatencyContext {
+                    model: model.to_string(),
+                    position,
+                    ..LatencyContext::default()
+                })
+            })
+            .sum();
+        (total / TBT_SAMPLES).mul_f64(self.time_scale.max(0.0))
+    }
+
+    /// Tool calls to answer a generated chat completion with, if any
+    pub fn simulate_tool_calls(&self, request: &ChatCompletionRequest) -> Vec<SimToolCall> {
+        self.tool_calls.simulate(request)
+    }
+
+    /// Run the configured generator. The text ends before the first of the
+    /// request's `stop` sequences in it; in JSON mode (`response_format`
+    /// `json_object`) it is then wrapped in a JSON object.
+    pub fn generate(&self, request: &ChatCompletionRequest) -> String {
+        self.generate_with_stop(request).0
+    }
+
+    /// Like `generate`, also returning the stop sequence the text ended at
+    pub fn generate_with_stop(&self, request: &ChatCompletionRequest) -> (String, Option<String>) {
+        let request = self.sampling_request(request);
+        let request = request.as_ref();
+        let sized = self.sized_generator(request);
+        let generator = sized.as_deref().unwrap_or(self.generator.as_ref());
+        let stops = request.stop_sequences();
+        let (text, stop) = if stops.is_empty() {
+            (generator.generate(request), None)
+        } else {
+            let pieces = self.emit_stop(request, generator, generator.generate_pieces(request));
+            let text: String = pieces.collect();
+            match find_stop(&text, &stops) {
+                Some((at, stop)) => (text[..at].to_string(), Some(stop.to_string())),
+                None => (text, None),
+            }
+        };
+        if json_mode(request) {
+            (serde_json::json!({ "response": text }).to_string(), stop)
+        } else {
+            (text, stop)
+        }
+    }
+
+    /// Run the configured generator incrementally, ended and wrapped like
+    /// `generate`
+    pub fn generate_pieces(&self, request: &ChatCompletionRequest) -> TextPieces {
+        let request = self.sampling_request(request);
+        let request = request.as_ref();
+        let sized = self.sized_generator(request);
+        let generator = sized.as_deref().unwrap_or(self.generator.as_ref());
+        let mut pieces = generator.generate_pieces(request);
+        let stops = request.stop_sequences();
+        if !stops.is_empty() {
+            pieces = cut_at_stop(
+                self.emit_stop(request, generator, pieces),
+                stops.into_iter().map(String::from).collect(),
+            );
+        }
+        if !json_mode(request) {
+            return pieces;
+        }
+        let escaped = pieces.map(|piece| {
+            let quoted = serde_json::Value::from(piece).to_string();
+            quoted[1..quoted.len() - 1].to_string()
+        });
+        Box::new(
+            std::iter::once(r#"{"response":""#.to_string())
+                .chain(escaped)
+                .chain(std::iter::once(r#""}"#.to_string())),
+        )
+    }
+
+    /// With the stop hit rate, have the model emit one of the request's stop
+    /// sequences somewhere within the generator's target length
+    fn emit_stop(
+        &self,
+        request: &ChatCompletionRequest,
+        generator: &dyn ResponseGenerator,
+        pieces: TextPieces,
+    ) -> TextPieces {
+        let mut rng = request_rng(request);
+        if !rng.random_bool(self.stop_hit_rate.clamp(0.0, 1.0)) {
+            return pieces;
+        }
+        let Some(stop) = request
+            .stop_sequences()
+            .choose(&mut rng)
+            .map(|s| s.to_string())
+        else {
+            return pieces;
+        };
+        // Words and the spaces between them, at about 0.75 words per token
+        let tokens = generator.target_tokens().unwrap_or(100);
+        let span = (tokens * 3 / 2).max(1);
+        crate::stop::emit_stop(pieces, rng.random_range(1..=span), stop)
+    }
+
+    /// The request generation sees: with temperature effects on, an
+    /// unseeded request at temperature 0 gets a seed from the model's system
+    /// fingerprint
+    fn sampling_request<'a>(
+        &self,
+        request: &'a ChatCompletionRequest,
+    ) -> Cow<'a, ChatCompletionRequest> {
+        if !self.temperature_effects || request.seed.is_some() || request.temperature != Some(0.0) {
+            return Cow::Borrowed(request);
+        }
+        let mut hasher = DefaultHasher::new();
+        self.fingerprints
+            .for_model(&request.model)
+            .hash(&mut hasher);
+        let mut seeded = request.clone();
+        seeded.seed = Some(hasher.finish() as i64);
+        Cow::Owned(seeded)
+    }
+
+    /// The generator resized to a learned response length for the model
+    /// and, with temperature effects on, spread by the request's
+    /// temperature. Lengths are drawn like the generator's own randomness,
+    /// so seeded requests repeat.
+    fn sized_generator(
+        &self,
+        request: &ChatCompletionRequest,
+    ) -> Option<Box<dyn ResponseGenerator>> {
+        let mut rng = request_rng(request);
+        let learned = self
+            .learned
+            .as_ref()
+            .and_then(|learned| learned.get(&request.model))
+            .and_then(|profile| profile.sample_completion_tokens(&mut rng));
+        let spread = self.length_spread(request);
+        if learned.is_none() && spread == 0.0 {
+            return None;
+        }
+        let tokens = learned.or_else(|| self.generator.target_tokens())?;
+        self.generator
+            .with_target_tokens(spread_length(tokens, spread, &mut rng))
+    }
+
+    /// Standard deviation of the log response length: none at temperature
+    /// 0, growing with `temperature * top_p` (both at their API defaults of
+    /// 1 when unset)
+    fn length_spread(&self, request: &ChatCompletionRequest) -> f64 {
+        if !self.temperature_effects {
+            return 0.0;
+        }
+        let temperature = request.temperature.unwrap_or(1.0).clamp(0.0, 2.0) as f64;
+        let top_p = request.top_p.unwrap_or(1.0).clamp(0.0, 1.0) as f64;
+        LENGTH_SPREAD_PER_TEMPERATURE * temperature * top_p
+    }
+
+    /// Cut generated text to `max_output_tokens`, or at a random point with
+    /// the configured truncation rate. Returns the text and whether it was
+    /// truncated.
+    pub fn limit_output(&self, text: String, max_output_tokens: Option<u32>) -> (String, bool) {
+        if let Some(cut) =
+            max_output_tokens.and_then(|max| self.tokenizer.truncate(&text, max as usize))
+        {
+            return (cut, true);
+        }
+        let mut rng = rand::rng();
+        if self.truncation_rate > 0.0 && rng.random_bool(self.truncation_rate.min(1.0)) {
+            let tokens = self.tokenizer.count(&text);
+            if tokens > 1 {
+                let keep = rng.random_range(1..tokens);
+                if let Some(cut) = self.tokenizer.truncate(&text, keep) {
+                    return (cut, true);
+                }
+            }
+        }
+        (text, false)
+    }
+
+    /// The model's refusal message, when this request draws one at the
+    /// configured refusal rate
+    pub fn refusal(&self) -> Option<&'static str> {
+        (self.refusal_rate > 0.0 && rand::rng().random_bool(self.refusal_rate.min(1.0)))
+            .then_some(REFUSAL_MESSAGE)
+    }
+
+    /// Citation annotations for generated `text` at the configured rate
+    pub fn citations(&self, text: &str) -> Vec<serde_json::Value> {
+        citations::cite(text, self.citation_rate, &mut rand::rng())
+    }
+
+    /// Start a request: records it in stats and resolves its latency profile
+    /// and system fingerprint
+    pub fn begin(&self, model: &str, streaming: bool, endpoint: EndpointType) -> PipelineRequest {
+        self.stats.record_request_start(model, streaming, endpoint);
+        let drill = self
+            .drills
+            .as_ref()
+            .map_or(DrillEffects::NONE, |d| d.effects(self.region.as_deref()));
+        let mut latency = self.latency_for(model);
+        if drill.latency_factor != 1.0 {
+            latency = latency.scaled(drill.latency_factor);
+        }
+        let latency_model: Arc<dyn LatencyModel> = match &self.latency_model {
+            Some(custom) => {
+                let factor = self.time_scale * drill.latency_factor;
+                if factor == 1.0 {
+                    custom.clone()
+                } else {
+                    Arc::new(ScaledLatency {
+                        inner: custom.clone(),
+                        factor,
+                    })
+                }
+            }
+            None => Arc::new(latency.clone()),
+        };
+        let latency_context = LatencyContext {
+            model: model.to_string(),
+            concurrency: self
+                .stats
+                .active_requests
+                .load(std::sync::atomic::Ordering::Relaxed),
+            ..LatencyContext::default()
+        };
+        let recording = self
+            .decisions
+            .as_ref()
+            .filter(|log| log.is_recording())
+            .map(|log| {
+                log.record(
+                    model,
+                    endpoint,
+                    streaming,
+                    latency.clone(),
+                    self.generator.name(),
+                )
+            });
+        let sleeper: Arc<dyn Sleeper> = match &recording {
+            Some(recording) => Arc::new(RecordingSleeper {
+                inner: self.sleeper.clone(),
+                recording: recording.clone(),
+            }),
+            None => self.sleeper.clone(),
+        };
+        let sleeper: Arc<dyn Sleeper> = if streaming {
+            Arc::new(PacingSleeper::new(sleeper, self.stats.clone()))
+        } else {
+            sleeper
+        };
+        PipelineRequest {
+            stats: self.stats.clone(),
+            errors: self.errors.clone(),
+            latency,
+            latency_model,
+            latency_context,
+            drill,
+            system_fingerprint: self.fingerprints.for_model(model),
+            shedder: self.shedder.clone(),
+            endpoint,
+            sleeper,
+            started: clock::now(),
+            recording,
+            time_scale: self.time_scale,
+            forced_error: None,
+            max_stream_duration: self.max_stream_duration,
+            delays_disabled: self.delays_disabled,
+        }
+    }
+}
+
+/// One in-flight request. Every request that was begun must end with
+/// exactly one of `fail`, `finish`, or a `finish_callback` being invoked so
+/// the active-request gauge stays balanced.
+pub struct PipelineRequest {
+    stats: SharedStats,
+    errors: ErrorConfig,
+    latency: LatencyProfile,
+    latency_model: Arc<dyn LatencyModel>,
+    latency_context: LatencyContext,
+    drill: DrillEffects,
+    system_fingerprint: String,
+    shedder: Option<Arc<LoadShedder>>,
+    endpoint: EndpointType,
+    sleeper: Arc<dyn Sleeper>,
+    started: Instant,
+    recording: Option<Arc<Recording>>,
+    /// `[simulation] time_scale`, applied to a key's latency profile
+    time_scale: f64,
+    /// Error the request's `llmsim` extension asked for
+    forced_error: Option<SimulatedError>,
+    /// `[stream] max_duration_secs`
+    max_stream_duration: Option<Duration>,
+    /// `[simulation] disable_delays`: latency overrides are ignored
+    delays_disabled: bool,
+}
+
+impl PipelineRequest {
+    /// Apply the request's own `llmsim` overrides: a forced error is
+    /// returned by `inject_error`, and a key's profile or overridden delays
+    /// replace the latency model with a fixed profile, unless delays are
+    /// disabled
+    pub fn with_overrides(mut self, overrides: &RequestOverrides) -> Self {
+        if let Some(forced) = overrides.force_error {
+            let timeout = Duration::from_millis(self.errors.timeout_after_ms);
+            self.forced_error = Some(forced.error(timeout));
+        }
+        if self.delays_disabled {
+            return self;
+        }
+        if let Some(profile) = overrides.profile.as_deref().and_then(LatencyProfile::named) {
+            self.latency = profile.scaled(self.time_scale);
+            self.latency_model = Arc::new(self.latency.clone());
+        }
+        if overrides.has_latency() {
+            let (ttft_mean, ttft_stddev) = overrides.ttft_ms.map_or(
+                (self.latency.ttft_mean_ms, self.latency.ttft_stddev_ms),
+                |ms| (ms, 0),
+            );
+            let (tbt_mean, tbt_stddev) = overrides.tbt_ms.map_or(
+                (self.latency.tbt_mean_ms, self.latency.tbt_stddev_ms),
+                |ms| (ms, 0),
+            );
+            self.latency = LatencyProfile::new(ttft_mean, ttft_stddev, tbt_mean, tbt_stddev);
+            self.latency_model = Arc::new(self.latency.clone());
+        }
+        if overrides.profile.is_some() || overrides.has_latency() {
+            if let Some(recording) = &self.recording {
+                recording.update(|decision| decision.latency = self.latency.clone());
+            }
+        }
+        self
+    }
+
+    pub fn latency(&self) -> &LatencyProfile {
+        &self.latency
+    }
+
+    /// Latency for this request's stream, bound to its model, load, and
+    /// `prompt_tokens`
+    pub fn request_latency(&self, prompt_tokens: u32) -> RequestLatency {
+        let context = LatencyContext {
+            prompt_tokens,
+            ..self.latency_context.clone()
+        };
+        RequestLatency::new(self.latency_model.clone(), context)
+    }
+
+    /// Fingerprint to report on every body and chunk of this request
+    pub fn system_fingerprint(&self) -> &str {
+        &self.system_fingerprint
+    }
+
+    pub fn endpoint(&self) -> EndpointType {
+        self.endpoint
+    }
+
+    pub fn started(&self) -> Instant {
+        self.started
+    }
+
+    /// Sleeper for this request's streams; notes each delay in its decision
+    /// when decisions are being logged
+    pub fn sleeper(&self) -> &Arc<dyn Sleeper> {
+        &self.sleeper
+    }
+
+    /// Time budget for this request's generated stream, counting the
+    /// stream in stats when it runs out
+    pub fn stream_watchdog(&self) -> StreamWatchdog {
+        let Some(limit) = self.max_stream_duration else {
+            return StreamWatchdog::unlimited();
+        };
+        let stats = self.stats.clone();
+        StreamWatchdog::new(limit).on_trip(move || stats.record_stream_capped())
+    }
+
+    /// Name the generator that wrote the response when it is not the
+    /// pipeline's own, e.g. `"script"`
+    pub fn set_generator(&self, name: &str) {
+        if let Some(recording) = &self.recording {
+            recording.update(|decision| decision.generator = name.to_string());
+        }
+    }
+
+    /// Note the request's prompt text in its decision, when decisions are
+    /// being logged; only the end of it is kept
+    pub fn set_prompt(&self, prompt: &str) {
+        if let Some(recording) = &self.recording {
+            recording.update(|decision| decision.prompt = Some(prompt_excerpt(prompt)));
+        }
+    }
+
+    /// Note the end user the client named in the request, when decisions are
+    /// being logged
+    pub fn set_user(&self, user: &str) {
+        if let Some(recording) = &self.recording {
+            recording.update(|decision| decision.user = Some(user.to_string()));
+        }
+    }
+
+    /// Fail the request with the error its overrides force, or if a drill
+    /// takes its listener down, shed it if over capacity, otherwise roll for
+    /// an injected error; any of these is recorded as a failed request
+    pub fn inject_error(&self) -> Option<SimulatedError> {
+        if let Some(error) = &self.forced_error {
+            return Some(self.fail_with(ErrorCause::Requested, error.clone()));
+        }
+        if self.drill.down {
+            return Some(self.fail_with(ErrorCause::Drill, SimulatedError::ServiceUnavailable));
+        }
+        if self.drill.error_rate > 0.0 && rand::rng().random_bool(self.drill.error_rate) {
+            return Some(self.fail_with(ErrorCause::Drill, SimulatedError::ServerError));
+        }
+        let storm = self.drill.rate_limit_rate;
+        if storm > 0.0 && rand::rng().random_bool(storm.min(1.0)) {
+            let error = SimulatedError::RateLimit {
+                retry_after_seconds: self.drill.retry_after_seconds,
+            };
+            return Some(self.fail_with(ErrorCause::Drill, error));
+        }
+        if let Some(shedder) = self.shedder.as_ref().filter(|s| !s.try_admit()) {
+            self.stats.record_shed();
+            let error = SimulatedError::Overloaded {
+                retry_after_seconds: shedder.retry_after_seconds(),
+            };
+            return Some(self.fail_with(ErrorCause::LoadShedding, error));
+        }
+        let error = ErrorInjector::new(self.errors.clone()).maybe_inject()?;
+        tracing::warn!("Injecting error: {:?}", error);
+        Some(self.fail_with(ErrorCause::Injected, error))
+    }
+
+    /// Record the request as failed with the given status
+    pub fn fail(&self, status_code: u16) {
+        self.stats.record_error(status_code);
+        if let Some(recording) = &self.recording {
+            recording.update(|decision| {
+                decision.outcome = Outcome::Failed {
+                    status: status_code,
+                }
+            });
+        }
+    }
+
+    /// Fail the request with a simulator-made error, noting why
+    pub fn fail_with(&self, cause: ErrorCause, error: SimulatedError) -> SimulatedError {
+        self.fail(error.status_code());
+        if let Some(recording) = &self.recording {
+            recording.update(|decision| decision.error = Some((cause, error.clone())));
+        }
+        error
+    }
+
+    /// Sleep for the time-to-first-token of a non-streaming response
+    pub async fn simulate_generation(&self, prompt_tokens: u32) {
+        let delay = self.request_latency(prompt_tokens).ttft();
+        if !delay.is_zero() {
+            self.sleeper.sleep(delay).await;
+        }
+    }
+
+    /// Record successful completion
+    pub fn finish(&self, input_tokens: u32, output_tokens: u32) {
+        self.stats
+            .record_request_end(clock::elapsed(self.started), input_tokens, output_tokens);
+        complete(self.recording.as_deref(), input_tokens, output_tokens);
+    }
+
+    /// Completion hook for streams, which finish after the handler returns
+    pub fn finish_callback(
+        &self,
+        input_tokens: u32,
+        output_tokens: u32,
+    ) -> impl FnOnce() + Send + 'static {
+        let finish = self.usage_callback();
+        move || finish(input_tokens, output_tokens)
+    }
+
+    /// Wrap a response stream so that dropping it before it ends (the client
+    /// went away) records the request as abandoned
+    pub fn guard_stream<S: Stream + Unpin>(&self, stream: S) -> GuardedStream<S> {
+        GuardedStream {
+            inner: stream,
+            stats: Some(self.stats.clone()),
+        }
+    }
+
+    /// Completion hook for streams whose usage is only known once they end;
+    /// called with `(input_tokens, output_tokens)`
+    pub fn usage_callback(&self) -> impl FnOnce(u32, u32) + Send + 'static {
+        let stats = self.stats.clone();
+        let started = self.started;
+        let recording = self.recording.clone();
+        move |input_tokens, output_tokens| {
+            stats.record_request_end(clock::elapsed(started), input_tokens, output_tokens);
+            complete(recording.as_deref(), input_tokens, output_tokens);
+        }
+    }
+}
+
+/// Whether the request asks for a JSON object answer
+pub(crate) fn json_mode(request: &ChatCompletionRequest) -> bool {
+    request
+        .response_format
+        .as_ref()
+        .is_some_and(|format| format.format_type == "json_object")
+}
+
+/// Note a successful completion in a request's decision
+fn complete(recording: Option<&Recording>, input_tokens: u32, output_tokens: u32) {
+    if let Some(recording) = recording {
+        recording.update(|decision| {
+            decision.outcome = Outcome::Completed {
+                input_tokens,
+                output_tokens,
+            }
+        });
+    }
+}
+
+/// A response stream that reports abandonment if dropped before its end.
+/// See `PipelineRequest::guard_stream`.
+pub struct GuardedStream<S> {
+    inner: S,
+    /// Cleared once the inner stream has ended
+    stats: Option<SharedStats>,
+}
+
+impl<S: Stream + Unpin> Stream for GuardedStream<S> {
+    type Item = S::Item;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<S::Item>> {
+        let poll = Pin::new(&mut self.inner).poll_next(cx);
+        if let Poll::Ready(None) = poll {
+            self.stats = None;
+        }
+        poll
+    }
+}
+
+impl<S> Drop for GuardedStream<S> {
+    fn drop(&mut self) {
+        if let Some(stats) = self.stats.take() {
+            tracing::debug!("Client disconnected mid-stream");
+            stats.record_stream_abandoned();
+        }
+    }
+}
+
+/// Counts and cuts simulated text in tokens
+pub trait TextTokenizer: Send + Sync {
+    fn count(&self, text: &str) -> usize;
+    /// The first `max_tokens` tokens of `text`; `None` when it already fits
+    fn truncate(&self, text: &str, max_tokens: usize) -> Option<String>;
+}
+
+/// `count_text_tokens` and `truncate_to_tokens`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DefaultTokenizer;
+
+impl TextTokenizer for DefaultTokenizer {
+    fn count(&self, text: &str) -> usize {
+        count_text_tokens(text)
+    }
+
+    fn truncate(&self, text: &str, max_tokens: usize) -> Option<String> {
+        truncate_to_tokens(text, max_tokens)
+    }
+}
+
+/// Token count for text, falling back to a whitespace word count when no
+/// tokenizer is available
+pub fn count_text_tokens(text: &str) -> usize {
+    #[cfg(feature = "tokens")]
+    {
+        crate::tokens::Tokenizer::default().count(text)
+    }
+    #[cfg(not(feature = "tokens"))]
+    {
+        text.split_whitespace().count()
+    }
+}
+
+/// The first `max_tokens` tokens of `text`, counted as in
+/// `count_text_tokens`; `None` when the text already fits
+pub fn truncate_to_tokens(text: &str, max_tokens: usize) -> Option<String> {
+    #[cfg(feature = "tokens")]
+    {
+        crate::tokens::Tokenizer::default().truncate(text, max_tokens)
+    }
+    #[cfg(not(feature = "tokens"))]
+    {
+        let words: Vec<&str> = text.split_whitespace().collect();
+        if words.len() <= max_tokens {
+            return None;
+        }
+        Some(words[..max_tokens].join(" "))
+    }
+}
+
+/// `tokens` scaled by a log-normal factor with standard deviation `spread`
+fn spread_length(tokens: usize, spread: f64, rng: &mut impl rand::Rng) -> usize {
+    if spread == 0.0 {
+        return tokens;
+    }
+    let u1: f64 = rng.random_range(f64::MIN_POSITIVE..1.0);
+    let u2: f64 = rng.random_range(0.0..1.0);
+    let z = (-2.0 * u1.ln()).sqrt() * (std::f64::consts::TAU * u2).cos();
+    ((tokens as f64 * (spread * z).exp()).round() as usize).max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator::FixedGenerator;
+    use crate::openai::Message;
+    use crate::stats::new_shared_stats;
+
+    fn request(model: &str) -> ChatCompletionRequest {
+        serde_json::from_value(serde_json::json!({
+            "model": model,
+            "messages": [Message::user("hi")],
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_latency_override() {
+        let pipeline = RequestPipeline::new(new_shared_stats());
+        assert_eq!(
+            pipeline.latency_for("claude-haiku-4.5").ttft_mean_ms,
+            LatencyProfile::claude_haiku().ttft_mean_ms
+        );
+
+        let pipeline = pipeline.with_latency(LatencyProfile::instant());
+        assert_eq!(pipeline.latency_for("claude-haiku-4.5").ttft_mean_ms, 0);
+    }
+
+    #[test]
+    fn test_model_latency_wins_over_the_registry() {
+        let pipeline = RequestPipeline::new(new_shared_stats())
+            .with_model_latency([("gpt-5-nano".to_string(), LatencyProfile::fast())].into());
+        assert_eq!(pipeline.latency_for("gpt-5-nano"), LatencyProfile::fast());
+        assert_eq!(pipeline.latency_for("gpt-5"), LatencyProfile::gpt5());
+
+        let pipeline = pipeline.with_latency(LatencyProfile::instant());
+        assert_eq!(pipeline.latency_for("gpt-5-nano").ttft_mean_ms, 0);
+    }
+
+    #[test]
+    fn test_time_scale_applies_to_every_profile() {
+        let pipeline = RequestPipeline::new(new_shared_stats()).with_time_scale(0.5);
+        assert_eq!(
+            pipeline.latency_for("gpt-4").ttft_mean_ms,
+            LatencyProfile::gpt4().ttft_mean_ms / 2
+        );
+
+        let pipeline = pipeline.with_latency(LatencyProfile::new(100, 10, 20, 4));
+        assert_eq!(pipeline.latency_for("gpt-4").tbt_mean_ms, 10);
+    }
+
+    #[test]
+    fn test_shortest_tbt_covers_every_served_profile() {
+        let ms = |ms| Some(Duration::from_millis(ms));
+        let pipeline = RequestPipeline::new(new_shared_stats());
+        assert_eq!(
+            pipeline.shortest_tbt(["gpt-5"]),
+            ms(LatencyProfile::gpt5().tbt_mean_ms)
+        );
+
+        // Pinned profiles count even for models not listed
+        let pipeline = pipeline
+            .with_model_latency([("gpt-5-nano".to_string(), LatencyProfile::fast())].into());
+        assert_eq!(pipeline.shortest_tbt(["gpt-5"]), ms(1));
+
+        let model = LatencyProfile::new(100, 0, 6, 0);
+        let pipeline = pipeline
+            .with_latency_model(Arc::new(model))
+            .with_time_scale(0.5);
+        assert_eq!(pipeline.shortest_tbt(["gpt-5"]), ms(3));
+
+        assert_eq!(pipeline.without_delays().shortest_tbt(["gpt-5"]), None);
+    }
+
+    #[test]
+    fn test_generate_uses_configured_generator() {
+        let pipeline = RequestPipeline::new(new_shared_stats())
+            .with_generator(Arc::new(FixedGenerator::new("canned")));
+        assert_eq!(pipeline.generate(&request("gpt-5")), "canned");
+        let pieces: Vec<String> = pipeline.generate_pieces(&request("gpt-5")).collect();
+        assert_eq!(pieces, vec!["canned"]);
+    }
+
+    #[test]
+    fn test_json_mode_wraps_text() {
+        let pipeline = RequestPipeline::new(new_shared_stats())
+            .with_generator(Arc::new(FixedGenerator::new("say \"hi\"")));
+        let mut request = request("gpt-5");
+        request.response_format =
+            serde_json::from_value(serde_json::json!({"type": "json_object"})).unwrap();
+        let whole = pipeline.generate(&request);
+        let streamed: String = pipeline.generate_pieces(&request).collect();
+        assert_eq!(whole, streamed);
+        let value: serde_json::Value = serde_json::from_str(&whole).unwrap();
+        assert_eq!(value["response"], "say \"hi\"");
+    }
+
+    #[test]
+    fn test_refusal_rate() {
+        let pipeline = RequestPipeline::new(new_shared_stats());
+        assert!(pipeline.refusal().is_none());
+        let pipeline = pipeline.with_refusal_rate(1.0);
+        assert_eq!(pipeline.refusal(), Some(REFUSAL_MESSAGE));
+    }
+
+    #[test]
+    fn test_learned_profiles_replay() {
+        let mut learned = LearnedProfiles::default();
+        learned.record(
+            "gpt-4o",
+            &crate::learned::Observation {
+                ttft: Some(std::time::Duration::from_millis(321)),
+                tbt: Some(std::time::Duration::from_millis(7)),
+                completion_tokens: Some(12),
+            },
+        );
+        let pipeline = RequestPipeline::new(new_shared_stats())
+            .with_generator(Arc::from(create_generator("sequence", 100)))
+            .with_learned_profiles(Arc::new(learned));
+
+        assert_eq!(pipeline.latency_for("gpt-4o").ttft_mean_ms, 321);
+        assert_eq!(pipeline.latency_for("gpt-4o").tbt_mean_ms, 7);
+        assert_eq!(pipeline.generate(&request("gpt-4o")).split(' ').count(), 12);
+        // Models that were never observed keep the defaults
+        assert_eq!(
+            pipeline.latency_for("gpt-4").ttft_mean_ms,
+            LatencyProfile::gpt4().ttft_mean_ms
+        );
+        assert_eq!(pipeline.generate(&request("gpt-4")).split(' ').count(), 100);
+
+        // A fixed profile still applies to every model
+        let pipeline = pipeline.with_latency(LatencyProfile::instant());
+        assert_eq!(pipeline.latency_for("gpt-4o").ttft_mean_ms, 0);
+    }
+
+    #[test]
+    fn test_temperature_effects() {
+        let words = |pipeline: &RequestPipeline, request: &ChatCompletionRequest| {
+            pipeline.generate(request).split(' ').count()
+        };
+        let lengths = |pipeline: &RequestPipeline, temperature: f32| {
+            let mut request = request("gpt-5");
+            request.temperature = Some(temperature);
+            (0..50)
+                .map(|_| words(pipeline, &request))
+                .collect::<Vec<_>>()
+        };
+        let pipeline = RequestPipeline::new(new_shared_stats())
+            .with_generator(Arc::from(create_generator("sequence", 100)));
+        assert!(lengths(&pipeline, 2.0).iter().all(|n| *n == 100));
+
+        let pipeline = pipeline.with_temperature_effects(true);
+        assert!(lengths(&pipeline, 0.0).iter().all(|n| *n == 100));
+        let hot = lengths(&pipeline, 2.0);
+        assert!(hot.iter().any(|n| *n < 90) && hot.iter().any(|n| *n > 110));
+        // A narrow top_p narrows the spread to nothing
+        let mut request = request("gpt-5");
+        request.temperature = Some(2.0);
+        request.top_p = Some(0.0);
+        assert_eq!(words(&pipeline, &request), 100);
+
+        // Temperature 0 repeats the same text while the fingerprint holds
+        let pipeline = RequestPipeline::new(new_shared_stats()).with_temperature_effects(true);
+        let mut request = request.clone();
+        request.temperature = Some(0.0);
+        let first = pipeline.generate(&request);
+        assert_eq!(pipeline.generate(&request), first);
+        let rotated = pipeline.with_fingerprints(SystemFingerprints::fixed("fp_other"));
+        assert_ne!(rotated.generate(&request), first);
+    }
+
+    #[test]
+    fn test_stop_sequences() {
+        let mut request = request("gpt-5");
+        request.messages = vec![Message::user("one two END three")];
+        request.stop = Some(crate::openai::StopCondition::Multiple(vec![
+            "END".to_string(),
+            "three".to_string(),
+        ]));
+        let echo = RequestPipeline::new(new_shared_stats())
+            .with_generator(Arc::from(create_generator("echo", 100)));
+        assert_eq!(
+            echo.generate_with_stop(&request),
+            ("Echo: one two ".to_string(), Some("END".to_string()))
+        );
+        let streamed: String = echo.generate_pieces(&request).collect();
+        assert_eq!(streamed, "Echo: one two ");
+
+        // Generated text hits a stop sequence only at the configured rate
+        let lorem = RequestPipeline::new(new_shared_stats());
+        let (text, stop) = lorem.generate_with_stop(&request);
+        assert_eq!(stop, None);
+        assert!(text.split(' ').count() >= 75);
+        let lorem = lorem.with_stop_hit_rate(1.0);
+        for _ in 0..20 {
+            let (text, stop) = lorem.generate_with_stop(&request);
+            assert!(stop.is_some());
+            assert!(text.split(' ').count() <= 75, "{}", text);
+            let streamed: String = lorem.generate_pieces(&request).collect();
+            assert!(!streamed.contains("END"));
+        }
+    }
+
+    #[test]
+    fn test_request_lifecycle_stats() {
+        let stats = new_shared_stats();
+        let pipeline = RequestPipeline::new(stats.clone());
+
+        let req = pipeline.begin("gpt-5", false, EndpointType::ChatCompletions);
+        assert_eq!(stats.snapshot().active_requests, 1);
+        assert!(req.inject_error().is_none());
+        req.finish(10, 20);
+
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.active_requests, 0);
+        assert_eq!(snapshot.prompt_tokens, 10);
+        assert_eq!(snapshot.completion_tokens, 20);
+
+        let req = pipeline.begin("gpt-5", true, EndpointType::Responses);
+        let done = req.finish_callback(1, 2);
+        assert_eq!(stats.snapshot().active_requests, 1);
+        done();
+        assert_eq!(stats.snapshot().active_requests, 0);
+    }
+
+    #[tokio::test]
+    async fn test_guarded_stream_abandoned() {
+        use futures_util::StreamExt;
+
+        let stats = new_shared_stats();
+        let pipeline = RequestPipeline::new(stats.clone());
+
+        // Drained to the end: not abandoned
+        let req = pipeline.begin("gpt-5", true, EndpointType::ChatCompletions);
+        let done = req.finish_callback(1, 1);
+        let items: Vec<u32> = req
+            .guard_stream(futures_util::stream::iter([1, 2]))
+            .collect()
+            .await;
+        done();
+        assert_eq!(items, vec![1, 2]);
+        assert_eq!(stats.snapshot().abandoned_streams, 0);
+
+        // Dropped after the first item: abandoned
+        let req = pipeline.begin("gpt-5", true, EndpointType::ChatCompletions);
+        let mut stream = req.guard_stream(futures_util::stream::iter([1, 2]));
+        assert_eq!(stream.next().await, Some(1));
+        drop(stream);
+
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.abandoned_streams, 1);
+        assert_eq!(snapshot.active_requests, 0);
+        assert_eq!(snapshot.completed_requests, 1);
+    }
+
+    #[test]
+    fn test_injected_error_is_recorded() {
+        let stats = new_shared_stats();
+        let pipeline = RequestPipeline::new(stats.clone())
+            .with_errors(ErrorConfig::new().with_rate_limit_rate(1.0));
+
+        let req = pipeline.begin("gpt-5", false, EndpointType::ChatCompletions);
+        let error = req.inject_error().unwrap();
+        assert_eq!(error.status_code(), 429);
+
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.active_requests, 0);
+        assert_eq!(snapshot.rate_limit_errors, 1);
+    }
+
+    #[test]
+    fn test_load_shedding() {
+        let stats = new_shared_stats();
+        let pipeline = RequestPipeline::new(stats.clone())
+            .with_load_shedding(LoadShedder::new(1).with_retry_after(2));
+
+        let first = pipeline.begin("gpt-5", false, EndpointType::ChatCompletions);
+        assert!(first.inject_error().is_none());
+        first.finish(1, 1);
+
+        let second = pipeline.begin("gpt-5", false, EndpointType::ChatCompletions);
+        let error = second.inject_error().unwrap();
+        assert_eq!(error.status_code(), 503);
+        assert_eq!(error.retry_after(), Some(2));
+
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.shed_requests, 1);
+        assert_eq!(snapshot.server_errors, 1);
+        assert_eq!(snapshot.active_requests, 0);
+    }
+
+    #[test]
+    fn test_limit_output() {
+        let pipeline = RequestPipeline::new(new_shared_stats());
+        let text = "one two three four five six seven eight".to_string();
+
+        let (cut, truncated) = pipeline.limit_output(text.clone(), Some(3));
+        assert!(truncated);
+        assert_eq!(count_text_tokens(&cut), 3);
+        assert!(text.starts_with(&cut));
+
+        assert_eq!(
+            pipeline.limit_output(text.clone(), Some(100)),
+            (text.clone(), false)
+        );
+        assert_eq!(
+            pipeline.limit_output(text.clone(), None),
+            (text.clone(), false)
+        );
+
+        let pipeline = pipeline.with_truncation_rate(1.0);
+        let (cut, truncated) = pipeline.limit_output(text.clone(), None);
+        assert!(truncated);
+        assert!(count_text_tokens(&cut) < count_text_tokens(&text));
+    }
+
+    #[test]
+    fn test_format_adapters() {
+        let error = SimulatedError::RateLimit {
+            retry_after_seconds: 5,
+        };
+        let openai = serde_json::to_value(OpenAIFormat::error_body(&error)).unwrap();
+        assert_eq!(openai["error"]["type"], "rate_limit_error");
+
+        let responses = serde_json::to_value(ResponsesFormat::error_body(&error)).unwrap();
+        assert_eq!(responses["error"]["type"], "rate_limit_error");
+
+        let anthropic = serde_json::to_value(AnthropicFormat::error_body(&error)).unwrap();
+        assert_eq!(anthropic["type"], "error");
+        assert_eq!(anthropic["error"]["type"], "rate_limit_error");
+    }
+
+    #[test]
+    fn test_count_text_tokens() {
+        assert!(count_text_tokens("hello world") >= 2);
+        assert_eq!(count_text_tokens(""), 0);
+    }
+}
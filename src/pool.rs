@@ -0,0 +1,401 @@
+// Response Pool Module
+// Chat completions generated, tokenized and serialized once at startup and
+// served round-robin, so a benchmark of a gateway measures the gateway and
+// not the simulator's generator or tokenizer.
+//
+// Decision: each pooled response keeps its non-streaming body and every SSE
+// frame as a template: the bytes serialized once, with holes where the
+// per-request values go (id, created, model, system fingerprint, prompt and
+// total tokens). Serving a request splices those values in, which is a few
+// copies per frame instead of generating, counting and serializing. Frames
+// that are not separated by a delay are sent as one write, so with the
+// instant profile a whole stream leaves in a single body chunk.
+
+use crate::generator::split_pieces;
+use crate::ids::{new_id, unix_timestamp, IdKind};
+use crate::latency::RequestLatency;
+use crate::openai::{
+    ChatCompletionChunk, ChatCompletionRequest, ChatCompletionResponse, Message, Usage,
+};
+use crate::pipeline::RequestPipeline;
+use crate::sleep::Sleeper;
+use async_stream::stream;
+use futures_core::Stream;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+// Stand-ins serialized where the per-request values go, then cut out
+const ID: &str = "@@llmsim-pool-id@@";
+const MODEL: &str = "@@llmsim-pool-model@@";
+const FINGERPRINT: &str = "@@llmsim-pool-fingerprint@@";
+const CREATED: i64 = 8_271_828_182_845_904;
+const PROMPT_TOKENS: u32 = 4_294_967_291;
+const TOTAL_TOKENS: u32 = 4_294_967_293;
+
+/// Last SSE frame of every stream
+const DONE: &str = "data: [DONE]\n\n";
+
+/// A per-request value spliced into a template
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Hole {
+    Id,
+    Model,
+    Fingerprint,
+    Created,
+    PromptTokens,
+    TotalTokens,
+}
+
+impl Hole {
+    const ALL: [Hole; 6] = [
+        Hole::Id,
+        Hole::Model,
+        Hole::Fingerprint,
+        Hole::Created,
+        Hole::PromptTokens,
+        Hole::TotalTokens,
+    ];
+
+    fn stand_in(self) -> String {
+        match self {
+            Hole::Id => ID.to_string(),
+            Hole::Model => MODEL.to_string(),
+            Hole::Fingerprint => FINGERPRINT.to_string(),
+            Hole::Created => CREATED.to_string(),
+            Hole::PromptTokens => PROMPT_TOKENS.to_string(),
+            Hole::TotalTokens => TOTAL_TOKENS.to_string(),
+        }
+    }
+}
+
+/// Serialized text with holes
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Template {
+    /// Text before each hole, then the text after the last one
+    texts: Vec<String>,
+    holes: Vec<Hole>,
+}
+
+impl Template {
+    /// Cut the stand-ins out of serialized text
+    fn parse(mut rest: &str) -> Self {
+        let stand_ins: Vec<(Hole, String)> = Hole::ALL
+            .iter()
+            .map(|hole| (*hole, hole.stand_in()))
+            .collect();
+        let mut texts = Vec::new();
+        let mut holes = Vec::new();
+        while let Some((at, hole, len)) = stand_ins
+            .iter()
+            .filter_map(|(hole, stand_in)| {
+                Some((rest.find(stand_in.as_str())?, *hole, stand_in.len()))
+            })
+            .min_by_key(|(at, _, _)| *at)
+        {
+            texts.push(rest[..at].to_string());
+            holes.push(hole);
+            rest = &rest[at + len..];
+        }
+        texts.push(rest.to_string());
+        Self { texts, holes }
+    }
+
+    /// Append the text with `fill`'s values in the holes
+    fn render_into(&self, out: &mut String, fill: &PoolFill, completion_tokens: u32) {
+        for (text, hole) in self.texts.iter().zip(&self.holes) {
+            out.push_str(text);
+            match hole {
+                Hole::Id => out.push_str(&fill.id),
+                Hole::Model => out.push_str(&fill.model),
+                Hole::Fingerprint => out.push_str(&fill.system_fingerprint),
+                Hole::Created => out.push_str(&fill.created.to_string()),
+                Hole::PromptTokens => out.push_str(&fill.prompt_tokens.to_string()),
+                Hole::TotalTokens => {
+                    out.push_str(&(fill.prompt_tokens + completion_tokens).to_string())
+                }
+            }
+        }
+        if let Some(last) = self.texts.last() {
+            out.push_str(last);
+        }
+    }
+}
+
+/// The per-request values of a pooled response
+#[derive(Debug, Clone)]
+pub struct PoolFill {
+    id: String,
+    created: i64,
+    /// JSON-escaped, without quotes
+    model: String,
+    /// JSON-escaped, without quotes
+    system_fingerprint: String,
+    prompt_tokens: u32,
+}
+
+impl PoolFill {
+    /// Values for a new response: a fresh id, the current time
+    pub fn new(model: &str, system_fingerprint: &str, prompt_tokens: u32) -> Self {
+        Self {
+            id: new_id(IdKind::ChatCompletion),
+            created: unix_timestamp(),
+            model: json_escaped(model),
+            system_fingerprint: json_escaped(system_fingerprint),
+            prompt_tokens,
+        }
+    }
+}
+
+/// `value` as inside a JSON string
+fn json_escaped(value: &str) -> String {
+    let quoted = serde_json::Value::from(value).to_string();
+    quoted[1..quoted.len() - 1].to_string()
+}
+
+/// One pre-generated chat completion
+#[derive(Debug)]
+pub struct PooledResponse {
+    completion_tokens: u32,
+    /// Non-streaming `chat.completion` body
+    body: Template,
+    /// Streamed chunks: the role, one per piece, then the finish with usage
+    frames: Vec<Template>,
+}
+
+impl PooledResponse {
+    /// Serialize a response with `content` of `completion_tokens` tokens
+    pub fn new(content: String, completion_tokens: u32) -> Self {
+        let usage = Usage {
+            prompt_tokens: PROMPT_TOKENS,
+            completion_tokens,
+            total_tokens: TOTAL_TOKENS,
+        };
+        let base = ChatCompletionChunk {
+            system_fingerprint: Some(FINGERPRINT.to_string()),
+            ..ChatCompletionChunk::new(ID.to_string(), MODEL.to_string(), CREATED)
+        };
+        let mut chunks = vec![base.clone().with_role()];
+        chunks.extend(
+            split_pieces(&content)
+                .into_iter()
+                .map(|piece| base.clone().with_content(piece)),
+        );
+        chunks.push(
+            base.with_finish("stop".to_string())
+                .with_usage(usage.clone()),
+        );
+        let frames = chunks
+            .iter()
+            .map(|chunk| Template::parse(&crate::stream::format_sse(chunk)))
+            .collect();
+
+        let mut body = ChatCompletionResponse::new(MODEL.to_string(), content, usage);
+        body.id = ID.to_string();
+        body.created = CREATED;
+        body.system_fingerprint = Some(FINGERPRINT.to_string());
+        let body = Template::parse(&serde_json::to_string(&body).unwrap_or_default());
+
+        Self {
+            completion_tokens,
+            body,
+            frames,
+        }
+    }
+
+    pub fn completion_tokens(&self) -> u32 {
+        self.completion_tokens
+    }
+
+    /// The non-streaming JSON body
+    pub fn body(&self, fill: &PoolFill) -> String {
+        let mut out = String::new();
+        self.body
+            .render_into(&mut out, fill, self.completion_tokens);
+        out
+    }
+
+    /// The SSE stream, timed by `latency` like a generated one: the time to
+    /// first token before the role chunk and a gap before each piece.
+    /// `on_complete` runs once the final frame is out.
+    pub fn stream<F>(
+        self: Arc<Self>,
+        fill: PoolFill,
+        mut latency: RequestLatency,
+        sleeper: Arc<dyn Sleeper>,
+        on_complete: F,
+    ) -> Pin<Box<dyn Stream<Item = String> + Send>>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        Box::pin(stream! {
+            let last = self.frames.len() - 1;
+            let mut pending = String::new();
+            for (index, frame) in self.frames.iter().enumerate() {
+                let delay = match index {
+                    0 => latency.ttft(),
+                    _ if index < last => latency.tbt(),
+                    _ => std::time::Duration::ZERO,
+                };
+                if !delay.is_zero() {
+                    if !pending.is_empty() {
+                        yield std::mem::take(&mut pending);
+                    }
+                    sleeper.sleep(delay).await;
+                }
+                frame.render_into(&mut pending, &fill, self.completion_tokens);
+            }
+            pending.push_str(DONE);
+            yield pending;
+            on_complete();
+        })
+    }
+}
+
+/// Pre-generated responses, handed out in turn
+#[derive(Debug)]
+pub struct ResponsePool {
+    responses: Vec<Arc<PooledResponse>>,
+    next: AtomicUsize,
+}
+
+impl ResponsePool {
+    /// A pool of `size` (at least one) responses from the pipeline's
+    /// generator and tokenizer. Each is generated for a seeded request, so
+    /// the same configuration builds the same pool.
+    pub fn generate(pipeline: &RequestPipeline, size: usize) -> Self {
+        let responses = (0..size.max(1) as i64)
+            .map(|seed| {
+                let request = ChatCompletionRequest {
+                    seed: Some(seed),
+                    ..pool_request()
+                };
+                let content = pipeline.generate(&request);
+                let tokens = pipeline.count_tokens(&content) as u32;
+                Arc::new(PooledResponse::new(content, tokens))
+            })
+            .collect();
+        Self {
+            responses,
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    /// The next response in turn
+    pub fn next(&self) -> Arc<PooledResponse> {
+        let index = self.next.fetch_add(1, Ordering::Relaxed) % self.responses.len();
+        self.responses[index].clone()
+    }
+
+    pub fn len(&self) -> usize {
+        self.responses.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.responses.is_empty()
+    }
+}
+
+/// The request pooled responses are generated for
+fn pool_request() -> ChatCompletionRequest {
+    serde_json::from_value(serde_json::json!({
+        "model": "llmsim-pool",
+        "messages": [Message::user("Tell me something.")],
+    }))
+    .expect("pool request is valid")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::latency::LatencyProfile;
+    use crate::sleep::default_sleeper;
+    use crate::stats::new_shared_stats;
+    use futures_util::StreamExt;
+    use serde_json::Value;
+    use std::sync::atomic::AtomicBool;
+
+    fn fill() -> PoolFill {
+        PoolFill::new("gpt-\"5\"", "fp_test", 12)
+    }
+
+    #[test]
+    fn test_template_holes() {
+        let template = Template::parse(&format!("a{}b{}c{}", ID, CREATED, MODEL));
+        assert_eq!(template.holes, [Hole::Id, Hole::Created, Hole::Model]);
+        let mut out = String::new();
+        template.render_into(&mut out, &fill(), 3);
+        assert!(out.starts_with("achatcmpl-"));
+        assert!(out.ends_with(r#"cgpt-\"5\""#));
+    }
+
+    #[test]
+    fn test_body_is_filled_in() {
+        let pooled = PooledResponse::new("Hello there world".to_string(), 3);
+        let fill = fill();
+        let body: Value = serde_json::from_str(&pooled.body(&fill)).unwrap();
+        assert_eq!(body["id"], fill.id.as_str());
+        assert_eq!(body["model"], "gpt-\"5\"");
+        assert_eq!(body["system_fingerprint"], "fp_test");
+        assert_eq!(body["created"], fill.created);
+        assert_eq!(
+            body["choices"][0]["message"]["content"],
+            "Hello there world"
+        );
+        assert_eq!(body["usage"]["prompt_tokens"], 12);
+        assert_eq!(body["usage"]["total_tokens"], 15);
+    }
+
+    #[tokio::test]
+    async fn test_instant_stream_is_one_write() {
+        let pooled = Arc::new(PooledResponse::new("Hello there".to_string(), 2));
+        let done = Arc::new(AtomicBool::new(false));
+        let flag = done.clone();
+        let writes: Vec<String> = pooled
+            .stream(
+                fill(),
+                LatencyProfile::instant().into(),
+                default_sleeper(),
+                move || flag.store(true, Ordering::Relaxed),
+            )
+            .collect()
+            .await;
+        assert_eq!(writes.len(), 1);
+        assert!(done.load(Ordering::Relaxed));
+
+        let events: Vec<&str> = writes[0].split("\n\n").filter(|e| !e.is_empty()).collect();
+        // Role, three pieces, finish, [DONE]
+        assert_eq!(events.len(), 6);
+        assert_eq!(events[5], "data: [DONE]");
+        let finish: Value = serde_json::from_str(&events[4]["data: ".len()..]).unwrap();
+        assert_eq!(finish["choices"][0]["finish_reason"], "stop");
+        assert_eq!(finish["usage"]["total_tokens"], 14);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_delays_split_writes() {
+        let pooled = Arc::new(PooledResponse::new("Hello there".to_string(), 2));
+        let latency = LatencyProfile::new(100, 0, 10, 0);
+        let started = tokio::time::Instant::now();
+        let writes: Vec<String> = pooled
+            .stream(fill(), latency.into(), default_sleeper(), || {})
+            .collect()
+            .await;
+        // Role after the first token delay, then each piece after its gap
+        assert_eq!(writes.len(), 4);
+        assert!(writes[3].ends_with("data: [DONE]\n\n"));
+        assert_eq!(started.elapsed().as_millis(), 130);
+    }
+
+    #[test]
+    fn test_pool_round_robin() {
+        let pipeline = RequestPipeline::new(new_shared_stats());
+        let pool = ResponsePool::generate(&pipeline, 3);
+        assert_eq!(pool.len(), 3);
+        let first = pool.next();
+        pool.next();
+        pool.next();
+        assert!(Arc::ptr_eq(&first, &pool.next()));
+        assert!(first.completion_tokens() > 0);
+    }
+}
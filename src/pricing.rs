@@ -0,0 +1,144 @@
+// Pricing Module
+// Per-model token prices used to attach simulated cost to responses.
+//
+// Decision: prices are a small baked-in table of list prices (USD per 1M
+// tokens) matched by model family, like `LatencyProfile::from_model`. They are
+// only meant to make gateway cost propagation testable, not to match an
+// invoice; operators who need exact figures override them in config.
+
+use serde::{Deserialize, Serialize};
+
+/// Token prices for one model, in USD per million tokens
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ModelPrice {
+    pub input_per_mtok: f64,
+    pub output_per_mtok: f64,
+}
+
+impl ModelPrice {
+    pub const fn new(input_per_mtok: f64, output_per_mtok: f64) -> Self {
+        Self {
+            input_per_mtok,
+            output_per_mtok,
+        }
+    }
+
+    /// Cost in USD of a request with the given token counts
+    pub fn cost(&self, input_tokens: u32, output_tokens: u32) -> f64 {
+        (input_tokens as f64 * self.input_per_mtok + output_tokens as f64 * self.output_per_mtok)
+            / 1_000_000.0
+    }
+
+    /// Look up the list price for a model family.
+    /// Returns None for models the table does not know about.
+    pub fn for_model(model: &str) -> Option<Self> {
+        let m = model.to_lowercase();
+
+        let price = if m.contains("gpt-5") && m.contains("pro") {
+            Self::new(15.0, 120.0)
+        } else if m.contains("gpt-5") && m.contains("nano") {
+            Self::new(0.05, 0.40)
+        } else if m.contains("gpt-5") && m.contains("mini") {
+            Self::new(0.25, 2.0)
+        } else if m.contains("gpt-5") {
+            Self::new(1.25, 10.0)
+        // O-series reasoning models
+        } else if m.starts_with("o1-mini") || m.starts_with("o3-mini") || m.starts_with("o4-mini") {
+            Self::new(1.10, 4.40)
+        } else if m.starts_with("o1") {
+            Self::new(15.0, 60.0)
+        } else if m.starts_with("o3") {
+            Self::new(2.0, 8.0)
+        // GPT-4 family
+        } else if m.contains("gpt-4o-mini") {
+            Self::new(0.15, 0.60)
+        } else if m.contains("gpt-4o") {
+            Self::new(2.50, 10.0)
+        } else if m.contains("gpt-4.1-nano") {
+            Self::new(0.10, 0.40)
+        } else if m.contains("gpt-4.1-mini") {
+            Self::new(0.40, 1.60)
+        } else if m.contains("gpt-4.1") {
+            Self::new(2.0, 8.0)
+        } else if m.contains("gpt-4-turbo") {
+            Self::new(10.0, 30.0)
+        } else if m.contains("gpt-4") {
+            Self::new(30.0, 60.0)
+        // Claude family (both `claude-opus-4.1` and `claude-opus-4-1` spellings)
+        } else if m.contains("opus-4.5")
+            || m.contains("opus-4-5")
+            || m.contains("opus-4.6")
+            || m.contains("opus-4-6")
+            || m.contains("opus-4.7")
+            || m.contains("opus-4-7")
+            || m.contains("opus-4.8")
+            || m.contains("opus-4-8")
+        {
+            Self::new(5.0, 25.0)
+        } else if m.contains("opus") {
+            Self::new(15.0, 75.0)
+        } else if m.contains("sonnet") {
+            Self::new(3.0, 15.0)
+        } else if m.contains("haiku") {
+            Self::new(1.0, 5.0)
+        // Gemini family
+        } else if m.contains("gemini-2.0-flash") {
+            Self::new(0.10, 0.40)
+        } else if m.contains("gemini") && m.contains("flash") {
+            Self::new(0.30, 2.50)
+        } else if m.contains("gemini-3") {
+            Self::new(2.0, 12.0)
+        } else if m.contains("gemini") {
+            Self::new(1.25, 10.0)
+        // DeepSeek family
+        } else if m.contains("deepseek-reasoner") {
+            Self::new(0.55, 2.19)
+        } else if m.contains("deepseek") {
+            Self::new(0.27, 1.10)
+        } else {
+            return None;
+        };
+        Some(price)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cost() {
+        let price = ModelPrice::new(1.25, 10.0);
+        let cost = price.cost(1_000, 500);
+        assert!((cost - 0.00625).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_for_model_families() {
+        assert_eq!(
+            ModelPrice::for_model("gpt-5"),
+            Some(ModelPrice::new(1.25, 10.0))
+        );
+        assert_eq!(
+            ModelPrice::for_model("gpt-5-mini"),
+            Some(ModelPrice::new(0.25, 2.0))
+        );
+        assert_eq!(
+            ModelPrice::for_model("gpt-4o-mini"),
+            Some(ModelPrice::new(0.15, 0.60))
+        );
+        assert_eq!(
+            ModelPrice::for_model("o4-mini"),
+            Some(ModelPrice::new(1.10, 4.40))
+        );
+        assert_eq!(
+            ModelPrice::for_model("claude-opus-4-8"),
+            ModelPrice::for_model("claude-opus-4.8")
+        );
+        assert_eq!(
+            ModelPrice::for_model("claude-opus-4.1"),
+            Some(ModelPrice::new(15.0, 75.0))
+        );
+        assert!(ModelPrice::for_model("my-custom-model").is_none());
+    }
+}
@@ -0,0 +1,402 @@
+// Rate Limit Module
+// Per-key request and token budgets behind the rate limit headers, and how
+// the `Retry-After` of a 429 is chosen: fixed, or growing exponentially with
+// a key's consecutive 429s, optionally jittered.
+//
+// Decision: each key has two token buckets refilling continuously at their
+// per-minute limit, the way providers describe their limits: "remaining" is
+// a bucket's level and "reset" the time until it is full again, so the
+// headers of successive responses count down and recover coherently instead
+// of being made up per response. Tokens are charged up front (the prompt plus
+// the requested maximum output, as OpenAI's limiter reserves them), because a
+// stream's real usage is only known after its headers are sent. Keys share
+// one mutex-guarded map; this is per listener and cheap next to a request.
+
+use crate::clock::{self, Instant};
+use rand::RngExt;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// How `Retry-After` grows with a key's consecutive 429s
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RetryAfterMode {
+    /// Always the base
+    Fixed,
+    /// The base doubled for each further consecutive 429, up to the maximum
+    Exponential,
+}
+
+/// `Retry-After` for a 429
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryAfterPolicy {
+    pub mode: RetryAfterMode,
+    /// Seconds of the first 429 in a row
+    pub seconds: u32,
+    /// Cap on the exponential growth
+    pub max_seconds: u32,
+    /// Share (0.0-1.0) the value may move up or down at random
+    pub jitter: f64,
+}
+
+impl RetryAfterPolicy {
+    pub fn fixed(seconds: u32) -> Self {
+        Self {
+            mode: RetryAfterMode::Fixed,
+            seconds,
+            max_seconds: seconds,
+            jitter: 0.0,
+        }
+    }
+
+    pub fn exponential(seconds: u32, max_seconds: u32) -> Self {
+        Self {
+            mode: RetryAfterMode::Exponential,
+            seconds,
+            max_seconds,
+            jitter: 0.0,
+        }
+    }
+
+    pub fn with_jitter(mut self, jitter: f64) -> Self {
+        self.jitter = jitter.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Seconds to wait after the `consecutive`-th 429 in a row (from 1);
+    /// never less than one
+    pub fn seconds(&self, consecutive: u32) -> u32 {
+        let base = match self.mode {
+            RetryAfterMode::Fixed => self.seconds,
+            RetryAfterMode::Exponential => {
+                let doublings = consecutive.saturating_sub(1).min(31);
+                self.seconds
+                    .saturating_mul(1 << doublings)
+                    .min(self.max_seconds.max(self.seconds))
+            }
+        };
+        let factor = if self.jitter > 0.0 {
+            rand::rng().random_range(1.0 - self.jitter..=1.0 + self.jitter)
+        } else {
+            1.0
+        };
+        ((base as f64 * factor).round() as u32).max(1)
+    }
+}
+
+/// One budget of a key at the moment a request was admitted or refused
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BudgetStatus {
+    /// Per-minute limit
+    pub limit: u32,
+    /// What is left after this request
+    pub remaining: u32,
+    /// Time until the budget is full again
+    pub reset: Duration,
+    /// Time until this request would have fit; zero if it did
+    pub wait: Duration,
+}
+
+/// The outcome of charging a request to a key
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimitStatus {
+    pub admitted: bool,
+    /// `None` without a request limit
+    pub requests: Option<BudgetStatus>,
+    /// `None` without a token limit
+    pub tokens: Option<BudgetStatus>,
+}
+
+impl RateLimitStatus {
+    /// Whole seconds until a refused request would fit, at least one
+    pub fn retry_after_seconds(&self) -> u32 {
+        let wait = [self.requests, self.tokens]
+            .into_iter()
+            .flatten()
+            .map(|budget| budget.wait)
+            .max()
+            .unwrap_or_default();
+        (wait.as_secs_f64().ceil() as u32).max(1)
+    }
+}
+
+/// A budget refilling continuously at `limit` per minute
+#[derive(Debug)]
+struct Bucket {
+    level: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn full(limit: u32) -> Self {
+        Self {
+            level: limit as f64,
+            updated: clock::now(),
+        }
+    }
+
+    /// Refill up to now, then take `amount` if it fits
+    fn charge(&mut self, limit: u32, amount: u32, take: bool) -> BudgetStatus {
+        let per_second = limit as f64 / 60.0;
+        let now = clock::now();
+        let refilled = now.saturating_duration_since(self.updated).as_secs_f64() * per_second;
+        self.level = (self.level + refilled).min(limit as f64);
+        self.updated = now;
+
+        let fits = self.level >= amount as f64;
+        if fits && take {
+            self.level -= amount as f64;
+        }
+        let seconds_until = |level: f64| {
+            if per_second > 0.0 {
+                Duration::from_secs_f64((level - self.level).max(0.0) / per_second)
+            } else {
+                Duration::ZERO
+            }
+        };
+        BudgetStatus {
+            limit,
+            remaining: self.level.floor() as u32,
+            reset: seconds_until(limit as f64),
+            wait: if fits {
+                Duration::ZERO
+            } else {
+                seconds_until(amount as f64)
+            },
+        }
+    }
+}
+
+/// Per-minute budgets a key is charged against; unlimited where unset
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct KeyLimits {
+    pub requests_per_minute: Option<u32>,
+    pub tokens_per_minute: Option<u32>,
+}
+
+impl KeyLimits {
+    /// These limits, with `fallback`'s where these are unset
+    pub fn or(self, fallback: KeyLimits) -> KeyLimits {
+        KeyLimits {
+            requests_per_minute: self.requests_per_minute.or(fallback.requests_per_minute),
+            tokens_per_minute: self.tokens_per_minute.or(fallback.tokens_per_minute),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct KeyState {
+    requests: Bucket,
+    tokens: Bucket,
+    consecutive_429s: u32,
+}
+
+/// Request and token budgets per API key, and the `Retry-After` policy
+#[derive(Debug, Default)]
+pub struct RateLimiter {
+    requests_per_minute: Option<u32>,
+    tokens_per_minute: Option<u32>,
+    retry_after: Option<RetryAfterPolicy>,
+    keys: Mutex<HashMap<String, KeyState>>,
+}
+
+impl RateLimiter {
+    /// A limiter with no budgets; every request is admitted
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_requests_per_minute(mut self, limit: u32) -> Self {
+        self.requests_per_minute = Some(limit);
+        self
+    }
+
+    pub fn with_tokens_per_minute(mut self, limit: u32) -> Self {
+        self.tokens_per_minute = Some(limit);
+        self
+    }
+
+    /// Choose the `Retry-After` of every 429 with `policy`
+    pub fn with_retry_after(mut self, policy: RetryAfterPolicy) -> Self {
+        self.retry_after = Some(policy);
+        self
+    }
+
+    pub fn requests_per_minute(&self) -> Option<u32> {
+        self.requests_per_minute
+    }
+
+    pub fn tokens_per_minute(&self) -> Option<u32> {
+        self.tokens_per_minute
+    }
+
+    pub fn retry_after(&self) -> Option<&RetryAfterPolicy> {
+        self.retry_after.as_ref()
+    }
+
+    /// The budgets every key is charged against
+    pub fn limits(&self) -> KeyLimits {
+        KeyLimits {
+            requests_per_minute: self.requests_per_minute,
+            tokens_per_minute: self.tokens_per_minute,
+        }
+    }
+
+    /// Whether any budget is set
+    pub fn has_limits(&self) -> bool {
+        self.requests_per_minute.is_some() || self.tokens_per_minute.is_some()
+    }
+
+    /// Charge one request of `tokens` tokens to `key`. A request is admitted
+    /// only if both budgets have room, and only charged if admitted.
+    pub fn acquire(&self, key: &str, tokens: u32) -> RateLimitStatus {
+        self.acquire_with(key, tokens, self.limits())
+    }
+
+    /// Charge one request to `key` against `limits` in place of the
+    /// limiter's own, for a key with quotas of its own
+    pub fn acquire_with(&self, key: &str, tokens: u32, limits: KeyLimits) -> RateLimitStatus {
+        let Ok(mut keys) = self.keys.lock() else {
+            return RateLimitStatus {
+                admitted: true,
+                requests: None,
+                tokens: None,
+            };
+        };
+        let state = keys
+            .entry(key.to_string())
+            .or_insert_with(|| new_key(limits));
+        let peek_requests = limits
+            .requests_per_minute
+            .map(|limit| state.requests.charge(limit, 1, false));
+        let peek_tokens = limits
+            .tokens_per_minute
+            .map(|limit| state.tokens.charge(limit, tokens, false));
+        let admitted = [peek_requests, peek_tokens]
+            .into_iter()
+            .flatten()
+            .all(|budget| budget.wait.is_zero());
+        if !admitted {
+            return RateLimitStatus {
+                admitted,
+                requests: peek_requests,
+                tokens: peek_tokens,
+            };
+        }
+        RateLimitStatus {
+            admitted,
+            requests: limits
+                .requests_per_minute
+                .map(|limit| state.requests.charge(limit, 1, true)),
+            tokens: limits
+                .tokens_per_minute
+                .map(|limit| state.tokens.charge(limit, tokens, true)),
+        }
+    }
+
+    /// Note how a request of `key` was answered. For a 429 this returns the
+    /// policy's `Retry-After`, if a policy is set; any other status ends the
+    /// key's run of 429s.
+    pub fn note_response(&self, key: &str, rate_limited: bool) -> Option<u32> {
+        let mut keys = self.keys.lock().ok()?;
+        if !rate_limited {
+            if let Some(state) = keys.get_mut(key) {
+                state.consecutive_429s = 0;
+            }
+            return None;
+        }
+        let state = keys
+            .entry(key.to_string())
+            .or_insert_with(|| new_key(self.limits()));
+        state.consecutive_429s += 1;
+        let consecutive = state.consecutive_429s;
+        Some(self.retry_after.as_ref()?.seconds(consecutive))
+    }
+}
+
+fn new_key(limits: KeyLimits) -> KeyState {
+    KeyState {
+        requests: Bucket::full(limits.requests_per_minute.unwrap_or(0)),
+        tokens: Bucket::full(limits.tokens_per_minute.unwrap_or(0)),
+        consecutive_429s: 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exponential_retry_after_caps() {
+        let policy = RetryAfterPolicy::exponential(2, 20);
+        let waits: Vec<u32> = (1..=6).map(|n| policy.seconds(n)).collect();
+        assert_eq!(waits, [2, 4, 8, 16, 20, 20]);
+        assert_eq!(RetryAfterPolicy::fixed(5).seconds(9), 5);
+    }
+
+    #[test]
+    fn test_jitter_stays_in_range() {
+        let policy = RetryAfterPolicy::fixed(100).with_jitter(0.2);
+        for _ in 0..200 {
+            assert!((80..=120).contains(&policy.seconds(1)));
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_budget_counts_down_and_refills() {
+        let limiter = RateLimiter::new().with_requests_per_minute(60);
+        for left in (0..60).rev() {
+            let status = limiter.acquire("k", 0);
+            assert!(status.admitted);
+            assert_eq!(status.requests.unwrap().remaining, left);
+        }
+        let refused = limiter.acquire("k", 0);
+        assert!(!refused.admitted);
+        assert_eq!(refused.retry_after_seconds(), 1);
+        assert_eq!(refused.requests.unwrap().reset, Duration::from_secs(60));
+        // Other keys have their own budget
+        assert!(limiter.acquire("other", 0).admitted);
+
+        tokio::time::advance(Duration::from_secs(30)).await;
+        let status = limiter.acquire("k", 0);
+        assert_eq!(status.requests.unwrap().remaining, 29);
+    }
+
+    #[test]
+    fn test_refused_request_is_not_charged() {
+        let limiter = RateLimiter::new()
+            .with_requests_per_minute(10)
+            .with_tokens_per_minute(100);
+        assert!(!limiter.acquire("k", 500).admitted);
+        let status = limiter.acquire("k", 40);
+        assert!(status.admitted);
+        assert_eq!(status.requests.unwrap().remaining, 9);
+        assert_eq!(status.tokens.unwrap().remaining, 60);
+    }
+
+    #[test]
+    fn test_consecutive_429s_reset_on_success() {
+        let limiter = RateLimiter::new().with_retry_after(RetryAfterPolicy::exponential(1, 60));
+        assert_eq!(limiter.note_response("k", true), Some(1));
+        assert_eq!(limiter.note_response("k", true), Some(2));
+        assert_eq!(limiter.note_response("k", true), Some(4));
+        assert_eq!(limiter.note_response("k", false), None);
+        assert_eq!(limiter.note_response("k", true), Some(1));
+        assert_eq!(RateLimiter::new().note_response("k", true), None);
+    }
+
+    #[test]
+    fn test_key_limits_replace_the_limiter_own() {
+        let limiter = RateLimiter::new().with_requests_per_minute(100);
+        let quota = KeyLimits {
+            requests_per_minute: Some(1),
+            tokens_per_minute: None,
+        }
+        .or(limiter.limits());
+        assert!(limiter.acquire_with("small", 0, quota).admitted);
+        assert!(!limiter.acquire_with("small", 0, quota).admitted);
+        assert_eq!(limiter.acquire("big", 0).requests.unwrap().remaining, 99);
+    }
+}
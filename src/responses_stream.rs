@@ -1,20 +1,28 @@
 // Responses API Streaming Engine
 // Implements streaming for the OpenAI Responses API format.
 
-use crate::ids::{prefixed_id, unix_timestamp};
-use crate::latency::LatencyProfile;
+use crate::citations;
+use crate::ids::{new_id, unix_timestamp, IdKind};
+use crate::latency::{LatencyProfile, RequestLatency};
 use crate::openai::{
     ItemStatus, OutputContentPart, OutputItem, OutputRole, OutputTokensDetails, ReasoningSummary,
     ResponseStatus, ResponsesResponse, ResponsesStreamEvent, ResponsesUsage,
 };
+use crate::sleep::{default_sleeper, Sleeper};
+use crate::watchdog::StreamWatchdog;
 use async_stream::stream;
 use futures_core::Stream;
+use rand::distr::Alphanumeric;
+use rand::RngExt;
 use std::pin::Pin;
-use tokio::time::sleep;
+use std::sync::Arc;
 
 /// Type alias for on-complete callback
 type OnCompleteCallback = Box<dyn FnOnce() + Send>;
 
+/// Type alias for the callback receiving the final response
+type OnResponseCallback = Box<dyn FnOnce(&ResponsesResponse) + Send>;
+
 /// Ensures completion callback runs exactly once, including when stream is dropped early.
 struct CompletionGuard {
     callback: Option<OnCompleteCallback>,
@@ -49,7 +57,7 @@ pub struct ResponsesTokenStream {
     /// Unix timestamp of creation
     created_at: i64,
     /// Latency profile for timing simulation
-    latency: LatencyProfile,
+    latency: RequestLatency,
     /// The full response text to stream
     content: String,
     /// Token usage
@@ -58,6 +66,29 @@ pub struct ResponsesTokenStream {
     include_reasoning: bool,
     /// Optional reasoning summary text to stream
     reasoning_summary: Option<String>,
+    /// Give the reasoning item an `encrypted_content`
+    encrypt_reasoning: bool,
+    /// End with `response.incomplete` (cut off by max_output_tokens)
+    truncated: bool,
+    /// Stream `content` as a refusal instead of output text
+    refusal: bool,
+    /// Annotations on `content`, each announced once its text is out
+    annotations: Vec<serde_json::Value>,
+    /// MCP listings, approval requests and calls, streamed before the message
+    mcp_items: Vec<OutputItem>,
+    /// Completed code interpreter call, streamed before the message
+    code_interpreter_call: Option<OutputItem>,
+    /// Pad delta events with a random `obfuscation` field
+    obfuscate: bool,
+    /// Repeat `response.in_progress` with the usage so far after this many
+    /// text deltas
+    usage_updates_every: Option<u32>,
+    /// Waits out the simulated delays
+    sleeper: Arc<dyn Sleeper>,
+    /// Ends the message early once the stream's time budget is spent
+    watchdog: StreamWatchdog,
+    /// Callback to invoke with the final response before it is sent
+    on_response: Option<OnResponseCallback>,
     /// Callback to invoke when stream completes
     on_complete: Option<OnCompleteCallback>,
 }
@@ -66,23 +97,40 @@ impl ResponsesTokenStream {
     pub fn new(
         model: String,
         content: String,
-        latency: LatencyProfile,
+        latency: impl Into<RequestLatency>,
         usage: ResponsesUsage,
     ) -> Self {
         Self {
-            response_id: prefixed_id("resp_"),
-            message_id: prefixed_id("msg_"),
+            response_id: new_id(IdKind::Response),
+            message_id: new_id(IdKind::Message),
             model,
             created_at: unix_timestamp(),
-            latency,
+            latency: latency.into(),
             content,
             usage,
             include_reasoning: false,
             reasoning_summary: None,
+            encrypt_reasoning: false,
+            truncated: false,
+            refusal: false,
+            annotations: Vec::new(),
+            mcp_items: Vec::new(),
+            code_interpreter_call: None,
+            obfuscate: false,
+            usage_updates_every: None,
+            sleeper: default_sleeper(),
+            watchdog: StreamWatchdog::unlimited(),
+            on_response: None,
             on_complete: None,
         }
     }
 
+    /// Wait out delays with this sleeper (default: the tokio timer)
+    pub fn with_sleeper(mut self, sleeper: Arc<dyn Sleeper>) -> Self {
+        self.sleeper = sleeper;
+        self
+    }
+
     pub fn with_on_complete<F>(mut self, callback: F) -> Self
     where
         F: FnOnce() + Send + 'static,
@@ -116,17 +164,37 @@ impl ResponsesTokenStream {
     }
 
     /// Create a streaming response as Server-Sent Events
+    /// Id of the response the stream creates
+    pub fn response_id(&self) -> &str {
+        &self.response_id
+    }
+
     pub fn into_stream(self) -> Pin<Box<dyn Stream<Item = String> + Send>> {
         let content_tokens = Self::tokenize_text(&self.content);
         let response_id = self.response_id.clone();
         let message_id = self.message_id.clone();
         let model = self.model.clone();
         let created_at = self.created_at;
-        let latency = self.latency.clone();
+        let mut latency = self.latency.clone();
         let usage = self.usage.clone();
         let content = self.content.clone();
         let include_reasoning = self.include_reasoning;
         let reasoning_summary = self.reasoning_summary.clone();
+        let encrypt_reasoning = self.encrypt_reasoning;
+        let reasoning_tokens = usage
+            .output_tokens_details
+            .as_ref()
+            .map_or(0, |details| details.reasoning_tokens);
+        let truncated = self.truncated;
+        let refusal = self.refusal;
+        let annotations = self.annotations;
+        let mcp_items = self.mcp_items;
+        let code_interpreter_call = self.code_interpreter_call;
+        let obfuscate = self.obfuscate;
+        let usage_updates_every = self.usage_updates_every.filter(|n| *n > 0);
+        let sleeper = self.sleeper;
+        let mut watchdog = self.watchdog;
+        let on_response = self.on_response;
         let on_complete = self.on_complete;
 
         Box::pin(stream! {
@@ -146,6 +214,7 @@ impl ResponsesTokenStream {
                 output_text: None,
                 usage: None,
                 error: None,
+                incomplete_details: None,
                 metadata: None,
             };
 
@@ -154,9 +223,9 @@ impl ResponsesTokenStream {
             seq += 1;
 
             // Initial delay (time to first token)
-            let ttft = latency.sample_ttft();
-            if !ttft.is_zero() {
-                sleep(ttft).await;
+            let ttft = latency.ttft();
+            if watchdog.allows(ttft) && !ttft.is_zero() {
+                sleeper.sleep(ttft).await;
             }
 
             // response.in_progress event
@@ -169,7 +238,7 @@ impl ResponsesTokenStream {
 
             // --- Reasoning output item (if enabled) ---
             if include_reasoning {
-                let reasoning_id = prefixed_id("rs_");
+                let reasoning_id = new_id(IdKind::Reasoning);
                 let reasoning_output_index: u32 = 0;
 
                 // Emit reasoning item added (in_progress, no summary yet)
@@ -177,6 +246,7 @@ impl ResponsesTokenStream {
                     id: reasoning_id.clone(),
                     status: ItemStatus::InProgress,
                     summary: None,
+                    encrypted_content: None,
                 };
                 yield ResponsesStreamEvent::output_item_added(reasoning_output_index, &reasoning_item, seq);
                 seq += 1;
@@ -197,13 +267,14 @@ impl ResponsesTokenStream {
                     // Stream summary text deltas
                     let summary_tokens = Self::tokenize_text(summary_text);
                     for token in summary_tokens.into_iter() {
-                        let tbt = latency.sample_tbt();
-                        if !tbt.is_zero() {
-                            sleep(tbt).await;
+                        let tbt = latency.tbt();
+                        if watchdog.allows(tbt) && !tbt.is_zero() {
+                            sleeper.sleep(tbt).await;
                         }
 
+                        let padding = obfuscate.then(|| obfuscation_for(&token));
                         yield ResponsesStreamEvent::reasoning_summary_text_delta(
-                            reasoning_output_index, 0, &reasoning_id, &token, seq,
+                            reasoning_output_index, 0, &reasoning_id, &token, padding.as_deref(), seq,
                         );
                         seq += 1;
                     }
@@ -235,14 +306,139 @@ impl ResponsesTokenStream {
                             text: text.clone(),
                         }]
                     }),
+                    encrypted_content: encrypt_reasoning
+                        .then(|| crate::encrypted_reasoning::encrypt(reasoning_tokens)),
                 };
                 yield ResponsesStreamEvent::output_item_done(reasoning_output_index, &final_reasoning_item, seq);
                 seq += 1;
                 final_output_items.push(final_reasoning_item);
             }
 
+            let mut message_output_index = if include_reasoning { 1 } else { 0 };
+
+            // --- MCP items (if an MCP server was offered) ---
+            for item in mcp_items {
+                let item_output_index = message_output_index;
+                message_output_index += 1;
+                match &item {
+                    OutputItem::McpListTools { id, server_label, .. } => {
+                        let listing = OutputItem::McpListTools {
+                            id: id.clone(),
+                            server_label: server_label.clone(),
+                            tools: Vec::new(),
+                            error: None,
+                        };
+                        yield ResponsesStreamEvent::output_item_added(item_output_index, &listing, seq);
+                        seq += 1;
+                        yield ResponsesStreamEvent::mcp_status("mcp_list_tools", "in_progress", item_output_index, id, seq);
+                        seq += 1;
+                        // Listing takes a round trip to the server
+                        let listed = latency.ttft();
+                        if watchdog.allows(listed) && !listed.is_zero() {
+                            sleeper.sleep(listed).await;
+                        }
+                        yield ResponsesStreamEvent::mcp_status("mcp_list_tools", "completed", item_output_index, id, seq);
+                        seq += 1;
+                    }
+                    OutputItem::McpCall { id, server_label, name, arguments, error, approval_request_id, .. } => {
+                        let call = OutputItem::McpCall {
+                            id: id.clone(),
+                            server_label: server_label.clone(),
+                            name: name.clone(),
+                            arguments: String::new(),
+                            output: None,
+                            error: None,
+                            status: ItemStatus::InProgress,
+                            approval_request_id: approval_request_id.clone(),
+                        };
+                        yield ResponsesStreamEvent::output_item_added(item_output_index, &call, seq);
+                        seq += 1;
+                        yield ResponsesStreamEvent::mcp_status("mcp_call", "in_progress", item_output_index, id, seq);
+                        seq += 1;
+                        for token in Self::tokenize_text(arguments) {
+                            let tbt = latency.tbt();
+                            if watchdog.allows(tbt) && !tbt.is_zero() {
+                                sleeper.sleep(tbt).await;
+                            }
+                            yield ResponsesStreamEvent::mcp_call_arguments_delta(item_output_index, id, &token, seq);
+                            seq += 1;
+                        }
+                        yield ResponsesStreamEvent::mcp_call_arguments_done(item_output_index, id, arguments, seq);
+                        seq += 1;
+                        // The server takes about a first token's time to answer
+                        let called = latency.ttft();
+                        if watchdog.allows(called) && !called.is_zero() {
+                            sleeper.sleep(called).await;
+                        }
+                        let status = if error.is_some() { "failed" } else { "completed" };
+                        yield ResponsesStreamEvent::mcp_status("mcp_call", status, item_output_index, id, seq);
+                        seq += 1;
+                    }
+                    // Approval requests arrive whole
+                    _ => {
+                        yield ResponsesStreamEvent::output_item_added(item_output_index, &item, seq);
+                        seq += 1;
+                    }
+                }
+                yield ResponsesStreamEvent::output_item_done(item_output_index, &item, seq);
+                seq += 1;
+                final_output_items.push(item);
+            }
+
+            // --- Code interpreter call (if the tool was called) ---
+            if let Some(OutputItem::CodeInterpreterCall { id, code, container_id, outputs, .. }) =
+                code_interpreter_call
+            {
+                let call_output_index = message_output_index;
+                message_output_index += 1;
+                let code = code.unwrap_or_default();
+
+                let call_item = OutputItem::CodeInterpreterCall {
+                    id: id.clone(),
+                    status: ItemStatus::InProgress,
+                    code: Some(String::new()),
+                    container_id: container_id.clone(),
+                    outputs: None,
+                };
+                yield ResponsesStreamEvent::output_item_added(call_output_index, &call_item, seq);
+                seq += 1;
+                yield ResponsesStreamEvent::code_interpreter_call_in_progress(call_output_index, &id, seq);
+                seq += 1;
+
+                for token in Self::tokenize_text(&code) {
+                    let tbt = latency.tbt();
+                    if watchdog.allows(tbt) && !tbt.is_zero() {
+                        sleeper.sleep(tbt).await;
+                    }
+                    yield ResponsesStreamEvent::code_interpreter_call_code_delta(call_output_index, &id, &token, seq);
+                    seq += 1;
+                }
+                yield ResponsesStreamEvent::code_interpreter_call_code_done(call_output_index, &id, &code, seq);
+                seq += 1;
+
+                // Running the code takes about as long as a first token
+                yield ResponsesStreamEvent::code_interpreter_call_interpreting(call_output_index, &id, seq);
+                seq += 1;
+                let run = latency.ttft();
+                if watchdog.allows(run) && !run.is_zero() {
+                    sleeper.sleep(run).await;
+                }
+                yield ResponsesStreamEvent::code_interpreter_call_completed(call_output_index, &id, seq);
+                seq += 1;
+
+                let final_call_item = OutputItem::CodeInterpreterCall {
+                    id,
+                    status: ItemStatus::Completed,
+                    code: Some(code),
+                    container_id,
+                    outputs,
+                };
+                yield ResponsesStreamEvent::output_item_done(call_output_index, &final_call_item, seq);
+                seq += 1;
+                final_output_items.push(final_call_item);
+            }
+
             // --- Message output item ---
-            let message_output_index = if include_reasoning { 1 } else { 0 };
 
             // Create the output item (message) with in_progress status
             let message_item = OutputItem::Message {
@@ -257,39 +453,91 @@ impl ResponsesTokenStream {
             seq += 1;
 
             // Create the content part
-            let content_part = OutputContentPart::OutputText {
-                text: String::new(),
-                annotations: vec![],
-            };
+            let content_part = content_part_for(refusal, String::new(), Vec::new());
 
             // response.content_part.added event
             yield ResponsesStreamEvent::content_part_added(message_output_index, 0, &message_id, &content_part, seq);
             seq += 1;
 
             // Stream content chunks with delta events
-            for token in content_tokens.into_iter() {
-                // Inter-token delay
-                let tbt = latency.sample_tbt();
+            let delta_count = content_tokens.len();
+            let mut chars_sent = 0;
+            let mut deltas_sent = 0;
+            let mut announced = 0;
+            for (index, token) in content_tokens.into_iter().enumerate() {
+                // Inter-token delay, unless the time budget has run out
+                let tbt = latency.tbt();
+                if !watchdog.allows(tbt) {
+                    break;
+                }
                 if !tbt.is_zero() {
-                    sleep(tbt).await;
+                    sleeper.sleep(tbt).await;
                 }
 
-                // response.output_text.delta event
-                yield ResponsesStreamEvent::output_text_delta(
-                    message_output_index, 0, &message_id, &token, seq,
-                );
+                // response.output_text.delta (or response.refusal.delta) event
+                if refusal {
+                    yield ResponsesStreamEvent::refusal_delta(
+                        message_output_index, 0, &message_id, &token, seq,
+                    );
+                } else {
+                    let padding = obfuscate.then(|| obfuscation_for(&token));
+                    yield ResponsesStreamEvent::output_text_delta(
+                        message_output_index, 0, &message_id, &token, padding.as_deref(), seq,
+                    );
+                }
                 seq += 1;
+
+                // Annotations whose cited text is now complete
+                chars_sent += token.chars().count();
+                while let Some(annotation) = annotations
+                    .get(announced)
+                    .filter(|a| citations::end_of(a) <= chars_sent)
+                {
+                    yield ResponsesStreamEvent::output_text_annotation_added(
+                        message_output_index, 0, &message_id, announced as u32, annotation, seq,
+                    );
+                    seq += 1;
+                    announced += 1;
+                }
+
+                // Running usage, with output tokens in proportion to the
+                // deltas sent so far
+                let sent = index + 1;
+                deltas_sent = sent;
+                if usage_updates_every.is_some_and(|n| sent % n as usize == 0 && sent < delta_count) {
+                    let update = ResponsesResponse {
+                        usage: Some(usage_so_far(&usage, sent, delta_count)),
+                        ..initial_response.clone()
+                    };
+                    yield ResponsesStreamEvent::response_in_progress(update, seq);
+                    seq += 1;
+                }
             }
 
-            // response.output_text.done event
-            yield ResponsesStreamEvent::output_text_done(message_output_index, 0, &message_id, &content, seq);
+            // A stream out of time ends with the text it sent, as if cut off
+            // by max_output_tokens
+            let (content, annotations, usage, truncated) = if watchdog.tripped() {
+                let content: String = content.chars().take(chars_sent).collect();
+                let annotations = annotations
+                    .into_iter()
+                    .filter(|a| citations::end_of(a) <= chars_sent)
+                    .collect();
+                let usage = usage_so_far(&usage, deltas_sent, delta_count);
+                (content, annotations, usage, true)
+            } else {
+                (content, annotations, usage, truncated)
+            };
+
+            // response.output_text.done (or response.refusal.done) event
+            if refusal {
+                yield ResponsesStreamEvent::refusal_done(message_output_index, 0, &message_id, &content, seq);
+            } else {
+                yield ResponsesStreamEvent::output_text_done(message_output_index, 0, &message_id, &content, seq);
+            }
             seq += 1;
 
             // response.content_part.done event
-            let final_content_part = OutputContentPart::OutputText {
-                text: content.clone(),
-                annotations: vec![],
-            };
+            let final_content_part = content_part_for(refusal, content.clone(), annotations);
             yield ResponsesStreamEvent::content_part_done(message_output_index, 0, &message_id, &final_content_part, seq);
             seq += 1;
 
@@ -297,14 +545,18 @@ impl ResponsesTokenStream {
             let final_message_item = OutputItem::Message {
                 id: message_id.clone(),
                 role: OutputRole::Assistant,
-                status: ItemStatus::Completed,
+                status: if truncated {
+                    ItemStatus::Incomplete
+                } else {
+                    ItemStatus::Completed
+                },
                 content: vec![final_content_part],
             };
             yield ResponsesStreamEvent::output_item_done(message_output_index, &final_message_item, seq);
             seq += 1;
             final_output_items.push(final_message_item);
 
-            // response.completed event with full response
+            // response.completed (or response.incomplete) with full response
             let final_response = ResponsesResponse {
                 id: response_id.clone(),
                 object: "response".to_string(),
@@ -312,12 +564,25 @@ impl ResponsesTokenStream {
                 model: model.clone(),
                 status: ResponseStatus::Completed,
                 output: final_output_items,
-                output_text: Some(content.clone()),
+                output_text: (!refusal).then(|| content.clone()),
                 usage: Some(usage),
                 error: None,
+                incomplete_details: None,
                 metadata: None,
             };
-            yield ResponsesStreamEvent::response_completed(final_response, seq);
+            let final_response = if truncated {
+                final_response.truncated()
+            } else {
+                final_response
+            };
+            if let Some(on_response) = on_response {
+                on_response(&final_response);
+            }
+            if truncated {
+                yield ResponsesStreamEvent::response_incomplete(final_response, seq);
+            } else {
+                yield ResponsesStreamEvent::response_completed(final_response, seq);
+            }
 
             // Invoke completion callback
             completion_guard.complete();
@@ -325,14 +590,62 @@ impl ResponsesTokenStream {
     }
 }
 
+/// Message content part carrying `text` as output or as a refusal
+fn content_part_for(
+    refusal: bool,
+    text: String,
+    annotations: Vec<serde_json::Value>,
+) -> OutputContentPart {
+    if refusal {
+        OutputContentPart::Refusal { refusal: text }
+    } else {
+        OutputContentPart::OutputText { text, annotations }
+    }
+}
+
+/// Random padding that rounds a delta's payload up to a multiple of 16
+/// characters, so event sizes don't reveal token lengths
+fn obfuscation_for(delta: &str) -> String {
+    let len = 16 - delta.chars().count() % 16;
+    let mut rng = rand::rng();
+    (0..len).map(|_| rng.sample(Alphanumeric) as char).collect()
+}
+
+/// `usage` with only the first `sent` of `total` text deltas generated;
+/// reasoning comes before the message, so its tokens are all counted
+fn usage_so_far(usage: &ResponsesUsage, sent: usize, total: usize) -> ResponsesUsage {
+    let output_tokens = (usage.output_tokens as usize * sent / total.max(1)) as u32;
+    let reasoning_tokens = usage
+        .output_tokens_details
+        .as_ref()
+        .map_or(0, |details| details.reasoning_tokens);
+    ResponsesUsage {
+        input_tokens: usage.input_tokens,
+        output_tokens,
+        total_tokens: usage.input_tokens + output_tokens + reasoning_tokens,
+        output_tokens_details: usage.output_tokens_details.clone(),
+    }
+}
+
 /// Builder for creating Responses API token streams
 pub struct ResponsesTokenStreamBuilder {
     model: String,
     content: String,
-    latency: LatencyProfile,
+    latency: RequestLatency,
     usage: ResponsesUsage,
     include_reasoning: bool,
     reasoning_summary: Option<String>,
+    encrypt_reasoning: bool,
+    truncated: bool,
+    refusal: bool,
+    annotations: Vec<serde_json::Value>,
+    mcp_items: Vec<OutputItem>,
+    code_interpreter_call: Option<OutputItem>,
+    obfuscate: bool,
+    usage_updates_every: Option<u32>,
+    sleeper: Option<Arc<dyn Sleeper>>,
+    watchdog: StreamWatchdog,
+    on_response: Option<OnResponseCallback>,
     on_complete: Option<OnCompleteCallback>,
 }
 
@@ -341,7 +654,7 @@ impl ResponsesTokenStreamBuilder {
         Self {
             model: model.into(),
             content: content.into(),
-            latency: LatencyProfile::default(),
+            latency: LatencyProfile::default().into(),
             usage: ResponsesUsage {
                 input_tokens: 0,
                 output_tokens: 0,
@@ -352,12 +665,23 @@ impl ResponsesTokenStreamBuilder {
             },
             include_reasoning: false,
             reasoning_summary: None,
+            encrypt_reasoning: false,
+            truncated: false,
+            refusal: false,
+            annotations: Vec::new(),
+            mcp_items: Vec::new(),
+            code_interpreter_call: None,
+            obfuscate: false,
+            usage_updates_every: None,
+            sleeper: None,
+            watchdog: StreamWatchdog::unlimited(),
+            on_response: None,
             on_complete: None,
         }
     }
 
-    pub fn latency(mut self, latency: LatencyProfile) -> Self {
-        self.latency = latency;
+    pub fn latency(mut self, latency: impl Into<RequestLatency>) -> Self {
+        self.latency = latency.into();
         self
     }
 
@@ -375,6 +699,87 @@ impl ResponsesTokenStreamBuilder {
         self
     }
 
+    /// Give the reasoning item an opaque `encrypted_content`, for
+    /// `include: ["reasoning.encrypted_content"]`
+    pub fn encrypted_reasoning(mut self, enabled: bool) -> Self {
+        self.encrypt_reasoning = enabled;
+        self
+    }
+
+    /// End the stream with `response.incomplete` for output cut off by
+    /// `max_output_tokens`
+    pub fn truncated(mut self, truncated: bool) -> Self {
+        self.truncated = truncated;
+        self
+    }
+
+    /// Stream the content as a `refusal` content part, with
+    /// `response.refusal.*` events in place of `response.output_text.*`
+    pub fn refusal(mut self, refusal: bool) -> Self {
+        self.refusal = refusal;
+        self
+    }
+
+    /// Annotations on the content, sorted by position: each is announced
+    /// with `response.output_text.annotation.added` after the delta that
+    /// completes its text, and all are in the final content part
+    pub fn annotations(mut self, annotations: Vec<serde_json::Value>) -> Self {
+        self.annotations = annotations;
+        self
+    }
+
+    /// Stream MCP items before the message: listings and calls with their
+    /// in-progress and completed transitions, approval requests whole
+    pub fn mcp_items(mut self, items: Vec<OutputItem>) -> Self {
+        self.mcp_items = items;
+        self
+    }
+
+    /// Stream a completed `code_interpreter_call` item before the message:
+    /// its code as deltas, then the interpreting and completed transitions
+    pub fn code_interpreter_call(mut self, item: Option<OutputItem>) -> Self {
+        self.code_interpreter_call = item;
+        self
+    }
+
+    /// Pad text deltas with a random `obfuscation` field, as the API does
+    /// unless `stream_options.include_obfuscation` is false
+    pub fn obfuscation(mut self, enabled: bool) -> Self {
+        self.obfuscate = enabled;
+        self
+    }
+
+    /// Send a `response.in_progress` event carrying the usage so far after
+    /// every `every` text deltas, for clients that show live token counts
+    pub fn usage_updates(mut self, every: Option<u32>) -> Self {
+        self.usage_updates_every = every;
+        self
+    }
+
+    /// Wait out delays with this sleeper
+    pub fn sleeper(mut self, sleeper: Arc<dyn Sleeper>) -> Self {
+        self.sleeper = Some(sleeper);
+        self
+    }
+
+    /// End the message early, with `response.incomplete`, once the
+    /// watchdog's budget is spent
+    pub fn watchdog(mut self, watchdog: StreamWatchdog) -> Self {
+        self.watchdog = watchdog;
+        self
+    }
+
+    /// Set a callback to be invoked with the final response, just before
+    /// `response.completed` (or `response.incomplete`) is sent. Not called
+    /// for a stream dropped before the end.
+    pub fn on_response<F>(mut self, callback: F) -> Self
+    where
+        F: FnOnce(&ResponsesResponse) + Send + 'static,
+    {
+        self.on_response = Some(Box::new(callback));
+        self
+    }
+
     /// Set a callback to be invoked when the stream completes
     pub fn on_complete<F>(mut self, callback: F) -> Self
     where
@@ -389,6 +794,19 @@ impl ResponsesTokenStreamBuilder {
             ResponsesTokenStream::new(self.model, self.content, self.latency, self.usage);
         stream.include_reasoning = self.include_reasoning;
         stream.reasoning_summary = self.reasoning_summary;
+        stream.encrypt_reasoning = self.encrypt_reasoning;
+        stream.truncated = self.truncated;
+        stream.refusal = self.refusal;
+        stream.annotations = self.annotations;
+        stream.mcp_items = self.mcp_items;
+        stream.code_interpreter_call = self.code_interpreter_call;
+        stream.obfuscate = self.obfuscate;
+        stream.usage_updates_every = self.usage_updates_every;
+        if let Some(sleeper) = self.sleeper {
+            stream.sleeper = sleeper;
+        }
+        stream.watchdog = self.watchdog;
+        stream.on_response = self.on_response;
         if let Some(on_complete) = self.on_complete {
             stream = stream.with_on_complete(on_complete);
         }
@@ -431,6 +849,238 @@ mod tests {
         assert!(events.last().unwrap().contains("response.completed"));
     }
 
+    #[tokio::test]
+    async fn test_responses_stream_truncated() {
+        let stream = ResponsesTokenStreamBuilder::new("gpt-5", "Hello")
+            .latency(LatencyProfile::instant())
+            .truncated(true)
+            .build();
+
+        let events: Vec<String> = stream.into_stream().collect().await;
+        let last = events.last().unwrap();
+        assert!(last.starts_with("event: response.incomplete"));
+        assert!(last.contains("\"status\":\"incomplete\""));
+        assert!(last.contains("\"reason\":\"max_output_tokens\""));
+        assert!(!events.iter().any(|e| e.contains("response.completed")));
+    }
+
+    #[tokio::test]
+    async fn test_responses_stream_obfuscation() {
+        let deltas = |obfuscate: bool| async move {
+            let stream = ResponsesTokenStreamBuilder::new("gpt-5", "Hello there world")
+                .latency(LatencyProfile::instant())
+                .obfuscation(obfuscate)
+                .build();
+            let events: Vec<String> = stream.into_stream().collect().await;
+            events
+                .iter()
+                .filter(|e| e.starts_with("event: response.output_text.delta"))
+                .map(|e| {
+                    serde_json::from_str::<serde_json::Value>(e.split("data: ").nth(1).unwrap())
+                        .unwrap()
+                })
+                .collect::<Vec<_>>()
+        };
+
+        for delta in deltas(true).await {
+            let text = delta["delta"].as_str().unwrap().chars().count();
+            let padding = delta["obfuscation"].as_str().unwrap().chars().count();
+            assert!(padding > 0);
+            assert_eq!((text + padding) % 16, 0);
+        }
+        assert!(deltas(false)
+            .await
+            .iter()
+            .all(|d| d.get("obfuscation").is_none()));
+    }
+
+    #[tokio::test]
+    async fn test_responses_stream_refusal() {
+        let stream = ResponsesTokenStreamBuilder::new("gpt-5", "I can't help")
+            .latency(LatencyProfile::instant())
+            .refusal(true)
+            .build();
+        let events: Vec<String> = stream.into_stream().collect().await;
+
+        assert!(!events.iter().any(|e| e.contains("output_text.delta")));
+        assert_eq!(
+            events
+                .iter()
+                .filter(|e| e.starts_with("event: response.refusal.delta"))
+                .count(),
+            5
+        );
+        let done = events
+            .iter()
+            .find(|e| e.starts_with("event: response.refusal.done"))
+            .unwrap();
+        assert!(done.contains(r#""refusal":"I can't help""#));
+
+        let completed: serde_json::Value =
+            serde_json::from_str(events.last().unwrap().split("data: ").nth(1).unwrap()).unwrap();
+        let part = &completed["response"]["output"][0]["content"][0];
+        assert_eq!(part["type"], "refusal");
+        assert_eq!(part["refusal"], "I can't help");
+        assert!(completed["response"]["output_text"].is_null());
+    }
+
+    #[tokio::test]
+    async fn test_responses_stream_annotations() {
+        let annotations = vec![
+            serde_json::json!({"type": "url_citation", "start_index": 0, "end_index": 3,
+                "url": "https://example.com", "title": "Example"}),
+            serde_json::json!({"type": "file_citation", "file_id": "file-1",
+                "filename": "a.pdf", "index": 7}),
+        ];
+        let stream = ResponsesTokenStreamBuilder::new("gpt-5", "one two three")
+            .latency(LatencyProfile::instant())
+            .annotations(annotations.clone())
+            .build();
+        let events: Vec<String> = stream.into_stream().collect().await;
+        let names: Vec<&str> = events
+            .iter()
+            .filter_map(|e| e.lines().next()?.strip_prefix("event: "))
+            .filter(|n| n.starts_with("response.output_text"))
+            .collect();
+
+        // Each annotation follows the delta completing the word it cites
+        assert_eq!(
+            names,
+            [
+                "response.output_text.delta",
+                "response.output_text.annotation.added",
+                "response.output_text.delta",
+                "response.output_text.delta",
+                "response.output_text.annotation.added",
+                "response.output_text.delta",
+                "response.output_text.delta",
+                "response.output_text.done",
+            ]
+        );
+        let completed: serde_json::Value =
+            serde_json::from_str(events.last().unwrap().split("data: ").nth(1).unwrap()).unwrap();
+        assert_eq!(
+            completed["response"]["output"][0]["content"][0]["annotations"],
+            serde_json::Value::from(annotations)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_responses_stream_code_interpreter_call() {
+        let call = crate::code_interpreter::CodeRun::for_prompt("hi there").into_item(None, false);
+        let stream = ResponsesTokenStreamBuilder::new("gpt-5", "Done")
+            .latency(LatencyProfile::instant())
+            .code_interpreter_call(Some(call))
+            .build();
+        let events: Vec<String> = stream.into_stream().collect().await;
+        let names: Vec<&str> = events
+            .iter()
+            .filter_map(|e| e.lines().next()?.strip_prefix("event: "))
+            .filter(|n| !n.ends_with("code.delta"))
+            .collect();
+
+        assert_eq!(
+            names[..8],
+            [
+                "response.created",
+                "response.in_progress",
+                "response.output_item.added",
+                "response.code_interpreter_call.in_progress",
+                "response.code_interpreter_call_code.done",
+                "response.code_interpreter_call.interpreting",
+                "response.code_interpreter_call.completed",
+                "response.output_item.done",
+            ]
+        );
+        let completed: serde_json::Value =
+            serde_json::from_str(events.last().unwrap().split("data: ").nth(1).unwrap()).unwrap();
+        let output = &completed["response"]["output"];
+        assert_eq!(output[0]["type"], "code_interpreter_call");
+        assert_eq!(output[0]["status"], "completed");
+        assert_eq!(output[1]["type"], "message");
+        // The message follows the call at the next output index
+        assert!(events
+            .iter()
+            .any(|e| e.starts_with("event: response.output_text.delta")
+                && e.contains("\"output_index\":1")));
+    }
+
+    #[tokio::test]
+    async fn test_responses_stream_mcp_items() {
+        let items = vec![
+            OutputItem::McpListTools {
+                id: "mcpl_1".to_string(),
+                server_label: "docs".to_string(),
+                tools: Vec::new(),
+                error: None,
+            },
+            OutputItem::McpApprovalRequest {
+                id: "mcpr_1".to_string(),
+                server_label: "docs".to_string(),
+                name: "search".to_string(),
+                arguments: "{}".to_string(),
+            },
+        ];
+        let stream = ResponsesTokenStreamBuilder::new("gpt-5", "Done")
+            .latency(LatencyProfile::instant())
+            .mcp_items(items)
+            .build();
+        let events: Vec<String> = stream.into_stream().collect().await;
+        let names: Vec<&str> = events
+            .iter()
+            .filter_map(|e| e.lines().next()?.strip_prefix("event: "))
+            .collect();
+
+        assert_eq!(
+            names[2..8],
+            [
+                "response.output_item.added",
+                "response.mcp_list_tools.in_progress",
+                "response.mcp_list_tools.completed",
+                "response.output_item.done",
+                "response.output_item.added",
+                "response.output_item.done",
+            ]
+        );
+        assert!(events
+            .iter()
+            .any(|e| e.starts_with("event: response.output_text.delta")
+                && e.contains("\"output_index\":2")));
+    }
+
+    #[tokio::test]
+    async fn test_responses_stream_usage_updates() {
+        let usage = ResponsesUsage {
+            input_tokens: 5,
+            output_tokens: 22,
+            total_tokens: 27,
+            output_tokens_details: None,
+        };
+        // Eleven deltas: six words and the spaces between them
+        let stream = ResponsesTokenStreamBuilder::new("gpt-5", "one two three four five six")
+            .latency(LatencyProfile::instant())
+            .usage(usage)
+            .usage_updates(Some(4))
+            .build();
+        let events: Vec<String> = stream.into_stream().collect().await;
+        let updates: Vec<serde_json::Value> = events
+            .iter()
+            .filter(|e| e.starts_with("event: response.in_progress"))
+            .map(|e| serde_json::from_str(e.split("data: ").nth(1).unwrap()).unwrap())
+            .collect();
+
+        // The first in_progress precedes any output and has no usage yet
+        assert_eq!(updates.len(), 3);
+        assert!(updates[0]["response"]["usage"].is_null());
+        let output: Vec<_> = updates[1..]
+            .iter()
+            .map(|u| u["response"]["usage"]["output_tokens"].as_u64().unwrap())
+            .collect();
+        assert_eq!(output, [8, 16]);
+        assert_eq!(updates[2]["response"]["usage"]["total_tokens"], 21);
+        assert!(updates[1]["sequence_number"].as_u64() < updates[2]["sequence_number"].as_u64());
+    }
+
     #[tokio::test]
     async fn test_responses_stream_deltas() {
         let usage = ResponsesUsage {
@@ -0,0 +1,413 @@
+// Payload Schema Module
+// Bundled JSON Schemas for the streamed payloads llmsim emits, and the
+// validator behind `--strict-schema`.
+//
+// Decision: the validator implements only the JSON Schema keywords the
+// bundled schemas use (`type`, `const`, `enum`, `properties`, `required`,
+// `additionalProperties`, `items`, `anyOf`/`oneOf`, local `$ref`, `minimum`)
+// instead of pulling in a general-purpose schema crate. The schemas are
+// closed (`additionalProperties: false`), so a field added to an event
+// without updating its schema fails just like a missing one. Union errors
+// are reported against the branch whose `type` const matches the payload,
+// which is how event unions are keyed, so a violation names the offending
+// field rather than "matched no branch".
+
+use crate::stats::EndpointType;
+use futures_core::Stream;
+use futures_util::StreamExt;
+use serde_json::Value;
+use std::fmt;
+use std::sync::OnceLock;
+
+/// A streamed payload format with a bundled schema
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PayloadSchema {
+    /// Chat Completions `chat.completion.chunk`
+    ChatCompletionChunk,
+    /// OpenAI Responses API stream events
+    ResponsesStreamEvent,
+}
+
+/// Where and how a payload departs from its schema
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaViolation {
+    /// JSON Pointer to the offending value (`""` for the payload itself)
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for SchemaViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = if self.path.is_empty() {
+            "/"
+        } else {
+            &self.path
+        };
+        write!(f, "{}: {}", path, self.message)
+    }
+}
+
+impl std::error::Error for SchemaViolation {}
+
+impl PayloadSchema {
+    /// Schema for the SSE payloads an endpoint streams, if one is bundled
+    pub fn for_endpoint(endpoint: EndpointType) -> Option<Self> {
+        match endpoint {
+            EndpointType::ChatCompletions => Some(Self::ChatCompletionChunk),
+            EndpointType::Responses | EndpointType::WebSocketResponses => {
+                Some(Self::ResponsesStreamEvent)
+            }
+            EndpointType::Messages | EndpointType::Images | EndpointType::Grpc => None,
+        }
+    }
+
+    /// The parsed schema document
+    pub fn schema(self) -> &'static Value {
+        static CHUNK: OnceLock<Value> = OnceLock::new();
+        static RESPONSES: OnceLock<Value> = OnceLock::new();
+        let (cell, source) = match self {
+            Self::ChatCompletionChunk => {
+                (&CHUNK, include_str!("schemas/chat_completion_chunk.json"))
+            }
+            Self::ResponsesStreamEvent => (
+                &RESPONSES,
+                include_str!("schemas/responses_stream_event.json"),
+            ),
+        };
+        cell.get_or_init(|| serde_json::from_str(source).expect("bundled schema is valid JSON"))
+    }
+
+    /// Check one JSON payload
+    pub fn validate(self, payload: &Value) -> Result<(), SchemaViolation> {
+        let root = self.schema();
+        check(root, root, payload, &mut String::new())
+    }
+
+    /// Check every `data:` payload of an SSE event; `[DONE]` is accepted
+    pub fn validate_sse(self, event: &str) -> Result<(), SchemaViolation> {
+        for data in event.lines().filter_map(|l| l.strip_prefix("data: ")) {
+            if data == "[DONE]" {
+                continue;
+            }
+            let payload: Value = serde_json::from_str(data).map_err(|e| SchemaViolation {
+                path: String::new(),
+                message: format!("payload is not JSON: {}", e),
+            })?;
+            self.validate(&payload)?;
+        }
+        Ok(())
+    }
+}
+
+/// Pass SSE events through until one violates `schema`. That event is
+/// replaced by an `error` event describing the violation, and the stream
+/// ends there so the drift cannot go unnoticed.
+pub fn validate_stream<S>(mut events: S, schema: PayloadSchema) -> impl Stream<Item = String> + Send
+where
+    S: Stream<Item = String> + Send + Unpin,
+{
+    async_stream::stream! {
+        while let Some(event) = events.next().await {
+            match schema.validate_sse(&event) {
+                Ok(()) => yield event,
+                Err(err) => {
+                    tracing::error!(schema = ?schema, %err, event = %event.trim_end(), "Emitted payload violates its schema");
+                    yield violation_event(&err);
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// SSE `error` event reporting a schema violation
+fn violation_event(err: &SchemaViolation) -> String {
+    let body = serde_json::json!({
+        "error": {
+            "type": "llmsim_schema_violation",
+            "message": format!("llmsim emitted a payload that violates its schema: {}", err),
+        }
+    });
+    format!("event: error\ndata: {}\n\n", body)
+}
+
+fn violation(path: &str, message: impl Into<String>) -> SchemaViolation {
+    SchemaViolation {
+        path: path.to_string(),
+        message: message.into(),
+    }
+}
+
+/// Resolve a local `#/$defs/...` reference
+fn resolve<'a>(root: &'a Value, schema: &'a Value) -> &'a Value {
+    static UNRESOLVED: Value = Value::Bool(false);
+    match schema.get("$ref").and_then(Value::as_str) {
+        Some(pointer) => root
+            .pointer(pointer.trim_start_matches('#'))
+            .unwrap_or(&UNRESOLVED),
+        None => schema,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(n) if n.is_i64() || n.is_u64() => "integer",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn has_type(value: &Value, expected: &str) -> bool {
+    let actual = type_name(value);
+    actual == expected || (expected == "number" && actual == "integer")
+}
+
+fn check(
+    root: &Value,
+    schema: &Value,
+    value: &Value,
+    path: &mut String,
+) -> Result<(), SchemaViolation> {
+    let schema = resolve(root, schema);
+    let Some(keywords) = schema.as_object() else {
+        return match schema {
+            Value::Bool(false) => Err(violation(path, "no value is allowed here")),
+            _ => Ok(()),
+        };
+    };
+
+    if let Some(expected) = keywords.get("type") {
+        let ok = match expected {
+            Value::String(t) => has_type(value, t),
+            Value::Array(ts) => ts
+                .iter()
+                .filter_map(Value::as_str)
+                .any(|t| has_type(value, t)),
+            _ => true,
+        };
+        if !ok {
+            return Err(violation(
+                path,
+                format!("expected {}, got {}", expected, type_name(value)),
+            ));
+        }
+    }
+    if let Some(expected) = keywords.get("const") {
+        if value != expected {
+            return Err(violation(
+                path,
+                format!("expected {}, got {}", expected, value),
+            ));
+        }
+    }
+    if let Some(Value::Array(allowed)) = keywords.get("enum") {
+        if !allowed.contains(value) {
+            return Err(violation(
+                path,
+                format!("{} is not one of {:?}", value, allowed),
+            ));
+        }
+    }
+    if let (Some(min), Some(n)) = (
+        keywords.get("minimum").and_then(Value::as_f64),
+        value.as_f64(),
+    ) {
+        if n < min {
+            return Err(violation(
+                path,
+                format!("{} is below the minimum {}", n, min),
+            ));
+        }
+    }
+    if let Some(branches) = keywords
+        .get("anyOf")
+        .or_else(|| keywords.get("oneOf"))
+        .and_then(Value::as_array)
+    {
+        check_union(root, branches, value, path)?;
+    }
+
+    if let Value::Object(fields) = value {
+        let properties = keywords.get("properties").and_then(Value::as_object);
+        if let Some(Value::Array(required)) = keywords.get("required") {
+            for name in required.iter().filter_map(Value::as_str) {
+                if !fields.contains_key(name) {
+                    return Err(violation(
+                        path,
+                        format!("missing required field `{}`", name),
+                    ));
+                }
+            }
+        }
+        for (name, field) in fields {
+            let field_schema = match properties.and_then(|p| p.get(name)) {
+                Some(s) => s,
+                None => match keywords.get("additionalProperties") {
+                    Some(s) => s,
+                    None => continue,
+                },
+            };
+            let len = path.len();
+            path.push('/');
+            path.push_str(name);
+            if matches!(field_schema, Value::Bool(false)) {
+                return Err(violation(path, "unexpected field"));
+            }
+            check(root, field_schema, field, path)?;
+            path.truncate(len);
+        }
+    }
+
+    if let (Value::Array(elements), Some(items)) = (value, keywords.get("items")) {
+        for (i, element) in elements.iter().enumerate() {
+            let len = path.len();
+            path.push_str(&format!("/{}", i));
+            check(root, items, element, path)?;
+            path.truncate(len);
+        }
+    }
+
+    Ok(())
+}
+
+/// Accept the value if any branch does; otherwise report the branch keyed
+/// by the value's `type`, so the error points inside the matching shape
+fn check_union(
+    root: &Value,
+    branches: &[Value],
+    value: &Value,
+    path: &mut String,
+) -> Result<(), SchemaViolation> {
+    let mut keyed = None;
+    for branch in branches {
+        let len = path.len();
+        match check(root, branch, value, path) {
+            Ok(()) => return Ok(()),
+            Err(err) => {
+                path.truncate(len);
+                let tag = resolve(root, branch).pointer("/properties/type/const");
+                if tag.is_some() && tag == value.get("type") {
+                    keyed = Some(err);
+                }
+            }
+        }
+    }
+    Err(keyed.unwrap_or_else(|| match value.get("type") {
+        Some(tag) => violation(path, format!("unknown type {}", tag)),
+        None => violation(path, "does not match any allowed shape"),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn chunk() -> Value {
+        json!({
+            "id": "chatcmpl-1",
+            "object": "chat.completion.chunk",
+            "created": 1,
+            "model": "gpt-5",
+            "system_fingerprint": "fp_llmsim",
+            "choices": [{"index": 0, "delta": {"role": "assistant"}}]
+        })
+    }
+
+    #[test]
+    fn test_valid_chunk() {
+        assert_eq!(
+            PayloadSchema::ChatCompletionChunk.validate(&chunk()),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_unexpected_field_is_reported_with_path() {
+        let mut payload = chunk();
+        payload["choices"][0]["delta"]["colour"] = json!("red");
+        let err = PayloadSchema::ChatCompletionChunk
+            .validate(&payload)
+            .unwrap_err();
+        assert_eq!(err.path, "/choices/0/delta/colour");
+        assert_eq!(err.message, "unexpected field");
+    }
+
+    #[test]
+    fn test_missing_and_mistyped_fields() {
+        let mut payload = chunk();
+        payload.as_object_mut().unwrap().remove("model");
+        let err = PayloadSchema::ChatCompletionChunk
+            .validate(&payload)
+            .unwrap_err();
+        assert_eq!(err.message, "missing required field `model`");
+
+        let mut payload = chunk();
+        payload["created"] = json!("now");
+        let err = PayloadSchema::ChatCompletionChunk
+            .validate(&payload)
+            .unwrap_err();
+        assert_eq!(err.path, "/created");
+    }
+
+    #[test]
+    fn test_union_error_names_the_keyed_branch() {
+        let event = json!({
+            "type": "response.output_text.delta",
+            "output_index": 0,
+            "content_index": 0,
+            "item_id": "msg_1",
+            "delta": 7,
+            "logprobs": [],
+            "sequence_number": 3
+        });
+        let err = PayloadSchema::ResponsesStreamEvent
+            .validate(&event)
+            .unwrap_err();
+        assert_eq!(err.path, "/delta");
+
+        let err = PayloadSchema::ResponsesStreamEvent
+            .validate(&json!({"type": "response.teleported", "sequence_number": 0}))
+            .unwrap_err();
+        assert!(err.message.contains("unknown type"));
+    }
+
+    #[tokio::test]
+    async fn test_validate_stream_stops_at_violation() {
+        let good = format!("data: {}\n\n", chunk());
+        let mut bad = chunk();
+        bad["object"] = json!("chat.completion");
+        let events = futures_util::stream::iter(vec![
+            good.clone(),
+            format!("data: {}\n\n", bad),
+            good.clone(),
+        ]);
+
+        let out: Vec<String> = validate_stream(events, PayloadSchema::ChatCompletionChunk)
+            .collect()
+            .await;
+        assert_eq!(out.len(), 2);
+        assert_eq!(out[0], good);
+        assert!(out[1].starts_with("event: error\n"));
+        assert!(out[1].contains("llmsim_schema_violation"));
+        assert!(out[1].contains("/object"));
+    }
+
+    #[test]
+    fn test_validate_sse() {
+        let event = format!("data: {}\n\n", chunk());
+        assert!(PayloadSchema::ChatCompletionChunk
+            .validate_sse(&event)
+            .is_ok());
+        assert!(PayloadSchema::ChatCompletionChunk
+            .validate_sse("data: [DONE]\n\n")
+            .is_ok());
+        assert!(PayloadSchema::ChatCompletionChunk
+            .validate_sse("data: {nope\n\n")
+            .is_err());
+    }
+}
@@ -0,0 +1,509 @@
+// Schema Faker Module
+// Plausible JSON values for a JSON Schema, for the arguments of simulated
+// tool calls: strings, enums, numbers within their bounds, nested objects and
+// arrays, with faker-style strings chosen by format and property name.
+//
+// Decision: the walker covers the schema keywords function definitions use
+// in practice (`type`, `enum`, `const`, `properties`, `required`, `items`,
+// bounds, `anyOf`/`oneOf`/`allOf`, local `$ref`s) and ignores the rest, so an
+// unusual schema yields a value of the right shape rather than an error.
+// Property names steer string values ("email", "city", "query"), since a
+// client under test more often reads `location` than validates it, and
+// realistic values make transcripts readable. All randomness comes from the
+// caller's RNG, so a seeded RNG reproduces the same arguments.
+
+use rand::prelude::IndexedRandom;
+use rand::RngExt;
+use serde_json::{Map, Value};
+
+/// Nesting beyond which objects and arrays are left empty, against
+/// recursive `$ref`s
+const MAX_DEPTH: usize = 8;
+
+const FIRST_NAMES: &[&str] = &[
+    "Ada", "Grace", "Alan", "Linus", "Margaret", "Dennis", "Barbara", "Ken", "Radia", "Edsger",
+];
+const LAST_NAMES: &[&str] = &[
+    "Lovelace", "Hopper", "Turing", "Torvalds", "Hamilton", "Ritchie", "Liskov", "Thompson",
+    "Perlman", "Dijkstra",
+];
+const CITIES: &[&str] = &[
+    "Paris", "Tokyo", "Lisbon", "Toronto", "Nairobi", "Sydney", "Berlin", "Seoul", "Austin", "Kyiv",
+];
+const COUNTRIES: &[&str] = &[
+    "France",
+    "Japan",
+    "Portugal",
+    "Canada",
+    "Kenya",
+    "Australia",
+    "Germany",
+    "Ukraine",
+];
+const STREETS: &[&str] = &[
+    "Main St", "Oak Ave", "Elm St", "Park Rd", "Maple Dr", "Cedar Ln",
+];
+const COMPANIES: &[&str] = &[
+    "Acme Corp",
+    "Globex",
+    "Initech",
+    "Umbrella",
+    "Hooli",
+    "Stark Industries",
+];
+const DOMAINS: &[&str] = &["example.com", "example.org", "example.net"];
+const WORDS: &[&str] = &[
+    "weather",
+    "report",
+    "quarterly",
+    "invoice",
+    "meeting",
+    "summary",
+    "project",
+    "update",
+    "release",
+    "forecast",
+    "budget",
+    "schedule",
+    "order",
+    "status",
+    "review",
+    "draft",
+];
+const LANGUAGES: &[&str] = &["en", "fr", "de", "ja", "es", "pt", "uk"];
+const CURRENCIES: &[&str] = &["USD", "EUR", "JPY", "GBP", "CAD", "UAH"];
+const UNITS: &[&str] = &["celsius", "fahrenheit"];
+
+/// A value satisfying `schema`, drawn from `rng`
+pub fn fake_value(schema: &Value, rng: &mut impl rand::Rng) -> Value {
+    Faker { root: schema }.value(schema, None, 0, rng)
+}
+
+/// Arguments for a function whose `parameters` are `schema`: always an
+/// object, as tool calls require
+pub fn fake_arguments(schema: Option<&Value>, rng: &mut impl rand::Rng) -> Value {
+    match schema.map(|schema| fake_value(schema, rng)) {
+        Some(value @ Value::Object(_)) => value,
+        _ => Value::Object(Map::new()),
+    }
+}
+
+struct Faker<'a> {
+    /// The whole schema, which `$ref`s point into
+    root: &'a Value,
+}
+
+impl<'a> Faker<'a> {
+    /// A value for `schema`, found under property `name` if any
+    fn value(
+        &self,
+        schema: &'a Value,
+        name: Option<&str>,
+        depth: usize,
+        rng: &mut impl rand::Rng,
+    ) -> Value {
+        let Some(object) = schema.as_object() else {
+            // `true` or an empty schema: anything goes
+            return Value::String(fake_string(name, None, rng));
+        };
+        if let Some(target) = object.get("$ref").and_then(Value::as_str) {
+            return match self.resolve(target) {
+                Some(schema) if depth < MAX_DEPTH => self.value(schema, name, depth + 1, rng),
+                _ => Value::Null,
+            };
+        }
+        if let Some(value) = object.get("const") {
+            return value.clone();
+        }
+        if let Some(choices) = object.get("enum").and_then(Value::as_array) {
+            if let Some(choice) = choices.choose(rng) {
+                return choice.clone();
+            }
+        }
+        for keyword in ["anyOf", "oneOf"] {
+            if let Some(choice) = object
+                .get(keyword)
+                .and_then(Value::as_array)
+                .and_then(|options| options.choose(rng))
+            {
+                return self.value(choice, name, depth + 1, rng);
+            }
+        }
+        if let Some(parts) = object.get("allOf").and_then(Value::as_array) {
+            return self.all_of(parts, name, depth, rng);
+        }
+
+        match schema_type(object) {
+            "object" => self.object(object, depth, rng),
+            "array" => self.array(object, name, depth, rng),
+            "integer" => integer(object, rng),
+            "number" => number(object, rng),
+            "boolean" => Value::Bool(rng.random_bool(0.5)),
+            "null" => Value::Null,
+            _ => Value::String(string(object, name, rng)),
+        }
+    }
+
+    /// A local reference such as `#/$defs/Address`
+    fn resolve(&self, target: &str) -> Option<&'a Value> {
+        let pointer = target.strip_prefix('#')?;
+        self.root.pointer(pointer)
+    }
+
+    /// Every part's value, object parts merged into one object
+    fn all_of(
+        &self,
+        parts: &'a [Value],
+        name: Option<&str>,
+        depth: usize,
+        rng: &mut impl rand::Rng,
+    ) -> Value {
+        let mut merged = Map::new();
+        let mut last = Value::Null;
+        for part in parts {
+            match self.value(part, name, depth + 1, rng) {
+                Value::Object(fields) => merged.extend(fields),
+                other => last = other,
+            }
+        }
+        if merged.is_empty() {
+            last
+        } else {
+            Value::Object(merged)
+        }
+    }
+
+    /// Every required property, and each optional one half the time
+    fn object(
+        &self,
+        object: &'a Map<String, Value>,
+        depth: usize,
+        rng: &mut impl rand::Rng,
+    ) -> Value {
+        let mut fields = Map::new();
+        if depth >= MAX_DEPTH {
+            return Value::Object(fields);
+        }
+        let required: Vec<&str> = object
+            .get("required")
+            .and_then(Value::as_array)
+            .map(|names| names.iter().filter_map(Value::as_str).collect())
+            .unwrap_or_default();
+        if let Some(properties) = object.get("properties").and_then(Value::as_object) {
+            for (key, schema) in properties {
+                if required.contains(&key.as_str()) || rng.random_bool(0.5) {
+                    fields.insert(key.clone(), self.value(schema, Some(key), depth + 1, rng));
+                }
+            }
+        }
+        Value::Object(fields)
+    }
+
+    fn array(
+        &self,
+        object: &'a Map<String, Value>,
+        name: Option<&str>,
+        depth: usize,
+        rng: &mut impl rand::Rng,
+    ) -> Value {
+        if depth >= MAX_DEPTH {
+            return Value::Array(Vec::new());
+        }
+        let min = bound(object, "minItems").unwrap_or(1.0) as usize;
+        let max = (bound(object, "maxItems").unwrap_or(3.0) as usize).max(min);
+        let count = rng.random_range(min..=max);
+        let items = object.get("items").unwrap_or(&Value::Bool(true));
+        Value::Array(
+            (0..count)
+                .map(|_| self.value(items, name, depth + 1, rng))
+                .collect(),
+        )
+    }
+}
+
+/// The schema's type, the first non-null one if it lists several, or one
+/// inferred from the keywords present
+fn schema_type(object: &Map<String, Value>) -> &str {
+    match object.get("type") {
+        Some(Value::String(kind)) => kind,
+        Some(Value::Array(kinds)) => kinds
+            .iter()
+            .filter_map(Value::as_str)
+            .find(|kind| *kind != "null")
+            .unwrap_or("null"),
+        _ if object.contains_key("properties") => "object",
+        _ if object.contains_key("items") => "array",
+        _ => "string",
+    }
+}
+
+fn bound(object: &Map<String, Value>, keyword: &str) -> Option<f64> {
+    object.get(keyword).and_then(Value::as_f64)
+}
+
+/// Lower and upper bounds from `minimum`/`maximum` and their exclusive
+/// forms, or `default` when neither is set
+fn range(object: &Map<String, Value>, step: f64, default: (f64, f64)) -> (f64, f64) {
+    let low =
+        bound(object, "minimum").or_else(|| bound(object, "exclusiveMinimum").map(|v| v + step));
+    let high =
+        bound(object, "maximum").or_else(|| bound(object, "exclusiveMaximum").map(|v| v - step));
+    match (low, high) {
+        (Some(low), Some(high)) => (low, high.max(low)),
+        (Some(low), None) => (low, low + (default.1 - default.0)),
+        (None, Some(high)) => ((high - (default.1 - default.0)).min(high), high),
+        (None, None) => default,
+    }
+}
+
+fn integer(object: &Map<String, Value>, rng: &mut impl rand::Rng) -> Value {
+    let (low, high) = range(object, 1.0, (1.0, 100.0));
+    let (low, high) = (low.ceil() as i64, high.floor() as i64);
+    let mut value = rng.random_range(low..=high.max(low));
+    if let Some(step) = bound(object, "multipleOf")
+        .map(|s| s as i64)
+        .filter(|s| *s > 0)
+    {
+        value -= value.rem_euclid(step);
+        if value < low {
+            value += step;
+        }
+    }
+    Value::from(value)
+}
+
+fn number(object: &Map<String, Value>, rng: &mut impl rand::Rng) -> Value {
+    let (low, high) = range(object, 0.01, (0.0, 1000.0));
+    let value = if high > low {
+        rng.random_range(low..high)
+    } else {
+        low
+    };
+    // Two decimals, as a person or a model would write it
+    let rounded = (value * 100.0).round() / 100.0;
+    Value::from(rounded.clamp(low, high.max(low)))
+}
+
+fn string(object: &Map<String, Value>, name: Option<&str>, rng: &mut impl rand::Rng) -> String {
+    let format = object.get("format").and_then(Value::as_str);
+    let mut text = fake_string(name, format, rng);
+    let min = bound(object, "minLength").unwrap_or(0.0) as usize;
+    let max = bound(object, "maxLength").map(|m| m as usize);
+    while text.chars().count() < min {
+        text.push_str(WORDS.choose(rng).unwrap_or(&"x"));
+    }
+    if let Some(max) = max {
+        text = text.chars().take(max.max(min)).collect();
+    }
+    text
+}
+
+/// A string for a `format`, else one suited to the property name
+fn fake_string(name: Option<&str>, format: Option<&str>, rng: &mut impl rand::Rng) -> String {
+    let pick = |list: &[&str], rng: &mut _| list.choose(rng).copied().unwrap_or("").to_string();
+    match format {
+        Some("date-time") => {
+            return format!(
+                "2025-{:02}-{:02}T{:02}:{:02}:00Z",
+                rng.random_range(1..=12),
+                rng.random_range(1..=28),
+                rng.random_range(0..24),
+                rng.random_range(0..60)
+            )
+        }
+        Some("date") => {
+            return format!(
+                "2025-{:02}-{:02}",
+                rng.random_range(1..=12),
+                rng.random_range(1..=28)
+            )
+        }
+        Some("time") => {
+            return format!(
+                "{:02}:{:02}:00",
+                rng.random_range(0..24),
+                rng.random_range(0..60)
+            )
+        }
+        Some("email") => return email(rng),
+        Some("uri") | Some("url") => return url(rng),
+        Some("uuid") => return uuid(rng),
+        Some("hostname") => return format!("api.{}", pick(DOMAINS, rng)),
+        Some("ipv4") => return format!("192.0.2.{}", rng.random_range(1..255)),
+        _ => {}
+    }
+
+    let name = name.unwrap_or("").to_lowercase();
+    let has = |words: &[&str]| words.iter().any(|word| name.contains(word));
+    if has(&["email"]) {
+        email(rng)
+    } else if has(&["url", "uri", "link", "website"]) {
+        url(rng)
+    } else if has(&["uuid", "guid"]) || name == "id" || name.ends_with("_id") {
+        uuid(rng)
+    } else if has(&["first_name", "firstname", "given"]) {
+        pick(FIRST_NAMES, rng)
+    } else if has(&["last_name", "lastname", "surname", "family"]) {
+        pick(LAST_NAMES, rng)
+    } else if has(&["company", "organization", "org"]) {
+        pick(COMPANIES, rng)
+    } else if has(&["name", "user", "author", "assignee"]) {
+        format!("{} {}", pick(FIRST_NAMES, rng), pick(LAST_NAMES, rng))
+    } else if has(&["city", "location", "place", "destination", "origin"]) {
+        pick(CITIES, rng)
+    } else if has(&["country"]) {
+        pick(COUNTRIES, rng)
+    } else if has(&["address", "street"]) {
+        format!("{} {}", rng.random_range(1..999), pick(STREETS, rng))
+    } else if has(&["zip", "postal"]) {
+        format!("{:05}", rng.random_range(10000..99999))
+    } else if has(&["phone"]) {
+        format!("+1-555-{:04}", rng.random_range(0..10000))
+    } else if has(&["lang", "locale"]) {
+        pick(LANGUAGES, rng)
+    } else if has(&["currency"]) {
+        pick(CURRENCIES, rng)
+    } else if has(&["unit"]) {
+        pick(UNITS, rng)
+    } else if has(&["date", "day"]) {
+        fake_string(None, Some("date"), rng)
+    } else if has(&["time"]) {
+        fake_string(None, Some("date-time"), rng)
+    } else if has(&["path", "file"]) {
+        format!("/data/{}.json", pick(WORDS, rng))
+    } else if has(&[
+        "query", "search", "prompt", "question", "text", "message", "title",
+    ]) {
+        let count = rng.random_range(2..=4);
+        let words: Vec<&str> = (0..count)
+            .filter_map(|_| WORDS.choose(rng).copied())
+            .collect();
+        words.join(" ")
+    } else {
+        pick(WORDS, rng)
+    }
+}
+
+fn email(rng: &mut impl rand::Rng) -> String {
+    let first = FIRST_NAMES.choose(rng).copied().unwrap_or("user");
+    let domain = DOMAINS.choose(rng).copied().unwrap_or("example.com");
+    format!("{}@{}", first.to_lowercase(), domain)
+}
+
+fn url(rng: &mut impl rand::Rng) -> String {
+    let domain = DOMAINS.choose(rng).copied().unwrap_or("example.com");
+    let word = WORDS.choose(rng).copied().unwrap_or("page");
+    format!("https://{}/{}", domain, word)
+}
+
+fn uuid(rng: &mut impl rand::Rng) -> String {
+    let bytes: [u8; 16] = rng.random();
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!(
+        "{}-{}-4{}-a{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[13..16],
+        &hex[17..20],
+        &hex[20..32]
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use serde_json::json;
+
+    fn weather_schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "location": {"type": "string", "description": "City name"},
+                "unit": {"type": "string", "enum": ["celsius", "fahrenheit"]},
+                "days": {"type": "integer", "minimum": 1, "maximum": 7},
+                "threshold": {"type": "number", "exclusiveMinimum": 0, "maximum": 1},
+                "include_hourly": {"type": "boolean"},
+                "contact": {"$ref": "#/$defs/contact"},
+                "tags": {"type": "array", "items": {"type": "string"}, "minItems": 2, "maxItems": 2}
+            },
+            "required": ["location", "unit", "days", "threshold", "include_hourly", "contact", "tags"],
+            "$defs": {
+                "contact": {
+                    "type": "object",
+                    "properties": {
+                        "email": {"type": "string", "format": "email"},
+                        "since": {"type": "string", "format": "date"}
+                    },
+                    "required": ["email", "since"]
+                }
+            }
+        })
+    }
+
+    #[test]
+    fn test_values_follow_the_schema() {
+        let mut rng = StdRng::seed_from_u64(3);
+        for _ in 0..50 {
+            let value = fake_value(&weather_schema(), &mut rng);
+            assert!(CITIES.contains(&value["location"].as_str().unwrap()));
+            assert!(["celsius", "fahrenheit"].contains(&value["unit"].as_str().unwrap()));
+            let days = value["days"].as_i64().unwrap();
+            assert!((1..=7).contains(&days), "{}", days);
+            let threshold = value["threshold"].as_f64().unwrap();
+            assert!(threshold > 0.0 && threshold <= 1.0, "{}", threshold);
+            assert!(value["include_hourly"].is_boolean());
+            assert!(value["contact"]["email"].as_str().unwrap().contains('@'));
+            assert_eq!(value["contact"]["since"].as_str().unwrap().len(), 10);
+            assert_eq!(value["tags"].as_array().unwrap().len(), 2);
+        }
+    }
+
+    #[test]
+    fn test_seeded_rng_repeats() {
+        let draw = |seed| fake_value(&weather_schema(), &mut StdRng::seed_from_u64(seed));
+        assert_eq!(draw(9), draw(9));
+        assert_ne!(draw(9), draw(10));
+    }
+
+    #[test]
+    fn test_unusual_schemas() {
+        let mut rng = StdRng::seed_from_u64(1);
+        assert_eq!(fake_value(&json!({"const": 42}), &mut rng), json!(42));
+        assert!(fake_value(&json!({"type": ["null", "integer"]}), &mut rng).is_i64());
+        let both = fake_value(
+            &json!({"allOf": [
+                {"type": "object", "properties": {"a": {"type": "integer"}}, "required": ["a"]},
+                {"type": "object", "properties": {"b": {"type": "boolean"}}, "required": ["b"]}
+            ]}),
+            &mut rng,
+        );
+        assert!(both["a"].is_i64() && both["b"].is_boolean());
+        let short = fake_value(&json!({"type": "string", "maxLength": 3}), &mut rng);
+        assert!(short.as_str().unwrap().chars().count() <= 3);
+        let stepped = fake_value(
+            &json!({"type": "integer", "minimum": 10, "maximum": 50, "multipleOf": 5}),
+            &mut rng,
+        );
+        assert_eq!(stepped.as_i64().unwrap() % 5, 0);
+
+        // Recursive references stop instead of overflowing
+        let tree = json!({
+            "$ref": "#/$defs/node",
+            "$defs": {"node": {
+                "type": "object",
+                "properties": {"child": {"$ref": "#/$defs/node"}},
+                "required": ["child"]
+            }}
+        });
+        assert!(fake_value(&tree, &mut rng).is_object());
+
+        // Arguments are always an object
+        assert_eq!(fake_arguments(None, &mut rng), json!({}));
+        assert_eq!(
+            fake_arguments(Some(&json!({"type": "string"})), &mut rng),
+            json!({})
+        );
+    }
+}
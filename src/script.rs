@@ -13,6 +13,7 @@
 use serde::{Deserialize, Serialize};
 use std::path::Path;
 use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
 
 /// A single scripted assistant turn.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -38,6 +39,34 @@ pub enum SimTurn {
     /// request gets the next turn (caller can retry against llmsim and
     /// see different behaviour).
     Error(SimError),
+
+    /// Require the request to carry a tool result for every call id the
+    /// previous turn handed out, then answer with `then`. A request that
+    /// omits one (or echoes a wrong id) gets an HTTP 400 instead.
+    ExpectToolResults { then: Box<SimTurn> },
+}
+
+impl SimTurn {
+    /// Split the turn into its assistant text and tool calls, or the error
+    /// it should be answered with. `expect_tool_results` turns yield the
+    /// parts of the turn they wrap.
+    pub fn into_parts(self) -> Result<(Option<String>, Vec<SimToolCall>), SimError> {
+        match self {
+            SimTurn::Assistant { text } => Ok((Some(text), Vec::new())),
+            SimTurn::ToolCalls { calls } => Ok((None, calls)),
+            SimTurn::Mixed { text, calls } => Ok((Some(text), calls)),
+            SimTurn::Error(err) => Err(err),
+            SimTurn::ExpectToolResults { then } => then.into_parts(),
+        }
+    }
+
+    fn has_tool_calls(&self) -> bool {
+        match self {
+            SimTurn::ToolCalls { .. } | SimTurn::Mixed { .. } => true,
+            SimTurn::ExpectToolResults { then } => then.has_tool_calls(),
+            SimTurn::Assistant { .. } | SimTurn::Error(_) => false,
+        }
+    }
 }
 
 /// A single tool call inside a scripted turn.
@@ -135,6 +164,8 @@ pub enum ScriptError {
     Parse(String),
     #[error("Script must contain at least one turn")]
     Empty,
+    #[error("Invalid script: {0}")]
+    Invalid(String),
 }
 
 /// Live, thread-safe scripted response source.
@@ -148,6 +179,9 @@ pub struct Script {
     turns: Vec<SimTurn>,
     on_exhausted: OnExhausted,
     cursor: AtomicUsize,
+    /// Call ids handed out by the most recently served turn, checked by a
+    /// following `expect_tool_results` turn.
+    issued_call_ids: Mutex<Vec<String>>,
 }
 
 /// A turn yielded by `Script::next_turn`. Includes whether the script
@@ -166,6 +200,7 @@ impl Script {
             turns,
             on_exhausted: OnExhausted::RepeatLast,
             cursor: AtomicUsize::new(0),
+            issued_call_ids: Mutex::new(Vec::new()),
         }
     }
 
@@ -173,10 +208,26 @@ impl Script {
         if spec.turns.is_empty() {
             return Err(ScriptError::Empty);
         }
+        for (i, turn) in spec.turns.iter().enumerate() {
+            let SimTurn::ExpectToolResults { then } = turn else {
+                continue;
+            };
+            if matches!(**then, SimTurn::ExpectToolResults { .. }) {
+                return Err(ScriptError::Invalid(format!(
+                    "turn {i}: expect_tool_results cannot wrap another expect_tool_results"
+                )));
+            }
+            if i == 0 || !spec.turns[i - 1].has_tool_calls() {
+                return Err(ScriptError::Invalid(format!(
+                    "turn {i}: expect_tool_results must follow a turn with tool calls"
+                )));
+            }
+        }
         Ok(Self {
             turns: spec.turns,
             on_exhausted: spec.on_exhausted,
             cursor: AtomicUsize::new(0),
+            issued_call_ids: Mutex::new(Vec::new()),
         })
     }
 
@@ -227,6 +278,48 @@ impl Script {
         }
     }
 
+    /// Like `next_turn`, but resolves an `expect_tool_results` turn against
+    /// the tool-result call ids the request carries: the wrapped turn if
+    /// every id recorded by `record_tool_call_ids` is answered, otherwise an
+    /// `invalid_request` error turn naming the expected and received ids.
+    pub fn next_turn_with_tool_results<'a>(
+        &self,
+        tool_result_ids: impl IntoIterator<Item = &'a str>,
+    ) -> ScriptedResponse {
+        let issued = std::mem::take(&mut *self.issued_call_ids.lock().unwrap());
+        let turn = match self.next_turn() {
+            ScriptedResponse::Turn(turn) => turn,
+            ScriptedResponse::Exhausted => return ScriptedResponse::Exhausted,
+        };
+        let SimTurn::ExpectToolResults { then } = turn else {
+            return ScriptedResponse::Turn(turn);
+        };
+
+        let received: Vec<&str> = tool_result_ids.into_iter().collect();
+        let missing: Vec<&str> = issued
+            .iter()
+            .map(String::as_str)
+            .filter(|id| !received.contains(id))
+            .collect();
+        if missing.is_empty() {
+            return ScriptedResponse::Turn(*then);
+        }
+        ScriptedResponse::Turn(SimTurn::Error(SimError::InvalidRequest {
+            message: format!(
+                "llmsim script expected tool results for [{}] but the request sent [{}] (missing [{}])",
+                issued.join(", "),
+                received.join(", "),
+                missing.join(", ")
+            ),
+        }))
+    }
+
+    /// Remember the call ids sent to the client for the turn just served,
+    /// so a following `expect_tool_results` turn can check them.
+    pub fn record_tool_call_ids(&self, ids: Vec<String>) {
+        *self.issued_call_ids.lock().unwrap() = ids;
+    }
+
     /// Number of turns consumed so far (for tests / debugging).
     pub fn cursor(&self) -> usize {
         self.cursor.load(Ordering::SeqCst)
@@ -304,6 +397,54 @@ mod tests {
         assert!(matches!(Script::from_json(spec), Err(ScriptError::Empty)));
     }
 
+    #[test]
+    fn expect_tool_results_must_follow_tool_calls() {
+        let spec = r#"{
+            "turns": [
+                {"type": "assistant", "text": "hi"},
+                {"type": "expect_tool_results", "then": {"type": "assistant", "text": "done"}}
+            ]
+        }"#;
+        assert!(matches!(
+            Script::from_json(spec),
+            Err(ScriptError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn expect_tool_results_checks_recorded_ids() {
+        let script = Script::new(vec![
+            SimTurn::ToolCalls {
+                calls: vec![SimToolCall {
+                    name: "a".into(),
+                    arguments: json!({}),
+                    id: None,
+                }],
+            },
+            SimTurn::ExpectToolResults {
+                then: Box::new(SimTurn::Assistant { text: "ok".into() }),
+            },
+        ])
+        .with_on_exhausted(OnExhausted::Loop);
+
+        script.next_turn_with_tool_results([]);
+        script.record_tool_call_ids(vec!["call_1".into()]);
+        assert_eq!(
+            script.next_turn_with_tool_results(["call_1"]),
+            ScriptedResponse::Turn(SimTurn::Assistant { text: "ok".into() })
+        );
+
+        script.next_turn_with_tool_results([]);
+        script.record_tool_call_ids(vec!["call_2".into()]);
+        let ScriptedResponse::Turn(SimTurn::Error(err)) =
+            script.next_turn_with_tool_results(["call_1"])
+        else {
+            panic!("expected an error turn");
+        };
+        assert_eq!(err.status_code(), 400);
+        assert!(err.message().contains("missing [call_2]"));
+    }
+
     #[test]
     fn cursor_advances_in_order() {
         let script = Script::new(vec![
@@ -4,16 +4,18 @@
 // tool calls and signal `finish_reason=tool_calls` — modelled here as a
 // dedicated builder so we can keep the simple path simple.
 
-use crate::ids::{prefixed_id, unix_timestamp};
-use crate::latency::LatencyProfile;
+use crate::ids::{new_id, unix_timestamp, IdKind};
+use crate::latency::RequestLatency;
 use crate::openai::{
-    ChatCompletionChunk, ChunkChoice, ChunkDelta, ChunkFunctionCall, ChunkToolCall, Role, Usage,
+    ChatCompletionChunk, ChunkChoice, ChunkDelta, ChunkFunctionCall, ChunkToolCall, Role,
+    ToolCallFormat, Usage, DEFAULT_SYSTEM_FINGERPRINT,
 };
 use crate::script::SimToolCall;
+use crate::sleep::{default_sleeper, Sleeper};
 use async_stream::stream;
 use futures_core::Stream;
 use std::pin::Pin;
-use tokio::time::sleep;
+use std::sync::Arc;
 
 type OnCompleteCallback = Box<dyn FnOnce() + Send + 'static>;
 
@@ -23,10 +25,13 @@ pub struct ScriptedChatStream {
     id: String,
     model: String,
     created: i64,
-    latency: LatencyProfile,
+    latency: RequestLatency,
     text: String,
     tool_calls: Vec<SimToolCall>,
     usage: Option<Usage>,
+    format: ToolCallFormat,
+    system_fingerprint: String,
+    sleeper: Arc<dyn Sleeper>,
     on_complete: Option<OnCompleteCallback>,
 }
 
@@ -35,20 +40,40 @@ impl ScriptedChatStream {
         model: impl Into<String>,
         text: String,
         tool_calls: Vec<SimToolCall>,
-        latency: LatencyProfile,
+        latency: impl Into<RequestLatency>,
     ) -> Self {
         Self {
-            id: prefixed_id("chatcmpl-"),
+            id: new_id(IdKind::ChatCompletion),
             model: model.into(),
             created: unix_timestamp(),
-            latency,
+            latency: latency.into(),
             text,
             tool_calls,
             usage: None,
+            format: ToolCallFormat::default(),
+            system_fingerprint: DEFAULT_SYSTEM_FINGERPRINT.to_string(),
+            sleeper: default_sleeper(),
             on_complete: None,
         }
     }
 
+    /// Emit tool calls in the given wire shape
+    pub fn with_tool_call_format(mut self, format: ToolCallFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    pub fn with_system_fingerprint(mut self, fingerprint: impl Into<String>) -> Self {
+        self.system_fingerprint = fingerprint.into();
+        self
+    }
+
+    /// Wait out delays with this sleeper (default: the tokio timer)
+    pub fn with_sleeper(mut self, sleeper: Arc<dyn Sleeper>) -> Self {
+        self.sleeper = sleeper;
+        self
+    }
+
     pub fn with_usage(mut self, usage: Usage) -> Self {
         self.usage = Some(usage);
         self
@@ -86,114 +111,114 @@ impl ScriptedChatStream {
     /// Render as SSE chunks for the HTTP body.
     pub fn into_stream(self) -> Pin<Box<dyn Stream<Item = String> + Send>> {
         let tokens = self.tokenize_text();
-        let id = self.id.clone();
-        let model = self.model.clone();
-        let created = self.created;
-        let latency = self.latency.clone();
+        let base = ChatCompletionChunk {
+            system_fingerprint: Some(self.system_fingerprint.clone()),
+            ..ChatCompletionChunk::new(self.id.clone(), self.model.clone(), self.created)
+        };
+        let mut latency = self.latency.clone();
         let tool_calls = self.tool_calls.clone();
         let usage = self.usage.clone();
+        let sleeper = self.sleeper;
         let on_complete = self.on_complete;
         let has_tool_calls = !tool_calls.is_empty();
+        let format = self.format;
+        let max_calls = format.max_calls();
 
         Box::pin(stream! {
             // TTFT.
-            let ttft = latency.sample_ttft();
+            let ttft = latency.ttft();
             if !ttft.is_zero() {
-                sleep(ttft).await;
+                sleeper.sleep(ttft).await;
             }
 
             // Role chunk first, as real OpenAI does.
-            let role_chunk = ChatCompletionChunk::new(id.clone(), model.clone(), created)
+            let role_chunk = base.clone()
                 .with_role();
             yield format_sse(&role_chunk);
 
             // Text deltas (if any).
             for token in tokens {
-                let tbt = latency.sample_tbt();
+                let tbt = latency.tbt();
                 if !tbt.is_zero() {
-                    sleep(tbt).await;
+                    sleeper.sleep(tbt).await;
                 }
-                let chunk = ChatCompletionChunk::new(id.clone(), model.clone(), created)
+                let chunk = base.clone()
                     .with_content(token);
                 yield format_sse(&chunk);
             }
 
-            // Tool call deltas. Each call is emitted as two chunks: an
-            // "announce" with name+id+empty args, then a single chunk
-            // streaming the full arguments JSON. This matches the
-            // shape SDKs (OpenAI Python, LangChain) parse, while
-            // staying simple — chunked-args streaming is overkill for
-            // a test fixture.
-            for (index, call) in tool_calls.iter().enumerate() {
-                let tbt = latency.sample_tbt();
+            // Tool call deltas. Each call is announced with name+id+empty
+            // args, then its arguments JSON follows in token-sized
+            // fragments, cut inside keys and string values like a real
+            // model's output, so clients must buffer until the call ends.
+            for (index, call) in tool_calls.iter().take(max_calls).enumerate() {
+                let tbt = latency.tbt();
                 if !tbt.is_zero() {
-                    sleep(tbt).await;
+                    sleeper.sleep(tbt).await;
                 }
 
-                // Announce.
-                let announce = ChatCompletionChunk {
-                    id: id.clone(),
-                    object: "chat.completion.chunk".to_string(),
-                    created,
-                    model: model.clone(),
-                    system_fingerprint: Some("fp_llmsim".to_string()),
-                    usage: None,
-                    choices: vec![ChunkChoice {
-                        index: 0,
-                        delta: ChunkDelta {
-                            role: None,
-                            content: None,
-                            tool_calls: Some(vec![ChunkToolCall {
-                                index: index as u32,
-                                id: call.id.clone(),
-                                call_type: Some("function".to_string()),
-                                function: Some(ChunkFunctionCall {
-                                    name: Some(call.name.clone()),
-                                    arguments: Some(String::new()),
-                                }),
-                            }]),
-                        },
-                        finish_reason: None,
-                        logprobs: None,
-                    }],
-                };
-                yield format_sse(&announce);
-
-                // Arguments delta (single chunk).
                 let args_str = serde_json::to_string(&call.arguments)
                     .unwrap_or_else(|_| "{}".to_string());
-                let args_chunk = ChatCompletionChunk {
-                    id: id.clone(),
-                    object: "chat.completion.chunk".to_string(),
-                    created,
-                    model: model.clone(),
-                    system_fingerprint: Some("fp_llmsim".to_string()),
-                    usage: None,
-                    choices: vec![ChunkChoice {
-                        index: 0,
-                        delta: ChunkDelta {
-                            role: None,
-                            content: None,
+                let announce = match format {
+                    ToolCallFormat::ToolCalls => ChunkDelta {
+                        tool_calls: Some(vec![ChunkToolCall {
+                            index: index as u32,
+                            id: call.id.clone(),
+                            call_type: Some("function".to_string()),
+                            function: Some(ChunkFunctionCall {
+                                name: Some(call.name.clone()),
+                                arguments: Some(String::new()),
+                            }),
+                        }]),
+                        ..ChunkDelta::default()
+                    },
+                    ToolCallFormat::FunctionCall => ChunkDelta {
+                        function_call: Some(ChunkFunctionCall {
+                            name: Some(call.name.clone()),
+                            arguments: Some(String::new()),
+                        }),
+                        ..ChunkDelta::default()
+                    },
+                };
+                yield format_sse(&delta_chunk(&base, announce));
+
+                for (n, fragment) in split_arguments(&args_str).into_iter().enumerate() {
+                    if n > 0 {
+                        let tbt = latency.tbt();
+                        if !tbt.is_zero() {
+                            sleeper.sleep(tbt).await;
+                        }
+                    }
+                    let function = Some(ChunkFunctionCall {
+                        name: None,
+                        arguments: Some(fragment),
+                    });
+                    let args = match format {
+                        ToolCallFormat::ToolCalls => ChunkDelta {
                             tool_calls: Some(vec![ChunkToolCall {
                                 index: index as u32,
                                 id: None,
                                 call_type: None,
-                                function: Some(ChunkFunctionCall {
-                                    name: None,
-                                    arguments: Some(args_str),
-                                }),
+                                function,
                             }]),
+                            ..ChunkDelta::default()
                         },
-                        finish_reason: None,
-                        logprobs: None,
-                    }],
-                };
-                yield format_sse(&args_chunk);
+                        ToolCallFormat::FunctionCall => ChunkDelta {
+                            function_call: function,
+                            ..ChunkDelta::default()
+                        },
+                    };
+                    yield format_sse(&delta_chunk(&base, args));
+                }
             }
 
             // Finish chunk.
-            let finish_reason = if has_tool_calls { "tool_calls" } else { "stop" };
-            let mut finish_chunk = ChatCompletionChunk::new(id.clone(), model.clone(), created)
+            let finish_reason = if has_tool_calls {
+                format.finish_reason()
+            } else {
+                "stop"
+            };
+            let mut finish_chunk = base.clone()
                 .with_finish(finish_reason.to_string());
             if let Some(u) = usage {
                 finish_chunk = finish_chunk.with_usage(u);
@@ -209,6 +234,62 @@ impl ScriptedChatStream {
     }
 }
 
+fn delta_chunk(base: &ChatCompletionChunk, delta: ChunkDelta) -> ChatCompletionChunk {
+    ChatCompletionChunk {
+        choices: vec![ChunkChoice {
+            index: 0,
+            delta,
+            finish_reason: None,
+            logprobs: None,
+        }],
+        ..base.clone()
+    }
+}
+
+/// Longest run of letters or digits in one fragment
+const MAX_WORD_CHARS: usize = 5;
+/// Longest run of punctuation in one fragment
+const MAX_PUNCT_CHARS: usize = 3;
+
+/// Split serialized tool call arguments into the fragments a model streams
+/// them in: words of up to a few characters, a leading space kept with the
+/// word after it, and short runs of punctuation, so `{"city":"New York"}`
+/// arrives as `{"`, `city`, `":"`, `New`, ` York`, `"}`. Concatenated, the
+/// fragments are the input again.
+pub fn split_arguments(arguments: &str) -> Vec<String> {
+    let mut fragments = Vec::new();
+    let mut current = String::new();
+    let mut current_word = false;
+    let mut current_chars = 0;
+    for ch in arguments.chars() {
+        let word = ch.is_alphanumeric() || ch == '_';
+        let limit = if word {
+            MAX_WORD_CHARS
+        } else {
+            MAX_PUNCT_CHARS
+        };
+        let after_space = current == " ";
+        let joins = !current.is_empty()
+            && ch != ' '
+            && current_chars < limit
+            && (word == current_word || (word && after_space));
+        if !joins && !current.is_empty() {
+            fragments.push(std::mem::take(&mut current));
+        }
+        if !joins || (word && after_space) {
+            // The leading space does not count toward the word's length
+            current_word = word;
+            current_chars = 0;
+        }
+        current.push(ch);
+        current_chars += 1;
+    }
+    if !current.is_empty() {
+        fragments.push(current);
+    }
+    fragments
+}
+
 fn format_sse(chunk: &ChatCompletionChunk) -> String {
     let json = serde_json::to_string(chunk).unwrap_or_else(|_| "{}".to_string());
     format!("data: {}\n\n", json)
@@ -219,41 +300,47 @@ pub fn build_chat_completion_response(
     model: String,
     text: Option<String>,
     tool_calls: Vec<crate::openai::ToolCall>,
+    format: ToolCallFormat,
     usage: Usage,
 ) -> crate::openai::ChatCompletionResponse {
     use crate::openai::{ChatCompletionResponse, Choice, Message};
 
     let finish_reason = if tool_calls.is_empty() {
-        "stop".to_string()
+        "stop"
     } else {
-        "tool_calls".to_string()
+        format.finish_reason()
     };
 
-    let message = Message {
+    let mut message = Message {
         role: Role::Assistant,
         content: text.map(crate::openai::ChatMessageContent::Text),
         name: None,
-        tool_calls: if tool_calls.is_empty() {
-            None
-        } else {
-            Some(tool_calls)
-        },
+        tool_calls: None,
         tool_call_id: None,
+        function_call: None,
     };
+    if !tool_calls.is_empty() {
+        match format {
+            ToolCallFormat::ToolCalls => message.tool_calls = Some(tool_calls),
+            ToolCallFormat::FunctionCall => {
+                message.function_call = tool_calls.into_iter().next().map(|c| c.function)
+            }
+        }
+    }
 
     ChatCompletionResponse {
-        id: prefixed_id("chatcmpl-"),
+        id: new_id(IdKind::ChatCompletion),
         object: "chat.completion".to_string(),
         created: unix_timestamp(),
         model,
         choices: vec![Choice {
             index: 0,
             message,
-            finish_reason: Some(finish_reason),
+            finish_reason: Some(finish_reason.to_string()),
             logprobs: None,
         }],
         usage: Some(usage),
-        system_fingerprint: Some("fp_llmsim".to_string()),
+        system_fingerprint: Some(DEFAULT_SYSTEM_FINGERPRINT.to_string()),
     }
 }
 
@@ -284,6 +371,7 @@ pub fn materialize_tool_calls(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::latency::LatencyProfile;
     use futures_util::StreamExt;
     use serde_json::json;
 
@@ -316,12 +404,42 @@ mod tests {
         let joined = chunks.join("");
         assert!(joined.contains("\"name\":\"bash\""));
         assert!(joined.contains("\"id\":\"call_x\""));
-        // arguments are serialized as a JSON string, so the inner
-        // quotes are escaped on the wire.
-        assert!(joined.contains("\\\"command\\\""));
+        // arguments arrive in fragments that concatenate to the JSON
+        assert!(!joined.contains("{\\\"command"));
+        assert_eq!(streamed_arguments(&chunks), r#"{"command":"ls"}"#);
         assert!(joined.contains("\"finish_reason\":\"tool_calls\""));
     }
 
+    /// Concatenated argument deltas of every streamed tool call
+    fn streamed_arguments(chunks: &[String]) -> String {
+        chunks
+            .iter()
+            .filter_map(|c| c.strip_prefix("data: "))
+            .filter_map(|d| serde_json::from_str::<serde_json::Value>(d.trim()).ok())
+            .filter_map(|v| {
+                v["choices"][0]["delta"]["tool_calls"][0]["function"]["arguments"]
+                    .as_str()
+                    .map(str::to_string)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn splits_arguments_like_tokens() {
+        assert_eq!(
+            split_arguments(r#"{"city":"New York","days":14}"#),
+            vec!["{\"", "city", "\":\"", "New", " York", "\",\"", "days", "\":", "14", "}"]
+        );
+        assert_eq!(split_arguments("{}"), vec!["{}"]);
+        assert_eq!(
+            split_arguments(r#"{"q":"abcdefghijk"}"#)[3..6],
+            ["abcde", "fghij", "k"]
+        );
+        let text = r#"{"note":"naïve café ☕ \"quoted\"\n","list":[1,2,{"deep":[]}]}"#;
+        assert_eq!(split_arguments(text).concat(), text);
+        assert!(split_arguments("").is_empty());
+    }
+
     #[tokio::test]
     async fn mixed_text_and_tool_calls() {
         let calls = vec![SimToolCall {
@@ -377,9 +495,57 @@ mod tests {
             completion_tokens: 1,
             total_tokens: 2,
         };
-        let resp = build_chat_completion_response("gpt-5".to_string(), None, calls, usage);
+        let resp = build_chat_completion_response(
+            "gpt-5".to_string(),
+            None,
+            calls.clone(),
+            ToolCallFormat::ToolCalls,
+            usage.clone(),
+        );
         assert_eq!(resp.choices[0].finish_reason.as_deref(), Some("tool_calls"));
         assert!(resp.choices[0].message.tool_calls.is_some());
         assert!(resp.choices[0].message.content.is_none());
+
+        let resp = build_chat_completion_response(
+            "gpt-5".to_string(),
+            None,
+            calls,
+            ToolCallFormat::FunctionCall,
+            usage,
+        );
+        let message = &resp.choices[0].message;
+        assert_eq!(
+            resp.choices[0].finish_reason.as_deref(),
+            Some("function_call")
+        );
+        assert!(message.tool_calls.is_none());
+        assert_eq!(message.function_call.as_ref().unwrap().name, "bash");
+    }
+
+    #[tokio::test]
+    async fn streams_legacy_function_call() {
+        let calls = vec![
+            SimToolCall {
+                name: "first".into(),
+                arguments: json!({"a": 1}),
+                id: None,
+            },
+            SimToolCall {
+                name: "second".into(),
+                arguments: json!({}),
+                id: None,
+            },
+        ];
+        let stream =
+            ScriptedChatStream::new("gpt-5", String::new(), calls, LatencyProfile::instant())
+                .with_tool_call_format(ToolCallFormat::FunctionCall);
+        let chunks: Vec<String> = stream.into_stream().collect().await;
+        let joined = chunks.join("");
+        assert!(joined.contains("\"function_call\":{\"name\":\"first\",\"arguments\":\"\"}"));
+        assert!(joined.contains("\"function_call\":{\"arguments\":\"{\\\"\"}"));
+        assert!(joined.contains("\"function_call\":{\"arguments\":\"1\"}"));
+        assert!(!joined.contains("second"));
+        assert!(!joined.contains("tool_calls"));
+        assert!(joined.contains("\"finish_reason\":\"function_call\""));
     }
 }
@@ -0,0 +1,167 @@
+// Sleeper Module
+// How the streaming engines wait out simulated latency: `TokioSleeper` sleeps
+// on the tokio timer, `VirtualSleeper` returns at once and only adds the
+// delay to a virtual clock.
+//
+// Decision: a trait object on each stream (like `TextTokenizer`), so the
+// engines have no timer of their own. On wasm32 there is no tokio timer, and
+// the default is `VirtualSleeper`: a browser playground gets the chunks in the
+// same order with the same sampled delays, and can pace them itself from
+// `elapsed()` if it wants them spread out. Its delays also move the
+// simulator's clock there, so stats and timestamps see them.
+//
+// Decision: a `TokioSleeper` can be calibrated to how late the machine's
+// timer fires. `measure_shortest_sleep` times a run of 1ms sleeps; a sleeper
+// built with `TokioSleeper::calibrated` from the result shortens longer
+// delays by the overshoot, and sleeps a delay shorter than the shortest
+// possible sleep at random, with a chance of delay / shortest sleep, so the
+// mean gap still comes out as configured where each gap on its own cannot.
+// The server hands the calibrated sleeper to the pipeline of every listener.
+
+#[cfg(not(target_arch = "wasm32"))]
+use rand::RngExt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Delay asked of each sleep timed by `measure_shortest_sleep`
+#[cfg(not(target_arch = "wasm32"))]
+const PROBE_SLEEP: Duration = Duration::from_millis(1);
+/// Sleeps timed by `measure_shortest_sleep`
+#[cfg(not(target_arch = "wasm32"))]
+const PROBES: u32 = 50;
+
+/// Future returned by `Sleeper::sleep`
+pub type SleepFuture = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// Waits out a simulated delay
+pub trait Sleeper: Send + Sync {
+    fn sleep(&self, duration: Duration) -> SleepFuture;
+}
+
+/// Sleeps on the tokio timer, so `tokio::time::pause` applies
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TokioSleeper {
+    /// Mean time a `PROBE_SLEEP` takes; zero when uncalibrated
+    shortest: Duration,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl TokioSleeper {
+    /// A sleeper making up for sleeps taking `shortest` at the least, as
+    /// measured by `measure_shortest_sleep`
+    pub fn calibrated(shortest: Duration) -> Self {
+        Self { shortest }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Sleeper for TokioSleeper {
+    fn sleep(&self, duration: Duration) -> SleepFuture {
+        match compensate(duration, self.shortest, rand::rng().random()) {
+            Some(duration) => Box::pin(tokio::time::sleep(duration)),
+            None => Box::pin(std::future::ready(())),
+        }
+    }
+}
+
+/// What to sleep on a timer whose sleeps take `shortest` at the least, for a
+/// mean of `duration`: a longer delay less the overshoot, a shorter one as
+/// one probe sleep when `roll` (uniform in 0..1) falls under its share of
+/// `shortest`, and nothing otherwise
+#[cfg(not(target_arch = "wasm32"))]
+fn compensate(duration: Duration, shortest: Duration, roll: f64) -> Option<Duration> {
+    if shortest <= PROBE_SLEEP {
+        Some(duration)
+    } else if duration >= shortest {
+        Some(duration - (shortest - PROBE_SLEEP))
+    } else if roll < duration.as_secs_f64() / shortest.as_secs_f64() {
+        Some(PROBE_SLEEP)
+    } else {
+        None
+    }
+}
+
+/// Mean time a 1ms sleep on the tokio timer actually takes, from a run of
+/// them on the calling task
+#[cfg(not(target_arch = "wasm32"))]
+pub async fn measure_shortest_sleep() -> Duration {
+    let mut total = Duration::ZERO;
+    for _ in 0..PROBES {
+        let started = std::time::Instant::now();
+        tokio::time::sleep(PROBE_SLEEP).await;
+        total += started.elapsed();
+    }
+    total / PROBES
+}
+
+/// Never waits; adds every delay to a virtual clock instead
+#[derive(Debug, Default)]
+pub struct VirtualSleeper {
+    elapsed_nanos: AtomicU64,
+}
+
+impl VirtualSleeper {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Total of the delays slept so far
+    pub fn elapsed(&self) -> Duration {
+        Duration::from_nanos(self.elapsed_nanos.load(Ordering::Relaxed))
+    }
+}
+
+impl Sleeper for VirtualSleeper {
+    fn sleep(&self, duration: Duration) -> SleepFuture {
+        let nanos = u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX);
+        self.elapsed_nanos.fetch_add(nanos, Ordering::Relaxed);
+        // The simulator's clock on wasm32
+        #[cfg(target_arch = "wasm32")]
+        crate::clock::advance(duration);
+        Box::pin(std::future::ready(()))
+    }
+}
+
+/// The tokio timer, or a virtual clock on wasm32
+pub fn default_sleeper() -> Arc<dyn Sleeper> {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        Arc::new(TokioSleeper::default())
+    }
+    #[cfg(target_arch = "wasm32")]
+    {
+        Arc::new(VirtualSleeper::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_virtual_sleeper_adds_up() {
+        let sleeper = VirtualSleeper::new();
+        sleeper.sleep(Duration::from_millis(250)).await;
+        sleeper.sleep(Duration::from_secs(3600)).await;
+        assert_eq!(sleeper.elapsed(), Duration::from_millis(3_600_250));
+    }
+
+    #[test]
+    fn test_compensate_for_a_slow_timer() {
+        let ms = Duration::from_millis;
+        // Uncalibrated, or a timer that keeps time
+        assert_eq!(compensate(ms(5), Duration::ZERO, 0.0), Some(ms(5)));
+        assert_eq!(compensate(ms(5), ms(1), 0.0), Some(ms(5)));
+
+        // Sleeps take 4ms at the least: longer ones are shortened by 3ms
+        assert_eq!(compensate(ms(10), ms(4), 0.0), Some(ms(7)));
+        assert_eq!(compensate(ms(4), ms(4), 0.9), Some(ms(1)));
+        // and a 1ms delay is one 4ms sleep a quarter of the time
+        assert_eq!(compensate(ms(1), ms(4), 0.2), Some(ms(1)));
+        assert_eq!(compensate(ms(1), ms(4), 0.3), None);
+    }
+}
@@ -0,0 +1,175 @@
+// Stamps Module
+// What a response is stamped with as it is built: the format of its ids and
+// the skew of its `created`/`created_at` timestamps.
+//
+// Decision: ids and timestamps are minted deep in the library, in response
+// builders and stream constructors no configuration reaches, yet each server
+// state (and each region) has its own. So a pipeline holds its `Stamps`, and
+// the server scopes every request in them: `Stamps::scope` wraps the handler's
+// future and the response body, making the stamps current on the thread for
+// each poll and restoring the previous ones after, the way a task-local
+// follows a task across worker threads. Work a request hands to a task of its
+// own is scoped in `Stamps::current()`. Outside any scope the real APIs' id
+// formats and the true time apply.
+
+use crate::clock::ClockSkew;
+use crate::ids::IdFormats;
+use std::cell::RefCell;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, LazyLock};
+use std::task::{Context, Poll};
+
+thread_local! {
+    /// Stamps of the scope being polled on this thread
+    static CURRENT: RefCell<Option<Arc<Stamps>>> = const { RefCell::new(None) };
+}
+
+/// Id formats and timestamp skew of the responses a pipeline builds
+#[derive(Debug, Clone, Default)]
+pub struct Stamps {
+    ids: IdFormats,
+    skew: ClockSkew,
+}
+
+impl Stamps {
+    pub fn new(ids: IdFormats, skew: ClockSkew) -> Self {
+        Self { ids, skew }
+    }
+
+    pub fn ids(&self) -> &IdFormats {
+        &self.ids
+    }
+
+    pub fn skew(&self) -> ClockSkew {
+        self.skew
+    }
+
+    /// The stamps of the scope being polled, or the default ones
+    pub fn current() -> Arc<Stamps> {
+        CURRENT
+            .with_borrow(Option::clone)
+            .unwrap_or_else(|| DEFAULT.clone())
+    }
+
+    /// Run `f` with these stamps current
+    pub fn enter<T>(self: &Arc<Self>, f: impl FnOnce() -> T) -> T {
+        let _restore = Restore(CURRENT.replace(Some(self.clone())));
+        f()
+    }
+
+    /// `inner` (a future, stream or response body) with these stamps current
+    /// whenever it is polled
+    pub fn scope<T: Unpin>(self: &Arc<Self>, inner: T) -> Scoped<T> {
+        Scoped {
+            stamps: self.clone(),
+            inner,
+        }
+    }
+}
+
+static DEFAULT: LazyLock<Arc<Stamps>> = LazyLock::new(Arc::default);
+
+/// Puts back the stamps an `enter` replaced, even on panic
+struct Restore(Option<Arc<Stamps>>);
+
+impl Drop for Restore {
+    fn drop(&mut self) {
+        CURRENT.set(self.0.take());
+    }
+}
+
+/// Apply `f` to the current stamps without cloning them
+pub(crate) fn with_current<T>(f: impl FnOnce(&Stamps) -> T) -> T {
+    CURRENT.with_borrow(|current| f(current.as_deref().unwrap_or(&DEFAULT)))
+}
+
+/// A future, stream or body polled under `Stamps::scope`
+#[derive(Debug)]
+pub struct Scoped<T> {
+    stamps: Arc<Stamps>,
+    inner: T,
+}
+
+impl<F: Future + Unpin> Future for Scoped<F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<F::Output> {
+        let this = self.get_mut();
+        this.stamps.enter(|| Pin::new(&mut this.inner).poll(cx))
+    }
+}
+
+impl<S: futures_core::Stream + Unpin> futures_core::Stream for Scoped<S> {
+    type Item = S::Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<S::Item>> {
+        let this = self.get_mut();
+        this.stamps
+            .enter(|| Pin::new(&mut this.inner).poll_next(cx))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+#[cfg(feature = "server")]
+impl<B: http_body::Body + Unpin> http_body::Body for Scoped<B> {
+    type Data = B::Data;
+    type Error = B::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<http_body::Frame<B::Data>, B::Error>>> {
+        let this = self.get_mut();
+        this.stamps
+            .enter(|| Pin::new(&mut this.inner).poll_frame(cx))
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> http_body::SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ids::{new_id, IdFormat, IdKind};
+    use std::collections::BTreeMap;
+
+    fn prefixed(prefix: &str) -> Arc<Stamps> {
+        let format = IdFormat {
+            prefix: Some(prefix.to_string()),
+            ..IdFormat::default()
+        };
+        let ids = IdFormats::new(&BTreeMap::from([(IdKind::Response, format)])).unwrap();
+        Arc::new(Stamps::new(ids, ClockSkew::default()))
+    }
+
+    #[tokio::test]
+    async fn test_scopes_nest_and_restore() {
+        let outer = prefixed("outer_");
+        let inner = prefixed("inner_");
+        assert!(new_id(IdKind::Response).starts_with("resp_"));
+        outer.enter(|| {
+            assert!(new_id(IdKind::Response).starts_with("outer_"));
+            inner.enter(|| assert!(new_id(IdKind::Response).starts_with("inner_")));
+            assert!(new_id(IdKind::Response).starts_with("outer_"));
+        });
+        assert!(new_id(IdKind::Response).starts_with("resp_"));
+
+        let id = inner
+            .scope(Box::pin(async {
+                tokio::task::yield_now().await;
+                new_id(IdKind::Response)
+            }))
+            .await;
+        assert!(id.starts_with("inner_"), "{}", id);
+    }
+}
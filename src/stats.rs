@@ -3,23 +3,29 @@
 //! This module provides thread-safe atomic counters and statistics
 //! collection for monitoring LLMSim server performance.
 
+use crate::clock::{self, Instant};
+use crate::drill::DrillStatus;
+use crate::pacing::StreamPacing;
 use std::collections::HashMap;
 use std::sync::atomic::{AtomicU64, Ordering};
-use std::sync::{Arc, RwLock};
-use std::time::{Duration, Instant};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
 
 /// Relaxed ordering for stats - we don't need strict ordering guarantees
 const ORDERING: Ordering = Ordering::Relaxed;
 
-/// Width of the rolling RPS window, in 1-second buckets.
+/// Default width of the rolling RPS window, in 1-second buckets.
 ///
 /// RPS is tracked as a ring of per-second atomic buckets instead of a
 /// `Vec<Instant>` behind a write lock. The previous design took a global write
 /// lock and did an O(n) `retain` on *every* request, which serialized all
 /// worker threads and was the dominant throughput bottleneck under load. Each
 /// bucket packs `(second_tag << 32) | count` into one AtomicU64, so recording a
-/// request is a single lock-free compare-exchange.
-const RPS_WINDOW_SECS: u64 = 60;
+/// request is a single lock-free compare-exchange. The width is configurable
+/// with `Stats::with_rps_window`; memory is one AtomicU64 per second of it.
+pub const DEFAULT_RPS_WINDOW_SECS: u64 = 60;
+/// Widest RPS window accepted by `Stats::with_rps_window`.
+pub const MAX_RPS_WINDOW_SECS: u64 = 3600;
 
 /// Maximum bytes kept for a model name in stats.
 const MAX_MODEL_NAME_BYTES: usize = 128;
@@ -27,6 +33,17 @@ const MAX_MODEL_NAME_BYTES: usize = 128;
 const MAX_TRACKED_MODELS: usize = 128;
 /// Bucket for model names beyond tracking limits.
 const OTHER_MODELS_BUCKET: &str = "__other__";
+/// Latency histogram buckets. Bucket 0 holds latencies under 1ms and bucket
+/// `i` those up to 2^(i/4) ms, so a percentile read from it is at most ~19%
+/// high; the last bucket (about 2^23 ms) takes everything longer.
+const LATENCY_BUCKETS: usize = 93;
+/// Request size histogram buckets. Bucket `i` holds sizes up to 2^i, so
+/// bucket 0 is a single message or token; the last bucket (over 2^23, about
+/// 8M) takes everything larger.
+const SIZE_BUCKETS: usize = 25;
+/// Maximum number of distinct clients tracked before aggregating; beyond it
+/// new clients share `OTHER_MODELS_BUCKET`.
+const MAX_TRACKED_CLIENTS: usize = 128;
 
 /// Type of API endpoint being called
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -41,6 +58,8 @@ pub enum EndpointType {
     Messages,
     /// Image generation API (/openai/v1/images/generations)
     Images,
+    /// gRPC Simulator service (/llmsim.v1.Simulator/*)
+    Grpc,
 }
 
 /// Global statistics tracker for the LLMSim server.
@@ -68,6 +87,18 @@ pub struct Stats {
     pub messages_requests: AtomicU64,
     /// Image generation API requests
     pub image_requests: AtomicU64,
+    /// gRPC Simulator service requests
+    pub grpc_requests: AtomicU64,
+    /// Streams whose client disconnected before the final event
+    pub abandoned_streams: AtomicU64,
+    /// Requests rejected by load shedding (also counted as 503 errors)
+    pub shed_requests: AtomicU64,
+    /// Requests held without an answer by `[errors] hang_rate`
+    pub hung_requests: AtomicU64,
+    /// Hung requests the client gave up on before any answer
+    pub client_timeouts: AtomicU64,
+    /// Streams cut short by `[stream] max_duration_secs`
+    pub capped_streams: AtomicU64,
     /// Currently active WebSocket connections
     pub active_websocket_connections: AtomicU64,
 
@@ -86,6 +117,12 @@ pub struct Stats {
     pub server_errors: AtomicU64,
     /// Timeout errors (504)
     pub timeout_errors: AtomicU64,
+    /// Hung requests answered with 408 Request Timeout
+    pub request_timeout_errors: AtomicU64,
+
+    // Spend tracking
+    /// Simulated spend in nano-USD, so it can be summed with a single atomic add
+    cost_nano_usd: AtomicU64,
 
     // Per-model request counts. The value is an AtomicU64 so the common case
     // (a model that's already been seen) increments under a shared read lock
@@ -93,6 +130,11 @@ pub struct Stats {
     // key, which is bounded by MAX_TRACKED_MODELS.
     model_requests: RwLock<HashMap<String, AtomicU64>>,
 
+    // Per-client concurrency, keyed like `model_requests`: known clients
+    // update their own entry under a shared read lock, and only a new client
+    // takes the write lock.
+    client_concurrency: RwLock<HashMap<String, Mutex<ClientConcurrency>>>,
+
     // Latency tracking (in microseconds)
     /// Total latency for calculating average
     total_latency_us: AtomicU64,
@@ -102,9 +144,27 @@ pub struct Stats {
     min_latency_us: AtomicU64,
     /// Maximum latency seen
     max_latency_us: AtomicU64,
+    /// Completed requests per latency bucket, for percentiles
+    latency_buckets: Vec<AtomicU64>,
+
+    // Request sizes, to spot prompts growing over a session
+    /// Prompt tokens of completed requests
+    prompt_sizes: SizeHistogram,
+    /// Messages (or input items) per request
+    message_counts: SizeHistogram,
+    /// Milliseconds clients waited on hung requests
+    client_waits: SizeHistogram,
+
+    // Stream pacing, reported as each stream ends
+    /// Emitted minus configured chunk gaps, over every stream
+    pacing: Mutex<StreamPacing>,
+    /// Each stream's jitter in microseconds
+    stream_jitter: SizeHistogram,
+    /// Each stream's mean lag in microseconds, running ahead counting as 0
+    stream_lag: SizeHistogram,
 
     // Rolling window for RPS calculation: one AtomicU64 per second bucket,
-    // each packing (second_tag << 32) | count. See RPS_WINDOW_SECS.
+    // each packing (second_tag << 32) | count. See DEFAULT_RPS_WINDOW_SECS.
     rps_buckets: Vec<AtomicU64>,
 }
 
@@ -117,8 +177,15 @@ impl Default for Stats {
 impl Stats {
     /// Create a new Stats instance
     pub fn new() -> Self {
+        Self::with_rps_window(DEFAULT_RPS_WINDOW_SECS)
+    }
+
+    /// Create a Stats instance whose requests-per-second figure covers the
+    /// last `window_secs` seconds (clamped to 1..=MAX_RPS_WINDOW_SECS)
+    pub fn with_rps_window(window_secs: u64) -> Self {
+        let window_secs = window_secs.clamp(1, MAX_RPS_WINDOW_SECS);
         Self {
-            start_time: Instant::now(),
+            start_time: clock::now(),
             total_requests: AtomicU64::new(0),
             active_requests: AtomicU64::new(0),
             streaming_requests: AtomicU64::new(0),
@@ -128,6 +195,12 @@ impl Stats {
             websocket_requests: AtomicU64::new(0),
             messages_requests: AtomicU64::new(0),
             image_requests: AtomicU64::new(0),
+            grpc_requests: AtomicU64::new(0),
+            abandoned_streams: AtomicU64::new(0),
+            shed_requests: AtomicU64::new(0),
+            hung_requests: AtomicU64::new(0),
+            capped_streams: AtomicU64::new(0),
+            client_timeouts: AtomicU64::new(0),
             active_websocket_connections: AtomicU64::new(0),
             prompt_tokens: AtomicU64::new(0),
             completion_tokens: AtomicU64::new(0),
@@ -135,12 +208,22 @@ impl Stats {
             rate_limit_errors: AtomicU64::new(0),
             server_errors: AtomicU64::new(0),
             timeout_errors: AtomicU64::new(0),
+            request_timeout_errors: AtomicU64::new(0),
+            cost_nano_usd: AtomicU64::new(0),
             model_requests: RwLock::new(HashMap::new()),
+            client_concurrency: RwLock::new(HashMap::new()),
             total_latency_us: AtomicU64::new(0),
             completed_requests: AtomicU64::new(0),
             min_latency_us: AtomicU64::new(u64::MAX),
             max_latency_us: AtomicU64::new(0),
-            rps_buckets: (0..RPS_WINDOW_SECS).map(|_| AtomicU64::new(0)).collect(),
+            latency_buckets: (0..LATENCY_BUCKETS).map(|_| AtomicU64::new(0)).collect(),
+            prompt_sizes: SizeHistogram::new(),
+            message_counts: SizeHistogram::new(),
+            client_waits: SizeHistogram::new(),
+            pacing: Mutex::new(StreamPacing::default()),
+            stream_jitter: SizeHistogram::new(),
+            stream_lag: SizeHistogram::new(),
+            rps_buckets: (0..window_secs).map(|_| AtomicU64::new(0)).collect(),
         }
     }
 
@@ -172,6 +255,9 @@ impl Stats {
             EndpointType::Images => {
                 self.image_requests.fetch_add(1, ORDERING);
             }
+            EndpointType::Grpc => {
+                self.grpc_requests.fetch_add(1, ORDERING);
+            }
         }
 
         // Track per-model requests with bounded key size/cardinality.
@@ -208,9 +294,9 @@ impl Stats {
 
         // Record into the rolling RPS window: lock-free update of this second's
         // bucket. Packs (second_tag << 32) | count into one AtomicU64.
-        let sec = self.start_time.elapsed().as_secs();
+        let sec = clock::elapsed(self.start_time).as_secs();
         let tag = (sec as u32) as u64;
-        let bucket = &self.rps_buckets[(sec % RPS_WINDOW_SECS) as usize];
+        let bucket = &self.rps_buckets[(sec % self.rps_window_secs()) as usize];
         let mut cur = bucket.load(ORDERING);
         loop {
             let new = if (cur >> 32) == tag {
@@ -239,10 +325,15 @@ impl Stats {
         self.prompt_tokens.fetch_add(prompt_tokens as u64, ORDERING);
         self.completion_tokens
             .fetch_add(completion_tokens as u64, ORDERING);
+        // Proxied requests whose upstream reported no usage have no known size
+        if prompt_tokens > 0 {
+            self.prompt_sizes.record(prompt_tokens as u64);
+        }
 
         // Update latency stats
         let latency_us = latency.as_micros() as u64;
         self.total_latency_us.fetch_add(latency_us, ORDERING);
+        self.latency_buckets[latency_bucket(latency)].fetch_add(1, ORDERING);
 
         // Update min latency
         let mut current_min = self.min_latency_us.load(ORDERING);
@@ -273,6 +364,11 @@ impl Stats {
         }
     }
 
+    /// Record how many messages (or Responses input items) a request sent
+    pub fn record_request_messages(&self, messages: usize) {
+        self.message_counts.record(messages as u64);
+    }
+
     /// Record an error response
     pub fn record_error(&self, status_code: u16) {
         self.total_errors.fetch_add(1, ORDERING);
@@ -288,10 +384,139 @@ impl Stats {
             504 => {
                 self.timeout_errors.fetch_add(1, ORDERING);
             }
+            408 => {
+                self.request_timeout_errors.fetch_add(1, ORDERING);
+            }
             _ => {}
         }
     }
 
+    /// Record a stream the client disconnected from before it completed.
+    /// The request leaves the in-flight count without counting as completed.
+    pub fn record_stream_abandoned(&self) {
+        self.active_requests.fetch_sub(1, ORDERING);
+        self.abandoned_streams.fetch_add(1, ORDERING);
+    }
+
+    /// Record a request rejected by load shedding. The caller still records
+    /// the 503 itself through `record_error`.
+    pub fn record_shed(&self) {
+        self.shed_requests.fetch_add(1, ORDERING);
+    }
+
+    /// Record a request held without an answer. It stays in flight until
+    /// answered (`record_error`) or given up on (`record_client_timeout`).
+    pub fn record_hang(&self) {
+        self.hung_requests.fetch_add(1, ORDERING);
+    }
+
+    /// Record a stream cut short by `[stream] max_duration_secs`. It still
+    /// ends normally, through `record_request_end`.
+    pub fn record_stream_capped(&self) {
+        self.capped_streams.fetch_add(1, ORDERING);
+    }
+
+    /// Record how long a client waited on a hung request before it was
+    /// answered
+    pub fn record_client_wait(&self, waited: Duration) {
+        self.client_waits.record(waited.as_millis() as u64);
+    }
+
+    /// Record a hung request the client gave up on after `waited`. The
+    /// request leaves the in-flight count without counting as an error.
+    pub fn record_client_timeout(&self, waited: Duration) {
+        self.active_requests.fetch_sub(1, ORDERING);
+        self.client_timeouts.fetch_add(1, ORDERING);
+        self.record_client_wait(waited);
+    }
+
+    /// Record how closely a finished stream kept to its configured chunk
+    /// gaps
+    pub fn record_stream_pacing(&self, stream: &StreamPacing) {
+        let mut pacing = self.pacing.lock().unwrap();
+        pacing.gaps += stream.gaps;
+        pacing.sum_ms += stream.sum_ms;
+        pacing.sum_sq_ms += stream.sum_sq_ms;
+        drop(pacing);
+        self.stream_jitter
+            .record((stream.jitter_ms() * 1000.0).round() as u64);
+        self.stream_lag
+            .record((stream.lag_ms().max(0.0) * 1000.0).round() as u64);
+    }
+
+    /// Pacing of the streams finished so far
+    pub fn pacing(&self) -> PacingSnapshot {
+        let pacing = self.pacing.lock().unwrap().clone();
+        PacingSnapshot {
+            gaps: pacing.gaps,
+            mean_lag_ms: pacing.lag_ms(),
+            jitter_ms: pacing.jitter_ms(),
+            stream_jitter_us: self.stream_jitter.snapshot(),
+            stream_lag_us: self.stream_lag.snapshot(),
+        }
+    }
+
+    /// Add the simulated cost (in USD) of a request to the spend total
+    pub fn record_cost(&self, usd: f64) {
+        let nano = (usd * 1e9).round();
+        if nano.is_finite() && nano > 0.0 {
+            self.cost_nano_usd.fetch_add(nano as u64, ORDERING);
+        }
+    }
+
+    /// Get the total simulated spend in USD
+    pub fn total_cost_usd(&self) -> f64 {
+        self.cost_nano_usd.load(ORDERING) as f64 / 1e9
+    }
+
+    /// Record a request from `client` going in flight
+    pub fn record_client_start(&self, client: &str) {
+        self.update_client(client, |concurrency, now| concurrency.start(now));
+    }
+
+    /// Record a request from `client` leaving flight, answered or abandoned
+    pub fn record_client_end(&self, client: &str) {
+        self.update_client(client, |concurrency, now| concurrency.end(now));
+    }
+
+    fn update_client(&self, client: &str, update: impl Fn(&mut ClientConcurrency, Instant)) {
+        let now = clock::now();
+        if let Ok(map) = self.client_concurrency.read() {
+            if let Some(entry) = map.get(client) {
+                if let Ok(mut concurrency) = entry.lock() {
+                    update(&mut concurrency, now);
+                }
+                return;
+            }
+        }
+        if let Ok(mut map) = self.client_concurrency.write() {
+            let key = if map.contains_key(client) || map.len() < MAX_TRACKED_CLIENTS {
+                client
+            } else {
+                OTHER_MODELS_BUCKET
+            };
+            let entry = map
+                .entry(key.to_string())
+                .or_insert_with(|| Mutex::new(ClientConcurrency::new(now)));
+            if let Ok(concurrency) = entry.get_mut() {
+                update(concurrency, now);
+            }
+        }
+    }
+
+    /// Get per-client concurrency figures
+    pub fn client_concurrency(&self) -> HashMap<String, ClientConcurrencySnapshot> {
+        let now = clock::now();
+        self.client_concurrency
+            .read()
+            .map(|m| {
+                m.iter()
+                    .filter_map(|(k, v)| Some((k.clone(), v.lock().ok()?.snapshot(now))))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
     /// Try to reserve capacity for a new WebSocket connection.
     /// Returns true if a slot was successfully reserved, false if the cap is already reached.
     pub fn try_reserve_ws_connection(&self, max_connections: u64) -> bool {
@@ -309,12 +534,18 @@ impl Stats {
 
     /// Get the uptime of the server
     pub fn uptime(&self) -> Duration {
-        self.start_time.elapsed()
+        clock::elapsed(self.start_time)
+    }
+
+    /// Width of the requests-per-second window in seconds
+    pub fn rps_window_secs(&self) -> u64 {
+        self.rps_buckets.len() as u64
     }
 
-    /// Get requests per second (over the last RPS_WINDOW_SECS seconds)
+    /// Get requests per second (over the last `rps_window_secs` seconds)
     pub fn requests_per_second(&self) -> f64 {
-        let now_tag = self.start_time.elapsed().as_secs() as u32;
+        let window = self.rps_window_secs();
+        let now_tag = clock::elapsed(self.start_time).as_secs() as u32;
         let mut total = 0u64;
         let mut oldest_age = 0u32;
         for bucket in &self.rps_buckets {
@@ -325,7 +556,7 @@ impl Stats {
             }
             // Wrapping subtraction so the comparison is correct as the tag wraps.
             let age = now_tag.wrapping_sub((packed >> 32) as u32);
-            if (age as u64) < RPS_WINDOW_SECS {
+            if (age as u64) < window {
                 total += count;
                 oldest_age = oldest_age.max(age);
             }
@@ -367,6 +598,29 @@ impl Stats {
         }
     }
 
+    /// Get the 99th percentile latency in milliseconds, from the latency
+    /// histogram: the upper bound of the bucket holding it, capped at the
+    /// maximum seen
+    pub fn p99_latency_ms(&self) -> Option<f64> {
+        let counts: Vec<u64> = self
+            .latency_buckets
+            .iter()
+            .map(|bucket| bucket.load(ORDERING))
+            .collect();
+        let total: u64 = counts.iter().sum();
+        if total == 0 {
+            return None;
+        }
+        let rank = (total * 99).div_ceil(100);
+        let mut seen = 0;
+        let bucket = counts.iter().position(|count| {
+            seen += count;
+            seen >= rank
+        })?;
+        let bound = 2f64.powf(bucket as f64 / 4.0);
+        Some(self.max_latency_ms().map_or(bound, |max| bound.min(max)))
+    }
+
     /// Get total tokens (prompt + completion)
     pub fn total_tokens(&self) -> u64 {
         self.prompt_tokens.load(ORDERING) + self.completion_tokens.load(ORDERING)
@@ -397,6 +651,12 @@ impl Stats {
             websocket_requests: self.websocket_requests.load(ORDERING),
             messages_requests: self.messages_requests.load(ORDERING),
             image_requests: self.image_requests.load(ORDERING),
+            grpc_requests: self.grpc_requests.load(ORDERING),
+            abandoned_streams: self.abandoned_streams.load(ORDERING),
+            shed_requests: self.shed_requests.load(ORDERING),
+            hung_requests: self.hung_requests.load(ORDERING),
+            capped_streams: self.capped_streams.load(ORDERING),
+            client_timeouts: self.client_timeouts.load(ORDERING),
             active_websocket_connections: self.active_websocket_connections.load(ORDERING),
             prompt_tokens: self.prompt_tokens.load(ORDERING),
             completion_tokens: self.completion_tokens.load(ORDERING),
@@ -405,11 +665,24 @@ impl Stats {
             rate_limit_errors: self.rate_limit_errors.load(ORDERING),
             server_errors: self.server_errors.load(ORDERING),
             timeout_errors: self.timeout_errors.load(ORDERING),
+            request_timeout_errors: self.request_timeout_errors.load(ORDERING),
             requests_per_second: self.requests_per_second(),
+            rps_window_secs: self.rps_window_secs(),
             avg_latency_ms: self.avg_latency_ms(),
             min_latency_ms: self.min_latency_ms(),
             max_latency_ms: self.max_latency_ms(),
+            p99_latency_ms: self.p99_latency_ms(),
+            completed_requests: self.completed_requests.load(ORDERING),
+            total_latency_ms: self.total_latency_us.load(ORDERING) as f64 / 1000.0,
+            total_cost_usd: self.total_cost_usd(),
+            prompt_sizes: self.prompt_sizes.snapshot(),
+            message_counts: self.message_counts.snapshot(),
+            client_wait_ms: self.client_waits.snapshot(),
+            pacing: self.pacing(),
             model_requests: self.model_requests(),
+            client_concurrency: self.client_concurrency(),
+            active_drills: Vec::new(),
+            runtime: None,
         }
     }
 }
@@ -429,6 +702,23 @@ pub struct StatsSnapshot {
     pub messages_requests: u64,
     #[serde(default)]
     pub image_requests: u64,
+    #[serde(default)]
+    pub grpc_requests: u64,
+    /// Streams the client disconnected from mid-stream
+    #[serde(default)]
+    pub abandoned_streams: u64,
+    /// Requests rejected by load shedding
+    #[serde(default)]
+    pub shed_requests: u64,
+    /// Requests held without an answer by `[errors] hang_rate`
+    #[serde(default)]
+    pub hung_requests: u64,
+    /// Hung requests the client gave up on before any answer
+    #[serde(default)]
+    pub client_timeouts: u64,
+    /// Streams cut short by `[stream] max_duration_secs`
+    #[serde(default)]
+    pub capped_streams: u64,
     pub active_websocket_connections: u64,
     pub prompt_tokens: u64,
     pub completion_tokens: u64,
@@ -437,11 +727,379 @@ pub struct StatsSnapshot {
     pub rate_limit_errors: u64,
     pub server_errors: u64,
     pub timeout_errors: u64,
+    /// Hung requests answered with 408
+    #[serde(default)]
+    pub request_timeout_errors: u64,
     pub requests_per_second: f64,
+    /// Window `requests_per_second` is averaged over
+    #[serde(default)]
+    pub rps_window_secs: u64,
     pub avg_latency_ms: f64,
     pub min_latency_ms: Option<f64>,
     pub max_latency_ms: Option<f64>,
+    /// 99th percentile latency over completed requests, within ~19%
+    #[serde(default)]
+    pub p99_latency_ms: Option<f64>,
+    /// Requests that finished successfully (denominator for avg latency)
+    #[serde(default)]
+    pub completed_requests: u64,
+    /// Sum of end-to-end latency over completed requests
+    #[serde(default)]
+    pub total_latency_ms: f64,
+    /// Simulated spend across all priced requests, in USD
+    #[serde(default)]
+    pub total_cost_usd: f64,
+    /// Distribution of prompt tokens over completed requests
+    #[serde(default)]
+    pub prompt_sizes: SizeSnapshot,
+    /// Distribution of messages per request (input items on the Responses API)
+    #[serde(default)]
+    pub message_counts: SizeSnapshot,
+    /// How long clients waited on hung requests, answered or not
+    #[serde(default)]
+    pub client_wait_ms: SizeSnapshot,
+    /// How closely streams kept to their configured chunk gaps
+    #[serde(default)]
+    pub pacing: PacingSnapshot,
     pub model_requests: HashMap<String, u64>,
+    /// In-flight request figures per client (API key or address)
+    #[serde(default)]
+    pub client_concurrency: HashMap<String, ClientConcurrencySnapshot>,
+    /// Failover drills running when the snapshot was served; filled in by
+    /// the server, which owns the drill board
+    #[serde(default)]
+    pub active_drills: Vec<DrillStatus>,
+    /// Scheduler figures of the runtime serving the snapshot; filled in by
+    /// the server, which owns the runtime
+    #[serde(default)]
+    pub runtime: Option<RuntimeSnapshot>,
+}
+
+impl StatsSnapshot {
+    /// Add the figures of another process serving the same traffic, as for
+    /// cluster-wide stats. Counters, rates and spend add up; `p99_latency_ms`
+    /// becomes the highest of the two, an upper bound on the combined
+    /// percentile. Runtime figures describe one process and are dropped.
+    pub fn merge(&mut self, other: &StatsSnapshot) {
+        self.uptime_secs = self.uptime_secs.max(other.uptime_secs);
+        self.total_requests += other.total_requests;
+        self.active_requests += other.active_requests;
+        self.streaming_requests += other.streaming_requests;
+        self.non_streaming_requests += other.non_streaming_requests;
+        self.completions_requests += other.completions_requests;
+        self.responses_requests += other.responses_requests;
+        self.websocket_requests += other.websocket_requests;
+        self.messages_requests += other.messages_requests;
+        self.image_requests += other.image_requests;
+        self.grpc_requests += other.grpc_requests;
+        self.abandoned_streams += other.abandoned_streams;
+        self.shed_requests += other.shed_requests;
+        self.hung_requests += other.hung_requests;
+        self.capped_streams += other.capped_streams;
+        self.client_timeouts += other.client_timeouts;
+        self.active_websocket_connections += other.active_websocket_connections;
+        self.prompt_tokens += other.prompt_tokens;
+        self.completion_tokens += other.completion_tokens;
+        self.total_tokens += other.total_tokens;
+        self.total_errors += other.total_errors;
+        self.rate_limit_errors += other.rate_limit_errors;
+        self.server_errors += other.server_errors;
+        self.timeout_errors += other.timeout_errors;
+        self.request_timeout_errors += other.request_timeout_errors;
+        self.requests_per_second += other.requests_per_second;
+        self.rps_window_secs = self.rps_window_secs.max(other.rps_window_secs);
+        self.completed_requests += other.completed_requests;
+        self.total_latency_ms += other.total_latency_ms;
+        self.avg_latency_ms = if self.completed_requests == 0 {
+            0.0
+        } else {
+            self.total_latency_ms / self.completed_requests as f64
+        };
+        self.min_latency_ms = merge_option(self.min_latency_ms, other.min_latency_ms, f64::min);
+        self.max_latency_ms = merge_option(self.max_latency_ms, other.max_latency_ms, f64::max);
+        self.p99_latency_ms = merge_option(self.p99_latency_ms, other.p99_latency_ms, f64::max);
+        self.total_cost_usd += other.total_cost_usd;
+        self.prompt_sizes.merge(&other.prompt_sizes);
+        self.message_counts.merge(&other.message_counts);
+        self.client_wait_ms.merge(&other.client_wait_ms);
+        self.pacing.merge(&other.pacing);
+        for (model, count) in &other.model_requests {
+            *self.model_requests.entry(model.clone()).or_default() += count;
+        }
+        for (client, theirs) in &other.client_concurrency {
+            match self.client_concurrency.get_mut(client) {
+                Some(mine) => mine.merge(theirs),
+                None => {
+                    self.client_concurrency
+                        .insert(client.clone(), theirs.clone());
+                }
+            }
+        }
+        self.active_drills
+            .extend(other.active_drills.iter().cloned());
+        self.runtime = None;
+    }
+}
+
+fn merge_option(a: Option<f64>, b: Option<f64>, pick: fn(f64, f64) -> f64) -> Option<f64> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(pick(a, b)),
+        (a, b) => a.or(b),
+    }
+}
+
+/// Count, sum, maximum and power-of-two histogram of a request size
+#[derive(Debug)]
+struct SizeHistogram {
+    count: AtomicU64,
+    sum: AtomicU64,
+    max: AtomicU64,
+    buckets: Vec<AtomicU64>,
+}
+
+impl SizeHistogram {
+    fn new() -> Self {
+        Self {
+            count: AtomicU64::new(0),
+            sum: AtomicU64::new(0),
+            max: AtomicU64::new(0),
+            buckets: (0..SIZE_BUCKETS).map(|_| AtomicU64::new(0)).collect(),
+        }
+    }
+
+    fn record(&self, size: u64) {
+        self.count.fetch_add(1, ORDERING);
+        self.sum.fetch_add(size, ORDERING);
+        self.max.fetch_max(size, ORDERING);
+        self.buckets[size_bucket(size)].fetch_add(1, ORDERING);
+    }
+
+    fn snapshot(&self) -> SizeSnapshot {
+        let counts: Vec<u64> = self.buckets.iter().map(|b| b.load(ORDERING)).collect();
+        let used = counts
+            .iter()
+            .rposition(|count| *count > 0)
+            .map_or(0, |i| i + 1);
+        let count = self.count.load(ORDERING);
+        SizeSnapshot {
+            count,
+            avg: if count == 0 {
+                0.0
+            } else {
+                self.sum.load(ORDERING) as f64 / count as f64
+            },
+            max: self.max.load(ORDERING),
+            buckets: counts[..used]
+                .iter()
+                .enumerate()
+                .map(|(i, count)| SizeBucket {
+                    le: (i + 1 < SIZE_BUCKETS).then(|| 1 << i),
+                    count: *count,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Distribution of a request size, such as prompt tokens
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct SizeSnapshot {
+    /// Requests measured
+    pub count: u64,
+    pub avg: f64,
+    pub max: u64,
+    /// Requests per power-of-two size bucket, up to the largest one used
+    #[serde(default)]
+    pub buckets: Vec<SizeBucket>,
+}
+
+impl SizeSnapshot {
+    /// Add the same distribution measured by another process
+    pub fn merge(&mut self, other: &SizeSnapshot) {
+        let count = self.count + other.count;
+        if count > 0 {
+            self.avg =
+                (self.avg * self.count as f64 + other.avg * other.count as f64) / count as f64;
+        }
+        self.count = count;
+        self.max = self.max.max(other.max);
+        // Bucket bounds depend only on the position, so a shorter list is
+        // the longer one's prefix
+        for (i, bucket) in other.buckets.iter().enumerate() {
+            match self.buckets.get_mut(i) {
+                Some(mine) => mine.count += bucket.count,
+                None => self.buckets.push(bucket.clone()),
+            }
+        }
+    }
+}
+
+/// Emitted minus configured gaps between stream chunks: the mean (lag) and
+/// spread (jitter) over every gap, and the distribution of each stream's own
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct PacingSnapshot {
+    /// Gaps measured, across all finished streams
+    pub gaps: u64,
+    /// Mean of emitted minus configured gap; positive when streams run behind
+    pub mean_lag_ms: f64,
+    /// Standard deviation of emitted minus configured gap
+    pub jitter_ms: f64,
+    /// Each stream's jitter (microseconds); `count` is the streams measured
+    #[serde(default)]
+    pub stream_jitter_us: SizeSnapshot,
+    /// Each stream's mean lag (microseconds), running ahead counting as 0
+    #[serde(default)]
+    pub stream_lag_us: SizeSnapshot,
+}
+
+impl PacingSnapshot {
+    /// Add the pacing measured by another process
+    pub fn merge(&mut self, other: &PacingSnapshot) {
+        let gaps = self.gaps + other.gaps;
+        if gaps > 0 {
+            // Pool the two means and variances over all gaps
+            let moment = |p: &PacingSnapshot| {
+                p.gaps as f64 * (p.jitter_ms * p.jitter_ms + p.mean_lag_ms * p.mean_lag_ms)
+            };
+            let mean = (self.mean_lag_ms * self.gaps as f64
+                + other.mean_lag_ms * other.gaps as f64)
+                / gaps as f64;
+            let variance = (moment(self) + moment(other)) / gaps as f64 - mean * mean;
+            self.mean_lag_ms = mean;
+            self.jitter_ms = variance.max(0.0).sqrt();
+        }
+        self.gaps = gaps;
+        self.stream_jitter_us.merge(&other.stream_jitter_us);
+        self.stream_lag_us.merge(&other.stream_lag_us);
+    }
+}
+
+/// Requests in one size bucket: larger than the previous bucket's bound, up
+/// to `le`
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct SizeBucket {
+    /// Inclusive upper bound; `None` for the last bucket, which is unbounded
+    pub le: Option<u64>,
+    pub count: u64,
+}
+
+/// Load on the async runtime, to tell a saturated simulator from a slow
+/// gateway
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct RuntimeSnapshot {
+    /// Threads running tasks
+    pub workers: usize,
+    /// Tasks spawned and not yet finished
+    pub alive_tasks: usize,
+    /// Tasks waiting in the shared queue for a free worker
+    pub global_queue_depth: usize,
+    /// One entry per worker thread
+    #[serde(default)]
+    pub worker_metrics: Vec<WorkerSnapshot>,
+}
+
+/// Scheduler figures of one runtime worker
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct WorkerSnapshot {
+    /// Time spent running tasks since the runtime started
+    pub busy_ms: f64,
+    /// Times the worker ran out of tasks and went to sleep
+    pub park_count: u64,
+    /// Tasks in the worker's own run queue; only measured in builds with
+    /// `RUSTFLAGS="--cfg tokio_unstable"`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub local_queue_depth: Option<usize>,
+}
+
+/// Requests one client has in flight, integrated over time so the mean
+/// reflects how long each level of concurrency lasted
+#[derive(Debug, Clone, Copy)]
+struct ClientConcurrency {
+    in_flight: u64,
+    max: u64,
+    requests: u64,
+    /// Sum of in-flight count × time, in request-microseconds
+    area_us: u128,
+    /// Time with at least one request in flight
+    busy: Duration,
+    last_change: Instant,
+}
+
+impl ClientConcurrency {
+    fn new(now: Instant) -> Self {
+        Self {
+            in_flight: 0,
+            max: 0,
+            requests: 0,
+            area_us: 0,
+            busy: Duration::ZERO,
+            last_change: now,
+        }
+    }
+
+    /// Account for the time since the last change at the current level
+    fn advance(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.last_change);
+        if self.in_flight > 0 {
+            self.area_us += elapsed.as_micros() * self.in_flight as u128;
+            self.busy += elapsed;
+        }
+        self.last_change = now;
+    }
+
+    fn start(&mut self, now: Instant) {
+        self.advance(now);
+        self.in_flight += 1;
+        self.requests += 1;
+        self.max = self.max.max(self.in_flight);
+    }
+
+    fn end(&mut self, now: Instant) {
+        self.advance(now);
+        self.in_flight = self.in_flight.saturating_sub(1);
+    }
+
+    fn snapshot(&self, now: Instant) -> ClientConcurrencySnapshot {
+        let mut current = *self;
+        current.advance(now);
+        let busy_us = current.busy.as_micros();
+        ClientConcurrencySnapshot {
+            in_flight: current.in_flight,
+            max_concurrency: current.max,
+            mean_concurrency: if busy_us == 0 {
+                current.in_flight as f64
+            } else {
+                current.area_us as f64 / busy_us as f64
+            },
+            requests: current.requests,
+        }
+    }
+}
+
+/// Concurrency of one client's requests
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ClientConcurrencySnapshot {
+    /// Requests in flight now
+    pub in_flight: u64,
+    /// Most requests ever in flight at once
+    pub max_concurrency: u64,
+    /// Average requests in flight while the client had any, weighted by time
+    pub mean_concurrency: f64,
+    /// Requests started
+    pub requests: u64,
+}
+
+impl ClientConcurrencySnapshot {
+    /// Add the same client's figures from another process, whose share of
+    /// its connections ran alongside this one's. The peaks of two processes
+    /// need not have overlapped, so the summed `max_concurrency` is an upper
+    /// bound.
+    pub fn merge(&mut self, other: &ClientConcurrencySnapshot) {
+        self.in_flight += other.in_flight;
+        self.max_concurrency += other.max_concurrency;
+        self.mean_concurrency += other.mean_concurrency;
+        self.requests += other.requests;
+    }
 }
 
 /// Shared stats handle for use across threads
@@ -452,6 +1110,21 @@ pub fn new_shared_stats() -> SharedStats {
     Arc::new(Stats::new())
 }
 
+/// Histogram bucket of a latency; see `LATENCY_BUCKETS`
+fn latency_bucket(latency: Duration) -> usize {
+    let ms = latency.as_secs_f64() * 1000.0;
+    if ms < 1.0 {
+        return 0;
+    }
+    (1 + (ms.log2() * 4.0) as usize).min(LATENCY_BUCKETS - 1)
+}
+
+/// Histogram bucket of a request size; see `SIZE_BUCKETS`
+fn size_bucket(size: u64) -> usize {
+    let bucket = (u64::BITS - size.saturating_sub(1).leading_zeros()) as usize;
+    bucket.min(SIZE_BUCKETS - 1)
+}
+
 fn normalize_model_name(model: &str) -> String {
     let trimmed = model.trim();
     if trimmed.is_empty() {
@@ -525,6 +1198,169 @@ mod tests {
         assert_eq!(stats.max_latency_ms(), Some(200.0));
     }
 
+    #[test]
+    fn test_p99_latency() {
+        let stats = Stats::new();
+        assert_eq!(stats.p99_latency_ms(), None);
+
+        for _ in 0..99 {
+            stats.record_request_start("gpt-4", false, EndpointType::ChatCompletions);
+            stats.record_request_end(Duration::from_millis(100), 10, 20);
+        }
+        stats.record_request_start("gpt-4", false, EndpointType::ChatCompletions);
+        stats.record_request_end(Duration::from_millis(5000), 10, 20);
+        let p99 = stats.p99_latency_ms().unwrap();
+        assert!((100.0..120.0).contains(&p99), "{}", p99);
+
+        stats.record_request_start("gpt-4", false, EndpointType::ChatCompletions);
+        stats.record_request_end(Duration::from_millis(5000), 10, 20);
+        // Capped at the slowest request rather than the bucket's bound
+        assert_eq!(stats.snapshot().p99_latency_ms, Some(5000.0));
+    }
+
+    #[test]
+    fn test_latency_buckets() {
+        assert_eq!(latency_bucket(Duration::from_micros(500)), 0);
+        assert_eq!(latency_bucket(Duration::from_millis(1)), 1);
+        assert_eq!(latency_bucket(Duration::from_millis(1000)), 40);
+        assert_eq!(
+            latency_bucket(Duration::from_secs(86400)),
+            LATENCY_BUCKETS - 1
+        );
+    }
+
+    #[test]
+    fn test_hung_requests() {
+        let stats = Stats::new();
+        for _ in 0..2 {
+            stats.record_request_start("gpt-4", false, EndpointType::ChatCompletions);
+            stats.record_hang();
+        }
+        stats.record_client_timeout(Duration::from_millis(3000));
+        stats.record_client_wait(Duration::from_millis(500));
+        stats.record_error(408);
+
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.hung_requests, 2);
+        assert_eq!(snapshot.client_timeouts, 1);
+        assert_eq!(snapshot.request_timeout_errors, 1);
+        assert_eq!(snapshot.total_errors, 1);
+        assert_eq!(snapshot.active_requests, 0);
+        let waits = &snapshot.client_wait_ms;
+        assert_eq!((waits.count, waits.avg, waits.max), (2, 1750.0, 3000));
+    }
+
+    #[test]
+    fn test_merge_snapshots() {
+        let first = Stats::new();
+        first.record_request_start("gpt-4", false, EndpointType::ChatCompletions);
+        first.record_request_messages(2);
+        first.record_request_end(Duration::from_millis(100), 10, 5);
+        let second = Stats::new();
+        for _ in 0..3 {
+            second.record_request_start("gpt-4", true, EndpointType::ChatCompletions);
+            second.record_request_messages(8);
+            second.record_request_end(Duration::from_millis(300), 1000, 5);
+        }
+        second.record_request_start("claude-sonnet-4", false, EndpointType::Messages);
+        second.record_error(429);
+
+        let mut merged = first.snapshot();
+        merged.merge(&second.snapshot());
+        assert_eq!(merged.total_requests, 5);
+        assert_eq!(merged.completed_requests, 4);
+        assert_eq!(merged.streaming_requests, 3);
+        assert_eq!(merged.rate_limit_errors, 1);
+        assert_eq!(merged.prompt_tokens, 3010);
+        assert_eq!(merged.model_requests["gpt-4"], 4);
+        assert_eq!(merged.model_requests["claude-sonnet-4"], 1);
+        assert_eq!(merged.avg_latency_ms, 250.0);
+        assert_eq!(merged.min_latency_ms, Some(100.0));
+        assert_eq!(merged.max_latency_ms, Some(300.0));
+
+        let messages = &merged.message_counts;
+        assert_eq!((messages.count, messages.avg, messages.max), (4, 6.5, 8));
+        let counts: Vec<u64> = messages.buckets.iter().map(|b| b.count).collect();
+        assert_eq!(counts, vec![0, 1, 0, 3]);
+        assert_eq!(merged.prompt_sizes.buckets.len(), 11);
+        assert_eq!(merged.prompt_sizes.count, 4);
+    }
+
+    #[test]
+    fn test_stream_pacing() {
+        let stream = |errors_ms: &[u64]| {
+            let mut pacing = StreamPacing::default();
+            for error in errors_ms {
+                pacing.add(Duration::from_millis(10 + error), Duration::from_millis(10));
+            }
+            pacing
+        };
+        let first = Stats::new();
+        first.record_stream_pacing(&stream(&[0, 2]));
+        let second = Stats::new();
+        second.record_stream_pacing(&stream(&[4, 6]));
+        let whole = Stats::new();
+        whole.record_stream_pacing(&stream(&[0, 2]));
+        whole.record_stream_pacing(&stream(&[4, 6]));
+
+        let pacing = whole.snapshot().pacing;
+        assert_eq!(pacing.gaps, 4);
+        assert!((pacing.mean_lag_ms - 3.0).abs() < 1e-9);
+        assert!((pacing.jitter_ms - 5.0f64.sqrt()).abs() < 1e-9);
+        // Each stream on its own jitters by 1ms
+        assert_eq!(pacing.stream_jitter_us.count, 2);
+        assert_eq!(pacing.stream_jitter_us.max, 1000);
+        assert_eq!(pacing.stream_lag_us.max, 5000);
+
+        // Merging processes pools the same way
+        let mut merged = first.snapshot();
+        merged.merge(&second.snapshot());
+        assert_eq!(merged.pacing.gaps, 4);
+        assert!((merged.pacing.mean_lag_ms - 3.0).abs() < 1e-9);
+        assert!((merged.pacing.jitter_ms - pacing.jitter_ms).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_request_sizes() {
+        assert_eq!(size_bucket(0), 0);
+        assert_eq!(size_bucket(1), 0);
+        assert_eq!(size_bucket(2), 1);
+        assert_eq!(size_bucket(3), 2);
+        assert_eq!(size_bucket(1024), 10);
+        assert_eq!(size_bucket(u64::MAX), SIZE_BUCKETS - 1);
+
+        let stats = Stats::new();
+        for (messages, prompt) in [(1, 30), (2, 60), (9, 1000)] {
+            stats.record_request_messages(messages);
+            stats.record_request_start("gpt-4", false, EndpointType::ChatCompletions);
+            stats.record_request_end(Duration::from_millis(10), prompt, 5);
+        }
+        // Usage an upstream did not report is left out
+        stats.record_request_end(Duration::from_millis(10), 0, 0);
+
+        let snapshot = stats.snapshot();
+        let messages = &snapshot.message_counts;
+        assert_eq!((messages.count, messages.avg, messages.max), (3, 4.0, 9));
+        let counts: Vec<u64> = messages.buckets.iter().map(|b| b.count).collect();
+        assert_eq!(counts, vec![1, 1, 0, 0, 1]);
+        assert_eq!(messages.buckets[4].le, Some(16));
+
+        let prompts = &snapshot.prompt_sizes;
+        assert_eq!((prompts.count, prompts.max), (3, 1000));
+        assert_eq!(prompts.buckets.len(), 11);
+        assert_eq!(
+            prompts.buckets[10],
+            SizeBucket {
+                le: Some(1024),
+                count: 1
+            }
+        );
+        assert_eq!(
+            Stats::new().snapshot().prompt_sizes,
+            SizeSnapshot::default()
+        );
+    }
+
     #[test]
     fn test_model_requests() {
         let stats = Stats::new();
@@ -590,6 +1426,37 @@ mod tests {
         assert!(rps.is_finite());
     }
 
+    #[tokio::test(start_paused = true)]
+    async fn test_rps_window_follows_paused_clock() {
+        let stats = Stats::new();
+        for _ in 0..5 {
+            stats.record_request_start("gpt-4", false, EndpointType::ChatCompletions);
+        }
+        tokio::time::advance(Duration::from_secs(2 * 3600)).await;
+
+        // Hours later the requests have left the window
+        assert_eq!(stats.requests_per_second(), 0.0);
+        assert!(stats.uptime() >= Duration::from_secs(2 * 3600));
+    }
+
+    #[test]
+    fn test_rps_window_is_configurable() {
+        assert_eq!(Stats::new().rps_window_secs(), DEFAULT_RPS_WINDOW_SECS);
+        assert_eq!(Stats::with_rps_window(10).rps_window_secs(), 10);
+        assert_eq!(Stats::with_rps_window(0).rps_window_secs(), 1);
+        assert_eq!(
+            Stats::with_rps_window(u64::MAX).rps_window_secs(),
+            MAX_RPS_WINDOW_SECS
+        );
+
+        let stats = Stats::with_rps_window(1);
+        for _ in 0..3 {
+            stats.record_request_start("gpt-4", false, EndpointType::ChatCompletions);
+        }
+        assert!(stats.requests_per_second() > 0.0);
+        assert_eq!(stats.snapshot().rps_window_secs, 1);
+    }
+
     #[test]
     fn test_endpoint_types() {
         let stats = Stats::new();
@@ -602,4 +1469,61 @@ mod tests {
         assert_eq!(stats.responses_requests.load(ORDERING), 1);
         assert_eq!(stats.total_requests.load(ORDERING), 3);
     }
+
+    #[test]
+    fn test_record_stream_abandoned() {
+        let stats = Stats::new();
+        stats.record_request_start("gpt-5", true, EndpointType::ChatCompletions);
+        stats.record_stream_abandoned();
+
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.active_requests, 0);
+        assert_eq!(snapshot.abandoned_streams, 1);
+        assert_eq!(snapshot.completed_requests, 0);
+        assert_eq!(snapshot.total_errors, 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_client_concurrency() {
+        let stats = Stats::new();
+        stats.record_client_start("a");
+        tokio::time::advance(Duration::from_secs(1)).await;
+        stats.record_client_start("a");
+        stats.record_client_start("b");
+        tokio::time::advance(Duration::from_secs(1)).await;
+        stats.record_client_end("a");
+        stats.record_client_end("a");
+        tokio::time::advance(Duration::from_secs(5)).await;
+
+        let clients = stats.snapshot().client_concurrency;
+        let a = &clients["a"];
+        assert_eq!((a.in_flight, a.max_concurrency, a.requests), (0, 2, 2));
+        // One second at 1, one at 2; idle time does not dilute the mean
+        assert_eq!(a.mean_concurrency, 1.5);
+        let b = &clients["b"];
+        assert_eq!((b.in_flight, b.max_concurrency), (1, 1));
+        assert_eq!(b.mean_concurrency, 1.0);
+    }
+
+    #[test]
+    fn test_client_cardinality_is_bounded() {
+        let stats = Stats::new();
+        for i in 0..(MAX_TRACKED_CLIENTS + 10) {
+            stats.record_client_start(&format!("client-{i}"));
+        }
+        let clients = stats.client_concurrency();
+        assert_eq!(clients.len(), MAX_TRACKED_CLIENTS + 1);
+        assert_eq!(clients[OTHER_MODELS_BUCKET].in_flight, 10);
+    }
+
+    #[test]
+    fn test_record_cost() {
+        let stats = Stats::new();
+        stats.record_cost(0.00625);
+        stats.record_cost(0.00125);
+        stats.record_cost(-1.0);
+
+        assert!((stats.total_cost_usd() - 0.0075).abs() < 1e-9);
+        assert!((stats.snapshot().total_cost_usd - 0.0075).abs() < 1e-9);
+    }
 }
@@ -0,0 +1,115 @@
+// Stop Sequences Module
+// Generated text ended at the request's `stop` sequences, the matched
+// sequence itself left out, as a model server does.
+//
+// Decision: synthetic text almost never contains a stop sequence on its own,
+// so hitting one is simulated by having the "model" emit one partway through
+// (`emit_stop`) and then cutting at it like any literal match. Cutting is a
+// separate step so text that really contains a stop sequence, an echoed
+// prompt or a fixed response, is cut the same way. Streamed pieces that could
+// be the start of a stop sequence are held back until the next piece decides,
+// so a client never sees part of a stop sequence it asked to exclude.
+
+use crate::generator::TextPieces;
+
+/// Earliest stop sequence in `text`: where it starts and which one it is.
+/// At the same position the longest sequence wins.
+pub fn find_stop<'a>(text: &str, stops: &[&'a str]) -> Option<(usize, &'a str)> {
+    stops
+        .iter()
+        .filter_map(|stop| text.find(stop).map(|at| (at, *stop)))
+        .min_by_key(|(at, stop)| (*at, std::cmp::Reverse(stop.len())))
+}
+
+/// `pieces` with `stop` emitted after the first `after` of them, as if the
+/// model had generated it there. Nothing past the stop sequence is produced.
+pub fn emit_stop(pieces: TextPieces, after: usize, stop: String) -> TextPieces {
+    Box::new(pieces.take(after).chain(std::iter::once(stop)))
+}
+
+/// `pieces` ending just before the first stop sequence they contain
+pub fn cut_at_stop(pieces: TextPieces, stops: Vec<String>) -> TextPieces {
+    if stops.is_empty() {
+        return pieces;
+    }
+    let mut pieces = pieces;
+    let mut pending = String::new();
+    let mut done = false;
+    Box::new(std::iter::from_fn(move || {
+        while !done {
+            let Some(piece) = pieces.next() else {
+                done = true;
+                return (!pending.is_empty()).then(|| std::mem::take(&mut pending));
+            };
+            pending.push_str(&piece);
+            let stops: Vec<&str> = stops.iter().map(String::as_str).collect();
+            if let Some((at, _)) = find_stop(&pending, &stops) {
+                done = true;
+                pending.truncate(at);
+                return (!pending.is_empty()).then(|| std::mem::take(&mut pending));
+            }
+            let held = held_from(&pending, &stops);
+            if held > 0 {
+                let rest = pending.split_off(held);
+                return Some(std::mem::replace(&mut pending, rest));
+            }
+        }
+        None
+    }))
+}
+
+/// Where the tail of `text` that could still grow into a stop sequence
+/// starts; `text.len()` if none could
+fn held_from(text: &str, stops: &[&str]) -> usize {
+    text.char_indices()
+        .map(|(i, _)| i)
+        .find(|i| stops.iter().any(|stop| stop.starts_with(&text[*i..])))
+        .unwrap_or(text.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pieces(words: &[&str]) -> TextPieces {
+        let words: Vec<String> = words.iter().map(|w| w.to_string()).collect();
+        Box::new(words.into_iter())
+    }
+
+    fn cut(words: &[&str], stops: &[&str]) -> Vec<String> {
+        cut_at_stop(pieces(words), stops.iter().map(|s| s.to_string()).collect()).collect()
+    }
+
+    #[test]
+    fn test_find_stop() {
+        assert_eq!(
+            find_stop("a END b STOP", &["STOP", "END"]),
+            Some((2, "END"))
+        );
+        assert_eq!(find_stop("a\n\nb", &["\n", "\n\n"]), Some((1, "\n\n")));
+        assert_eq!(find_stop("abc", &["x"]), None);
+    }
+
+    #[test]
+    fn test_cut_at_stop_holds_back_partial_matches() {
+        // "EN" could start "END", so it waits for the next piece
+        assert_eq!(
+            cut(&["one", " EN", "D", " two"], &["END"]),
+            vec!["one", " "]
+        );
+        // A partial match that does not complete is released
+        assert_eq!(
+            cut(&["one", " EN", "TER"], &["END"]),
+            vec!["one", " ", "ENTER"]
+        );
+        assert_eq!(cut(&["a", " b"], &["zz"]), vec!["a", " b"]);
+        assert!(cut(&["STOP", "x"], &["STOP"]).is_empty());
+    }
+
+    #[test]
+    fn test_emitted_stop_is_cut() {
+        let emitted = emit_stop(pieces(&["a", " ", "b", " ", "c"]), 3, "###".to_string());
+        let text: String = cut_at_stop(emitted, vec!["###".to_string()]).collect();
+        assert_eq!(text, "a b");
+    }
+}
@@ -1,17 +1,46 @@
 // Streaming Engine Module
 // Implements token-by-token streaming with realistic latency simulation.
-
-use crate::ids::{prefixed_id, unix_timestamp};
-use crate::latency::LatencyProfile;
-use crate::openai::{ChatCompletionChunk, Role, Usage};
+//
+// Decision: SSE output does not go through `ChatCompletionChunk` and serde.
+// At thousands of concurrent streams the per-chunk allocations of building a
+// chunk, serializing it and formatting the event dominate, so `ChunkEncoder`
+// serializes the parts every chunk of a stream shares once and writes each
+// event into a single buffer of the right size. Its output is byte for byte
+// what serde produces for the same chunk.
+
+use crate::generator::{split_pieces, TextPieces};
+use crate::ids::{new_id, unix_timestamp, IdKind};
+use crate::latency::{LatencyProfile, RequestLatency};
+use crate::openai::{ChatCompletionChunk, Role, Usage, DEFAULT_SYSTEM_FINGERPRINT};
+use crate::pipeline::{DefaultTokenizer, TextTokenizer};
+use crate::sleep::{default_sleeper, Sleeper};
+use crate::watchdog::StreamWatchdog;
 use async_stream::stream;
 use futures_core::Stream;
+use futures_util::StreamExt;
+use std::fmt::Write;
 use std::pin::Pin;
-use tokio::time::sleep;
+use std::sync::Arc;
 
 /// Callback type for stream completion
 type OnCompleteCallback = Box<dyn FnOnce() + Send + 'static>;
 
+/// Callback receiving the final usage of a stream
+type OnUsageCallback = Box<dyn FnOnce(Usage) + Send + 'static>;
+
+/// One event of a stream, with the usage it carries
+enum Frame {
+    Role(Option<Usage>),
+    Content(String, Option<Usage>),
+    Finish(&'static str, Option<Usage>),
+}
+
+/// What a stream emits: known text, or pieces produced as the stream runs
+enum Source {
+    Text(String),
+    Pieces(TextPieces),
+}
+
 /// A streaming response that yields chunks with simulated delays
 pub struct TokenStream {
     /// The response ID (shared across all chunks)
@@ -21,33 +50,110 @@ pub struct TokenStream {
     /// Unix timestamp of creation
     created: i64,
     /// Latency profile for timing simulation
-    latency: LatencyProfile,
-    /// The full response text to stream
-    content: String,
+    latency: RequestLatency,
+    /// The response content to stream
+    source: Source,
     /// Token usage (included in final chunk if stream_options.include_usage is true)
     usage: Option<Usage>,
+    /// Attach running usage to every chunk (vLLM `continuous_usage_stats`)
+    continuous_usage: bool,
+    /// Reported on every chunk
+    system_fingerprint: String,
+    /// Counts produced pieces
+    tokenizer: Arc<dyn TextTokenizer>,
+    /// Waits out the simulated delays
+    sleeper: Arc<dyn Sleeper>,
+    /// Ends the stream early once its time budget is spent
+    watchdog: StreamWatchdog,
     /// Callback to invoke when stream completes
     on_complete: Option<OnCompleteCallback>,
+    /// Callback to invoke with the final usage when stream completes
+    on_usage: Option<OnUsageCallback>,
 }
 
 impl TokenStream {
-    pub fn new(id: String, model: String, content: String, latency: LatencyProfile) -> Self {
+    pub fn new(
+        id: String,
+        model: String,
+        content: String,
+        latency: impl Into<RequestLatency>,
+    ) -> Self {
+        Self::with_source(id, model, Source::Text(content), latency)
+    }
+
+    /// Stream pieces as they are produced instead of a known text.
+    ///
+    /// Completion tokens are counted as pieces are emitted, so any usage set
+    /// on the stream only needs the prompt tokens; its completion and total
+    /// counts are replaced with the counted values.
+    pub fn from_pieces(
+        id: String,
+        model: String,
+        pieces: TextPieces,
+        latency: impl Into<RequestLatency>,
+    ) -> Self {
+        Self::with_source(id, model, Source::Pieces(pieces), latency)
+    }
+
+    fn with_source(
+        id: String,
+        model: String,
+        source: Source,
+        latency: impl Into<RequestLatency>,
+    ) -> Self {
         Self {
             id,
             model,
             created: unix_timestamp(),
-            latency,
-            content,
+            latency: latency.into(),
+            source,
             usage: None,
+            continuous_usage: false,
+            system_fingerprint: DEFAULT_SYSTEM_FINGERPRINT.to_string(),
+            tokenizer: Arc::new(DefaultTokenizer),
+            sleeper: default_sleeper(),
+            watchdog: StreamWatchdog::unlimited(),
             on_complete: None,
+            on_usage: None,
         }
     }
 
+    /// Count produced pieces with this tokenizer (default
+    /// `count_text_tokens`). Text streams use the usage they are given.
+    pub fn with_tokenizer(mut self, tokenizer: Arc<dyn TextTokenizer>) -> Self {
+        self.tokenizer = tokenizer;
+        self
+    }
+
+    /// Wait out delays with this sleeper (default: the tokio timer)
+    pub fn with_sleeper(mut self, sleeper: Arc<dyn Sleeper>) -> Self {
+        self.sleeper = sleeper;
+        self
+    }
+
+    /// End the stream with `length` once the watchdog's budget is spent
+    pub fn with_watchdog(mut self, watchdog: StreamWatchdog) -> Self {
+        self.watchdog = watchdog;
+        self
+    }
+
+    pub fn with_system_fingerprint(mut self, fingerprint: impl Into<String>) -> Self {
+        self.system_fingerprint = fingerprint.into();
+        self
+    }
+
     pub fn with_usage(mut self, usage: Usage) -> Self {
         self.usage = Some(usage);
         self
     }
 
+    /// Include running usage in every chunk instead of only the final one.
+    /// Has no effect unless usage is also set.
+    pub fn with_continuous_usage(mut self, enabled: bool) -> Self {
+        self.continuous_usage = enabled;
+        self
+    }
+
     pub fn with_on_complete<F>(mut self, callback: F) -> Self
     where
         F: FnOnce() + Send + 'static,
@@ -56,156 +162,371 @@ impl TokenStream {
         self
     }
 
-    /// Convert the content into chunks for streaming
-    /// This simulates word-by-word streaming (approximating token streaming)
-    fn tokenize(&self) -> Vec<String> {
-        // Split by whitespace but keep spaces as separate tokens
-        // This approximates token-level streaming
-        let mut tokens = Vec::new();
-        let mut current_word = String::new();
-
-        for ch in self.content.chars() {
-            if ch.is_whitespace() {
-                if !current_word.is_empty() {
-                    tokens.push(current_word.clone());
-                    current_word.clear();
-                }
-                tokens.push(ch.to_string());
-            } else {
-                current_word.push(ch);
-            }
-        }
-
-        if !current_word.is_empty() {
-            tokens.push(current_word);
-        }
-
-        tokens
+    /// Receive the final usage once the stream completes. Only invoked when
+    /// usage is set.
+    pub fn with_on_usage<F>(mut self, callback: F) -> Self
+    where
+        F: FnOnce(Usage) + Send + 'static,
+    {
+        self.on_usage = Some(Box::new(callback));
+        self
     }
 
     /// Create a streaming response as Server-Sent Events
-    pub fn into_stream(self) -> Pin<Box<dyn Stream<Item = String> + Send>> {
-        let tokens = self.tokenize();
-        let id = self.id.clone();
-        let model = self.model.clone();
-        let created = self.created;
-        let latency = self.latency.clone();
-        let usage = self.usage.clone();
-        let on_complete = self.on_complete;
+    pub fn into_stream(mut self) -> Pin<Box<dyn Stream<Item = String> + Send>> {
+        let on_complete = self.on_complete.take();
+        let on_usage = self.on_usage.take();
+        let encoder = ChunkEncoder::new(
+            &self.id,
+            &self.model,
+            self.created,
+            Some(&self.system_fingerprint),
+        );
+        let mut frames = self.into_frames();
 
         Box::pin(stream! {
-            // Initial delay (time to first token)
-            let ttft = latency.sample_ttft();
-            if !ttft.is_zero() {
-                sleep(ttft).await;
-            }
-
-            // First chunk: role announcement
-            let role_chunk = ChatCompletionChunk::new(id.clone(), model.clone(), created)
-                .with_role();
-            yield format_sse(&role_chunk);
-
-            // Content chunks
-            for token in tokens {
-                // Inter-token delay
-                let tbt = latency.sample_tbt();
-                if !tbt.is_zero() {
-                    sleep(tbt).await;
-                }
-
-                let content_chunk = ChatCompletionChunk::new(id.clone(), model.clone(), created)
-                    .with_content(token);
-                yield format_sse(&content_chunk);
-            }
-
-            // Final chunk with finish_reason
-            let mut finish_chunk = ChatCompletionChunk::new(id.clone(), model.clone(), created)
-                .with_finish("stop".to_string());
-
-            // Include usage in final chunk if available
-            if let Some(u) = usage {
-                finish_chunk = finish_chunk.with_usage(u);
+            let mut final_usage = None;
+            while let Some(frame) = frames.next().await {
+                yield match frame {
+                    Frame::Role(usage) => encoder.role(usage.as_ref()),
+                    Frame::Content(piece, usage) => encoder.content(&piece, usage.as_ref()),
+                    Frame::Finish(reason, usage) => {
+                        let event = encoder.finish(reason, usage.as_ref());
+                        final_usage = usage;
+                        event
+                    }
+                };
             }
-            yield format_sse(&finish_chunk);
 
             // Done marker
             yield "data: [DONE]\n\n".to_string();
 
-            // Invoke completion callback
+            // Invoke completion callbacks
             if let Some(callback) = on_complete {
                 callback();
             }
+            if let (Some(callback), Some(usage)) = (on_usage, final_usage) {
+                callback(usage);
+            }
         })
     }
 
-    /// Create a stream that yields ChatCompletionChunk objects directly
+    /// Create a stream that yields ChatCompletionChunk objects directly.
+    /// This simulates word-by-word streaming (approximating token streaming).
     pub fn into_chunk_stream(self) -> Pin<Box<dyn Stream<Item = ChatCompletionChunk> + Send>> {
-        let tokens = self.tokenize();
-        let id = self.id.clone();
-        let model = self.model.clone();
-        let created = self.created;
-        let latency = self.latency.clone();
-        let usage = self.usage.clone();
+        let base = ChatCompletionChunk {
+            system_fingerprint: Some(self.system_fingerprint.clone()),
+            ..ChatCompletionChunk::new(self.id.clone(), self.model.clone(), self.created)
+        };
+        let frames = self.into_frames();
+        Box::pin(frames.map(move |frame| {
+            let (chunk, usage) = match frame {
+                Frame::Role(usage) => (base.clone().with_role(), usage),
+                Frame::Content(piece, usage) => (base.clone().with_content(piece), usage),
+                Frame::Finish(reason, usage) => {
+                    (base.clone().with_finish(reason.to_string()), usage)
+                }
+            };
+            match usage {
+                Some(usage) => chunk.with_usage(usage),
+                None => chunk,
+            }
+        }))
+    }
+
+    /// The stream's events, timed
+    fn into_frames(self) -> Pin<Box<dyn Stream<Item = Frame> + Send>> {
+        let mut latency = self.latency;
+        let usage = self.usage;
+        let continuous_usage = self.continuous_usage;
+        let tokenizer = self.tokenizer;
+        let sleeper = self.sleeper;
+        let mut watchdog = self.watchdog;
+        // Known text has a known piece count; produced pieces are counted.
+        let (pieces, total): (TextPieces, Option<usize>) = match self.source {
+            Source::Text(text) => {
+                let pieces = split_pieces(&text);
+                let total = pieces.len();
+                (Box::new(pieces.into_iter()), Some(total))
+            }
+            Source::Pieces(pieces) => (pieces, None),
+        };
 
         Box::pin(stream! {
+            let running = usage.as_ref().filter(|_| continuous_usage);
+            let mut emitted = 0;
+            let mut counted = 0u32;
+            let mut spacing = String::new();
+
             // Initial delay (time to first token)
-            let ttft = latency.sample_ttft();
-            if !ttft.is_zero() {
-                sleep(ttft).await;
+            let ttft = latency.ttft();
+            if watchdog.allows(ttft) && !ttft.is_zero() {
+                sleeper.sleep(ttft).await;
             }
 
             // First chunk: role announcement
-            yield ChatCompletionChunk::new(id.clone(), model.clone(), created).with_role();
+            yield Frame::Role(running.map(|u| progress_usage(u, total, 0, 0)));
 
-            // Content chunks
-            for token in tokens {
+            // Content chunks, until the text or the time budget runs out
+            for piece in pieces {
                 // Inter-token delay
-                let tbt = latency.sample_tbt();
+                let tbt = latency.tbt();
+                if !watchdog.allows(tbt) {
+                    break;
+                }
                 if !tbt.is_zero() {
-                    sleep(tbt).await;
+                    sleeper.sleep(tbt).await;
                 }
 
-                yield ChatCompletionChunk::new(id.clone(), model.clone(), created)
-                    .with_content(token);
+                emitted += 1;
+                if total.is_none() {
+                    // A word is counted with the whitespace before it, as
+                    // the tokenizer sees it in the full text; counted bare,
+                    // many words take an extra token.
+                    spacing.push_str(&piece);
+                    if !piece.trim().is_empty() {
+                        counted += tokenizer.count(&spacing) as u32;
+                        spacing.clear();
+                    }
+                }
+                let progress = running.map(|u| progress_usage(u, total, emitted, counted));
+                yield Frame::Content(piece, progress);
             }
 
-            // Final chunk with finish_reason
-            let mut finish_chunk = ChatCompletionChunk::new(id.clone(), model.clone(), created)
-                .with_finish("stop".to_string());
-
-            if let Some(u) = usage {
-                finish_chunk = finish_chunk.with_usage(u);
-            }
-            yield finish_chunk;
+            // Final chunk with finish_reason, and usage if available. A
+            // stream cut short reports what it sent.
+            let capped = watchdog.tripped();
+            yield Frame::Finish(
+                if capped { "length" } else { "stop" },
+                usage.map(|u| match total {
+                    Some(total) if capped => running_usage(&u, emitted, total),
+                    Some(_) => u,
+                    None => counted_usage(&u, counted),
+                }),
+            );
         })
     }
 }
 
+/// Running usage after `emitted` pieces: prorated over a known piece count,
+/// or taken from the tokens counted so far for produced pieces.
+fn progress_usage(
+    final_usage: &Usage,
+    total: Option<usize>,
+    emitted: usize,
+    counted: u32,
+) -> Usage {
+    match total {
+        Some(total) => running_usage(final_usage, emitted, total),
+        None => counted_usage(final_usage, counted),
+    }
+}
+
+/// Prompt usage with a counted completion
+fn counted_usage(usage: &Usage, completion_tokens: u32) -> Usage {
+    Usage {
+        prompt_tokens: usage.prompt_tokens,
+        completion_tokens,
+        total_tokens: usage.prompt_tokens + completion_tokens,
+    }
+}
+
+/// Usage after `emitted` of `total` stream pieces. Completion tokens are
+/// prorated so the running count is monotonic and lands exactly on the
+/// final usage once every piece has been sent.
+fn running_usage(final_usage: &Usage, emitted: usize, total: usize) -> Usage {
+    let completion_tokens = if total == 0 {
+        0
+    } else {
+        (final_usage.completion_tokens as u64 * emitted as u64 / total as u64) as u32
+    };
+    Usage {
+        prompt_tokens: final_usage.prompt_tokens,
+        completion_tokens,
+        total_tokens: final_usage.prompt_tokens + completion_tokens,
+    }
+}
+
 /// Format a chunk as Server-Sent Event
 pub fn format_sse(chunk: &ChatCompletionChunk) -> String {
     let json = serde_json::to_string(chunk).unwrap_or_else(|_| "{}".to_string());
     format!("data: {}\n\n", json)
 }
 
+/// Writes the SSE events of one stream's chunks without serde.
+///
+/// The `id`, `object`, `created` and `model` fields, and the system
+/// fingerprint, are the same in every chunk of a stream, so they are
+/// serialized once; each event is then one allocation of the right size.
+/// The output is what `format_sse` gives for the same chunk.
+#[derive(Debug, Clone)]
+pub struct ChunkEncoder {
+    /// `data: {"id":...,"model":...,"choices":[{"index":0,"delta":`
+    head: String,
+    /// `,"system_fingerprint":...`, or nothing
+    fingerprint: String,
+}
+
+impl ChunkEncoder {
+    pub fn new(id: &str, model: &str, created: i64, system_fingerprint: Option<&str>) -> Self {
+        let mut head = String::from("data: {\"id\":\"");
+        escape_json_into(&mut head, id);
+        head.push_str("\",\"object\":\"chat.completion.chunk\",\"created\":");
+        head.push_str(&created.to_string());
+        head.push_str(",\"model\":\"");
+        escape_json_into(&mut head, model);
+        head.push_str("\",\"choices\":[{\"index\":0,\"delta\":");
+        let fingerprint = system_fingerprint
+            .map(|fingerprint| {
+                let mut out = String::from(",\"system_fingerprint\":\"");
+                escape_json_into(&mut out, fingerprint);
+                out.push('"');
+                out
+            })
+            .unwrap_or_default();
+        Self { head, fingerprint }
+    }
+
+    /// The chunk announcing the assistant role
+    pub fn role(&self, usage: Option<&Usage>) -> String {
+        self.event(Delta::Role, None, usage)
+    }
+
+    /// A chunk adding `piece` to the content
+    pub fn content(&self, piece: &str, usage: Option<&Usage>) -> String {
+        self.event(Delta::Content(piece), None, usage)
+    }
+
+    /// The chunk ending the choice with `reason`
+    pub fn finish(&self, reason: &str, usage: Option<&Usage>) -> String {
+        self.event(Delta::Empty, Some(reason), usage)
+    }
+
+    fn event(
+        &self,
+        delta: Delta<'_>,
+        finish_reason: Option<&str>,
+        usage: Option<&Usage>,
+    ) -> String {
+        let content = match delta {
+            Delta::Content(content) => content,
+            _ => "",
+        };
+        // Room for the fixed parts, the content with a few escapes, the
+        // finish reason and usage counts
+        let capacity = self.head.len()
+            + content.len()
+            + content.len() / 8
+            + self.fingerprint.len()
+            + finish_reason.map_or(0, |reason| reason.len() + 20)
+            + usage.map_or(0, |_| 100)
+            + 16;
+        let mut out = String::with_capacity(capacity);
+        out.push_str(&self.head);
+        match delta {
+            Delta::Role => out.push_str("{\"role\":\"assistant\"}"),
+            Delta::Content(content) => {
+                out.push_str("{\"content\":\"");
+                escape_json_into(&mut out, content);
+                out.push_str("\"}");
+            }
+            Delta::Empty => out.push_str("{}"),
+        }
+        if let Some(reason) = finish_reason {
+            out.push_str(",\"finish_reason\":\"");
+            escape_json_into(&mut out, reason);
+            out.push('"');
+        }
+        out.push_str("}]");
+        out.push_str(&self.fingerprint);
+        if let Some(usage) = usage {
+            let _ = write!(
+                out,
+                ",\"usage\":{{\"prompt_tokens\":{},\"completion_tokens\":{},\"total_tokens\":{}}}",
+                usage.prompt_tokens, usage.completion_tokens, usage.total_tokens
+            );
+        }
+        out.push_str("}\n\n");
+        out
+    }
+}
+
+/// The delta of a chunk written by `ChunkEncoder`
+#[derive(Clone, Copy)]
+enum Delta<'a> {
+    Role,
+    Content(&'a str),
+    Empty,
+}
+
+/// Append `value` as the inside of a JSON string, escaped as serde_json
+/// escapes it
+fn escape_json_into(out: &mut String, value: &str) {
+    let mut start = 0;
+    for (i, byte) in value.bytes().enumerate() {
+        let escape = match byte {
+            b'"' => "\\\"",
+            b'\\' => "\\\\",
+            b'\n' => "\\n",
+            b'\r' => "\\r",
+            b'\t' => "\\t",
+            0x08 => "\\b",
+            0x0c => "\\f",
+            0x00..=0x1f => "",
+            _ => continue,
+        };
+        out.push_str(&value[start..i]);
+        if escape.is_empty() {
+            let _ = write!(out, "\\u{:04x}", byte);
+        } else {
+            out.push_str(escape);
+        }
+        start = i + 1;
+    }
+    out.push_str(&value[start..]);
+}
+
 /// Builder for creating token streams
 pub struct TokenStreamBuilder {
     id: Option<String>,
     model: String,
-    content: String,
-    latency: LatencyProfile,
+    source: Source,
+    latency: RequestLatency,
     usage: Option<Usage>,
+    continuous_usage: bool,
+    system_fingerprint: Option<String>,
+    tokenizer: Option<Arc<dyn TextTokenizer>>,
+    sleeper: Option<Arc<dyn Sleeper>>,
+    watchdog: StreamWatchdog,
     on_complete: Option<OnCompleteCallback>,
+    on_usage: Option<OnUsageCallback>,
 }
 
 impl TokenStreamBuilder {
     pub fn new(model: impl Into<String>, content: impl Into<String>) -> Self {
+        Self::with_source(model.into(), Source::Text(content.into()))
+    }
+
+    /// Build a stream over incrementally produced pieces (see
+    /// `TokenStream::from_pieces`)
+    pub fn from_pieces(model: impl Into<String>, pieces: TextPieces) -> Self {
+        Self::with_source(model.into(), Source::Pieces(pieces))
+    }
+
+    fn with_source(model: String, source: Source) -> Self {
         Self {
             id: None,
-            model: model.into(),
-            content: content.into(),
-            latency: LatencyProfile::default(),
+            model,
+            source,
+            latency: LatencyProfile::default().into(),
             usage: None,
+            continuous_usage: false,
+            system_fingerprint: None,
+            tokenizer: None,
+            sleeper: None,
+            watchdog: StreamWatchdog::unlimited(),
             on_complete: None,
+            on_usage: None,
         }
     }
 
@@ -214,8 +535,8 @@ impl TokenStreamBuilder {
         self
     }
 
-    pub fn latency(mut self, latency: LatencyProfile) -> Self {
-        self.latency = latency;
+    pub fn latency(mut self, latency: impl Into<RequestLatency>) -> Self {
+        self.latency = latency.into();
         self
     }
 
@@ -224,6 +545,35 @@ impl TokenStreamBuilder {
         self
     }
 
+    /// Attach running usage to every chunk (vLLM `continuous_usage_stats`)
+    pub fn continuous_usage(mut self, enabled: bool) -> Self {
+        self.continuous_usage = enabled;
+        self
+    }
+
+    pub fn system_fingerprint(mut self, fingerprint: impl Into<String>) -> Self {
+        self.system_fingerprint = Some(fingerprint.into());
+        self
+    }
+
+    /// Count produced pieces with this tokenizer
+    pub fn tokenizer(mut self, tokenizer: Arc<dyn TextTokenizer>) -> Self {
+        self.tokenizer = Some(tokenizer);
+        self
+    }
+
+    /// Wait out delays with this sleeper
+    pub fn sleeper(mut self, sleeper: Arc<dyn Sleeper>) -> Self {
+        self.sleeper = Some(sleeper);
+        self
+    }
+
+    /// End the stream with `length` once the watchdog's budget is spent
+    pub fn watchdog(mut self, watchdog: StreamWatchdog) -> Self {
+        self.watchdog = watchdog;
+        self
+    }
+
     /// Set a callback to be invoked when the stream completes
     pub fn on_complete<F>(mut self, callback: F) -> Self
     where
@@ -233,16 +583,33 @@ impl TokenStreamBuilder {
         self
     }
 
-    pub fn build(self) -> TokenStream {
-        let id = self.id.unwrap_or_else(|| prefixed_id("chatcmpl-"));
+    /// Set a callback to receive the final usage when the stream completes
+    pub fn on_usage<F>(mut self, callback: F) -> Self
+    where
+        F: FnOnce(Usage) + Send + 'static,
+    {
+        self.on_usage = Some(Box::new(callback));
+        self
+    }
 
-        let mut stream = TokenStream::new(id, self.model, self.content, self.latency);
-        if let Some(usage) = self.usage {
-            stream = stream.with_usage(usage);
+    pub fn build(self) -> TokenStream {
+        let id = self.id.unwrap_or_else(|| new_id(IdKind::ChatCompletion));
+
+        let mut stream = TokenStream::with_source(id, self.model, self.source, self.latency)
+            .with_continuous_usage(self.continuous_usage)
+            .with_watchdog(self.watchdog);
+        stream.usage = self.usage;
+        if let Some(fingerprint) = self.system_fingerprint {
+            stream.system_fingerprint = fingerprint;
         }
-        if let Some(on_complete) = self.on_complete {
-            stream = stream.with_on_complete(on_complete);
+        if let Some(tokenizer) = self.tokenizer {
+            stream.tokenizer = tokenizer;
         }
+        if let Some(sleeper) = self.sleeper {
+            stream.sleeper = sleeper;
+        }
+        stream.on_complete = self.on_complete;
+        stream.on_usage = self.on_usage;
         stream
     }
 }
@@ -256,6 +623,7 @@ pub fn create_role_chunk(id: &str, model: &str, created: i64) -> ChatCompletionC
             role: Some(Role::Assistant),
             content: None,
             tool_calls: None,
+            function_call: None,
         },
         finish_reason: None,
         logprobs: None,
@@ -266,6 +634,7 @@ pub fn create_role_chunk(id: &str, model: &str, created: i64) -> ChatCompletionC
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::pipeline::count_text_tokens;
     use futures_util::StreamExt;
 
     #[tokio::test]
@@ -318,6 +687,36 @@ mod tests {
         assert_eq!(last.usage.as_ref().unwrap().total_tokens, 15);
     }
 
+    #[tokio::test]
+    async fn test_stream_continuous_usage() {
+        let usage = Usage {
+            prompt_tokens: 10,
+            completion_tokens: 3,
+            total_tokens: 13,
+        };
+
+        let stream = TokenStreamBuilder::new("gpt-4", "one two three")
+            .latency(LatencyProfile::instant())
+            .usage(usage)
+            .continuous_usage(true)
+            .build();
+
+        let chunks: Vec<ChatCompletionChunk> = stream.into_chunk_stream().collect().await;
+
+        // Every chunk carries usage, completion tokens never go backwards,
+        // and the stream ends on the real totals.
+        assert!(chunks.iter().all(|c| c.usage.is_some()));
+        let counts: Vec<u32> = chunks
+            .iter()
+            .map(|c| c.usage.as_ref().unwrap().completion_tokens)
+            .collect();
+        assert_eq!(counts[0], 0);
+        assert!(counts.windows(2).all(|w| w[0] <= w[1]));
+        let last = chunks.last().unwrap().usage.as_ref().unwrap();
+        assert_eq!(last.completion_tokens, 3);
+        assert_eq!(last.total_tokens, 13);
+    }
+
     #[tokio::test]
     async fn test_sse_format() {
         let chunk =
@@ -339,10 +738,76 @@ mod tests {
             LatencyProfile::instant(),
         );
 
-        let tokens = stream.tokenize();
+        let chunks: Vec<ChatCompletionChunk> = stream.into_chunk_stream().collect().await;
+        let tokens: Vec<String> = chunks
+            .iter()
+            .filter_map(|c| c.choices[0].delta.content.clone())
+            .collect();
         assert_eq!(tokens, vec!["Hello,", " ", "world!"]);
     }
 
+    #[tokio::test]
+    async fn test_stream_from_pieces_counts_usage() {
+        let pieces: TextPieces = Box::new(
+            ["one", " ", "two", " ", "three"]
+                .into_iter()
+                .map(String::from),
+        );
+        let prompt = Usage {
+            prompt_tokens: 7,
+            completion_tokens: 0,
+            total_tokens: 7,
+        };
+        let (tx, rx) = std::sync::mpsc::channel();
+
+        let stream = TokenStreamBuilder::from_pieces("gpt-4", pieces)
+            .latency(LatencyProfile::instant())
+            .usage(prompt)
+            .continuous_usage(true)
+            .on_usage(move |u| tx.send(u).unwrap())
+            .build();
+        let chunks: Vec<String> = stream.into_stream().collect().await;
+        let parsed: Vec<ChatCompletionChunk> = chunks
+            .iter()
+            .filter_map(|c| c.strip_prefix("data: "))
+            .filter(|d| !d.starts_with("[DONE]"))
+            .map(|d| serde_json::from_str(d.trim()).unwrap())
+            .collect();
+
+        let counts: Vec<u32> = parsed
+            .iter()
+            .map(|c| c.usage.as_ref().unwrap().completion_tokens)
+            .collect();
+        assert!(counts.windows(2).all(|w| w[0] <= w[1]));
+        let last = parsed.last().unwrap().usage.clone().unwrap();
+        assert_eq!(last.completion_tokens, 3);
+        assert_eq!(last.total_tokens, 10);
+
+        let reported = rx.try_recv().unwrap();
+        assert_eq!(reported.completion_tokens, 3);
+    }
+
+    #[tokio::test]
+    async fn test_stream_from_pieces_counts_like_full_text() {
+        let text = "Ullamco reprehenderit, velit exercitation.";
+        let pieces: TextPieces = Box::new(split_pieces(text).into_iter());
+        let (tx, rx) = std::sync::mpsc::channel();
+
+        let stream = TokenStreamBuilder::from_pieces("gpt-4", pieces)
+            .latency(LatencyProfile::instant())
+            .usage(Usage {
+                prompt_tokens: 0,
+                completion_tokens: 0,
+                total_tokens: 0,
+            })
+            .on_usage(move |u| tx.send(u).unwrap())
+            .build();
+        let _: Vec<String> = stream.into_stream().collect().await;
+
+        let reported = rx.try_recv().unwrap();
+        assert_eq!(reported.completion_tokens as usize, count_text_tokens(text));
+    }
+
     #[tokio::test]
     async fn test_empty_content() {
         let stream = TokenStreamBuilder::new("gpt-4", "")
@@ -354,4 +819,119 @@ mod tests {
         // Should still have role and finish chunks
         assert!(chunks.len() >= 2);
     }
+
+    #[tokio::test]
+    async fn test_virtual_sleeper_skips_waiting() {
+        let sleeper = Arc::new(crate::sleep::VirtualSleeper::new());
+        // Half a second to the first token, then 50ms per token, no jitter
+        let latency = LatencyProfile::new(500, 0, 50, 0);
+        let stream = TokenStreamBuilder::new("gpt-5", "Hello there, world")
+            .latency(latency)
+            .sleeper(sleeper.clone())
+            .build();
+
+        let started = std::time::Instant::now();
+        let chunks: Vec<String> = stream.into_stream().collect().await;
+
+        assert!(chunks.last().unwrap().contains("[DONE]"));
+        assert!(started.elapsed() < std::time::Duration::from_millis(100));
+        assert!(sleeper.elapsed() >= std::time::Duration::from_millis(550));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_paused_clock_fast_forwards() {
+        let stream = TokenStreamBuilder::new("gpt-5", "Hello there, world")
+            .latency(LatencyProfile::new(500, 0, 50, 0))
+            .build();
+
+        let wall = std::time::Instant::now();
+        let simulated = crate::clock::now();
+        let chunks: Vec<String> = stream.into_stream().collect().await;
+
+        assert!(chunks.last().unwrap().contains("[DONE]"));
+        assert!(crate::clock::elapsed(simulated) >= std::time::Duration::from_millis(550));
+        assert!(wall.elapsed() < std::time::Duration::from_millis(500));
+    }
+
+    #[tokio::test]
+    async fn test_watchdog_ends_stream_with_length() {
+        let sleeper = Arc::new(crate::sleep::VirtualSleeper::new());
+        // 100ms to the first token and per token: two tokens fit in 300ms
+        let stream = TokenStreamBuilder::new("gpt-5", "one two three four")
+            .latency(LatencyProfile::new(100, 0, 100, 0))
+            .sleeper(sleeper.clone())
+            .usage(Usage {
+                prompt_tokens: 5,
+                completion_tokens: 4,
+                total_tokens: 9,
+            })
+            .watchdog(StreamWatchdog::new(std::time::Duration::from_millis(300)))
+            .build();
+
+        let chunks: Vec<ChatCompletionChunk> = stream.into_chunk_stream().collect().await;
+        let text: String = chunks
+            .iter()
+            .filter_map(|c| c.choices[0].delta.content.clone())
+            .collect();
+        assert_eq!(text, "one ");
+        let last = chunks.last().unwrap();
+        assert_eq!(last.choices[0].finish_reason.as_deref(), Some("length"));
+        // Usage covers the two of seven pieces sent
+        assert_eq!(last.usage.as_ref().unwrap().completion_tokens, 1);
+        assert_eq!(sleeper.elapsed(), std::time::Duration::from_millis(300));
+    }
+
+    #[test]
+    fn test_encoder_matches_serde() {
+        let usage = Usage {
+            prompt_tokens: 12,
+            completion_tokens: 3,
+            total_tokens: 15,
+        };
+        for fingerprint in [Some("fp_\"x\""), None] {
+            let encoder = ChunkEncoder::new("chatcmpl-1", "gpt-5\u{1}", 1700000000, fingerprint);
+            let base = ChatCompletionChunk {
+                system_fingerprint: fingerprint.map(str::to_string),
+                ..ChatCompletionChunk::new("chatcmpl-1".into(), "gpt-5\u{1}".into(), 1700000000)
+            };
+            assert_eq!(encoder.role(None), format_sse(&base.clone().with_role()));
+            for piece in [
+                "Hello",
+                " ",
+                "\"quoted\"\n\t\\",
+                "caf\u{e9} \u{1f600}",
+                "\u{8}\u{c}\u{1f}\u{7f}",
+            ] {
+                let chunk = base.clone().with_content(piece.to_string());
+                assert_eq!(encoder.content(piece, None), format_sse(&chunk));
+                let chunk = chunk.with_usage(usage.clone());
+                assert_eq!(encoder.content(piece, Some(&usage)), format_sse(&chunk));
+            }
+            let finish = base
+                .with_finish("stop".to_string())
+                .with_usage(usage.clone());
+            assert_eq!(encoder.finish("stop", Some(&usage)), format_sse(&finish));
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_events_match_chunks() {
+        let build = || {
+            TokenStreamBuilder::new("gpt-5", "Hello \"there\"\nworld")
+                .id("chatcmpl-fixed")
+                .latency(LatencyProfile::instant())
+                .usage(Usage {
+                    prompt_tokens: 5,
+                    completion_tokens: 4,
+                    total_tokens: 9,
+                })
+                .continuous_usage(true)
+                .build()
+        };
+        let events: Vec<String> = build().into_stream().collect().await;
+        let chunks: Vec<ChatCompletionChunk> = build().into_chunk_stream().collect().await;
+        let mut expected: Vec<String> = chunks.iter().map(format_sse).collect();
+        expected.push("data: [DONE]\n\n".to_string());
+        assert_eq!(events, expected);
+    }
 }
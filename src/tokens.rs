@@ -9,7 +9,22 @@
 // dominant throughput bottleneck. Caching keeps the encodings resident, so the
 // build cost is paid once per process. CoreBPE is Send + Sync, so sharing a
 // &'static across worker threads is safe.
-
+//
+// Decision: `Tokenizer` resolves the encoding once (per server, or per
+// counter) instead of matching the model name on every call, and its
+// `Estimate` variant derives a count from characters per token with no BPE
+// at all, for load tests where exact counts matter less than throughput.
+//
+// Decision: model names are resolved through an `EncodingMap`, the default
+// one unless counted with a map of its own: custom model names (fine-tunes,
+// in-house deployments) are mapped to an encoding by glob before the
+// built-in family table, and a model neither knows gets the map's fallback
+// encoding. The longest matching pattern wins, so `acme-*` and
+// `acme-vision-*` can both be mapped. Counting never fails on a model name.
+
+use crate::glob::glob_match;
+use crate::openai::Message;
+use serde::{Deserialize, Serialize};
 use std::sync::OnceLock;
 use tiktoken_rs::{cl100k_base, o200k_base, p50k_base, r50k_base, CoreBPE};
 
@@ -18,6 +33,8 @@ use tiktoken_rs::{cl100k_base, o200k_base, p50k_base, r50k_base, CoreBPE};
 pub enum TokenError {
     #[error("Failed to initialize tokenizer: {0}")]
     InitError(String),
+    #[error("Unknown encoding: {0}")]
+    UnknownEncoding(String),
 }
 
 /// Build (once) and return a shared reference to a cached encoding.
@@ -52,47 +69,265 @@ fn r50k() -> Result<&'static CoreBPE, TokenError> {
     cached(&CACHE, r50k_base)
 }
 
-/// Get the appropriate tokenizer for a model (cached, see module note)
-fn get_tokenizer_for_model(model: &str) -> Result<&'static CoreBPE, TokenError> {
-    // Model to encoding mapping based on OpenAI's documentation
-    let model_lower = model.to_lowercase();
+/// A tiktoken encoding
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Encoding {
+    /// GPT-5, GPT-4o and the o-series
+    O200kBase,
+    /// GPT-4 and GPT-3.5; also used for Claude, Gemini and DeepSeek
+    Cl100kBase,
+    /// text-davinci-002/003 and code-* models
+    P50kBase,
+    /// GPT-3 (ada, babbage, curie)
+    R50kBase,
+}
+
+impl Encoding {
+    /// The encoding a model family uses, or `None` for an unknown model
+    pub fn for_model(model: &str) -> Option<Self> {
+        // Model to encoding mapping based on OpenAI's documentation
+        let model_lower = model.to_lowercase();
+
+        if model_lower.contains("gpt-5")
+            || model_lower.contains("gpt-4o")
+            || model_lower.starts_with("o1")
+            || model_lower.starts_with("o3")
+            || model_lower.starts_with("o4")
+            || model_lower.contains("chatgpt-4o")
+        {
+            return Some(Self::O200kBase);
+        }
+
+        if model_lower.contains("gpt-4")
+            || model_lower.contains("gpt-3.5")
+            || model_lower.contains("text-embedding")
+            || model_lower.contains("claude")
+            || model_lower.contains("gemini")
+            || model_lower.contains("deepseek")
+        {
+            return Some(Self::Cl100kBase);
+        }
+
+        if model_lower.contains("davinci") || model_lower.contains("code-") {
+            return Some(Self::P50kBase);
+        }
+
+        if model_lower.contains("ada")
+            || model_lower.contains("babbage")
+            || model_lower.contains("curie")
+        {
+            return Some(Self::R50kBase);
+        }
+
+        None
+    }
 
-    // o200k_base: GPT-5, GPT-4o, O-series and newer models
-    if model_lower.contains("gpt-5")
-        || model_lower.contains("gpt-4o")
-        || model_lower.starts_with("o1")
-        || model_lower.starts_with("o3")
-        || model_lower.starts_with("o4")
-        || model_lower.contains("chatgpt-4o")
-    {
-        return o200k();
+    /// tiktoken name, e.g. `"o200k_base"`
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::O200kBase => "o200k_base",
+            Self::Cl100kBase => "cl100k_base",
+            Self::P50kBase => "p50k_base",
+            Self::R50kBase => "r50k_base",
+        }
     }
 
-    // cl100k_base: GPT-4, text-embedding, Claude, Gemini, DeepSeek
-    if model_lower.contains("gpt-4")
-        || model_lower.contains("text-embedding")
-        || model_lower.contains("claude")
-        || model_lower.contains("gemini")
-        || model_lower.contains("deepseek")
-    {
-        return cl100k();
+    /// The cached BPE for this encoding (see module note)
+    fn bpe(self) -> Result<&'static CoreBPE, TokenError> {
+        match self {
+            Self::O200kBase => o200k(),
+            Self::Cl100kBase => cl100k(),
+            Self::P50kBase => p50k(),
+            Self::R50kBase => r50k(),
+        }
     }
+}
 
-    // p50k_base: text-davinci-002, text-davinci-003, code-* models
-    if model_lower.contains("davinci") || model_lower.contains("code-") {
-        return p50k();
+impl std::str::FromStr for Encoding {
+    type Err = TokenError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "o200k_base" => Ok(Self::O200kBase),
+            "cl100k_base" => Ok(Self::Cl100kBase),
+            "p50k_base" => Ok(Self::P50kBase),
+            "r50k_base" => Ok(Self::R50kBase),
+            other => Err(TokenError::UnknownEncoding(other.to_string())),
+        }
     }
+}
 
-    // r50k_base: GPT-3 models (ada, babbage, curie, davinci without version)
-    if model_lower.contains("ada")
-        || model_lower.contains("babbage")
-        || model_lower.contains("curie")
-    {
-        return r50k();
+impl std::fmt::Display for Encoding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
     }
+}
+
+/// Which encoding a model name is counted with: custom patterns, then the
+/// built-in families, then a fallback
+#[derive(Debug, Clone, PartialEq)]
+pub struct EncodingMap {
+    /// Glob patterns (`acme-*`) and their encodings
+    models: Vec<(String, Encoding)>,
+    fallback: Encoding,
+}
 
-    // Default to cl100k_base as it's the most common for modern models
-    cl100k()
+impl Default for EncodingMap {
+    fn default() -> Self {
+        Self {
+            models: Vec::new(),
+            // The most common encoding of current models
+            fallback: Encoding::Cl100kBase,
+        }
+    }
+}
+
+impl EncodingMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count models matching `pattern` (`*` and `?` globs) with `encoding`;
+    /// a later mapping of the same pattern replaces the earlier one
+    pub fn with_model(mut self, pattern: impl Into<String>, encoding: Encoding) -> Self {
+        let pattern = pattern.into();
+        self.models.retain(|(existing, _)| *existing != pattern);
+        self.models.push((pattern, encoding));
+        self
+    }
+
+    /// Encoding of models no pattern or built-in family covers
+    pub fn with_fallback(mut self, encoding: Encoding) -> Self {
+        self.fallback = encoding;
+        self
+    }
+
+    pub fn fallback(&self) -> Encoding {
+        self.fallback
+    }
+
+    /// The encoding of the longest pattern matching `model`, else its
+    /// built-in family's; `None` for a model neither knows
+    pub fn lookup(&self, model: &str) -> Option<Encoding> {
+        self.models
+            .iter()
+            .filter(|(pattern, _)| glob_match(pattern, model))
+            .max_by_key(|(pattern, _)| pattern.chars().count())
+            .map(|(_, encoding)| *encoding)
+            .or_else(|| Encoding::for_model(model))
+    }
+
+    /// The encoding `model` is counted with
+    pub fn encoding_for(&self, model: &str) -> Encoding {
+        self.lookup(model).unwrap_or(self.fallback)
+    }
+
+    /// The model's encoding in this map, or `fallback` for a model it does
+    /// not know
+    pub fn tokenizer(&self, model: &str, fallback: Tokenizer) -> Tokenizer {
+        self.lookup(model).map_or(fallback, Tokenizer::Bpe)
+    }
+
+    /// Count tokens in `text` as `model` does
+    pub fn count_tokens(&self, text: &str, model: &str) -> Result<usize, TokenError> {
+        let bpe = self.encoding_for(model).bpe()?;
+        Ok(bpe.encode_with_special_tokens(text).len())
+    }
+
+    /// `count_chat_tokens`, with this map's encodings
+    pub fn count_chat_tokens(
+        &self,
+        messages: &[Message],
+        model: &str,
+    ) -> Result<usize, TokenError> {
+        let mut total = CHAT_REPLY_PRIMING_TOKENS;
+        for message in messages {
+            total += CHAT_TOKENS_PER_MESSAGE + self.count_tokens(message.role.as_str(), model)?;
+            if let Some(content) = &message.content {
+                total += self.count_tokens(&content.text(), model)?;
+                for image in content.images() {
+                    total += estimate_image_tokens(image.detail.as_deref());
+                }
+            }
+            if let Some(name) = &message.name {
+                total += CHAT_TOKENS_PER_NAME + self.count_tokens(name, model)?;
+            }
+        }
+        Ok(total)
+    }
+
+    /// A `TokenCounter` for `model`, with this map's encoding
+    pub fn counter(&self, model: &str) -> Result<TokenCounter, TokenError> {
+        Ok(TokenCounter {
+            bpe: self.encoding_for(model).bpe()?,
+            model: model.to_string(),
+        })
+    }
+}
+
+/// How simulated text is counted: a real encoding, or a characters-per-token
+/// estimate that skips BPE entirely
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Tokenizer {
+    Bpe(Encoding),
+    Estimate { chars_per_token: f64 },
+}
+
+impl Default for Tokenizer {
+    fn default() -> Self {
+        Self::Bpe(Encoding::Cl100kBase)
+    }
+}
+
+impl Tokenizer {
+    /// Rough English average for OpenAI encodings
+    pub const DEFAULT_CHARS_PER_TOKEN: f64 = 4.0;
+
+    pub fn count(&self, text: &str) -> usize {
+        match *self {
+            Self::Bpe(encoding) => match encoding.bpe() {
+                Ok(bpe) => bpe.encode_with_special_tokens(text).len(),
+                Err(_) => text.split_whitespace().count(),
+            },
+            Self::Estimate { chars_per_token } => (text.chars().count() as f64
+                / chars_per_token.max(f64::MIN_POSITIVE))
+            .ceil() as usize,
+        }
+    }
+
+    /// The first `max_tokens` tokens of `text`; `None` when it already fits
+    pub fn truncate(&self, text: &str, max_tokens: usize) -> Option<String> {
+        match *self {
+            Self::Bpe(encoding) => {
+                let bpe = encoding.bpe().ok()?;
+                let tokens = bpe.encode_with_special_tokens(text);
+                if tokens.len() <= max_tokens {
+                    return None;
+                }
+                // A cut can land inside a multi-byte character; back off
+                // until the prefix decodes
+                (0..=max_tokens)
+                    .rev()
+                    .find_map(|end| bpe.decode(&tokens[..end]).ok())
+            }
+            Self::Estimate { chars_per_token } => {
+                let keep = (max_tokens as f64 * chars_per_token).floor() as usize;
+                let end = text.char_indices().nth(keep)?.0;
+                Some(text[..end].to_string())
+            }
+        }
+    }
+}
+
+impl crate::pipeline::TextTokenizer for Tokenizer {
+    fn count(&self, text: &str) -> usize {
+        Tokenizer::count(self, text)
+    }
+
+    fn truncate(&self, text: &str, max_tokens: usize) -> Option<String> {
+        Tokenizer::truncate(self, text, max_tokens)
+    }
 }
 
 /// Count tokens in a text string for a specific model
@@ -104,8 +339,7 @@ fn get_tokenizer_for_model(model: &str) -> Result<&'static CoreBPE, TokenError>
 /// # Returns
 /// The number of tokens in the text
 pub fn count_tokens(text: &str, model: &str) -> Result<usize, TokenError> {
-    let bpe = get_tokenizer_for_model(model)?;
-    Ok(bpe.encode_with_special_tokens(text).len())
+    EncodingMap::default().count_tokens(text, model)
 }
 
 /// Count tokens in a text string using default encoding (cl100k_base)
@@ -122,10 +356,14 @@ pub struct TokenCounter {
 impl TokenCounter {
     /// Create a new TokenCounter for a specific model
     pub fn new(model: &str) -> Result<Self, TokenError> {
-        let bpe = get_tokenizer_for_model(model)?;
+        EncodingMap::default().counter(model)
+    }
+
+    /// Create a TokenCounter for an encoding regardless of model
+    pub fn for_encoding(encoding: Encoding) -> Result<Self, TokenError> {
         Ok(Self {
-            bpe,
-            model: model.to_string(),
+            bpe: encoding.bpe()?,
+            model: encoding.name().to_string(),
         })
     }
 
@@ -186,10 +424,56 @@ pub fn estimate_message_tokens(
     Ok(content_tokens + role_tokens + 4)
 }
 
+/// Framing tokens around every chat message (`<|start|>{role}...<|end|>`)
+pub const CHAT_TOKENS_PER_MESSAGE: usize = 3;
+/// Extra token for a message that carries a `name`
+pub const CHAT_TOKENS_PER_NAME: usize = 1;
+/// Tokens priming the assistant reply (`<|start|>assistant<|message|>`)
+pub const CHAT_REPLY_PRIMING_TOKENS: usize = 3;
+
+/// Count the prompt tokens of a chat conversation, chat framing included.
+///
+/// Follows OpenAI's published accounting for current chat models: each message
+/// costs its role, content and name plus fixed framing, and the reply priming
+/// is added once. Image parts use `estimate_image_tokens`.
+pub fn count_chat_tokens(messages: &[Message], model: &str) -> Result<usize, TokenError> {
+    EncodingMap::default().count_chat_tokens(messages, model)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_count_chat_tokens() {
+        let messages = vec![
+            Message::system("You are a helpful assistant."),
+            Message::user("Hello!"),
+        ];
+        let content = count_tokens("You are a helpful assistant.", "gpt-4o").unwrap()
+            + count_tokens("Hello!", "gpt-4o").unwrap();
+        let roles =
+            count_tokens("system", "gpt-4o").unwrap() + count_tokens("user", "gpt-4o").unwrap();
+        assert_eq!(
+            count_chat_tokens(&messages, "gpt-4o").unwrap(),
+            content + roles + 2 * CHAT_TOKENS_PER_MESSAGE + CHAT_REPLY_PRIMING_TOKENS
+        );
+
+        let mut named = Message::user("Hello!");
+        named.name = Some("alice".to_string());
+        let with_name = count_chat_tokens(&[named], "gpt-4o").unwrap();
+        let without = count_chat_tokens(&[Message::user("Hello!")], "gpt-4o").unwrap();
+        assert_eq!(
+            with_name,
+            without + CHAT_TOKENS_PER_NAME + count_tokens("alice", "gpt-4o").unwrap()
+        );
+
+        assert_eq!(
+            count_chat_tokens(&[], "gpt-4o").unwrap(),
+            CHAT_REPLY_PRIMING_TOKENS
+        );
+    }
+
     #[test]
     fn test_count_tokens_gpt4() {
         // "Hello, world!" should be around 4 tokens
@@ -276,4 +560,85 @@ mod tests {
         assert!(count_o3 > 0);
         assert_eq!(count_o3, count_o4);
     }
+
+    #[test]
+    fn test_encoding_for_model() {
+        assert_eq!(Encoding::for_model("gpt-5-mini"), Some(Encoding::O200kBase));
+        assert_eq!(Encoding::for_model("GPT-4"), Some(Encoding::Cl100kBase));
+        assert_eq!(Encoding::for_model("my-finetune"), None);
+        assert_eq!("p50k_base".parse::<Encoding>().unwrap(), Encoding::P50kBase);
+        assert!("gpt2".parse::<Encoding>().is_err());
+        assert_eq!(Encoding::R50kBase.to_string(), "r50k_base");
+    }
+
+    #[test]
+    fn test_encoding_map() {
+        let map = EncodingMap::new()
+            .with_model("acme-*", Encoding::O200kBase)
+            .with_model("acme-legacy-*", Encoding::P50kBase)
+            .with_model("gpt-4-custom", Encoding::R50kBase)
+            .with_fallback(Encoding::O200kBase);
+        assert_eq!(map.encoding_for("acme-large"), Encoding::O200kBase);
+        // The longest matching pattern wins
+        assert_eq!(map.encoding_for("acme-legacy-v1"), Encoding::P50kBase);
+        // Custom mappings come before the built-in families
+        assert_eq!(map.encoding_for("gpt-4-custom"), Encoding::R50kBase);
+        assert_eq!(map.encoding_for("gpt-4"), Encoding::Cl100kBase);
+        assert_eq!(map.lookup("in-house-llm"), None);
+        assert_eq!(map.encoding_for("in-house-llm"), Encoding::O200kBase);
+        assert_eq!(
+            map.count_tokens("Hello, world!", "in-house-llm").unwrap(),
+            count_tokens("Hello, world!", "gpt-5").unwrap()
+        );
+
+        let remapped = map.with_model("acme-*", Encoding::Cl100kBase);
+        assert_eq!(remapped.encoding_for("acme-large"), Encoding::Cl100kBase);
+        assert_eq!(
+            EncodingMap::default().encoding_for("in-house-llm"),
+            Encoding::Cl100kBase
+        );
+    }
+
+    #[test]
+    fn test_tokenizer_for_model_fallback() {
+        let estimate = Tokenizer::Estimate {
+            chars_per_token: 4.0,
+        };
+        let map = EncodingMap::default();
+        assert_eq!(
+            map.tokenizer("gpt-4o", estimate),
+            Tokenizer::Bpe(Encoding::O200kBase)
+        );
+        assert_eq!(map.tokenizer("my-finetune", estimate), estimate);
+        assert_eq!(
+            Tokenizer::Bpe(Encoding::O200kBase).count("Hello, world!"),
+            count_tokens("Hello, world!", "gpt-5").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_estimate_tokenizer() {
+        let tokenizer = Tokenizer::Estimate {
+            chars_per_token: 4.0,
+        };
+        assert_eq!(tokenizer.count(""), 0);
+        assert_eq!(tokenizer.count("abcd"), 1);
+        assert_eq!(tokenizer.count("abcde"), 2);
+        // Characters, not bytes
+        assert_eq!(tokenizer.count("éééé"), 1);
+
+        assert_eq!(
+            tokenizer.truncate("abcdefghij", 2).as_deref(),
+            Some("abcdefgh")
+        );
+        assert_eq!(tokenizer.truncate("abcd", 1), None);
+    }
+
+    #[test]
+    fn test_bpe_truncate() {
+        let tokenizer = Tokenizer::Bpe(Encoding::Cl100kBase);
+        let cut = tokenizer.truncate("one two three four five", 3).unwrap();
+        assert_eq!(tokenizer.count(&cut), 3);
+        assert_eq!(tokenizer.truncate("one two", 5), None);
+    }
 }
@@ -0,0 +1,327 @@
+// Tool Call Simulation
+// Decides when a generated chat completion answers with tool calls instead of
+// text, and how many calls a single assistant turn carries.
+//
+// Decision: calls are drawn from the request's own tool list, so any client
+// works without per-tool configuration. The number of parallel calls follows
+// a weight table instead of a fixed count: executors tend to break on the
+// turn shapes they rarely see, and a distribution keeps those in the mix.
+// Arguments are an empty object unless `schema` arguments are asked for, in
+// which case they are faked from the tool's JSON Schema; scripted mode covers
+// exact arguments. An arguments seed fixes each tool's arguments across
+// requests while which tools get called still varies.
+
+use crate::generator::request_rng;
+use crate::ids::{new_id, IdKind};
+use crate::openai::{ChatCompletionRequest, FunctionCallChoice, ToolChoice};
+use crate::schema_faker::fake_arguments;
+use crate::script::SimToolCall;
+use rand::rngs::StdRng;
+use rand::{RngExt, SeedableRng};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+/// Where the arguments of simulated tool calls come from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolArguments {
+    /// Always `{}`
+    #[default]
+    Empty,
+    /// Values faked from the tool's `parameters` schema
+    Schema,
+}
+
+/// Configuration for simulated tool calls in generated responses
+#[derive(Debug, Clone)]
+pub struct ToolCallConfig {
+    /// Probability (0.0-1.0) that a request offering tools with
+    /// `tool_choice: "auto"` is answered with tool calls
+    pub call_rate: f64,
+    /// Relative weights of turns with 1, 2, 3, ... parallel calls
+    pub count_weights: Vec<f64>,
+    /// How call arguments are filled in
+    pub arguments: ToolArguments,
+    /// Seed for schema arguments, so each tool gets the same arguments on
+    /// every call; drawn from the request's randomness if unset
+    pub arguments_seed: Option<u64>,
+}
+
+impl Default for ToolCallConfig {
+    fn default() -> Self {
+        Self::none()
+    }
+}
+
+impl ToolCallConfig {
+    /// Only calls the request forces are made, one per turn
+    pub fn none() -> Self {
+        Self {
+            call_rate: 0.0,
+            count_weights: vec![1.0],
+            arguments: ToolArguments::Empty,
+            arguments_seed: None,
+        }
+    }
+
+    pub fn with_call_rate(mut self, rate: f64) -> Self {
+        self.call_rate = rate.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn with_count_weights(mut self, weights: Vec<f64>) -> Self {
+        self.count_weights = weights;
+        self
+    }
+
+    pub fn with_arguments(mut self, arguments: ToolArguments) -> Self {
+        self.arguments = arguments;
+        self
+    }
+
+    pub fn with_arguments_seed(mut self, seed: u64) -> Self {
+        self.arguments_seed = Some(seed);
+        self
+    }
+
+    /// Tool calls to answer the request with; empty means answer with text.
+    ///
+    /// `tool_choice: "none"` never calls, `"required"` always does, and a
+    /// named tool or legacy `function_call` is called exactly once. Multiple
+    /// calls are only drawn when the request allows parallel tool calls.
+    pub fn simulate(&self, request: &ChatCompletionRequest) -> Vec<SimToolCall> {
+        let mut rng = request_rng(request);
+        self.simulate_with(request, &mut rng)
+    }
+
+    fn simulate_with(
+        &self,
+        request: &ChatCompletionRequest,
+        rng: &mut impl rand::Rng,
+    ) -> Vec<SimToolCall> {
+        let functions: Vec<(&str, Option<&Value>)> = match (&request.tools, &request.functions) {
+            (Some(tools), _) => tools
+                .iter()
+                .map(|t| (t.function.name.as_str(), t.function.parameters.as_ref()))
+                .collect(),
+            (None, Some(functions)) => functions
+                .iter()
+                .map(|f| (f.name.as_str(), f.parameters.as_ref()))
+                .collect(),
+            (None, None) => Vec::new(),
+        };
+        if let Some(name) = forced_name(request) {
+            let schema = functions
+                .iter()
+                .find(|(function, _)| *function == name)
+                .and_then(|(_, schema)| *schema);
+            return vec![self.new_call(name, schema, rng)];
+        }
+        if functions.is_empty() {
+            return Vec::new();
+        }
+
+        let should_call = match (&request.tool_choice, &request.function_call) {
+            (Some(ToolChoice::String(mode)), _) | (None, Some(FunctionCallChoice::Mode(mode))) => {
+                match mode.as_str() {
+                    "none" => false,
+                    "required" => true,
+                    _ => rng.random_bool(self.call_rate.clamp(0.0, 1.0)),
+                }
+            }
+            _ => rng.random_bool(self.call_rate.clamp(0.0, 1.0)),
+        };
+        if !should_call {
+            return Vec::new();
+        }
+
+        let parallel =
+            !request.uses_legacy_functions() && request.parallel_tool_calls != Some(false);
+        let count = if parallel {
+            sample_count(&self.count_weights, rng)
+        } else {
+            1
+        };
+        (0..count)
+            .map(|_| {
+                let (name, schema) = functions[rng.random_range(0..functions.len())];
+                self.new_call(name, schema, rng)
+            })
+            .collect()
+    }
+
+    fn new_call(
+        &self,
+        name: &str,
+        schema: Option<&Value>,
+        rng: &mut impl rand::Rng,
+    ) -> SimToolCall {
+        let arguments = match (self.arguments, self.arguments_seed) {
+            (ToolArguments::Empty, _) => serde_json::json!({}),
+            (ToolArguments::Schema, None) => fake_arguments(schema, rng),
+            (ToolArguments::Schema, Some(seed)) => {
+                let mut hasher = DefaultHasher::new();
+                seed.hash(&mut hasher);
+                name.hash(&mut hasher);
+                fake_arguments(schema, &mut StdRng::seed_from_u64(hasher.finish()))
+            }
+        };
+        SimToolCall {
+            name: name.to_string(),
+            arguments,
+            id: Some(new_id(IdKind::ToolCall)),
+        }
+    }
+}
+
+/// Function the request forces, through `tool_choice` or `function_call`
+fn forced_name(request: &ChatCompletionRequest) -> Option<&str> {
+    match &request.tool_choice {
+        Some(ToolChoice::Object { function, .. }) => Some(&function.name),
+        _ => request.forced_function(),
+    }
+}
+
+/// Draw a call count: weight `i` is the chance of `i + 1` calls
+fn sample_count(weights: &[f64], rng: &mut impl rand::Rng) -> usize {
+    let total: f64 = weights.iter().filter(|w| **w > 0.0).sum();
+    if total <= 0.0 {
+        return 1;
+    }
+    let mut roll = rng.random_range(0.0..total);
+    for (i, weight) in weights.iter().enumerate() {
+        if *weight <= 0.0 {
+            continue;
+        }
+        if roll < *weight {
+            return i + 1;
+        }
+        roll -= weight;
+    }
+    weights.iter().rposition(|w| *w > 0.0).unwrap_or(0) + 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::openai::{Function, Message, Tool, ToolChoiceFunction};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn request(tools: &[&str]) -> ChatCompletionRequest {
+        let mut request: ChatCompletionRequest = serde_json::from_value(serde_json::json!({
+            "model": "gpt-5",
+            "messages": [],
+        }))
+        .unwrap();
+        request.messages.push(Message::user("hi"));
+        request.tools = Some(
+            tools
+                .iter()
+                .map(|name| Tool {
+                    tool_type: "function".to_string(),
+                    function: Function {
+                        name: name.to_string(),
+                        description: None,
+                        parameters: None,
+                    },
+                })
+                .collect(),
+        );
+        request
+    }
+
+    #[test]
+    fn test_no_tools_no_calls() {
+        let config = ToolCallConfig::none().with_call_rate(1.0);
+        let mut request = request(&[]);
+        request.tools = None;
+        assert!(config.simulate(&request).is_empty());
+    }
+
+    #[test]
+    fn test_call_rate_and_tool_choice() {
+        let always = ToolCallConfig::none().with_call_rate(1.0);
+        let never = ToolCallConfig::none();
+        let mut request = request(&["search", "fetch"]);
+
+        let calls = always.simulate(&request);
+        assert_eq!(calls.len(), 1);
+        assert!(["search", "fetch"].contains(&calls[0].name.as_str()));
+        assert!(calls[0].id.as_deref().unwrap().starts_with("call_"));
+        assert!(never.simulate(&request).is_empty());
+
+        request.tool_choice = Some(ToolChoice::String("required".to_string()));
+        assert_eq!(never.simulate(&request).len(), 1);
+
+        request.tool_choice = Some(ToolChoice::String("none".to_string()));
+        assert!(always.simulate(&request).is_empty());
+
+        request.tool_choice = Some(ToolChoice::Object {
+            choice_type: "function".to_string(),
+            function: ToolChoiceFunction {
+                name: "fetch".to_string(),
+            },
+        });
+        let calls = never.simulate(&request);
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].name, "fetch");
+    }
+
+    #[test]
+    fn test_parallel_count_distribution() {
+        let config = ToolCallConfig::none()
+            .with_call_rate(1.0)
+            .with_count_weights(vec![0.0, 1.0, 1.0]);
+        let mut request = request(&["search"]);
+        let mut rng = StdRng::seed_from_u64(7);
+
+        let counts: Vec<usize> = (0..200)
+            .map(|_| config.simulate_with(&request, &mut rng).len())
+            .collect();
+        assert!(counts.iter().all(|c| *c == 2 || *c == 3));
+        assert!(counts.contains(&2) && counts.contains(&3));
+
+        request.parallel_tool_calls = Some(false);
+        assert_eq!(config.simulate_with(&request, &mut rng).len(), 1);
+    }
+
+    #[test]
+    fn test_schema_arguments() {
+        let mut request = request(&["get_weather"]);
+        request.tools.as_mut().unwrap()[0].function.parameters = Some(serde_json::json!({
+            "type": "object",
+            "properties": {
+                "location": {"type": "string"},
+                "unit": {"type": "string", "enum": ["celsius", "fahrenheit"]},
+                "days": {"type": "integer", "minimum": 1, "maximum": 3}
+            },
+            "required": ["location", "unit", "days"]
+        }));
+        request.tool_choice = Some(ToolChoice::String("required".to_string()));
+
+        let empty = ToolCallConfig::none().simulate(&request);
+        assert_eq!(empty[0].arguments, serde_json::json!({}));
+
+        let faked = ToolCallConfig::none().with_arguments(ToolArguments::Schema);
+        let arguments = &faked.simulate(&request)[0].arguments;
+        assert!(arguments["location"].is_string());
+        assert!(["celsius", "fahrenheit"].contains(&arguments["unit"].as_str().unwrap()));
+        assert!((1..=3).contains(&arguments["days"].as_i64().unwrap()));
+
+        // A seed fixes the arguments across unrelated requests
+        let seeded = faked.with_arguments_seed(42);
+        let first = seeded.simulate(&request)[0].arguments.clone();
+        request.messages.push(Message::user("and tomorrow?"));
+        assert_eq!(seeded.simulate(&request)[0].arguments, first);
+    }
+
+    #[test]
+    fn test_sample_count() {
+        let mut rng = StdRng::seed_from_u64(1);
+        assert_eq!(sample_count(&[], &mut rng), 1);
+        assert_eq!(sample_count(&[0.0, 0.0, 5.0], &mut rng), 3);
+    }
+}
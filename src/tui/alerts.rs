@@ -0,0 +1,157 @@
+//! Alarm thresholds for the dashboard.
+//!
+//! Each refresh compares the stats with the `[tui.alerts]` thresholds. A
+//! breached threshold turns its panel red and logs a line in the alerts
+//! pane; recovering logs another, so a long soak test leaves a record of
+//! when things went wrong even if nobody was watching at the time.
+
+use super::ui::format_uptime;
+use crate::cli::AlertsConfig;
+use crate::stats::StatsSnapshot;
+use std::collections::VecDeque;
+
+/// Lines kept in the alerts pane
+const MAX_ALERT_LINES: usize = 100;
+
+/// Which thresholds are breached right now
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Alarms {
+    pub error_rate: bool,
+    pub p99_latency: bool,
+    pub active_requests: bool,
+}
+
+/// Thresholds, their current state and the log of changes
+#[derive(Debug, Default)]
+pub struct Alerts {
+    thresholds: AlertsConfig,
+    /// Current state, for the panels
+    pub alarms: Alarms,
+    /// Breaches and recoveries, oldest first
+    pub log: VecDeque<String>,
+}
+
+impl Alerts {
+    pub fn new(thresholds: AlertsConfig) -> Self {
+        Self {
+            thresholds,
+            ..Self::default()
+        }
+    }
+
+    /// Whether any threshold is set, and so the alerts pane is shown
+    pub fn is_active(&self) -> bool {
+        self.thresholds.is_active()
+    }
+
+    /// Compare a fresh snapshot with the thresholds, logging changes
+    pub fn check(&mut self, stats: &StatsSnapshot) {
+        let at = format_uptime(stats.uptime_secs);
+        let thresholds = self.thresholds.clone();
+
+        if let Some(limit) = thresholds.error_rate_percent {
+            let rate = error_rate_percent(stats);
+            let breached = stats.total_requests > 0 && rate > limit;
+            let line = format!("error rate {:.1}% (limit {:.1}%)", rate, limit);
+            let alarm = self.alarms.error_rate;
+            self.alarms.error_rate = self.note(&at, alarm, breached, line);
+        }
+        if let Some(limit) = thresholds.p99_latency_ms {
+            let p99 = stats.p99_latency_ms.unwrap_or(0.0);
+            let line = format!("p99 latency {:.0} ms (limit {:.0} ms)", p99, limit);
+            let alarm = self.alarms.p99_latency;
+            self.alarms.p99_latency = self.note(&at, alarm, p99 > limit, line);
+        }
+        if let Some(limit) = thresholds.active_requests {
+            let active = stats.active_requests;
+            let line = format!("{} active requests (limit {})", active, limit);
+            let alarm = self.alarms.active_requests;
+            self.alarms.active_requests = self.note(&at, alarm, active > limit, line);
+        }
+    }
+
+    /// Log a change of one alarm's state and return the new state
+    fn note(&mut self, at: &str, was: bool, breached: bool, line: String) -> bool {
+        if was != breached {
+            let level = if breached { "ALERT" } else { "OK" };
+            self.log.push_back(format!("[{}] {:5} {}", at, level, line));
+            if self.log.len() > MAX_ALERT_LINES {
+                self.log.pop_front();
+            }
+        }
+        breached
+    }
+}
+
+/// Errors as a percentage of all requests
+pub fn error_rate_percent(stats: &StatsSnapshot) -> f64 {
+    if stats.total_requests == 0 {
+        return 0.0;
+    }
+    stats.total_errors as f64 / stats.total_requests as f64 * 100.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::Stats;
+    use crate::tui::app::App;
+    use crate::tui::history::render_text;
+
+    #[test]
+    fn breaches_and_recoveries_are_logged_once() {
+        let mut alerts = Alerts::new(AlertsConfig {
+            error_rate_percent: Some(10.0),
+            active_requests: Some(1),
+            ..AlertsConfig::default()
+        });
+        let mut stats = Stats::new().snapshot();
+        stats.total_requests = 10;
+        stats.total_errors = 5;
+        stats.active_requests = 1;
+
+        alerts.check(&stats);
+        alerts.check(&stats);
+        assert!(alerts.alarms.error_rate);
+        assert!(!alerts.alarms.active_requests);
+        assert_eq!(alerts.log.len(), 1);
+        assert!(alerts.log[0].contains("ALERT error rate 50.0% (limit 10.0%)"));
+
+        stats.total_requests = 100;
+        alerts.check(&stats);
+        assert!(!alerts.alarms.error_rate);
+        assert!(alerts.log[1].contains("OK    error rate 5.0%"));
+    }
+
+    #[test]
+    fn alarms_show_on_the_dashboard() {
+        let mut app = App::new("http://127.0.0.1:8080".to_string());
+        app.alerts = Alerts::new(AlertsConfig {
+            p99_latency_ms: Some(1000.0),
+            ..AlertsConfig::default()
+        });
+        let text = render_text(&app, 100, 40);
+        assert!(text.contains("No alerts"));
+
+        let mut stats = Stats::new().snapshot();
+        stats.p99_latency_ms = Some(2500.0);
+        app.alerts.check(&stats);
+        app.stats = Some(stats);
+        let text = render_text(&app, 100, 40);
+        assert!(text.contains("Latency ⚠ ALERT"));
+        assert!(text.contains("ALERT p99 latency 2500 ms (limit 1000 ms)"));
+    }
+
+    #[test]
+    fn unset_thresholds_never_alarm() {
+        let mut alerts = Alerts::new(AlertsConfig::default());
+        let mut stats = Stats::new().snapshot();
+        stats.total_requests = 1;
+        stats.total_errors = 1;
+        stats.p99_latency_ms = Some(60_000.0);
+        alerts.check(&stats);
+        assert!(!alerts.is_active());
+        assert_eq!(alerts.alarms, Alarms::default());
+        assert!(alerts.log.is_empty());
+    }
+}
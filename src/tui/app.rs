@@ -1,6 +1,10 @@
 //! TUI Application logic and event handling.
 
+use super::alerts::Alerts;
+use super::history::{self, History, HISTORY_LEN};
 use super::ui;
+use crate::cli::{AlertsConfig, RequestSummary};
+use crate::clock;
 use crate::stats::StatsSnapshot;
 use crossterm::{
     event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEventKind},
@@ -9,6 +13,7 @@ use crossterm::{
 };
 use ratatui::{backend::CrosstermBackend, Terminal};
 use std::io;
+use std::path::PathBuf;
 use std::time::{Duration, Instant};
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::TcpStream;
@@ -20,6 +25,12 @@ pub struct DashboardConfig {
     pub server_url: String,
     /// Refresh interval in milliseconds
     pub refresh_ms: u64,
+    /// File the chart history is written to on exit (`.csv` or JSON)
+    pub history_path: Option<PathBuf>,
+    /// Start the charts from `history_path` instead of empty
+    pub resume: bool,
+    /// Thresholds turning panels red, from `[tui.alerts]`
+    pub alerts: AlertsConfig,
 }
 
 impl Default for DashboardConfig {
@@ -27,6 +38,9 @@ impl Default for DashboardConfig {
         Self {
             server_url: "http://127.0.0.1:8080".to_string(),
             refresh_ms: 200,
+            history_path: None,
+            resume: false,
+            alerts: AlertsConfig::default(),
         }
     }
 }
@@ -37,7 +51,7 @@ pub struct App {
     pub stats: Option<StatsSnapshot>,
     /// Last error message
     pub error: Option<String>,
-    /// Historical RPS values for sparkline (last 60 values)
+    /// Historical RPS values for sparkline (last `HISTORY_LEN` values)
     pub rps_history: Vec<f64>,
     /// Historical token rate values for sparkline
     pub tokens_history: Vec<f64>,
@@ -49,6 +63,13 @@ pub struct App {
     pub server_url: String,
     /// Total tokens from last snapshot (for rate calculation)
     pub last_total_tokens: u64,
+    /// Outcome of the last export, shown in the footer
+    pub notice: Option<String>,
+    /// Threshold alarms and their log
+    pub alerts: Alerts,
+    /// Most recent requests, newest first; `None` while the server's
+    /// `[request_log]` is off or unreachable
+    pub requests: Option<Vec<RequestSummary>>,
 }
 
 impl App {
@@ -56,12 +77,29 @@ impl App {
         Self {
             stats: None,
             error: None,
-            rps_history: Vec::with_capacity(60),
-            tokens_history: Vec::with_capacity(60),
+            rps_history: Vec::with_capacity(HISTORY_LEN),
+            tokens_history: Vec::with_capacity(HISTORY_LEN),
             last_fetch: Instant::now(),
             should_quit: false,
             server_url,
             last_total_tokens: 0,
+            notice: None,
+            alerts: Alerts::default(),
+            requests: None,
+        }
+    }
+
+    /// Continue the charts from a saved history
+    pub fn restore(&mut self, history: History) {
+        self.rps_history = history.rps;
+        self.tokens_history = history.tokens_per_sec;
+    }
+
+    /// The charts' samples, for saving
+    pub fn history(&self) -> History {
+        History {
+            rps: self.rps_history.clone(),
+            tokens_per_sec: self.tokens_history.clone(),
         }
     }
 
@@ -75,7 +113,7 @@ impl App {
                     let token_diff = snapshot.total_tokens.saturating_sub(self.last_total_tokens);
                     let token_rate = token_diff as f64 / elapsed;
                     self.tokens_history.push(token_rate);
-                    if self.tokens_history.len() > 60 {
+                    if self.tokens_history.len() > HISTORY_LEN {
                         self.tokens_history.remove(0);
                     }
                 }
@@ -83,10 +121,11 @@ impl App {
 
                 // Update RPS history
                 self.rps_history.push(snapshot.requests_per_second);
-                if self.rps_history.len() > 60 {
+                if self.rps_history.len() > HISTORY_LEN {
                     self.rps_history.remove(0);
                 }
 
+                self.alerts.check(&snapshot);
                 self.stats = Some(snapshot);
                 self.error = None;
                 self.last_fetch = Instant::now();
@@ -95,56 +134,77 @@ impl App {
                 self.error = Some(e);
             }
         }
+        self.requests = fetch_json::<RecentRequests>(&self.server_url, "requests?limit=20")
+            .await
+            .ok()
+            .map(|recent| recent.requests);
     }
 }
 
+/// Body of `/llmsim/requests`
+#[derive(serde::Deserialize)]
+struct RecentRequests {
+    requests: Vec<RequestSummary>,
+}
+
 async fn fetch_stats(server_url: &str) -> Result<StatsSnapshot, String> {
-    let endpoint = StatsEndpoint::parse(server_url)?;
+    fetch_json(server_url, "stats").await
+}
+
+/// GET `/llmsim/<name>` from the server and parse its JSON body
+async fn fetch_json<T: serde::de::DeserializeOwned>(
+    server_url: &str,
+    name: &str,
+) -> Result<T, String> {
+    let endpoint = ServerEndpoint::parse(server_url)?;
     let mut stream = TcpStream::connect(&endpoint.connect_addr)
         .await
         .map_err(|e| format!("Failed to connect: {}", e))?;
     let request = format!(
         "GET {} HTTP/1.1\r\nHost: {}\r\nAccept: application/json\r\nConnection: close\r\n\r\n",
-        endpoint.path, endpoint.host_header
+        endpoint.path(name),
+        endpoint.host_header
     );
 
     stream
         .write_all(request.as_bytes())
         .await
-        .map_err(|e| format!("Failed to request stats: {}", e))?;
+        .map_err(|e| format!("Failed to request {}: {}", name, e))?;
 
     let mut response = Vec::new();
     stream
         .read_to_end(&mut response)
         .await
-        .map_err(|e| format!("Failed to read stats: {}", e))?;
+        .map_err(|e| format!("Failed to read {}: {}", name, e))?;
 
     let header_end = response
         .windows(4)
         .position(|window| window == b"\r\n\r\n")
-        .ok_or_else(|| "Failed to parse stats response: missing headers".to_string())?;
+        .ok_or_else(|| format!("Failed to parse {} response: missing headers", name))?;
     let headers = std::str::from_utf8(&response[..header_end])
-        .map_err(|e| format!("Failed to parse stats response headers: {}", e))?;
+        .map_err(|e| format!("Failed to parse {} response headers: {}", name, e))?;
     let status_line = headers
         .lines()
         .next()
-        .ok_or_else(|| "Failed to parse stats response: missing status".to_string())?;
+        .ok_or_else(|| format!("Failed to parse {} response: missing status", name))?;
 
     if !status_line.contains(" 200 ") {
-        return Err(format!("Stats endpoint returned {}", status_line));
+        return Err(format!("/llmsim/{} returned {}", name, status_line));
     }
 
     serde_json::from_slice(&response[header_end + 4..])
-        .map_err(|e| format!("Failed to parse stats: {}", e))
+        .map_err(|e| format!("Failed to parse {}: {}", name, e))
 }
 
-struct StatsEndpoint {
+/// Where the server's `/llmsim/...` endpoints are
+struct ServerEndpoint {
     connect_addr: String,
     host_header: String,
-    path: String,
+    /// Path the server is mounted under, `""` or `/<prefix>`
+    prefix: String,
 }
 
-impl StatsEndpoint {
+impl ServerEndpoint {
     fn parse(server_url: &str) -> Result<Self, String> {
         let server_url = server_url.trim().trim_end_matches('/');
         let rest = server_url
@@ -182,18 +242,23 @@ impl StatsEndpoint {
             format!("{}:80", authority)
         };
 
-        let path = if path_prefix.is_empty() {
-            "/llmsim/stats".to_string()
+        let prefix = if path_prefix.is_empty() {
+            String::new()
         } else {
-            format!("/{}/llmsim/stats", path_prefix.trim_end_matches('/'))
+            format!("/{}", path_prefix.trim_end_matches('/'))
         };
 
         Ok(Self {
             connect_addr,
             host_header: authority.to_string(),
-            path,
+            prefix,
         })
     }
+
+    /// Path of `/llmsim/<name>` on the server
+    fn path(&self, name: &str) -> String {
+        format!("{}/llmsim/{}", self.prefix, name)
+    }
 }
 
 fn contains_invalid_request_chars(value: &str) -> bool {
@@ -213,6 +278,13 @@ pub async fn run_dashboard(config: DashboardConfig) -> io::Result<()> {
 
     // Create app state
     let mut app = App::new(config.server_url);
+    app.alerts = Alerts::new(config.alerts);
+    if let (Some(path), true) = (&config.history_path, config.resume) {
+        match History::load(path) {
+            Ok(history) => app.restore(history),
+            Err(e) => app.notice = Some(format!("Not resumed from {}: {}", path.display(), e)),
+        }
+    }
     let tick_rate = Duration::from_millis(config.refresh_ms);
     let mut last_tick = Instant::now();
 
@@ -236,6 +308,14 @@ pub async fn run_dashboard(config: DashboardConfig) -> io::Result<()> {
                             // Force refresh
                             app.update_stats().await;
                         }
+                        KeyCode::Char('e') => {
+                            let size = terminal.size()?;
+                            app.notice =
+                                Some(match export_snapshot(&app, size.width, size.height) {
+                                    Ok(path) => format!("Exported to {}", path.display()),
+                                    Err(e) => format!("Export failed: {}", e),
+                                });
+                        }
                         _ => {}
                     }
                 }
@@ -263,16 +343,37 @@ pub async fn run_dashboard(config: DashboardConfig) -> io::Result<()> {
     )?;
     terminal.show_cursor()?;
 
+    if let Some(path) = &config.history_path {
+        app.history().save(path)?;
+    }
     Ok(())
 }
 
+/// Write the dashboard as on screen to `llmsim-dashboard-<unix time>.txt`
+/// in the working directory
+fn export_snapshot(app: &App, width: u16, height: u16) -> io::Result<PathBuf> {
+    let path = PathBuf::from(format!("llmsim-dashboard-{}.txt", clock::unix_seconds()));
+    std::fs::write(&path, history::render_text(app, width, height))?;
+    Ok(path)
+}
+
 #[cfg(test)]
 mod tests {
-    use super::StatsEndpoint;
+    use super::ServerEndpoint;
+
+    #[test]
+    fn paths_follow_the_mount_prefix() {
+        let endpoint = ServerEndpoint::parse("http://localhost:8080").unwrap();
+        assert_eq!(endpoint.connect_addr, "localhost:8080");
+        assert_eq!(endpoint.path("stats"), "/llmsim/stats");
+        let endpoint = ServerEndpoint::parse("http://localhost/eu/").unwrap();
+        assert_eq!(endpoint.connect_addr, "localhost:80");
+        assert_eq!(endpoint.path("requests"), "/eu/llmsim/requests");
+    }
 
     #[test]
     fn parse_rejects_crlf_in_host() {
-        let err = match StatsEndpoint::parse("http://localhost\r\nX-Test: 1") {
+        let err = match ServerEndpoint::parse("http://localhost\r\nX-Test: 1") {
             Ok(_) => panic!("host containing CRLF should be rejected"),
             Err(err) => err,
         };
@@ -281,7 +382,7 @@ mod tests {
 
     #[test]
     fn parse_rejects_crlf_in_path_prefix() {
-        let err = match StatsEndpoint::parse("http://localhost/base\r\nX-Test: 1") {
+        let err = match ServerEndpoint::parse("http://localhost/base\r\nX-Test: 1") {
             Ok(_) => panic!("path containing CRLF should be rejected"),
             Err(err) => err,
         };
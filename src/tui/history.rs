@@ -0,0 +1,187 @@
+//! Chart history persistence and dashboard snapshots.
+//!
+//! The RPS and token-rate ring buffers are written to disk when the
+//! dashboard exits and read back with `--resume`, as JSON or as CSV
+//! depending on the file extension. Snapshots render the dashboard as it is
+//! on screen into a plain-text file, for post-run notes without scraping the
+//! stats endpoint.
+
+use super::app::App;
+use super::ui;
+use ratatui::{backend::TestBackend, Terminal};
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::path::Path;
+
+/// Samples kept per chart, one per refresh
+pub const HISTORY_LEN: usize = 60;
+
+/// Chart samples, oldest first
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct History {
+    /// Requests per second
+    #[serde(default)]
+    pub rps: Vec<f64>,
+    /// Tokens per second
+    #[serde(default)]
+    pub tokens_per_sec: Vec<f64>,
+}
+
+impl History {
+    /// Read a history file; a missing file is an empty history, so
+    /// `--resume` also works on the first run
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e),
+        };
+        let mut history = if is_csv(path) {
+            Self::from_csv(&text)?
+        } else {
+            serde_json::from_str(&text)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?
+        };
+        history.truncate();
+        Ok(history)
+    }
+
+    /// Write the history, as CSV for a `.csv` path and JSON otherwise
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let text = if is_csv(path) {
+            self.to_csv()
+        } else {
+            serde_json::to_string_pretty(self).map_err(io::Error::other)?
+        };
+        std::fs::write(path, text)
+    }
+
+    /// Drop samples beyond `HISTORY_LEN`, oldest first
+    pub fn truncate(&mut self) {
+        for samples in [&mut self.rps, &mut self.tokens_per_sec] {
+            let excess = samples.len().saturating_sub(HISTORY_LEN);
+            samples.drain(..excess);
+        }
+    }
+
+    /// One row per sample, aligned on the latest: the token rate starts a
+    /// refresh later than RPS, so its first cell is empty
+    fn to_csv(&self) -> String {
+        let rows = self.rps.len().max(self.tokens_per_sec.len());
+        let cell = |samples: &[f64], row: usize| {
+            (row + samples.len())
+                .checked_sub(rows)
+                .map(|i| samples[i].to_string())
+                .unwrap_or_default()
+        };
+        let mut csv = String::from("rps,tokens_per_sec\n");
+        for row in 0..rows {
+            csv.push_str(&format!(
+                "{},{}\n",
+                cell(&self.rps, row),
+                cell(&self.tokens_per_sec, row)
+            ));
+        }
+        csv
+    }
+
+    fn from_csv(text: &str) -> io::Result<Self> {
+        let invalid = |line: usize, e: std::num::ParseFloatError| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("line {}: {}", line + 1, e),
+            )
+        };
+        let mut history = Self::default();
+        for (line, row) in text.lines().enumerate().skip(1) {
+            let (rps, tokens) = row.split_once(',').unwrap_or((row, ""));
+            for (cell, samples) in [
+                (rps, &mut history.rps),
+                (tokens, &mut history.tokens_per_sec),
+            ] {
+                let cell = cell.trim();
+                if !cell.is_empty() {
+                    samples.push(cell.parse().map_err(|e| invalid(line, e))?);
+                }
+            }
+        }
+        Ok(history)
+    }
+}
+
+fn is_csv(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("csv"))
+}
+
+/// The dashboard drawn at `width`×`height` as plain text, one line per row
+pub fn render_text(app: &App, width: u16, height: u16) -> String {
+    // Drawing into memory cannot fail
+    let Ok(mut terminal) = Terminal::new(TestBackend::new(width, height));
+    let Ok(_) = terminal.draw(|f| ui::draw(f, app));
+    let buffer = terminal.backend().buffer();
+    let mut text = String::new();
+    for y in 0..buffer.area.height {
+        let line: String = (0..buffer.area.width)
+            .map(|x| buffer[(x, y)].symbol())
+            .collect();
+        text.push_str(line.trim_end());
+        text.push('\n');
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> History {
+        History {
+            rps: vec![1.0, 2.5, 4.0],
+            tokens_per_sec: vec![120.0, 80.5],
+        }
+    }
+
+    #[test]
+    fn csv_round_trip() {
+        let csv = sample().to_csv();
+        assert_eq!(csv, "rps,tokens_per_sec\n1,\n2.5,120\n4,80.5\n");
+        assert_eq!(History::from_csv(&csv).unwrap(), sample());
+        assert!(History::from_csv("rps,tokens_per_sec\nfast,\n").is_err());
+    }
+
+    #[test]
+    fn save_and_load_by_extension() {
+        let dir = std::env::temp_dir().join(format!("llmsim-history-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for name in ["history.json", "history.csv"] {
+            let path = dir.join(name);
+            sample().save(&path).unwrap();
+            assert_eq!(History::load(&path).unwrap(), sample());
+        }
+        let missing = History::load(&dir.join("missing.json")).unwrap();
+        assert_eq!(missing, History::default());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn load_keeps_the_latest_samples() {
+        let mut history = History {
+            rps: (0..100).map(f64::from).collect(),
+            tokens_per_sec: Vec::new(),
+        };
+        history.truncate();
+        assert_eq!(history.rps.len(), HISTORY_LEN);
+        assert_eq!(history.rps[0], 40.0);
+    }
+
+    #[test]
+    fn snapshot_renders_the_charts() {
+        let mut app = App::new("http://127.0.0.1:8080".to_string());
+        app.restore(sample());
+        let text = render_text(&app, 100, 40);
+        assert!(text.contains("LLMSim Stats Dashboard"));
+        assert!(text.contains("RPS (current: 4.00, max: 4.00)"));
+        assert_eq!(text.lines().count(), 40);
+    }
+}
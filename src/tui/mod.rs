@@ -3,7 +3,10 @@
 //! This module provides a terminal-based dashboard for monitoring
 //! LLMSim server statistics in real-time.
 
+mod alerts;
 mod app;
+mod history;
 mod ui;
 
 pub use app::{run_dashboard, DashboardConfig};
+pub use history::{History, HISTORY_LEN};
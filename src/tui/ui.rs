@@ -1,6 +1,8 @@
 //! TUI rendering logic using Ratatui.
 
+use super::alerts::error_rate_percent;
 use super::app::App;
+use crate::cli::RequestSummary;
 use ratatui::{
     layout::{Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
@@ -11,15 +13,21 @@ use ratatui::{
 
 /// Main draw function
 pub fn draw(f: &mut Frame, app: &App) {
+    // The alerts pane only takes room when thresholds are configured
+    let alerts_height = if app.alerts.is_active() { 6 } else { 0 };
+    // So does the request tail, while the server keeps a request log
+    let requests_height = if app.requests.is_some() { 8 } else { 0 };
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .margin(1)
         .constraints([
-            Constraint::Length(3), // Header
-            Constraint::Length(9), // Request stats + Token stats
-            Constraint::Length(8), // Latency + Errors
-            Constraint::Min(8),    // Charts
-            Constraint::Length(1), // Footer
+            Constraint::Length(3),               // Header
+            Constraint::Length(9),               // Request stats + Token stats
+            Constraint::Length(8),               // Latency + Errors
+            Constraint::Min(8),                  // Charts
+            Constraint::Length(requests_height), // Recent requests
+            Constraint::Length(alerts_height),   // Alerts
+            Constraint::Length(1),               // Footer
         ])
         .split(f.area());
 
@@ -27,7 +35,32 @@ pub fn draw(f: &mut Frame, app: &App) {
     draw_stats_row(f, app, chunks[1]);
     draw_latency_errors_row(f, app, chunks[2]);
     draw_charts(f, app, chunks[3]);
-    draw_footer(f, chunks[4]);
+    if let Some(requests) = &app.requests {
+        draw_requests(f, requests, chunks[4]);
+    }
+    if app.alerts.is_active() {
+        draw_alerts(f, app, chunks[5]);
+    }
+    draw_footer(f, app, chunks[6]);
+}
+
+/// A titled panel, red with a marker while its alarm is raised
+fn panel(title: &str, color: Color, alarm: bool) -> Block<'static> {
+    let (title, color) = if alarm {
+        (format!(" {} ⚠ ALERT ", title.trim()), Color::Red)
+    } else {
+        (title.to_string(), color)
+    };
+    let title_style = if alarm {
+        Style::default().fg(Color::White).bg(Color::Red).bold()
+    } else {
+        Style::default().fg(color).bold()
+    };
+    Block::default()
+        .title(title)
+        .title_style(title_style)
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(color))
 }
 
 fn draw_header(f: &mut Frame, app: &App, area: Rect) {
@@ -137,13 +170,11 @@ fn draw_request_stats(f: &mut Frame, app: &App, area: Rect) {
         rows,
         [Constraint::Percentage(60), Constraint::Percentage(40)],
     )
-    .block(
-        Block::default()
-            .title(" Requests ")
-            .title_style(Style::default().fg(Color::Green).bold())
-            .borders(Borders::ALL)
-            .border_style(Style::default().fg(Color::Green)),
-    );
+    .block(panel(
+        " Requests ",
+        Color::Green,
+        app.alerts.alarms.active_requests,
+    ));
 
     f.render_widget(table, area);
 }
@@ -154,6 +185,8 @@ fn draw_token_stats(f: &mut Frame, app: &App, area: Rect) {
     let prompt = stats.map(|s| s.prompt_tokens).unwrap_or(0);
     let completion = stats.map(|s| s.completion_tokens).unwrap_or(0);
     let total = stats.map(|s| s.total_tokens).unwrap_or(0);
+    let prompt_sizes = stats.map(|s| s.prompt_sizes.clone()).unwrap_or_default();
+    let message_counts = stats.map(|s| s.message_counts.clone()).unwrap_or_default();
 
     // Calculate token rate
     let token_rate = if !app.tokens_history.is_empty() {
@@ -188,6 +221,24 @@ fn draw_token_stats(f: &mut Frame, app: &App, area: Rect) {
                 Style::default().fg(Color::Green),
             ),
         ]),
+        Row::new(vec![
+            Span::raw("Prompt avg / max"),
+            Span::styled(
+                format!(
+                    "{} / {}",
+                    format_number(prompt_sizes.avg.round() as u64),
+                    format_number(prompt_sizes.max)
+                ),
+                Style::default().fg(Color::Blue),
+            ),
+        ]),
+        Row::new(vec![
+            Span::raw("Messages avg / max"),
+            Span::styled(
+                format!("{:.1} / {}", message_counts.avg, message_counts.max),
+                Style::default().fg(Color::Yellow),
+            ),
+        ]),
     ];
 
     let table = Table::new(
@@ -221,6 +272,7 @@ fn draw_latency_stats(f: &mut Frame, app: &App, area: Rect) {
     let avg = stats.map(|s| s.avg_latency_ms).unwrap_or(0.0);
     let min = stats.and_then(|s| s.min_latency_ms).unwrap_or(0.0);
     let max = stats.and_then(|s| s.max_latency_ms).unwrap_or(0.0);
+    let p99 = stats.and_then(|s| s.p99_latency_ms).unwrap_or(0.0);
 
     let rows = vec![
         Row::new(vec![
@@ -242,19 +294,28 @@ fn draw_latency_stats(f: &mut Frame, app: &App, area: Rect) {
                 },
             ),
         ]),
+        Row::new(vec![
+            Span::raw("p99"),
+            Span::styled(
+                format!("{:.2} ms", p99),
+                if app.alerts.alarms.p99_latency {
+                    Style::default().fg(Color::Red).bold()
+                } else {
+                    Style::default().fg(Color::Yellow)
+                },
+            ),
+        ]),
     ];
 
     let table = Table::new(
         rows,
         [Constraint::Percentage(50), Constraint::Percentage(50)],
     )
-    .block(
-        Block::default()
-            .title(" Latency ")
-            .title_style(Style::default().fg(Color::Yellow).bold())
-            .borders(Borders::ALL)
-            .border_style(Style::default().fg(Color::Yellow)),
-    );
+    .block(panel(
+        " Latency ",
+        Color::Yellow,
+        app.alerts.alarms.p99_latency,
+    ));
 
     f.render_widget(table, area);
 }
@@ -266,14 +327,11 @@ fn draw_error_stats(f: &mut Frame, app: &App, area: Rect) {
     let rate_limit = stats.map(|s| s.rate_limit_errors).unwrap_or(0);
     let server = stats.map(|s| s.server_errors).unwrap_or(0);
     let timeout = stats.map(|s| s.timeout_errors).unwrap_or(0);
+    let request_timeout = stats.map(|s| s.request_timeout_errors).unwrap_or(0);
+    let gave_up = stats.map(|s| s.client_timeouts).unwrap_or(0);
+    let avg_wait = stats.map(|s| s.client_wait_ms.avg).unwrap_or(0.0);
 
-    // Calculate error rate
-    let total_requests = stats.map(|s| s.total_requests).unwrap_or(0);
-    let error_rate = if total_requests > 0 {
-        (total as f64 / total_requests as f64) * 100.0
-    } else {
-        0.0
-    };
+    let error_rate = stats.map(error_rate_percent).unwrap_or(0.0);
 
     let error_style = if total > 0 {
         Style::default().fg(Color::Red).bold()
@@ -301,19 +359,27 @@ fn draw_error_stats(f: &mut Frame, app: &App, area: Rect) {
             Span::raw("Timeout (504)"),
             Span::styled(format!("{}", timeout), Style::default().fg(Color::Magenta)),
         ]),
+        Row::new(vec![
+            Span::raw("Timeout (408)"),
+            Span::styled(
+                format!("{}", request_timeout),
+                Style::default().fg(Color::Magenta),
+            ),
+        ]),
+        Row::new(vec![
+            Span::raw("Client Gave Up"),
+            Span::styled(
+                format!("{} (avg wait {:.0} ms)", gave_up, avg_wait),
+                Style::default().fg(Color::Magenta),
+            ),
+        ]),
     ];
 
     let table = Table::new(
         rows,
         [Constraint::Percentage(60), Constraint::Percentage(40)],
     )
-    .block(
-        Block::default()
-            .title(" Errors ")
-            .title_style(Style::default().fg(Color::Red).bold())
-            .borders(Borders::ALL)
-            .border_style(Style::default().fg(Color::Red)),
-    );
+    .block(panel(" Errors ", Color::Red, app.alerts.alarms.error_rate));
 
     f.render_widget(table, area);
 }
@@ -439,20 +505,110 @@ fn draw_model_chart(f: &mut Frame, app: &App, area: Rect) {
     f.render_widget(bar_chart, area);
 }
 
-fn draw_footer(f: &mut Frame, area: Rect) {
-    let footer = Paragraph::new(Line::from(vec![
+/// Live tail of `/llmsim/requests`, newest at the top
+fn draw_requests(f: &mut Frame, requests: &[RequestSummary], area: Rect) {
+    let visible = area.height.saturating_sub(3) as usize;
+    let rows: Vec<Row> = requests
+        .iter()
+        .take(visible)
+        .map(|request| {
+            let (outcome, color) = match (request.outcome.as_str(), request.status) {
+                ("completed", _) => ("200".to_string(), Color::Green),
+                (_, Some(status)) => (status.to_string(), Color::Red),
+                (outcome, None) => (outcome.to_string(), Color::Yellow),
+            };
+            let tokens = match (request.input_tokens, request.output_tokens) {
+                (Some(input), Some(output)) => format!("{} → {}", input, output),
+                _ => "-".to_string(),
+            };
+            let mut detail = request.error_cause.clone().unwrap_or_default();
+            if let Some(prompt) = &request.prompt {
+                detail = prompt.replace('\n', " ");
+            }
+            Row::new(vec![
+                Span::raw(format!("#{}", request.id)),
+                Span::styled(request.model.clone(), Style::default().fg(Color::Cyan)),
+                Span::raw(format!(
+                    "{}{}",
+                    request.endpoint,
+                    if request.stream { " (stream)" } else { "" }
+                )),
+                Span::styled(outcome, Style::default().fg(color)),
+                Span::raw(tokens),
+                Span::raw(format!("{:.0} ms", request.elapsed_ms)),
+                Span::styled(detail, Style::default().fg(Color::Gray)),
+            ])
+        })
+        .collect();
+    let header = Row::new(vec![
+        "Request", "Model", "Endpoint", "Status", "Tokens", "Time", "",
+    ])
+    .style(Style::default().fg(Color::Gray).bold());
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Length(8),
+            Constraint::Length(20),
+            Constraint::Length(28),
+            Constraint::Length(10),
+            Constraint::Length(14),
+            Constraint::Length(10),
+            Constraint::Min(10),
+        ],
+    )
+    .header(header)
+    .block(panel(" Recent Requests ", Color::Blue, false));
+    f.render_widget(table, area);
+}
+
+fn draw_alerts(f: &mut Frame, app: &App, area: Rect) {
+    let raised = app.alerts.alarms != Default::default();
+    // Newest last, showing as many as fit inside the borders
+    let visible = area.height.saturating_sub(2) as usize;
+    let lines: Vec<Line> = app
+        .alerts
+        .log
+        .iter()
+        .skip(app.alerts.log.len().saturating_sub(visible))
+        .map(|line| {
+            let color = if line.contains("ALERT") {
+                Color::Red
+            } else {
+                Color::Green
+            };
+            Line::from(Span::styled(line.clone(), Style::default().fg(color)))
+        })
+        .collect();
+    let alerts = if lines.is_empty() {
+        Paragraph::new("No alerts").style(Style::default().fg(Color::Gray))
+    } else {
+        Paragraph::new(lines)
+    };
+    f.render_widget(alerts.block(panel(" Alerts ", Color::Gray, raised)), area);
+}
+
+fn draw_footer(f: &mut Frame, app: &App, area: Rect) {
+    let mut spans = vec![
         Span::styled(" q ", Style::default().fg(Color::Black).bg(Color::White)),
         Span::raw(" Quit  "),
         Span::styled(" r ", Style::default().fg(Color::Black).bg(Color::White)),
         Span::raw(" Refresh  "),
-    ]))
-    .style(Style::default().fg(Color::Gray));
+        Span::styled(" e ", Style::default().fg(Color::Black).bg(Color::White)),
+        Span::raw(" Export  "),
+    ];
+    if let Some(notice) = &app.notice {
+        spans.push(Span::styled(
+            notice.clone(),
+            Style::default().fg(Color::Yellow),
+        ));
+    }
+    let footer = Paragraph::new(Line::from(spans)).style(Style::default().fg(Color::Gray));
 
     f.render_widget(footer, area);
 }
 
 /// Format uptime in human-readable format
-fn format_uptime(secs: u64) -> String {
+pub fn format_uptime(secs: u64) -> String {
     let days = secs / 86400;
     let hours = (secs % 86400) / 3600;
     let minutes = (secs % 3600) / 60;
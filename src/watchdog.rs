@@ -0,0 +1,109 @@
+// Stream Watchdog Module
+// The safety cap of `[stream] max_duration_secs`: a generated stream that
+// would run longer (a huge `target_tokens` on a slow profile can take hours)
+// is ended early, as cut short, so it cannot hold a connection indefinitely.
+//
+// Decision: a stream's duration is the total of the delays it waits out, not
+// the wall clock, so a capped stream ends at the same token under
+// `VirtualSleeper`, a paused tokio clock and real time, and a client that
+// reads slowly does not bring its end forward. Each engine asks before every
+// wait and, once the budget is spent, finishes at once the way it finishes a
+// `max_tokens` cut (`length`, `response.incomplete`, `max_tokens`), an ending
+// clients already handle.
+
+use std::time::Duration;
+
+/// Callback run the first time a stream runs out of time
+type OnTripCallback = Box<dyn FnOnce() + Send + 'static>;
+
+/// Time budget of one stream
+#[derive(Default)]
+pub struct StreamWatchdog {
+    /// Longest the stream may wait in total; unlimited if `None`
+    limit: Option<Duration>,
+    /// Delays waited out so far
+    spent: Duration,
+    tripped: bool,
+    on_trip: Option<OnTripCallback>,
+}
+
+impl StreamWatchdog {
+    /// A budget of `limit` in total
+    pub fn new(limit: Duration) -> Self {
+        Self {
+            limit: Some(limit),
+            ..Self::default()
+        }
+    }
+
+    /// A budget that never runs out
+    pub fn unlimited() -> Self {
+        Self::default()
+    }
+
+    /// Set a callback to be invoked when the budget runs out
+    pub fn on_trip<F>(mut self, callback: F) -> Self
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.on_trip = Some(Box::new(callback));
+        self
+    }
+
+    /// Whether a wait of `delay` still fits the budget, charging it if so.
+    /// The first wait that does not fit trips the watchdog; every call after
+    /// that is refused.
+    pub fn allows(&mut self, delay: Duration) -> bool {
+        if self.tripped {
+            return false;
+        }
+        let Some(limit) = self.limit else {
+            return true;
+        };
+        if self.spent + delay > limit {
+            self.tripped = true;
+            if let Some(callback) = self.on_trip.take() {
+                callback();
+            }
+            return false;
+        }
+        self.spent += delay;
+        true
+    }
+
+    /// Whether the stream ran out of time and was cut short
+    pub fn tripped(&self) -> bool {
+        self.tripped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_budget_runs_out_once() {
+        let trips = Arc::new(AtomicU32::new(0));
+        let counter = trips.clone();
+        let mut watchdog = StreamWatchdog::new(Duration::from_secs(1)).on_trip(move || {
+            counter.fetch_add(1, Ordering::Relaxed);
+        });
+        assert!(watchdog.allows(Duration::from_millis(600)));
+        assert!(watchdog.allows(Duration::from_millis(400)));
+        assert!(!watchdog.tripped());
+        assert!(!watchdog.allows(Duration::from_millis(1)));
+        // Once tripped, even a free wait is refused
+        assert!(!watchdog.allows(Duration::ZERO));
+        assert!(watchdog.tripped());
+        assert_eq!(trips.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn test_unlimited() {
+        let mut watchdog = StreamWatchdog::unlimited();
+        assert!(watchdog.allows(Duration::from_secs(365 * 24 * 3600)));
+        assert!(!watchdog.tripped());
+    }
+}
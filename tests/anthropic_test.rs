@@ -195,6 +195,32 @@ async fn messages_streaming_event_sequence() {
     assert!(start < stop);
 }
 
+#[tokio::test]
+async fn messages_end_at_stop_sequences() {
+    let router = router();
+    let request = |stream: bool| {
+        json!({
+            "model": "claude-opus-4-8",
+            "max_tokens": 64,
+            "stream": stream,
+            "stop_sequences": ["STOP"],
+            "messages": [{"role": "user", "content": "alpha beta STOP gamma"}]
+        })
+    };
+
+    let (status, body) = post_messages(&router, request(false)).await;
+    assert_eq!(status, StatusCode::OK);
+    let v: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(v["stop_reason"], "stop_sequence");
+    assert_eq!(v["stop_sequence"], "STOP");
+    let text = v["content"][0]["text"].as_str().unwrap();
+    assert!(text.ends_with("alpha beta "), "{}", text);
+
+    let (_, body) = post_messages(&router, request(true)).await;
+    assert!(body.contains(r#""stop_reason":"stop_sequence","stop_sequence":"STOP""#));
+    assert!(!body.contains("gamma"));
+}
+
 // --- Models endpoints ---
 
 #[tokio::test]
@@ -0,0 +1,100 @@
+//! Helpers shared by the integration tests: routers built from a TOML
+//! config, and requests sent to them in-process via
+//! `tower::ServiceExt::oneshot`, the same HTTP path real clients hit.
+
+#![allow(dead_code)]
+
+use std::sync::Arc;
+
+use axum::body::{to_bytes, Body};
+use axum::http::{Request, StatusCode};
+use axum::Router;
+use llmsim::cli::{build_router, AppState, Config};
+use llmsim::stats::{new_shared_stats, SharedStats};
+use serde_json::{json, Value};
+use tower::ServiceExt;
+
+/// State for the config in `toml`
+pub fn state(toml: &str) -> Arc<AppState> {
+    state_with_stats(toml, new_shared_stats())
+}
+
+/// State for the config in `toml`, recording into `stats`
+pub fn state_with_stats(toml: &str, stats: SharedStats) -> Arc<AppState> {
+    Arc::new(AppState::new(Config::from_toml(toml).unwrap(), stats))
+}
+
+/// Router for the config in `toml`
+pub fn router(toml: &str) -> Router {
+    build_router(state(toml))
+}
+
+/// Router for the config in `toml` with instant latency, for tests that
+/// time nothing
+pub fn instant_router(toml: &str) -> Router {
+    router(&format!("[latency]\nprofile = \"instant\"\n\n{}", toml))
+}
+
+/// Router for `config`, recording into `stats`
+pub fn config_router(config: Config, stats: SharedStats) -> Router {
+    build_router(Arc::new(AppState::new(config, stats)))
+}
+
+/// A request with `body` as JSON, or with no body
+pub fn request(method: &str, uri: &str, body: Option<Value>) -> Request<Body> {
+    Request::builder()
+        .method(method)
+        .uri(uri)
+        .header("content-type", "application/json")
+        .body(body.map_or_else(Body::empty, |body| Body::from(body.to_string())))
+        .unwrap()
+}
+
+/// `request` sent with `key` as its bearer token
+pub fn with_key(mut request: Request<Body>, key: &str) -> Request<Body> {
+    let value = format!("Bearer {}", key).parse().unwrap();
+    request.headers_mut().insert("authorization", value);
+    request
+}
+
+/// Send `request`, returning the status and the body
+pub async fn call(router: &Router, request: Request<Body>) -> (StatusCode, Vec<u8>) {
+    let resp = router.clone().oneshot(request).await.unwrap();
+    let status = resp.status();
+    let bytes = to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+    (status, bytes.to_vec())
+}
+
+/// Send a request, returning the status and the body as JSON (null if it
+/// is not JSON)
+pub async fn send(
+    router: &Router,
+    method: &str,
+    uri: &str,
+    body: Option<Value>,
+) -> (StatusCode, Value) {
+    let (status, bytes) = call(router, request(method, uri, body)).await;
+    (status, serde_json::from_slice(&bytes).unwrap_or_default())
+}
+
+/// POST `body` to `uri`
+pub async fn post(router: &Router, uri: &str, body: Value) -> (StatusCode, Value) {
+    send(router, "POST", uri, Some(body)).await
+}
+
+/// POST `body` to `uri`, which must answer 200, and return what it answered
+pub async fn post_ok(router: &Router, uri: &str, body: Value) -> Value {
+    let (status, body) = post(router, uri, body).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    body
+}
+
+/// GET `uri`
+pub async fn get(router: &Router, uri: &str) -> (StatusCode, Value) {
+    send(router, "GET", uri, None).await
+}
+
+/// A chat completions body for `model` with one user message
+pub fn chat(model: &str, content: &str) -> Value {
+    json!({"model": model, "messages": [{"role": "user", "content": content}]})
+}
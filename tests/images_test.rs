@@ -126,6 +126,44 @@ async fn defaults_when_params_omitted() {
     assert!(v["data"][0]["b64_json"].is_string());
 }
 
+#[tokio::test]
+async fn url_response_format_links_to_hosted_file() {
+    let router = router();
+    let body = json!({
+        "model": "dall-e-3",
+        "prompt": "a lighthouse",
+        "size": "256x256",
+        "response_format": "url"
+    });
+    let (status, bytes) = post_images(&router, body).await;
+    assert_eq!(status, StatusCode::OK);
+    let v: Value = serde_json::from_slice(&bytes).unwrap();
+    assert!(v["data"][0].get("b64_json").is_none());
+    let url = v["data"][0]["url"].as_str().unwrap();
+    let path = url.strip_prefix("http://localhost").unwrap();
+    assert!(path.starts_with("/llmsim/files/file-"));
+
+    let resp = router
+        .clone()
+        .oneshot(Request::get(path).body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.headers()["content-type"], "image/png");
+    let png = to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+    assert_eq!(png[0..8], [137, 80, 78, 71, 13, 10, 26, 10]);
+
+    let resp = router
+        .oneshot(
+            Request::get("/llmsim/files/file-unknown")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
 #[tokio::test]
 async fn streaming_emits_partials_then_completed() {
     let router = router();
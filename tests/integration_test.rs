@@ -11,6 +11,8 @@ use llmsim::stats::{EndpointType, Stats};
 use llmsim::StatsSnapshot;
 use std::time::Duration;
 
+mod common;
+
 mod stats_tests {
     use super::*;
 
@@ -207,8 +209,14 @@ mod generator_tests {
             user: None,
             tools: None,
             tool_choice: None,
+            parallel_tool_calls: None,
             response_format: None,
             seed: None,
+            stream_options: None,
+            functions: None,
+            function_call: None,
+            store: None,
+            metadata: None,
         }
     }
 
@@ -272,6 +280,28 @@ mod stream_tests {
         let has_usage = chunks.iter().any(|c| c.contains("\"total_tokens\":30"));
         assert!(has_usage, "Stream should include usage in final chunk");
     }
+
+    #[tokio::test]
+    async fn test_stream_from_generator_pieces() {
+        use llmsim::openai::{ChatCompletionRequest, Message};
+        use llmsim::{LoremGenerator, ResponseGenerator};
+
+        let request: ChatCompletionRequest = serde_json::from_value(serde_json::json!({
+            "model": "gpt-4",
+            "messages": [Message::user("hi")],
+        }))
+        .unwrap();
+        // Far too large to materialize; only the pulled pieces are generated.
+        let generator = LoremGenerator::new(1_000_000_000);
+
+        let stream = TokenStreamBuilder::from_pieces("gpt-4", generator.generate_pieces(&request))
+            .latency(LatencyProfile::instant())
+            .build();
+
+        let chunks: Vec<String> = stream.into_stream().take(5).collect().await;
+        assert_eq!(chunks.len(), 5);
+        assert!(chunks[1].contains("\"content\""));
+    }
 }
 
 mod openresponses_tests {
@@ -497,3 +527,5695 @@ mod openresponses_tests {
         assert_eq!(reasoning.summary, Some("detailed".to_string()));
     }
 }
+
+/// `[auxiliary]` slows down and fails model listing and the other endpoints
+/// that generate nothing, leaving completions to `[latency]` and `[errors]`.
+mod auxiliary_tests {
+    use crate::common::{chat, get, instant_router, post};
+    use axum::http::StatusCode;
+    use llmsim::cli::Config;
+    use std::time::Duration;
+
+    #[tokio::test(start_paused = true)]
+    async fn model_listing_is_delayed() {
+        let router = instant_router("[auxiliary]\nlatency_mean_ms = 2000\n");
+
+        let started = tokio::time::Instant::now();
+        let (status, body) = get(&router, "/openai/v1/models").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["object"], "list");
+        assert_eq!(started.elapsed(), Duration::from_secs(2));
+
+        let started = tokio::time::Instant::now();
+        get(&router, "/health").await;
+        assert_eq!(started.elapsed(), Duration::ZERO);
+    }
+
+    #[tokio::test]
+    async fn errors_in_each_providers_format() {
+        let router =
+            instant_router("[auxiliary]\nrate_limit_rate = 1.0\npaths = [\"*/v1/models*\"]\n");
+
+        let (status, body) = get(&router, "/openai/v1/models").await;
+        assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(body["error"]["type"], "rate_limit_error");
+
+        let (status, body) = get(&router, "/anthropic/v1/models/claude-sonnet-4").await;
+        assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(body["type"], "error");
+
+        // Outside `paths`, and generation endpoints, are untouched
+        let (status, _) = get(&router, "/vllm/metrics").await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = post(&router, "/openai/v1/chat/completions", chat("gpt-5", "hi")).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[test]
+    fn rates_over_one_are_a_config_error() {
+        let err = Config::from_toml("[auxiliary]\nserver_error_rate = 0.6\ntimeout_rate = 0.6\n")
+            .unwrap_err();
+        assert!(err.to_string().contains("[auxiliary]"));
+    }
+}
+
+/// Streams are generated on their own clock: a client that stops reading
+/// finds the events generated meanwhile waiting in one frame, up to
+/// `[stream] pending_events`, beyond which generation waits for it.
+mod backpressure_tests {
+    use crate::common::{request, router};
+    use futures_util::StreamExt;
+    use serde_json::json;
+    use std::time::Duration;
+    use tower::ServiceExt;
+
+    fn events(frame: &[u8]) -> usize {
+        String::from_utf8_lossy(frame).matches("data: ").count()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn slow_reader_gets_coalesced_events() {
+        let router = router("[stream]\npending_events = 4\n");
+        let body = json!({
+            "model": "gpt-5",
+            "stream": true,
+            "messages": [{"role": "user", "content": "hi"}],
+            "llmsim": {"ttft_ms": 100, "tbt_ms": 10}
+        });
+        let req = request("POST", "/openai/v1/chat/completions", Some(body));
+        let mut frames = router
+            .oneshot(req)
+            .await
+            .unwrap()
+            .into_body()
+            .into_data_stream();
+
+        // A prompt reader gets the role chunk alone after the first token delay
+        let first = frames.next().await.unwrap().unwrap();
+        assert_eq!(events(&first), 1);
+
+        // Stop reading for a while: four events wait, then generation pauses
+        tokio::time::sleep(Duration::from_secs(5)).await;
+        let waiting = frames.next().await.unwrap().unwrap();
+        assert_eq!(events(&waiting), 4);
+
+        // Reading on, the rest of the stream is all there
+        let mut total = 1 + 4;
+        let mut last = Vec::new();
+        while let Some(frame) = frames.next().await {
+            let frame = frame.unwrap();
+            total += events(&frame);
+            last = frame.to_vec();
+        }
+        assert!(String::from_utf8(last)
+            .unwrap()
+            .ends_with("data: [DONE]\n\n"));
+        assert!(total > 6);
+    }
+}
+
+/// `[models] enforce_capabilities`: tools, JSON mode and image inputs sent to
+/// a known model that lacks them are refused with the API's error.
+mod capabilities_tests {
+    use crate::common::{instant_router, post};
+    use axum::http::StatusCode;
+    use axum::Router;
+    use serde_json::{json, Value};
+
+    fn router(enforce: bool) -> Router {
+        instant_router(&format!("[models]\nenforce_capabilities = {}\n", enforce))
+    }
+
+    fn weather_tool() -> Value {
+        json!({
+            "type": "function",
+            "function": {"name": "get_weather", "parameters": {"type": "object"}}
+        })
+    }
+
+    #[tokio::test]
+    async fn chat_refuses_unsupported_tools_and_json_mode() {
+        let router = router(true);
+
+        // o1-mini has no function calling
+        let (status, body) = post(
+            &router,
+            "/openai/v1/chat/completions",
+            json!({
+                "model": "o1-mini",
+                "messages": [{"role": "user", "content": "Weather?"}],
+                "tools": [weather_tool()]
+            }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(
+            body["error"]["message"],
+            "Unsupported parameter: 'tools' is not supported with this model."
+        );
+        assert_eq!(body["error"]["type"], "invalid_request_error");
+        assert_eq!(body["error"]["param"], "tools");
+        assert_eq!(body["error"]["code"], "unsupported_parameter");
+
+        // Image models have no JSON mode
+        let (status, body) = post(
+            &router,
+            "/openai/v1/chat/completions",
+            json!({
+                "model": "gpt-image-1",
+                "messages": [{"role": "user", "content": "JSON please"}],
+                "response_format": {"type": "json_object"}
+            }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"]["param"], "response_format");
+
+        // A capable model, or an unknown one, is answered
+        for model in ["gpt-5", "my-finetune"] {
+            let (status, _) = post(
+                &router,
+                "/openai/v1/chat/completions",
+                json!({
+                    "model": model,
+                    "messages": [{"role": "user", "content": "Weather?"}],
+                    "tools": [weather_tool()],
+                    "response_format": {"type": "json_object"}
+                }),
+            )
+            .await;
+            assert_eq!(status, StatusCode::OK, "{}", model);
+        }
+    }
+
+    #[tokio::test]
+    async fn responses_refuses_unsupported_tools_and_images() {
+        let router = router(true);
+
+        let (status, body) = post(
+            &router,
+            "/openai/v1/responses",
+            json!({
+                "model": "o1-mini",
+                "input": "Weather?",
+                "tools": [{"type": "function", "name": "get_weather", "parameters": {}}]
+            }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"]["code"], "unsupported_parameter");
+
+        // gpt-4 has no vision
+        let (status, body) = post(
+            &router,
+            "/openai/v1/responses",
+            json!({
+                "model": "gpt-4",
+                "input": [{
+                    "role": "user",
+                    "content": [
+                        {"type": "input_text", "text": "What is this?"},
+                        {"type": "input_image", "image_url": "https://example.com/cat.png"}
+                    ]
+                }]
+            }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"]["type"], "invalid_request_error");
+        assert!(body["error"]["message"]
+            .as_str()
+            .unwrap()
+            .contains("input_image"));
+    }
+
+    #[tokio::test]
+    async fn nothing_is_enforced_by_default() {
+        let router = router(false);
+        let (status, _) = post(
+            &router,
+            "/openai/v1/chat/completions",
+            json!({
+                "model": "o1-mini",
+                "messages": [{"role": "user", "content": "Weather?"}],
+                "tools": [weather_tool()]
+            }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+    }
+}
+
+/// Load shedding above `[capacity] max_rps`.
+mod capacity_tests {
+    use crate::common::{chat, config_router, request};
+    use axum::body::to_bytes;
+    use axum::http::StatusCode;
+    use llmsim::cli::Config;
+    use llmsim::stats::new_shared_stats;
+    use serde_json::{json, Value};
+    use tower::ServiceExt;
+
+    fn config(max_rps: u32) -> Config {
+        let mut config = Config::default();
+        config.latency.profile = Some("instant".to_string());
+        config.capacity.max_rps = Some(max_rps);
+        config
+    }
+
+    #[tokio::test]
+    async fn requests_over_max_rps_are_shed() {
+        let stats = new_shared_stats();
+        let router = config_router(config(2), stats.clone());
+
+        let mut statuses = Vec::new();
+        for _ in 0..4 {
+            let req = request(
+                "POST",
+                "/openai/v1/chat/completions",
+                Some(chat("gpt-5", "hi")),
+            );
+            let resp = router.clone().oneshot(req).await.unwrap();
+            statuses.push(resp.status());
+            if resp.status() == StatusCode::SERVICE_UNAVAILABLE {
+                assert_eq!(resp.headers()["retry-after"], "1");
+                let bytes = to_bytes(resp.into_body(), 1024 * 1024).await.unwrap();
+                let body: Value = serde_json::from_slice(&bytes).unwrap();
+                assert_eq!(body["error"]["type"], "service_unavailable");
+            }
+        }
+
+        let served = statuses.iter().filter(|s| **s == StatusCode::OK).count();
+        assert_eq!(served, 2, "{statuses:?}");
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.shed_requests, 2);
+        assert_eq!(snapshot.active_requests, 0);
+    }
+
+    #[tokio::test]
+    async fn retry_after_is_configurable() {
+        let mut config = config(0);
+        config.capacity.retry_after_secs = 7;
+        let router = config_router(config, new_shared_stats());
+
+        let body = json!({"model": "gpt-5", "input": "hi"});
+        let resp = router
+            .oneshot(request("POST", "/openai/v1/responses", Some(body)))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(resp.headers()["retry-after"], "7");
+    }
+}
+
+/// `[errors] cdn_error_rate`: generation requests answered with a CDN's HTML,
+/// plain-text or empty error page instead of the API's JSON.
+mod cdn_errors_tests {
+    use crate::common::{chat, get, instant_router, request};
+    use axum::body::to_bytes;
+    use axum::http::StatusCode;
+    use axum::Router;
+    use llmsim::cli::Config;
+    use tower::ServiceExt;
+
+    fn router(kinds: &str) -> Router {
+        instant_router(&format!(
+            "[errors]\ncdn_error_rate = 1.0\ncdn_errors = {}\n",
+            kinds
+        ))
+    }
+
+    /// Status, content type and body of a chat completion
+    async fn complete(router: &Router) -> (StatusCode, Option<String>, String) {
+        let req = request(
+            "POST",
+            "/openai/v1/chat/completions",
+            Some(chat("gpt-5", "Hello")),
+        );
+        let resp = router.clone().oneshot(req).await.unwrap();
+        let status = resp.status();
+        let content_type = resp
+            .headers()
+            .get("content-type")
+            .map(|value| value.to_str().unwrap().to_string());
+        let body = to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        (
+            status,
+            content_type,
+            String::from_utf8(body.to_vec()).unwrap(),
+        )
+    }
+
+    #[tokio::test]
+    async fn html_pages_replace_the_json_error() {
+        let (status, content_type, body) = complete(&router("[\"html_520\"]")).await;
+        assert_eq!(status.as_u16(), 520);
+        assert_eq!(content_type.as_deref(), Some("text/html; charset=UTF-8"));
+        assert!(body.starts_with("<!DOCTYPE html>"));
+        assert!(body.contains("Error code 520"));
+    }
+
+    #[tokio::test]
+    async fn empty_pages_have_no_body() {
+        let (status, content_type, body) = complete(&router("[\"empty_524\"]")).await;
+        assert_eq!(status.as_u16(), 524);
+        assert_eq!(content_type, None);
+        assert!(body.is_empty());
+    }
+
+    #[tokio::test]
+    async fn only_generation_requests_fail() {
+        let router = router("[]");
+        let (status, _) = get(&router, "/openai/v1/models").await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _, _) = complete(&router).await;
+        assert!(matches!(status.as_u16(), 502 | 520 | 524));
+    }
+
+    #[test]
+    fn rate_is_validated() {
+        let err = Config::from_toml("[errors]\ncdn_error_rate = 1.5\n").unwrap_err();
+        assert!(err.to_string().contains("[errors] cdn_error_rate"));
+        assert!(Config::from_toml("[errors]\ncdn_errors = [\"html_418\"]\n").is_err());
+    }
+}
+
+/// `/llmsim/stats` reports how many generation requests each client had in
+/// flight at once, keyed by API key.
+mod clients_tests {
+    use crate::common::{call, get, instant_router, request, with_key};
+    use axum::Router;
+    use serde_json::json;
+
+    /// A streamed chat completion taking a second to its first token
+    async fn stream(router: &Router, key: &str) {
+        let body = json!({
+            "model": "gpt-5",
+            "stream": true,
+            "messages": [{"role": "user", "content": "hi"}],
+            "llmsim": {"ttft_ms": 1000, "tbt_ms": 0}
+        });
+        let req = request("POST", "/openai/v1/chat/completions", Some(body));
+        call(router, with_key(req, key)).await;
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn concurrency_per_api_key() {
+        let router = instant_router("");
+
+        tokio::join!(
+            stream(&router, "worker-a"),
+            stream(&router, "worker-a"),
+            stream(&router, "worker-a"),
+            stream(&router, "worker-b"),
+        );
+        stream(&router, "worker-b").await;
+
+        let (_, stats) = get(&router, "/llmsim/stats").await;
+        let clients = &stats["client_concurrency"];
+
+        assert_eq!(clients["worker-a"]["requests"], 3);
+        assert_eq!(clients["worker-a"]["in_flight"], 0);
+        assert_eq!(clients["worker-a"]["max_concurrency"], 3);
+        assert_eq!(clients["worker-a"]["mean_concurrency"], 3.0);
+        // Sequential requests never overlap
+        assert_eq!(clients["worker-b"]["requests"], 2);
+        assert_eq!(clients["worker-b"]["max_concurrency"], 1);
+        assert_eq!(clients["worker-b"]["mean_concurrency"], 1.0);
+    }
+}
+
+/// `[clock]` skews the `created`/`created_at` timestamps on responses of
+/// one server state without touching another's.
+mod clock_skew_tests {
+    use crate::common::{chat, instant_router, post_ok};
+    use serde_json::json;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn now() -> i64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64
+    }
+
+    #[tokio::test]
+    async fn created_timestamps_follow_the_skewed_clock() {
+        let chat = chat("gpt-5", "hi");
+        let responses = json!({"model": "gpt-5", "input": "hi"});
+
+        let behind = instant_router("[clock]\nskew_secs = -3600\n");
+        let jittery = instant_router("[clock]\nskew_secs = 600\njitter_secs = 60\n");
+        let accurate = instant_router("");
+        let completion = post_ok(&behind, "/openai/v1/chat/completions", chat.clone()).await;
+        let drift = completion["created"].as_i64().unwrap() - now();
+        assert!((-3602..=-3598).contains(&drift), "drift {}", drift);
+        let response = post_ok(&behind, "/openai/v1/responses", responses).await;
+        let drift = response["created_at"].as_i64().unwrap() - now();
+        assert!((-3602..=-3598).contains(&drift), "drift {}", drift);
+
+        let mut seen = Vec::new();
+        for _ in 0..20 {
+            let completion = post_ok(&jittery, "/openai/v1/chat/completions", chat.clone()).await;
+            let drift = completion["created"].as_i64().unwrap() - now();
+            assert!((538..=662).contains(&drift), "drift {}", drift);
+            seen.push(drift);
+        }
+        seen.sort_unstable();
+        seen.dedup();
+        assert!(seen.len() > 1, "no jitter in {:?}", seen);
+
+        let completion = post_ok(&accurate, "/openai/v1/chat/completions", chat).await;
+        assert!((completion["created"].as_i64().unwrap() - now()).abs() <= 2);
+    }
+}
+
+/// `[cluster] stats_dir`: `/llmsim/cluster/stats` adds the stats other
+/// processes publish to the directory to this one's.
+mod cluster_tests {
+    use crate::common::{call, chat, post, request, router};
+    use axum::http::StatusCode;
+    use llmsim::cli::ClusterStats;
+    use llmsim::stats::Stats;
+    use llmsim::EndpointType;
+
+    #[tokio::test]
+    async fn cluster_stats_add_up_published_files() {
+        let (status, _) = call(&router(""), request("GET", "/llmsim/cluster/stats", None)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let dir = std::env::temp_dir().join(format!("llmsim-cluster-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let peer = Stats::new();
+        for _ in 0..4 {
+            peer.record_request_start("gpt-5", false, EndpointType::ChatCompletions);
+            peer.record_request_end(std::time::Duration::from_millis(10), 5, 5);
+        }
+        std::fs::write(
+            dir.join("worker-1.json"),
+            serde_json::to_vec(&peer.snapshot()).unwrap(),
+        )
+        .unwrap();
+
+        let router = router(&format!(
+        "[latency]\nprofile = \"instant\"\n\n[cluster]\nstats_dir = {:?}\nnode = \"worker-0\"\n",
+        dir.display().to_string()
+    ));
+        let (status, _) = post(&router, "/openai/v1/chat/completions", chat("gpt-5", "hi")).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (status, body) = call(&router, request("GET", "/llmsim/cluster/stats", None)).await;
+        assert_eq!(status, StatusCode::OK);
+        let cluster: ClusterStats = serde_json::from_slice(&body).unwrap();
+        let nodes: Vec<&str> = cluster.nodes.iter().map(|n| n.node.as_str()).collect();
+        assert_eq!(nodes, vec!["worker-0", "worker-1"]);
+        assert_eq!(cluster.stats.total_requests, 5);
+        assert_eq!(cluster.stats.completed_requests, 5);
+        assert_eq!(cluster.stats.model_requests["gpt-5"], 5);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
+
+/// Responses requests offering the `code_interpreter` tool are answered with
+/// a `code_interpreter_call` item before the message.
+mod code_interpreter_tests {
+    use crate::common::{call, config_router, request};
+    use axum::http::StatusCode;
+    use axum::Router;
+    use llmsim::cli::Config;
+    use llmsim::stats::new_shared_stats;
+    use serde_json::{json, Value};
+
+    fn router() -> Router {
+        let mut config = Config::default();
+        config.latency.profile = Some("instant".to_string());
+        config.stream.strict_schema = true;
+        config_router(config, new_shared_stats())
+    }
+
+    async fn create(router: &Router, body: Value) -> (StatusCode, Vec<u8>) {
+        let mut req = request("POST", "/openai/v1/responses", Some(body));
+        req.headers_mut()
+            .insert("host", "sim.test".parse().unwrap());
+        call(router, req).await
+    }
+
+    #[tokio::test]
+    async fn call_precedes_message_with_outputs_on_request() {
+        let router = router();
+        let (status, body) = create(
+            &router,
+            json!({
+                "model": "gpt-4o",
+                "input": "count these words",
+                "tools": [{"type": "code_interpreter", "container": {"type": "auto"}}],
+                "include": ["code_interpreter_call.outputs"]
+            }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let resp: Value = serde_json::from_slice(&body).unwrap();
+        let item = &resp["output"][0];
+        assert_eq!(item["type"], "code_interpreter_call");
+        assert!(item["code"].as_str().unwrap().contains("count these words"));
+        assert_eq!(item["outputs"][0]["logs"], "3 words, 17 characters\n");
+        assert_eq!(resp["output"][1]["type"], "message");
+
+        // The image output downloads from the file store
+        let url = item["outputs"][1]["url"].as_str().unwrap();
+        let path = url.strip_prefix("http://sim.test").unwrap();
+        let (status, png) = call(&router, request("GET", path, None)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(png[1..4], *b"PNG");
+    }
+
+    #[tokio::test]
+    async fn outputs_are_null_without_include() {
+        let (_, body) = create(
+            &router(),
+            json!({"model": "gpt-4o", "input": "hi", "tools": [{"type": "code_interpreter"}]}),
+        )
+        .await;
+        let resp: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(resp["output"][0]["type"], "code_interpreter_call");
+        assert!(resp["output"][0]["outputs"].is_null());
+    }
+
+    #[tokio::test]
+    async fn tool_choice_none_skips_the_call() {
+        let (_, body) = create(
+            &router(),
+            json!({
+                "model": "gpt-4o",
+                "input": "hi",
+                "tools": [{"type": "code_interpreter"}],
+                "tool_choice": "none"
+            }),
+        )
+        .await;
+        let resp: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(resp["output"][0]["type"], "message");
+    }
+
+    #[tokio::test]
+    async fn streamed_call_conforms_to_schema() {
+        let (_, body) = create(
+            &router(),
+            json!({
+                "model": "gpt-4o",
+                "input": "hi",
+                "stream": true,
+                "tools": [{"type": "code_interpreter"}],
+                "include": ["code_interpreter_call.outputs"]
+            }),
+        )
+        .await;
+        let body = String::from_utf8(body).unwrap();
+        assert!(!body.contains("llmsim_schema_violation"), "{}", body);
+        assert!(body.contains("event: response.code_interpreter_call.interpreting"));
+        assert!(body.contains("event: response.code_interpreter_call.completed"));
+    }
+}
+
+/// Response compression negotiated via `Accept-Encoding`.
+mod compression_tests {
+    use crate::common::{config_router, request};
+    use axum::body::Body;
+    use axum::http::{header, Response};
+    use flate2::read::GzDecoder;
+    use llmsim::cli::Config;
+    use llmsim::stats::new_shared_stats;
+    use serde_json::json;
+    use std::io::Read;
+    use tower::ServiceExt;
+
+    fn config(enabled: bool) -> Config {
+        let mut config = Config::default();
+        config.latency.profile = Some("instant".to_string());
+        config.compression.enabled = enabled;
+        config
+    }
+
+    async fn chat(config: Config, stream: bool, encoding: &str) -> Response<Body> {
+        let body = json!({
+            "model": "gpt-5",
+            "messages": [{"role": "user", "content": "hi"}],
+            "stream": stream
+        });
+        let mut req = request("POST", "/openai/v1/chat/completions", Some(body));
+        req.headers_mut()
+            .insert(header::ACCEPT_ENCODING, encoding.parse().unwrap());
+        config_router(config, new_shared_stats())
+            .oneshot(req)
+            .await
+            .unwrap()
+    }
+
+    async fn body_bytes(resp: Response<Body>) -> Vec<u8> {
+        axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap()
+            .to_vec()
+    }
+
+    #[tokio::test]
+    async fn json_is_gzipped_when_enabled() {
+        let resp = chat(config(true), false, "gzip").await;
+        assert_eq!(resp.headers()[header::CONTENT_ENCODING], "gzip");
+
+        let mut text = String::new();
+        GzDecoder::new(body_bytes(resp).await.as_slice())
+            .read_to_string(&mut text)
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_str(&text).unwrap();
+        assert_eq!(json["object"], "chat.completion");
+    }
+
+    #[tokio::test]
+    async fn brotli_is_preferred_when_offered() {
+        let resp = chat(config(true), false, "gzip, br").await;
+        assert_eq!(resp.headers()[header::CONTENT_ENCODING], "br");
+    }
+
+    #[tokio::test]
+    async fn disabled_by_default() {
+        let resp = chat(config(false), false, "gzip").await;
+        assert!(resp.headers().get(header::CONTENT_ENCODING).is_none());
+        serde_json::from_slice::<serde_json::Value>(&body_bytes(resp).await).unwrap();
+    }
+
+    #[tokio::test]
+    async fn unlisted_content_type_is_not_compressed() {
+        let mut config = config(true);
+        config.compression.content_types = vec!["text/plain".to_string()];
+        let resp = chat(config, false, "gzip").await;
+        assert!(resp.headers().get(header::CONTENT_ENCODING).is_none());
+    }
+
+    #[tokio::test]
+    async fn sse_is_never_compressed() {
+        let mut config = config(true);
+        config.compression.content_types = vec!["text/event-stream".to_string()];
+        let resp = chat(config, true, "gzip, br").await;
+        assert!(resp.headers().get(header::CONTENT_ENCODING).is_none());
+
+        let text = String::from_utf8(body_bytes(resp).await).unwrap();
+        assert!(text.ends_with("data: [DONE]\n\n"));
+    }
+}
+
+/// `llmsim::conformance` run against llmsim itself over randomized request
+/// shapes: whatever the combination, the streams must pass every check.
+mod conformance_tests {
+    use crate::common::{call, config_router, request};
+    use llmsim::cli::Config;
+    use llmsim::conformance::{ConformanceChecker, Rule};
+    use llmsim::stats::new_shared_stats;
+    use rand::rngs::StdRng;
+    use rand::{RngExt, SeedableRng};
+    use serde_json::{json, Value};
+
+    const CASES: u64 = 40;
+    const MODELS: [&str; 3] = ["gpt-5", "gpt-4o", "o3"];
+
+    async fn stream(config: Config, uri: &str, body: &Value) -> String {
+        let router = config_router(config, new_shared_stats());
+        let (_, bytes) = call(&router, request("POST", uri, Some(body.clone()))).await;
+        String::from_utf8(bytes).unwrap()
+    }
+
+    fn random_config(rng: &mut StdRng) -> Config {
+        let mut config = Config::default();
+        config.latency.profile = Some("instant".to_string());
+        config.response.generator = ["lorem", "echo", "random", "fixed:Héllo, wörld 👋"]
+            [rng.random_range(0..4)]
+        .to_string();
+        config.response.target_tokens = rng.random_range(1..300);
+        config
+    }
+
+    fn checker() -> ConformanceChecker {
+        ConformanceChecker::new().with_tokenizer("gpt-4").unwrap()
+    }
+
+    fn assert_conforms(failures: Vec<llmsim::ConformanceFailure>, case: u64, request: &Value) {
+        assert!(
+            failures.is_empty(),
+            "case {} ({}) failed:\n{}",
+            case,
+            request,
+            failures
+                .iter()
+                .map(|f| f.to_string())
+                .collect::<Vec<_>>()
+                .join("\n")
+        );
+    }
+
+    #[tokio::test]
+    async fn chat_streams_conform() {
+        let mut rng = StdRng::seed_from_u64(1630);
+        for case in 0..CASES {
+            let mut config = random_config(&mut rng);
+            let model = MODELS[rng.random_range(0..MODELS.len())];
+            let mut request = json!({
+                "model": model,
+                "messages": [{"role": "user", "content": "Tell me something"}],
+                "stream": true,
+                "seed": rng.random_range(0..1000),
+                "stream_options": {
+                    "include_usage": rng.random_bool(0.7),
+                    "continuous_usage_stats": rng.random_bool(0.3),
+                },
+            });
+            if rng.random_bool(0.3) {
+                config.tools.call_rate = 1.0;
+                config.tools.parallel_weights = vec![1.0, 1.0, 1.0];
+                request["tools"] = json!([
+                    {"type": "function", "function": {"name": "search"}},
+                    {"type": "function", "function": {"name": "lookup"}}
+                ]);
+            }
+            let body = stream(config, "/openai/v1/chat/completions", &request).await;
+            assert_conforms(checker().check_chat_stream(&body), case, &request);
+        }
+    }
+
+    #[tokio::test]
+    async fn responses_streams_conform() {
+        let mut rng = StdRng::seed_from_u64(1630);
+        for case in 0..CASES {
+            let config = random_config(&mut rng);
+            let model = MODELS[rng.random_range(0..MODELS.len())];
+            let mut request = json!({
+                "model": model,
+                "input": "Tell me something",
+                "stream": true,
+            });
+            if rng.random_bool(0.5) {
+                let effort = ["low", "medium", "high"][rng.random_range(0..3)];
+                request["reasoning"] = json!({"effort": effort, "summary": "auto"});
+            }
+            if rng.random_bool(0.3) {
+                request["max_output_tokens"] = json!(rng.random_range(1..50));
+            }
+            let body = stream(config, "/openai/v1/responses", &request).await;
+            assert_conforms(checker().check_responses_stream(&body), case, &request);
+        }
+    }
+
+    #[tokio::test]
+    async fn error_stream_is_reported() {
+        let mut config = Config::default();
+        config.latency.profile = Some("instant".to_string());
+        let body = stream(
+            config,
+            "/openai/v1/responses",
+            &json!({"model": "gpt-5", "input": "hi", "stream": true}),
+        )
+        .await;
+        // Dropping the terminal event must be caught
+        let cut = &body[..body.rfind("event: response.completed").unwrap()];
+        let failures = ConformanceChecker::new().check_responses_stream(cut);
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].rule, Rule::Ordering);
+    }
+}
+
+/// Responses chained with `previous_response_id` carry the conversation's
+/// tokens forward, and are refused, or cut with `truncation: "auto"`, once
+/// they outgrow the context window.
+mod context_window_tests {
+    use crate::common::{instant_router, post};
+    use axum::http::StatusCode;
+    use axum::Router;
+    use serde_json::{json, Value};
+
+    async fn create(router: &Router, body: Value) -> (StatusCode, Value) {
+        post(router, "/openai/v1/responses", body).await
+    }
+
+    fn tokens(response: &Value, kind: &str) -> u64 {
+        response["usage"][kind].as_u64().unwrap()
+    }
+
+    #[tokio::test]
+    async fn chained_responses_accumulate_usage() {
+        let router = instant_router("");
+        let (status, first) = create(&router, json!({"model": "gpt-5", "input": "Hello"})).await;
+        assert_eq!(status, StatusCode::OK);
+        let carried = tokens(&first, "input_tokens") + tokens(&first, "output_tokens");
+
+        let (status, second) = create(
+            &router,
+            json!({"model": "gpt-5", "input": "Hello", "previous_response_id": first["id"]}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            tokens(&second, "input_tokens"),
+            tokens(&first, "input_tokens") + carried
+        );
+        assert_eq!(
+            tokens(&second, "total_tokens"),
+            tokens(&second, "input_tokens") + tokens(&second, "output_tokens")
+        );
+    }
+
+    #[tokio::test]
+    async fn outgrown_context_is_refused_or_truncated() {
+        let router = instant_router("[store]\ncontext_window = 40\n");
+        let mut previous = Value::Null;
+        let mut turns = 0;
+        let error = loop {
+            let (status, body) = create(
+                &router,
+                json!({"model": "gpt-5", "input": "Hello", "previous_response_id": previous}),
+            )
+            .await;
+            if status != StatusCode::OK {
+                assert_eq!(status, StatusCode::BAD_REQUEST);
+                break body;
+            }
+            assert!(tokens(&body, "input_tokens") <= 40);
+            previous = body["id"].clone();
+            turns += 1;
+            assert!(turns < 40, "context never ran out");
+        };
+        assert_eq!(error["error"]["code"], "context_length_exceeded");
+        assert_eq!(error["error"]["type"], "invalid_request_error");
+
+        let (status, body) = create(
+            &router,
+            json!({
+                "model": "gpt-5",
+                "input": "Hello",
+                "previous_response_id": previous,
+                "truncation": "auto"
+            }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(tokens(&body, "input_tokens"), 40);
+    }
+
+    #[tokio::test]
+    async fn unknown_previous_response_is_refused() {
+        let (status, body) = create(
+            &instant_router(""),
+            json!({"model": "gpt-5", "input": "Hello", "previous_response_id": "resp_missing"}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"]["code"], "previous_response_not_found");
+    }
+}
+
+/// `/llmsim/debug/echo`: the request as the simulator received it, headers
+/// and all, for checking what a gateway forwards.
+mod debug_echo_tests {
+    use crate::common::{call, instant_router};
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use axum::Router;
+    use serde_json::{json, Value};
+
+    fn router() -> Router {
+        instant_router("")
+    }
+
+    async fn echo(router: &Router, req: Request<Body>) -> Value {
+        let (status, bytes) = call(router, req).await;
+        assert_eq!(status, StatusCode::OK);
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    #[tokio::test]
+    async fn echoes_method_path_headers_and_body() {
+        let router = router();
+        let req = Request::put("/llmsim/debug/echo?trace=1")
+            .header("authorization", "Bearer sk-forwarded")
+            .header("openai-organization", "org-gateway")
+            .header("x-forwarded-for", "10.0.0.1")
+            .header("x-forwarded-for", "10.0.0.2")
+            .body(Body::from(r#"{"model":"gpt-5"}"#))
+            .unwrap();
+        let echoed = echo(&router, req).await;
+
+        assert_eq!(echoed["method"], "PUT");
+        assert_eq!(echoed["path"], "/llmsim/debug/echo");
+        assert_eq!(echoed["query"], "trace=1");
+        let headers = &echoed["headers"];
+        assert_eq!(headers["authorization"], "Bearer sk-forwarded");
+        assert_eq!(headers["openai-organization"], "org-gateway");
+        assert_eq!(headers["x-forwarded-for"], json!(["10.0.0.1", "10.0.0.2"]));
+        assert_eq!(echoed["body"], r#"{"model":"gpt-5"}"#);
+        assert_eq!(echoed["body_bytes"], 17);
+        assert_eq!(echoed["body_truncated"], false);
+
+        let req = Request::get("/llmsim/debug/echo")
+            .body(Body::empty())
+            .unwrap();
+        let echoed = echo(&router, req).await;
+        assert_eq!(echoed["method"], "GET");
+        assert_eq!(echoed["query"], Value::Null);
+        assert_eq!(echoed["body"], "");
+    }
+
+    #[tokio::test]
+    async fn long_bodies_are_cut() {
+        let body = "x".repeat(100 * 1024);
+        let req = Request::post("/llmsim/debug/echo")
+            .body(Body::from(body))
+            .unwrap();
+        let echoed = echo(&router(), req).await;
+        assert_eq!(echoed["body"].as_str().unwrap().len(), 64 * 1024);
+        assert_eq!(echoed["body_bytes"], 100 * 1024);
+        assert_eq!(echoed["body_truncated"], true);
+    }
+}
+
+/// Every request's simulator decisions reach decision log subscribers, so a
+/// test can tell an injected error from a bug in the client.
+mod decisions_tests {
+    use crate::common::{get, post, state};
+    use axum::http::StatusCode;
+    use llmsim::cli::build_router;
+    use llmsim::{ErrorCause, Outcome, SimulatedError};
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn rate_limit_is_the_simulators() {
+        let state = state("[latency]\nprofile = \"instant\"\n\n[errors]\nrate_limit_rate = 1.0\n");
+        let decisions = state.decisions.subscribe();
+        let router = build_router(state);
+
+        let body = json!({"model": "gpt-5", "messages": [{"role": "user", "content": "hi"}]});
+        let (status, _) = post(&router, "/openai/v1/chat/completions", body).await;
+        assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+
+        let decision = decisions.try_recv().unwrap();
+        assert_eq!(decision.model, "gpt-5");
+        assert!(matches!(
+            decision.error,
+            Some((ErrorCause::Injected, SimulatedError::RateLimit { .. }))
+        ));
+        assert_eq!(decision.outcome, Outcome::Failed { status: 429 });
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn stream_records_delays_and_tokens() {
+        let state = state(
+        "[latency]\nttft_mean_ms = 200\nttft_stddev_ms = 0\ntbt_mean_ms = 20\ntbt_stddev_ms = 0\n",
+    );
+        let decisions = state.decisions.subscribe();
+        let router = build_router(state);
+
+        let body = json!({
+            "model": "gpt-5",
+            "stream": true,
+            "messages": [{"role": "user", "content": "hi"}]
+        });
+        let (status, _) = post(&router, "/openai/v1/chat/completions", body).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let decision = decisions.try_recv().unwrap();
+        assert!(decision.streaming);
+        assert_eq!(decision.generator, "lorem");
+        assert_eq!(decision.latency.ttft_mean_ms, 200);
+        assert_eq!(decision.delays[0].as_millis(), 200);
+        assert!(decision.delays[1..].iter().all(|d| d.as_millis() == 20));
+        let Outcome::Completed { output_tokens, .. } = decision.outcome else {
+            panic!("not completed: {:?}", decision.outcome);
+        };
+        assert!(output_tokens > 0);
+        assert!(decision.error.is_none());
+    }
+
+    #[tokio::test]
+    async fn request_log_lists_get() {
+        let router = build_router(state(
+            "[latency]\nprofile = \"instant\"\n\n[request_log]\nenabled = true\ncapacity = 2\n",
+        ));
+        for model in ["gpt-4o", "gpt-5", "gpt-5-mini"] {
+            let body = json!({"model": model, "messages": [{"role": "user", "content": "secret"}]});
+            post(&router, "/openai/v1/chat/completions", body).await;
+        }
+        let forced = json!({
+            "model": "gpt-5",
+            "messages": [{"role": "user", "content": "secret"}],
+            "llmsim": {"force_error": "rate_limit"}
+        });
+        post(&router, "/openai/v1/chat/completions", forced).await;
+
+        let (status, body) = get(&router, "/llmsim/requests").await;
+        assert_eq!(status, StatusCode::OK);
+        let requests = body["requests"].as_array().unwrap();
+        // Newest first, the oldest dropped beyond the capacity
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0]["id"], 4);
+        assert_eq!(requests[0]["outcome"], "failed");
+        assert_eq!(requests[0]["status"], 429);
+        assert_eq!(requests[0]["error_cause"], "requested");
+        assert_eq!(requests[1]["model"], "gpt-5-mini");
+        assert_eq!(requests[1]["outcome"], "completed");
+        assert_eq!(requests[1]["endpoint"], "chat_completions");
+        assert!(requests[1]["output_tokens"].as_u64().unwrap() > 0);
+        // Content stays out unless asked for
+        assert!(requests[1].get("prompt").is_none());
+
+        let (_, body) = get(&router, "/llmsim/requests?limit=1").await;
+        assert_eq!(body["requests"].as_array().unwrap().len(), 1);
+        let (status, _) = get(&router, "/llmsim/requests?limit=0").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn request_log_shows_prompts_only_when_enabled() {
+        let (status, _) = get(&build_router(state("")), "/llmsim/requests").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let router = build_router(state(
+        "[latency]\nprofile = \"instant\"\n\n[request_log]\nenabled = true\ninclude_content = true\n",
+    ));
+        let body =
+            json!({"model": "gpt-5", "messages": [{"role": "user", "content": "what is 2+2?"}]});
+        post(&router, "/openai/v1/chat/completions", body).await;
+        let (_, body) = get(&router, "/llmsim/requests").await;
+        assert_eq!(body["requests"][0]["prompt"], "what is 2+2?");
+    }
+
+    #[tokio::test]
+    async fn request_log_redacts_users_and_long_prompts() {
+        let log = |redact: &str| {
+            build_router(state(&format!(
+            "[latency]\nprofile = \"instant\"\n\n[request_log]\nenabled = true\ninclude_content = true\n\n[request_log.redact]\n{}",
+            redact
+        )))
+        };
+        let body = json!({
+            "model": "gpt-5",
+            "user": "alice@example.com",
+            "messages": [{"role": "user", "content": "my card number is 4111"}]
+        });
+
+        let router = log("");
+        post(&router, "/openai/v1/chat/completions", body.clone()).await;
+        let (_, listed) = get(&router, "/llmsim/requests").await;
+        let user = listed["requests"][0]["user"].as_str().unwrap();
+        assert!(
+            user.starts_with("user-") && !user.contains("alice"),
+            "{}",
+            user
+        );
+
+        let router = log("user = \"strip\"\nmax_content_chars = 4\n");
+        post(&router, "/openai/v1/chat/completions", body.clone()).await;
+        let (_, listed) = get(&router, "/llmsim/requests").await;
+        assert!(listed["requests"][0].get("user").is_none());
+        assert_eq!(listed["requests"][0]["prompt"], "4111");
+
+        let router = log("user = \"keep\"\n");
+        post(&router, "/openai/v1/chat/completions", body).await;
+        let (_, listed) = get(&router, "/llmsim/requests").await;
+        assert_eq!(listed["requests"][0]["user"], "alice@example.com");
+    }
+}
+
+/// Models on a `[deprecations]` schedule warn with `Deprecation`/`Sunset`
+/// headers, then answer with a retirement error in each provider's format.
+mod deprecation_tests {
+    use crate::common::{instant_router, request};
+    use axum::body::to_bytes;
+    use axum::http::StatusCode;
+    use axum::response::Response;
+    use axum::Router;
+    use llmsim::cli::Config;
+    use serde_json::{json, Value};
+    use tower::ServiceExt;
+
+    async fn post(router: &Router, path: &str, body: Value) -> Response {
+        router
+            .clone()
+            .oneshot(request("POST", path, Some(body)))
+            .await
+            .unwrap()
+    }
+
+    async fn chat(router: &Router, model: &str) -> Response {
+        post(
+            router,
+            "/openai/v1/chat/completions",
+            json!({"model": model, "messages": [{"role": "user", "content": "hi"}]}),
+        )
+        .await
+    }
+
+    async fn json_body(response: Response) -> Value {
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    #[tokio::test]
+    async fn past_sunset_is_model_not_found() {
+        let router = instant_router("[deprecations.\"gpt-4\"]\nsunset = \"2020-01-01\"\n");
+        let resp = chat(&router, "gpt-4").await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        assert_eq!(resp.headers()["sunset"], "Wed, 01 Jan 2020 00:00:00 GMT");
+        let body = json_body(resp).await;
+        assert_eq!(body["error"]["code"], "model_not_found");
+
+        // Other models are unaffected
+        let resp = chat(&router, "gpt-5").await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert!(resp.headers().get("deprecation").is_none());
+    }
+
+    #[tokio::test]
+    async fn warning_period_then_deprecation_error() {
+        let router = instant_router(
+            "[deprecations.\"claude-sonnet-4\"]\nafter_requests = 2\nerror = \"deprecated\"\n",
+        );
+        let message = json!({
+            "model": "claude-sonnet-4",
+            "max_tokens": 10,
+            "messages": [{"role": "user", "content": "hi"}]
+        });
+        for _ in 0..2 {
+            let resp = post(&router, "/anthropic/v1/messages", message.clone()).await;
+            assert_eq!(resp.status(), StatusCode::OK);
+            let deprecation = resp.headers()["deprecation"].to_str().unwrap();
+            assert!(deprecation.starts_with('@'), "{}", deprecation);
+        }
+
+        let resp = post(&router, "/anthropic/v1/messages", message).await;
+        assert_eq!(resp.status(), StatusCode::GONE);
+        assert!(resp.headers().contains_key("sunset"));
+        let body = json_body(resp).await;
+        assert_eq!(body["type"], "error");
+        assert_eq!(body["error"]["type"], "not_found_error");
+    }
+
+    #[tokio::test]
+    async fn responses_error_carries_code() {
+        let router = instant_router("[deprecations.\"gpt-4o\"]\nafter_requests = 0\n");
+        let resp = post(
+            &router,
+            "/openai/v1/responses",
+            json!({"model": "gpt-4o", "input": "hi"}),
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        let body = json_body(resp).await;
+        assert_eq!(body["error"]["code"], "model_not_found");
+    }
+
+    #[test]
+    fn invalid_sunset_is_rejected() {
+        let err =
+            Config::from_toml("[deprecations.\"gpt-4\"]\nsunset = \"next week\"\n").unwrap_err();
+        assert!(err.to_string().contains("invalid sunset"), "{}", err);
+    }
+}
+
+/// Client disconnects mid-stream.
+///
+/// Reads part of an SSE body through the in-process router, then drops it the
+/// way hyper does when the client connection goes away, and checks that the
+/// request leaves the in-flight count and is counted as abandoned.
+///
+/// Events are generated in a task of their own, so stats settle once that
+/// task has seen the client go.
+mod disconnect_tests {
+    use crate::common::{config_router, request};
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use axum::Router;
+    use futures_util::StreamExt;
+    use llmsim::cli::Config;
+    use llmsim::stats::{new_shared_stats, SharedStats};
+    use serde_json::json;
+    use tower::ServiceExt;
+
+    fn router(stats: SharedStats) -> Router {
+        let mut config = Config::default();
+        // Slow enough that the stream is still running when the body is dropped.
+        config.latency.ttft_mean_ms = Some(0);
+        config.latency.ttft_stddev_ms = Some(0);
+        config.latency.tbt_mean_ms = Some(50);
+        config.latency.tbt_stddev_ms = Some(0);
+        config_router(config, stats)
+    }
+
+    /// Let the generating task run until no request is in flight
+    async fn settle(stats: &SharedStats) {
+        for _ in 0..1000 {
+            if stats.snapshot().active_requests == 0 {
+                return;
+            }
+            tokio::task::yield_now().await;
+        }
+    }
+
+    fn chat_request(stream: bool) -> Request<Body> {
+        let body = json!({
+            "model": "gpt-5",
+            "messages": [{"role": "user", "content": "hi"}],
+            "stream": stream
+        });
+        request("POST", "/openai/v1/chat/completions", Some(body))
+    }
+
+    #[tokio::test]
+    async fn dropped_stream_counts_as_abandoned() {
+        let stats = new_shared_stats();
+        let resp = router(stats.clone())
+            .oneshot(chat_request(true))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let mut body = resp.into_body().into_data_stream();
+        assert!(body.next().await.is_some());
+        assert_eq!(stats.snapshot().active_requests, 1);
+        drop(body);
+        settle(&stats).await;
+
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.active_requests, 0);
+        assert_eq!(snapshot.abandoned_streams, 1);
+        assert_eq!(snapshot.completed_requests, 0);
+    }
+
+    #[tokio::test]
+    async fn completed_stream_is_not_abandoned() {
+        let stats = new_shared_stats();
+        let mut config = Config::default();
+        config.latency.profile = Some("instant".to_string());
+        let router = config_router(config, stats.clone());
+
+        let resp = router.oneshot(chat_request(true)).await.unwrap();
+        let mut body = resp.into_body().into_data_stream();
+        while body.next().await.is_some() {}
+        drop(body);
+
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.abandoned_streams, 0);
+        assert_eq!(snapshot.completed_requests, 1);
+        assert_eq!(snapshot.active_requests, 0);
+    }
+
+    #[tokio::test]
+    async fn generated_stream_left_unread_is_not_abandoned() {
+        let stats = new_shared_stats();
+        let mut config = Config::default();
+        config.latency.profile = Some("instant".to_string());
+        // Far fewer events than the channel between generation and client holds
+        config.response.target_tokens = 3;
+        let router = config_router(config, stats.clone());
+
+        let resp = router.oneshot(chat_request(true)).await.unwrap();
+        // The whole stream is generated; the client leaves without reading it
+        settle(&stats).await;
+        drop(resp);
+        tokio::task::yield_now().await;
+
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.active_requests, 0);
+        assert_eq!(snapshot.abandoned_streams, 0);
+        assert_eq!(snapshot.completed_requests, 1);
+    }
+}
+
+/// `/llmsim/drill`: timed failure drills started over HTTP, scoped to a
+/// region or global, and visible in stats.
+mod drill_tests {
+    use crate::common::{self, post, send};
+    use axum::http::StatusCode;
+    use axum::Router;
+    use serde_json::json;
+    use std::time::{Duration, Instant};
+
+    fn router() -> Router {
+        common::router(
+            r#"
+[latency]
+ttft_mean_ms = 10
+ttft_stddev_ms = 0
+tbt_mean_ms = 0
+
+[regions.eu-west]
+"#,
+        )
+    }
+
+    async fn chat(router: &Router, prefix: &str) -> StatusCode {
+        let uri = format!("{}/openai/v1/chat/completions", prefix);
+        post(router, &uri, common::chat("gpt-5", "hi")).await.0
+    }
+
+    #[tokio::test]
+    async fn region_outage_drill() {
+        let router = router();
+        let (status, drill) = send(
+            &router,
+            "POST",
+            "/llmsim/drill",
+            Some(json!({"kind": "outage", "region": "eu-west", "duration_secs": 120})),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+        let id = drill["id"].as_str().unwrap().to_string();
+        assert_eq!(drill["remaining_secs"], 120);
+
+        assert_eq!(
+            chat(&router, "/eu-west").await,
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+        assert_eq!(chat(&router, "").await, StatusCode::OK);
+
+        let (_, stats) = send(&router, "GET", "/llmsim/stats", None).await;
+        assert_eq!(stats["active_drills"][0]["id"], id.as_str());
+        assert_eq!(stats["active_drills"][0]["kind"], "outage");
+        let (_, stats) = send(&router, "GET", "/eu-west/llmsim/stats", None).await;
+        assert_eq!(stats["server_errors"], 1);
+
+        let uri = format!("/llmsim/drill/{}", id);
+        let (status, _) = send(&router, "DELETE", &uri, None).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let (status, _) = send(&router, "DELETE", &uri, None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        assert_eq!(chat(&router, "/eu-west").await, StatusCode::OK);
+        let (_, drills) = send(&router, "GET", "/llmsim/drill", None).await;
+        assert_eq!(drills["drills"], json!([]));
+    }
+
+    #[tokio::test]
+    async fn global_latency_drill() {
+        let router = router();
+        let (status, _) = send(
+            &router,
+            "POST",
+            "/llmsim/drill",
+            Some(json!({"kind": "latency", "factor": 30, "duration_secs": 60})),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+
+        for prefix in ["", "/eu-west"] {
+            let started = Instant::now();
+            assert_eq!(chat(&router, prefix).await, StatusCode::OK);
+            assert!(started.elapsed() >= Duration::from_millis(300));
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn storm_ramps_holds_and_recovers() {
+        let router = router();
+        let storm = json!({
+            "kind": "storm",
+            "peak_rate": 1.0,
+            "ramp_secs": 10,
+            "hold_secs": 10,
+            "retry_after_secs": 4,
+            "duration_secs": 40
+        });
+        let (status, drill) = send(&router, "POST", "/llmsim/drill", Some(storm)).await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(
+            drill["storm"],
+            json!({"phase": "ramp", "rate_limit_rate": 0.0})
+        );
+        assert_eq!(chat(&router, "").await, StatusCode::OK);
+
+        tokio::time::advance(Duration::from_secs(15)).await;
+        assert_eq!(
+            chat(&router, "/eu-west").await,
+            StatusCode::TOO_MANY_REQUESTS
+        );
+        let (_, stats) = send(&router, "GET", "/llmsim/stats", None).await;
+        assert_eq!(stats["active_drills"][0]["storm"]["phase"], "hold");
+        assert_eq!(stats["active_drills"][0]["storm"]["rate_limit_rate"], 1.0);
+
+        tokio::time::advance(Duration::from_secs(15)).await;
+        let (_, stats) = send(&router, "GET", "/llmsim/stats", None).await;
+        let storm = &stats["active_drills"][0]["storm"];
+        assert_eq!(storm["phase"], "recover");
+        assert!(storm["rate_limit_rate"].as_f64().unwrap() < 1.0);
+        let (_, stats) = send(&router, "GET", "/eu-west/llmsim/stats", None).await;
+        assert_eq!(stats["rate_limit_errors"], 1);
+    }
+
+    #[tokio::test]
+    async fn invalid_drills_are_rejected() {
+        let router = router();
+        for body in [
+            json!({"kind": "outage", "region": "mars", "duration_secs": 60}),
+            json!({"kind": "errors", "rate": 2.0, "duration_secs": 60}),
+            json!({"kind": "outage", "duration_secs": 0}),
+            json!({"kind": "storm", "ramp_secs": 50, "hold_secs": 20, "duration_secs": 60}),
+        ] {
+            let (status, error) = send(&router, "POST", "/llmsim/drill", Some(body)).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+            assert_eq!(error["error"]["type"], "invalid_request_error");
+        }
+    }
+}
+
+/// `include: ["reasoning.encrypted_content"]`: reasoning items come with an
+/// opaque blob that a stateless (`store: false`) client passes back as input,
+/// and that is checked when it comes back.
+mod encrypted_reasoning_tests {
+    use crate::common::{call, instant_router, request};
+    use axum::http::StatusCode;
+    use axum::Router;
+    use serde_json::{json, Value};
+
+    fn router() -> Router {
+        instant_router("")
+    }
+
+    async fn post(router: &Router, body: Value) -> (StatusCode, String) {
+        let (status, bytes) =
+            call(router, request("POST", "/openai/v1/responses", Some(body))).await;
+        (status, String::from_utf8(bytes).unwrap())
+    }
+
+    fn turn(input: Value) -> Value {
+        json!({
+            "model": "o3",
+            "input": input,
+            "reasoning": {"effort": "medium"},
+            "include": ["reasoning.encrypted_content"],
+            "store": false
+        })
+    }
+
+    fn reasoning_item(response: &Value) -> Value {
+        response["output"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|item| item["type"] == "reasoning")
+            .cloned()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn encrypted_reasoning_round_trips() {
+        let router = router();
+
+        let (status, body) = post(&router, turn(json!("Plan a trip"))).await;
+        assert_eq!(status, StatusCode::OK);
+        let response: Value = serde_json::from_str(&body).unwrap();
+        let reasoning = reasoning_item(&response);
+        assert!(reasoning["id"].as_str().unwrap().starts_with("rs_"));
+        let blob = reasoning["encrypted_content"].as_str().unwrap();
+        assert!(blob.starts_with("gAAAAA"));
+
+        // The next turn carries the reasoning back
+        let next = json!([
+            {"role": "user", "content": "Plan a trip"},
+            reasoning,
+            {"role": "user", "content": "Make it shorter"}
+        ]);
+        let (status, _) = post(&router, turn(next)).await;
+        assert_eq!(status, StatusCode::OK);
+
+        // Not asked for, not sent
+        let (_, body) = post(
+            &router,
+            json!({"model": "o3", "input": "Plan a trip", "reasoning": {"effort": "medium"}}),
+        )
+        .await;
+        let response: Value = serde_json::from_str(&body).unwrap();
+        assert!(reasoning_item(&response).get("encrypted_content").is_none());
+    }
+
+    #[tokio::test]
+    async fn streamed_reasoning_is_encrypted_when_done() {
+        let mut request = turn(json!("Plan a trip"));
+        request["stream"] = json!(true);
+        let (status, body) = post(&router(), request).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let events: Vec<Value> = body
+            .lines()
+            .filter_map(|line| line.strip_prefix("data: "))
+            .filter_map(|data| serde_json::from_str(data).ok())
+            .collect();
+        let done = events
+            .iter()
+            .find(|e| e["type"] == "response.output_item.done" && e["item"]["type"] == "reasoning")
+            .unwrap();
+        let blob = done["item"]["encrypted_content"].as_str().unwrap();
+        let completed = events
+            .iter()
+            .find(|e| e["type"] == "response.completed")
+            .unwrap();
+        assert_eq!(
+            reasoning_item(&completed["response"])["encrypted_content"],
+            blob
+        );
+    }
+
+    #[tokio::test]
+    async fn mangled_or_missing_encrypted_content_is_refused() {
+        let router = router();
+        let (_, body) = post(&router, turn(json!("Plan a trip"))).await;
+        let mut reasoning = reasoning_item(&serde_json::from_str(&body).unwrap());
+        let id = reasoning["id"].as_str().unwrap().to_string();
+
+        let blob = reasoning["encrypted_content"].as_str().unwrap().to_string();
+        reasoning["encrypted_content"] = json!(&blob[..blob.len() / 2]);
+        let (status, body) = post(&router, turn(json!([reasoning.clone()]))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let error: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(error["error"]["code"], "invalid_encrypted_content");
+        assert!(error["error"]["message"].as_str().unwrap().contains(&id));
+
+        // By id alone, with nothing stored to find it in
+        reasoning
+            .as_object_mut()
+            .unwrap()
+            .remove("encrypted_content");
+        let (status, body) = post(&router, turn(json!([reasoning.clone()]))).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert!(body.contains("Items are not persisted when `store` is set to false"));
+
+        // Stored responses are looked up by id instead
+        let mut stored = turn(json!([reasoning]));
+        stored["store"] = json!(true);
+        let (status, _) = post(&router, stored).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+}
+
+/// `/llmsim/errors/catalog` and `/llmsim/errors/force`: every simulated error
+/// as it is rendered, and errors armed for the next matching requests.
+mod error_catalog_tests {
+    use crate::common::{self, instant_router, post, send};
+    use axum::http::StatusCode;
+    use axum::Router;
+    use serde_json::{json, Value};
+
+    fn router() -> Router {
+        instant_router("")
+    }
+
+    async fn chat(router: &Router, model: &str) -> (StatusCode, Value) {
+        post(
+            router,
+            "/openai/v1/chat/completions",
+            common::chat(model, "Hello"),
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn catalog_lists_every_error_in_every_format() {
+        let (status, catalog) = send(&router(), "GET", "/llmsim/errors/catalog", None).await;
+        assert_eq!(status, StatusCode::OK);
+        let data = catalog["data"].as_array().unwrap();
+        let overloaded = data.iter().find(|e| e["type"] == "overloaded").unwrap();
+        assert_eq!(overloaded["status"], 503);
+        assert_eq!(overloaded["formats"]["anthropic"]["body"]["type"], "error");
+        assert_eq!(
+            overloaded["formats"]["openai"]["headers"]["retry-after"],
+            "1"
+        );
+        // Only the CDN's pages are not JSON
+        for entry in data
+            .iter()
+            .filter(|e| e["source"] != "[errors] cdn_error_rate")
+        {
+            for format in ["openai", "responses", "openresponses", "anthropic"] {
+                assert!(
+                    entry["formats"][format]["body"].is_object(),
+                    "{} {}",
+                    entry["type"],
+                    format
+                );
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn armed_error_answers_the_next_matching_request_once() {
+        let router = router();
+        let (status, armed) = send(
+            &router,
+            "POST",
+            "/llmsim/errors/force",
+            Some(json!({"error": "rate_limit", "model": "gpt-5*"})),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(armed["remaining"], 1);
+
+        // Other models pass
+        assert_eq!(chat(&router, "gpt-4o").await.0, StatusCode::OK);
+        let (status, body) = chat(&router, "gpt-5").await;
+        assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(body["error"]["type"], "rate_limit_error");
+        assert_eq!(chat(&router, "gpt-5").await.0, StatusCode::OK);
+
+        let (_, list) = send(&router, "GET", "/llmsim/errors/force", None).await;
+        assert_eq!(list["data"], json!([]));
+    }
+
+    #[tokio::test]
+    async fn armed_errors_can_be_cancelled() {
+        let router = router();
+        let (_, armed) = send(
+            &router,
+            "POST",
+            "/llmsim/errors/force",
+            Some(json!({"error": "server_error", "count": 3})),
+        )
+        .await;
+        let uri = format!("/llmsim/errors/force/{}", armed["id"].as_str().unwrap());
+        assert_eq!(
+            chat(&router, "gpt-5").await.0,
+            StatusCode::INTERNAL_SERVER_ERROR
+        );
+        let (_, list) = send(&router, "GET", "/llmsim/errors/force", None).await;
+        assert_eq!(list["data"][0]["remaining"], 2);
+
+        let (status, _) = send(&router, "DELETE", &uri, None).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        assert_eq!(chat(&router, "gpt-5").await.0, StatusCode::OK);
+        let (status, _) = send(&router, "DELETE", &uri, None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (status, _) = send(
+            &router,
+            "POST",
+            "/llmsim/errors/force",
+            Some(json!({"error": "teapot"})),
+        )
+        .await;
+        assert!(status.is_client_error());
+    }
+}
+
+/// `[[errors.rules]]` fail requests by their content: the model, the
+/// message text, a header, or the prompt size, in each provider's format or
+/// with an `[errors.responses]` template sent as written.
+mod error_rules_tests {
+    use crate::common::{call, chat, request, state};
+    use axum::body::{to_bytes, Body};
+    use axum::http::{Request, StatusCode};
+    use axum::Router;
+    use llmsim::cli::{build_router, Config};
+    use llmsim::{ErrorCause, SimulatedError};
+    use serde_json::{json, Value};
+    use tower::ServiceExt;
+
+    const RULES: &str = r#"
+[latency]
+profile = "instant"
+
+[[errors.rules]]
+model = "gpt-4o"
+status = 429
+retry_after_seconds = 30
+
+[[errors.rules]]
+min_prompt_tokens = 1000
+status = 400
+message = "Prompt is too long"
+
+[[errors.rules]]
+matches = "(?i)please\\s+fail"
+status = 500
+
+[[errors.rules]]
+header = "x-fail"
+header_value = "auth"
+status = 401
+
+[[errors.rules]]
+header = "x-fail"
+header_value = "cdn"
+response = "cloudflare_520"
+
+[[errors.rules]]
+header = "x-fail"
+header_value = "quota"
+response = "quota"
+
+[errors.responses.cloudflare_520]
+status = 520
+headers = { "content-type" = "text/html", "server" = "cloudflare" }
+body = "<html><title>520: Web server is returning an unknown error</title>{model}</html>"
+
+[errors.responses.quota]
+status = 429
+headers = { "retry-after" = "{status}" }
+body = { error = { code = "insufficient_quota", message = "No quota left for {model}" } }
+"#;
+
+    /// A POST of `body`, with `x-fail: header` if given
+    fn failing(path: &str, header: Option<&str>, body: Value) -> Request<Body> {
+        let mut req = request("POST", path, Some(body));
+        if let Some(value) = header {
+            req.headers_mut().insert("x-fail", value.parse().unwrap());
+        }
+        req
+    }
+
+    async fn post(
+        router: &Router,
+        path: &str,
+        header: Option<&str>,
+        body: Value,
+    ) -> (StatusCode, Value) {
+        let (status, bytes) = call(router, failing(path, header, body)).await;
+        (status, serde_json::from_slice(&bytes).unwrap_or_default())
+    }
+
+    #[tokio::test]
+    async fn rate_limit_only_for_matching_model() {
+        let router = build_router(state(RULES));
+        let path = "/openai/v1/chat/completions";
+
+        let (status, _) = post(&router, path, None, chat("gpt-4o", "hi")).await;
+        assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+        let (status, _) = post(&router, path, None, chat("gpt-4o-mini", "hi")).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn long_prompt_is_rejected() {
+        let state = state(RULES);
+        let decisions = state.decisions.subscribe();
+        let router = build_router(state);
+        let long = "word ".repeat(2000);
+
+        let body = json!({
+            "model": "claude-sonnet-4",
+            "max_tokens": 10,
+            "messages": [{"role": "user", "content": [{"type": "text", "text": long}]}]
+        });
+        let (status, body) = post(&router, "/anthropic/v1/messages", None, body).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["type"], "error");
+        assert_eq!(body["error"]["message"], "Prompt is too long");
+
+        let decision = decisions.try_recv().unwrap();
+        assert!(matches!(
+            decision.error,
+            Some((ErrorCause::Rule, SimulatedError::InvalidRequest { .. }))
+        ));
+
+        let (status, _) = post(
+            &router,
+            "/openai/v1/responses",
+            None,
+            json!({"model": "gpt-5", "input": "short"}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn pattern_and_header_rules() {
+        let router = build_router(state(RULES));
+        let path = "/openai/v1/chat/completions";
+
+        let (status, _) = post(&router, path, None, chat("gpt-5", "Please   FAIL now")).await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+
+        let (status, _) = post(&router, path, Some("auth"), chat("gpt-5", "hi")).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (status, _) = post(&router, path, Some("other"), chat("gpt-5", "hi")).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn templates_are_sent_as_written() {
+        let router = build_router(state(RULES));
+        let req = failing(
+            "/anthropic/v1/messages",
+            Some("cdn"),
+            chat("claude-sonnet-4", "hi"),
+        );
+        let resp = router.clone().oneshot(req).await.unwrap();
+        assert_eq!(resp.status().as_u16(), 520);
+        assert_eq!(resp.headers()["content-type"], "text/html");
+        assert_eq!(resp.headers()["server"], "cloudflare");
+        let body = to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(
+        body,
+        "<html><title>520: Web server is returning an unknown error</title>claude-sonnet-4</html>"
+    );
+
+        let path = "/openai/v1/chat/completions";
+        let (status, body) = post(&router, path, Some("quota"), chat("gpt-5", "hi")).await;
+        assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(
+            body,
+            json!({"error": {"code": "insufficient_quota", "message": "No quota left for gpt-5"}})
+        );
+    }
+
+    #[test]
+    fn unknown_template_is_a_config_error() {
+        let err = Config::from_toml("[[errors.rules]]\nresponse = \"missing\"\n").unwrap_err();
+        assert!(err.to_string().contains("missing"), "{}", err);
+        let err = Config::from_toml("[errors.responses.ok]\nstatus = 200\n").unwrap_err();
+        assert!(err.to_string().contains("errors.responses.ok"), "{}", err);
+    }
+
+    #[test]
+    fn invalid_pattern_is_a_config_error() {
+        let err =
+            Config::from_toml("[[errors.rules]]\nmatches = \"[\"\nstatus = 400\n").unwrap_err();
+        assert!(err.to_string().contains("errors.rules"));
+    }
+}
+
+/// `[framing]` decides whether bodies go out chunked or with a
+/// `Content-Length`, and whether chunked ones end with trailers. Framing is
+/// only visible on the wire, so these tests speak raw HTTP/1.1.
+mod framing_tests {
+    use crate::common::instant_router;
+    use llmsim::cli::Config;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+
+    async fn serve(toml: &str) -> std::net::SocketAddr {
+        let app = instant_router(toml);
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        addr
+    }
+
+    /// Send one request on a fresh connection and return the raw response
+    async fn exchange(addr: std::net::SocketAddr, body: &str, extra_headers: &str) -> String {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let request = format!(
+            "POST /openai/v1/chat/completions HTTP/1.1\r\nHost: llmsim\r\n\
+         Content-Type: application/json\r\nContent-Length: {}\r\n\
+         Connection: close\r\n{}\r\n{}",
+            body.len(),
+            extra_headers,
+            body
+        );
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).await.unwrap();
+        String::from_utf8(response).unwrap().to_lowercase()
+    }
+
+    const CHAT: &str = r#"{"model":"gpt-5","messages":[{"role":"user","content":"hi"}]}"#;
+    const CHAT_STREAM: &str =
+        r#"{"model":"gpt-5","stream":true,"messages":[{"role":"user","content":"hi"}]}"#;
+
+    #[tokio::test]
+    async fn chunked_with_trailers() {
+        let addr =
+            serve("[framing]\nbody = \"chunked\"\nchunk_bytes = 16\ntrailers = true\n").await;
+
+        let response = exchange(addr, CHAT, "TE: trailers\r\n").await;
+        assert!(
+            response.contains("transfer-encoding: chunked"),
+            "{}",
+            response
+        );
+        assert!(!response.contains("content-length:"), "{}", response);
+        assert!(response.contains("\r\n10\r\n"), "{}", response);
+        assert!(response.contains("x-llmsim-body-bytes: "), "{}", response);
+
+        // Trailers are only sent to clients that accept them
+        let response = exchange(addr, CHAT, "").await;
+        assert!(
+            response.contains("transfer-encoding: chunked"),
+            "{}",
+            response
+        );
+        assert!(!response.contains("x-llmsim-body-bytes: "), "{}", response);
+    }
+
+    #[tokio::test]
+    async fn content_length_leaves_streams_chunked() {
+        let addr = serve("[framing]\nbody = \"content_length\"\n").await;
+
+        let response = exchange(addr, CHAT, "").await;
+        assert!(response.contains("content-length: "), "{}", response);
+        assert!(!response.contains("transfer-encoding"), "{}", response);
+
+        let response = exchange(addr, CHAT_STREAM, "").await;
+        assert!(
+            response.contains("transfer-encoding: chunked"),
+            "{}",
+            response
+        );
+        assert!(response.contains("data: [done]"), "{}", response);
+    }
+
+    #[tokio::test]
+    async fn streams_get_trailers() {
+        let addr = serve("[framing]\ntrailers = true\n").await;
+
+        let response = exchange(addr, CHAT_STREAM, "TE: trailers\r\n").await;
+        assert!(
+            response.contains("trailer: x-llmsim-body-bytes"),
+            "{}",
+            response
+        );
+        assert!(response.contains("x-llmsim-body-bytes: "), "{}", response);
+    }
+
+    #[test]
+    fn zero_chunk_bytes_is_a_config_error() {
+        let err = Config::from_toml("[framing]\nchunk_bytes = 0\n").unwrap_err();
+        assert!(err.to_string().contains("[framing]"));
+    }
+}
+
+/// The `llmsim.v1.Simulator` gRPC service: requests and responses framed and
+/// encoded as a gRPC client sends and reads them, with the status in
+/// trailers, or in the headers when there is no message.
+mod grpc_tests {
+    use crate::common::state_with_stats;
+    use axum::body::{Body, HttpBody};
+    use axum::http::{HeaderMap, Request, StatusCode};
+    use axum::Router;
+    use llmsim::cli::build_router;
+    use llmsim::stats::{new_shared_stats, SharedStats};
+    use std::pin::Pin;
+    use tower::ServiceExt;
+
+    fn router(toml: &str, stats: SharedStats) -> Router {
+        let toml = format!("[latency]\nprofile = \"instant\"\n\n{}", toml);
+        build_router(state_with_stats(&toml, stats))
+    }
+
+    fn varint(mut value: u64, out: &mut Vec<u8>) {
+        while value >= 0x80 {
+            out.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        out.push(value as u8);
+    }
+
+    fn string_field(field: u64, value: &[u8], out: &mut Vec<u8>) {
+        varint(field << 3 | 2, out);
+        varint(value.len() as u64, out);
+        out.extend_from_slice(value);
+    }
+
+    /// A framed `GenerateRequest` with one user message
+    fn generate_request(model: &str, prompt: &str, max_tokens: u64) -> Vec<u8> {
+        let mut message = Vec::new();
+        string_field(1, b"user", &mut message);
+        string_field(2, prompt.as_bytes(), &mut message);
+        let mut request = Vec::new();
+        string_field(1, model.as_bytes(), &mut request);
+        string_field(2, &message, &mut request);
+        if max_tokens > 0 {
+            varint(3 << 3, &mut request);
+            varint(max_tokens, &mut request);
+        }
+        let mut framed = vec![0];
+        framed.extend_from_slice(&(request.len() as u32).to_be_bytes());
+        framed.extend_from_slice(&request);
+        framed
+    }
+
+    /// Top-level fields of a message: length-delimited ones as bytes, varints
+    /// as their value's little-endian bytes
+    fn decode(mut buf: &[u8]) -> Vec<(u64, Vec<u8>)> {
+        fn read(buf: &mut &[u8]) -> u64 {
+            let mut value = 0;
+            let mut shift = 0;
+            loop {
+                let byte = buf[0];
+                *buf = &buf[1..];
+                value |= u64::from(byte & 0x7f) << shift;
+                shift += 7;
+                if byte & 0x80 == 0 {
+                    return value;
+                }
+            }
+        }
+        let mut fields = Vec::new();
+        while !buf.is_empty() {
+            let key = read(&mut buf);
+            if key & 7 == 0 {
+                fields.push((key >> 3, read(&mut buf).to_le_bytes().to_vec()));
+            } else {
+                let len = read(&mut buf) as usize;
+                fields.push((key >> 3, buf[..len].to_vec()));
+                buf = &buf[len..];
+            }
+        }
+        fields
+    }
+
+    fn field(fields: &[(u64, Vec<u8>)], number: u64) -> Option<&[u8]> {
+        fields
+            .iter()
+            .find(|(n, _)| *n == number)
+            .map(|(_, v)| v.as_slice())
+    }
+
+    fn text(fields: &[(u64, Vec<u8>)], number: u64) -> String {
+        String::from_utf8(field(fields, number).unwrap_or_default().to_vec()).unwrap()
+    }
+
+    /// `Usage` as (input, output) tokens
+    fn usage(fields: &[(u64, Vec<u8>)], number: u64) -> (u64, u64) {
+        let usage = decode(field(fields, number).unwrap());
+        let count =
+            |n| field(&usage, n).map_or(0, |bytes| u64::from_le_bytes(bytes.try_into().unwrap()));
+        (count(1), count(2))
+    }
+
+    struct Reply {
+        headers: HeaderMap,
+        messages: Vec<Vec<(u64, Vec<u8>)>>,
+        trailers: Option<HeaderMap>,
+    }
+
+    async fn call(router: &Router, method: &str, body: Vec<u8>) -> Reply {
+        let resp = router
+            .clone()
+            .oneshot(
+                Request::post(format!("/llmsim.v1.Simulator/{}", method))
+                    .header("content-type", "application/grpc")
+                    .header("te", "trailers")
+                    .body(Body::from(body))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let headers = resp.headers().clone();
+        let mut body = resp.into_body();
+        let mut data = Vec::new();
+        let mut trailers = None;
+        while let Some(frame) = std::future::poll_fn(|cx| Pin::new(&mut body).poll_frame(cx)).await
+        {
+            match frame.unwrap().into_data() {
+                Ok(bytes) => data.extend_from_slice(&bytes),
+                Err(frame) => trailers = frame.into_trailers().ok(),
+            }
+        }
+        let mut messages = Vec::new();
+        let mut rest = data.as_slice();
+        while !rest.is_empty() {
+            assert_eq!(rest[0], 0, "compressed message");
+            let len = u32::from_be_bytes(rest[1..5].try_into().unwrap()) as usize;
+            messages.push(decode(&rest[5..5 + len]));
+            rest = &rest[5 + len..];
+        }
+        Reply {
+            headers,
+            messages,
+            trailers,
+        }
+    }
+
+    #[tokio::test]
+    async fn generate_answers_in_one_message() {
+        let stats = new_shared_stats();
+        let router = router("", stats.clone());
+
+        let reply = call(&router, "Generate", generate_request("gpt-5", "Hello", 0)).await;
+        assert_eq!(reply.headers["content-type"], "application/grpc");
+        assert_eq!(reply.trailers.unwrap()["grpc-status"], "0");
+        assert_eq!(reply.messages.len(), 1);
+        let response = &reply.messages[0];
+        assert!(text(response, 1).starts_with("gen-"));
+        assert_eq!(text(response, 2), "gpt-5");
+        assert!(!text(response, 3).is_empty());
+        assert_eq!(text(response, 4), "stop");
+        let (input, output) = usage(response, 5);
+        assert!(input > 0 && output > 0);
+
+        // max_tokens cuts the answer
+        let reply = call(&router, "Generate", generate_request("gpt-5", "Hello", 3)).await;
+        let response = &reply.messages[0];
+        assert_eq!(text(response, 4), "length");
+        assert_eq!(usage(response, 5).1, 3);
+
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.grpc_requests, 2);
+        assert_eq!(snapshot.completed_requests, 2);
+        assert_eq!(snapshot.non_streaming_requests, 2);
+    }
+
+    #[tokio::test]
+    async fn generate_stream_sends_pieces_then_usage() {
+        let stats = new_shared_stats();
+        let router = router("", stats.clone());
+
+        let reply = call(
+            &router,
+            "GenerateStream",
+            generate_request("gpt-5", "Hello", 0),
+        )
+        .await;
+        assert_eq!(reply.trailers.unwrap()["grpc-status"], "0");
+        let (last, pieces) = reply.messages.split_last().unwrap();
+        assert!(pieces.len() > 1);
+        let id = text(&pieces[0], 1);
+        assert!(pieces.iter().all(|piece| text(piece, 1) == id));
+        let streamed: String = pieces.iter().map(|piece| text(piece, 2)).collect();
+        assert!(!streamed.trim().is_empty());
+
+        assert_eq!(text(last, 1), id);
+        assert_eq!(text(last, 2), "");
+        assert_eq!(text(last, 3), "stop");
+        assert!(usage(last, 4).1 > 0);
+
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.grpc_requests, 1);
+        assert_eq!(snapshot.streaming_requests, 1);
+        assert_eq!(snapshot.completed_requests, 1);
+        assert_eq!(snapshot.active_requests, 0);
+    }
+
+    #[tokio::test]
+    async fn injected_errors_are_grpc_statuses() {
+        let stats = new_shared_stats();
+        let limited = router("[errors]\nrate_limit_rate = 1.0\n", stats.clone());
+        let reply = call(&limited, "Generate", generate_request("gpt-5", "Hello", 0)).await;
+        assert!(reply.messages.is_empty());
+        assert!(reply.trailers.is_none());
+        // RESOURCE_EXHAUSTED
+        assert_eq!(reply.headers["grpc-status"], "8");
+        assert!(reply.headers.contains_key("grpc-message"));
+        assert!(reply.headers.contains_key("retry-after"));
+        assert_eq!(stats.snapshot().rate_limit_errors, 1);
+
+        let failing = router("[errors]\ntimeout_rate = 1.0\n", new_shared_stats());
+        let reply = call(
+            &failing,
+            "GenerateStream",
+            generate_request("gpt-5", "Hello", 0),
+        )
+        .await;
+        // DEADLINE_EXCEEDED
+        assert_eq!(reply.headers["grpc-status"], "4");
+    }
+
+    #[tokio::test]
+    async fn malformed_requests_are_refused() {
+        let router = router("", new_shared_stats());
+
+        // INVALID_ARGUMENT
+        let reply = call(&router, "Generate", vec![0, 0, 0, 0, 9, 0x0a]).await;
+        assert_eq!(reply.headers["grpc-status"], "3");
+        // UNIMPLEMENTED: compressed messages
+        let mut compressed = generate_request("gpt-5", "Hello", 0);
+        compressed[0] = 1;
+        let reply = call(&router, "Generate", compressed).await;
+        assert_eq!(reply.headers["grpc-status"], "12");
+
+        let resp = router
+            .oneshot(
+                Request::post("/llmsim.v1.Simulator/Generate")
+                    .header("content-type", "application/json")
+                    .body(Body::from("{}"))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    }
+}
+
+/// `[errors] hang_rate`: requests accepted and never answered until the
+/// client gives up, or answered with 408 after `hang_respond_after_ms`.
+mod hang_tests {
+    use crate::common::{self, request, state_with_stats};
+    use axum::body::{to_bytes, Body};
+    use axum::http::{Request, StatusCode};
+    use axum::Router;
+    use llmsim::cli::build_router;
+    use llmsim::stats::{new_shared_stats, SharedStats};
+    use serde_json::{json, Value};
+    use std::time::Duration;
+    use tower::ServiceExt;
+
+    fn router(errors: &str, stats: SharedStats) -> Router {
+        let toml = format!("[latency]\nprofile = \"instant\"\n\n[errors]\n{}", errors);
+        build_router(state_with_stats(&toml, stats))
+    }
+
+    fn chat() -> Request<Body> {
+        let body = common::chat("gpt-5", "hi");
+        request("POST", "/openai/v1/chat/completions", Some(body))
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn hung_request_waits_until_the_client_gives_up() {
+        let stats = new_shared_stats();
+        let router = router("hang_rate = 1.0\n", stats.clone());
+
+        let gave_up = tokio::time::timeout(Duration::from_secs(30), router.oneshot(chat())).await;
+        assert!(gave_up.is_err(), "a hung request was answered");
+
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.hung_requests, 1);
+        assert_eq!(snapshot.client_timeouts, 1);
+        assert_eq!(snapshot.active_requests, 0);
+        assert_eq!(snapshot.total_errors, 0);
+        assert_eq!(snapshot.client_wait_ms.count, 1);
+        assert_eq!(snapshot.client_wait_ms.max, 30_000);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn hung_request_answers_408_when_configured() {
+        let stats = new_shared_stats();
+        let router = router(
+            "hang_rate = 1.0\nhang_respond_after_ms = 5000\n",
+            stats.clone(),
+        );
+
+        let resp = router.clone().oneshot(chat()).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::REQUEST_TIMEOUT);
+        let body: Value =
+            serde_json::from_slice(&to_bytes(resp.into_body(), usize::MAX).await.unwrap()).unwrap();
+        assert_eq!(body["error"]["type"], "request_timeout");
+
+        // Each provider answers in its own error shape
+        let resp = router
+        .oneshot(request(
+            "POST",
+            "/anthropic/v1/messages",
+            Some(json!({"model": "claude-sonnet-4-6", "max_tokens": 10, "messages": [{"role": "user", "content": "hi"}]})),
+        ))
+        .await
+        .unwrap();
+        assert_eq!(resp.status(), StatusCode::REQUEST_TIMEOUT);
+        let body: Value =
+            serde_json::from_slice(&to_bytes(resp.into_body(), usize::MAX).await.unwrap()).unwrap();
+        assert_eq!(body["type"], "error");
+
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.hung_requests, 2);
+        assert_eq!(snapshot.request_timeout_errors, 2);
+        assert_eq!(snapshot.client_timeouts, 0);
+        assert_eq!(snapshot.active_requests, 0);
+        assert_eq!(snapshot.client_wait_ms.avg, 5000.0);
+    }
+
+    #[tokio::test]
+    async fn other_routes_are_never_held() {
+        let stats = new_shared_stats();
+        let router = router("hang_rate = 1.0\n", stats.clone());
+        let resp = router
+            .oneshot(request("GET", "/openai/v1/models", None))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(stats.snapshot().hung_requests, 0);
+    }
+}
+
+/// Ids shaped like the real APIs', and `[ids]` replacing the formats of one
+/// server state without touching another's.
+mod ids_tests {
+    use crate::common::{chat, instant_router, post_ok};
+    use serde_json::{json, Value};
+
+    fn shaped(id: &str, prefix: &str, len: usize, alphabet: fn(char) -> bool) -> bool {
+        id.strip_prefix(prefix)
+            .is_some_and(|rest| rest.len() == len && rest.chars().all(alphabet))
+    }
+
+    /// Id of the response's message item
+    fn message_id(response: &Value) -> &str {
+        let output = response["output"].as_array().unwrap();
+        let message = output
+            .iter()
+            .find(|item| item["type"] == "message")
+            .unwrap();
+        message["id"].as_str().unwrap()
+    }
+
+    fn lower_hex(c: char) -> bool {
+        c.is_ascii_digit() || ('a'..='f').contains(&c)
+    }
+
+    #[tokio::test]
+    async fn ids_follow_real_and_configured_formats() {
+        let chat = chat("gpt-5", "hi");
+        let responses = json!({"model": "gpt-5", "input": "hi"});
+
+        let real = instant_router("");
+        let configured = instant_router(
+            "[ids.chat_completion]\nprefix = \"cmpl-\"\nalphabet = \"0123456789\"\nlength = 12\n\n\
+         [ids.response]\nalphabet = \"uuid\"\n",
+        );
+        let completion = post_ok(&real, "/openai/v1/chat/completions", chat.clone()).await;
+        let id = completion["id"].as_str().unwrap();
+        assert!(
+            shaped(id, "chatcmpl-", 29, |c| c.is_ascii_alphanumeric()),
+            "{}",
+            id
+        );
+        let response = post_ok(&real, "/openai/v1/responses", responses.clone()).await;
+        assert!(shaped(
+            response["id"].as_str().unwrap(),
+            "resp_",
+            48,
+            lower_hex
+        ));
+        let message = message_id(&response);
+        assert!(shaped(message, "msg_", 48, lower_hex), "{}", message);
+
+        let completion = post_ok(&configured, "/openai/v1/chat/completions", chat.clone()).await;
+        let id = completion["id"].as_str().unwrap();
+        assert!(shaped(id, "cmpl-", 12, |c| c.is_ascii_digit()), "{}", id);
+        let response = post_ok(&configured, "/openai/v1/responses", responses).await;
+        let id = response["id"].as_str().unwrap();
+        assert!(
+            id.starts_with("resp_") && id.matches('-').count() == 4,
+            "{}",
+            id
+        );
+        // Kinds left alone keep the real format
+        let message = message_id(&response);
+        assert!(shaped(message, "msg_", 48, lower_hex), "{}", message);
+
+        // The state built last does not decide the formats of the other
+        let completion = post_ok(&real, "/openai/v1/chat/completions", chat).await;
+        let id = completion["id"].as_str().unwrap();
+        assert!(id.starts_with("chatcmpl-"), "{}", id);
+    }
+}
+
+/// `[json_body]`: JSON responses labelled with a charset, or sent with a
+/// UTF-8 byte order mark or a trailing newline, as some providers do.
+mod json_body_tests {
+    use crate::common::{call, instant_router, request};
+    use axum::body::{to_bytes, Body};
+    use axum::http::{Response, StatusCode};
+    use axum::Router;
+    use llmsim::cli::Config;
+    use serde_json::{json, Value};
+    use tower::ServiceExt;
+
+    fn router(toml: &str) -> Router {
+        instant_router(&format!("[json_body]\n{}", toml))
+    }
+
+    async fn chat(router: &Router, stream: bool) -> Response<Body> {
+        let body = json!({
+            "model": "gpt-5",
+            "messages": [{"role": "user", "content": "Hello"}],
+            "stream": stream
+        });
+        let req = request("POST", "/openai/v1/chat/completions", Some(body));
+        router.clone().oneshot(req).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn bodies_get_a_bom_and_a_trailing_newline() {
+        let router =
+            router("content_type = \"charset\"\nbom_rate = 1.0\ntrailing_newline_rate = 1.0\n");
+        let resp = chat(&router, false).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            resp.headers()["content-type"],
+            "application/json; charset=utf-8"
+        );
+        let bytes = to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        let json = bytes
+            .strip_prefix(b"\xEF\xBB\xBF")
+            .and_then(|rest| rest.strip_suffix(b"\n"))
+            .expect("a BOM and a newline around the body");
+        let json: Value = serde_json::from_slice(json).unwrap();
+        assert_eq!(json["object"], "chat.completion");
+
+        // Error bodies vary too
+        let req = request("GET", "/openai/v1/models/no-such-model", None);
+        let (status, bytes) = call(&router, req).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert!(bytes.starts_with(b"\xEF\xBB\xBF"));
+    }
+
+    #[tokio::test]
+    async fn streams_are_left_alone() {
+        let router = router("content_type = \"charset\"\nbom_rate = 1.0\n");
+        let resp = chat(&router, true).await;
+        assert!(resp.headers()["content-type"]
+            .to_str()
+            .unwrap()
+            .starts_with("text/event-stream"));
+        let bytes = to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        assert!(bytes.starts_with(b"data: "));
+    }
+
+    #[tokio::test]
+    async fn bare_by_default() {
+        let resp = chat(&router(""), false).await;
+        assert_eq!(resp.headers()["content-type"], "application/json");
+        let bytes = to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        assert!(bytes.starts_with(b"{") && bytes.ends_with(b"}"));
+    }
+
+    #[test]
+    fn rates_are_validated() {
+        let err = Config::from_toml("[json_body]\nbom_rate = 2.0\n").unwrap_err();
+        assert!(err.to_string().contains("[json_body] bom_rate"));
+    }
+}
+
+/// `[keys]`: API keys created and revoked at runtime through `/llmsim/keys`,
+/// each with its own quotas, kept across restarts in `[keys] file`.
+mod keys_tests {
+    use crate::common::{call, chat, instant_router, request, with_key};
+    use axum::http::StatusCode;
+    use axum::Router;
+    use serde_json::{json, Value};
+
+    fn router(toml: &str) -> Router {
+        instant_router(&format!("[keys]\nenabled = true\n{}", toml))
+    }
+
+    async fn admin(
+        router: &Router,
+        method: &str,
+        uri: &str,
+        token: Option<&str>,
+        body: Option<Value>,
+    ) -> (StatusCode, Value) {
+        let mut req = request(method, uri, body);
+        if let Some(token) = token {
+            req = with_key(req, token);
+        }
+        let (status, bytes) = call(router, req).await;
+        (status, serde_json::from_slice(&bytes).unwrap_or_default())
+    }
+
+    async fn create_key(router: &Router, body: Value) -> Value {
+        let (status, key) = admin(router, "POST", "/llmsim/keys", None, Some(body)).await;
+        assert_eq!(status, StatusCode::CREATED);
+        key
+    }
+
+    async fn complete(router: &Router, key: &str) -> StatusCode {
+        let body = chat("gpt-5", "Hello");
+        let req = request("POST", "/openai/v1/chat/completions", Some(body));
+        call(router, with_key(req, key)).await.0
+    }
+
+    #[tokio::test]
+    async fn created_keys_are_served_until_revoked() {
+        let router = router("");
+        assert_eq!(
+            complete(&router, "sk-unknown").await,
+            StatusCode::UNAUTHORIZED
+        );
+
+        let key = create_key(&router, json!({"name": "ci"})).await;
+        let value = key["key"].as_str().unwrap();
+        assert!(value.starts_with("sk-sim-"));
+        assert_eq!(complete(&router, value).await, StatusCode::OK);
+
+        let (_, list) = admin(&router, "GET", "/llmsim/keys", None, None).await;
+        let listed = &list["data"][0];
+        assert_eq!(listed["id"], key["id"]);
+        assert_eq!(listed["name"], "ci");
+        assert_ne!(listed["key"], key["key"]);
+
+        let uri = format!("/llmsim/keys/{}", key["id"].as_str().unwrap());
+        let (status, revoked) = admin(&router, "DELETE", &uri, None, None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(revoked["revoked_at"].is_u64());
+        assert_eq!(complete(&router, value).await, StatusCode::UNAUTHORIZED);
+        let (status, _) = admin(&router, "DELETE", "/llmsim/keys/key_missing", None, None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn keys_have_their_own_quotas() {
+        let router = router("[rate_limits]\nrequests_per_minute = 100\n");
+        let small = create_key(&router, json!({"requests_per_minute": 1})).await;
+        let big = create_key(&router, json!({})).await;
+        let small = small["key"].as_str().unwrap();
+        let big = big["key"].as_str().unwrap();
+
+        assert_eq!(complete(&router, small).await, StatusCode::OK);
+        assert_eq!(
+            complete(&router, small).await,
+            StatusCode::TOO_MANY_REQUESTS
+        );
+        for _ in 0..3 {
+            assert_eq!(complete(&router, big).await, StatusCode::OK);
+        }
+    }
+
+    #[tokio::test]
+    async fn keys_survive_a_restart() {
+        let path =
+            std::env::temp_dir().join(format!("llmsim-keys-test-{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let toml = format!("file = {:?}\n", path.display().to_string());
+        let key = create_key(&router(&toml), json!({"key": "sk-team-search"})).await;
+        assert_eq!(key["key"], "sk-team-search");
+
+        let restarted = router(&toml);
+        assert_eq!(complete(&restarted, "sk-team-search").await, StatusCode::OK);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn admin_endpoints_need_the_admin_key() {
+        let router = router("admin_key = \"admin-secret\"\n");
+        let (status, body) = admin(&router, "GET", "/llmsim/keys", None, None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(body["error"]["type"], "authentication_error");
+        let (status, _) = admin(
+            &router,
+            "POST",
+            "/llmsim/keys",
+            Some("admin-secret"),
+            Some(json!({"latency_profile": "gpt-5"})),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+        let (status, _) = admin(
+            &router,
+            "POST",
+            "/llmsim/keys",
+            Some("admin-secret"),
+            Some(json!({"latency_profile": "warp-speed"})),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let disabled = instant_router("");
+        let (status, _) = admin(&disabled, "GET", "/llmsim/keys", None, None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn key_profiles_are_instant_without_delays() {
+        // Time on the paused clock a request with a `gpt-5` key takes
+        async fn keyed_request_time(router: &Router) -> std::time::Duration {
+            let key = create_key(router, json!({"latency_profile": "gpt-5"})).await;
+            let started = tokio::time::Instant::now();
+            assert_eq!(
+                complete(router, key["key"].as_str().unwrap()).await,
+                StatusCode::OK
+            );
+            started.elapsed()
+        }
+
+        assert!(keyed_request_time(&router("")).await > std::time::Duration::ZERO);
+        let disabled = router("[simulation]\ndisable_delays = true\n");
+        assert_eq!(
+            keyed_request_time(&disabled).await,
+            std::time::Duration::ZERO
+        );
+    }
+}
+
+/// A custom `LatencyModel` registered with the library and picked with
+/// `[latency] model` times every stream, seeing each request's context.
+mod latency_model_tests {
+    use crate::common::{call, request};
+    use axum::http::StatusCode;
+    use llmsim::cli::{build_router, AppState, Config};
+    use llmsim::stats::new_shared_stats;
+    use llmsim::{LatencyContext, LatencyModel, LatencyModels, Outcome};
+    use serde_json::json;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    /// Slower with longer prompts, and notes what it was asked
+    #[derive(Default)]
+    struct PromptSized {
+        seen: Mutex<Vec<LatencyContext>>,
+    }
+
+    impl LatencyModel for PromptSized {
+        fn sample_ttft(&self, context: &LatencyContext) -> Duration {
+            self.seen.lock().unwrap().push(context.clone());
+            Duration::from_millis(10 * context.prompt_tokens as u64)
+        }
+
+        fn sample_tbt(&self, context: &LatencyContext) -> Duration {
+            self.seen.lock().unwrap().push(context.clone());
+            Duration::from_millis(3)
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn configured_model_times_the_stream() {
+        let model = Arc::new(PromptSized::default());
+        let config = Config::from_toml("[latency]\nmodel = \"prompt-sized\"\n").unwrap();
+        let models = LatencyModels::new().with("prompt-sized", model.clone());
+        let state = Arc::new(AppState::with_latency_models(
+            config,
+            new_shared_stats(),
+            models,
+        ));
+        let decisions = state.decisions.subscribe();
+        let router = build_router(state);
+
+        let body = json!({
+            "model": "gpt-5",
+            "stream": true,
+            "messages": [{"role": "user", "content": "Tell me a story about a lighthouse"}]
+        });
+        let req = request("POST", "/openai/v1/chat/completions", Some(body));
+        let (status, _) = call(&router, req).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let seen = model.seen.lock().unwrap().clone();
+        assert_eq!(seen[0].model, "gpt-5");
+        assert_eq!(seen[0].position, 0);
+        assert_eq!(seen[0].concurrency, 1);
+        assert!(seen[0].prompt_tokens > 0);
+        assert_eq!(seen[2].position, 2);
+
+        let decision = decisions.try_recv().unwrap();
+        assert!(matches!(decision.outcome, Outcome::Completed { .. }));
+        assert_eq!(
+            decision.delays[0],
+            Duration::from_millis(10 * seen[0].prompt_tokens as u64)
+        );
+        assert!(decision.delays[1..]
+            .iter()
+            .all(|d| *d == Duration::from_millis(3)));
+    }
+}
+
+/// `[learned]`: profiles recorded from proxied responses of a real upstream
+/// (here a second llmsim with known timing) replay without the upstream.
+mod learned_tests {
+    use crate::common::{call, get, request, router};
+    use axum::http::StatusCode;
+    use axum::Router;
+    use llmsim::LearnedProfiles;
+    use serde_json::{json, Value};
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    /// An upstream taking 200ms to the first token, with six-token answers
+    async fn start_upstream() -> String {
+        let app = router(
+            r#"
+[latency]
+ttft_mean_ms = 200
+ttft_stddev_ms = 0
+tbt_mean_ms = 5
+tbt_stddev_ms = 0
+
+[response]
+generator = "fixed:one two three four five six"
+"#,
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        format!("http://{}/openai", addr)
+    }
+
+    fn profile_path(name: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("llmsim-{}-{}.json", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    async fn post(router: &Router, body: Value) -> (StatusCode, Vec<u8>) {
+        call(
+            router,
+            request("POST", "/openai/v1/chat/completions", Some(body)),
+        )
+        .await
+    }
+
+    fn chat(stream: bool) -> Value {
+        json!({
+            "model": "gpt-4o",
+            "stream": stream,
+            "messages": [{"role": "user", "content": "hi"}]
+        })
+    }
+
+    #[tokio::test]
+    async fn recorded_profiles_replay_without_upstream() {
+        let path = profile_path("record");
+        let recording = router(&format!(
+            r#"
+[upstreams.openai]
+base_url = "{}"
+
+[[proxy.rules]]
+model = "gpt-4o"
+
+[learned]
+record = "{}"
+save_interval_secs = 0
+"#,
+            start_upstream().await,
+            path.display()
+        ));
+        for _ in 0..2 {
+            let (status, _) = post(&recording, chat(true)).await;
+            assert_eq!(status, StatusCode::OK);
+        }
+
+        let saved = LearnedProfiles::load(&path).unwrap();
+        let profile = &saved.models["gpt-4o"];
+        assert_eq!(profile.completion_tokens.count(), 2);
+        assert_eq!(profile.completion_tokens.mean(), 6.0);
+        let ttft = profile.ttft_ms.mean();
+        assert!((150.0..1000.0).contains(&ttft), "learned TTFT {ttft}ms");
+
+        let replaying = router(&format!(
+            r#"
+[response]
+generator = "sequence"
+
+[learned]
+replay = "{}"
+"#,
+            path.display()
+        ));
+        // Replayed delays pass on the paused clock exactly as sampled, however
+        // busy the machine is; their mean over many replays stays within half a
+        // recorded deviation of the learned TTFT
+        tokio::time::pause();
+        const REPLAYS: u32 = 64;
+        let started = tokio::time::Instant::now();
+        for _ in 0..REPLAYS {
+            let (status, body) = post(&replaying, chat(false)).await;
+            assert_eq!(status, StatusCode::OK);
+            let body: Value = serde_json::from_slice(&body).unwrap();
+            assert_eq!(body["choices"][0]["message"]["content"], "1 2 3 4 5 6");
+        }
+        let mean = started.elapsed() / REPLAYS;
+        let floor = ttft - 4.0 * profile.ttft_ms.stddev() / f64::from(REPLAYS).sqrt();
+        assert!(
+            mean >= Duration::from_secs_f64(floor.max(0.0) / 1000.0),
+            "replayed in {mean:?} on average, learned TTFT {ttft}ms"
+        );
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn profiles_endpoint_reports_recording() {
+        let off = router("[latency]\nprofile = \"instant\"\n");
+        let (status, _) = get(&off, "/llmsim/profiles").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let path = profile_path("endpoint");
+        let on = router(&format!("[learned]\nrecord = \"{}\"\n", path.display()));
+        let (status, body) = get(&on, "/llmsim/profiles").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, json!({"models": {}}));
+    }
+}
+
+/// Deprecated `functions` / `function_call` chat requests.
+///
+/// Clients on the old functions API must get `message.function_call` (and
+/// `delta.function_call` when streaming) back instead of `tool_calls`.
+mod legacy_functions_tests {
+    use crate::common::{call, request};
+    use llmsim::cli::{build_router, AppState, Config};
+    use llmsim::script::{Script, SimToolCall, SimTurn};
+    use llmsim::stats::new_shared_stats;
+    use serde_json::{json, Value};
+    use std::sync::Arc;
+
+    fn state() -> AppState {
+        let mut config = Config::default();
+        config.latency.profile = Some("instant".to_string());
+        AppState::new(config, new_shared_stats())
+    }
+
+    async fn post(state: AppState, body: Value) -> String {
+        let router = build_router(Arc::new(state));
+        let req = request("POST", "/openai/v1/chat/completions", Some(body));
+        String::from_utf8(call(&router, req).await.1).unwrap()
+    }
+
+    fn legacy_request(stream: bool, function_call: Value) -> Value {
+        json!({
+            "model": "gpt-3.5-turbo",
+            "messages": [{"role": "user", "content": "weather in Paris?"}],
+            "functions": [{
+                "name": "get_weather",
+                "parameters": {"type": "object", "properties": {"city": {"type": "string"}}}
+            }],
+            "function_call": function_call,
+            "stream": stream
+        })
+    }
+
+    #[tokio::test]
+    async fn forced_function_call_is_answered_with_function_call() {
+        let body = post(
+            state(),
+            legacy_request(false, json!({"name": "get_weather"})),
+        )
+        .await;
+        let v: Value = serde_json::from_str(&body).unwrap();
+        let choice = &v["choices"][0];
+        assert_eq!(choice["finish_reason"], "function_call");
+        assert_eq!(choice["message"]["function_call"]["name"], "get_weather");
+        assert_eq!(choice["message"]["function_call"]["arguments"], "{}");
+        assert!(choice["message"]["tool_calls"].is_null());
+    }
+
+    #[tokio::test]
+    async fn forced_function_call_streams_function_call_deltas() {
+        let body = post(
+            state(),
+            legacy_request(true, json!({"name": "get_weather"})),
+        )
+        .await;
+        assert!(body.contains(
+            "\"delta\":{\"function_call\":{\"name\":\"get_weather\",\"arguments\":\"\"}}"
+        ));
+        assert!(body.contains("\"delta\":{\"function_call\":{\"arguments\":\"{}\"}}"));
+        assert!(body.contains("\"finish_reason\":\"function_call\""));
+        assert!(!body.contains("tool_calls"));
+        assert!(body.ends_with("data: [DONE]\n\n"));
+    }
+
+    #[tokio::test]
+    async fn auto_function_call_without_script_returns_text() {
+        let body = post(state(), legacy_request(false, json!("auto"))).await;
+        let v: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(v["choices"][0]["finish_reason"], "stop");
+        assert!(v["choices"][0]["message"]["content"].is_string());
+    }
+
+    #[tokio::test]
+    async fn scripted_tool_calls_use_legacy_shape_for_functions_requests() {
+        let script = Script::new(vec![SimTurn::ToolCalls {
+            calls: vec![SimToolCall {
+                name: "get_weather".into(),
+                arguments: json!({"city": "Paris"}),
+                id: None,
+            }],
+        }]);
+        let state = state().with_script(Arc::new(script));
+        let body = post(state, legacy_request(false, json!("auto"))).await;
+        let v: Value = serde_json::from_str(&body).unwrap();
+        let call = &v["choices"][0]["message"]["function_call"];
+        assert_eq!(call["name"], "get_weather");
+        let args: Value = serde_json::from_str(call["arguments"].as_str().unwrap()).unwrap();
+        assert_eq!(args["city"], "Paris");
+    }
+
+    #[tokio::test]
+    async fn legacy_function_messages_are_accepted() {
+        // A follow-up turn carrying the assistant's function_call and the
+        // function role result
+        let body = post(
+            state(),
+            json!({
+                "model": "gpt-3.5-turbo",
+                "messages": [
+                    {"role": "user", "content": "weather in Paris?"},
+                    {"role": "assistant", "content": null,
+                     "function_call": {"name": "get_weather", "arguments": "{\"city\":\"Paris\"}"}},
+                    {"role": "function", "name": "get_weather", "content": "{\"temp\": 21}"}
+                ],
+                "functions": [{"name": "get_weather"}]
+            }),
+        )
+        .await;
+        let v: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(v["object"], "chat.completion");
+    }
+}
+
+/// Responses requests offering `mcp` tools get the server's tool listing,
+/// then an approval request or a call, from fake tool catalogs.
+mod mcp_tests {
+    use crate::common::{call, instant_router, request};
+    use axum::http::StatusCode;
+    use axum::Router;
+    use serde_json::{json, Value};
+
+    const CONFIG: &str = r#"
+[stream]
+strict_schema = true
+
+[[mcp.servers.deepwiki.tools]]
+name = "ask_question"
+description = "Ask a question about a repository"
+input_schema = { type = "object", properties = { question = { type = "string" } } }
+output = "The repository is a simulator."
+"#;
+
+    async fn create(router: &Router, body: Value) -> (StatusCode, Vec<u8>) {
+        call(router, request("POST", "/openai/v1/responses", Some(body))).await
+    }
+
+    fn mcp_tool(require_approval: &str) -> Value {
+        json!({
+            "type": "mcp",
+            "server_label": "deepwiki",
+            "server_url": "https://mcp.deepwiki.com/mcp",
+            "require_approval": require_approval
+        })
+    }
+
+    #[tokio::test]
+    async fn approval_round_trip() {
+        let router = instant_router(CONFIG);
+        let (status, body) = create(
+            &router,
+            json!({"model": "gpt-4o", "input": "what is llmsim?", "tools": [mcp_tool("always")]}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let resp: Value = serde_json::from_slice(&body).unwrap();
+        let listing = resp["output"][0].clone();
+        assert_eq!(listing["type"], "mcp_list_tools");
+        assert_eq!(listing["tools"][0]["name"], "ask_question");
+        let request = resp["output"][1].clone();
+        assert_eq!(request["type"], "mcp_approval_request");
+        assert_eq!(request["arguments"], r#"{"question":"what is llmsim?"}"#);
+
+        // Approving it in the next turn makes the call
+        let (_, body) = create(
+        &router,
+        json!({
+            "model": "gpt-4o",
+            "tools": [mcp_tool("always")],
+            "input": [
+                {"role": "user", "content": "what is llmsim?"},
+                listing,
+                request,
+                {"type": "mcp_approval_response", "approval_request_id": request["id"], "approve": true}
+            ]
+        }),
+    )
+    .await;
+        let resp: Value = serde_json::from_slice(&body).unwrap();
+        let item = &resp["output"][0];
+        assert_eq!(item["type"], "mcp_call");
+        assert_eq!(item["approval_request_id"], request["id"]);
+        assert_eq!(item["output"], "The repository is a simulator.");
+        assert_eq!(resp["output"][1]["type"], "message");
+    }
+
+    #[tokio::test]
+    async fn denied_request_is_not_called() {
+        let (_, body) = create(
+        &instant_router(CONFIG),
+        json!({
+            "model": "gpt-4o",
+            "tools": [mcp_tool("always")],
+            "input": [
+                {"role": "user", "content": "hi"},
+                {"type": "mcp_list_tools", "id": "mcpl_1", "server_label": "deepwiki", "tools": []},
+                {"type": "mcp_approval_request", "id": "mcpr_1", "server_label": "deepwiki",
+                 "name": "ask_question", "arguments": "{}"},
+                {"type": "mcp_approval_response", "approval_request_id": "mcpr_1", "approve": false}
+            ]
+        }),
+    )
+    .await;
+        let resp: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(resp["output"][0]["type"], "message");
+    }
+
+    #[tokio::test]
+    async fn streamed_call_conforms_to_schema() {
+        let (_, body) = create(
+            &instant_router(CONFIG),
+            json!({"model": "gpt-4o", "input": "hi", "stream": true, "tools": [mcp_tool("never")]}),
+        )
+        .await;
+        let body = String::from_utf8(body).unwrap();
+        assert!(!body.contains("llmsim_schema_violation"), "{}", body);
+        assert!(body.contains("event: response.mcp_list_tools.completed"));
+        assert!(body.contains("event: response.mcp_call_arguments.done"));
+        assert!(body.contains("event: response.mcp_call.completed"));
+    }
+}
+
+/// `[mirror]`: requests are copied to a real upstream (here a second llmsim
+/// on a local port) and the simulated response is compared with its answer.
+mod mirror_tests {
+    use crate::common::{chat, get, instant_router, post};
+    use axum::http::StatusCode;
+    use axum::Router;
+    use serde_json::{json, Value};
+    use std::time::Duration;
+
+    /// A real server standing in for the provider, answering with five tokens
+    async fn start_upstream() -> String {
+        let app = instant_router(
+            r#"
+[response]
+generator = "fixed:one two three four five"
+"#,
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        // llmsim serves OpenAI under /openai, where the real API has no prefix
+        format!("http://{}/openai", addr)
+    }
+
+    fn mirrored(base_url: &str) -> Router {
+        instant_router(&format!(
+            r#"
+[response]
+target_tokens = 40
+
+[mirror]
+enabled = true
+
+[upstreams.openai]
+base_url = "{base_url}"
+api_key = "sk-upstream"
+"#
+        ))
+    }
+
+    /// Poll `/llmsim/mirror` until `done` holds for the summary
+    async fn wait_for(router: &Router, done: impl Fn(&Value) -> bool) -> Value {
+        for _ in 0..100 {
+            let (_, summary) = get(router, "/llmsim/mirror").await;
+            if done(&summary) {
+                return summary;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        let (_, summary) = get(router, "/llmsim/mirror").await;
+        panic!("mirror summary never settled: {summary}");
+    }
+
+    #[tokio::test]
+    async fn simulated_and_upstream_usage_are_compared() {
+        let router = mirrored(&start_upstream().await);
+        let (status, body) =
+            post(&router, "/openai/v1/chat/completions", chat("gpt-5", "Hi")).await;
+        assert_eq!(status, StatusCode::OK);
+        let simulated_tokens = body["usage"]["completion_tokens"].as_u64().unwrap();
+
+        let summary = wait_for(&router, |s| s["compared"] == 1).await;
+        assert_eq!(summary["mirrored"], 1);
+        assert_eq!(summary["failed"], 0);
+        let recent = &summary["recent"][0];
+        assert_eq!(recent["path"], "/openai/v1/chat/completions");
+        assert_eq!(recent["model"], "gpt-5");
+        assert_eq!(recent["simulated"]["completion_tokens"], simulated_tokens);
+        assert_eq!(recent["upstream"]["status"], 200);
+        assert_eq!(recent["upstream"]["completion_tokens"], 5);
+        assert_eq!(
+            summary["upstream"]["prompt_tokens"],
+            recent["simulated"]["prompt_tokens"]
+        );
+
+        // Only provider routes with an upstream are mirrored
+        post(&router, "/anthropic/v1/messages", json!({})).await;
+        let (_, summary) = get(&router, "/llmsim/mirror").await;
+        assert_eq!(summary["mirrored"], 1);
+    }
+
+    #[tokio::test]
+    async fn unreachable_upstream_is_counted_not_surfaced() {
+        // Bind and drop a listener to get a port nothing answers on
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+
+        let router = mirrored(&format!("http://{}", addr));
+        let (status, _) = post(&router, "/openai/v1/chat/completions", chat("gpt-5", "Hi")).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let summary = wait_for(&router, |s| s["failed"] == 1).await;
+        assert_eq!(summary["compared"], 0);
+    }
+
+    #[tokio::test]
+    async fn mirror_endpoint_requires_mirroring() {
+        let (status, _) = get(&instant_router(""), "/llmsim/mirror").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+}
+
+/// `/llmsim/openapi.json`: every documented operation is routed, and the
+/// document lists mounted regions as servers.
+mod openapi_tests {
+    use crate::common::{call, instant_router, request};
+    use axum::http::StatusCode;
+    use axum::Router;
+    use serde_json::{json, Value};
+
+    fn router() -> Router {
+        instant_router(
+            r#"
+[regions.eu-west]
+prefix = "/eu"
+"#,
+        )
+    }
+
+    async fn send(router: &Router, method: &str, uri: &str) -> (StatusCode, Vec<u8>) {
+        call(router, request(method, uri, Some(json!({})))).await
+    }
+
+    async fn document(router: &Router) -> Value {
+        let (status, body) = send(router, "GET", "/llmsim/openapi.json").await;
+        assert_eq!(status, StatusCode::OK);
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    #[tokio::test]
+    async fn documented_operations_are_routed() {
+        let router = router();
+        let doc = document(&router).await;
+        let paths = doc["paths"].as_object().unwrap();
+        assert!(paths.len() >= 18);
+
+        for (path, item) in paths {
+            let uri = path
+                .replace("{model_id}", "gpt-5")
+                .replace("{id}", "drill_404");
+            for method in item.as_object().unwrap().keys() {
+                let (status, body) = send(&router, &method.to_uppercase(), &uri).await;
+                assert_ne!(
+                    status,
+                    StatusCode::METHOD_NOT_ALLOWED,
+                    "{} {}",
+                    method,
+                    path
+                );
+                // A handler's 404 has a JSON body; the router's has none
+                assert!(
+                    status != StatusCode::NOT_FOUND || !body.is_empty(),
+                    "{} {} is not routed",
+                    method,
+                    path
+                );
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn regions_are_listed_as_servers() {
+        let router = router();
+        let doc = document(&router).await;
+        assert_eq!(doc["servers"][0]["url"], "/");
+        assert_eq!(doc["servers"][1]["url"], "/eu");
+        assert_eq!(doc["info"]["version"], env!("CARGO_PKG_VERSION"));
+
+        let (status, _) = send(&router, "GET", "/eu/llmsim/openapi.json").await;
+        assert_eq!(status, StatusCode::OK);
+    }
+}
+
+/// The `llmsim` object in a request body overrides the simulator for that
+/// request and never reaches the handlers.
+mod overrides_tests {
+    use crate::common::{instant_router, post, state};
+    use axum::http::StatusCode;
+    use llmsim::cli::build_router;
+    use llmsim::{ErrorCause, SimulatedError};
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn forced_errors_in_each_format() {
+        let state = state("[latency]\nprofile = \"instant\"\n");
+        let decisions = state.decisions.subscribe();
+        let router = build_router(state);
+
+        let body = json!({
+            "model": "gpt-5",
+            "messages": [{"role": "user", "content": "hi"}],
+            "llmsim": {"force_error": "rate_limit"}
+        });
+        let (status, body) = post(&router, "/openai/v1/chat/completions", body).await;
+        assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(body["error"]["type"], "rate_limit_error");
+        let decision = decisions.try_recv().unwrap();
+        assert!(matches!(
+            decision.error,
+            Some((ErrorCause::Requested, SimulatedError::RateLimit { .. }))
+        ));
+
+        let body = json!({
+            "model": "claude-sonnet-4",
+            "max_tokens": 10,
+            "messages": [{"role": "user", "content": "hi"}],
+            "llmsim": {"force_error": "server_error"}
+        });
+        let (status, body) = post(&router, "/anthropic/v1/messages", body).await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(body["type"], "error");
+
+        // Without the extension the same router answers normally
+        let body = json!({"model": "gpt-5", "input": "hi"});
+        let (status, _) = post(&router, "/openai/v1/responses", body).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn delays_are_taken_literally() {
+        let state = state("[latency]\nprofile = \"instant\"\n\n[simulation]\ntime_scale = 0.1\n");
+        let decisions = state.decisions.subscribe();
+        let router = build_router(state);
+
+        let body = json!({
+            "model": "gpt-5",
+            "stream": true,
+            "messages": [{"role": "user", "content": "hi"}],
+            "llmsim": {"ttft_ms": 5000, "tbt_ms": 7}
+        });
+        let (status, _) = post(&router, "/openai/v1/chat/completions", body).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let decision = decisions.try_recv().unwrap();
+        assert_eq!(decision.latency.ttft_mean_ms, 5000);
+        assert_eq!(decision.delays[0].as_millis(), 5000);
+        assert!(decision.delays[1..].iter().all(|d| d.as_millis() == 7));
+    }
+
+    #[tokio::test]
+    async fn extension_is_stripped_before_echo() {
+        let router = instant_router("[response]\ngenerator = \"echo\"\n");
+        let body = json!({
+            "model": "gpt-5",
+            "messages": [{"role": "user", "content": "echo me"}],
+            "llmsim": {"ttft_ms": 0}
+        });
+        let (status, body) = post(&router, "/openai/v1/chat/completions", body).await;
+        assert_eq!(status, StatusCode::OK);
+        let content = body["choices"][0]["message"]["content"].as_str().unwrap();
+        assert!(content.contains("echo me"));
+        assert!(!content.contains("llmsim"));
+    }
+
+    #[tokio::test]
+    async fn invalid_extension_is_a_bad_request() {
+        let router = instant_router("");
+        let body = json!({
+            "model": "gpt-5",
+            "messages": [{"role": "user", "content": "hi"}],
+            "llmsim": {"ttft": 5000}
+        });
+        let (status, body) = post(&router, "/openai/v1/chat/completions", body).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body["error"]["message"]
+            .as_str()
+            .unwrap()
+            .contains("llmsim"));
+    }
+}
+
+/// Stream pacing in `/llmsim/stats`: gaps between chunks are timed against
+/// the delays configured for them.
+mod pacing_tests {
+    use crate::common::{call, config_router, request};
+    use axum::http::StatusCode;
+    use llmsim::cli::Config;
+    use llmsim::stats::new_shared_stats;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn streams_report_their_pacing() {
+        let config = Config::from_toml(
+        "[latency]\nttft_mean_ms = 5\nttft_stddev_ms = 0\ntbt_mean_ms = 2\ntbt_stddev_ms = 0\n\n[response]\ntarget_tokens = 10\n",
+    )
+    .unwrap();
+        let stats = new_shared_stats();
+        let router = config_router(config, stats.clone());
+
+        for stream in [true, false] {
+            let body = json!({
+                "model": "gpt-5",
+                "messages": [{"role": "user", "content": "Hello"}],
+                "stream": stream
+            });
+            let req = request("POST", "/openai/v1/chat/completions", Some(body));
+            let (status, _) = call(&router, req).await;
+            assert_eq!(status, StatusCode::OK);
+        }
+
+        // Only the stream is measured: its first token and each gap after
+        let pacing = stats.snapshot().pacing;
+        assert_eq!(pacing.stream_jitter_us.count, 1);
+        assert!(pacing.gaps >= 10, "{:?}", pacing);
+        // Timers never fire early
+        assert!(pacing.mean_lag_ms >= 0.0, "{:?}", pacing);
+        assert!(pacing.jitter_ms.is_finite());
+    }
+}
+
+/// With `[pool]` enabled, chat completions are served from responses
+/// generated at startup, filled in with each request's id and model.
+mod pool_tests {
+    use crate::common::{instant_router, request};
+    use axum::body::to_bytes;
+    use axum::Router;
+    use serde_json::{json, Value};
+    use tower::ServiceExt;
+
+    fn router() -> Router {
+        instant_router("[pool]\nenabled = true\nsize = 2\n")
+    }
+
+    async fn chat(router: &Router, body: Value) -> (Option<String>, String) {
+        let req = request("POST", "/openai/v1/chat/completions", Some(body));
+        let resp = router.clone().oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), 200);
+        let content_type = resp
+            .headers()
+            .get("content-type")
+            .map(|v| v.to_str().unwrap().to_string());
+        let bytes = to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        (content_type, String::from_utf8(bytes.to_vec()).unwrap())
+    }
+    #[tokio::test]
+    async fn non_streaming_from_the_pool() {
+        let router = router();
+        let body = json!({"model": "gpt-5", "messages": [{"role": "user", "content": "hi"}]});
+
+        let (content_type, first) = chat(&router, body.clone()).await;
+        assert_eq!(content_type.as_deref(), Some("application/json"));
+        let first: Value = serde_json::from_str(&first).unwrap();
+        let (_, second) = chat(&router, body.clone()).await;
+        let second: Value = serde_json::from_str(&second).unwrap();
+        let (_, third) = chat(&router, body).await;
+        let third: Value = serde_json::from_str(&third).unwrap();
+
+        assert_eq!(first["object"], "chat.completion");
+        assert_eq!(first["model"], "gpt-5");
+        assert!(first["id"].as_str().unwrap().starts_with("chatcmpl-"));
+        assert_ne!(first["id"], third["id"]);
+        // Two responses served in turn
+        let content = |v: &Value| v["choices"][0]["message"]["content"].clone();
+        assert_ne!(content(&first), content(&second));
+        assert_eq!(content(&first), content(&third));
+
+        let usage = &first["usage"];
+        assert_eq!(
+            usage["total_tokens"].as_u64().unwrap(),
+            usage["prompt_tokens"].as_u64().unwrap() + usage["completion_tokens"].as_u64().unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn streaming_from_the_pool() {
+        let router = router();
+        let body = json!({
+            "model": "gpt-5-mini",
+            "stream": true,
+            "messages": [{"role": "user", "content": "hi"}]
+        });
+        let (content_type, text) = chat(&router, body.clone()).await;
+        assert!(content_type.unwrap().starts_with("text/event-stream"));
+
+        let events: Vec<&str> = text
+            .split("\n\n")
+            .filter_map(|e| e.strip_prefix("data: "))
+            .collect();
+        assert_eq!(events.last(), Some(&"[DONE]"));
+        let chunks: Vec<Value> = events[..events.len() - 1]
+            .iter()
+            .map(|e| serde_json::from_str(e).unwrap())
+            .collect();
+        let id = &chunks[0]["id"];
+        assert!(chunks
+            .iter()
+            .all(|c| c["id"] == *id && c["model"] == "gpt-5-mini"));
+        assert_eq!(chunks[0]["choices"][0]["delta"]["role"], "assistant");
+
+        let streamed: String = chunks
+            .iter()
+            .filter_map(|c| c["choices"][0]["delta"]["content"].as_str())
+            .collect();
+        let last = chunks.last().unwrap();
+        assert_eq!(last["choices"][0]["finish_reason"], "stop");
+
+        // The same pooled response, non-streamed, has the same text and usage
+        let (_, other) = chat(&router, body).await;
+        assert!(other.ends_with("data: [DONE]\n\n"));
+        let (_, plain) = chat(
+            &router,
+            json!({"model": "gpt-5-mini", "messages": [{"role": "user", "content": "hi"}]}),
+        )
+        .await;
+        let plain: Value = serde_json::from_str(&plain).unwrap();
+        assert_eq!(plain["choices"][0]["message"]["content"], streamed);
+        assert_eq!(plain["usage"], last["usage"]);
+    }
+
+    #[tokio::test]
+    async fn json_mode_bypasses_the_pool() {
+        let router = router();
+        let (_, text) = chat(
+            &router,
+            json!({
+                "model": "gpt-5",
+                "response_format": {"type": "json_object"},
+                "messages": [{"role": "user", "content": "hi"}]
+            }),
+        )
+        .await;
+        let body: Value = serde_json::from_str(&text).unwrap();
+        let content = body["choices"][0]["message"]["content"].as_str().unwrap();
+        assert!(serde_json::from_str::<Value>(content).unwrap()["response"].is_string());
+    }
+}
+
+/// `[[proxy.rules]]`: matching requests are answered by a real upstream
+/// (here a second llmsim on a local port), everything else is simulated.
+mod proxy_tests {
+    use crate::common::{instant_router, request};
+    use axum::body::to_bytes;
+    use axum::http::StatusCode;
+    use axum::Router;
+    use serde_json::{json, Value};
+    use tower::ServiceExt;
+
+    const UPSTREAM_TEXT: &str = "one two three four five";
+
+    /// A real server standing in for the provider
+    async fn start_upstream() -> String {
+        let app = instant_router(&format!(
+            "[response]\ngenerator = \"fixed:{UPSTREAM_TEXT}\"\n"
+        ));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        format!("http://{}/openai", addr)
+    }
+
+    fn hybrid(base_url: &str) -> Router {
+        instant_router(&format!(
+            r#"
+[upstreams.openai]
+base_url = "{base_url}"
+
+[[proxy.rules]]
+model = "gpt-4*"
+
+[[proxy.rules]]
+header = "x-llmsim-proxy"
+"#
+        ))
+    }
+
+    async fn send(
+        router: &Router,
+        method: &str,
+        uri: &str,
+        body: Option<Value>,
+        header: Option<(&'static str, &str)>,
+    ) -> (StatusCode, Option<String>, String) {
+        let mut req = request(method, uri, body);
+        if let Some((name, value)) = header {
+            req.headers_mut().insert(name, value.parse().unwrap());
+        }
+        let resp = router.clone().oneshot(req).await.unwrap();
+        let status = resp.status();
+        let mode = resp
+            .headers()
+            .get("x-llmsim-mode")
+            .map(|v| v.to_str().unwrap().to_string());
+        let bytes = to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        (status, mode, String::from_utf8(bytes.to_vec()).unwrap())
+    }
+    fn chat(model: &str, stream: bool) -> Option<Value> {
+        Some(json!({
+            "model": model,
+            "stream": stream,
+            "messages": [{"role": "user", "content": "hi"}]
+        }))
+    }
+
+    const CHAT: &str = "/openai/v1/chat/completions";
+
+    #[tokio::test]
+    async fn rules_split_traffic_between_modes() {
+        let router = hybrid(&start_upstream().await);
+
+        let (status, mode, body) = send(&router, "POST", CHAT, chat("gpt-4o", false), None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(mode.as_deref(), Some("proxied"));
+        let body: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(body["choices"][0]["message"]["content"], UPSTREAM_TEXT);
+
+        let (status, mode, body) = send(&router, "POST", CHAT, chat("gpt-5", false), None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(mode, None);
+        assert!(!body.contains(UPSTREAM_TEXT));
+
+        let header = Some(("x-llmsim-proxy", "1"));
+        let (_, mode, _) = send(&router, "POST", CHAT, chat("gpt-5", false), header).await;
+        assert_eq!(mode.as_deref(), Some("proxied"));
+
+        // Streams are relayed event by event
+        let (status, mode, body) = send(&router, "POST", CHAT, chat("gpt-4o", true), None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(mode.as_deref(), Some("proxied"));
+        assert!(body.contains("data: "));
+        assert!(body.trim_end().ends_with("data: [DONE]"));
+
+        let (status, _, summary) = send(&router, "GET", "/llmsim/proxy", None, None).await;
+        assert_eq!(status, StatusCode::OK);
+        let summary: Value = serde_json::from_str(&summary).unwrap();
+        assert_eq!(summary["proxied"]["total_requests"], 3);
+        assert_eq!(summary["proxied"]["completions_requests"], 3);
+        assert_eq!(summary["proxied"]["completion_tokens"], 3 * 5);
+        assert_eq!(summary["proxied"]["active_requests"], 0);
+        assert_eq!(summary["simulated"]["total_requests"], 1);
+        assert_eq!(summary["rules"][0]["matched"], 2);
+        assert_eq!(summary["rules"][1]["matched"], 1);
+    }
+
+    #[tokio::test]
+    async fn unreachable_upstream_is_a_bad_gateway() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+
+        let router = hybrid(&format!("http://{}", addr));
+        let (status, _, body) = send(&router, "POST", CHAT, chat("gpt-4o", false), None).await;
+        assert_eq!(status, StatusCode::BAD_GATEWAY);
+        let body: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(body["error"]["type"], "upstream_error");
+
+        let (_, _, summary) = send(&router, "GET", "/llmsim/proxy", None, None).await;
+        let summary: Value = serde_json::from_str(&summary).unwrap();
+        assert_eq!(summary["proxied"]["total_errors"], 1);
+    }
+}
+
+/// `[rate_limits]` give every generation response coherent rate limit
+/// headers per API key, refuse a key once its budget is spent, and choose the
+/// `Retry-After` of every 429.
+mod rate_limits_tests {
+    use crate::common::{instant_router, request, with_key};
+    use axum::body::to_bytes;
+    use axum::http::{HeaderMap, StatusCode};
+    use axum::Router;
+    use llmsim::cli::Config;
+    use serde_json::json;
+    use std::time::Duration;
+    use tower::ServiceExt;
+
+    async fn chat(router: &Router, key: &str) -> (StatusCode, HeaderMap) {
+        let body = json!({
+            "model": "gpt-5",
+            "max_tokens": 10,
+            "messages": [{"role": "user", "content": "hi"}]
+        });
+        let req = with_key(
+            request("POST", "/openai/v1/chat/completions", Some(body)),
+            key,
+        );
+        let resp = router.clone().oneshot(req).await.unwrap();
+        let status = resp.status();
+        let headers = resp.headers().clone();
+        to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        (status, headers)
+    }
+
+    fn header<'a>(headers: &'a HeaderMap, name: &str) -> &'a str {
+        headers.get(name).unwrap().to_str().unwrap()
+    }
+    #[tokio::test(start_paused = true)]
+    async fn headers_count_down_and_recover() {
+        let router =
+            instant_router("[rate_limits]\nrequests_per_minute = 3\ntokens_per_minute = 1000\n");
+
+        let (status, headers) = chat(&router, "sk-a").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(header(&headers, "x-ratelimit-limit-requests"), "3");
+        assert_eq!(header(&headers, "x-ratelimit-remaining-requests"), "2");
+        assert_eq!(header(&headers, "x-ratelimit-reset-requests"), "20s");
+        assert_eq!(header(&headers, "x-ratelimit-limit-tokens"), "1000");
+        let tokens: u32 = header(&headers, "x-ratelimit-remaining-tokens")
+            .parse()
+            .unwrap();
+        assert!(tokens < 990);
+
+        chat(&router, "sk-a").await;
+        chat(&router, "sk-a").await;
+        let (status, headers) = chat(&router, "sk-a").await;
+        assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(header(&headers, "x-ratelimit-remaining-requests"), "0");
+        assert_eq!(header(&headers, "x-ratelimit-reset-requests"), "1m0s");
+        assert_eq!(header(&headers, "retry-after"), "20");
+
+        // Another key is unaffected
+        let (status, _) = chat(&router, "sk-b").await;
+        assert_eq!(status, StatusCode::OK);
+
+        tokio::time::advance(Duration::from_secs(20)).await;
+        let (status, headers) = chat(&router, "sk-a").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(header(&headers, "x-ratelimit-remaining-requests"), "0");
+    }
+
+    #[tokio::test]
+    async fn anthropic_resets_are_timestamps() {
+        let router = instant_router("[rate_limits]\nrequests_per_minute = 50\n");
+        let body = json!({
+            "model": "claude-sonnet-4",
+            "max_tokens": 10,
+            "messages": [{"role": "user", "content": "hi"}]
+        });
+        let mut req = request("POST", "/anthropic/v1/messages", Some(body));
+        req.headers_mut()
+            .insert("x-api-key", "sk-ant".parse().unwrap());
+        let resp = router.oneshot(req).await.unwrap();
+        let headers = resp.headers();
+        assert_eq!(header(headers, "anthropic-ratelimit-requests-limit"), "50");
+        assert_eq!(
+            header(headers, "anthropic-ratelimit-requests-remaining"),
+            "49"
+        );
+        let reset = header(headers, "anthropic-ratelimit-requests-reset");
+        assert!(reset.ends_with('Z') && reset.contains('T'), "{}", reset);
+        assert!(headers.get("x-ratelimit-limit-requests").is_none());
+    }
+
+    #[tokio::test]
+    async fn retry_after_grows_with_consecutive_429s() {
+        let router = instant_router(
+        "[errors]\nrate_limit_rate = 1.0\n\n[rate_limits]\nretry_after = \"exponential\"\nretry_after_secs = 2\nmax_retry_after_secs = 10\n",
+    );
+        let mut waits = Vec::new();
+        for _ in 0..5 {
+            let (status, headers) = chat(&router, "sk-a").await;
+            assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+            assert!(headers.get("x-ratelimit-limit-requests").is_none());
+            waits.push(header(&headers, "retry-after").to_string());
+        }
+        assert_eq!(waits, ["2", "4", "8", "10", "10"]);
+
+        let (_, headers) = chat(&router, "sk-b").await;
+        assert_eq!(header(&headers, "retry-after"), "2");
+    }
+
+    #[test]
+    fn bad_jitter_is_a_config_error() {
+        let err = Config::from_toml("[rate_limits]\nretry_after_jitter = 1.5\n").unwrap_err();
+        assert!(err.to_string().contains("retry_after_jitter"));
+    }
+}
+
+/// `[regions]`: logical regions mounted next to the main routes, each with
+/// its own latency, error rates, clock and stats.
+mod regions_tests {
+    use crate::common::{get, instant_router, post};
+    use axum::http::StatusCode;
+    use axum::Router;
+    use serde_json::{json, Value};
+
+    fn router() -> Router {
+        instant_router(
+            r#"
+[regions.us-east]
+clock = { skew_secs = -3600 }
+
+[regions.eu-west]
+prefix = "/eu"
+errors = { rate_limit_rate = 1.0 }
+
+[regions.ap-south]
+port = 9001
+"#,
+        )
+    }
+
+    fn chat() -> Value {
+        json!({"model": "gpt-5", "messages": [{"role": "user", "content": "hi"}]})
+    }
+
+    #[tokio::test]
+    async fn regions_behave_independently() {
+        let router = router();
+        let (status, us_east) = post(&router, "/us-east/openai/v1/chat/completions", chat()).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, eu) = post(&router, "/eu/openai/v1/chat/completions", chat()).await;
+        assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(eu["error"]["type"], "rate_limit_error");
+        let (status, main) = post(&router, "/openai/v1/chat/completions", chat()).await;
+        assert_eq!(status, StatusCode::OK);
+        // Only us-east runs an hour behind
+        let behind = main["created"].as_i64().unwrap() - us_east["created"].as_i64().unwrap();
+        assert!((3598..=3602).contains(&behind), "{behind}");
+
+        let (_, main) = get(&router, "/llmsim/stats").await;
+        assert_eq!(main["total_requests"], 1);
+        let (_, eu) = get(&router, "/eu/llmsim/stats").await;
+        assert_eq!(eu["total_requests"], 1);
+        assert_eq!(eu["total_errors"], 1);
+    }
+
+    #[tokio::test]
+    async fn regions_are_listed() {
+        let router = router();
+        post(&router, "/us-east/openai/v1/chat/completions", chat()).await;
+
+        let (_, body) = get(&router, "/llmsim/regions").await;
+        let regions = body["regions"].as_array().unwrap();
+        assert_eq!(regions.len(), 3);
+        assert_eq!(regions[0]["name"], "ap-south");
+        assert_eq!(regions[0]["port"], 9001);
+        assert_eq!(regions[0]["prefix"], Value::Null);
+        assert_eq!(regions[1]["prefix"], "/eu");
+        assert_eq!(regions[2]["name"], "us-east");
+        assert_eq!(regions[2]["stats"]["total_requests"], 1);
+    }
+}
+
+/// Responses the simulator creates are stored, listed at
+/// `GET /openai/v1/responses` with cursor pagination, and retrievable by id
+/// along with their input items.
+mod responses_store_tests {
+    use crate::common::{call, config_router, get, instant_router, request};
+    use axum::http::StatusCode;
+    use axum::Router;
+    use futures_util::StreamExt;
+    use llmsim::cli::Config;
+    use llmsim::stats::new_shared_stats;
+    use serde_json::{json, Value};
+    use tower::ServiceExt;
+
+    fn router() -> Router {
+        instant_router("")
+    }
+
+    async fn create(router: &Router, body: Value) -> Vec<u8> {
+        let (status, body) =
+            call(router, request("POST", "/openai/v1/responses", Some(body))).await;
+        assert_eq!(status, StatusCode::OK);
+        body
+    }
+
+    fn ids(page: &Value) -> Vec<String> {
+        page["data"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|r| r["id"].as_str().unwrap().to_string())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn list_pages_newest_first() {
+        let router = router();
+        let mut created = Vec::new();
+        for i in 0..5 {
+            let body = create(
+                &router,
+                json!({"model": "gpt-5", "input": format!("hi {}", i)}),
+            )
+            .await;
+            let response: Value = serde_json::from_slice(&body).unwrap();
+            created.push(response["id"].as_str().unwrap().to_string());
+        }
+        // Not kept
+        create(
+            &router,
+            json!({"model": "gpt-5", "input": "secret", "store": false}),
+        )
+        .await;
+
+        let (status, page) = get(&router, "/openai/v1/responses?limit=2").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(page["object"], "list");
+        assert_eq!(ids(&page), [created[4].as_str(), created[3].as_str()]);
+        assert_eq!(page["has_more"], true);
+
+        let mut seen = ids(&page);
+        let mut cursor = page["last_id"].as_str().unwrap().to_string();
+        loop {
+            let uri = format!("/openai/v1/responses?limit=2&after={}", cursor);
+            let (_, page) = get(&router, &uri).await;
+            seen.extend(ids(&page));
+            if page["has_more"] == false {
+                break;
+            }
+            cursor = page["last_id"].as_str().unwrap().to_string();
+        }
+        created.reverse();
+        assert_eq!(seen, created);
+
+        let uri = format!("/openai/v1/responses?order=asc&before={}", created[0]);
+        let (_, page) = get(&router, &uri).await;
+        assert_eq!(page["data"].as_array().unwrap().len(), 4);
+        assert_eq!(page["first_id"], created[4].as_str());
+    }
+
+    #[tokio::test]
+    async fn streamed_response_is_retrievable() {
+        let router = router();
+        let body = create(
+            &router,
+            json!({"model": "gpt-5", "input": "hello", "stream": true}),
+        )
+        .await;
+        let text = String::from_utf8(body).unwrap();
+        let completed = text
+            .lines()
+            .filter_map(|line| line.strip_prefix("data: "))
+            .filter_map(|data| serde_json::from_str::<Value>(data).ok())
+            .find(|event| event["type"] == "response.completed")
+            .unwrap();
+        let id = completed["response"]["id"].as_str().unwrap();
+
+        let (status, stored) = get(&router, &format!("/openai/v1/responses/{}", id)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(stored, completed["response"]);
+
+        let (status, _) = get(&router, "/openai/v1/responses/resp_missing").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn bad_pagination_is_rejected() {
+        let router = router();
+        let (status, body) = get(&router, "/openai/v1/responses?limit=500").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body["error"]["message"].as_str().unwrap().contains("limit"));
+
+        let (status, _) = get(&router, "/openai/v1/responses?after=resp_missing").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn input_items_are_listed() {
+        let router = router();
+        let body = create(
+            &router,
+            json!({
+                "model": "gpt-5",
+                "input": [
+                    {"role": "user", "content": "What is 2+2?"},
+                    {"role": "assistant", "content": "4"},
+                    {"role": "user", "content": [{"type": "input_text", "text": "And 3+3?"}]}
+                ]
+            }),
+        )
+        .await;
+        let response: Value = serde_json::from_slice(&body).unwrap();
+        let uri = format!(
+            "/openai/v1/responses/{}/input_items",
+            response["id"].as_str().unwrap()
+        );
+
+        let (status, page) = get(&router, &format!("{}?order=asc&limit=2", uri)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(page["object"], "list");
+        assert_eq!(page["has_more"], true);
+        assert_eq!(page["data"][0]["type"], "message");
+        assert_eq!(page["data"][0]["role"], "user");
+        assert_eq!(
+            page["data"][0]["content"],
+            json!([{"type": "input_text", "text": "What is 2+2?"}])
+        );
+        assert_eq!(page["data"][1]["content"][0]["type"], "output_text");
+
+        let next = format!(
+            "{}?order=asc&after={}",
+            uri,
+            page["last_id"].as_str().unwrap()
+        );
+        let (_, page) = get(&router, &next).await;
+        assert_eq!(page["has_more"], false);
+        assert_eq!(page["data"][0]["content"][0]["text"], "And 3+3?");
+
+        // Newest first by default
+        let (_, page) = get(&router, &uri).await;
+        assert_eq!(page["data"][0]["content"][0]["text"], "And 3+3?");
+
+        let (status, _) = get(&router, "/openai/v1/responses/resp_missing/input_items").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    /// `(type, sequence_number)` of each event in an SSE body
+    fn events(body: &[u8]) -> Vec<(String, u64)> {
+        String::from_utf8(body.to_vec())
+            .unwrap()
+            .lines()
+            .filter_map(|line| line.strip_prefix("data: "))
+            .map(|data| {
+                let event: Value = serde_json::from_str(data).unwrap();
+                let kind = event["type"].as_str().unwrap().to_string();
+                (kind, event["sequence_number"].as_u64().unwrap())
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn background_streams_resume_after_a_sequence_number() {
+        // Slow enough that the client leaves mid-stream
+        let config = Config::from_toml(
+        "[latency]\nttft_mean_ms = 20\nttft_stddev_ms = 0\ntbt_mean_ms = 5\ntbt_stddev_ms = 0\n",
+    )
+    .unwrap();
+        let router = config_router(config, new_shared_stats());
+        let body = json!({"model": "gpt-5", "input": "hi", "stream": true, "background": true});
+        let resp = router
+            .clone()
+            .oneshot(request("POST", "/openai/v1/responses", Some(body)))
+            .await
+            .unwrap();
+        let mut frames = resp.into_body().into_data_stream();
+        let first = frames.next().await.unwrap().unwrap();
+        drop(frames);
+        let created: Value = serde_json::from_str(
+            String::from_utf8(first.to_vec())
+                .unwrap()
+                .lines()
+                .find_map(|line| line.strip_prefix("data: "))
+                .unwrap(),
+        )
+        .unwrap();
+        let id = created["response"]["id"].as_str().unwrap();
+
+        // Generation carries on without the client
+        let uri = format!("/openai/v1/responses/{}?stream=true&starting_after=2", id);
+        let (status, body) = call(&router, request("GET", &uri, None)).await;
+        assert_eq!(status, StatusCode::OK);
+        let resumed = events(&body);
+        assert_eq!(resumed[0].1, 3);
+        assert!(resumed.windows(2).all(|pair| pair[1].1 == pair[0].1 + 1));
+        assert_eq!(resumed.last().unwrap().0, "response.completed");
+
+        // From the start without starting_after
+        let uri = format!("/openai/v1/responses/{}?stream=true", id);
+        let (_, body) = call(&router, request("GET", &uri, None)).await;
+        let all = events(&body);
+        assert_eq!(all[0], ("response.created".to_string(), 0));
+        assert_eq!(all[3..], resumed[..]);
+
+        let (status, response) = get(&router, &format!("/openai/v1/responses/{}", id)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(response["status"], "completed");
+    }
+
+    #[tokio::test]
+    async fn only_background_responses_stream() {
+        let router = router();
+        let body = create(&router, json!({"model": "gpt-5", "input": "hi"})).await;
+        let response: Value = serde_json::from_slice(&body).unwrap();
+        let uri = format!(
+            "/openai/v1/responses/{}?stream=true",
+            response["id"].as_str().unwrap()
+        );
+        let (status, body) = get(&router, &uri).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body["error"]["message"]
+            .as_str()
+            .unwrap()
+            .contains("background=true"));
+
+        let (status, _) = get(&router, "/openai/v1/responses/resp_missing?stream=true").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, _) = get(&router, &format!("{}&starting_after=x", uri)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+}
+
+/// `/llmsim/stats` reports the scheduler figures of the runtime serving it.
+mod runtime_tests {
+    use crate::common::{config_router, get};
+    use llmsim::cli::Config;
+    use llmsim::stats::new_shared_stats;
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn stats_report_runtime_workers() {
+        let router = config_router(Config::default(), new_shared_stats());
+        let (_, stats) = get(&router, "/llmsim/stats").await;
+
+        let runtime = &stats["runtime"];
+        assert_eq!(runtime["workers"], 2);
+        assert!(runtime["alive_tasks"].is_u64());
+        assert!(runtime["global_queue_depth"].is_u64());
+        let workers = runtime["worker_metrics"].as_array().unwrap();
+        assert_eq!(workers.len(), 2);
+        assert!(workers[0]["busy_ms"].as_f64().unwrap() >= 0.0);
+        assert!(workers[0].get("local_queue_depth").is_none() || cfg!(tokio_unstable));
+    }
+}
+
+/// `[stream] strict_schema`: every emitted payload must match its bundled
+/// schema, or the stream ends in a `llmsim_schema_violation` error.
+mod schema_tests {
+    use crate::common::{call, config_router, request};
+    use llmsim::cli::Config;
+    use llmsim::stats::new_shared_stats;
+    use serde_json::{json, Value};
+
+    fn strict() -> Config {
+        let mut config = Config::default();
+        config.latency.profile = Some("instant".to_string());
+        config.stream.strict_schema = true;
+        config
+    }
+
+    async fn stream(config: Config, uri: &str, body: Value) -> String {
+        let router = config_router(config, new_shared_stats());
+        let (_, bytes) = call(&router, request("POST", uri, Some(body))).await;
+        String::from_utf8(bytes).unwrap()
+    }
+
+    fn assert_conforms(body: &str) {
+        assert!(
+            !body.contains("llmsim_schema_violation"),
+            "schema violation in stream:\n{}",
+            body
+        );
+    }
+
+    #[tokio::test]
+    async fn chat_chunks_conform() {
+        let body = stream(
+            strict(),
+            "/openai/v1/chat/completions",
+            json!({
+                "model": "gpt-5",
+                "messages": [{"role": "user", "content": "hi"}],
+                "stream": true,
+                "stream_options": {"include_usage": true}
+            }),
+        )
+        .await;
+        assert_conforms(&body);
+        assert!(body.ends_with("data: [DONE]\n\n"));
+    }
+
+    #[tokio::test]
+    async fn chat_tool_call_chunks_conform() {
+        let mut config = strict();
+        config.tools.call_rate = 1.0;
+        config.tools.parallel_weights = vec![0.0, 1.0];
+        let body = stream(
+            config,
+            "/openai/v1/chat/completions",
+            json!({
+                "model": "gpt-5",
+                "messages": [{"role": "user", "content": "look it up"}],
+                "tools": [{"type": "function", "function": {"name": "search"}}],
+                "stream": true
+            }),
+        )
+        .await;
+        assert_conforms(&body);
+        assert!(body.contains("\"tool_calls\""));
+    }
+
+    #[tokio::test]
+    async fn responses_events_conform() {
+        let body = stream(
+            strict(),
+            "/openai/v1/responses",
+            json!({
+                "model": "o3",
+                "input": "hi",
+                "reasoning": {"effort": "low", "summary": "auto"},
+                "stream": true
+            }),
+        )
+        .await;
+        assert_conforms(&body);
+        assert!(body.contains("event: response.reasoning_summary_text.delta"));
+        assert!(body.contains("\"obfuscation\""));
+        assert!(body.contains("event: response.completed"));
+    }
+
+    #[tokio::test]
+    async fn truncated_responses_events_conform() {
+        let body = stream(
+            strict(),
+            "/openai/v1/responses",
+            json!({"model": "gpt-5", "input": "hi", "max_output_tokens": 5, "stream": true}),
+        )
+        .await;
+        assert_conforms(&body);
+        assert!(body.contains("event: response.incomplete"));
+    }
+
+    #[tokio::test]
+    async fn obfuscation_can_be_disabled() {
+        let body = stream(
+            strict(),
+            "/openai/v1/responses",
+            json!({
+                "model": "gpt-5",
+                "input": "hi",
+                "stream": true,
+                "stream_options": {"include_obfuscation": false}
+            }),
+        )
+        .await;
+        assert_conforms(&body);
+        assert!(body.contains("event: response.output_text.delta"));
+        assert!(!body.contains("\"obfuscation\""));
+    }
+
+    #[tokio::test]
+    async fn refusal_responses_events_conform() {
+        let mut config = strict();
+        config.response.refusal_rate = 1.0;
+        let body = stream(
+            config,
+            "/openai/v1/responses",
+            json!({"model": "gpt-5", "input": "hi", "stream": true}),
+        )
+        .await;
+        assert_conforms(&body);
+        assert!(body.contains("event: response.refusal.delta"));
+        assert!(body.contains("event: response.refusal.done"));
+        assert!(!body.contains("event: response.output_text.delta"));
+        assert!(body.contains("\"type\":\"refusal\""));
+    }
+
+    #[tokio::test]
+    async fn cited_responses_events_conform() {
+        let mut config = strict();
+        config.response.citation_rate = 0.5;
+        let body = stream(
+            config,
+            "/openai/v1/responses",
+            json!({"model": "gpt-5", "input": "hi", "stream": true}),
+        )
+        .await;
+        assert_conforms(&body);
+        assert!(body.contains("event: response.output_text.annotation.added"));
+        assert!(
+            body.contains("\"type\":\"url_citation\"")
+                || body.contains("\"type\":\"file_citation\"")
+        );
+    }
+}
+
+/// Reproducibility of seeded chat completions.
+mod seed_tests {
+    use crate::common::{chat, instant_router, post_ok};
+    use axum::Router;
+    use serde_json::{json, Value};
+
+    async fn complete(router: &Router, seed: Option<i64>) -> Value {
+        let mut body = chat("gpt-5", "tell me something");
+        if let Some(seed) = seed {
+            body["seed"] = json!(seed);
+        }
+        post_ok(router, "/openai/v1/chat/completions", body).await
+    }
+
+    #[tokio::test]
+    async fn same_seed_reproduces_response() {
+        let router = instant_router("");
+
+        let first = complete(&router, Some(7)).await;
+        let second = complete(&router, Some(7)).await;
+        assert_eq!(
+            first["choices"][0]["message"]["content"],
+            second["choices"][0]["message"]["content"]
+        );
+        assert_eq!(first["system_fingerprint"], second["system_fingerprint"]);
+
+        let other = complete(&router, Some(8)).await;
+        assert_ne!(
+            first["choices"][0]["message"]["content"],
+            other["choices"][0]["message"]["content"]
+        );
+    }
+}
+
+/// `[simulation] disable_delays` (`--no-latency`): no simulated delay
+/// anywhere, whatever the latency config says. `time_scale` keeps the
+/// delays but shrinks them.
+///
+/// Requests run on a paused tokio clock, so the time they take is the
+/// simulated delay alone, not how fast the test machine is.
+mod simulation_tests {
+    use crate::common::{call, config_router, request};
+    use llmsim::cli::Config;
+    use llmsim::stats::new_shared_stats;
+    use serde_json::{json, Value};
+    use std::time::Duration;
+    use tokio::time::Instant;
+
+    /// Simulated time a request takes, on the paused clock
+    async fn timed_post(config: Config, uri: &str, body: Value) -> Duration {
+        let router = config_router(config, new_shared_stats());
+        let started = Instant::now();
+        let (status, _) = call(&router, request("POST", uri, Some(body))).await;
+        assert!(status.is_success());
+        started.elapsed()
+    }
+
+    /// A config that would be slow: a named profile and long responses
+    fn slow_config() -> Config {
+        let mut config = Config::default();
+        config.latency.profile = Some("claude-opus".to_string());
+        config.response.target_tokens = 200;
+        config.simulation.disable_delays = true;
+        config
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn streams_run_without_delays() {
+        let elapsed = timed_post(
+        slow_config(),
+        "/openai/v1/chat/completions",
+        json!({"model": "gpt-5", "messages": [{"role": "user", "content": "hi"}], "stream": true}),
+    )
+    .await;
+        assert_eq!(elapsed, Duration::ZERO);
+
+        let elapsed = timed_post(
+            slow_config(),
+            "/anthropic/v1/messages",
+            json!({
+                "model": "claude-opus-4",
+                "max_tokens": 100,
+                "messages": [{"role": "user", "content": "hi"}],
+                "stream": true
+            }),
+        )
+        .await;
+        assert_eq!(elapsed, Duration::ZERO);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn model_derived_latency_is_disabled_too() {
+        let mut config = Config::default();
+        config.simulation.disable_delays = true;
+        let elapsed = timed_post(
+            config.clone(),
+            "/openai/v1/responses",
+            json!({"model": "o3", "input": "hi", "stream": true}),
+        )
+        .await;
+        assert_eq!(elapsed, Duration::ZERO);
+
+        let elapsed = timed_post(
+            config,
+            "/openai/v1/images/generations",
+            json!({"model": "gpt-image-1", "prompt": "a cat", "quality": "high"}),
+        )
+        .await;
+        assert_eq!(elapsed, Duration::ZERO);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn time_scale_shrinks_delays() {
+        let mut config = Config::default();
+        config.latency.ttft_mean_ms = Some(2000);
+        config.latency.ttft_stddev_ms = Some(0);
+        config.latency.tbt_mean_ms = Some(0);
+        config.simulation.time_scale = 0.1;
+        let elapsed = timed_post(
+        config,
+        "/openai/v1/chat/completions",
+        json!({"model": "gpt-5", "messages": [{"role": "user", "content": "hi"}], "stream": true}),
+    )
+    .await;
+        // 200ms of scaled TTFT: still delayed, nowhere near the full two seconds
+        assert_eq!(elapsed, Duration::from_millis(200));
+    }
+}
+
+/// SSE transport behavior: how events are framed in the HTTP body.
+///
+/// Collects the raw body frames from the in-process router, since framing is
+/// invisible once a body has been buffered.
+mod sse_transport_tests {
+    use crate::common::{call, config_router, request};
+    use futures_util::StreamExt;
+    use llmsim::cli::Config;
+    use llmsim::stats::new_shared_stats;
+    use serde_json::json;
+    use tower::ServiceExt;
+
+    fn config() -> Config {
+        let mut config = Config::default();
+        config.latency.profile = Some("instant".to_string());
+        config.response.generator = "fixed:héllo wörld 👋".to_string();
+        config
+    }
+
+    async fn stream_frames(config: Config) -> Vec<Vec<u8>> {
+        let router = config_router(config, new_shared_stats());
+        let body = json!({
+            "model": "gpt-5",
+            "messages": [{"role": "user", "content": "hi"}],
+            "stream": true
+        });
+        let req = request("POST", "/openai/v1/chat/completions", Some(body));
+        let resp = router.oneshot(req).await.unwrap();
+        resp.into_body()
+            .into_data_stream()
+            .map(|frame| frame.unwrap().to_vec())
+            .collect()
+            .await
+    }
+
+    #[tokio::test]
+    async fn utf8_boundaries_preserved_by_default() {
+        let frames = stream_frames(config()).await;
+        assert!(frames.iter().all(|f| std::str::from_utf8(f).is_ok()));
+    }
+
+    #[tokio::test]
+    async fn utf8_split_cuts_characters_across_frames() {
+        let mut config = config();
+        config.stream.utf8_boundaries = llmsim::cli::Utf8Boundaries::Split;
+        let frames = stream_frames(config).await;
+
+        assert!(frames.iter().any(|f| std::str::from_utf8(f).is_err()));
+
+        // Reassembled, the stream is intact
+        let text = String::from_utf8(frames.concat()).unwrap();
+        let content: String = text
+            .lines()
+            .filter_map(|l| l.strip_prefix("data: "))
+            .filter(|d| *d != "[DONE]")
+            .map(|d| serde_json::from_str::<serde_json::Value>(d).unwrap())
+            .filter_map(|v| {
+                v["choices"][0]["delta"]["content"]
+                    .as_str()
+                    .map(String::from)
+            })
+            .collect();
+        assert_eq!(content, "héllo wörld 👋");
+    }
+
+    #[tokio::test]
+    async fn buffer_bytes_coalesces_stream_into_one_flush() {
+        let mut config = config();
+        config.stream.buffer_bytes = Some(4096);
+        let frames = stream_frames(config).await;
+
+        assert_eq!(frames.len(), 1);
+        let text = String::from_utf8(frames.concat()).unwrap();
+        assert!(text.starts_with("data: "));
+        assert!(text.ends_with("data: [DONE]\n\n"));
+    }
+
+    #[tokio::test]
+    async fn buffer_interval_flushes_periodically() {
+        let mut config = config();
+        config.latency.profile = None;
+        config.latency.ttft_mean_ms = Some(0);
+        config.latency.ttft_stddev_ms = Some(0);
+        config.latency.tbt_mean_ms = Some(20);
+        config.latency.tbt_stddev_ms = Some(0);
+        config.response.generator = "fixed:one two three four five six seven eight nine ten eleven twelve thirteen fourteen fifteen sixteen".to_string();
+        let unbuffered = stream_frames(config.clone()).await.len();
+
+        config.stream.buffer_interval_ms = Some(100);
+        let frames = stream_frames(config).await;
+
+        // ~350ms of tokens arrive in a handful of flushes rather than one per event
+        assert!(frames.len() > 1, "got {} frames", frames.len());
+        assert!(frames.len() * 3 < unbuffered, "got {} frames", frames.len());
+    }
+
+    #[tokio::test]
+    async fn every_chunk_carries_the_configured_fingerprint() {
+        let mut config = config();
+        config
+            .fingerprint
+            .models
+            .insert("gpt-5".to_string(), "fp_rotated".to_string());
+        let frames = stream_frames(config).await;
+
+        let text = String::from_utf8(frames.concat()).unwrap();
+        let chunks: Vec<serde_json::Value> = text
+            .lines()
+            .filter_map(|l| l.strip_prefix("data: "))
+            .filter(|d| *d != "[DONE]")
+            .map(|d| serde_json::from_str(d).unwrap())
+            .collect();
+        assert!(chunks.len() > 2);
+        assert!(chunks
+            .iter()
+            .all(|c| c["system_fingerprint"] == "fp_rotated"));
+    }
+
+    #[tokio::test]
+    async fn malformed_frames_break_the_stream_as_configured() {
+        use llmsim::cli::MalformedFrame;
+
+        let mut config = config();
+        config.stream.malformed_rate = 1.0;
+        config.stream.malformed_kinds = vec![MalformedFrame::SplitPrefix];
+        let frames = stream_frames(config.clone()).await;
+        assert!(frames.iter().any(|f| f == b"da"));
+        // Reassembled, the events are intact
+        let text = String::from_utf8(frames.concat()).unwrap();
+        assert!(text.starts_with("data: {"));
+        assert!(text.ends_with("data: [DONE]\n\n"));
+
+        config.stream.malformed_kinds = vec![MalformedFrame::DuplicateDone];
+        let text = String::from_utf8(stream_frames(config.clone()).await.concat()).unwrap();
+        assert!(text.ends_with("data: [DONE]\n\ndata: [DONE]\n\n"));
+
+        config.stream.malformed_kinds = vec![MalformedFrame::MissingSeparator];
+        let text = String::from_utf8(stream_frames(config.clone()).await.concat()).unwrap();
+        // Each frame's first event runs into the next
+        assert!(text.matches("\n\n").count() < text.matches("data: ").count());
+
+        config.stream.malformed_kinds = vec![MalformedFrame::StrayComment];
+        let text = String::from_utf8(stream_frames(config).await.concat()).unwrap();
+        assert!(text.starts_with(": llmsim\ndata: "));
+    }
+
+    fn contents(frames: &[Vec<u8>]) -> Vec<String> {
+        String::from_utf8(frames.concat())
+            .unwrap()
+            .lines()
+            .filter_map(|l| l.strip_prefix("data: "))
+            .filter(|d| *d != "[DONE]")
+            .map(|d| serde_json::from_str::<serde_json::Value>(d).unwrap())
+            .filter_map(|v| {
+                v["choices"][0]["delta"]["content"]
+                    .as_str()
+                    .map(String::from)
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn duplicated_and_reordered_deltas() {
+        let in_order = contents(&stream_frames(config()).await);
+
+        let mut duplicated = config();
+        duplicated.stream.duplicate_rate = 1.0;
+        let doubled = contents(&stream_frames(duplicated).await);
+        let expected: Vec<String> = in_order
+            .iter()
+            .flat_map(|c| [c.clone(), c.clone()])
+            .collect();
+        assert_eq!(doubled, expected);
+        // Role and finish chunks are not content deltas
+        let mut duplicated = config();
+        duplicated.stream.duplicate_rate = 1.0;
+        let text = String::from_utf8(stream_frames(duplicated).await.concat()).unwrap();
+        assert_eq!(text.matches("\"role\":\"assistant\"").count(), 1);
+        assert_eq!(text.matches("\"finish_reason\":\"stop\"").count(), 1);
+
+        let mut reordered = config();
+        reordered.stream.reorder_rate = 1.0;
+        let swapped = contents(&stream_frames(reordered).await);
+        assert_ne!(swapped, in_order);
+        let (mut a, mut b) = (swapped.clone(), in_order.clone());
+        a.sort();
+        b.sort();
+        assert_eq!(a, b);
+    }
+
+    /// Ids of the events in an SSE body, in order
+    fn event_ids(text: &str) -> Vec<String> {
+        text.split("\n\n")
+            .filter(|event| !event.is_empty() && !event.starts_with(':'))
+            .map(|event| {
+                let id = event.lines().find_map(|l| l.strip_prefix("id: "));
+                id.unwrap_or_else(|| panic!("no id on {:?}", event))
+                    .to_string()
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn event_ids_let_a_client_resume_the_stream() {
+        let mut config = config();
+        config.stream.event_ids = true;
+        config.stream.retry_ms = Some(1500);
+        let stats = new_shared_stats();
+        let router = config_router(config, stats.clone());
+        let send = |path: &'static str, body: serde_json::Value, last_event_id: Option<String>| {
+            let mut req = request("POST", path, Some(body));
+            if let Some(id) = last_event_id {
+                req.headers_mut()
+                    .insert("last-event-id", id.parse().unwrap());
+            }
+            let router = router.clone();
+            async move { String::from_utf8(call(&router, req).await.1).unwrap() }
+        };
+        let chat = json!({
+            "model": "gpt-5",
+            "messages": [{"role": "user", "content": "hi"}],
+            "stream": true
+        });
+
+        let text = send("/openai/v1/chat/completions", chat.clone(), None).await;
+        let text = text.strip_prefix("retry: 1500\n\n").unwrap();
+        let ids = event_ids(text);
+        assert!(ids.len() > 3);
+        let key = ids[0].split_once(':').unwrap().0;
+        let expected: Vec<String> = (1..=ids.len()).map(|n| format!("{}:{}", key, n)).collect();
+        assert_eq!(ids, expected);
+
+        // Reconnecting after the second event replays the rest, [DONE] included
+        let resumed = send(
+            "/openai/v1/chat/completions",
+            chat.clone(),
+            Some(ids[1].clone()),
+        )
+        .await;
+        let resumed = resumed.strip_prefix("retry: 1500\n\n").unwrap();
+        assert_eq!(event_ids(resumed), ids[2..]);
+        assert!(text.ends_with(resumed));
+        assert_eq!(stats.snapshot().total_requests, 1);
+
+        // An id no stream has is an ordinary request
+        let fresh = send(
+            "/openai/v1/chat/completions",
+            chat,
+            Some("gone:2".to_string()),
+        )
+        .await;
+        let fresh = fresh.strip_prefix("retry: 1500\n\n").unwrap();
+        assert_ne!(event_ids(fresh)[0].split_once(':').unwrap().0, key);
+        assert_eq!(stats.snapshot().total_requests, 2);
+
+        // Every event of a multi-event protocol is numbered
+        let messages = json!({
+            "model": "claude-sonnet-4-5",
+            "max_tokens": 64,
+            "messages": [{"role": "user", "content": "hi"}],
+            "stream": true
+        });
+        let text = send("/anthropic/v1/messages", messages, None).await;
+        let text = text.strip_prefix("retry: 1500\n\n").unwrap();
+        assert!(event_ids(text).len() > 4);
+    }
+}
+
+/// Stored chat completions: `store: true` keeps a completion, streamed or
+/// not, to be listed, retrieved, given new metadata and deleted at
+/// `/openai/v1/chat/completions`.
+mod stored_completions_tests {
+    use crate::common::{call, instant_router, request};
+    use axum::http::{Method, StatusCode};
+    use axum::Router;
+    use serde_json::{json, Value};
+
+    fn router() -> Router {
+        instant_router("")
+    }
+
+    async fn send(
+        router: &Router,
+        method: Method,
+        uri: &str,
+        body: Option<Value>,
+    ) -> (StatusCode, String) {
+        let (status, bytes) = call(router, request(method.as_str(), uri, body)).await;
+        (status, String::from_utf8(bytes).unwrap())
+    }
+
+    async fn json_of(
+        router: &Router,
+        method: Method,
+        uri: &str,
+        body: Option<Value>,
+    ) -> (StatusCode, Value) {
+        let (status, body) = send(router, method, uri, body).await;
+        (status, serde_json::from_str(&body).unwrap())
+    }
+
+    async fn create(router: &Router, model: &str, metadata: Value) -> Value {
+        let (status, completion) = json_of(
+            router,
+            Method::POST,
+            "/openai/v1/chat/completions",
+            Some(json!({
+                "model": model,
+                "messages": [{"role": "user", "content": "Hello"}],
+                "store": true,
+                "metadata": metadata
+            })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        completion
+    }
+
+    #[tokio::test]
+    async fn stored_completions_are_retrieved_updated_and_deleted() {
+        let router = router();
+        let completion = create(&router, "gpt-5", json!({"team": "search"})).await;
+        let id = completion["id"].as_str().unwrap();
+        let uri = format!("/openai/v1/chat/completions/{}", id);
+
+        let (status, stored) = json_of(&router, Method::GET, &uri, None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(stored["choices"], completion["choices"]);
+        assert_eq!(stored["metadata"], json!({"team": "search"}));
+
+        let (status, updated) = json_of(
+            &router,
+            Method::POST,
+            &uri,
+            Some(json!({"metadata": {"team": "ads", "env": "ci"}})),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(updated["metadata"], json!({"team": "ads", "env": "ci"}));
+        let (_, stored) = json_of(&router, Method::GET, &uri, None).await;
+        assert_eq!(stored["metadata"]["team"], "ads");
+
+        let (status, deleted) = json_of(&router, Method::DELETE, &uri, None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            deleted,
+            json!({"object": "chat.completion.deleted", "id": id, "deleted": true})
+        );
+        let (status, _) = send(&router, Method::GET, &uri, None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, _) = send(&router, Method::DELETE, &uri, None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn list_filters_by_model_and_metadata() {
+        let router = router();
+        let first = create(&router, "gpt-5", json!({"team": "search"})).await;
+        create(&router, "gpt-4o", json!({"team": "search"})).await;
+        create(&router, "gpt-5", json!({"team": "ads"})).await;
+
+        // Not stored, not listed
+        let (status, _) = send(
+            &router,
+            Method::POST,
+            "/openai/v1/chat/completions",
+            Some(json!({"model": "gpt-5", "messages": [{"role": "user", "content": "Hi"}]})),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (_, page) = json_of(&router, Method::GET, "/openai/v1/chat/completions", None).await;
+        assert_eq!(page["object"], "list");
+        assert_eq!(page["data"].as_array().unwrap().len(), 3);
+        // Oldest first unless asked otherwise
+        assert_eq!(page["first_id"], first["id"]);
+
+        let (_, page) = json_of(
+            &router,
+            Method::GET,
+            "/openai/v1/chat/completions?model=gpt-5&metadata%5Bteam%5D=search",
+            None,
+        )
+        .await;
+        let data = page["data"].as_array().unwrap();
+        assert_eq!(data.len(), 1);
+        assert_eq!(data[0]["id"], first["id"]);
+    }
+
+    #[tokio::test]
+    async fn streamed_completions_are_stored_whole() {
+        let router = router();
+        let (status, body) = send(
+            &router,
+            Method::POST,
+            "/openai/v1/chat/completions",
+            Some(json!({
+                "model": "gpt-5",
+                "messages": [{"role": "user", "content": "Hello"}],
+                "stream": true,
+                "store": true
+            })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let chunks: Vec<Value> = body
+            .lines()
+            .filter_map(|line| line.strip_prefix("data: "))
+            .filter_map(|data| serde_json::from_str(data).ok())
+            .collect();
+        let id = chunks[0]["id"].as_str().unwrap();
+        let content: String = chunks
+            .iter()
+            .filter_map(|chunk| chunk["choices"][0]["delta"]["content"].as_str())
+            .collect();
+
+        let (status, stored) = json_of(
+            &router,
+            Method::GET,
+            &format!("/openai/v1/chat/completions/{}", id),
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(stored["object"], "chat.completion");
+        assert_eq!(stored["choices"][0]["message"]["content"], content);
+        assert_eq!(stored["choices"][0]["finish_reason"], "stop");
+    }
+
+    #[tokio::test]
+    async fn oversized_metadata_is_refused() {
+        let router = router();
+        let metadata: serde_json::Map<String, Value> =
+            (0..17).map(|i| (i.to_string(), json!("x"))).collect();
+        let (status, _) = send(
+            &router,
+            Method::POST,
+            "/openai/v1/chat/completions",
+            Some(json!({
+                "model": "gpt-5",
+                "messages": [{"role": "user", "content": "Hello"}],
+                "store": true,
+                "metadata": metadata
+            })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+}
+
+/// `[stream] max_duration_secs`: a long generated stream on a slow profile
+/// is ended early as cut short, and counted in stats.
+mod stream_duration_tests {
+    use crate::common::{call, config_router, request};
+    use axum::Router;
+    use llmsim::cli::Config;
+    use llmsim::stats::{new_shared_stats, SharedStats};
+    use serde_json::{json, Value};
+
+    /// One second per token, for a thousand tokens
+    fn router(stream: &str, stats: SharedStats) -> Router {
+        let config = Config::from_toml(&format!(
+        "[latency]\nttft_mean_ms = 500\nttft_stddev_ms = 0\ntbt_mean_ms = 1000\ntbt_stddev_ms = 0\n\
+         [response]\ntarget_tokens = 1000\n[stream]\n{}",
+        stream
+    ))
+        .unwrap();
+        config_router(config, stats)
+    }
+
+    async fn post(router: &Router, uri: &str, body: Value) -> String {
+        let (_, bytes) = call(router, request("POST", uri, Some(body))).await;
+        String::from_utf8(bytes).unwrap()
+    }
+
+    /// JSON payloads of an SSE body's `data:` lines
+    fn events(body: &str) -> Vec<Value> {
+        body.lines()
+            .filter_map(|line| line.strip_prefix("data: "))
+            .filter_map(|data| serde_json::from_str(data).ok())
+            .collect()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn chat_stream_ends_with_length() {
+        let stats = new_shared_stats();
+        let router = router("max_duration_secs = 5\n", stats.clone());
+        let started = tokio::time::Instant::now();
+        let body = post(
+            &router,
+            "/openai/v1/chat/completions",
+            json!({
+                "model": "gpt-5",
+                "messages": [{"role": "user", "content": "Hello"}],
+                "stream": true,
+                "stream_options": {"include_usage": true}
+            }),
+        )
+        .await;
+
+        let chunks = events(&body);
+        let deltas = chunks
+            .iter()
+            .filter(|c| c["choices"][0]["delta"]["content"].is_string())
+            .count();
+        assert_eq!(deltas, 4);
+        let last = chunks
+            .iter()
+            .rev()
+            .find(|c| !c["choices"][0]["finish_reason"].is_null())
+            .unwrap();
+        assert_eq!(last["choices"][0]["finish_reason"], "length");
+        assert!(body.ends_with("data: [DONE]\n\n"));
+        assert!(started.elapsed() <= std::time::Duration::from_secs(5));
+
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.capped_streams, 1);
+        assert_eq!(snapshot.active_requests, 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn responses_stream_ends_incomplete() {
+        let stats = new_shared_stats();
+        let router = router("max_duration_secs = 3\n", stats.clone());
+        let body = post(
+            &router,
+            "/openai/v1/responses",
+            json!({"model": "gpt-5", "input": "Hello", "stream": true}),
+        )
+        .await;
+
+        let last = events(&body).pop().unwrap();
+        assert_eq!(last["type"], "response.incomplete");
+        assert_eq!(
+            last["response"]["incomplete_details"]["reason"],
+            "max_output_tokens"
+        );
+        assert_eq!(stats.snapshot().capped_streams, 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn anthropic_stream_ends_at_max_tokens() {
+        let stats = new_shared_stats();
+        let router = router("max_duration_secs = 3\n", stats.clone());
+        let body = post(
+            &router,
+            "/anthropic/v1/messages",
+            json!({
+                "model": "claude-sonnet-4-5",
+                "max_tokens": 4096,
+                "messages": [{"role": "user", "content": "Hello"}],
+                "stream": true
+            }),
+        )
+        .await;
+
+        let delta = events(&body)
+            .into_iter()
+            .find(|e| e["type"] == "message_delta")
+            .unwrap();
+        assert_eq!(delta["delta"]["stop_reason"], "max_tokens");
+        assert_eq!(stats.snapshot().capped_streams, 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn streams_run_to_the_end_without_a_cap() {
+        let stats = new_shared_stats();
+        let router = router("", stats.clone());
+        let body = post(
+            &router,
+            "/openai/v1/chat/completions",
+            json!({
+                "model": "gpt-5",
+                "messages": [{"role": "user", "content": "Hello"}],
+                "stream": true,
+                "max_tokens": 5
+            }),
+        )
+        .await;
+        assert!(body.contains("\"finish_reason\":\"stop\""));
+        assert_eq!(stats.snapshot().capped_streams, 0);
+    }
+}
+
+/// `[stream.tee]`: the exact bytes of selected streams written to files, with
+/// the frame boundaries they were sent at.
+mod tee_tests {
+    use crate::common::{call, instant_router, request};
+    use axum::http::StatusCode;
+    use axum::Router;
+    use serde_json::{json, Value};
+    use std::path::{Path, PathBuf};
+    use std::time::Duration;
+
+    fn capture_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("llmsim-tee-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn router(dir: &Path, toml: &str) -> Router {
+        instant_router(&format!(
+            "[stream.tee]\ndir = {:?}\n{}",
+            dir.display().to_string(),
+            toml
+        ))
+    }
+
+    async fn chat(router: &Router, request_id: &str, stream: bool) -> Vec<u8> {
+        let body = json!({
+            "model": "gpt-5",
+            "messages": [{"role": "user", "content": "Hello"}],
+            "stream": stream
+        });
+        let mut req = request("POST", "/openai/v1/chat/completions", Some(body));
+        req.headers_mut()
+            .insert("x-request-id", request_id.parse().unwrap());
+        let (status, body) = call(router, req).await;
+        assert_eq!(status, StatusCode::OK);
+        body
+    }
+
+    /// The capture's metadata once the writer thread has saved it
+    async fn saved(dir: &Path, name: &str) -> Value {
+        let path = dir.join(format!("{}.json", name));
+        for _ in 0..100 {
+            if let Ok(json) = std::fs::read(&path) {
+                return serde_json::from_slice(&json).unwrap();
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("{} was never written", path.display());
+    }
+
+    #[tokio::test]
+    async fn selected_streams_are_captured_byte_for_byte() {
+        let dir = capture_dir("selected");
+        let router = router(&dir, "request_ids = [\"fixture-*\"]\n");
+        let body = chat(&router, "fixture-hello", true).await;
+
+        let meta = saved(&dir, "fixture-hello").await;
+        let bytes = std::fs::read(dir.join("fixture-hello.sse")).unwrap();
+        assert_eq!(bytes, body);
+        assert_eq!(meta["request_id"], "fixture-hello");
+        assert_eq!(meta["path"], "/openai/v1/chat/completions");
+        assert_eq!(meta["status"], 200);
+        assert_eq!(meta["complete"], true);
+        assert_eq!(meta["bytes"], body.len());
+
+        // Frames tile the bytes exactly, in order
+        let frames = meta["frames"].as_array().unwrap();
+        assert!(frames.len() > 1);
+        let mut offset = 0;
+        for frame in frames {
+            assert_eq!(frame["offset"], offset);
+            offset += frame["bytes"].as_u64().unwrap();
+        }
+        assert_eq!(offset, body.len() as u64);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn other_requests_are_not_captured() {
+        let dir = capture_dir("other");
+        let router = router(&dir, "request_ids = [\"fixture-*\"]\n");
+        chat(&router, "fixture-json", false).await;
+        chat(&router, "unrelated", true).await;
+        // A later capture is written after any earlier one
+        chat(&router, "fixture-last", true).await;
+        saved(&dir, "fixture-last").await;
+
+        let mut files: Vec<_> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        files.sort();
+        assert_eq!(files, ["fixture-last.json", "fixture-last.sse"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn sampled_streams_get_names_of_their_own() {
+        let dir = capture_dir("sampled");
+        let router = router(&dir, "sample_rate = 1.0\n");
+        chat(&router, "a", true).await;
+        chat(&router, "b", true).await;
+
+        for _ in 0..100 {
+            let captures = std::fs::read_dir(&dir)
+                .unwrap()
+                .filter(|entry| entry.as_ref().unwrap().path().extension() == Some("sse".as_ref()))
+                .count();
+            if captures == 2 {
+                std::fs::remove_dir_all(&dir).unwrap();
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("expected two sampled captures");
+    }
+}
+
+/// `[tokens]`: the encoding (or estimate) usage is counted with.
+mod tokens_tests {
+    use crate::common::{call, config_router, request};
+    use llmsim::cli::{Config, TokenEncoding};
+    use llmsim::stats::new_shared_stats;
+    use serde_json::{json, Value};
+
+    async fn post(config: Config, uri: &str, body: Value) -> String {
+        let router = config_router(config, new_shared_stats());
+        let (_, bytes) = call(&router, request("POST", uri, Some(body))).await;
+        String::from_utf8(bytes).unwrap()
+    }
+
+    fn estimate_config() -> Config {
+        let mut config = Config::default();
+        config.latency.profile = Some("instant".to_string());
+        config.response.generator = "fixed:abcdefghijklmnopqrstuvwxyz".to_string();
+        config.tokens.encoding = TokenEncoding::Estimate;
+        config.tokens.chars_per_token = 2.0;
+        config
+    }
+
+    #[tokio::test]
+    async fn estimate_counts_characters() {
+        let body = post(
+            estimate_config(),
+            "/openai/v1/chat/completions",
+            json!({"model": "gpt-5", "messages": [{"role": "user", "content": "hi"}]}),
+        )
+        .await;
+        let resp: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(resp["usage"]["completion_tokens"], 13);
+    }
+
+    #[tokio::test]
+    async fn streamed_usage_uses_the_same_tokenizer() {
+        let body = post(
+            estimate_config(),
+            "/openai/v1/chat/completions",
+            json!({
+                "model": "gpt-5",
+                "messages": [{"role": "user", "content": "hi"}],
+                "stream": true,
+                "stream_options": {"include_usage": true}
+            }),
+        )
+        .await;
+        assert!(body.contains("\"completion_tokens\":13"), "{}", body);
+    }
+
+    #[tokio::test]
+    async fn estimate_truncates_responses() {
+        let body = post(
+            estimate_config(),
+            "/openai/v1/responses",
+            json!({"model": "gpt-5", "input": "hi", "max_output_tokens": 5}),
+        )
+        .await;
+        let resp: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(resp["status"], "incomplete");
+        assert_eq!(resp["output_text"], "abcdefghij");
+        assert_eq!(resp["usage"]["output_tokens"], 5);
+    }
+}
+
+/// Simulated tool calls in generated (non-scripted) chat completions.
+mod tool_calls_tests {
+    use crate::common::{call, config_router};
+    use llmsim::cli::Config;
+    use llmsim::stats::new_shared_stats;
+    use serde_json::{json, Value};
+    use std::collections::HashSet;
+
+    fn config(weights: Vec<f64>) -> Config {
+        let mut config = Config::default();
+        config.latency.profile = Some("instant".to_string());
+        config.tools.call_rate = 1.0;
+        config.tools.parallel_weights = weights;
+        config
+    }
+
+    async fn post(config: Config, body: Value) -> String {
+        let router = config_router(config, new_shared_stats());
+        let req = crate::common::request("POST", "/openai/v1/chat/completions", Some(body));
+        String::from_utf8(call(&router, req).await.1).unwrap()
+    }
+
+    fn request(stream: bool, extra: Value) -> Value {
+        let mut body = json!({
+            "model": "gpt-5",
+            "messages": [{"role": "user", "content": "look it up"}],
+            "tools": [
+                {"type": "function", "function": {"name": "search"}},
+                {"type": "function", "function": {"name": "fetch"}}
+            ],
+            "stream": stream
+        });
+        body.as_object_mut()
+            .unwrap()
+            .extend(extra.as_object().unwrap().clone());
+        body
+    }
+
+    #[tokio::test]
+    async fn parallel_tool_calls_in_one_turn() {
+        let body = post(config(vec![0.0, 0.0, 1.0]), request(false, json!({}))).await;
+        let v: Value = serde_json::from_str(&body).unwrap();
+        let choice = &v["choices"][0];
+        assert_eq!(choice["finish_reason"], "tool_calls");
+
+        let calls = choice["message"]["tool_calls"].as_array().unwrap();
+        assert_eq!(calls.len(), 3);
+        let ids: HashSet<&str> = calls.iter().map(|c| c["id"].as_str().unwrap()).collect();
+        assert_eq!(ids.len(), 3);
+        for call in calls {
+            assert!(["search", "fetch"].contains(&call["function"]["name"].as_str().unwrap()));
+        }
+    }
+
+    #[tokio::test]
+    async fn parallel_tool_call_deltas_are_indexed() {
+        let body = post(config(vec![0.0, 1.0]), request(true, json!({}))).await;
+        let deltas: Vec<serde_json::Map<String, Value>> = body
+            .lines()
+            .filter_map(|l| l.strip_prefix("data: "))
+            .filter(|d| *d != "[DONE]")
+            .map(|d| serde_json::from_str::<Value>(d).unwrap())
+            .filter_map(|v| {
+                v["choices"][0]["delta"]["tool_calls"][0]
+                    .as_object()
+                    .cloned()
+            })
+            .collect();
+
+        // Announce + arguments for each call, in index order
+        let indexes: Vec<u64> = deltas
+            .iter()
+            .map(|d| d["index"].as_u64().unwrap())
+            .collect();
+        assert_eq!(indexes, vec![0, 0, 1, 1]);
+        assert!(deltas[0]["id"].is_string() && deltas[2]["id"].is_string());
+        assert_ne!(deltas[0]["id"], deltas[2]["id"]);
+        assert!(body.contains("\"finish_reason\":\"tool_calls\""));
+    }
+
+    #[tokio::test]
+    async fn parallel_tool_calls_false_limits_to_one() {
+        let body = post(
+            config(vec![0.0, 0.0, 1.0]),
+            request(false, json!({"parallel_tool_calls": false})),
+        )
+        .await;
+        let v: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(
+            v["choices"][0]["message"]["tool_calls"]
+                .as_array()
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn tool_choice_none_answers_with_text() {
+        let body = post(
+            config(vec![1.0]),
+            request(false, json!({"tool_choice": "none"})),
+        )
+        .await;
+        let v: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(v["choices"][0]["finish_reason"], "stop");
+        assert!(v["choices"][0]["message"]["tool_calls"].is_null());
+    }
+
+    #[tokio::test]
+    async fn tools_are_ignored_by_default() {
+        let body = post(Config::default(), request(false, json!({}))).await;
+        let v: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(v["choices"][0]["finish_reason"], "stop");
+    }
+}
+
+/// Responses output cut off by `max_output_tokens` (`status: incomplete`).
+mod truncation_tests {
+    use crate::common::{call, config_router, request};
+    use llmsim::cli::Config;
+    use llmsim::stats::new_shared_stats;
+    use serde_json::{json, Value};
+
+    fn config() -> Config {
+        let mut config = Config::default();
+        config.latency.profile = Some("instant".to_string());
+        config.response.target_tokens = 200;
+        config
+    }
+
+    async fn post(config: Config, uri: &str, body: Value) -> String {
+        let router = config_router(config, new_shared_stats());
+        let (_, bytes) = call(&router, request("POST", uri, Some(body))).await;
+        String::from_utf8(bytes).unwrap()
+    }
+
+    /// Event names in order from an SSE body
+    fn event_names(body: &str) -> Vec<&str> {
+        body.lines()
+            .filter_map(|l| l.strip_prefix("event: "))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn max_output_tokens_marks_response_incomplete() {
+        let body = post(
+            config(),
+            "/openai/v1/responses",
+            json!({"model": "gpt-4o", "input": "hi", "max_output_tokens": 10}),
+        )
+        .await;
+        let resp: Value = serde_json::from_str(&body).unwrap();
+
+        assert_eq!(resp["status"], "incomplete");
+        assert_eq!(resp["incomplete_details"]["reason"], "max_output_tokens");
+        assert_eq!(resp["output"][0]["status"], "incomplete");
+        assert_eq!(resp["usage"]["output_tokens"], 10);
+    }
+
+    #[tokio::test]
+    async fn output_within_limit_is_completed() {
+        let body = post(
+            config(),
+            "/openai/v1/responses",
+            json!({"model": "gpt-4o", "input": "hi", "max_output_tokens": 10000}),
+        )
+        .await;
+        let resp: Value = serde_json::from_str(&body).unwrap();
+
+        assert_eq!(resp["status"], "completed");
+        assert!(resp.get("incomplete_details").is_none());
+    }
+
+    #[tokio::test]
+    async fn truncated_stream_ends_with_response_incomplete() {
+        let body = post(
+            config(),
+            "/openai/v1/responses",
+            json!({"model": "gpt-4o", "input": "hi", "max_output_tokens": 10, "stream": true}),
+        )
+        .await;
+
+        let events = event_names(&body);
+        assert_eq!(events.last(), Some(&"response.incomplete"));
+        assert!(!events.contains(&"response.completed"));
+        assert!(body.contains("\"reason\":\"max_output_tokens\""));
+    }
+
+    #[tokio::test]
+    async fn truncation_rate_applies_to_openresponses() {
+        let mut config = config();
+        config.response.truncation_rate = 1.0;
+
+        let body = post(
+            config.clone(),
+            "/openresponses/v1/responses",
+            json!({"model": "gpt-4o", "input": "hi"}),
+        )
+        .await;
+        let resp: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(resp["status"], "incomplete");
+        assert_eq!(resp["incomplete_details"]["reason"], "max_output_tokens");
+
+        let body = post(
+            config,
+            "/openresponses/v1/responses",
+            json!({"model": "gpt-4o", "input": "hi", "stream": true}),
+        )
+        .await;
+        assert!(body.contains("\"type\":\"response.incomplete\""));
+        assert!(!body.contains("\"type\":\"response.completed\""));
+    }
+}
+
+/// `[usage]`: completed requests reported at the organization usage and
+/// costs endpoints, in the shapes of OpenAI's usage API.
+mod usage_tests {
+    use crate::common::{call, get, instant_router, request};
+    use axum::http::StatusCode;
+    use axum::Router;
+    use serde_json::{json, Value};
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    async fn complete(router: &Router, model: &str, stream: bool) -> Value {
+        let body = json!({
+            "model": model,
+            "messages": [{"role": "user", "content": "Hello"}],
+            "stream": stream,
+            "stream_options": {"include_usage": true}
+        });
+        let req = request("POST", "/openai/v1/chat/completions", Some(body));
+        let (status, body) = call(router, req).await;
+        assert_eq!(status, StatusCode::OK);
+        let body = String::from_utf8(body).unwrap();
+        if !stream {
+            return serde_json::from_str::<Value>(&body).unwrap()["usage"].clone();
+        }
+        body.lines()
+            .filter_map(|line| line.strip_prefix("data: "))
+            .filter_map(|data| serde_json::from_str::<Value>(data).ok())
+            .find(|chunk| !chunk["usage"].is_null())
+            .unwrap()["usage"]
+            .clone()
+    }
+
+    /// Start of the hour before now, so today's usage lands in the first bucket
+    fn start_time() -> u64 {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        now - now % 3600 - 3600
+    }
+
+    /// The results of the first bucket once `requests` requests are counted;
+    /// usage is recorded off the request's task
+    async fn first_bucket(router: &Router, uri: &str, requests: u64) -> Vec<Value> {
+        for _ in 0..100 {
+            let (status, page) = get(router, uri).await;
+            assert_eq!(status, StatusCode::OK);
+            let results = page["data"][0]["results"].as_array().unwrap().clone();
+            let counted: u64 = results
+                .iter()
+                .filter_map(|r| r["num_model_requests"].as_u64())
+                .sum();
+            if counted >= requests {
+                return results;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("usage never reached {} requests", requests);
+    }
+
+    #[tokio::test]
+    async fn completions_usage_adds_up_the_requests_served() {
+        let router = instant_router("[usage]\nenabled = true\n");
+        let first = complete(&router, "gpt-5", false).await;
+        let second = complete(&router, "gpt-5", true).await;
+        let third = complete(&router, "gpt-4o", false).await;
+
+        let uri = format!(
+            "/openai/v1/organization/usage/completions?start_time={}&group_by[]=model",
+            start_time()
+        );
+        let results = first_bucket(&router, &uri, 3).await;
+        assert_eq!(results.len(), 2);
+        let gpt5 = results.iter().find(|r| r["model"] == "gpt-5").unwrap();
+        assert_eq!(gpt5["object"], "organization.usage.completions.result");
+        assert_eq!(gpt5["num_model_requests"], 2);
+        assert_eq!(
+            gpt5["input_tokens"],
+            first["prompt_tokens"].as_u64().unwrap() + second["prompt_tokens"].as_u64().unwrap()
+        );
+        assert_eq!(
+            gpt5["output_tokens"],
+            first["completion_tokens"].as_u64().unwrap()
+                + second["completion_tokens"].as_u64().unwrap()
+        );
+        let gpt4o = results.iter().find(|r| r["model"] == "gpt-4o").unwrap();
+        assert_eq!(gpt4o["output_tokens"], third["completion_tokens"]);
+
+        let (_, page) = get(&router, &uri).await;
+        assert_eq!(page["object"], "page");
+        assert_eq!(page["data"][0]["object"], "bucket");
+        assert_eq!(
+            page["data"][0]["end_time"],
+            page["data"][0]["start_time"].as_u64().unwrap() + 86_400
+        );
+    }
+
+    #[tokio::test]
+    async fn costs_price_the_usage() {
+        let router = instant_router("[usage]\nenabled = true\n");
+        let usage = complete(&router, "gpt-5", false).await;
+        let usage_uri = format!(
+            "/openai/v1/organization/usage/completions?start_time={}",
+            start_time()
+        );
+        first_bucket(&router, &usage_uri, 1).await;
+
+        let (status, page) = get(
+            &router,
+            &format!(
+                "/openai/v1/organization/costs?start_time={}&group_by=project_id",
+                start_time()
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let result = &page["data"][0]["results"][0];
+        assert_eq!(result["object"], "organization.costs.result");
+        assert_eq!(result["project_id"], "proj_llmsim");
+        // gpt-5 lists at $1.25 in and $10 out per million tokens
+        let expected = usage["prompt_tokens"].as_f64().unwrap() * 1.25e-6
+            + usage["completion_tokens"].as_f64().unwrap() * 10e-6;
+        assert!((result["amount"]["value"].as_f64().unwrap() - expected).abs() < 1e-12);
+        assert_eq!(result["amount"]["currency"], "usd");
+    }
+
+    #[tokio::test]
+    async fn usage_needs_enabling_and_a_start_time() {
+        let (status, _) = get(
+            &instant_router(""),
+            "/openai/v1/organization/usage/completions?start_time=0",
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let router = instant_router("[usage]\nenabled = true\n");
+        let (status, body) = get(&router, "/openai/v1/organization/usage/completions").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body["error"]["message"]
+            .as_str()
+            .unwrap()
+            .contains("start_time"));
+        let (status, _) = get(
+            &router,
+            "/openai/v1/organization/costs?start_time=0&bucket_width=1h",
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+}
+
+/// `llmsim verify` passes against llmsim itself and lists the deviations of
+/// an endpoint that breaks what clients rely on.
+mod verify_tests {
+    use crate::common::instant_router;
+    use axum::routing::{get, post};
+    use axum::{Json, Router};
+    use llmsim::cli::{verify, verify_report, VerifyOptions};
+    use serde_json::json;
+
+    async fn serve(app: Router) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        format!("http://{}", addr)
+    }
+
+    #[tokio::test]
+    async fn llmsim_passes_every_check() {
+        let base = serve(instant_router("")).await;
+
+        let mut options = VerifyOptions::new(format!("{}/openai", base));
+        options.strict_schema = true;
+        let results = verify(&options).await.unwrap();
+        assert_eq!(results.len(), 7);
+        assert!(
+            results.iter().all(|r| r.passed()),
+            "{}",
+            verify_report(&results)
+        );
+    }
+
+    #[tokio::test]
+    async fn deviations_are_reported() {
+        // Lists models, but answers completions without usage and unknown models
+        // with a 200
+        let app = Router::new()
+            .route(
+                "/v1/models",
+                get(|| async { Json(json!({"object": "list", "data": []})) }),
+            )
+            .route(
+                "/v1/models/{id}",
+                get(|| async { Json(json!({"id": "x"})) }),
+            )
+            .route(
+                "/v1/chat/completions",
+                post(|| async {
+                    Json(json!({
+                        "id": "c1",
+                        "object": "chat.completion",
+                        "choices": [{
+                            "message": {"role": "assistant", "content": "hi"},
+                            "finish_reason": "stop"
+                        }]
+                    }))
+                }),
+            );
+        let results = verify(&VerifyOptions::new(serve(app).await)).await.unwrap();
+        let failures = |name: &str| {
+            results
+                .iter()
+                .find(|r| r.name == name)
+                .unwrap()
+                .failures
+                .clone()
+        };
+
+        assert!(failures("models").is_empty());
+        assert_eq!(failures("chat"), ["`usage` lacks token counts"]);
+        assert!(failures("chat_stream")[0].contains("text/event-stream"));
+        assert!(failures("json_mode")[0].contains("not a JSON object"));
+        assert!(failures("responses_stream")[0].starts_with("status 404"));
+        assert!(failures("error")[0].starts_with("status 200, expected 404"));
+
+        let report = verify_report(&results);
+        assert!(report.ends_with("1/7 checks passed\n"), "{}", report);
+    }
+
+    #[tokio::test]
+    async fn bad_target_is_an_error() {
+        assert!(verify(&VerifyOptions::new("localhost:8080")).await.is_err());
+    }
+}
+
+/// End-to-end tests for the vLLM-compatible `/vllm/...` routes, driven the
+/// way an OpenAI SDK configured with `{base_url}/vllm/v1` would.
+mod vllm_tests {
+    use crate::common::{call, config_router, request};
+    use axum::body::to_bytes;
+    use axum::http::StatusCode;
+    use axum::Router;
+    use llmsim::cli::Config;
+    use llmsim::stats::new_shared_stats;
+    use serde_json::{json, Value};
+    use tower::ServiceExt;
+
+    const SERVED: &str = "meta-llama/Llama-3.1-8B-Instruct";
+
+    fn router(continuous_usage_stats: bool) -> Router {
+        let mut config = Config::default();
+        config.latency.profile = Some("instant".to_string());
+        config.vllm.served_model_name = Some(SERVED.to_string());
+        config.vllm.continuous_usage_stats = continuous_usage_stats;
+        config_router(config, new_shared_stats())
+    }
+
+    async fn post_chat(router: &Router, body: Value) -> (StatusCode, String) {
+        let req = request("POST", "/vllm/v1/chat/completions", Some(body));
+        let (status, bytes) = call(router, req).await;
+        (status, String::from_utf8(bytes).unwrap())
+    }
+
+    async fn get(router: &Router, uri: &str) -> (StatusCode, String) {
+        let (status, bytes) = call(router, request("GET", uri, None)).await;
+        (status, String::from_utf8(bytes).unwrap())
+    }
+
+    fn sse_chunks(body: &str) -> Vec<Value> {
+        body.lines()
+            .filter_map(|l| l.strip_prefix("data: "))
+            .filter(|d| *d != "[DONE]")
+            .map(|d| serde_json::from_str(d).unwrap())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn models_lists_only_served_model() {
+        let (status, body) = get(&router(false), "/vllm/v1/models").await;
+        assert_eq!(status, StatusCode::OK);
+        let v: Value = serde_json::from_str(&body).unwrap();
+        let data = v["data"].as_array().unwrap();
+        assert_eq!(data.len(), 1);
+        assert_eq!(data[0]["id"], SERVED);
+        assert_eq!(data[0]["owned_by"], "vllm");
+        assert_eq!(data[0]["root"], SERVED);
+    }
+
+    #[tokio::test]
+    async fn unknown_model_is_not_found() {
+        let (status, body) = post_chat(
+            &router(false),
+            json!({"model": "gpt-5", "messages": [{"role": "user", "content": "hi"}]}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let v: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(v["object"], "error");
+        assert_eq!(v["type"], "NotFoundError");
+        assert_eq!(v["code"], 404);
+    }
+
+    #[tokio::test]
+    async fn streaming_usage_only_on_final_chunk_by_default() {
+        let (status, body) = post_chat(
+            &router(false),
+            json!({
+                "model": SERVED,
+                "messages": [{"role": "user", "content": "hi"}],
+                "stream": true
+            }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let chunks = sse_chunks(&body);
+        let with_usage = chunks.iter().filter(|c| c.get("usage").is_some()).count();
+        assert_eq!(with_usage, 1);
+    }
+
+    #[tokio::test]
+    async fn continuous_usage_stats_per_request() {
+        let (_, body) = post_chat(
+            &router(false),
+            json!({
+                "model": SERVED,
+                "messages": [{"role": "user", "content": "hi"}],
+                "stream": true,
+                "stream_options": {"include_usage": true, "continuous_usage_stats": true}
+            }),
+        )
+        .await;
+        let chunks = sse_chunks(&body);
+        assert!(chunks.len() > 2);
+        assert!(chunks.iter().all(|c| c["usage"]["total_tokens"].is_u64()));
+    }
+
+    #[tokio::test]
+    async fn continuous_usage_stats_from_config() {
+        let (_, body) = post_chat(
+            &router(true),
+            json!({
+                "model": SERVED,
+                "messages": [{"role": "user", "content": "hi"}],
+                "stream": true
+            }),
+        )
+        .await;
+        let chunks = sse_chunks(&body);
+        assert!(chunks.iter().all(|c| c.get("usage").is_some()));
+    }
+
+    #[tokio::test]
+    async fn metrics_use_vllm_names() {
+        let router = router(false);
+        post_chat(
+            &router,
+            json!({"model": SERVED, "messages": [{"role": "user", "content": "hi"}]}),
+        )
+        .await;
+
+        let req = request("GET", "/vllm/metrics", None);
+        let resp = router.clone().oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert!(resp.headers()["content-type"]
+            .to_str()
+            .unwrap()
+            .starts_with("text/plain"));
+        let bytes = to_bytes(resp.into_body(), 1024 * 1024).await.unwrap();
+        let text = String::from_utf8(bytes.to_vec()).unwrap();
+        let label = format!("{{model_name=\"{}\"}}", SERVED);
+        assert!(text.contains(&format!("vllm:request_success_total{} 1", label)));
+        assert!(text.contains(&format!("vllm:num_requests_running{} 0", label)));
+        assert!(text.contains("vllm:generation_tokens_total"));
+    }
+}
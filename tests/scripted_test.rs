@@ -240,10 +240,85 @@ async fn chat_completions_streaming_emits_tool_call_deltas() {
     assert!(body.contains("\"finish_reason\":\"tool_calls\""));
     assert!(body.contains("\"name\":\"write_file\""));
     assert!(body.contains("\"id\":\"call_w\""));
-    assert!(body.contains("\\\"path\\\""));
+    // Arguments arrive in fragments that concatenate to the full JSON
+    let fragments: Vec<String> = body
+        .lines()
+        .filter_map(|l| l.strip_prefix("data: "))
+        .filter_map(|d| serde_json::from_str::<Value>(d).ok())
+        .filter_map(|v| {
+            v["choices"][0]["delta"]["tool_calls"][0]["function"]["arguments"]
+                .as_str()
+                .map(str::to_string)
+        })
+        .filter(|f| !f.is_empty())
+        .collect();
+    assert!(fragments.len() > 5, "{:?}", fragments);
+    let arguments: Value = serde_json::from_str(&fragments.concat()).unwrap();
+    assert_eq!(arguments, json!({"path": "x.txt", "content": "hi"}));
     assert!(body.contains("[DONE]"));
 }
 
+#[tokio::test]
+async fn api_version_selects_legacy_function_call_format() {
+    let script = Script::from_spec(ScriptSpec {
+        turns: vec![SimTurn::ToolCalls {
+            calls: vec![SimToolCall {
+                name: "bash".into(),
+                arguments: json!({"command": "ls"}),
+                id: None,
+            }],
+        }],
+        on_exhausted: OnExhausted::RepeatLast,
+    })
+    .unwrap();
+    let mut config =
+        Config::from_toml("[api_versions.\"2023-05-15\"]\ntool_call_format = \"function_call\"\n")
+            .unwrap();
+    config.latency.profile = Some("instant".to_string());
+    let state = AppState::new(config, new_shared_stats()).with_script(Arc::new(script));
+    let router = build_router(Arc::new(state));
+
+    let send = |uri: &str, stream: bool| {
+        let body = json!({
+            "model": "gpt-5",
+            "messages": [{"role": "user", "content": "x"}],
+            "stream": stream
+        });
+        let req = Request::builder()
+            .method("POST")
+            .uri(uri)
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let router = router.clone();
+        async move {
+            let resp = router.oneshot(req).await.unwrap();
+            let bytes = to_bytes(resp.into_body(), 1024 * 1024).await.unwrap();
+            String::from_utf8(bytes.to_vec()).unwrap()
+        }
+    };
+
+    let legacy: Value = serde_json::from_str(
+        &send("/openai/v1/chat/completions?api-version=2023-05-15", false).await,
+    )
+    .unwrap();
+    let choice = &legacy["choices"][0];
+    assert_eq!(choice["finish_reason"], "function_call");
+    assert_eq!(choice["message"]["function_call"]["name"], "bash");
+    assert!(choice["message"]["tool_calls"].is_null());
+
+    let streamed = send("/openai/v1/chat/completions?api-version=2023-05-15", true).await;
+    assert!(streamed.contains("\"function_call\":{\"name\":\"bash\""));
+    assert!(!streamed.contains("tool_calls"));
+
+    // Unlisted versions get the current format
+    let current: Value = serde_json::from_str(
+        &send("/openai/v1/chat/completions?api-version=2024-10-21", false).await,
+    )
+    .unwrap();
+    assert_eq!(current["choices"][0]["finish_reason"], "tool_calls");
+}
+
 #[tokio::test]
 async fn responses_api_returns_function_call_output_items() {
     let script = Script::new(vec![SimTurn::Mixed {
@@ -278,6 +353,89 @@ async fn responses_api_error_turn() {
     assert_eq!(v["error"]["type"], "rate_limit_error");
 }
 
+fn expect_tool_results_script() -> Script {
+    Script::from_json(
+        r#"{
+            "turns": [
+                {"type": "tool_calls", "calls": [
+                    {"name": "bash", "arguments": {"cmd": "ls"}}
+                ]},
+                {"type": "expect_tool_results", "then": {"type": "assistant", "text": "done"}}
+            ]
+        }"#,
+    )
+    .unwrap()
+}
+
+fn tool_result_turn(call_id: &str) -> Value {
+    json!({
+        "model": "gpt-5",
+        "messages": [
+            {"role": "user", "content": "list files"},
+            {"role": "assistant", "content": null, "tool_calls": [{
+                "id": call_id, "type": "function",
+                "function": {"name": "bash", "arguments": "{}"}
+            }]},
+            {"role": "tool", "tool_call_id": call_id, "content": "a.txt"}
+        ]
+    })
+}
+
+#[tokio::test]
+async fn expect_tool_results_accepts_matching_call_id() {
+    let router = router_with_script(expect_tool_results_script());
+    let (_, body) = post_chat_completions(
+        &router,
+        json!({"model": "gpt-5", "messages": [{"role": "user", "content": "list files"}]}),
+    )
+    .await;
+    let v: Value = serde_json::from_str(&body).unwrap();
+    let call_id = v["choices"][0]["message"]["tool_calls"][0]["id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    let (status, body) = post_chat_completions(&router, tool_result_turn(&call_id)).await;
+    assert_eq!(status, StatusCode::OK);
+    let v: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(v["choices"][0]["message"]["content"], "done");
+}
+
+#[tokio::test]
+async fn expect_tool_results_rejects_wrong_call_id() {
+    let router = router_with_script(expect_tool_results_script());
+    post_chat_completions(
+        &router,
+        json!({"model": "gpt-5", "messages": [{"role": "user", "content": "list files"}]}),
+    )
+    .await;
+
+    let (status, body) = post_chat_completions(&router, tool_result_turn("call_wrong")).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let v: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(v["error"]["type"], "invalid_request_error");
+    let message = v["error"]["message"].as_str().unwrap();
+    assert!(message.contains("call_llmsim_0_0"), "{message}");
+    assert!(message.contains("call_wrong"), "{message}");
+}
+
+#[tokio::test]
+async fn expect_tool_results_checks_function_call_output_items() {
+    let router = router_with_script(expect_tool_results_script());
+    post_responses(&router, json!({"model": "gpt-5", "input": "list files"})).await;
+
+    let (status, body) = post_responses(
+        &router,
+        json!({"model": "gpt-5", "input": [
+            {"type": "function_call_output", "call_id": "call_llmsim_0_0", "output": "a.txt"}
+        ]}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let v: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(v["output_text"], "done");
+}
+
 #[tokio::test]
 async fn script_loads_from_file_via_config() {
     let dir = tempfile_dir();
@@ -155,6 +155,54 @@ async fn test_ws_event_sequence() {
     assert_eq!(types.last().unwrap(), "response.completed");
 }
 
+#[tokio::test]
+async fn test_ws_sequence_numbers_strictly_increase() {
+    let addr = start_server().await;
+    let (mut sink, mut stream) = ws_connect(addr).await;
+    let events = send_and_collect(&mut sink, &mut stream, "o3", "Hi").await;
+
+    let seqs: Vec<u64> = events
+        .iter()
+        .map(|e| {
+            e["sequence_number"]
+                .as_u64()
+                .unwrap_or_else(|| panic!("{} has no sequence_number", e["type"]))
+        })
+        .collect();
+    assert_eq!(seqs[0], 0);
+    assert!(
+        seqs.windows(2).all(|w| w[1] == w[0] + 1),
+        "gap or reorder: {:?}",
+        seqs
+    );
+}
+
+#[tokio::test]
+async fn test_ws_warmup_has_sequence_number() {
+    let addr = start_server().await;
+    let (mut sink, mut stream) = ws_connect(addr).await;
+
+    let request = serde_json::json!({
+        "type": "response.create",
+        "model": "gpt-5",
+        "input": "Hello",
+        "generate": false
+    });
+    sink.send(Message::Text(request.to_string().into()))
+        .await
+        .unwrap();
+
+    let timeout = tokio::time::Duration::from_secs(5);
+    match tokio::time::timeout(timeout, stream.next()).await {
+        Ok(Some(Ok(Message::Text(text)))) => {
+            let event: Value = serde_json::from_str(&text).unwrap();
+            assert_eq!(event["type"], "response.created");
+            assert_eq!(event["sequence_number"], 0);
+        }
+        other => panic!("Expected response.created, got: {:?}", other),
+    }
+}
+
 #[tokio::test]
 async fn test_ws_multiple_requests_sequential() {
     let addr = start_server().await;
@@ -219,6 +267,11 @@ async fn test_ws_previous_response_id_cached() {
 
     // Should succeed (response.created, not an error)
     assert_eq!(events2[0]["type"], "response.created");
+
+    // The follow-up's input carries the whole first turn
+    let usage1 = &events1.last().unwrap()["response"]["usage"];
+    let usage2 = &events2.last().unwrap()["response"]["usage"];
+    assert!(usage2["input_tokens"].as_u64().unwrap() > usage1["total_tokens"].as_u64().unwrap());
 }
 
 #[tokio::test]